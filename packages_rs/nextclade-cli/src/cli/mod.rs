@@ -1,9 +1,22 @@
+pub mod nextclade_annotation_diff;
 pub mod nextclade_cli;
+pub mod nextclade_consensus;
 pub mod nextclade_dataset_get;
 pub mod nextclade_dataset_list;
 pub mod nextclade_loop;
+pub mod nextclade_manifest;
 pub mod nextclade_ordered_writer;
+pub mod nextclade_pairwise;
+pub mod nextclade_qc_calibrate;
 pub mod nextclade_read_annotation;
+pub mod nextclade_results_merge;
+pub mod nextclade_run_aa;
+pub mod nextclade_run_config;
 pub mod nextclade_seq_sort;
+pub mod nextclade_translate;
+pub mod nextclade_tree_augment;
+pub mod ordered_pipeline;
 pub mod print_help_markdown;
+pub mod sequence_dedup;
+pub mod usage_summary;
 pub mod verbosity;
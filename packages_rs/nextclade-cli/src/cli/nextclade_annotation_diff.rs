@@ -0,0 +1,24 @@
+use crate::cli::nextclade_cli::NextcladeAnnotationDiffArgs;
+use eyre::{Report, WrapErr};
+use nextclade::gene::gene_map::GeneMap;
+use nextclade::gene::gene_map_diff::{gene_map_diff, gene_map_diff_to_table_string};
+use nextclade::io::json::{json_or_yaml_write, json_stringify, JsonPretty};
+
+pub fn nextclade_annotation_diff(args: &NextcladeAnnotationDiffArgs) -> Result<(), Report> {
+  let old = GeneMap::from_path(&args.old_annotation).wrap_err("When reading old genome annotation")?;
+  let new = GeneMap::from_path(&args.new_annotation).wrap_err("When reading new genome annotation")?;
+
+  let diff = gene_map_diff(&old, &new);
+
+  if args.json {
+    println!("{}\n", json_stringify(&diff, JsonPretty(true))?);
+  } else {
+    println!("{}", gene_map_diff_to_table_string(&diff)?);
+  }
+
+  if let Some(output) = &args.output {
+    json_or_yaml_write(output, &diff)?;
+  }
+
+  Ok(())
+}
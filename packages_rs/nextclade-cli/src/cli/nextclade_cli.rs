@@ -1,8 +1,17 @@
+use crate::cli::nextclade_annotation_diff::nextclade_annotation_diff;
+use crate::cli::nextclade_consensus::nextclade_consensus;
 use crate::cli::nextclade_dataset_get::nextclade_dataset_get;
 use crate::cli::nextclade_dataset_list::nextclade_dataset_list;
 use crate::cli::nextclade_loop::nextclade_run;
+use crate::cli::nextclade_manifest::nextclade_manifest;
+use crate::cli::nextclade_pairwise::nextclade_pairwise;
+use crate::cli::nextclade_qc_calibrate::nextclade_qc_calibrate;
 use crate::cli::nextclade_read_annotation::nextclade_read_annotation;
+use crate::cli::nextclade_results_merge::nextclade_results_merge;
+use crate::cli::nextclade_run_aa::nextclade_run_aa;
 use crate::cli::nextclade_seq_sort::nextclade_seq_sort;
+use crate::cli::nextclade_translate::nextclade_translate;
+use crate::cli::nextclade_tree_augment::nextclade_tree_augment;
 use crate::cli::print_help_markdown::print_help_markdown;
 use crate::cli::verbosity::{Verbosity, WarnLevel};
 use crate::io::http_client::ProxyConfig;
@@ -14,8 +23,11 @@ use eyre::{eyre, ContextCompat, Report, WrapErr};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use nextclade::io::fs::add_extension;
+use nextclade::io::nextclade_csv::CsvColumnsCompat;
 use nextclade::run::params::NextcladeInputParamsOptional;
-use nextclade::sort::params::NextcladeSeqSortParams;
+use nextclade::sort::params::NextcladeSeqSortParamsOptional;
+use nextclade::tree::params::TreeBuilderParamsOptional;
+use nextclade::utils::datetime::{date_format_safe, date_now};
 use nextclade::utils::global_init::setup_logger;
 use nextclade::{getenv, make_error};
 use std::fmt::Debug;
@@ -85,6 +97,16 @@ pub enum NextcladeCommands {
   /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade run --help`.
   Run(Box<NextcladeRunArgs>),
 
+  /// Translate sequences: align them to the reference and translate CDSes into peptides, without the rest of the analysis (mutation calling, clade assignment, QC and phylogenetic placement)
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade translate --help`.
+  Translate(Box<NextcladeTranslateArgs>),
+
+  /// Compare two sequences: align the query sequences to a given sequence, which is treated as the reference, and report mutations between them. Does not require a dataset.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade pairwise --help`.
+  Pairwise(Box<NextcladePairwiseArgs>),
+
   /// List and download available Nextclade datasets (pathogens)
   ///
   /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade dataset --help`.
@@ -100,6 +122,36 @@ pub enum NextcladeCommands {
   /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade sort --help`.
   ReadAnnotation(Box<NextcladeReadAnnotationArgs>),
 
+  /// Compute consensus sequences of aligned sequences, per group (for example, per clade) or for the whole batch
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade consensus --help`.
+  Consensus(Box<NextcladeConsensusArgs>),
+
+  /// Work with genome annotations.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade annotation diff --help`.
+  Annotation(Box<NextcladeAnnotationArgs>),
+
+  /// Work with reference trees.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade tree augment --help`.
+  Tree(Box<NextcladeTreeArgs>),
+
+  /// Work with results of previous runs.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade results merge --help`.
+  Results(Box<NextcladeResultsArgs>),
+
+  /// Work with quality control (QC) configuration.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade qc calibrate --help`.
+  Qc(Box<NextcladeQcArgs>),
+
+  /// Print machine-readable description of the environment: Nextclade version, dataset name, version tag and checksums, and minimizer index version. This is useful for recording provenance of the results in pipelines and for reproducibility audits.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade manifest --help`.
+  Manifest(Box<NextcladeManifestArgs>),
+
   /// Print command-line reference documentation in Markdown format
   HelpMarkdown,
 }
@@ -283,6 +335,11 @@ pub enum NextcladeOutputSelection {
   Tree,
   TreeNwk,
   Translations,
+  NucCds,
+  CodonAlignment,
+  Haplotypes,
+  CodonUsage,
+  Support,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -318,6 +375,15 @@ pub struct NextcladeRunInputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub input_dataset: Option<PathBuf>,
 
+  /// Path to a directory or a zip file containing a dataset with an alternative reference sequence. Can be provided multiple times.
+  ///
+  /// For each input sequence, the most similar reference is selected, using minimizer matching, among the reference of the main dataset (provided with `--input-dataset`, `--dataset-name` or with individual `--input-*` flags) and the references of the alternative datasets. The sequence is then analyzed using the dataset of this reference. This is useful for pathogens with lineages too divergent to be aligned well to a single reference (e.g. RSV-A and RSV-B). The name of the reference used is recorded in the `refName` column of the CSV and TSV outputs and in the `refName` field of the JSON and NDJSON outputs.
+  ///
+  /// Individual `--input-*` flags only override files of the main dataset. Output tree, haplotypes, codon usage and position support only include the sequences analyzed against the main dataset.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub input_dataset_alt: Vec<PathBuf>,
+
   /// Name of the dataset to download and use during the run
   ///
   /// This is a convenience shortcut to first downloading a dataset and then immediately running with it. Providing this flag is equivalent to running 2 commands: `dataset get` followed by `run`, with the difference that the dataset files from the first command are not saved to disk and cannot be reused later. The default parameters are used for the dataset (e.g. default reference name and latest version tag).
@@ -332,6 +398,9 @@ pub struct NextcladeRunInputArgs {
 
   /// Path to a FASTA file containing reference sequence. This file should contain exactly 1 sequence.
   ///
+  /// A GFF3 file with the sequence embedded into its `##FASTA` section is also accepted, so that the same file can be
+  /// passed to both `--input-ref` and `--input-annotation`.
+  ///
   /// Overrides path to `reference.fasta` in the dataset (`--input-dataset`).
   ///
   /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
@@ -345,11 +414,32 @@ pub struct NextcladeRunInputArgs {
   ///
   /// Overrides path to `tree.json` in the dataset (`--input-dataset`).
   ///
+  /// Files with extension `.nwk`, `.newick`, `.tree` or `.tre` are read as Newick trees, with node metadata from
+  /// `--input-tree-metadata`. Files with extension `.pb` are read as UShER mutation-annotated trees (MAT).
+  ///
+  /// The reference tree is optional. Without it, the sequences are still aligned, translated and their mutations and
+  /// tree-independent QC rules are reported, but phylogenetic placement, clade assignment, private mutations and
+  /// divergence are skipped, the corresponding output columns are left empty and the `treeDependentSkipped` output
+  /// column is set to `true`.
+  ///
   /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
   #[clap(long, short = 'a')]
   #[clap(value_hint = ValueHint::FilePath)]
   pub input_tree: Option<PathBuf>,
 
+  /// Path to a TSV or CSV file containing metadata of the nodes of the reference tree, when `--input-tree` is a Newick
+  /// file (with extension `.nwk`, `.newick`, `.tree` or `.tre`).
+  ///
+  /// The column `name` should contain names of the tree nodes. Optional columns `clade`, `mutations` (nucleotide
+  /// mutations on the branch leading to the node, comma-separated, e.g. `C241T,A23403G`) and `aaMutations` (aminoacid
+  /// mutations, e.g. `S:D614G`) are used for placement and clade assignment. The remaining columns are treated as
+  /// additional clade-like attributes. CSV files are expected to use semicolon as a delimiter.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst".
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree_metadata: Option<PathBuf>,
+
   /// Path to a JSON file containing configuration and data specific to a pathogen.
   ///
   /// Overrides path to `pathogen.json` in the dataset (`--input-dataset`).
@@ -397,6 +487,100 @@ pub struct NextcladeRunInputArgs {
   #[clap(default_value_t = Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"))]
   pub server: Url,
 
+  /// Analyze only a reproducible fraction of input sequences, for example `0.01` for approximately 1% of them.
+  ///
+  /// Sequences are selected pseudo-randomly, but deterministically based on their position in the input, so that repeated runs on the same input analyze the same sequences. This is useful for quick trial runs on large inputs, for example when tuning parameters. Sequence indices in the outputs refer to positions within the selected subset.
+  ///
+  /// This flag is mutually exclusive with `--sample-every-n`
+  #[clap(long, conflicts_with = "sample_every_n")]
+  pub sample_fraction: Option<f64>,
+
+  /// Analyze only every n-th input sequence, starting from the first one.
+  ///
+  /// Sequence indices in the outputs refer to positions within the selected subset.
+  ///
+  /// This flag is mutually exclusive with `--sample-fraction`
+  #[clap(long)]
+  pub sample_every_n: Option<usize>,
+
+  /// Analyze every input sequence anew, even if it is identical to a sequence analyzed previously.
+  ///
+  /// By default, if an input sequence is identical to one of the sequences analyzed previously (character by character, and against the same reference), the results of the previous analysis are reused, with the sequence name and index replaced, instead of analyzing the sequence again. This saves time on inputs with many duplicate sequences, which are common in surveillance data. Errors are reused in the same way. The results of every distinct sequence are kept in memory until the end of the run, so for inputs consisting mostly of distinct sequences this flag reduces memory usage.
+  #[clap(long)]
+  pub no_dedup: bool,
+
+  /// Treat input sequences as aminoacid sequences (peptides) of the CDS given by `--cds`, rather than as nucleotide sequences.
+  ///
+  /// Peptides are aligned directly to the reference peptide of this CDS, translated from the reference sequence using the genome annotation. Nucleotide alignment and all analysis steps which depend on it (nucleotide mutations, clade assignment, phylogenetic placement, QC etc.) are skipped. Only aminoacid substitutions, deletions and insertions are reported, in `--output-tsv`, `--output-csv`, `--output-ndjson` and `--output-json`, and the aligned peptides are written to `--output-translations`. Other outputs are not produced.
+  ///
+  /// This is useful if only protein sequences are available.
+  #[clap(long, requires = "cds")]
+  pub input_aa: bool,
+
+  /// Name of the CDS, peptides of which are provided as input sequences with `--input-aa`. Must be present in the genome annotation.
+  #[clap(long, requires = "input_aa")]
+  pub cds: Option<String>,
+
+  /// Path to a plugin script (in Rhai language), which computes additional fields from the analysis results of each sequence.
+  ///
+  /// The script should define two functions: `columns()`, returning an array with names of the fields it computes, and `process(result)`, receiving the analysis result of a sequence (an object map, with the same structure as an entry of `--output-ndjson`) and returning an object map from field names to values. The fields appear as additional columns in `--output-tsv` and `--output-csv`, in `customNodeAttributes` of `--output-json` and `--output-ndjson`, as well as attributes of the new nodes in the output trees. Field names must differ from the names of the clade attributes of the reference tree, otherwise the run is aborted. Failure of the script for a sequence is reported as an error of this sequence.
+  ///
+  /// Only available if Nextclade is built with the `plugins` cargo feature.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub plugin_script: Option<PathBuf>,
+
+  /// Path to a BED file with regions of the reference sequence to mask, such as problematic sites.
+  ///
+  /// Query nucleotides aligned to the masked regions are replaced with `N` before mutation calling and phylogenetic placement, so that they do not produce mutations and do not affect placement. Deletions are not masked. The masked positions are reported in the `maskedRanges` field of JSON and NDJSON outputs and in the `masked` and `totalMasked` columns of CSV and TSV outputs, and they are not counted as missing data in QC. Positions in the BED file are 0-based, with exclusive ends. The chromosome column is ignored. Overrides `maskedRanges` in pathogen JSON.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_mask_bed: Option<PathBuf>,
+
+  /// Path to a CSV or TSV file with hierarchical lineage definitions, for assigning lineages independently of the reference tree.
+  ///
+  /// The file should contain columns `lineage` (name of the lineage), `parent` (name of the parent lineage, empty for top-level lineages) and `mutations` (comma-separated nucleotide substitutions acquired relative to the parent lineage, e.g. `C241T,A23403G`). Values are delimited by semicolons if the file path ends with ".csv", and by tabs otherwise. Starting from the top-level lineages, each sequence descends into the sublineage best supported by its mutations. The assigned lineage, confidence (fraction of the covered defining mutations present in the sequence) and conflicts (contradicted defining mutations) are added to the `lineageCall` field of JSON and NDJSON outputs, and as `lineage.*` columns of CSV and TSV outputs.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_lineages: Option<PathBuf>,
+
+  /// Path to a knowledge base of known aminoacid mutations (e.g. antiviral resistance, monoclonal antibody escape), in JSON, CSV or TSV format.
+  ///
+  /// The JSON file should contain an array of objects, and the CSV or TSV file should contain columns, with fields `mutation` (aminoacid substitution including the CDS name, e.g. `S:E484K`), `annotation` (free text) and optionally `source`. Values are delimited by semicolons if the file path ends with ".csv", and by tabs otherwise. Aminoacid substitutions of each sequence are matched against the knowledge base by CDS, position and query aminoacid, and the matching entries are added to the `annotations` field of JSON and NDJSON outputs, and to the `annotations` column of CSV and TSV outputs.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_mutation_annotations: Option<PathBuf>,
+
+  /// Path to a VCF file with allele frequencies of the samples, for reporting minor (sub-consensus) variants alongside the consensus-level results.
+  ///
+  /// Reads should be aligned to the reference sequence of the dataset and variants called by a dedicated tool (e.g. LoFreq, iVar, bcftools), with frequencies of all variants, including the ones below the consensus threshold. Sample columns of the VCF file are matched to the query sequences by sequence name. The frequencies are taken from the `AF` field of the sample columns, or computed from the allelic depths in the `AD` field. If the VCF file has no sample columns (as in the output of single-sample variant callers), the `AF` field of the `INFO` column is used for every query sequence. Only single-nucleotide variants are considered. Variants with frequencies between `--minor-variant-min-frequency` and `--minor-variant-max-frequency` are added to the `minorVariants` field of JSON and NDJSON outputs, and to the `minorVariants` column of CSV and TSV outputs.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_minor_variants: Option<PathBuf>,
+
+  /// Path to a CSV or TSV file mapping sequence names to batches (plates, sequencing runs etc.), for `--output-batch-qc`.
+  ///
+  /// The file should contain a column `seqName` with sequence names and a column with batch names (see `--batch-column`). Values are delimited by semicolons if the file path ends with ".csv", and by tabs otherwise. Sequences not listed in the file, or with an empty batch, are not included in the batch QC report.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_batch_map: Option<PathBuf>,
+
+  /// Name of the column in `--input-batch-map` containing batch names.
+  #[clap(long, default_value = "batch", requires = "input_batch_map")]
+  pub batch_column: String,
+
+  /// Path to a CSV or TSV file with metadata of the input sequences, to be added to the sequences placed onto the output tree.
+  ///
+  /// The file should contain a column `seqName` with sequence names. The selected columns (see `--metadata-columns`) are added as node attributes to the matching new nodes of the output tree (`--output-tree`), along with categorical colorings and filters, such that the tree can be colored and filtered by them in Auspice. Columns `region`, `country` and `division` fill the corresponding built-in node attributes. Values are delimited by semicolons if the file path ends with ".csv", and by tabs otherwise.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_metadata: Option<PathBuf>,
+
+  /// Comma-separated list of columns of `--input-metadata` to add to the output tree. By default, all columns are added.
+  #[clap(long, num_args=1.., use_value_delimiter = true)]
+  #[clap(requires = "input_metadata")]
+  pub metadata_columns: Vec<String>,
+
   // Deprecated arguments. Kept in oder to detect usage and print error messages.
   /// REMOVED. Use --input-ref instead
   #[clap(long)]
@@ -439,8 +623,49 @@ pub struct NextcladeRunInputArgs {
   pub genes: Option<Vec<String>>,
 }
 
+impl Default for NextcladeRunInputArgs {
+  fn default() -> Self {
+    Self {
+      input_fastas: vec![],
+      input_fasta: None,
+      input_dataset: None,
+      input_dataset_alt: vec![],
+      dataset_name: None,
+      input_ref: None,
+      input_tree: None,
+      input_tree_metadata: None,
+      input_pathogen_json: None,
+      input_annotation: None,
+      cds_selection: None,
+      server: Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"),
+      sample_fraction: None,
+      sample_every_n: None,
+      no_dedup: false,
+      input_aa: false,
+      cds: None,
+      plugin_script: None,
+      input_mask_bed: None,
+      input_lineages: None,
+      input_mutation_annotations: None,
+      input_minor_variants: None,
+      input_batch_map: None,
+      batch_column: "batch".to_owned(),
+      input_metadata: None,
+      metadata_columns: vec![],
+      input_root_seq: None,
+      reference: None,
+      input_qc_config: None,
+      input_virus_properties: None,
+      input_pcr_primers: None,
+      input_gene_map: None,
+      genemap: None,
+      genes: None,
+    }
+  }
+}
+
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 pub struct NextcladeRunOutputArgs {
   /// REMOVED. Use `--output-all` instead
   #[clap(long)]
@@ -455,7 +680,7 @@ pub struct NextcladeRunOutputArgs {
   ///
   /// If both the `--output-all` and individual `--output-*` flags are provided, each individual flag overrides the corresponding default output path.
   ///
-  /// At least one of the output flags is required: `--output-all`, `--output-fasta`, `--output-ndjson`, `--output-json`, `--output-csv`, `--output-tsv`, `--output-tree`, `--output-translations`.
+  /// At least one of the output flags is required: `--output-all`, `--output-fasta`, `--output-ndjson`, `--output-json`, `--output-csv`, `--output-tsv`, `--output-tree`, `--output-translations`, `--output-nuc-cds-fasta`, `--output-codon-alignment`, `--output-haplotypes`, `--output-codon-usage`, `--output-support`, `--output-alignment-band`.
   ///
   /// If the required directory tree does not exist, it will be created.
   #[clap(long, short = 'O')]
@@ -471,6 +696,24 @@ pub struct NextcladeRunOutputArgs {
   #[clap(requires = "output_all")]
   pub output_basename: Option<String>,
 
+  /// Identifier of this run.
+  ///
+  /// The run ID is recorded in the JSON results file (`--output-json`) and can be included into output file paths using the template variable `{run_id}`. For example: `--output-basename='nextclade_{run_id}'` or `--output-tsv='results/{run_id}/nextclade.tsv'`.
+  ///
+  /// If not provided, a run ID is generated from the current date and time and the process ID.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::Other)]
+  pub run_id: Option<String>,
+
+  /// Write outputs of `--output-all` into a fresh subdirectory, named after the current date and time and the run ID.
+  ///
+  /// This prevents accidental overwrites of results of previous runs, when using a shared output directory.
+  ///
+  /// Only valid together with `--output-all` flag.
+  #[clap(long)]
+  #[clap(requires = "output_all")]
+  pub output_timestamped_subdir: bool,
+
   /// Restricts outputs for `--output-all` flag.
   ///
   /// Should contain a comma-separated list of names of output files to produce.
@@ -517,6 +760,52 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_translations: Option<String>,
 
+  /// Template string for path to output fasta files containing nucleotide sequences of CDSes of the query sequences. A separate file will be generated for every CDS.
+  ///
+  /// The sequences are extracted from the aligned query sequences (with insertions stripped), so that the sequences in each file are aligned to each other and to the reference CDS. Segments of CDSes consisting of multiple segments are joined together, and CDSes on the reverse strand are reverse-complemented. Deletions are kept as gaps, unless `--nuc-cds-strip-gaps` is set. This is useful for gene-level phylogenetic analyses.
+  ///
+  /// The string should contain template variable `{cds}`, where the CDS name will be substituted.
+  /// Make sure you properly quote and/or escape the curly braces, so that your shell, programming language or pipeline manager does not attempt to substitute the variables.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  ///
+  /// Example for bash shell:
+  ///
+  ///   --output-nuc-cds-fasta='output_dir/cds_{cds}.nuc.fasta'
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_nuc_cds_fasta: Option<String>,
+
+  /// Remove gaps (deletions relative to the reference) from the nucleotide sequences of CDSes written to `--output-nuc-cds-fasta`.
+  ///
+  /// By default, the gaps are kept, such that the sequences are aligned.
+  #[clap(long)]
+  pub nuc_cds_strip_gaps: bool,
+
+  /// Template string for path to output fasta files containing codon alignments of CDSes. A separate file will be generated for every CDS.
+  ///
+  /// For each query sequence, the nucleotide sequence of the CDS is aligned to the reference CDS codon by codon, by back-translating the aligned peptide (as in `--output-translations`): each aminoacid is replaced with the codon it is translated from. Codons inserted relative to the reference are omitted, deleted codons are written as `---` and codons of aminoacids masked due to frame shifts are written as `NNN`, such that all sequences in each file have length of 3 nucleotides per aminoacid of the reference CDS. Such alignments are required by selection analysis tools, for example HyPhy and PAML.
+  ///
+  /// The string should contain template variable `{cds}`, where the CDS name will be substituted.
+  /// Make sure you properly quote and/or escape the curly braces, so that your shell, programming language or pipeline manager does not attempt to substitute the variables.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  ///
+  /// Example for bash shell:
+  ///
+  ///   --output-codon-alignment='output_dir/cds_{cds}.codon_alignment.fasta'
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_codon_alignment: Option<String>,
+
   /// Path to output Newline-delimited JSON (NDJSON) results file.
   ///
   /// This file format is most suitable for further machine processing of the results. By contrast to plain json, it can be streamed line-by line, so much bigger outputs are feasible.
@@ -573,13 +862,43 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_tsv: Option<PathBuf>,
 
-  /// Restricts columns written into tabular output files (CSV and TSV).
+  /// Path to output Parquet results file.
+  ///
+  /// Parquet is a compressed columnar file format, which can be read efficiently by data analysis tools, such as pandas, polars, Apache Arrow, Spark or DuckDB. The file contains the same columns as the CSV and TSV outputs, with empty values written as nulls, followed by list columns with individual mutations: `substitutionsList`, `deletionsList`, `insertionsList`, `aaSubstitutionsList`, `aaDeletionsList` and `aaInsertionsList`.
+  ///
+  /// For file format description see: https://parquet.apache.org/docs/file-format/
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// Use "-" to write to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_parquet: Option<PathBuf>,
+
+  /// Path to output Arrow IPC results file (also known as Feather V2).
+  ///
+  /// The file can be loaded without parsing by data analysis tools, such as pyarrow, polars, pandas or the R `arrow` package. It contains the same columns as the CSV and TSV outputs, with empty values written as nulls, followed by list columns with individual mutations: `substitutionsList`, `deletionsList`, `insertionsList`, `aaSubstitutionsList`, `aaDeletionsList` and `aaInsertionsList`.
+  ///
+  /// For file format description see: https://arrow.apache.org/docs/format/Columnar.html#ipc-file-format
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// Use "-" to write to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_arrow: Option<PathBuf>,
+
+  /// Restricts columns written into tabular output files (CSV, TSV, Parquet and Arrow).
   ///
-  /// Should contain a comma-separated list of individual column names and/or column category names to include into both CSV and TSV outputs.
+  /// Should contain a comma-separated list of individual column names and/or column category names to include into CSV, TSV, Parquet and Arrow outputs.
   ///
   /// If this flag is omitted, or if category 'all' is present in the list, then all other entries are ignored and all columns are written.
   ///
-  /// Only valid together with one or multiple of flags: `--output-csv`, `--output-tsv`, `--output-all`.
+  /// Only valid together with one or multiple of flags: `--output-csv`, `--output-tsv`, `--output-parquet`, `--output-arrow`, `--output-all`.
   #[clap(
     long,
     short = 'C',
@@ -588,6 +907,12 @@ pub struct NextcladeRunOutputArgs {
   )]
   pub output_columns_selection: Vec<String>,
 
+  /// Compatibility mode of tabular output files (CSV and TSV).
+  ///
+  /// With `v2`, the columns which did not exist in Nextclade v2 (for example `index` and `isReverseComplement`, as well as columns with amino acid motifs) are omitted, such that downstream parsers written for v2 outputs can consume the files unchanged while they are being migrated.
+  #[clap(long, value_enum, default_value_t = CsvColumnsCompat::default())]
+  pub output_columns_compat: CsvColumnsCompat,
+
   /// Path to output phylogenetic graph with input sequences placed onto it, in Nextclade graph JSON format.
   ///
   /// Currently this format is not stable and not documented. It can change at any time without a warning. Use it at own risk.
@@ -633,144 +958,825 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_tree_nwk: Option<PathBuf>,
 
-  /// REMOVED. The argument `--output-insertions` have been removed in favor of `--output-csv` and `--output-tsv`.
-  #[clap(long, short = 'I')]
-  #[clap(value_hint = ValueHint::AnyPath)]
-  #[clap(hide_long_help = true, hide_short_help = true)]
-  pub output_insertions: Option<PathBuf>,
-
-  /// REMOVED. The argument `--output-errors` have been removed in favor of `--output-csv` and `--output-tsv`.
-  #[clap(long, short = 'e')]
+  /// Path to output phylogenetic tree with input sequences placed onto it, in NEXUS format
+  ///
+  /// The tree is written in a trees block, preceded by a taxa block listing the leaves. Each node is annotated with a BEAST-style comment (`[&clade="...",nuc_mutations="...",aa_mutations="..."]`), containing its clade and the nucleotide and aminoacid mutations on the branch leading to it, which can be displayed in FigTree and similar tools.
+  ///
+  /// For file format description see: https://doi.org/10.1093/sysbio/46.4.590
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
   #[clap(value_hint = ValueHint::AnyPath)]
-  #[clap(hide_long_help = true, hide_short_help = true)]
-  pub output_errors: Option<PathBuf>,
-}
-
-#[derive(Parser, Debug, Clone)]
-pub struct NextcladeRunOtherParams {
-  /// Number of processing jobs. If not specified, all available CPU threads will be used.
-  #[clap(global = false, long, short = 'j', default_value_t = num_cpus::get())]
-  pub jobs: usize,
-}
-
-#[derive(Parser, Debug, Clone)]
-pub struct NextcladeRunArgs {
-  #[clap(flatten, next_help_heading = "Inputs")]
-  pub inputs: NextcladeRunInputArgs,
-
-  #[clap(flatten, next_help_heading = "Outputs")]
-  pub outputs: NextcladeRunOutputArgs,
-
-  #[clap(flatten)]
-  pub params: NextcladeInputParamsOptional,
-
-  #[clap(flatten, next_help_heading = "Other")]
-  pub other_params: NextcladeRunOtherParams,
-}
+  pub output_tree_nexus: Option<PathBuf>,
 
-#[allow(clippy::struct_excessive_bools)]
-#[derive(Parser, Debug)]
-#[clap(verbatim_doc_comment)]
-pub struct NextcladeSortArgs {
-  /// Path to one or multiple FASTA files with input sequences
+  /// Path to output phylogenetic tree with input sequences placed onto it, in the protobuf format of UShER mutation-annotated trees (MAT)
   ///
-  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". If no files provided, the plain fasta input is read from standard input (stdin).
+  /// Only nucleotide substitutions between "A", "C", "G" and "T" are written to the branches. Clades are written as the first clade annotation of the nodes, and the `lineage` node attribute (if present) as the second one.
   ///
-  /// See: https://en.wikipedia.org/wiki/FASTA_format
-  #[clap(value_hint = ValueHint::FilePath)]
-  pub input_fastas: Vec<PathBuf>,
-
-  /// Path to input minimizer index JSON file.
+  /// For file format description see: https://usher-wiki.readthedocs.io/en/latest/matUtils.html
   ///
-  /// By default, the latest reference minimizer index is fetched from the dataset server (default or customized with `--server` argument). If this argument is provided, the algorithm skips fetching the default index and uses the index provided in the JSON file.
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
   ///
-  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
-  #[clap(long, short = 'm')]
-  #[clap(value_hint = ValueHint::FilePath)]
-  pub input_minimizer_index_json: Option<PathBuf>,
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_pb: Option<PathBuf>,
 
-  /// Path to output directory
+  /// Path to output phylogenetic placements of the input sequences onto the reference tree, in jplace format
   ///
-  /// Sequences will be written in subdirectories: one subdirectory per dataset. Sequences inferred to be belonging to a particular dataset will be placed in the corresponding subdirectory. The subdirectory tree can be nested, depending on how dataset names are organized - dataset names can contain slashes, and they will be treated as path segment delimiters.
+  /// The file contains the reference tree (without the input sequences) with numbered edges, and for each input sequence, the edge it is attached to, with the branch length of the sequence as the pendant length. If `--num-placement-candidates` is provided, all candidate placements are listed along with their relative weights. This allows for downstream analysis of the placements using tools such as gappa or guppy.
   ///
-  /// If the required directory tree does not exist, it will be created.
+  /// For file format description see: https://doi.org/10.1371/journal.pone.0031009
   ///
-  /// Mutually exclusive with `--output-path`.
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
   ///
-  #[clap(short = 'O', long)]
-  #[clap(value_hint = ValueHint::DirPath)]
-  #[clap(group = "outputs")]
-  pub output_dir: Option<PathBuf>,
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_jplace: Option<PathBuf>,
 
-  /// Template string for the file path to output sorted sequences. A separate file will be generated per dataset.
+  /// Template string for paths to output context trees: small subtrees of the phylogenetic tree, one per input sequence, each containing the sequence and its closest neighbours.
   ///
-  /// The string should contain template variable `{name}`, where the dataset name will be substituted. Note that if the `{name}` variable contains slashes, they will be interpreted as path segments and subdirectories will be created.
+  /// For each input sequence placed onto the tree, Nextclade finds its nearest ancestor with at least `--context-tree-leaves` leaves, and keeps the placed sequence along with the leaves of this ancestor which are the closest to it by divergence. This allows to quickly view each sample in its phylogenetic context without loading the full tree.
   ///
-  /// Make sure you properly quote and/or escape the curly braces, so that your shell, programming language or pipeline manager does not attempt to substitute the variables.
+  /// The string should contain template variable `{seq_name}`, where the sequence name will be substituted. Characters other than letters, digits, "-", "_" and "." in sequence names are replaced with "_".
   ///
-  /// Mutually exclusive with `--output-dir`.
+  /// If the resulting file path ends with ".nwk", the trees are written in Newick format, otherwise in Auspice JSON v2 format.
   ///
-  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. If the required directory tree does not exist, it will be created.
+  /// This output is not produced by `--output-all`.
+  ///
+  /// If the required directory tree does not exist, it will be created.
   ///
   /// Example for bash shell:
   ///
-  ///   --output='outputs/{name}/sorted.fasta.gz'
-  #[clap(short = 'o', long)]
-  #[clap(group = "outputs")]
-  pub output_path: Option<String>,
+  ///   --output-context-trees='output_dir/context/{seq_name}.auspice.json'
+  #[clap(long)]
+  pub output_context_trees: Option<String>,
 
-  /// Path to output results TSV file
+  /// Number of leaves in each of the context trees, including the input sequence itself.
   ///
-  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write uncompressed to standard output (stdout). If the required directory tree does not exist, it will be created.
-  #[clap(short = 'r', long)]
-  #[clap(value_hint = ValueHint::FilePath)]
-  pub output_results_tsv: Option<String>,
+  /// Only valid together with `--output-context-trees`.
+  #[clap(long, default_value_t = 20)]
+  #[clap(requires = "output_context_trees")]
+  pub context_tree_leaves: usize,
 
-  #[clap(flatten, next_help_heading = "Algorithm")]
-  pub search_params: NextcladeSeqSortParams,
+  /// Path to output haplotype table.
+  ///
+  /// Sequences with identical nucleotide mutation profiles (substitutions and deletions) are grouped into haplotypes. For each haplotype, the table contains its ID, the defining mutations, the number of member sequences and their names. Haplotypes are sorted by the number of member sequences, in descending order.
+  ///
+  /// The set of mutations taken into account can be restricted using `--haplotype-cdses` and `--haplotype-positions`.
+  ///
+  /// If the provided file path ends with ".json", the table is written in JSON format. If it ends with ".csv", the table is written with semicolon delimiters, otherwise with tab delimiters.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_haplotypes: Option<PathBuf>,
 
-  #[clap(flatten, next_help_heading = "Other")]
-  pub other_params: NextcladeRunOtherParams,
+  /// Comma-separated list of names of CDSes. When grouping sequences into haplotypes, only take into account mutations within these CDSes.
+  ///
+  /// Can be combined with `--haplotype-positions`. Only valid together with `--output-haplotypes` or `--output-all`.
+  #[clap(long, num_args=1.., use_value_delimiter = true)]
+  pub haplotype_cdses: Option<Vec<String>>,
 
-  /// Use custom dataset server.
+  /// Comma-separated list of nucleotide positions (1-based). When grouping sequences into haplotypes, only take into account mutations at these positions.
   ///
-  /// You can host your own dataset server, with one or more datasets, grouped into dataset collections, and use this server to provide datasets to users of Nextclade CLI and Nextclade Web. Refer to Nextclade dataset documentation for more details.
-  #[clap(long)]
-  #[clap(value_hint = ValueHint::Url)]
-  #[clap(default_value_t = Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"))]
-  pub server: Url,
+  /// Can be combined with `--haplotype-cdses`. Only valid together with `--output-haplotypes` or `--output-all`.
+  #[clap(long, num_args=1.., use_value_delimiter = true)]
+  pub haplotype_positions: Option<Vec<usize>>,
 
-  #[clap(flatten)]
+  /// Path to output codon usage table.
+  ///
+  /// For each CDS, the table contains the number of occurrences of each codon in the reference sequence and in all query sequences combined, as well as the fraction of each codon among all synonymous codons (encoding the same aminoacid) in the same CDS. Only codons consisting of unambiguous nucleotides (A, C, G, T) are counted. Query codons are read from aligned sequences, in the reading frame of the reference. This is useful for codon deoptimization and attenuation studies, as well as a sanity check of translation.
+  ///
+  /// If the provided file path ends with ".json", the table is written in JSON format. If it ends with ".csv", the table is written with semicolon delimiters, otherwise with tab delimiters.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_codon_usage: Option<PathBuf>,
+
+  /// Path to output per-position support track, in bedGraph or wiggle format.
+  ///
+  /// For each position of the reference sequence, the track contains the number of query sequences having a call (a nucleotide or a deletion) at this position, i.e. the position is within the alignment range and is not missing (`N`). This is similar to sequencing depth, but across the sequences of the batch, and is useful for designing masks and for finding regions which are systematically dropped out.
+  ///
+  /// If the provided file path ends with ".wig", the track is written in wiggle format (fixed step), otherwise in bedGraph format. The name of the reference sequence is used as the chromosome name. Positions in bedGraph are 0-based, with exclusive ends.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_support: Option<PathBuf>,
+
+  /// Path to output file with alignment bands and traceback paths of nucleotide alignments, for visualization.
+  ///
+  /// For each sequence, the file contains the boundaries of the band of the alignment score matrix (the range of query positions considered for each reference position), as well as the path through the matrix taken by the final alignment. The entries are stored in a compact form: the stripes of the band which merely continue the diagonal of the previous stripe are omitted, and the path only contains the points where it changes direction. This is useful when tuning band width and seed matching parameters for new pathogens.
+  ///
+  /// If the provided file path ends with ".csv" or ".tsv", the output is written as a table with semicolon or tab delimiters respectively, otherwise in newline-delimited JSON format, one sequence per line.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_alignment_band: Option<PathBuf>,
+
+  /// Path to output file with pairwise alignments of sequences to the reference, in Multiple Alignment Format (MAF).
+  ///
+  /// For each sequence, the file contains an alignment block with two rows: the reference and the query. Unlike the `--output-fasta`, insertions relative to the reference are kept in the alignment, and the block only spans the aligned region of the reference, i.e. the unsequenced ends of the query are not included. Whitespace in sequence names is replaced with underscores. This is useful for downstream tools operating on MAF, such as genome browsers and comparative genomics pipelines.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_maf: Option<PathBuf>,
+
+  /// Path to output file with aggregate QC of batches of sequences (plates, sequencing runs etc.), as defined by `--input-batch-map`.
+  ///
+  /// For each batch, the report contains the number of sequences, the numbers of failed sequences and of sequences of each QC status, mean QC score, coverage and missing data, as well as signals of problems affecting the batch as a whole: fractions of sequences flagged by the "mixed sites" and "SNP clusters" QC rules, which may indicate contamination, and CDSes with low coverage in the majority of the sequences, which may indicate failing amplicons.
+  ///
+  /// If the provided file path ends with ".json", the report is written in JSON format. If it ends with ".csv", the report is written with semicolon delimiters, otherwise with tab delimiters.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, requires = "input_batch_map")]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_batch_qc: Option<PathBuf>,
+
+  /// Path to output HTML report.
+  ///
+  /// The report is a single self-contained HTML file, which can be opened in a web browser and shared without running Nextclade Web. It contains a summary of the run, counts of clades, distributions of QC statuses and of overall QC scores, and a table of sequences, which can be filtered and sorted, with details of each sequence.
+  ///
+  /// All results are held in memory until the end of the run, so the report is mostly suitable for runs of up to a few thousand sequences.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_html: Option<PathBuf>,
+
+  /// Path to output VCF file with nucleotide substitutions, deletions and insertions of the sequences, relative to the reference sequence.
+  ///
+  /// By default, a single multi-sample VCF file is written, with one haploid genotype column per sequence. Samples without a variant at a site have the reference allele, unless the site is outside of the alignment or in a range of missing data, in which case the genotype is unknown (`.`). If the path contains the template variable `{seq_name}`, a separate single-sample VCF file is written for every sequence instead, with the sequence name substituted (characters other than letters, digits, `-`, `_` and `.` are replaced with `_`). Example: `--output-vcf='output_dir/vcf/{seq_name}.vcf'`.
+  ///
+  /// Indels are anchored at the preceding reference base, as required by the format. Sites overlapping the masked ranges of the reference (`maskedRanges` in pathogen JSON or `--input-mask-bed`), which are excluded from mutation calling and QC, have the filter `MASKED`.
+  ///
+  /// For file format description see: https://samtools.github.io/hts-specs/VCFv4.2.pdf
+  ///
+  /// Only sequences analyzed against the main reference are included (see `--input-dataset-alt`). All variants are held in memory until the end of the run.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_vcf: Option<PathBuf>,
+
+  /// Path to output JSON file with the summary of resources used by the run: wall time, CPU time, peak memory (resident set size), number of sequences processed per second, as well as sizes of input and output files.
+  ///
+  /// The same summary is printed to standard error (stderr) at the end of the run, unless logging is silenced. This is useful for sizing of jobs on compute clusters. All values are collected locally, nothing is sent over the network. CPU time and peak memory are only available on Linux.
+  ///
+  /// This output is not produced by `--output-all` and has to be requested explicitly.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_usage: Option<PathBuf>,
+
+  /// REMOVED. The argument `--output-insertions` have been removed in favor of `--output-csv` and `--output-tsv`.
+  #[clap(long, short = 'I')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(hide_long_help = true, hide_short_help = true)]
+  pub output_insertions: Option<PathBuf>,
+
+  /// REMOVED. The argument `--output-errors` have been removed in favor of `--output-csv` and `--output-tsv`.
+  #[clap(long, short = 'e')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(hide_long_help = true, hide_short_help = true)]
+  pub output_errors: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct NextcladeRunOtherParams {
+  /// Number of processing jobs. If not specified, all available CPU threads will be used.
+  #[clap(global = false, long, short = 'j', default_value_t = num_cpus::get())]
+  pub jobs: usize,
+}
+
+impl Default for NextcladeRunOtherParams {
+  fn default() -> Self {
+    Self { jobs: num_cpus::get() }
+  }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct NextcladeRunArgs {
+  #[clap(flatten, next_help_heading = "Inputs")]
+  pub inputs: NextcladeRunInputArgs,
+
+  #[clap(flatten, next_help_heading = "Outputs")]
+  pub outputs: NextcladeRunOutputArgs,
+
+  #[clap(flatten)]
+  pub params: NextcladeInputParamsOptional,
+
+  #[clap(flatten, next_help_heading = "Other")]
+  pub other_params: NextcladeRunOtherParams,
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeSortArgs {
+  /// Path to one or multiple FASTA files with input sequences
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". If no files provided, the plain fasta input is read from standard input (stdin).
+  ///
+  /// See: https://en.wikipedia.org/wiki/FASTA_format
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_fastas: Vec<PathBuf>,
+
+  /// Path to input minimizer index JSON file.
+  ///
+  /// By default, the latest reference minimizer index is fetched from the dataset server (default or customized with `--server` argument). If this argument is provided, the algorithm skips fetching the default index and uses the index provided in the JSON file.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_minimizer_index_json: Option<PathBuf>,
+
+  /// Path to output directory
+  ///
+  /// Sequences will be written in subdirectories: one subdirectory per dataset. Sequences inferred to be belonging to a particular dataset will be placed in the corresponding subdirectory. The subdirectory tree can be nested, depending on how dataset names are organized - dataset names can contain slashes, and they will be treated as path segment delimiters.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  ///
+  /// Mutually exclusive with `--output-path`.
+  ///
+  #[clap(short = 'O', long)]
+  #[clap(value_hint = ValueHint::DirPath)]
+  #[clap(group = "outputs")]
+  pub output_dir: Option<PathBuf>,
+
+  /// Template string for the file path to output sorted sequences. A separate file will be generated per dataset.
+  ///
+  /// The string should contain template variable `{name}`, where the dataset name will be substituted. Note that if the `{name}` variable contains slashes, they will be interpreted as path segments and subdirectories will be created.
+  ///
+  /// Make sure you properly quote and/or escape the curly braces, so that your shell, programming language or pipeline manager does not attempt to substitute the variables.
+  ///
+  /// Mutually exclusive with `--output-dir`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. If the required directory tree does not exist, it will be created.
+  ///
+  /// Example for bash shell:
+  ///
+  ///   --output='outputs/{name}/sorted.fasta.gz'
+  #[clap(short = 'o', long)]
+  #[clap(group = "outputs")]
+  pub output_path: Option<String>,
+
+  /// Path to output results TSV file
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write uncompressed to standard output (stdout). If the required directory tree does not exist, it will be created.
+  #[clap(short = 'r', long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub output_results_tsv: Option<String>,
+
+  #[clap(flatten, next_help_heading = "Algorithm")]
+  pub search_params: Option<NextcladeSeqSortParamsOptional>,
+
+  #[clap(flatten, next_help_heading = "Other")]
+  pub other_params: NextcladeRunOtherParams,
+
+  /// Use custom dataset server.
+  ///
+  /// You can host your own dataset server, with one or more datasets, grouped into dataset collections, and use this server to provide datasets to users of Nextclade CLI and Nextclade Web. Refer to Nextclade dataset documentation for more details.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::Url)]
+  #[clap(default_value_t = Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"))]
+  pub server: Url,
+
+  #[clap(flatten)]
   pub proxy_config: ProxyConfig,
 }
 
-#[allow(clippy::struct_excessive_bools)]
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeReadAnnotationArgs {
+  /// Genome annotation file in GFF3 format.
+  ///
+  /// Learn more about Generic Feature Format Version 3 (GFF3):
+  /// https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md
+  ///
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 0)]
+  pub input_annotation: Option<PathBuf>,
+
+  /// Path to output JSON or YAML file.
+  ///
+  /// The format is chosen based on file extension: ".json" or ".yaml".
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::DirPath)]
+  pub output: Option<PathBuf>,
+
+  /// Present features in "feature tree" format. This format is a precursor of genome annotation format - it contains all genetic features, even the ones that Nextclade does not use, but also less information about each feature.
+  #[clap(long)]
+  pub feature_tree: bool,
+
+  /// Print console output in JSON format, rather than human-readable table.
+  #[clap(long)]
+  pub json: bool,
+
+  /// Path to output GFF3 file with suggested repairs of the genome annotation applied.
+  ///
+  /// If some of the CDSes have length not divisible by 3 or extend beyond the end of the sequence region, Nextclade proposes minimal changes of their coordinates, prints them and writes the annotation with these changes applied into this file. The rest of the file is preserved as is. Review the repaired annotation before using it.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub fix_annotation_into: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct NextcladeTranslateArgs {
+  #[clap(flatten, next_help_heading = "Inputs")]
+  pub inputs: NextcladeRunInputArgs,
+
+  /// Template string for paths to output translated CDS sequences, one FASTA file per CDS.
+  ///
+  /// The template string should contain template variable `{cds}`, which will be substituted with CDS name, for example: "output_dir/cds_{cds}.translation.fasta". Peptides are aligned to the reference peptides, with insertions stripped, same as the output of `nextclade run --output-translations`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'P')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(help_heading = "Outputs")]
+  pub output_translations: String,
+
+  /// Also write the translation of the reference sequence, as the first entry of each output file.
+  #[clap(long)]
+  #[clap(help_heading = "Outputs")]
+  pub include_reference: bool,
+
+  #[clap(flatten)]
+  pub params: NextcladeInputParamsOptional,
+
+  #[clap(flatten, next_help_heading = "Other")]
+  pub other_params: NextcladeRunOtherParams,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct NextcladePairwiseArgs {
+  /// Path to FASTA file with the sequence to compare against. It is treated as the reference sequence. If the file contains multiple sequences, only the first one is used.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst".
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub reference: PathBuf,
+
+  /// Path to FASTA file with the sequence(s) to compare. Each of the sequences is compared to the reference sequence.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub query: PathBuf,
+
+  /// Path to a file containing genome annotation (gene map) in GFF3 format, in coordinates of the reference sequence.
+  ///
+  /// If provided, coding regions are translated and aminoacid mutations are reported in addition to nucleotide mutations.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst".
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub annotation: Option<PathBuf>,
+
+  /// Path to output CSV or TSV file with the mutations, one row per query sequence. The delimiter is deduced from the file extension: semicolon for ".csv" and tab otherwise.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed data to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'o', default_value = "-")]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output: PathBuf,
+
+  /// Template string for paths to output translated CDS sequences, one FASTA file per CDS. Requires `--annotation`.
+  ///
+  /// The template string should contain template variable `{cds}`, which will be substituted with CDS name, for example: "output_dir/cds_{cds}.translation.fasta".
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'P')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_translations: Option<String>,
+
+  #[clap(flatten)]
+  pub params: NextcladeInputParamsOptional,
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+#[clap(group(ArgGroup::new("outputs").required(true).multiple(true)))]
+pub struct NextcladeConsensusArgs {
+  /// Path to one or multiple FASTA files with aligned sequences, for example as produced by `nextclade run --output-fasta`.
+  ///
+  /// All sequences are expected to be aligned to the same reference, with insertions stripped, so that they all have the same length.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". If no files provided, the plain fasta input is read from standard input (stdin).
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 0)]
+  pub input_fastas: Vec<PathBuf>,
+
+  /// Path to a CSV or TSV file which assigns sequences to groups, for example results table produced by `nextclade run --output-tsv`.
+  ///
+  /// The file should contain column `seqName` and the column given by `--group-by`. Files with ".csv" extension are expected to be semicolon-separated, all other files are expected to be tab-separated.
+  ///
+  /// If not provided, a single consensus of all input sequences is computed.
+  #[clap(long, short = 'g')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_groups: Option<PathBuf>,
+
+  /// Name of the column in `--input-groups` file by which the sequences are grouped
+  #[clap(long, default_value = "clade")]
+  pub group_by: String,
+
+  /// Minimum frequency of a nucleotide at a site for it to be included into the consensus.
+  ///
+  /// At polymorphic sites, all nucleotides with frequency above this threshold are combined into an IUPAC ambiguity code. If none of the nucleotides reach the threshold, the most frequent one is used. Gap is used if it is more frequent than any of the nucleotides. Missing data ('N') is not taken into account.
+  #[clap(long, default_value = "0.2")]
+  pub min_frequency: f64,
+
+  /// Path to output FASTA file with consensus sequences, one per group.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(group = "outputs")]
+  pub output_fasta: Option<PathBuf>,
+
+  /// Path to output table with nucleotide frequencies at polymorphic sites, per group.
+  ///
+  /// If the provided file path ends with ".csv", the table is written with semicolon delimiters, otherwise with tab delimiters.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(group = "outputs")]
+  pub output_variability: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct NextcladeAnnotationArgs {
+  #[clap(subcommand)]
+  pub command: NextcladeAnnotationCommands,
+}
+
+#[derive(Subcommand, Debug)]
+#[clap(verbatim_doc_comment)]
+pub enum NextcladeAnnotationCommands {
+  /// Compare two genome annotations and report the differences between them: added and removed genes and CDSes, renamed CDSes, changes of CDS coordinates and strands.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade annotation diff --help`.
+  Diff(NextcladeAnnotationDiffArgs),
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeAnnotationDiffArgs {
+  /// Old genome annotation file in GFF3 format.
+  ///
+  /// Learn more about Generic Feature Format Version 3 (GFF3):
+  /// https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md
+  ///
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 0)]
+  pub old_annotation: PathBuf,
+
+  /// New genome annotation file in GFF3 format.
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 1)]
+  pub new_annotation: PathBuf,
+
+  /// Path to output JSON or YAML file with the differences.
+  ///
+  /// The format is chosen based on file extension: ".json" or ".yaml".
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output: Option<PathBuf>,
+
+  /// Print console output in JSON format, rather than human-readable table.
+  #[clap(long)]
+  pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct NextcladeTreeArgs {
+  #[clap(subcommand)]
+  pub command: NextcladeTreeCommands,
+}
+
+#[derive(Subcommand, Debug)]
+#[clap(verbatim_doc_comment)]
+pub enum NextcladeTreeCommands {
+  /// Add query sequences from a previous run to the reference tree as permanent nodes, and write the resulting tree. This allows to grow in-house reference trees from high-quality sequences.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade tree augment --help`.
+  Augment(Box<NextcladeTreeAugmentArgs>),
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeTreeAugmentArgs {
+  /// Path to results of a previous run, produced by `nextclade run --output-json` or `nextclade run --output-ndjson`. Files with ".ndjson" extension are read as newline-delimited JSON, all other files as JSON.
+  ///
+  /// The run must have used the same reference tree, reference sequence and genome annotation as passed to this command. Placements refer to the nodes of the reference tree and will be wrong for any other tree.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 0)]
+  pub input_results: PathBuf,
+
+  /// Path to Auspice JSON v2 file containing reference tree, which was used in the previous run.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'a')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree: PathBuf,
+
+  /// Path to a FASTA file containing reference sequence, which was used in the previous run. This file should contain exactly 1 sequence.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'r')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_ref: PathBuf,
+
+  /// Path to a file containing genome annotation in GFF3 format, which was used in the previous run. If not provided, aminoacid mutations are not added to the new branches.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_annotation: Option<PathBuf>,
+
+  /// Comma-separated list of QC statuses. Only sequences with one of these overall QC statuses are added to the tree.
+  #[clap(long, num_args=1.., use_value_delimiter = true)]
+  #[clap(value_parser = ["good", "mediocre", "bad"])]
+  #[clap(default_value = "good")]
+  pub include_qc_status: Vec<String>,
+
+  /// Path to a plain text file with names of sequences to add to the tree, one per line. If provided, only these sequences are considered (and they still have to pass `--include-qc-status` filter).
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub include_seq_names_from: Option<PathBuf>,
+
+  /// Path to output Auspice JSON v2 file with the augmented reference tree. It can be used as a reference tree (`--input-tree`) in subsequent runs.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree: PathBuf,
+
+  /// Path to output Newick file with the augmented reference tree.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_nwk: Option<PathBuf>,
+
+  #[clap(flatten, next_help_heading = "Phylogenetic tree parameters")]
+  pub tree_builder: Option<TreeBuilderParamsOptional>,
+}
+
 #[derive(Parser, Debug)]
+pub struct NextcladeQcArgs {
+  #[clap(subcommand)]
+  pub command: NextcladeQcCommands,
+}
+
+#[derive(Subcommand, Debug)]
 #[clap(verbatim_doc_comment)]
-pub struct NextcladeReadAnnotationArgs {
-  /// Genome annotation file in GFF3 format.
+pub enum NextcladeQcCommands {
+  /// Suggest parameters of QC rules from results of known-good sequences and write them as a ready-to-use QC config. This is useful for custom datasets, for which the QC parameters are not yet known.
   ///
-  /// Learn more about Generic Feature Format Version 3 (GFF3):
-  /// https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade qc calibrate --help`.
+  Calibrate(Box<NextcladeQcCalibrateArgs>),
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeQcCalibrateArgs {
+  /// Paths to results of previous runs on sequences known to be of good quality, produced by `nextclade run` with any of `--output-json`, `--output-ndjson`, `--output-csv` or `--output-tsv`. Files with ".ndjson" extension are read as newline-delimited JSON, with ".csv" extension as semicolon-separated CSV, with ".tsv" extension as TSV, all other files as JSON. Sequences which failed to be analyzed are ignored.
   ///
+  /// Note that CSV and TSV files don't contain private deletions, so these are not counted when calibrating the "private mutations" rule.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst".
   #[clap(value_hint = ValueHint::FilePath)]
   #[clap(display_order = 0)]
-  pub input_annotation: Option<PathBuf>,
+  #[clap(required = true)]
+  pub input_results: Vec<PathBuf>,
 
-  /// Path to output JSON or YAML file.
+  /// Path to QC config (usually `qc` field of `pathogen.json` of a dataset, saved as a separate file) to take the parameters which are not calibrated from. If not provided, the defaults are used.
   ///
-  /// The format is chosen based on file extension: ".json" or ".yaml".
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'q')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_qc_config: Option<PathBuf>,
+
+  /// Percentile of the known-good sequences, which is placed at the boundary between "good" and "mediocre" QC status. Sequences at the median are not penalized.
+  #[clap(long, default_value_t = 99.0)]
+  pub percentile: f64,
+
+  /// Path to output JSON file with the calibrated QC config.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
   #[clap(long, short = 'o')]
-  #[clap(value_hint = ValueHint::DirPath)]
-  pub output: Option<PathBuf>,
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(default_value = "-")]
+  pub output: PathBuf,
+}
 
-  /// Present features in "feature tree" format. This format is a precursor of genome annotation format - it contains all genetic features, even the ones that Nextclade does not use, but also less information about each feature.
+#[derive(Parser, Debug)]
+pub struct NextcladeResultsArgs {
+  #[clap(subcommand)]
+  pub command: NextcladeResultsCommands,
+}
+
+#[derive(Subcommand, Debug)]
+#[clap(verbatim_doc_comment)]
+pub enum NextcladeResultsCommands {
+  /// Merge results of multiple runs into one set of output files, for example when chunks of a large input were processed on different machines. Sequences are renumbered in the order of the input files.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade results merge --help`.
+  Merge(Box<NextcladeResultsMergeArgs>),
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeResultsMergeArgs {
+  /// Paths to results of previous runs, produced by `nextclade run --output-json` or `nextclade run --output-ndjson`. Files with ".ndjson" extension are read as newline-delimited JSON, all other files as JSON. CSV and TSV outputs cannot be merged, because they don't contain all of the results.
+  ///
+  /// All runs must have used the same dataset. For JSON files this is verified using the custom clade and phenotype columns recorded in the file.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst".
+  #[clap(value_hint = ValueHint::FilePath)]
+  #[clap(display_order = 0)]
+  #[clap(required = true)]
+  pub input_results: Vec<PathBuf>,
+
+  /// Allow the same sequence name to occur more than once. By default, this is an error, because it usually means that the same sequences were analyzed in more than one run.
   #[clap(long)]
-  pub feature_tree: bool,
+  pub allow_duplicates: bool,
 
-  /// Print console output in JSON format, rather than human-readable table.
+  /// Path to Auspice JSON v2 file containing reference tree, which was used in the runs. Required for `--output-tree` and `--output-tree-nwk`.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'a')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree: Option<PathBuf>,
+
+  /// Path to a FASTA file containing reference sequence, which was used in the runs. Required for `--output-tree` and `--output-tree-nwk`.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'r')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_ref: Option<PathBuf>,
+
+  /// Path to a file containing genome annotation in GFF3 format, which was used in the runs. If not provided, aminoacid mutations are not added to the branches of the output tree.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_annotation: Option<PathBuf>,
+
+  /// Path to output JSON file with the merged results.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  #[clap(long, short = 'J')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_json: Option<PathBuf>,
+
+  /// Path to output newline-delimited JSON (NDJSON) file with the merged results.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  #[clap(long, short = 'N')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_ndjson: Option<PathBuf>,
+
+  /// Path to output CSV file (semicolon-separated) with the merged results.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  #[clap(long, short = 'c')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_csv: Option<PathBuf>,
+
+  /// Path to output TSV file (tab-separated) with the merged results.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  #[clap(long, short = 't')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tsv: Option<PathBuf>,
+
+  /// Path to output Auspice JSON v2 file with the reference tree, with all of the merged sequences placed on it. Requires `--input-tree` and `--input-ref`.
+  ///
+  /// Trees written by the individual runs cannot be merged directly, so the sequences are placed on the reference tree again, the same way as in `nextclade run --output-tree`.
+  #[clap(long, short = 'T')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree: Option<PathBuf>,
+
+  /// Path to output Newick file with the reference tree, with all of the merged sequences placed on it. Requires `--input-tree` and `--input-ref`.
   #[clap(long)]
-  pub json: bool,
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_nwk: Option<PathBuf>,
+
+  #[clap(flatten, next_help_heading = "Phylogenetic tree parameters")]
+  pub tree_builder: Option<TreeBuilderParamsOptional>,
+}
+
+#[derive(Copy, Debug, Clone, PartialEq, Eq, ValueEnum)]
+pub enum NextcladeManifestFormat {
+  Json,
+  Spdx,
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+#[clap(group(ArgGroup::new("dataset").required(true)))]
+pub struct NextcladeManifestArgs {
+  /// Path to a directory or a zip file containing a dataset.
+  ///
+  /// For zip files, the checksum of the archive is reported. For directories, the checksum of the list of files and their individual checksums is reported.
+  #[clap(long, short = 'D')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  #[clap(group = "dataset")]
+  pub input_dataset: Option<PathBuf>,
+
+  /// Name of the dataset on the dataset server. The dataset zip archive is downloaded to compute its checksum, but is not saved.
+  ///
+  /// See `dataset list --help` for more details.
+  #[clap(long, short = 'd')]
+  #[clap(group = "dataset")]
+  pub dataset_name: Option<String>,
+
+  /// Version tag of the dataset given by `--dataset-name`. If not provided, the latest version is used.
+  #[clap(long, short = 't')]
+  #[clap(requires = "dataset_name")]
+  pub tag: Option<String>,
+
+  /// Use custom dataset server.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::Url)]
+  #[clap(default_value_t = Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"))]
+  pub server: Url,
+
+  /// Path to output JSON file. If not provided, the manifest is printed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output: Option<PathBuf>,
+
+  /// Path to input minimizer index JSON file, the version of which to record in the manifest.
+  ///
+  /// If not provided and the dataset is given by `--dataset-name`, the reference minimizer index is fetched from the dataset server. If not provided and the dataset is given by `--input-dataset`, the minimizer index version is not recorded.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_minimizer_index_json: Option<PathBuf>,
+
+  /// Format of the manifest: Nextclade's own JSON format or SPDX 2.3 document in JSON format.
+  #[clap(long, value_enum, default_value_t = NextcladeManifestFormat::Json)]
+  pub format: NextcladeManifestFormat,
+
+  #[clap(flatten)]
+  pub proxy_config: ProxyConfig,
 }
 
 fn generate_completions(shell: &str) -> Result<(), Report> {
@@ -799,24 +1805,61 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
         output_all,
         output_basename,
         output_selection,
+        run_id,
+        output_timestamped_subdir,
         output_fasta,
         output_translations,
+        output_nuc_cds_fasta,
+        output_codon_alignment,
         output_ndjson,
         output_json,
         output_csv,
         output_tsv,
+        output_parquet,
+        output_arrow,
         output_tree,
+        output_graph,
         output_tree_nwk,
+        output_tree_nexus,
+        output_tree_pb,
+        output_jplace,
+        output_context_trees,
+        output_haplotypes,
+        output_codon_usage,
+        output_support,
+        output_alignment_band,
+        output_maf,
+        output_batch_qc,
+        output_html,
+        output_vcf,
+        output_usage,
         ..
       },
     ..
   } = run_args;
 
+  let now = date_format_safe(&date_now());
+  let is_run_id_generated = run_id.is_none();
+  let run_id = run_id.get_or_insert_with(|| format!("{now}_{}", std::process::id()));
+
+  if *output_timestamped_subdir {
+    if let Some(output_all) = output_all {
+      // Generated run ID already starts with the date and time
+      let subdir = if is_run_id_generated {
+        run_id.clone()
+      } else {
+        format!("{now}_{run_id}")
+      };
+      *output_all = output_all.join(subdir);
+    }
+  }
+
   // If `--output-all` is provided, then we need to deduce default output filenames,
   // while taking care to preserve values of any individual `--output-*` flags,
   // as well as to honor restrictions put by the `--output-selection` flag, if provided.
   if let Some(output_all) = output_all {
     let output_basename = output_basename.clone().unwrap_or_else(|| "nextclade".to_owned());
+    let output_basename = substitute_run_id(&output_basename, run_id);
 
     let default_output_file_path = output_all.join(&output_basename);
 
@@ -845,6 +1888,30 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
       output_translations.get_or_insert(output_translations_template);
     }
 
+    if output_selection.contains(&NextcladeOutputSelection::NucCds) {
+      let output_nuc_cds_path =
+        default_output_file_path.with_file_name(format!("{output_basename}.cds_nuc.{{cds}}.fasta"));
+
+      let output_nuc_cds_template = output_nuc_cds_path
+        .to_str()
+        .wrap_err_with(|| format!("When converting path to string: '{output_nuc_cds_path:?}'"))?
+        .to_owned();
+
+      output_nuc_cds_fasta.get_or_insert(output_nuc_cds_template);
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::CodonAlignment) {
+      let output_codon_alignment_path =
+        default_output_file_path.with_file_name(format!("{output_basename}.cds_codon_alignment.{{cds}}.fasta"));
+
+      let output_codon_alignment_template = output_codon_alignment_path
+        .to_str()
+        .wrap_err_with(|| format!("When converting path to string: '{output_codon_alignment_path:?}'"))?
+        .to_owned();
+
+      output_codon_alignment.get_or_insert(output_codon_alignment_template);
+    }
+
     if output_selection.contains(&NextcladeOutputSelection::Ndjson) {
       output_ndjson.get_or_insert(add_extension(&default_output_file_path, "ndjson"));
     }
@@ -868,6 +1935,65 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
     if output_selection.contains(&NextcladeOutputSelection::TreeNwk) {
       output_tree_nwk.get_or_insert(add_extension(&default_output_file_path, "nwk"));
     }
+
+    if output_selection.contains(&NextcladeOutputSelection::Haplotypes) {
+      output_haplotypes.get_or_insert(add_extension(&default_output_file_path, "haplotypes.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::CodonUsage) {
+      output_codon_usage.get_or_insert(add_extension(&default_output_file_path, "codon_usage.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Support) {
+      output_support.get_or_insert(add_extension(&default_output_file_path, "support.bedgraph"));
+    }
+  }
+
+  // Substitute template variable `{run_id}` in all output paths
+  for output in [
+    &mut *output_fasta,
+    &mut *output_ndjson,
+    &mut *output_json,
+    &mut *output_csv,
+    &mut *output_tsv,
+    &mut *output_parquet,
+    &mut *output_arrow,
+    &mut *output_tree,
+    &mut *output_graph,
+    &mut *output_tree_nwk,
+    &mut *output_tree_nexus,
+    &mut *output_tree_pb,
+    &mut *output_jplace,
+    &mut *output_haplotypes,
+    &mut *output_codon_usage,
+    &mut *output_support,
+    &mut *output_alignment_band,
+    &mut *output_maf,
+    &mut *output_batch_qc,
+    &mut *output_html,
+    &mut *output_vcf,
+    &mut *output_usage,
+  ]
+  .into_iter()
+  .flatten()
+  {
+    *output = PathBuf::from(substitute_run_id(&output.to_string_lossy(), run_id));
+  }
+
+  if let Some(output_translations) = output_translations {
+    *output_translations = substitute_run_id(output_translations, run_id);
+  }
+
+  if let Some(output_nuc_cds_fasta) = output_nuc_cds_fasta {
+    *output_nuc_cds_fasta = substitute_run_id(output_nuc_cds_fasta, run_id);
+  }
+
+  if let Some(output_codon_alignment) = output_codon_alignment {
+    *output_codon_alignment = substitute_run_id(output_codon_alignment, run_id);
+  }
+
+  if let Some(output_context_trees) = output_context_trees {
+    *output_context_trees = substitute_run_id(output_context_trees, run_id);
   }
 
   if let Some(output_translations) = output_translations {
@@ -888,6 +2014,60 @@ Example for bash shell:
     }
   }
 
+  if let Some(output_nuc_cds_fasta) = output_nuc_cds_fasta {
+    if !output_nuc_cds_fasta.contains("{cds}") {
+      return make_error!(
+        r#"
+Expected `--output-nuc-cds-fasta` argument to contain a template string containing template variable {{cds}} (with curly braces), but received:
+
+  {output_nuc_cds_fasta}
+
+Make sure the variable is not substituted by your shell, programming language or workflow manager. Apply proper escaping as needed.
+Example for bash shell:
+
+  --output-nuc-cds-fasta='output_dir/cds_{{cds}}.nuc.fasta'
+
+      "#
+      );
+    }
+  }
+
+  if let Some(output_codon_alignment) = output_codon_alignment {
+    if !output_codon_alignment.contains("{cds}") {
+      return make_error!(
+        r#"
+Expected `--output-codon-alignment` argument to contain a template string containing template variable {{cds}} (with curly braces), but received:
+
+  {output_codon_alignment}
+
+Make sure the variable is not substituted by your shell, programming language or workflow manager. Apply proper escaping as needed.
+Example for bash shell:
+
+  --output-codon-alignment='output_dir/cds_{{cds}}.codon_alignment.fasta'
+
+      "#
+      );
+    }
+  }
+
+  if let Some(output_context_trees) = output_context_trees {
+    if !output_context_trees.contains("{seq_name}") {
+      return make_error!(
+        r#"
+Expected `--output-context-trees` argument to contain a template string containing template variable {{seq_name}} (with curly braces), but received:
+
+  {output_context_trees}
+
+Make sure the variable is not substituted by your shell, programming language or workflow manager. Apply proper escaping as needed.
+Example for bash shell:
+
+  --output-context-trees='output_dir/context/{{seq_name}}.auspice.json'
+
+      "#
+      );
+    }
+  }
+
   let all_outputs_are_missing = [
     output_all,
     output_fasta,
@@ -895,11 +2075,24 @@ Example for bash shell:
     output_json,
     output_csv,
     output_tsv,
+    output_parquet,
+    output_arrow,
     output_tree,
+    output_haplotypes,
+    output_codon_usage,
+    output_support,
+    output_alignment_band,
+    output_maf,
+    output_batch_qc,
+    output_html,
+    output_vcf,
   ]
   .iter()
   .all(|o| o.is_none())
-    && output_translations.is_none();
+    && output_translations.is_none()
+    && output_nuc_cds_fasta.is_none()
+    && output_codon_alignment.is_none()
+    && output_context_trees.is_none();
 
   if all_outputs_are_missing {
     return make_error!(
@@ -912,14 +2105,31 @@ At least one of the following flags is required:
   --output-json
   --output-csv
   --output-tsv
+  --output-parquet
+  --output-arrow
   --output-tree
-  --output-translations"#
+  --output-translations
+  --output-nuc-cds-fasta
+  --output-codon-alignment
+  --output-context-trees
+  --output-haplotypes
+  --output-codon-usage
+  --output-support
+  --output-alignment-band
+  --output-maf
+  --output-batch-qc
+  --output-html
+  --output-vcf"#
     );
   }
 
   Ok(())
 }
 
+fn substitute_run_id(template: &str, run_id: &str) -> String {
+  template.replace("{run_id}", run_id)
+}
+
 const ERROR_MSG_INPUT_FASTA_REMOVED: &str = r#"The argument `--input-fasta` (alias: `--sequences`, `-i`) is removed in favor of positional arguments.
 
 Try:
@@ -1096,12 +2306,18 @@ pub fn nextclade_check_column_config_args(run_args: &NextcladeRunArgs) -> Result
     output_all,
     output_csv,
     output_tsv,
+    output_parquet,
+    output_arrow,
     output_columns_selection,
     ..
   } = &run_args.outputs;
 
-  if !output_columns_selection.is_empty() && [output_all, output_csv, output_tsv].iter().all(|arg| arg.is_none()) {
-    return make_error!("The `--output-columns-selection` argument configures column-based output formats and can only be used when one or more of the column-based file outputs is requested, i.e. together with one or multiple of `--output-all`, `--output-csv`, `--output-tsv`.");
+  if !output_columns_selection.is_empty()
+    && [output_all, output_csv, output_tsv, output_parquet, output_arrow]
+      .iter()
+      .all(|arg| arg.is_none())
+  {
+    return make_error!("The `--output-columns-selection` argument configures column-based output formats and can only be used when one or more of the column-based file outputs is requested, i.e. together with one or multiple of `--output-all`, `--output-csv`, `--output-tsv`, `--output-parquet`, `--output-arrow`.");
   }
 
   Ok(())
@@ -1121,7 +2337,11 @@ pub fn nextclade_parse_cli_args() -> Result<(), Report> {
       nextclade_check_removed_args(&run_args)?;
       nextclade_check_column_config_args(&run_args)?;
       nextclade_get_output_filenames(&mut run_args).wrap_err("When deducing output filenames")?;
-      nextclade_run(*run_args)
+      if run_args.inputs.input_aa {
+        nextclade_run_aa(&run_args)
+      } else {
+        nextclade_run(*run_args)
+      }
     }
     NextcladeCommands::Dataset(dataset_command) => match dataset_command.command {
       NextcladeDatasetCommands::List(dataset_list_args) => {
@@ -1133,7 +2353,123 @@ pub fn nextclade_parse_cli_args() -> Result<(), Report> {
         nextclade_dataset_get(&dataset_get_args)
       }
     },
+    NextcladeCommands::Translate(translate_args) => nextclade_translate(&translate_args),
+    NextcladeCommands::Pairwise(pairwise_args) => nextclade_pairwise(&pairwise_args),
     NextcladeCommands::Sort(seq_sort_args) => nextclade_seq_sort(&seq_sort_args),
     NextcladeCommands::ReadAnnotation(read_annotation_args) => nextclade_read_annotation(&read_annotation_args),
+    NextcladeCommands::Consensus(consensus_args) => nextclade_consensus(&consensus_args),
+    NextcladeCommands::Annotation(annotation_command) => match annotation_command.command {
+      NextcladeAnnotationCommands::Diff(annotation_diff_args) => nextclade_annotation_diff(&annotation_diff_args),
+    },
+    NextcladeCommands::Tree(tree_command) => match tree_command.command {
+      NextcladeTreeCommands::Augment(tree_augment_args) => nextclade_tree_augment(&tree_augment_args),
+    },
+    NextcladeCommands::Results(results_command) => match results_command.command {
+      NextcladeResultsCommands::Merge(results_merge_args) => nextclade_results_merge(&results_merge_args),
+    },
+    NextcladeCommands::Qc(qc_command) => match qc_command.command {
+      NextcladeQcCommands::Calibrate(qc_calibrate_args) => nextclade_qc_calibrate(&qc_calibrate_args),
+    },
+    NextcladeCommands::Manifest(manifest_args) => nextclade_manifest(&manifest_args),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::path::Path;
+
+  #[rstest]
+  #[case::no_template("nextclade", "run1", "nextclade")]
+  #[case::basename("nextclade_{run_id}", "run1", "nextclade_run1")]
+  #[case::directory("results/{run_id}/nextclade.tsv", "run1", "results/run1/nextclade.tsv")]
+  #[case::multiple("{run_id}/{run_id}.json", "run1", "run1/run1.json")]
+  #[case::other_templates_untouched("out.{cds}.{run_id}.fasta", "run1", "out.{cds}.run1.fasta")]
+  #[trace]
+  fn substitutes_run_id(#[case] template: &str, #[case] run_id: &str, #[case] expected: &str) {
+    assert_eq!(substitute_run_id(template, run_id), expected);
+  }
+
+  fn parse_run_args(args: &[&str]) -> NextcladeRunArgs {
+    let args = ["nextclade", "run"].iter().chain(args.iter()).copied();
+    match NextcladeArgs::try_parse_from(args).unwrap().command {
+      NextcladeCommands::Run(run_args) => *run_args,
+      _ => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn substitutes_run_id_in_output_all_filenames() -> Result<(), Report> {
+    let mut run_args = parse_run_args(&[
+      "--output-all=out",
+      "--output-basename=nextclade_{run_id}",
+      "--output-selection=tsv,translations",
+      "--run-id=run1",
+      "seq.fasta",
+    ]);
+    nextclade_get_output_filenames(&mut run_args)?;
+    assert_eq!(
+      run_args.outputs.output_tsv.as_deref(),
+      Some(Path::new("out/nextclade_run1.tsv"))
+    );
+    assert_eq!(
+      run_args.outputs.output_translations.as_deref(),
+      Some("out/nextclade_run1.cds_translation.{cds}.fasta.translation.fasta")
+    );
+    assert_eq!(run_args.outputs.output_csv, None);
+    Ok(())
+  }
+
+  #[test]
+  fn substitutes_run_id_in_individual_output_filenames() -> Result<(), Report> {
+    let mut run_args = parse_run_args(&[
+      "--output-tsv=results/{run_id}/nextclade.tsv",
+      "--output-nuc-cds-fasta=results/{run_id}/{cds}.fasta",
+      "--run-id=run1",
+      "seq.fasta",
+    ]);
+    nextclade_get_output_filenames(&mut run_args)?;
+    assert_eq!(
+      run_args.outputs.output_tsv.as_deref(),
+      Some(Path::new("results/run1/nextclade.tsv"))
+    );
+    assert_eq!(
+      run_args.outputs.output_nuc_cds_fasta.as_deref(),
+      Some("results/run1/{cds}.fasta")
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn places_outputs_into_timestamped_subdir_with_run_id() -> Result<(), Report> {
+    let mut run_args = parse_run_args(&[
+      "--output-all=out",
+      "--output-timestamped-subdir",
+      "--output-selection=tsv",
+      "--run-id=run1",
+      "seq.fasta",
+    ]);
+    nextclade_get_output_filenames(&mut run_args)?;
+    let output_tsv = run_args.outputs.output_tsv.unwrap();
+    let subdir = output_tsv.parent().unwrap();
+    assert_eq!(subdir.parent(), Some(Path::new("out")));
+    assert!(subdir.to_string_lossy().ends_with("_run1"));
+    assert_eq!(output_tsv.file_name().unwrap(), "nextclade.tsv");
+    Ok(())
+  }
+
+  #[test]
+  fn generates_run_id_when_not_provided() -> Result<(), Report> {
+    let mut run_args = parse_run_args(&["--output-tsv=out/{run_id}.tsv", "seq.fasta"]);
+    nextclade_get_output_filenames(&mut run_args)?;
+    let run_id = run_args.outputs.run_id.clone().unwrap();
+    assert!(run_id.ends_with(&format!("_{}", std::process::id())));
+    assert_eq!(
+      run_args.outputs.output_tsv.as_deref(),
+      Some(Path::new(&format!("out/{run_id}.tsv")))
+    );
+    Ok(())
   }
 }
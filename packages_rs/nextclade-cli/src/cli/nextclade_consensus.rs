@@ -0,0 +1,99 @@
+use crate::cli::nextclade_cli::NextcladeConsensusArgs;
+use eyre::{Report, WrapErr};
+use log::{info, warn};
+use nextclade::alphabet::nuc::{from_nuc_seq, to_nuc_seq_replacing};
+use nextclade::analyze::consensus::{consensus_variability_write, ConsensusGrouper};
+use nextclade::io::csv::read_csv_file_with_delimiter;
+use nextclade::io::fasta::{FastaReader, FastaRecord, FastaWriter};
+use nextclade::io::fs::has_extension;
+use nextclade::make_error;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Name of the group containing all sequences, when no grouping is requested
+const WHOLE_BATCH_GROUP: &str = "all";
+
+pub fn nextclade_consensus(args: &NextcladeConsensusArgs) -> Result<(), Report> {
+  let NextcladeConsensusArgs {
+    input_fastas,
+    input_groups,
+    group_by,
+    min_frequency,
+    output_fasta,
+    output_variability,
+  } = args;
+
+  if !(0.0..=1.0).contains(min_frequency) {
+    return make_error!("Minimum frequency is expected to be between 0 and 1, but found {min_frequency}");
+  }
+
+  let groups = input_groups
+    .as_ref()
+    .map(|input_groups| read_groups(input_groups, group_by))
+    .transpose()?;
+
+  let mut grouper = ConsensusGrouper::new();
+  let mut reader = FastaReader::from_paths(input_fastas)?;
+  loop {
+    let mut record = FastaRecord::default();
+    reader.read(&mut record)?;
+    if record.is_empty() {
+      break;
+    }
+
+    let group = match &groups {
+      None => WHOLE_BATCH_GROUP,
+      Some(groups) => {
+        if let Some(group) = groups.get(&record.seq_name) {
+          group.as_str()
+        } else {
+          warn!(
+            "Sequence #{} '{}' is not found in the groups file or has no value in column '{group_by}'. It will not be included into consensus.",
+            record.index, record.seq_name
+          );
+          continue;
+        }
+      }
+    };
+
+    grouper
+      .add(group, &to_nuc_seq_replacing(&record.seq))
+      .wrap_err_with(|| format!("When processing sequence #{} '{}'", record.index, record.seq_name))?;
+  }
+
+  if let Some(output_fasta) = output_fasta {
+    let mut writer = FastaWriter::from_path(output_fasta)?;
+    for (group, builder) in grouper.iter() {
+      info!("Consensus of group '{group}': {} sequences", builder.num_seqs());
+      writer.write(group, &from_nuc_seq(&builder.consensus(*min_frequency)), false)?;
+    }
+  }
+
+  if let Some(output_variability) = output_variability {
+    consensus_variability_write(output_variability, &grouper, *min_frequency)?;
+  }
+
+  Ok(())
+}
+
+/// Reads mapping from sequence name to group name from a CSV or TSV file
+fn read_groups(filepath: &Path, group_by: &str) -> Result<HashMap<String, String>, Report> {
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+
+  let rows: Vec<BTreeMap<String, String>> = read_csv_file_with_delimiter(filepath, delimiter)
+    .wrap_err_with(|| format!("When reading sequence groups from file {filepath:#?}"))?;
+
+  rows
+    .into_iter()
+    .filter_map(|mut row| {
+      let seq_name = row.remove("seqName");
+      let group = row.remove(group_by);
+      match (seq_name, group) {
+        (None, _) => Some(make_error!("Column 'seqName' is not found in file {filepath:#?}")),
+        (Some(_), None) => Some(make_error!("Column '{group_by}' is not found in file {filepath:#?}")),
+        (Some(_), Some(group)) if group.is_empty() => None,
+        (Some(seq_name), Some(group)) => Some(Ok((seq_name, group))),
+      }
+    })
+    .collect()
+}
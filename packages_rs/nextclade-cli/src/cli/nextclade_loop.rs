@@ -2,72 +2,299 @@ use crate::cli::nextclade_cli::{
   NextcladeRunArgs, NextcladeRunInputArgs, NextcladeRunOtherParams, NextcladeRunOutputArgs,
 };
 use crate::cli::nextclade_ordered_writer::NextcladeOrderedWriter;
+use crate::cli::sequence_dedup::{run_deduplicated, SequenceDedup};
+use crate::cli::usage_summary::{run_output_paths, UsageSummary};
 use crate::dataset::dataset_download::nextclade_get_inputs;
+use crate::io::output_sink::{fail_outputs_isolated, report_output_errors, write_output_isolated};
+use crate::plugin::sequence_plugin::SequencePlugin;
 use eyre::{Report, WrapErr};
-use log::info;
+use itertools::{chain, Itertools};
+use log::{info, warn};
+use nextclade::analyze::codon_usage::{codon_usage_write, CodonUsageCounter};
+use nextclade::analyze::haplotypes::{haplotypes_write, HaplotypeGrouper, HaplotypeMask};
+use nextclade::analyze::lineage_caller::{lineage_definitions_read, LineageCaller};
+use nextclade::analyze::minor_variants::AlleleFrequencies;
+use nextclade::analyze::mutation_annotations::MutationKnowledgeBase;
+use nextclade::analyze::position_support::{position_support_write, PositionSupportCounter};
+use nextclade::gene::gene_map::GeneMap;
 use nextclade::gene::gene_map_display::gene_map_to_table_string;
-use nextclade::graph::graph::convert_graph_to_auspice_tree;
+use nextclade::graph::graph::{convert_auspice_tree_to_graph, convert_graph_to_auspice_tree};
+use nextclade::io::bed::read_bed_file;
 use nextclade::io::fasta::{FastaReader, FastaRecord};
+use nextclade::io::fs::has_extension;
+use nextclade::io::html_report::{html_report_write, HtmlReportBuilder};
+use nextclade::io::jplace::{jplace_write_to_file, JplaceQuery};
 use nextclade::io::json::{json_write, JsonPretty};
-use nextclade::io::nextclade_csv::CsvColumnConfig;
+use nextclade::io::nextclade_csv::{CsvColumnCategory, CsvColumnConfig};
+use nextclade::io::nexus_writer::nexus_write_to_file;
 use nextclade::io::nwk_writer::nwk_write_to_file;
-use nextclade::run::nextclade_wasm::{AnalysisInitialData, AnalysisOutput, Nextclade};
-use nextclade::tree::tree_builder::graph_attach_new_nodes_in_place;
-use nextclade::types::outputs::NextcladeOutputs;
+use nextclade::io::usher_mat::usher_mat_write_to_file;
+use nextclade::io::vcf_writer::{vcf_write_to_file, VcfSampleCollector};
+use nextclade::qc::qc_batch::{qc_batch_map_read, qc_batch_write, QcBatchCounter};
+use nextclade::run::nextclade_wasm::{AnalysisOutput, Nextclade};
+use nextclade::sort::reference_selection::ReferenceSelector;
+use nextclade::tree::tree_builder::BatchedTreeBuilder;
+use nextclade::tree::tree_context::extract_context_subtree;
+use nextclade::tree::tree_metadata::{graph_join_metadata_in_place, SampleMetadata};
+use nextclade::utils::error::report_to_string;
+use nextclade::utils::progress::{with_progress_sink, ProgressSink};
+use nextclade::utils::sampling::RecordSampler;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::time::Instant;
 
 pub struct NextcladeRecord {
   pub index: usize,
   pub seq_name: String,
+  /// Index of the reference the sequence was analyzed against: 0 for the main dataset, followed by the alternative
+  /// datasets (`--input-dataset-alt`) in the order they were provided
+  pub ref_index: usize,
   pub outputs_or_err: Result<AnalysisOutput, Report>,
 }
 
 pub fn nextclade_run(run_args: NextcladeRunArgs) -> Result<(), Report> {
+  let start = Instant::now();
   info!("Command-line arguments:\n{run_args:#?}");
 
   let NextcladeRunArgs {
-    inputs: NextcladeRunInputArgs {
-      input_fastas, cds_selection: cdses, ..
-    },
+    inputs:
+      NextcladeRunInputArgs {
+        input_fastas,
+        input_dataset_alt,
+        cds_selection: cdses,
+        sample_fraction,
+        sample_every_n,
+        no_dedup,
+        plugin_script,
+        input_mask_bed,
+        input_lineages,
+        input_mutation_annotations,
+        input_minor_variants,
+        input_batch_map,
+        batch_column,
+        input_metadata,
+        metadata_columns,
+        ..
+      },
     outputs:
       NextcladeRunOutputArgs {
         output_columns_selection,
+        output_columns_compat,
         output_graph,
         output_tree,
         output_tree_nwk,
+        output_tree_nexus,
+        output_tree_pb,
+        output_jplace,
+        output_context_trees,
+        context_tree_leaves,
+        output_haplotypes,
+        output_codon_usage,
+        output_support,
+        output_batch_qc,
+        output_html,
+        output_vcf,
+        haplotype_cdses,
+        haplotype_positions,
+        output_usage,
+        output_alignment_band,
         ..
       },
     params,
     other_params: NextcladeRunOtherParams { jobs },
   } = run_args.clone();
 
-  let inputs = nextclade_get_inputs(&run_args, &cdses)?;
-  let nextclade = Nextclade::new(inputs, &params)?;
+  let mut inputs = nextclade_get_inputs(&run_args.inputs, &cdses)?;
+  if let Some(input_mask_bed) = &input_mask_bed {
+    inputs.virus_properties.masked_ranges = read_bed_file(input_mask_bed)?;
+  }
+  let mut nextclade = Nextclade::new(inputs, &params)?;
+  nextclade.include_alignment_band = output_alignment_band.is_some();
+  if let Some(input_lineages) = &input_lineages {
+    nextclade.lineage_caller = Some(LineageCaller::new(lineage_definitions_read(input_lineages)?)?);
+  }
+  if let Some(input_mutation_annotations) = &input_mutation_annotations {
+    nextclade.mutation_knowledge_base = Some(MutationKnowledgeBase::from_path(input_mutation_annotations)?);
+  }
+  if let Some(input_minor_variants) = &input_minor_variants {
+    nextclade.allele_frequencies = Some(AlleleFrequencies::from_vcf_path(input_minor_variants)?);
+  }
+  let sample_metadata = input_metadata
+    .as_ref()
+    .map(|input_metadata| SampleMetadata::from_path(input_metadata, &metadata_columns))
+    .transpose()?;
+
+  let nextclades_alt = input_dataset_alt
+    .iter()
+    .map(|input_dataset| {
+      let inputs = NextcladeRunInputArgs {
+        input_dataset: Some(input_dataset.clone()),
+        ..NextcladeRunInputArgs::default()
+      };
+      nextclade_get_inputs(&inputs, &cdses)
+        .and_then(|inputs| Nextclade::new(inputs, &params))
+        .map(|nextclade| Nextclade {
+          include_alignment_band: output_alignment_band.is_some(),
+          ..nextclade
+        })
+        .wrap_err_with(|| format!("When loading alternative dataset {input_dataset:#?}"))
+    })
+    .collect::<Result<Vec<Nextclade>, Report>>()?;
+
+  let reference_selector = (!nextclades_alt.is_empty())
+    .then(|| ReferenceSelector::new(chain([&nextclade], &nextclades_alt).map(|nextclade| &nextclade.ref_record)));
+  let sampler = RecordSampler::new(sample_fraction, sample_every_n)?;
+  let plugin = plugin_script
+    .as_ref()
+    .map(|plugin_script| -> Result<SequencePlugin, Report> {
+      let plugin = SequencePlugin::from_path(plugin_script)?;
+      chain([&nextclade], &nextclades_alt)
+        .try_for_each(|nextclade| plugin.check_clade_attr_conflicts(&nextclade.clade_attr_descs))
+        .wrap_err_with(|| format!("When loading plugin script {plugin_script:#?}"))?;
+      Ok(plugin)
+    })
+    .transpose()?;
+  let dedup = (!no_dedup).then(SequenceDedup::default);
+
+  let should_write_tree = output_tree.is_some()
+    || output_tree_nwk.is_some()
+    || output_tree_nexus.is_some()
+    || output_tree_pb.is_some()
+    || output_jplace.is_some()
+    || output_context_trees.is_some()
+    || output_graph.is_some();
+
+  if nextclade.graph.is_none() {
+    warn!("Reference tree is not provided. Phylogenetic placement, clade assignment, private mutations, divergence and the QC rules which depend on them will be skipped. The corresponding output columns will be empty and the `treeDependentSkipped` column will be set to `true`.");
+    if should_write_tree {
+      warn!("Reference tree is not provided. Output trees and graph ('--output-tree', '--output-tree-nwk', '--output-tree-nexus', '--output-tree-pb', '--output-jplace', '--output-context-trees', '--output-graph') will not be written.");
+    }
+  }
+
+  let should_build_tree = output_tree.is_some()
+    || output_tree_nwk.is_some()
+    || output_tree_nexus.is_some()
+    || output_tree_pb.is_some()
+    || output_context_trees.is_some()
+    || output_graph.is_some();
+  let mut tree_builder = nextclade
+    .graph
+    .as_ref()
+    .filter(|_| should_build_tree)
+    .map(|graph| BatchedTreeBuilder::new(graph, nextclade.ref_seq.len(), &nextclade.params.tree_builder));
+  let should_write_jplace = output_jplace.is_some() && nextclade.graph.is_some();
+  let mut jplace_queries = Vec::<JplaceQuery>::new();
 
-  let should_write_tree = output_tree.is_some() || output_tree_nwk.is_some() || output_graph.is_some();
-  let mut outputs = Vec::<NextcladeOutputs>::new();
+  let mut haplotype_grouper = output_haplotypes
+    .as_ref()
+    .map(|_| HaplotypeMask::new(&nextclade.gene_map, &haplotype_cdses, &haplotype_positions).map(HaplotypeGrouper::new))
+    .transpose()?;
 
-  let csv_column_config = CsvColumnConfig::new(&output_columns_selection)?;
+  let mut codon_usage_counter = output_codon_usage
+    .as_ref()
+    .map(|_| CodonUsageCounter::new(&nextclade.ref_seq, &nextclade.gene_map));
+
+  let mut position_support_counter = output_support
+    .as_ref()
+    .map(|_| PositionSupportCounter::new(nextclade.ref_seq.len()));
+
+  let batch_map = match (&output_batch_qc, &input_batch_map) {
+    (Some(_), Some(input_batch_map)) => Some(qc_batch_map_read(input_batch_map, &batch_column)?),
+    _ => None,
+  };
+  let mut qc_batch_counter = batch_map.as_ref().map(|_| QcBatchCounter::default());
+
+  let mut html_report_builder = output_html.as_ref().map(|_| HtmlReportBuilder::default());
+
+  let mut vcf_collector = output_vcf.as_ref().map(|_| VcfSampleCollector::new(&nextclade.ref_seq));
+
+  let output_paths = run_output_paths(&run_args.outputs, &nextclade.gene_map);
+  let mut num_sequences = 0_usize;
+
+  let mut csv_column_config = CsvColumnConfig::new(&output_columns_selection, output_columns_compat)?;
+  if nextclade.params.general.include_alignment_diagnostics {
+    csv_column_config.enable_category(CsvColumnCategory::AlignmentDiagnostics);
+  }
+  if nextclade.params.general.mutations_relative_to.is_some() {
+    csv_column_config.enable_category(CsvColumnCategory::RelativeMuts);
+  }
+  if nextclade.params.general.include_hgvs {
+    csv_column_config.enable_category(CsvColumnCategory::Hgvs);
+  }
+  if nextclade.params.alignment.normalize_homopolymer_indels {
+    csv_column_config.enable_column(&CsvColumnCategory::RefMuts, "normalizedHomopolymerIndels");
+  }
+  if nextclade.params.general.detect_recombinants && nextclade.graph.is_some() {
+    csv_column_config.enable_category(CsvColumnCategory::Recombination);
+  }
+  if nextclade.params.general.num_placement_candidates > 0 && nextclade.graph.is_some() {
+    csv_column_config.enable_category(CsvColumnCategory::Placement);
+  }
+  if nextclade.params.general.include_mutation_path && nextclade.graph.is_some() {
+    csv_column_config.enable_category(CsvColumnCategory::MutationPath);
+  }
+  if nextclade.lineage_caller.is_some() {
+    csv_column_config.enable_category(CsvColumnCategory::Lineage);
+  }
+  if nextclade.mutation_knowledge_base.is_some() {
+    csv_column_config.enable_column(&CsvColumnCategory::RefMuts, "annotations");
+  }
+  if nextclade.allele_frequencies.is_some() {
+    csv_column_config.enable_column(&CsvColumnCategory::RefMuts, "minorVariants");
+  }
+  if nextclade.params.general.impute_ambiguous {
+    csv_column_config.enable_column(&CsvColumnCategory::Qc, "imputedNucs");
+  }
+  if !nextclade.virus_properties.masked_ranges.is_empty() {
+    csv_column_config.enable_column(&CsvColumnCategory::Qc, "masked");
+    csv_column_config.enable_column(&CsvColumnCategory::Qc, "totalMasked");
+  }
+  if let Some(qc_config) = &nextclade.virus_properties.qc {
+    if !qc_config.custom_rules.is_empty() {
+      csv_column_config.enable_column(&CsvColumnCategory::Qc, "qc.customRules");
+    }
+  }
 
   info!("Parameters (final):\n{:#?}", &nextclade.params);
   info!("Genome annotation:\n{}", gene_map_to_table_string(&nextclade.gene_map)?);
 
-  std::thread::scope(|s| {
+  let mut output_errors = std::thread::scope(|s| {
     const CHANNEL_SIZE: usize = 128;
     let (fasta_sender, fasta_receiver) = crossbeam_channel::bounded::<FastaRecord>(CHANNEL_SIZE);
     let (result_sender, result_receiver) = crossbeam_channel::bounded::<NextcladeRecord>(CHANNEL_SIZE);
 
     let nextclade = &nextclade;
-    let outputs = &mut outputs;
+    let nextclades_alt = &nextclades_alt;
+    let reference_selector = &reference_selector;
+    let plugin = &plugin;
+    let dedup = &dedup;
+    let tree_builder = &mut tree_builder;
+    let jplace_queries = &mut jplace_queries;
+    let haplotype_grouper = &mut haplotype_grouper;
+    let codon_usage_counter = &mut codon_usage_counter;
+    let position_support_counter = &mut position_support_counter;
+    let batch_map = &batch_map;
+    let qc_batch_counter = &mut qc_batch_counter;
+    let html_report_builder = &mut html_report_builder;
+    let vcf_collector = &mut vcf_collector;
+    let num_sequences = &mut num_sequences;
     let run_args = &run_args;
 
     s.spawn(|| {
       let mut reader = FastaReader::from_paths(&input_fastas).unwrap();
+      let mut num_selected = 0;
       loop {
         let mut record = FastaRecord::default();
         reader.read(&mut record).unwrap();
         if record.is_empty() {
           break;
         }
+        if !sampler.is_selected(record.index) {
+          continue;
+        }
+        // Selected records are renumbered, because output writers expect consecutive indices
+        record.index = num_selected;
+        num_selected += 1;
         fasta_sender
           .send(record)
           .wrap_err("When sending a FastaRecord")
@@ -86,7 +313,32 @@ pub fn nextclade_run(run_args: NextcladeRunArgs) -> Result<(), Report> {
         for fasta_record in &fasta_receiver {
           info!("Processing sequence '{}'", fasta_record.seq_name);
 
-          let outputs_or_err = nextclade.run(&fasta_record).wrap_err_with(|| {
+          // Intra-sequence progress, reported for very long sequences only, to distinguish a slow run from a stalled one
+          let progress_sink: ProgressSink = {
+            let index = fasta_record.index;
+            let seq_name = fasta_record.seq_name.clone();
+            Box::new(move |event| info!("Sequence #{index} '{seq_name}': {event}"))
+          };
+
+          let ref_index = reference_selector
+            .as_ref()
+            .map_or(0, |reference_selector| reference_selector.select(&fasta_record));
+          let state = if ref_index == 0 {
+            nextclade
+          } else {
+            &nextclades_alt[ref_index - 1]
+          };
+
+          let outputs_or_err = run_deduplicated(dedup.as_ref(), ref_index, &fasta_record, || {
+            with_progress_sink(progress_sink, || state.run(&fasta_record))
+          })
+          .and_then(|mut output| {
+            if let Some(plugin) = plugin {
+              plugin.process(&mut output.analysis_result)?;
+            }
+            Ok(output)
+          })
+          .wrap_err_with(|| {
             format!(
               "When processing sequence #{} '{}'",
               fasta_record.index, fasta_record.seq_name
@@ -101,6 +353,7 @@ pub fn nextclade_run(run_args: NextcladeRunArgs) -> Result<(), Report> {
             .send(NextcladeRecord {
               index: fasta_record.index,
               seq_name: fasta_record.seq_name,
+              ref_index,
               outputs_or_err,
             })
             .wrap_err("When sending NextcladeRecord")
@@ -114,71 +367,307 @@ pub fn nextclade_run(run_args: NextcladeRunArgs) -> Result<(), Report> {
     let writer = s.spawn(move || {
       let nextclade = &nextclade;
 
-      let AnalysisInitialData {
-        genome_size,
-        gene_map,
-        clade_node_attr_key_descs,
-        phenotype_attr_descs,
-        aa_motif_keys,
-        ..
-      } = nextclade.get_initial_data();
+      // Outputs should accommodate the CDSes and the custom columns of all of the datasets in use
+      let all_nextclades = chain([*nextclade], nextclades_alt).collect_vec();
+      let gene_map = GeneMap::from_genes(
+        all_nextclades
+          .iter()
+          .flat_map(|nextclade| nextclade.gene_map.iter_genes())
+          .unique_by(|gene| gene.name.clone())
+          .cloned()
+          .collect(),
+      );
+      let plugin_attr_descs = plugin.iter().flat_map(SequencePlugin::attr_descs).collect_vec();
+      let clade_node_attr_key_descs = all_nextclades
+        .iter()
+        .flat_map(|nextclade| &nextclade.clade_attr_descs)
+        .chain(&plugin_attr_descs)
+        .unique_by(|desc| desc.name.clone())
+        .cloned()
+        .collect_vec();
+      let phenotype_attr_descs = all_nextclades
+        .iter()
+        .flat_map(|nextclade| &nextclade.phenotype_attr_descs)
+        .unique_by(|desc| desc.name.clone())
+        .cloned()
+        .collect_vec();
+      let aa_motif_keys = all_nextclades
+        .iter()
+        .flat_map(|nextclade| &nextclade.aa_motifs_keys)
+        .unique()
+        .cloned()
+        .collect_vec();
+
+      let references = all_nextclades
+        .iter()
+        .map(|nextclade| (nextclade.ref_record.seq_name.as_str(), nextclade.ref_seq.as_slice()))
+        .collect_vec();
 
       let mut output_writer = NextcladeOrderedWriter::new(
-        &nextclade.gene_map,
-        clade_node_attr_key_descs,
-        phenotype_attr_descs,
-        aa_motif_keys,
+        &gene_map,
+        &clade_node_attr_key_descs,
+        &phenotype_attr_descs,
+        &aa_motif_keys,
         &csv_column_config,
         &run_args.outputs,
         &nextclade.params,
+        &references,
       )
       .wrap_err("When creating output writer")
       .unwrap();
 
       if nextclade.params.general.include_reference {
-        output_writer
-          .write_ref(&nextclade.ref_record, &nextclade.ref_translation)
-          .wrap_err("When writing output record for ref sequence")
-          .unwrap();
+        output_writer.write_ref(&nextclade.ref_record, &nextclade.ref_translation);
       }
 
       for record in result_receiver {
-        if should_write_tree {
-          // Save analysis results if they will be needed later
-          if let Ok(AnalysisOutput { analysis_result, .. }) = &record.outputs_or_err {
-            outputs.push(analysis_result.clone());
+        *num_sequences += 1;
+
+        if let (Some(qc_batch_counter), Some(batch)) = (
+          qc_batch_counter.as_mut(),
+          batch_map.as_ref().and_then(|batch_map| batch_map.get(&record.seq_name)),
+        ) {
+          match &record.outputs_or_err {
+            Ok(AnalysisOutput { analysis_result, .. }) => qc_batch_counter.add(batch, analysis_result),
+            Err(_) => qc_batch_counter.add_failed(batch),
           }
         }
 
-        output_writer
-          .write_record(record)
-          .wrap_err("When writing output record")
-          .unwrap();
+        if let Some(html_report_builder) = html_report_builder.as_mut() {
+          match &record.outputs_or_err {
+            Ok(AnalysisOutput { analysis_result, .. }) => html_report_builder.add(analysis_result),
+            Err(report) => html_report_builder.add_failed(record.index, &record.seq_name, &report_to_string(report)),
+          }
+        }
+
+        // Outputs below are in coordinates of the main reference, so only the sequences analyzed against it are added
+        if record.ref_index != 0 {
+          output_writer.write_record(record);
+          continue;
+        }
+
+        if let (Some(haplotype_grouper), Ok(AnalysisOutput { analysis_result, .. })) =
+          (haplotype_grouper.as_mut(), &record.outputs_or_err)
+        {
+          haplotype_grouper.add(analysis_result);
+        }
+
+        if let (Some(codon_usage_counter), Ok(AnalysisOutput { query, .. })) =
+          (codon_usage_counter.as_mut(), &record.outputs_or_err)
+        {
+          codon_usage_counter.add(query);
+        }
+
+        if let (Some(position_support_counter), Ok(AnalysisOutput { analysis_result, .. })) =
+          (position_support_counter.as_mut(), &record.outputs_or_err)
+        {
+          position_support_counter.add(&analysis_result.alignment_range, &analysis_result.missing);
+        }
+
+        if let Some(vcf_collector) = vcf_collector.as_mut() {
+          match &record.outputs_or_err {
+            Ok(AnalysisOutput { analysis_result, .. }) => vcf_collector.add(analysis_result),
+            Err(_) => vcf_collector.add_failed(record.index, &record.seq_name),
+          }
+        }
+
+        // Save the parts of analysis results which will be needed later
+        if let (Some(tree_builder), Some(graph)) = (tree_builder.as_mut(), &nextclade.graph) {
+          let analysis_result = record
+            .outputs_or_err
+            .as_ref()
+            .ok()
+            .map(|output| output.analysis_result.clone());
+          tree_builder.add(graph, record.index, analysis_result);
+        }
+        if let Ok(AnalysisOutput { analysis_result, .. }) = &record.outputs_or_err {
+          if should_write_jplace {
+            jplace_queries.push(JplaceQuery::from(analysis_result));
+          }
+        }
+
+        output_writer.write_record(record);
       }
+
+      output_writer.finish()
     });
+
+    writer.join().expect("Output writer thread panicked")
   });
 
+  if let (Some(output_haplotypes), Some(haplotype_grouper)) = (output_haplotypes, haplotype_grouper) {
+    write_output_isolated(&mut output_errors, "--output-haplotypes", &output_haplotypes, || {
+      haplotypes_write(&output_haplotypes, &haplotype_grouper.finish())
+    });
+  }
+
+  if let (Some(output_codon_usage), Some(codon_usage_counter)) = (output_codon_usage, codon_usage_counter) {
+    write_output_isolated(&mut output_errors, "--output-codon-usage", &output_codon_usage, || {
+      codon_usage_write(&output_codon_usage, &codon_usage_counter.finish()?)
+    });
+  }
+
+  if let (Some(output_support), Some(position_support_counter)) = (output_support, position_support_counter) {
+    write_output_isolated(&mut output_errors, "--output-support", &output_support, || {
+      position_support_write(
+        &output_support,
+        &nextclade.ref_record.seq_name,
+        &position_support_counter.finish(),
+      )
+    });
+  }
+
+  if let (Some(output_batch_qc), Some(qc_batch_counter)) = (output_batch_qc, qc_batch_counter) {
+    write_output_isolated(&mut output_errors, "--output-batch-qc", &output_batch_qc, || {
+      qc_batch_write(&output_batch_qc, &qc_batch_counter.finish())
+    });
+  }
+
+  if let (Some(output_html), Some(html_report_builder)) = (output_html, html_report_builder) {
+    write_output_isolated(&mut output_errors, "--output-html", &output_html, || {
+      let title = format!("Nextclade report: {}", nextclade.ref_record.seq_name);
+      html_report_write(&output_html, &html_report_builder.finish(), &title)
+    });
+  }
+
+  if let (Some(output_vcf), Some(vcf_collector)) = (output_vcf, vcf_collector) {
+    match vcf_collector.finish() {
+      Err(report) => write_output_isolated(&mut output_errors, "--output-vcf", &output_vcf, || Err(report)),
+      Ok(samples) => {
+        let ref_name = &nextclade.ref_record.seq_name;
+        let ref_len = nextclade.ref_seq.len();
+        let masked_ranges = &nextclade.virus_properties.masked_ranges;
+        let template = output_vcf.to_string_lossy();
+        if template.contains("{seq_name}") {
+          for sample in &samples {
+            let filepath = PathBuf::from(template.replace("{seq_name}", &sanitize_file_name(&sample.seq_name)));
+            write_output_isolated(&mut output_errors, "--output-vcf", &filepath, || {
+              vcf_write_to_file(&filepath, slice::from_ref(sample), ref_name, ref_len, masked_ranges)
+            });
+          }
+        } else {
+          write_output_isolated(&mut output_errors, "--output-vcf", &output_vcf, || {
+            vcf_write_to_file(&output_vcf, &samples, ref_name, ref_len, masked_ranges)
+          });
+        }
+      }
+    }
+  }
+
   if should_write_tree {
     let Nextclade {
-      ref_seq, params, graph, ..
+      ref_seq,
+      ref_record,
+      graph,
+      ..
     } = nextclade;
+
+    // Placements are relative to the reference tree, rather than to the tree with the new nodes attached
+    if let (Some(output_jplace), Some(graph)) = (output_jplace, &graph) {
+      write_output_isolated(&mut output_errors, "--output-jplace", &output_jplace, || {
+        jplace_write_to_file(&output_jplace, graph, &jplace_queries, ref_seq.len())
+      });
+    }
+
+    // All of the tree outputs need the tree with the new nodes attached. If it cannot be built, each of them is
+    // reported as failed, while the remaining outputs are still written.
+    let graph = match (tree_builder, graph) {
+      (Some(tree_builder), Some(graph)) => tree_builder.finish(graph).map_or_else(
+        |report| {
+          fail_outputs_isolated(
+            &mut output_errors,
+            &[
+              ("--output-tree", output_tree.as_deref()),
+              ("--output-tree-nwk", output_tree_nwk.as_deref()),
+              ("--output-tree-nexus", output_tree_nexus.as_deref()),
+              ("--output-tree-pb", output_tree_pb.as_deref()),
+              ("--output-context-trees", output_context_trees.as_deref().map(Path::new)),
+              ("--output-graph", output_graph.as_deref()),
+            ],
+            &report,
+          );
+          None
+        },
+        Some,
+      ),
+      _ => None,
+    };
+
     if let Some(mut graph) = graph {
-      graph_attach_new_nodes_in_place(&mut graph, outputs, ref_seq.len(), &params.tree_builder)?;
+      if let Some(sample_metadata) = &sample_metadata {
+        graph_join_metadata_in_place(&mut graph, sample_metadata);
+      }
 
       if let Some(output_tree) = output_tree {
-        let tree = convert_graph_to_auspice_tree(&graph)?;
-        json_write(output_tree, &tree, JsonPretty(true))?;
+        write_output_isolated(&mut output_errors, "--output-tree", &output_tree, || {
+          let tree = convert_graph_to_auspice_tree(&graph)?;
+          json_write(&output_tree, &tree, JsonPretty(true))
+        });
       }
 
       if let Some(output_tree_nwk) = output_tree_nwk {
-        nwk_write_to_file(output_tree_nwk, &graph)?;
+        write_output_isolated(&mut output_errors, "--output-tree-nwk", &output_tree_nwk, || {
+          nwk_write_to_file(&output_tree_nwk, &graph)
+        });
+      }
+
+      if let Some(output_tree_nexus) = output_tree_nexus {
+        write_output_isolated(&mut output_errors, "--output-tree-nexus", &output_tree_nexus, || {
+          nexus_write_to_file(&output_tree_nexus, &graph)
+        });
+      }
+
+      if let Some(output_tree_pb) = output_tree_pb {
+        write_output_isolated(&mut output_errors, "--output-tree-pb", &output_tree_pb, || {
+          usher_mat_write_to_file(&output_tree_pb, &graph, &ref_seq, &ref_record.seq_name)
+        });
+      }
+
+      if let Some(output_context_trees) = output_context_trees {
+        for node in graph.iter_nodes().filter(|node| node.payload().is_new()) {
+          let seq_name = &node.payload().name;
+          let filepath = PathBuf::from(output_context_trees.replace("{seq_name}", &sanitize_file_name(seq_name)));
+          write_output_isolated(&mut output_errors, "--output-context-trees", &filepath, || {
+            let tree = extract_context_subtree(&graph, node.key(), context_tree_leaves)
+              .wrap_err_with(|| format!("When extracting context tree of sequence '{seq_name}'"))?;
+            if has_extension(&filepath, "nwk") {
+              nwk_write_to_file(&filepath, &convert_auspice_tree_to_graph(tree)?)
+            } else {
+              json_write(&filepath, &tree, JsonPretty(true))
+            }
+          });
+        }
       }
 
       if let Some(output_graph) = run_args.outputs.output_graph {
-        json_write(output_graph, &graph, JsonPretty(true))?;
+        write_output_isolated(&mut output_errors, "--output-graph", &output_graph, || {
+          json_write(&output_graph, &graph, JsonPretty(true))
+        });
       }
     }
   }
 
-  Ok(())
+  let usage_summary = UsageSummary::collect(start, num_sequences, &input_fastas, &output_paths);
+  if let Some(output_usage) = output_usage {
+    write_output_isolated(&mut output_errors, "--output-usage", &output_usage, || {
+      usage_summary.write_json(&output_usage)
+    });
+  }
+  usage_summary.print();
+
+  report_output_errors(&output_errors)
+}
+
+/// Replaces characters which are not safe to use in file names
+fn sanitize_file_name(name: &str) -> String {
+  name
+    .chars()
+    .map(|c| {
+      if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect()
 }
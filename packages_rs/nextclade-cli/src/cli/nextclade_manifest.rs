@@ -0,0 +1,469 @@
+use crate::cli::nextclade_cli::{NextcladeManifestArgs, NextcladeManifestFormat};
+use crate::cli::nextclade_dataset_get::dataset_http_get;
+use crate::dataset::dataset_download::{dataset_zip_fetch, download_datasets_index_json, zip_read_str};
+use crate::io::http_client::HttpClient;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::LevelFilter;
+use nextclade::analyze::virus_properties::VirusProperties;
+use nextclade::io::fs::{has_extension, read_file_to_string};
+use nextclade::io::json::{json_write, JsonPretty};
+use nextclade::make_error;
+use nextclade::sort::minimizer_index::{MinimizerIndexJson, MINIMIZER_INDEX_ALGO_VERSION};
+use nextclade::utils::any::AnyType;
+use nextclade::utils::datetime::date_now;
+use nextclade::utils::info::{this_package_name, this_package_version_str};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+const MANIFEST_SCHEMA_VERSION: &str = "1.0.0";
+
+/// Machine-readable description of the software and data used in a Nextclade run
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentManifest {
+  pub schema_version: String,
+  pub created: String,
+  pub nextclade: ManifestSoftware,
+  pub dataset: ManifestDataset,
+  pub minimizer_index_version: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestSoftware {
+  pub name: String,
+  pub version: String,
+}
+
+/// Dataset identity and checksums.
+///
+/// For zip archives, `sha256` is the checksum of the archive file. For dataset directories, it is the checksum of the
+/// list of files in the format of `sha256sum` utility (`<sha256>  <path>` lines, sorted by path).
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDataset {
+  pub name: Option<String>,
+  pub tag: Option<String>,
+  pub source: String,
+  pub sha256: String,
+  pub files: Vec<ManifestFile>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+  pub path: String,
+  pub sha256: String,
+}
+
+pub fn nextclade_manifest(args: &NextcladeManifestArgs) -> Result<(), Report> {
+  let NextcladeManifestArgs {
+    input_dataset,
+    dataset_name,
+    tag,
+    server,
+    input_minimizer_index_json,
+    output,
+    format,
+    proxy_config,
+  } = args;
+
+  let mut minimizer_index = input_minimizer_index_json
+    .as_ref()
+    .map(MinimizerIndexJson::from_path)
+    .transpose()?;
+
+  let dataset = if let Some(dataset_name) = dataset_name {
+    let verbose = log::max_level() > LevelFilter::Info;
+    let mut http = HttpClient::new(server, proxy_config, verbose)?;
+    if minimizer_index.is_none() {
+      minimizer_index = minimizer_index_fetch(&mut http)?;
+    }
+    let dataset = dataset_http_get(&mut http, dataset_name, tag)?;
+    let content = dataset_zip_fetch(&mut http, &dataset)?;
+    let source = format!(
+      "{}/{}",
+      server.as_str().trim_end_matches('/'),
+      dataset.file_path("dataset.zip")
+    );
+    manifest_dataset_from_zip(&mut Cursor::new(content), source)
+      .wrap_err_with(|| format!("When reading dataset '{dataset_name}'"))?
+  } else if let Some(input_dataset) = input_dataset {
+    let result = if input_dataset.is_file() && has_extension(input_dataset, "zip") {
+      let file = fs::File::open(input_dataset).wrap_err_with(|| format!("When opening file {input_dataset:#?}"))?;
+      manifest_dataset_from_zip(&mut BufReader::new(file), input_dataset.to_string_lossy().to_string())
+    } else if input_dataset.is_dir() {
+      manifest_dataset_from_dir(input_dataset)
+    } else {
+      make_error!(
+        "--input-dataset: path is invalid. \
+        Expected a directory path or a zip archive file path, but got: '{input_dataset:#?}'"
+      )
+    };
+    result.wrap_err_with(|| format!("When reading dataset from {input_dataset:#?}"))?
+  } else {
+    return make_error!("One of the arguments is required: --input-dataset or --dataset-name");
+  };
+
+  let manifest = EnvironmentManifest {
+    schema_version: MANIFEST_SCHEMA_VERSION.to_owned(),
+    created: date_now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    nextclade: ManifestSoftware {
+      name: this_package_name().to_owned(),
+      version: this_package_version_str().to_owned(),
+    },
+    dataset,
+    minimizer_index_version: minimizer_index.map(|minimizer_index| minimizer_index.version),
+  };
+
+  let output = output.clone().unwrap_or_else(|| PathBuf::from("-"));
+  let result = match format {
+    NextcladeManifestFormat::Json => json_write(&output, &manifest, JsonPretty(true)),
+    NextcladeManifestFormat::Spdx => json_write(&output, &manifest_to_spdx(&manifest), JsonPretty(true)),
+  };
+  result.wrap_err_with(|| format!("When writing environment manifest to {output:#?}"))
+}
+
+/// Fetches the reference minimizer index, compatible with this version of Nextclade, from the dataset server
+fn minimizer_index_fetch(http: &mut HttpClient) -> Result<Option<MinimizerIndexJson>, Report> {
+  let index = download_datasets_index_json(http)?;
+  index
+    .minimizer_index
+    .iter()
+    .find(|minimizer_index| MINIMIZER_INDEX_ALGO_VERSION == minimizer_index.version)
+    .map(|minimizer_index| {
+      let minimizer_index_str = http.get(&minimizer_index.path)?;
+      MinimizerIndexJson::from_str(String::from_utf8(minimizer_index_str)?)
+        .wrap_err_with(|| format!("When reading minimizer index '{}'", minimizer_index.path))
+    })
+    .transpose()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+  format!("{:x}", Sha256::digest(data))
+}
+
+/// Extracts dataset name and version tag from pathogen.json
+fn dataset_name_and_tag(pathogen_json: &impl AsRef<str>) -> Result<(Option<String>, Option<String>), Report> {
+  let virus_properties = VirusProperties::from_str(pathogen_json).wrap_err("When reading pathogen JSON")?;
+  let name = virus_properties
+    .attributes
+    .get("name")
+    .and_then(AnyType::as_str_maybe)
+    .map(ToOwned::to_owned);
+  let tag = virus_properties.version.map(|version| version.tag);
+  Ok((name, tag))
+}
+
+fn manifest_dataset_from_zip<R: Read>(reader: &mut R, source: String) -> Result<ManifestDataset, Report> {
+  let mut content = vec![];
+  reader.read_to_end(&mut content)?;
+  let sha256 = sha256_hex(&content);
+
+  let mut zip = ZipArchive::new(Cursor::new(content))?;
+
+  let mut files = vec![];
+  for i in 0..zip.len() {
+    let mut file = zip.by_index(i)?;
+    if file.is_dir() {
+      continue;
+    }
+    let mut data = vec![];
+    file.read_to_end(&mut data)?;
+    files.push(ManifestFile {
+      path: file.name().to_owned(),
+      sha256: sha256_hex(&data),
+    });
+  }
+  files.sort_by(|a, b| a.path.cmp(&b.path));
+
+  let pathogen_json = zip_read_str(&mut zip, "pathogen.json")
+    .wrap_err("Pathogen JSON must always be present in the dataset but not found.")?;
+  let (name, tag) = dataset_name_and_tag(&pathogen_json)?;
+
+  Ok(ManifestDataset {
+    name,
+    tag,
+    source,
+    sha256,
+    files,
+  })
+}
+
+fn manifest_dataset_from_dir(dataset_dir: &Path) -> Result<ManifestDataset, Report> {
+  let mut paths = vec![];
+  list_files_recursive(dataset_dir, &mut paths)?;
+
+  let mut files = paths
+    .iter()
+    .map(|path| {
+      let data = fs::read(path).wrap_err_with(|| format!("When reading file {path:#?}"))?;
+      let relative = path.strip_prefix(dataset_dir).unwrap_or(path);
+      Ok(ManifestFile {
+        path: relative.to_string_lossy().replace('\\', "/"),
+        sha256: sha256_hex(&data),
+      })
+    })
+    .collect::<Result<Vec<_>, Report>>()?;
+  files.sort_by(|a, b| a.path.cmp(&b.path));
+
+  let checksums = files
+    .iter()
+    .map(|file| format!("{}  {}\n", file.sha256, file.path))
+    .join("");
+  let sha256 = sha256_hex(checksums.as_bytes());
+
+  let pathogen_json = read_file_to_string(dataset_dir.join("pathogen.json"))
+    .wrap_err("Pathogen JSON must always be present in the dataset but not found.")?;
+  let (name, tag) = dataset_name_and_tag(&pathogen_json)?;
+
+  Ok(ManifestDataset {
+    name,
+    tag,
+    source: dataset_dir.to_string_lossy().to_string(),
+    sha256,
+    files,
+  })
+}
+
+fn list_files_recursive(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Report> {
+  for entry in fs::read_dir(dir).wrap_err_with(|| format!("When listing directory {dir:#?}"))? {
+    let path = entry?.path();
+    if path.is_dir() {
+      list_files_recursive(&path, paths)?;
+    } else {
+      paths.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// Converts manifest to a SPDX 2.3 document in JSON format.
+///
+/// Nextclade, the dataset and the minimizer index are described as packages, and the dependencies between them as
+/// relationships. See: https://spdx.github.io/spdx-spec/v2.3/
+fn manifest_to_spdx(manifest: &EnvironmentManifest) -> serde_json::Value {
+  let EnvironmentManifest {
+    created,
+    nextclade,
+    dataset,
+    minimizer_index_version,
+    ..
+  } = manifest;
+
+  let noassertion = || "NOASSERTION".to_owned();
+  let minimizer_index_version = minimizer_index_version.clone().unwrap_or_else(noassertion);
+  let dataset_name = dataset.name.clone().unwrap_or_else(noassertion);
+  let dataset_tag = dataset.tag.clone().unwrap_or_else(noassertion);
+  let dataset_location = if dataset.source.starts_with("http") {
+    dataset.source.clone()
+  } else {
+    noassertion()
+  };
+
+  json!({
+    "spdxVersion": "SPDX-2.3",
+    "dataLicense": "CC0-1.0",
+    "SPDXID": "SPDXRef-DOCUMENT",
+    "name": format!("{}-{}-{}-{}", nextclade.name, nextclade.version, dataset_name, dataset_tag),
+    "documentNamespace": format!("https://clades.nextstrain.org/spdx/{}/{}", nextclade.version, dataset.sha256),
+    "creationInfo": {
+      "created": created,
+      "creators": [format!("Tool: {}-{}", nextclade.name, nextclade.version)],
+    },
+    "packages": [
+      {
+        "SPDXID": "SPDXRef-Package-nextclade",
+        "name": nextclade.name,
+        "versionInfo": nextclade.version,
+        "downloadLocation": "https://github.com/nextstrain/nextclade",
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": "MIT",
+        "copyrightText": "NOASSERTION",
+      },
+      {
+        "SPDXID": "SPDXRef-Package-dataset",
+        "name": dataset_name,
+        "versionInfo": dataset_tag,
+        "downloadLocation": dataset_location,
+        "filesAnalyzed": false,
+        "checksums": [{ "algorithm": "SHA256", "checksumValue": dataset.sha256 }],
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": "NOASSERTION",
+        "copyrightText": "NOASSERTION",
+        "comment": dataset.files.iter().map(|file| format!("{}  {}", file.sha256, file.path)).join("\n"),
+      },
+      {
+        "SPDXID": "SPDXRef-Package-minimizer-index",
+        "name": "nextclade-minimizer-index",
+        "versionInfo": minimizer_index_version,
+        "downloadLocation": "NOASSERTION",
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": "NOASSERTION",
+        "copyrightText": "NOASSERTION",
+      },
+    ],
+    "relationships": [
+      {
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": "SPDXRef-Package-nextclade",
+      },
+      {
+        "spdxElementId": "SPDXRef-Package-nextclade",
+        "relationshipType": "DEPENDS_ON",
+        "relatedSpdxElement": "SPDXRef-Package-dataset",
+      },
+      {
+        "spdxElementId": "SPDXRef-Package-nextclade",
+        "relationshipType": "DEPENDS_ON",
+        "relatedSpdxElement": "SPDXRef-Package-minimizer-index",
+      },
+    ],
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use std::io::Write;
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  const PATHOGEN_JSON: &str = r#"{
+    "schemaVersion": "3.0.0",
+    "attributes": { "name": "Test virus" },
+    "files": { "reference": "reference.fasta", "pathogenJson": "pathogen.json" },
+    "version": { "tag": "2024-01-01--00-00-00Z" }
+  }"#;
+
+  const DATASET_FILES: &[(&str, &str)] = &[
+    ("reference.fasta", ">ref\nACGT\n"),
+    ("pathogen.json", PATHOGEN_JSON),
+    ("sub/tree.json", "{}"),
+  ];
+
+  fn expected_files() -> Vec<ManifestFile> {
+    DATASET_FILES
+      .iter()
+      .sorted_by_key(|(path, _)| *path)
+      .map(|(path, content)| ManifestFile {
+        path: (*path).to_owned(),
+        sha256: sha256_hex(content.as_bytes()),
+      })
+      .collect_vec()
+  }
+
+  #[test]
+  fn computes_sha256_hex() {
+    assert_eq!(
+      sha256_hex(b"abc"),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+  }
+
+  #[test]
+  fn reads_dataset_name_and_tag() -> Result<(), Report> {
+    let (name, tag) = dataset_name_and_tag(&PATHOGEN_JSON)?;
+    assert_eq!(name.as_deref(), Some("Test virus"));
+    assert_eq!(tag.as_deref(), Some("2024-01-01--00-00-00Z"));
+    Ok(())
+  }
+
+  #[test]
+  fn creates_manifest_from_zip() -> Result<(), Report> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (path, content) in DATASET_FILES {
+      zip.start_file(*path, FileOptions::default())?;
+      zip.write_all(content.as_bytes())?;
+    }
+    let content = zip.finish()?.into_inner();
+
+    let dataset = manifest_dataset_from_zip(&mut Cursor::new(&content), "dataset.zip".to_owned())?;
+
+    assert_eq!(dataset.name.as_deref(), Some("Test virus"));
+    assert_eq!(dataset.tag.as_deref(), Some("2024-01-01--00-00-00Z"));
+    assert_eq!(dataset.source, "dataset.zip");
+    assert_eq!(dataset.sha256, sha256_hex(&content));
+    assert_eq!(
+      dataset
+        .files
+        .iter()
+        .map(|file| (&file.path, &file.sha256))
+        .collect_vec(),
+      expected_files()
+        .iter()
+        .map(|file| (&file.path, &file.sha256))
+        .collect_vec()
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn creates_manifest_from_dir() -> Result<(), Report> {
+    let dataset_dir = std::env::temp_dir().join(format!("nextclade-manifest-test-{}", std::process::id()));
+    for (path, content) in DATASET_FILES {
+      let path = dataset_dir.join(path);
+      fs::create_dir_all(path.parent().unwrap())?;
+      fs::write(path, content)?;
+    }
+
+    let dataset = manifest_dataset_from_dir(&dataset_dir);
+    fs::remove_dir_all(&dataset_dir)?;
+    let dataset = dataset?;
+
+    let files = expected_files();
+    let checksums = files
+      .iter()
+      .map(|file| format!("{}  {}\n", file.sha256, file.path))
+      .join("");
+
+    assert_eq!(dataset.name.as_deref(), Some("Test virus"));
+    assert_eq!(dataset.sha256, sha256_hex(checksums.as_bytes()));
+    assert_eq!(
+      dataset
+        .files
+        .iter()
+        .map(|file| (&file.path, &file.sha256))
+        .collect_vec(),
+      files.iter().map(|file| (&file.path, &file.sha256)).collect_vec()
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn converts_manifest_to_spdx() {
+    let manifest = EnvironmentManifest {
+      schema_version: MANIFEST_SCHEMA_VERSION.to_owned(),
+      created: "2024-01-01T00:00:00Z".to_owned(),
+      nextclade: ManifestSoftware {
+        name: "nextclade".to_owned(),
+        version: "1.2.3".to_owned(),
+      },
+      dataset: ManifestDataset {
+        name: Some("Test virus".to_owned()),
+        tag: None,
+        source: "dataset.zip".to_owned(),
+        sha256: "abc".to_owned(),
+        files: vec![],
+      },
+      minimizer_index_version: Some("4".to_owned()),
+    };
+
+    let spdx = manifest_to_spdx(&manifest);
+
+    assert_eq!(spdx["name"], "nextclade-1.2.3-Test virus-NOASSERTION");
+    assert_eq!(spdx["packages"][1]["downloadLocation"], "NOASSERTION");
+    assert_eq!(spdx["packages"][1]["checksums"][0]["checksumValue"], "abc");
+    assert_eq!(spdx["packages"][2]["versionInfo"], "4");
+  }
+}
@@ -1,14 +1,21 @@
 use crate::cli::nextclade_cli::NextcladeRunOutputArgs;
 use crate::cli::nextclade_loop::NextcladeRecord;
-use eyre::{Report, WrapErr};
+use crate::io::output_sink::{OutputError, OutputSink};
+use eyre::Report;
 use itertools::Itertools;
 use log::{info, warn};
-use nextclade::alphabet::nuc::from_nuc_seq;
+use nextclade::align::alignment_band::AlignmentBandFileWriter;
+use nextclade::alphabet::nuc::{from_nuc_seq, to_nuc_seq, Nuc};
 use nextclade::analyze::virus_properties::PhenotypeAttrDesc;
 use nextclade::gene::gene_map::GeneMap;
-use nextclade::io::fasta::{FastaPeptideWriter, FastaRecord, FastaWriter};
+use nextclade::io::fasta::{
+  FastaCodonAlignmentWriter, FastaNucCdsWriter, FastaPeptideWriter, FastaRecord, FastaWriter,
+};
+use nextclade::io::maf::MafFileWriter;
 use nextclade::io::ndjson::NdjsonFileWriter;
+use nextclade::io::nextclade_arrow::NextcladeResultsArrowFileWriter;
 use nextclade::io::nextclade_csv::{CsvColumnConfig, NextcladeResultsCsvFileWriter};
+use nextclade::io::nextclade_parquet::NextcladeResultsParquetFileWriter;
 use nextclade::io::results_json::ResultsJsonWriter;
 use nextclade::run::nextclade_wasm::AnalysisOutput;
 use nextclade::run::params::NextcladeInputParams;
@@ -21,16 +28,26 @@ use std::collections::HashMap;
 use std::hash::Hasher;
 
 /// Writes output files, potentially preserving the initial order of records (same as in the inputs)
+///
+/// Each output is written through its own sink: a failure to write one of the outputs does not prevent writing of the
+/// others. Failures are collected and returned from `finish()`.
 pub struct NextcladeOrderedWriter {
-  fasta_writer: Option<FastaWriter>,
-  fasta_peptide_writer: Option<FastaPeptideWriter>,
-  output_json_writer: Option<ResultsJsonWriter>,
-  output_ndjson_writer: Option<NdjsonFileWriter>,
-  output_csv_writer: Option<NextcladeResultsCsvFileWriter>,
-  output_tsv_writer: Option<NextcladeResultsCsvFileWriter>,
+  fasta_writer: Option<OutputSink<FastaWriter>>,
+  fasta_peptide_writer: Option<OutputSink<FastaPeptideWriter>>,
+  fasta_nuc_cds_writer: Option<OutputSink<FastaNucCdsWriter>>,
+  fasta_codon_alignment_writer: Option<OutputSink<FastaCodonAlignmentWriter>>,
+  output_json_writer: Option<OutputSink<ResultsJsonWriter>>,
+  output_ndjson_writer: Option<OutputSink<NdjsonFileWriter>>,
+  output_csv_writer: Option<OutputSink<NextcladeResultsCsvFileWriter>>,
+  output_tsv_writer: Option<OutputSink<NextcladeResultsCsvFileWriter>>,
+  output_parquet_writer: Option<OutputSink<NextcladeResultsParquetFileWriter>>,
+  output_arrow_writer: Option<OutputSink<NextcladeResultsArrowFileWriter>>,
+  output_alignment_band_writer: Option<OutputSink<AlignmentBandFileWriter>>,
+  output_maf_writer: Option<OutputSink<MafFileWriter>>,
   expected_index: usize,
   queue: HashMap<usize, NextcladeRecord>,
   in_order: bool,
+  is_finished: bool,
 }
 
 impl NextcladeOrderedWriter {
@@ -42,18 +59,48 @@ impl NextcladeOrderedWriter {
     csv_column_config: &CsvColumnConfig,
     output_params: &NextcladeRunOutputArgs,
     params: &NextcladeInputParams,
+    references: &[(&str, &[Nuc])],
   ) -> Result<Self, Report> {
-    let fasta_writer = output_params.output_fasta.map_ref_fallible(FastaWriter::from_path)?;
+    let fasta_writer = output_params.output_fasta.map_ref_fallible(|output_fasta| {
+      FastaWriter::from_path(output_fasta).map(|writer| OutputSink::new("--output-fasta", output_fasta, writer))
+    })?;
 
     let fasta_peptide_writer = output_params
       .output_translations
-      .map_ref_fallible(|output_translations| FastaPeptideWriter::new(gene_map, output_translations))?;
+      .map_ref_fallible(|output_translations| {
+        FastaPeptideWriter::new(gene_map, output_translations)
+          .map(|writer| OutputSink::new("--output-translations", output_translations, writer))
+      })?;
+
+    let fasta_nuc_cds_writer = output_params
+      .output_nuc_cds_fasta
+      .map_ref_fallible(|output_nuc_cds_fasta| {
+        FastaNucCdsWriter::new(gene_map, output_nuc_cds_fasta, output_params.nuc_cds_strip_gaps)
+          .map(|writer| OutputSink::new("--output-nuc-cds-fasta", output_nuc_cds_fasta, writer))
+      })?;
+
+    let fasta_codon_alignment_writer =
+      output_params
+        .output_codon_alignment
+        .map_ref_fallible(|output_codon_alignment| {
+          FastaCodonAlignmentWriter::new(gene_map, output_codon_alignment)
+            .map(|writer| OutputSink::new("--output-codon-alignment", output_codon_alignment, writer))
+        })?;
 
     let output_json_writer = output_params.output_json.map_ref_fallible(|output_json| {
-      ResultsJsonWriter::new(output_json, clade_node_attr_key_descs, phenotype_attr_key_desc)
+      ResultsJsonWriter::new(
+        output_json,
+        clade_node_attr_key_descs,
+        phenotype_attr_key_desc,
+        &output_params.run_id,
+        &params.alignment,
+      )
+      .map(|writer| OutputSink::new("--output-json", output_json, writer))
     })?;
 
-    let output_ndjson_writer = output_params.output_ndjson.map_ref_fallible(NdjsonFileWriter::new)?;
+    let output_ndjson_writer = output_params.output_ndjson.map_ref_fallible(|output_ndjson| {
+      NdjsonFileWriter::new(output_ndjson).map(|writer| OutputSink::new("--output-ndjson", output_ndjson, writer))
+    })?;
 
     let clade_node_attr_keys = clade_node_attr_key_descs
       .iter()
@@ -65,6 +112,8 @@ impl NextcladeOrderedWriter {
       .map(|desc| desc.name.clone())
       .collect_vec();
 
+    let cds_names = gene_map.iter_cdses().map(|cds| cds.name.clone()).collect_vec();
+
     let output_csv_writer = output_params.output_csv.map_ref_fallible(|output_csv| {
       NextcladeResultsCsvFileWriter::new(
         output_csv,
@@ -72,8 +121,10 @@ impl NextcladeOrderedWriter {
         &clade_node_attr_keys,
         &phenotype_attr_keys,
         aa_motifs_keys,
+        &cds_names,
         csv_column_config,
       )
+      .map(|writer| OutputSink::new("--output-csv", output_csv, writer))
     })?;
 
     let output_tsv_writer = output_params.output_tsv.map_ref_fallible(|output_tsv| {
@@ -83,45 +134,107 @@ impl NextcladeOrderedWriter {
         &clade_node_attr_keys,
         &phenotype_attr_keys,
         aa_motifs_keys,
+        &cds_names,
+        csv_column_config,
+      )
+      .map(|writer| OutputSink::new("--output-tsv", output_tsv, writer))
+    })?;
+
+    let output_parquet_writer = output_params.output_parquet.map_ref_fallible(|output_parquet| {
+      NextcladeResultsParquetFileWriter::new(
+        output_parquet,
+        &clade_node_attr_keys,
+        &phenotype_attr_keys,
+        aa_motifs_keys,
+        &cds_names,
+        csv_column_config,
+      )
+      .map(|writer| OutputSink::new("--output-parquet", output_parquet, writer))
+    })?;
+
+    let output_arrow_writer = output_params.output_arrow.map_ref_fallible(|output_arrow| {
+      NextcladeResultsArrowFileWriter::new(
+        output_arrow,
+        &clade_node_attr_keys,
+        &phenotype_attr_keys,
+        aa_motifs_keys,
+        &cds_names,
         csv_column_config,
       )
+      .map(|writer| OutputSink::new("--output-arrow", output_arrow, writer))
+    })?;
+
+    let output_alignment_band_writer =
+      output_params
+        .output_alignment_band
+        .map_ref_fallible(|output_alignment_band| {
+          AlignmentBandFileWriter::new(output_alignment_band)
+            .map(|writer| OutputSink::new("--output-alignment-band", output_alignment_band, writer))
+        })?;
+
+    let output_maf_writer = output_params.output_maf.map_ref_fallible(|output_maf| {
+      MafFileWriter::new(output_maf, references).map(|writer| OutputSink::new("--output-maf", output_maf, writer))
     })?;
 
     Ok(Self {
       fasta_writer,
       fasta_peptide_writer,
+      fasta_nuc_cds_writer,
+      fasta_codon_alignment_writer,
       output_json_writer,
       output_ndjson_writer,
       output_csv_writer,
       output_tsv_writer,
+      output_parquet_writer,
+      output_arrow_writer,
+      output_alignment_band_writer,
+      output_maf_writer,
       expected_index: 0,
       queue: HashMap::<usize, NextcladeRecord>::new(),
       in_order: params.general.in_order,
+      is_finished: false,
     })
   }
 
-  pub fn write_ref(&mut self, ref_record: &FastaRecord, ref_translation: &Translation) -> Result<(), Report> {
+  pub fn write_ref(&mut self, ref_record: &FastaRecord, ref_translation: &Translation) {
     let FastaRecord { seq_name, seq, .. } = &ref_record;
 
     if let Some(fasta_writer) = &mut self.fasta_writer {
-      fasta_writer.write(seq_name, seq, false)?;
+      fasta_writer.write(|writer| writer.write(seq_name, seq, false));
     }
 
-    ref_translation.cdses().try_for_each(|cds_tr| {
-      if let Some(fasta_peptide_writer) = &mut self.fasta_peptide_writer {
-        fasta_peptide_writer.write(seq_name, cds_tr)?;
-      }
-      Result::<(), Report>::Ok(())
-    })?;
+    if let Some(fasta_peptide_writer) = &mut self.fasta_peptide_writer {
+      fasta_peptide_writer.write(|writer| {
+        ref_translation
+          .cdses()
+          .try_for_each(|cds_tr| writer.write(seq_name, cds_tr))
+      });
+    }
+
+    if let Some(fasta_nuc_cds_writer) = &mut self.fasta_nuc_cds_writer {
+      fasta_nuc_cds_writer.write(|writer| {
+        let seq = to_nuc_seq(seq)?;
+        ref_translation
+          .cdses()
+          .try_for_each(|cds_tr| writer.write(seq_name, &seq, &cds_tr.name))
+      });
+    }
 
-    Ok(())
+    if let Some(fasta_codon_alignment_writer) = &mut self.fasta_codon_alignment_writer {
+      fasta_codon_alignment_writer.write(|writer| {
+        ref_translation
+          .cdses()
+          .try_for_each(|cds_tr| writer.write(seq_name, cds_tr))
+      });
+    }
   }
 
   /// Writes output record into output files
-  fn write_impl(&mut self, record: NextcladeRecord) -> Result<(), Report> {
+  fn write_impl(&mut self, record: NextcladeRecord) {
     let NextcladeRecord {
       index,
       seq_name,
+      ref_index,
       outputs_or_err,
     } = record;
 
@@ -130,6 +243,7 @@ impl NextcladeOrderedWriter {
         query,
         translation,
         analysis_result,
+        alignment_band,
       }) => {
         let NextcladeOutputs {
           warnings,
@@ -141,13 +255,31 @@ impl NextcladeOrderedWriter {
         } = &analysis_result;
 
         if let Some(fasta_writer) = &mut self.fasta_writer {
-          fasta_writer.write(&seq_name, &from_nuc_seq(&query), *is_reverse_complement)?;
+          fasta_writer.write(|writer| writer.write(&seq_name, &from_nuc_seq(&query), *is_reverse_complement));
         }
 
         if let Some(fasta_peptide_writer) = &mut self.fasta_peptide_writer {
-          for cds_tr in translation.cdses() {
-            fasta_peptide_writer.write(&seq_name, cds_tr)?;
-          }
+          fasta_peptide_writer.write(|writer| {
+            translation
+              .cdses()
+              .try_for_each(|cds_tr| writer.write(&seq_name, cds_tr))
+          });
+        }
+
+        if let Some(fasta_nuc_cds_writer) = &mut self.fasta_nuc_cds_writer {
+          fasta_nuc_cds_writer.write(|writer| {
+            translation
+              .cdses()
+              .try_for_each(|cds_tr| writer.write(&seq_name, &query, &cds_tr.name))
+          });
+        }
+
+        if let Some(fasta_codon_alignment_writer) = &mut self.fasta_codon_alignment_writer {
+          fasta_codon_alignment_writer.write(|writer| {
+            translation
+              .cdses()
+              .try_for_each(|cds_tr| writer.write(&seq_name, cds_tr))
+          });
         }
 
         for warning in warnings {
@@ -155,19 +287,40 @@ impl NextcladeOrderedWriter {
         }
 
         if let Some(output_csv_writer) = &mut self.output_csv_writer {
-          output_csv_writer.write(&analysis_result)?;
+          output_csv_writer.write(|writer| writer.write(&analysis_result));
         }
 
         if let Some(output_tsv_writer) = &mut self.output_tsv_writer {
-          output_tsv_writer.write(&analysis_result)?;
+          output_tsv_writer.write(|writer| writer.write(&analysis_result));
+        }
+
+        if let Some(output_parquet_writer) = &mut self.output_parquet_writer {
+          output_parquet_writer.write(|writer| writer.write(&analysis_result));
+        }
+
+        if let Some(output_arrow_writer) = &mut self.output_arrow_writer {
+          output_arrow_writer.write(|writer| writer.write(&analysis_result));
         }
 
         if let Some(output_ndjson_writer) = &mut self.output_ndjson_writer {
-          output_ndjson_writer.write(&analysis_result)?;
+          output_ndjson_writer.write(|writer| writer.write(&analysis_result));
+        }
+
+        if let (Some(output_alignment_band_writer), Some(alignment_band)) =
+          (&mut self.output_alignment_band_writer, &alignment_band)
+        {
+          output_alignment_band_writer.write(|writer| writer.write(alignment_band));
+        }
+
+        if let Some(output_maf_writer) = &mut self.output_maf_writer {
+          output_maf_writer.write(|writer| writer.write(ref_index, &query, &analysis_result));
         }
 
         if let Some(output_json_writer) = &mut self.output_json_writer {
-          output_json_writer.write(analysis_result);
+          output_json_writer.write(|writer| {
+            writer.write(analysis_result);
+            Ok(())
+          });
         }
       }
       Err(report) => {
@@ -176,31 +329,37 @@ impl NextcladeOrderedWriter {
           "In sequence #{index} '{seq_name}': {cause}. Note that this sequence will not be included in the results."
         );
         if let Some(output_csv_writer) = &mut self.output_csv_writer {
-          output_csv_writer.write_nuc_error(index, &seq_name, &cause)?;
+          output_csv_writer.write(|writer| writer.write_nuc_error(index, &seq_name, &cause));
         }
         if let Some(output_tsv_writer) = &mut self.output_tsv_writer {
-          output_tsv_writer.write_nuc_error(index, &seq_name, &cause)?;
+          output_tsv_writer.write(|writer| writer.write_nuc_error(index, &seq_name, &cause));
+        }
+        if let Some(output_parquet_writer) = &mut self.output_parquet_writer {
+          output_parquet_writer.write(|writer| writer.write_nuc_error(index, &seq_name, &cause));
+        }
+        if let Some(output_arrow_writer) = &mut self.output_arrow_writer {
+          output_arrow_writer.write(|writer| writer.write_nuc_error(index, &seq_name, &cause));
         }
         if let Some(output_ndjson_writer) = &mut self.output_ndjson_writer {
-          output_ndjson_writer.write_nuc_error(index, &seq_name, &[cause.clone()])?;
+          output_ndjson_writer.write(|writer| writer.write_nuc_error(index, &seq_name, &[cause.clone()]));
         }
         if let Some(output_json_writer) = &mut self.output_json_writer {
-          output_json_writer.write_nuc_error(index, &seq_name, &[cause]);
+          output_json_writer.write(|writer| {
+            writer.write_nuc_error(index, &seq_name, &[cause]);
+            Ok(())
+          });
         }
       }
     }
-
-    Ok(())
   }
 
   /// In in-order mode, writes all queued records with indices subsequent to the next expected index.
   /// On out-of-order mode, does nothing - the queue is always empty.
-  fn write_queued_records(&mut self) -> Result<(), Report> {
+  fn write_queued_records(&mut self) {
     while let Some(record) = self.queue.remove(&self.expected_index) {
-      self.write_impl(record)?;
+      self.write_impl(record);
       self.expected_index += 1;
     }
-    Ok(())
   }
 
   /// Writes a record.
@@ -211,15 +370,15 @@ impl NextcladeOrderedWriter {
   ///
   /// In out-of-order mode, records are written as they come from worker threads. In this case the order in output files
   /// is not defined (due to differences in processing times between items, and thread scheduling between runs)
-  pub fn write_record(&mut self, record: NextcladeRecord) -> Result<(), Report> {
+  pub fn write_record(&mut self, record: NextcladeRecord) {
     if !self.in_order {
       // Out-of-order mode: write immediately
-      self.write_impl(record)?;
+      self.write_impl(record);
     } else {
       // In-order mode: check if the record has next expected index
       if record.index == self.expected_index {
         // If the record has next expected index, write it immediately
-        self.write_impl(record)?;
+        self.write_impl(record);
         self.expected_index += 1;
       } else {
         // If the record has an unexpected index, queue it to write later
@@ -227,23 +386,70 @@ impl NextcladeOrderedWriter {
       }
 
       // Periodically try to write the queued records
-      self.write_queued_records()?;
+      self.write_queued_records();
     }
-    Ok(())
   }
 
-  /// Finalizes output by writing all queued records
-  pub fn finish(&mut self) -> Result<(), Report> {
-    self.write_queued_records()?;
-    if let Some(output_json_writer) = &mut self.output_json_writer {
-      output_json_writer.finish()?;
+  /// Finalizes output by writing all queued records. Returns errors of all outputs which failed to be written.
+  pub fn finish(&mut self) -> Vec<OutputError> {
+    if self.is_finished {
+      return vec![];
     }
-    Ok(())
+    self.is_finished = true;
+
+    self.write_queued_records();
+
+    [
+      self.fasta_writer.as_mut().and_then(|sink| sink.finish(|_| Ok(()))),
+      self
+        .fasta_peptide_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|_| Ok(()))),
+      self
+        .fasta_nuc_cds_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|_| Ok(()))),
+      self
+        .fasta_codon_alignment_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|_| Ok(()))),
+      self
+        .output_json_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|writer| writer.finish())),
+      self
+        .output_ndjson_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|_| Ok(()))),
+      self.output_csv_writer.as_mut().and_then(|sink| sink.finish(|_| Ok(()))),
+      self.output_tsv_writer.as_mut().and_then(|sink| sink.finish(|_| Ok(()))),
+      self
+        .output_parquet_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(NextcladeResultsParquetFileWriter::finish)),
+      self
+        .output_arrow_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(NextcladeResultsArrowFileWriter::finish)),
+      self
+        .output_alignment_band_writer
+        .as_mut()
+        .and_then(|sink| sink.finish(|_| Ok(()))),
+      self.output_maf_writer.as_mut().and_then(|sink| sink.finish(|_| Ok(()))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect_vec()
   }
 }
 
 impl Drop for NextcladeOrderedWriter {
   fn drop(&mut self) {
-    self.finish().wrap_err("When finalizing output writer").unwrap();
+    for OutputError { name, filepath, report } in self.finish() {
+      warn!(
+        "Failed to write output {name} to {filepath:#?}: {}",
+        report_to_string(&report)
+      );
+    }
   }
 }
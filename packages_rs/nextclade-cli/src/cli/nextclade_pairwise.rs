@@ -0,0 +1,110 @@
+use crate::cli::nextclade_cli::{NextcladePairwiseArgs, NextcladeRunInputArgs};
+use crate::dataset::dataset_download::dataset_individual_files_load;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::{info, warn};
+use nextclade::io::fasta::{FastaPeptideWriter, FastaReader, FastaRecord};
+use nextclade::io::fs::has_extension;
+use nextclade::io::nextclade_csv::{CsvColumnConfig, CsvColumnsCompat, NextcladeResultsCsvFileWriter};
+use nextclade::make_error;
+use nextclade::run::nextclade_wasm::{AnalysisOutput, Nextclade};
+use nextclade::utils::error::report_to_string;
+use nextclade::utils::option::OptionMapRefFallible;
+
+/// Columns of the pairwise comparison output, in addition to the mandatory index and sequence name
+const PAIRWISE_COLUMNS: &[&str] = &[
+  "alignmentScore",
+  "alignmentStart",
+  "alignmentEnd",
+  "coverage",
+  "totalSubstitutions",
+  "totalDeletions",
+  "totalInsertions",
+  "substitutions",
+  "deletions",
+  "insertions",
+  "frameShifts",
+  "totalAminoacidSubstitutions",
+  "totalAminoacidDeletions",
+  "totalAminoacidInsertions",
+  "aaSubstitutions",
+  "aaDeletions",
+  "aaInsertions",
+  "errors",
+];
+
+pub fn nextclade_pairwise(args: &NextcladePairwiseArgs) -> Result<(), Report> {
+  info!("Command-line arguments:\n{args:#?}");
+
+  let NextcladePairwiseArgs {
+    reference,
+    query,
+    annotation,
+    output,
+    output_translations,
+    params,
+  } = args;
+
+  if output_translations.is_some() && annotation.is_none() {
+    return make_error!(
+      "Argument `--output-translations` requires genome annotation. Provide it using `--annotation`."
+    );
+  }
+
+  // The pairwise comparison is the same as a run with an ad-hoc dataset, consisting only of the reference sequence and
+  // of the (optional) genome annotation
+  let inputs = NextcladeRunInputArgs {
+    input_ref: Some(reference.clone()),
+    input_annotation: annotation.clone(),
+    ..NextcladeRunInputArgs::default()
+  };
+  let nextclade_inputs = dataset_individual_files_load(&inputs, &None)?;
+  let nextclade = Nextclade::new(nextclade_inputs, params)?;
+
+  let column_config = CsvColumnConfig::new(
+    &PAIRWISE_COLUMNS.iter().map(|&column| column.to_owned()).collect_vec(),
+    CsvColumnsCompat::default(),
+  )?;
+  let delimiter = if has_extension(output, "csv") { b';' } else { b'\t' };
+  let mut csv_writer = NextcladeResultsCsvFileWriter::new(output, delimiter, &[], &[], &[], &[], &column_config)?;
+
+  let mut peptide_writer = output_translations.map_ref_fallible(|output_translations| {
+    if !output_translations.contains("{cds}") {
+      return make_error!(
+        "Expected `--output-translations` argument to contain a template string containing template variable {{cds}} (with curly braces), but received: {output_translations}"
+      );
+    }
+    FastaPeptideWriter::new(&nextclade.gene_map, output_translations)
+  })?;
+
+  let mut reader = FastaReader::from_paths(&[query])?;
+  loop {
+    let mut record = FastaRecord::default();
+    reader.read(&mut record)?;
+    if record.is_empty() {
+      break;
+    }
+
+    match nextclade.run(&record) {
+      Ok(AnalysisOutput {
+        translation,
+        analysis_result,
+        ..
+      }) => {
+        csv_writer.write(&analysis_result)?;
+        if let Some(peptide_writer) = &mut peptide_writer {
+          translation
+            .cdses()
+            .try_for_each(|cds_tr| peptide_writer.write(&record.seq_name, cds_tr))?;
+        }
+      }
+      Err(report) => {
+        let cause = report_to_string(&report);
+        warn!("In sequence #{} '{}': {cause}", record.index, record.seq_name);
+        csv_writer.write_nuc_error(record.index, &record.seq_name, &cause)?;
+      }
+    }
+  }
+
+  Ok(())
+}
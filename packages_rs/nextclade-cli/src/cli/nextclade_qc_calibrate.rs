@@ -0,0 +1,99 @@
+use crate::cli::nextclade_cli::NextcladeQcCalibrateArgs;
+use eyre::{Report, WrapErr};
+use log::info;
+use nextclade::io::csv::read_csv_file_with_delimiter;
+use nextclade::io::fs::has_extension;
+use nextclade::io::json::{json_write, JsonPretty};
+use nextclade::io::results_json::results_read;
+use nextclade::make_error;
+use nextclade::qc::qc_calibrate::{qc_calibrate, QcCalibrationPrivateMutations, QcCalibrationSample};
+use nextclade::qc::qc_config::QcConfig;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub fn nextclade_qc_calibrate(args: &NextcladeQcCalibrateArgs) -> Result<(), Report> {
+  let NextcladeQcCalibrateArgs {
+    input_results,
+    input_qc_config,
+    percentile,
+    output,
+  } = args;
+
+  let base = match input_qc_config {
+    Some(input_qc_config) => QcConfig::from_path(input_qc_config)?,
+    None => QcConfig::default(),
+  };
+
+  let mut samples = vec![];
+  for filepath in input_results {
+    let file_samples = read_samples(filepath).wrap_err_with(|| format!("When reading results file {filepath:#?}"))?;
+    info!("Read {} results from {filepath:#?}", file_samples.len());
+    samples.extend(file_samples);
+  }
+
+  let config = qc_calibrate(&samples, &base, *percentile)?;
+
+  json_write(output, &config, JsonPretty(true)).wrap_err_with(|| format!("When writing QC config to {output:#?}"))
+}
+
+fn read_samples(filepath: &Path) -> Result<Vec<QcCalibrationSample>, Report> {
+  if has_extension(filepath, "csv") {
+    read_samples_from_table(filepath, b';')
+  } else if has_extension(filepath, "tsv") {
+    read_samples_from_table(filepath, b'\t')
+  } else {
+    let (results, _) = results_read(filepath)?;
+    Ok(results.iter().map(QcCalibrationSample::from_outputs).collect())
+  }
+}
+
+/// Reads samples from results in CSV or TSV format. Private deletions are not available in these formats.
+fn read_samples_from_table(filepath: &Path, delimiter: u8) -> Result<Vec<QcCalibrationSample>, Report> {
+  let rows: Vec<BTreeMap<String, String>> = read_csv_file_with_delimiter(filepath, delimiter)?;
+
+  rows
+    .iter()
+    .filter(|row| row.get("errors").map_or(true, String::is_empty))
+    .map(|row| {
+      let get = |column: &str| -> Result<Option<usize>, Report> {
+        match row.get(column).map(String::as_str) {
+          None | Some("") => Ok(None),
+          Some(value) => value
+            .parse()
+            .map(Some)
+            .wrap_err_with(|| format!("When parsing value '{value}' of column '{column}'")),
+        }
+      };
+
+      let (Some(total_missing), Some(total_mixed_sites)) = (get("totalMissing")?, get("totalNonACGTNs")?) else {
+        return make_error!("Columns 'totalMissing' and 'totalNonACGTNs' are required, but not found in {filepath:#?}");
+      };
+
+      // Without reference tree there is no clade and no private mutations
+      let has_tree = row.get("clade").map_or(false, |clade| !clade.is_empty());
+      let private_mutations = match (
+        get("privateNucMutations.totalReversionSubstitutions")?,
+        get("privateNucMutations.totalLabeledSubstitutions")?,
+        get("privateNucMutations.totalUnlabeledSubstitutions")?,
+      ) {
+        (Some(num_reversion_substitutions), Some(num_labeled_substitutions), Some(num_unlabeled_substitutions))
+          if has_tree =>
+        {
+          Some(QcCalibrationPrivateMutations {
+            num_reversion_substitutions,
+            num_labeled_substitutions,
+            num_unlabeled_substitutions,
+            total_deletion_ranges: 0,
+          })
+        }
+        _ => None,
+      };
+
+      Ok(QcCalibrationSample {
+        total_missing,
+        total_mixed_sites,
+        private_mutations,
+      })
+    })
+    .collect()
+}
@@ -1,11 +1,14 @@
 use crate::cli::nextclade_cli::NextcladeReadAnnotationArgs;
-use eyre::Report;
+use eyre::{Report, WrapErr};
+use log::info;
 use nextclade::features::feature_tree::FeatureTree;
 use nextclade::gene::gene_map::GeneMap;
 use nextclade::gene::gene_map_display::gene_map_to_table_string;
-use nextclade::io::file::open_file_or_stdin;
+use nextclade::gene::gene_map_repair::{gene_map_repair_suggestions, gff3_apply_repairs};
+use nextclade::io::file::{create_file_or_stdout, open_file_or_stdin};
 use nextclade::io::json::{json_or_yaml_write, json_stringify, JsonPretty};
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::Path;
 
 pub fn nextclade_read_annotation(args: &NextcladeReadAnnotationArgs) -> Result<(), Report> {
   let content = {
@@ -14,7 +17,9 @@ pub fn nextclade_read_annotation(args: &NextcladeReadAnnotationArgs) -> Result<(
     content
   };
 
-  if args.feature_tree {
+  if let Some(fix_annotation_into) = &args.fix_annotation_into {
+    handle_fix_annotation(args, &content, fix_annotation_into)
+  } else if args.feature_tree {
     handle_feature_tree(args, &content)
   } else {
     handle_genome_annotation(args, &content)
@@ -52,3 +57,32 @@ fn handle_feature_tree(args: &NextcladeReadAnnotationArgs, content: &str) -> Res
 
   Ok(())
 }
+
+fn handle_fix_annotation(args: &NextcladeReadAnnotationArgs, content: &str, output_gff: &Path) -> Result<(), Report> {
+  let feature_tree = FeatureTree::from_gff3_str(content)?;
+  let suggestions = gene_map_repair_suggestions(&feature_tree);
+
+  if args.json {
+    println!("{}\n", json_stringify(&suggestions, JsonPretty(true))?);
+  } else if suggestions.is_empty() {
+    println!("No repairs are suggested for this genome annotation");
+  } else {
+    println!("Suggested repairs:");
+    for suggestion in &suggestions {
+      println!("  - {suggestion}");
+    }
+  }
+
+  let repaired = gff3_apply_repairs(content, &suggestions);
+  create_file_or_stdout(output_gff)
+    .and_then(|mut file| Ok(file.write_all(repaired.as_bytes())?))
+    .wrap_err_with(|| format!("When writing repaired genome annotation to {output_gff:#?}"))?;
+  info!("Repaired genome annotation is written to {output_gff:#?}. Please review it before use.");
+
+  if let Some(output) = &args.output {
+    let gene_map = GeneMap::from_str(&repaired).wrap_err("When reading repaired genome annotation")?;
+    json_or_yaml_write(output, &gene_map)?;
+  }
+
+  Ok(())
+}
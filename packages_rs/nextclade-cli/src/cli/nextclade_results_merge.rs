@@ -0,0 +1,308 @@
+use crate::cli::nextclade_cli::NextcladeResultsMergeArgs;
+use eyre::{Report, WrapErr};
+use itertools::{chain, Itertools};
+use log::{info, warn};
+use nextclade::align::params::AlignPairwiseParams;
+use nextclade::alphabet::nuc::to_nuc_seq;
+use nextclade::gene::gene_map::GeneMap;
+use nextclade::graph::graph::{convert_auspice_tree_to_graph, convert_graph_to_auspice_tree};
+use nextclade::io::fasta::read_one_fasta;
+use nextclade::io::json::{json_write, JsonPretty};
+use nextclade::io::ndjson::NdjsonFileWriter;
+use nextclade::io::nextclade_csv::{CsvColumnConfig, NextcladeResultsCsvFileWriter};
+use nextclade::io::nwk_writer::nwk_write_to_file;
+use nextclade::io::results_json::{results_read_with_header, ResultsFileContents, ResultsJson, ResultsJsonHeader};
+use nextclade::make_error;
+use nextclade::translate::translate_genes::Translation;
+use nextclade::translate::translate_genes_ref::translate_genes_ref;
+use nextclade::tree::params::TreeBuilderParams;
+use nextclade::tree::tree::AuspiceTree;
+use nextclade::tree::tree_builder::graph_attach_new_nodes_in_place;
+use nextclade::tree::tree_preprocess::graph_preprocess_in_place;
+use nextclade::types::outputs::{
+  combine_outputs_and_errors_sorted, NextcladeErrorOutputs, NextcladeOutputOrError, NextcladeOutputs,
+};
+use std::path::{Path, PathBuf};
+
+pub fn nextclade_results_merge(args: &NextcladeResultsMergeArgs) -> Result<(), Report> {
+  let NextcladeResultsMergeArgs {
+    input_results,
+    allow_duplicates,
+    input_tree,
+    input_ref,
+    input_annotation,
+    output_json,
+    output_ndjson,
+    output_csv,
+    output_tsv,
+    output_tree,
+    output_tree_nwk,
+    tree_builder,
+  } = args;
+
+  if [
+    output_json,
+    output_ndjson,
+    output_csv,
+    output_tsv,
+    output_tree,
+    output_tree_nwk,
+  ]
+  .iter()
+  .all(|output| output.is_none())
+  {
+    return make_error!("No outputs requested. Please provide at least one of the `--output-*` arguments.");
+  }
+
+  let should_write_tree = output_tree.is_some() || output_tree_nwk.is_some();
+  if should_write_tree && (input_tree.is_none() || input_ref.is_none()) {
+    return make_error!("Arguments `--output-tree` and `--output-tree-nwk` require `--input-tree` and `--input-ref`.");
+  }
+
+  let ResultsFileContents {
+    header,
+    results,
+    errors,
+  } = merge_results_files(input_results)?;
+
+  check_duplicates(&results, &errors, *allow_duplicates)?;
+
+  info!(
+    "Merged {} results and {} errors from {} files",
+    results.len(),
+    errors.len(),
+    input_results.len()
+  );
+
+  let clade_node_attr_keys = header
+    .as_ref()
+    .map(|header| header.clade_node_attr_keys.clone())
+    .unwrap_or_default();
+  let phenotype_attr_keys = header
+    .as_ref()
+    .map(|header| header.phenotype_attr_keys.clone())
+    .unwrap_or_default();
+
+  if let Some(output_json) = output_json {
+    let mut results_json = ResultsJson::new(&clade_node_attr_keys, &phenotype_attr_keys);
+    results_json.results = results.clone();
+    results_json.errors = errors.clone();
+    json_write(output_json, &results_json, JsonPretty(true))
+      .wrap_err_with(|| format!("When writing merged results to {output_json:#?}"))?;
+  }
+
+  if let Some(output_ndjson) = output_ndjson {
+    let mut writer = NdjsonFileWriter::new(output_ndjson)?;
+    for (_, output_or_error) in combine_outputs_and_errors_sorted(&results, &errors) {
+      match output_or_error {
+        NextcladeOutputOrError::Outputs(output) => writer.write(&output),
+        NextcladeOutputOrError::Error(error) => writer.write_nuc_error(error.index, &error.seq_name, &error.errors),
+      }
+      .wrap_err_with(|| format!("When writing merged results to {output_ndjson:#?}"))?;
+    }
+  }
+
+  for (output_table, delimiter) in [(output_csv, b';'), (output_tsv, b'\t')] {
+    if let Some(output_table) = output_table {
+      write_table(output_table, delimiter, &results, &errors)
+        .wrap_err_with(|| format!("When writing merged results to {output_table:#?}"))?;
+    }
+  }
+
+  if let (true, Some(input_tree), Some(input_ref)) = (should_write_tree, input_tree, input_ref) {
+    let mut params = TreeBuilderParams::default();
+    if let Some(tree_builder) = tree_builder {
+      params.merge_opt(tree_builder.clone());
+    }
+
+    let ref_record = read_one_fasta(input_ref).wrap_err("When reading reference sequence")?;
+    let ref_seq = to_nuc_seq(&ref_record.seq).wrap_err("When converting reference sequence")?;
+
+    let ref_translation = match input_annotation {
+      Some(input_annotation) => {
+        let gene_map = GeneMap::from_path(input_annotation).wrap_err("When reading genome annotation")?;
+        translate_genes_ref(&ref_seq, &gene_map, &AlignPairwiseParams::default())
+          .wrap_err("When translating reference sequence")?
+      }
+      None => Translation::default(),
+    };
+
+    let tree = AuspiceTree::from_path(input_tree)?;
+    let mut graph = convert_auspice_tree_to_graph(tree).wrap_err("When converting Auspice tree to Nextclade graph")?;
+    graph_preprocess_in_place(&mut graph, &ref_seq, &ref_translation).wrap_err("When preprocessing Nextclade graph")?;
+    graph_attach_new_nodes_in_place(&mut graph, results, ref_seq.len(), &params)
+      .wrap_err("When placing merged sequences on the reference tree")?;
+
+    if let Some(output_tree) = output_tree {
+      let tree = convert_graph_to_auspice_tree(&graph)?;
+      json_write(output_tree, &tree, JsonPretty(true))?;
+    }
+
+    if let Some(output_tree_nwk) = output_tree_nwk {
+      nwk_write_to_file(output_tree_nwk, &graph)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Reads results files and concatenates their entries, in the order of files and of the entries within each file.
+/// Entries are renumbered, such that the indices are consecutive across the merged results.
+fn merge_results_files(input_results: &[PathBuf]) -> Result<ResultsFileContents, Report> {
+  let mut merged_header: Option<(&Path, ResultsJsonHeader)> = None;
+  let mut merged_results = vec![];
+  let mut merged_errors = vec![];
+  let mut index = 0;
+
+  for filepath in input_results {
+    let ResultsFileContents {
+      header,
+      results,
+      errors,
+    } = results_read_with_header(filepath).wrap_err_with(|| format!("When reading results file {filepath:#?}"))?;
+
+    if let Some(header) = header {
+      match &merged_header {
+        None => merged_header = Some((filepath.as_path(), header)),
+        Some((first_filepath, first_header)) => check_compatibility(first_filepath, first_header, filepath, &header)?,
+      }
+    }
+
+    for (_, output_or_error) in combine_outputs_and_errors_sorted(&results, &errors) {
+      match output_or_error {
+        NextcladeOutputOrError::Outputs(mut output) => {
+          output.index = index;
+          merged_results.push(*output);
+        }
+        NextcladeOutputOrError::Error(mut error) => {
+          error.index = index;
+          merged_errors.push(error);
+        }
+      }
+      index += 1;
+    }
+  }
+
+  Ok(ResultsFileContents {
+    header: merged_header.map(|(_, header)| header),
+    results: merged_results,
+    errors: merged_errors,
+  })
+}
+
+/// Verifies that two results files are produced with the same dataset, as far as it can be deduced from the files
+fn check_compatibility(
+  first_filepath: &Path,
+  first_header: &ResultsJsonHeader,
+  filepath: &Path,
+  header: &ResultsJsonHeader,
+) -> Result<(), Report> {
+  if first_header.nextclade_algo_version != header.nextclade_algo_version {
+    warn!(
+      "Results files {first_filepath:#?} and {filepath:#?} are produced by different versions of Nextclade: '{}' and '{}'. The results might not be comparable.",
+      first_header.nextclade_algo_version, header.nextclade_algo_version
+    );
+  }
+
+  let first_keys = chain(
+    first_header.clade_node_attr_keys.iter().map(|desc| &desc.name),
+    first_header.phenotype_attr_keys.iter().map(|desc| &desc.name),
+  )
+  .collect_vec();
+  let keys = chain(
+    header.clade_node_attr_keys.iter().map(|desc| &desc.name),
+    header.phenotype_attr_keys.iter().map(|desc| &desc.name),
+  )
+  .collect_vec();
+
+  if first_keys != keys {
+    return make_error!(
+      "Results files {first_filepath:#?} and {filepath:#?} appear to be produced with different datasets: they contain different custom clade and phenotype columns: '{}' and '{}'. Only results produced with the same dataset can be merged.",
+      first_keys.iter().join(", "),
+      keys.iter().join(", ")
+    );
+  }
+
+  Ok(())
+}
+
+fn check_duplicates(
+  results: &[NextcladeOutputs],
+  errors: &[NextcladeErrorOutputs],
+  allow_duplicates: bool,
+) -> Result<(), Report> {
+  let duplicates = chain(
+    results.iter().map(|result| &result.seq_name),
+    errors.iter().map(|error| &error.seq_name),
+  )
+  .duplicates()
+  .collect_vec();
+
+  if duplicates.is_empty() {
+    return Ok(());
+  }
+
+  let names = duplicates.iter().map(|name| format!("'{name}'")).join(", ");
+  if allow_duplicates {
+    warn!("The following sequence names occur more than once in the merged results: {names}");
+    Ok(())
+  } else {
+    make_error!(
+      "The following sequence names occur more than once in the merged results: {names}. This usually means that the same sequences were analyzed in more than one run. Use `--allow-duplicates` to merge regardless."
+    )
+  }
+}
+
+fn write_table(
+  filepath: &Path,
+  delimiter: u8,
+  results: &[NextcladeOutputs],
+  errors: &[NextcladeErrorOutputs],
+) -> Result<(), Report> {
+  // Custom columns are gathered from the results themselves, because NDJSON files don't list them
+  let clade_attr_keys = results
+    .iter()
+    .flat_map(|result| result.custom_node_attributes.keys())
+    .unique()
+    .cloned()
+    .collect_vec();
+  let phenotype_attr_keys = results
+    .iter()
+    .flat_map(|result| result.phenotype_values.iter().flatten().map(|value| &value.name))
+    .unique()
+    .cloned()
+    .collect_vec();
+  let aa_motifs_keys = results
+    .iter()
+    .flat_map(|result| result.aa_motifs.keys())
+    .unique()
+    .cloned()
+    .collect_vec();
+  let cds_names = results
+    .iter()
+    .flat_map(|result| result.cds_coverage.keys())
+    .unique()
+    .cloned()
+    .collect_vec();
+
+  let mut writer = NextcladeResultsCsvFileWriter::new(
+    filepath,
+    delimiter,
+    &clade_attr_keys,
+    &phenotype_attr_keys,
+    &aa_motifs_keys,
+    &cds_names,
+    &CsvColumnConfig::default(),
+  )?;
+
+  for (_, output_or_error) in combine_outputs_and_errors_sorted(results, errors) {
+    match output_or_error {
+      NextcladeOutputOrError::Outputs(output) => writer.write(&output)?,
+      NextcladeOutputOrError::Error(error) => {
+        writer.write_nuc_error(error.index, &error.seq_name, &error.errors.join(";"))?;
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,298 @@
+use crate::cli::nextclade_cli::{NextcladeRunArgs, NextcladeRunOtherParams, NextcladeRunOutputArgs};
+use crate::cli::ordered_pipeline::process_fasta_in_order;
+use crate::dataset::dataset_download::nextclade_get_inputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::{info, warn};
+use nextclade::coord::position::PositionLike;
+use nextclade::io::csv::CsvStructFileWriter;
+use nextclade::io::fasta::FastaPeptideWriter;
+use nextclade::io::json::{json_write, JsonPretty};
+use nextclade::io::ndjson::NdjsonFileWriter;
+use nextclade::io::nextclade_csv::{format_aa_deletions, format_aa_insertions, format_aa_substitutions};
+use nextclade::make_error;
+use nextclade::run::nextclade_run_aa::{AaAnalysisOutput, NextcladeAaOutputs};
+use nextclade::run::nextclade_wasm::Nextclade;
+use nextclade::types::outputs::NextcladeErrorOutputs;
+use nextclade::utils::error::report_to_string;
+use serde::Serialize;
+
+const ARRAY_ITEM_DELIMITER: &str = ",";
+
+struct AaRecord {
+  index: usize,
+  seq_name: String,
+  outputs_or_err: Result<AaAnalysisOutput, Report>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AaResultsJson {
+  cds_name: String,
+  results: Vec<NextcladeAaOutputs>,
+  errors: Vec<NextcladeErrorOutputs>,
+}
+
+/// Row of the results table (CSV or TSV). Fields of failed sequences are empty, except for `errors`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AaResultsCsvRow<'a> {
+  index: usize,
+  seq_name: &'a str,
+  cds_name: &'a str,
+  alignment_start: Option<isize>,
+  alignment_end: Option<isize>,
+  alignment_score: Option<i32>,
+  aa_substitutions: String,
+  aa_deletions: String,
+  aa_insertions: String,
+  total_aminoacid_substitutions: Option<usize>,
+  total_aminoacid_deletions: Option<usize>,
+  total_aminoacid_insertions: Option<usize>,
+  total_unknown_aa: Option<usize>,
+  errors: String,
+}
+
+struct AaOutputWriters {
+  ndjson: Option<NdjsonFileWriter>,
+  tables: Vec<CsvStructFileWriter>,
+  translations: Option<FastaPeptideWriter>,
+  json: Option<AaResultsJson>,
+}
+
+/// Runs the analysis of peptides of one CDS (`--input-aa` mode of `nextclade run`)
+pub fn nextclade_run_aa(run_args: &NextcladeRunArgs) -> Result<(), Report> {
+  info!("Command-line arguments:\n{run_args:#?}");
+
+  let NextcladeRunArgs {
+    inputs,
+    outputs:
+      NextcladeRunOutputArgs {
+        output_fasta,
+        output_translations,
+        output_nuc_cds_fasta,
+        output_codon_alignment,
+        output_ndjson,
+        output_json,
+        output_csv,
+        output_tsv,
+        output_parquet,
+        output_arrow,
+        output_graph,
+        output_tree,
+        output_tree_nwk,
+        output_tree_nexus,
+        output_tree_pb,
+        output_jplace,
+        output_context_trees,
+        output_haplotypes,
+        output_codon_usage,
+        output_support,
+        output_alignment_band,
+        output_maf,
+        output_batch_qc,
+        output_html,
+        output_vcf,
+        output_insertions,
+        output_errors,
+        output_usage,
+        ..
+      },
+    params,
+    other_params: NextcladeRunOtherParams { jobs },
+  } = run_args;
+
+  let Some(cds_name) = &inputs.cds else {
+    return make_error!("Argument `--input-aa` requires `--cds`");
+  };
+
+  let unsupported_outputs = [
+    ("--output-fasta", output_fasta),
+    ("--output-parquet", output_parquet),
+    ("--output-arrow", output_arrow),
+    ("--output-graph", output_graph),
+    ("--output-tree", output_tree),
+    ("--output-tree-nwk", output_tree_nwk),
+    ("--output-tree-nexus", output_tree_nexus),
+    ("--output-tree-pb", output_tree_pb),
+    ("--output-jplace", output_jplace),
+    ("--output-haplotypes", output_haplotypes),
+    ("--output-codon-usage", output_codon_usage),
+    ("--output-support", output_support),
+    ("--output-alignment-band", output_alignment_band),
+    ("--output-maf", output_maf),
+    ("--output-batch-qc", output_batch_qc),
+    ("--output-html", output_html),
+    ("--output-vcf", output_vcf),
+    ("--output-insertions", output_insertions),
+    ("--output-errors", output_errors),
+    ("--output-usage", output_usage),
+  ]
+  .into_iter()
+  .filter_map(|(arg, output)| output.is_some().then_some(arg))
+  .chain(output_nuc_cds_fasta.is_some().then_some("--output-nuc-cds-fasta"))
+  .chain(output_codon_alignment.is_some().then_some("--output-codon-alignment"))
+  .chain(output_context_trees.is_some().then_some("--output-context-trees"))
+  .collect_vec();
+  if !unsupported_outputs.is_empty() {
+    warn!(
+      "When input sequences are peptides (`--input-aa`), the following outputs are not produced: {}",
+      unsupported_outputs.join(", ")
+    );
+  }
+
+  if let Some(output_translations) = output_translations {
+    if !output_translations.contains("{cds}") {
+      return make_error!(
+        "Expected `--output-translations` argument to contain a template string containing template variable {{cds}} (with curly braces), but received: {output_translations}"
+      );
+    }
+  }
+
+  let mut nextclade_inputs = nextclade_get_inputs(inputs, &inputs.cds_selection)?;
+  if !nextclade_inputs.gene_map.iter_cdses().any(|cds| cds.name == *cds_name) {
+    return make_error!(
+      "CDS '{cds_name}' requested using `--cds` is not found in the genome annotation. Available CDSes: {}",
+      nextclade_inputs.gene_map.iter_cdses().map(|cds| &cds.name).join(", ")
+    );
+  }
+
+  // Reference tree is not used for peptides, and preprocessing it can take considerable time
+  nextclade_inputs.tree = None;
+
+  let nextclade = Nextclade::new(nextclade_inputs, params)?;
+
+  let mut writers = AaOutputWriters {
+    ndjson: output_ndjson.as_ref().map(NdjsonFileWriter::new).transpose()?,
+    tables: [(output_csv, b';'), (output_tsv, b'\t')]
+      .into_iter()
+      .filter_map(|(filepath, delimiter)| {
+        filepath
+          .as_ref()
+          .map(|filepath| CsvStructFileWriter::new(filepath, delimiter))
+      })
+      .collect::<Result<Vec<_>, Report>>()?,
+    translations: output_translations
+      .as_ref()
+      .map(|output_translations| FastaPeptideWriter::new(&nextclade.gene_map, output_translations))
+      .transpose()?,
+    json: output_json.as_ref().map(|_| AaResultsJson {
+      cds_name: cds_name.clone(),
+      results: vec![],
+      errors: vec![],
+    }),
+  };
+
+  process_fasta_in_order(
+    &inputs.input_fastas,
+    *jobs,
+    |fasta_record| {
+      info!("Processing peptide '{}'", fasta_record.seq_name);
+
+      let outputs_or_err = nextclade.run_aa(&fasta_record, cds_name).wrap_err_with(|| {
+        format!(
+          "When processing peptide #{} '{}'",
+          fasta_record.index, fasta_record.seq_name
+        )
+      });
+
+      AaRecord {
+        index: fasta_record.index,
+        seq_name: fasta_record.seq_name,
+        outputs_or_err,
+      }
+    },
+    |record| write_record(&mut writers, cds_name, record),
+  )?;
+
+  if let (Some(output_json), Some(results_json)) = (output_json, &writers.json) {
+    json_write(output_json, results_json, JsonPretty(true))
+      .wrap_err_with(|| format!("When writing results to {output_json:#?}"))?;
+  }
+
+  Ok(())
+}
+
+fn write_record(writers: &mut AaOutputWriters, cds_name: &str, record: AaRecord) -> Result<(), Report> {
+  let AaRecord {
+    index,
+    seq_name,
+    outputs_or_err,
+  } = record;
+
+  match outputs_or_err {
+    Ok(AaAnalysisOutput { outputs, translation }) => {
+      if let Some(ndjson) = &mut writers.ndjson {
+        ndjson.write(&outputs)?;
+      }
+
+      for table in &mut writers.tables {
+        table.write(&AaResultsCsvRow {
+          index,
+          seq_name: &seq_name,
+          cds_name,
+          alignment_start: Some(outputs.alignment_range.begin.as_isize() + 1),
+          alignment_end: Some(outputs.alignment_range.end.as_isize()),
+          alignment_score: Some(outputs.alignment_score),
+          aa_substitutions: format_aa_substitutions(&outputs.aa_substitutions, ARRAY_ITEM_DELIMITER),
+          aa_deletions: format_aa_deletions(&outputs.aa_deletions, ARRAY_ITEM_DELIMITER),
+          aa_insertions: format_aa_insertions(&outputs.aa_insertions, ARRAY_ITEM_DELIMITER),
+          total_aminoacid_substitutions: Some(outputs.total_aminoacid_substitutions),
+          total_aminoacid_deletions: Some(outputs.total_aminoacid_deletions),
+          total_aminoacid_insertions: Some(outputs.total_aminoacid_insertions),
+          total_unknown_aa: Some(outputs.total_unknown_aa),
+          errors: String::new(),
+        })?;
+      }
+
+      if let Some(translations) = &mut writers.translations {
+        translations
+          .write(&seq_name, &translation)
+          .wrap_err_with(|| format!("When writing aligned peptide #{index} '{seq_name}'"))?;
+      }
+
+      if let Some(results_json) = &mut writers.json {
+        results_json.results.push(outputs);
+      }
+    }
+    Err(report) => {
+      let cause = report_to_string(&report);
+      warn!("In peptide #{index} '{seq_name}': {cause}. Note that this peptide will not be included in the results.");
+
+      let errors = vec![cause];
+
+      if let Some(ndjson) = &mut writers.ndjson {
+        ndjson.write_nuc_error(index, &seq_name, &errors)?;
+      }
+
+      for table in &mut writers.tables {
+        table.write(&AaResultsCsvRow {
+          index,
+          seq_name: &seq_name,
+          cds_name,
+          alignment_start: None,
+          alignment_end: None,
+          alignment_score: None,
+          aa_substitutions: String::new(),
+          aa_deletions: String::new(),
+          aa_insertions: String::new(),
+          total_aminoacid_substitutions: None,
+          total_aminoacid_deletions: None,
+          total_aminoacid_insertions: None,
+          total_unknown_aa: None,
+          errors: errors.join(";"),
+        })?;
+      }
+
+      if let Some(results_json) = &mut writers.json {
+        results_json.errors.push(NextcladeErrorOutputs {
+          index,
+          seq_name,
+          errors,
+        });
+      }
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,203 @@
+use crate::cli::nextclade_cli::{
+  nextclade_check_column_config_args, nextclade_check_removed_args, nextclade_get_output_filenames, NextcladeRunArgs,
+  NextcladeRunInputArgs, NextcladeRunOtherParams, NextcladeRunOutputArgs,
+};
+use crate::cli::nextclade_loop::nextclade_run;
+use eyre::{Report, WrapErr};
+use nextclade::make_error;
+use nextclade::run::params::NextcladeInputParamsOptional;
+use std::path::PathBuf;
+use url::Url;
+
+/// Where to take the dataset from
+#[derive(Clone, Debug)]
+pub enum NextcladeDatasetSource {
+  /// Dataset directory or dataset zip archive on the local filesystem
+  Local(PathBuf),
+  /// Name of the dataset to download from the dataset server
+  Remote(String),
+}
+
+/// Validated configuration of a Nextclade run, for use of Nextclade as a library.
+///
+/// This is the programmatic equivalent of `nextclade run` arguments. Construct it with `NextcladeRunConfig::builder()`
+/// rather than by filling `NextcladeRunArgs` directly: the builder checks for incompatible combinations of options and
+/// deduces output filenames, the same way as it is done for the command-line arguments.
+#[derive(Clone, Debug)]
+pub struct NextcladeRunConfig {
+  args: NextcladeRunArgs,
+}
+
+impl NextcladeRunConfig {
+  pub fn builder() -> NextcladeRunConfigBuilder {
+    NextcladeRunConfigBuilder::default()
+  }
+
+  pub const fn args(&self) -> &NextcladeRunArgs {
+    &self.args
+  }
+
+  #[allow(clippy::missing_const_for_fn)] // `self` has a destructor, which is not allowed in `const fn`
+  pub fn into_args(self) -> NextcladeRunArgs {
+    self.args
+  }
+
+  pub fn run(self) -> Result<(), Report> {
+    nextclade_run(self.args)
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NextcladeRunConfigBuilder {
+  inputs: NextcladeRunInputArgs,
+  outputs: NextcladeRunOutputArgs,
+  params: NextcladeInputParamsOptional,
+  other_params: NextcladeRunOtherParams,
+}
+
+// Clippy suggests `const fn` for setters which replace fields with destructors, which is not allowed in `const fn`
+#[allow(clippy::missing_const_for_fn)]
+impl NextcladeRunConfigBuilder {
+  /// Paths to FASTA files with input sequences
+  #[must_use]
+  pub fn with_sequences<P: Into<PathBuf>>(mut self, input_fastas: impl IntoIterator<Item = P>) -> Self {
+    self.inputs.input_fastas = input_fastas.into_iter().map(Into::into).collect();
+    self
+  }
+
+  #[must_use]
+  pub fn with_dataset(mut self, dataset: NextcladeDatasetSource) -> Self {
+    match dataset {
+      NextcladeDatasetSource::Local(input_dataset) => {
+        self.inputs.input_dataset = Some(input_dataset);
+        self.inputs.dataset_name = None;
+      }
+      NextcladeDatasetSource::Remote(dataset_name) => {
+        self.inputs.input_dataset = None;
+        self.inputs.dataset_name = Some(dataset_name);
+      }
+    }
+    self
+  }
+
+  /// Dataset server to download the dataset from, if the dataset is `NextcladeDatasetSource::Remote`
+  #[must_use]
+  pub fn with_server(mut self, server: Url) -> Self {
+    self.inputs.server = server;
+    self
+  }
+
+  /// Reference sequence. Overrides the one from the dataset, if any.
+  #[must_use]
+  pub fn with_reference(mut self, input_ref: impl Into<PathBuf>) -> Self {
+    self.inputs.input_ref = Some(input_ref.into());
+    self
+  }
+
+  /// Reference tree. Overrides the one from the dataset, if any.
+  #[must_use]
+  pub fn with_tree(mut self, input_tree: impl Into<PathBuf>) -> Self {
+    self.inputs.input_tree = Some(input_tree.into());
+    self
+  }
+
+  /// Pathogen JSON. Overrides the one from the dataset, if any.
+  #[must_use]
+  pub fn with_pathogen_json(mut self, input_pathogen_json: impl Into<PathBuf>) -> Self {
+    self.inputs.input_pathogen_json = Some(input_pathogen_json.into());
+    self
+  }
+
+  /// Genome annotation. Overrides the one from the dataset, if any.
+  #[must_use]
+  pub fn with_annotation(mut self, input_annotation: impl Into<PathBuf>) -> Self {
+    self.inputs.input_annotation = Some(input_annotation.into());
+    self
+  }
+
+  /// Names of CDSes to translate and to analyze. All CDSes are used if not set.
+  #[must_use]
+  pub fn with_cds_selection<S: Into<String>>(mut self, cdses: impl IntoIterator<Item = S>) -> Self {
+    self.inputs.cds_selection = Some(cdses.into_iter().map(Into::into).collect());
+    self
+  }
+
+  #[must_use]
+  pub fn with_outputs(mut self, outputs: NextcladeRunOutputArgs) -> Self {
+    self.outputs = outputs;
+    self
+  }
+
+  /// Produce all outputs into a given directory. Equivalent to `--output-all`.
+  #[must_use]
+  pub fn with_output_all(mut self, output_all: impl Into<PathBuf>) -> Self {
+    self.outputs.output_all = Some(output_all.into());
+    self
+  }
+
+  /// Analysis parameters. These override parameters from the dataset, same as the corresponding command-line arguments.
+  #[must_use]
+  pub fn with_params(mut self, params: NextcladeInputParamsOptional) -> Self {
+    self.params = params;
+    self
+  }
+
+  /// Number of processing jobs
+  #[must_use]
+  pub const fn with_jobs(mut self, jobs: usize) -> Self {
+    self.other_params.jobs = jobs;
+    self
+  }
+
+  /// Checks the configuration and deduces output filenames
+  pub fn build(self) -> Result<NextcladeRunConfig, Report> {
+    let Self {
+      inputs,
+      outputs,
+      params,
+      other_params,
+    } = self;
+
+    let has_dataset = inputs.input_dataset.is_some() || inputs.dataset_name.is_some();
+
+    if !has_dataset && inputs.input_ref.is_none() {
+      return make_error!("Either a dataset or a reference sequence is required");
+    }
+
+    if !has_dataset && inputs.input_annotation.is_none() && outputs.output_translations.is_some() {
+      return make_error!("Translations are requested, but neither a dataset nor a genome annotation is provided");
+    }
+
+    let requests_tree = [
+      &outputs.output_tree,
+      &outputs.output_tree_nwk,
+      &outputs.output_tree_nexus,
+      &outputs.output_tree_pb,
+      &outputs.output_jplace,
+      &outputs.output_graph,
+    ]
+    .iter()
+    .any(|output| output.is_some())
+      || outputs.output_context_trees.is_some();
+    if !has_dataset && inputs.input_tree.is_none() && requests_tree {
+      return make_error!("Tree outputs are requested, but neither a dataset nor a reference tree is provided");
+    }
+
+    if other_params.jobs == 0 {
+      return make_error!("Number of jobs should be at least 1");
+    }
+
+    let mut args = NextcladeRunArgs {
+      inputs,
+      outputs,
+      params,
+      other_params,
+    };
+
+    nextclade_check_removed_args(&args)?;
+    nextclade_check_column_config_args(&args)?;
+    nextclade_get_output_filenames(&mut args).wrap_err("When deducing output filenames")?;
+
+    Ok(NextcladeRunConfig { args })
+  }
+}
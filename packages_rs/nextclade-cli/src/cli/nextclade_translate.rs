@@ -0,0 +1,104 @@
+use crate::cli::nextclade_cli::{NextcladeRunOtherParams, NextcladeTranslateArgs};
+use crate::cli::ordered_pipeline::process_fasta_in_order;
+use crate::dataset::dataset_download::nextclade_get_inputs;
+use eyre::{Report, WrapErr};
+use log::{info, warn};
+use nextclade::io::fasta::FastaPeptideWriter;
+use nextclade::make_error;
+use nextclade::run::nextclade_wasm::Nextclade;
+use nextclade::translate::translate_genes::Translation;
+use nextclade::utils::error::report_to_string;
+
+struct TranslationRecord {
+  index: usize,
+  seq_name: String,
+  translation_or_err: Result<Translation, Report>,
+}
+
+pub fn nextclade_translate(args: &NextcladeTranslateArgs) -> Result<(), Report> {
+  info!("Command-line arguments:\n{args:#?}");
+
+  let NextcladeTranslateArgs {
+    inputs,
+    output_translations,
+    include_reference,
+    params,
+    other_params: NextcladeRunOtherParams { jobs },
+  } = args;
+
+  if !output_translations.contains("{cds}") {
+    return make_error!(
+      "Expected `--output-translations` argument to contain a template string containing template variable {{cds}} (with curly braces), but received: {output_translations}"
+    );
+  }
+
+  let mut nextclade_inputs = nextclade_get_inputs(inputs, &inputs.cds_selection)?;
+  if nextclade_inputs.gene_map.is_empty() {
+    return make_error!(
+      "Genome annotation is required for translation, but it is either not provided or contains no CDS. Provide it using `--input-annotation` or use a dataset which contains it."
+    );
+  }
+
+  // Reference tree is not needed for translation, and preprocessing it can take considerable time
+  nextclade_inputs.tree = None;
+
+  let nextclade = Nextclade::new(nextclade_inputs, params)?;
+
+  let mut writer = FastaPeptideWriter::new(&nextclade.gene_map, output_translations)?;
+
+  if *include_reference {
+    for cds_tr in nextclade.ref_translation.cdses() {
+      writer.write(&nextclade.ref_record.seq_name, cds_tr)?;
+    }
+  }
+
+  process_fasta_in_order(
+    &inputs.input_fastas,
+    *jobs,
+    |fasta_record| {
+      info!("Translating sequence '{}'", fasta_record.seq_name);
+
+      let translation_or_err = nextclade.translate(&fasta_record).wrap_err_with(|| {
+        format!(
+          "When translating sequence #{} '{}'",
+          fasta_record.index, fasta_record.seq_name
+        )
+      });
+
+      TranslationRecord {
+        index: fasta_record.index,
+        seq_name: fasta_record.seq_name,
+        translation_or_err,
+      }
+    },
+    |record| write_record(&mut writer, record),
+  )
+}
+
+fn write_record(writer: &mut FastaPeptideWriter, record: TranslationRecord) -> Result<(), Report> {
+  let TranslationRecord {
+    index,
+    seq_name,
+    translation_or_err,
+  } = record;
+
+  match translation_or_err {
+    Ok(translation) => {
+      for (_, gene_tr) in translation.iter_genes() {
+        for warning in &gene_tr.warnings {
+          info!("In sequence #{index} '{seq_name}': {}", warning.warning);
+        }
+      }
+
+      translation
+        .cdses()
+        .try_for_each(|cds_tr| writer.write(&seq_name, cds_tr))
+        .wrap_err_with(|| format!("When writing translations of sequence #{index} '{seq_name}'"))
+    }
+    Err(report) => {
+      let cause = report_to_string(&report);
+      warn!("In sequence #{index} '{seq_name}': {cause}. Note that this sequence will not be included in the results.");
+      Ok(())
+    }
+  }
+}
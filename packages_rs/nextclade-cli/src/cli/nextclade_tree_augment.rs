@@ -0,0 +1,121 @@
+use crate::cli::nextclade_cli::NextcladeTreeAugmentArgs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::{info, warn};
+use nextclade::align::params::AlignPairwiseParams;
+use nextclade::alphabet::nuc::to_nuc_seq;
+use nextclade::gene::gene_map::GeneMap;
+use nextclade::graph::graph::{convert_auspice_tree_to_graph, convert_graph_to_auspice_tree};
+use nextclade::io::fasta::read_one_fasta;
+use nextclade::io::fs::read_file_to_string;
+use nextclade::io::json::{json_write, JsonPretty};
+use nextclade::io::nwk_writer::nwk_write_to_file;
+use nextclade::io::results_json::results_read;
+use nextclade::make_error;
+use nextclade::translate::translate_genes::Translation;
+use nextclade::translate::translate_genes_ref::translate_genes_ref;
+use nextclade::tree::params::TreeBuilderParams;
+use nextclade::tree::tree::AuspiceTree;
+use nextclade::tree::tree_augment::graph_augment_in_place;
+use nextclade::tree::tree_preprocess::graph_preprocess_in_place;
+use std::collections::HashSet;
+
+pub fn nextclade_tree_augment(args: &NextcladeTreeAugmentArgs) -> Result<(), Report> {
+  let NextcladeTreeAugmentArgs {
+    input_results,
+    input_tree,
+    input_ref,
+    input_annotation,
+    include_qc_status,
+    include_seq_names_from,
+    output_tree,
+    output_tree_nwk,
+    tree_builder,
+  } = args;
+
+  let mut params = TreeBuilderParams::default();
+  if let Some(tree_builder) = tree_builder {
+    params.merge_opt(tree_builder.clone());
+  }
+
+  let ref_record = read_one_fasta(input_ref).wrap_err("When reading reference sequence")?;
+  let ref_seq = to_nuc_seq(&ref_record.seq).wrap_err("When converting reference sequence")?;
+
+  let ref_translation = match input_annotation {
+    Some(input_annotation) => {
+      let gene_map = GeneMap::from_path(input_annotation).wrap_err("When reading genome annotation")?;
+      translate_genes_ref(&ref_seq, &gene_map, &AlignPairwiseParams::default())
+        .wrap_err("When translating reference sequence")?
+    }
+    None => Translation::default(),
+  };
+
+  let tree = AuspiceTree::from_path(input_tree)?;
+  let mut graph = convert_auspice_tree_to_graph(tree).wrap_err("When converting Auspice tree to Nextclade graph")?;
+  graph_preprocess_in_place(&mut graph, &ref_seq, &ref_translation).wrap_err("When preprocessing Nextclade graph")?;
+
+  let (results, errors) = results_read(input_results)?;
+  if !errors.is_empty() {
+    info!(
+      "Skipping {} sequences which failed to be analyzed in the previous run",
+      errors.len()
+    );
+  }
+
+  let seq_names = include_seq_names_from
+    .as_ref()
+    .map(|filepath| -> Result<HashSet<String>, Report> {
+      let content = read_file_to_string(filepath).wrap_err("When reading list of sequence names")?;
+      Ok(
+        content
+          .lines()
+          .map(str::trim)
+          .filter(|name| !name.is_empty())
+          .map(ToOwned::to_owned)
+          .collect(),
+      )
+    })
+    .transpose()?;
+
+  let n_results = results.len();
+  let selected = results
+    .into_iter()
+    .filter(|result| {
+      seq_names
+        .as_ref()
+        .map_or(true, |seq_names| seq_names.contains(&result.seq_name))
+    })
+    .filter(|result| include_qc_status.contains(&result.qc.overall_status.to_string()))
+    .collect_vec();
+
+  if selected.is_empty() {
+    return make_error!(
+      "None of the {n_results} sequences in {input_results:#?} passed the selection criteria. Nothing to add to the tree."
+    );
+  }
+
+  let duplicates = selected
+    .iter()
+    .map(|result| &result.seq_name)
+    .duplicates()
+    .collect_vec();
+  if !duplicates.is_empty() {
+    warn!(
+      "The following sequence names occur more than once and will be added to the tree multiple times: {}",
+      duplicates.iter().join(", ")
+    );
+  }
+
+  let n_added = graph_augment_in_place(&mut graph, selected, ref_seq.len(), &params)
+    .wrap_err("When adding sequences to the reference tree")?;
+  info!("Added {n_added} of {n_results} sequences to the reference tree");
+
+  let tree = convert_graph_to_auspice_tree(&graph)?;
+  json_write(output_tree, &tree, JsonPretty(true))?;
+
+  if let Some(output_tree_nwk) = output_tree_nwk {
+    nwk_write_to_file(output_tree_nwk, &graph)?;
+  }
+
+  Ok(())
+}
@@ -0,0 +1,169 @@
+use eyre::Report;
+use nextclade::io::fasta::{FastaReader, FastaRecord};
+use std::collections::BTreeMap;
+use std::panic::resume_unwind;
+use std::path::PathBuf;
+
+const CHANNEL_SIZE: usize = 128;
+
+/// Reads sequences from FASTA files and processes them in parallel, passing the results to `write` in the order of the
+/// inputs. See `process_in_order()`.
+pub fn process_fasta_in_order<R, P, W>(
+  input_fastas: &[PathBuf],
+  jobs: usize,
+  process: P,
+  write: W,
+) -> Result<(), Report>
+where
+  R: Send,
+  P: Fn(FastaRecord) -> R + Sync,
+  W: FnMut(R) -> Result<(), Report>,
+{
+  let read_records = || {
+    let mut reader = FastaReader::from_paths(input_fastas)?;
+    Ok(std::iter::from_fn(move || {
+      let mut record = FastaRecord::default();
+      match reader.read(&mut record) {
+        Ok(()) if record.is_empty() => None,
+        Ok(()) => Some(Ok((record.index, record))),
+        Err(report) => Some(Err(report)),
+      }
+    }))
+  };
+  process_in_order(read_records, jobs, process, write)
+}
+
+/// Processes inputs on `jobs` worker threads and passes the results to `write`, on the calling thread, in the order of
+/// the inputs. The iterator over the inputs is created by `read_inputs`, on a separate reader thread, such that it does
+/// not need to be `Send`. Inputs are pairs of an index and of an item, and the indices are expected to be consecutive,
+/// starting from 0: the results which arrive early are buffered until all of the preceding ones are written.
+///
+/// Reading of the inputs stops at the first error, and the error is returned after the items read so far are written.
+/// If `write` fails, processing stops and the error is returned.
+pub fn process_in_order<T, R, F, I, P, W>(read_inputs: F, jobs: usize, process: P, mut write: W) -> Result<(), Report>
+where
+  T: Send,
+  R: Send,
+  F: FnOnce() -> Result<I, Report> + Send,
+  I: Iterator<Item = Result<(usize, T), Report>>,
+  P: Fn(T) -> R + Sync,
+  W: FnMut(R) -> Result<(), Report>,
+{
+  std::thread::scope(|s| -> Result<(), Report> {
+    let (input_sender, input_receiver) = crossbeam_channel::bounded::<(usize, T)>(CHANNEL_SIZE);
+    let (result_sender, result_receiver) = crossbeam_channel::bounded::<(usize, R)>(CHANNEL_SIZE);
+
+    let reader = s.spawn(move || -> Result<(), Report> {
+      for input in read_inputs()? {
+        // Receiver is gone only if writing of outputs failed. The error is reported by the writer.
+        if input_sender.send(input?).is_err() {
+          break;
+        }
+      }
+      Ok(())
+    });
+
+    let process = &process;
+    for _ in 0..jobs.max(1) {
+      let input_receiver = input_receiver.clone();
+      let result_sender = result_sender.clone();
+
+      s.spawn(move || {
+        for (index, input) in &input_receiver {
+          if result_sender.send((index, process(input))).is_err() {
+            break;
+          }
+        }
+      });
+    }
+
+    // Only the senders owned by the worker threads should remain, such that the loop below ends when they finish
+    drop(result_sender);
+
+    // Results arrive in the order of completion. Buffer them, such that the outputs are in the order of the inputs.
+    let mut pending = BTreeMap::<usize, R>::new();
+    let mut next_index = 0_usize;
+    for (index, result) in result_receiver {
+      pending.insert(index, result);
+      while let Some(result) = pending.remove(&next_index) {
+        write(result)?;
+        next_index += 1;
+      }
+    }
+
+    reader.join().unwrap_or_else(|panic| resume_unwind(panic))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nextclade::make_error;
+  use nextclade::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+
+  fn inputs(n: usize) -> impl Iterator<Item = Result<(usize, usize), Report>> {
+    (0..n).map(|i| Ok((i, i)))
+  }
+
+  #[test]
+  fn writes_results_in_the_order_of_inputs() -> Result<(), Report> {
+    let mut written = vec![];
+    process_in_order(
+      || Ok(inputs(200)),
+      4,
+      |i| {
+        // Make the items complete out of order
+        std::thread::sleep(std::time::Duration::from_micros(((i * 7919) % 13) as u64 * 50));
+        i * 2
+      },
+      |result| {
+        written.push(result);
+        Ok(())
+      },
+    )?;
+    assert_eq!(written, (0..200).map(|i| i * 2).collect::<Vec<_>>());
+    Ok(())
+  }
+
+  #[test]
+  fn returns_error_of_reading_after_writing_preceding_results() {
+    let mut written = vec![];
+    let result = process_in_order(
+      || {
+        Ok(
+          inputs(3)
+            .chain([make_error!("Unable to read record #3")])
+            .chain(inputs(5).skip(4)),
+        )
+      },
+      2,
+      |i| i,
+      |result| {
+        written.push(result);
+        Ok(())
+      },
+    );
+    assert_eq!(report_to_string(&result.unwrap_err()), "Unable to read record #3");
+    assert_eq!(written, vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn stops_on_error_of_writing() {
+    let mut written = vec![];
+    let result = process_in_order(
+      || Ok(inputs(1000)),
+      2,
+      |i| i,
+      |result| {
+        if result == 3 {
+          return make_error!("Disk full");
+        }
+        written.push(result);
+        Ok(())
+      },
+    );
+    assert_eq!(report_to_string(&result.unwrap_err()), "Disk full");
+    assert_eq!(written, vec![0, 1, 2]);
+  }
+}
@@ -0,0 +1,213 @@
+use eyre::{eyre, Report};
+use nextclade::io::fasta::FastaRecord;
+use nextclade::run::nextclade_wasm::AnalysisOutput;
+use nextclade::utils::error::report_to_string;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type SequenceKey = (usize, [u8; 32]);
+
+/// Result of the analysis of a sequence, as remembered for reuse
+enum DedupEntry {
+  Output(AnalysisOutput),
+  /// `Report` cannot be cloned, so only the message is kept, along with the sequence it originates from
+  Error {
+    index: usize,
+    seq_name: String,
+    message: String,
+  },
+}
+
+/// Keeps the analysis results of distinct sequences, such that identical sequences are analyzed only once.
+///
+/// Sequences are identified by the index of the reference they are analyzed against and by the checksum of the sequence
+/// characters. Both successful results and errors are kept. Results are shared between threads. If identical sequences
+/// are processed concurrently, they may be analyzed more than once, which is harmless.
+///
+/// NOTE: there is one entry per distinct sequence, so the memory usage grows with the number of distinct sequences in
+/// the input. Deduplication can be disabled with `--no-dedup`.
+#[derive(Default)]
+pub struct SequenceDedup {
+  results: Mutex<HashMap<SequenceKey, Arc<DedupEntry>>>,
+}
+
+impl SequenceDedup {
+  /// Returns the results of a previously analyzed sequence identical to the given one, with the index and the name
+  /// replaced with the ones of the given record. If there is no such sequence, analyzes the given one using `run` and
+  /// remembers the results.
+  pub fn get_or_run(
+    &self,
+    ref_index: usize,
+    record: &FastaRecord,
+    run: impl FnOnce() -> Result<AnalysisOutput, Report>,
+  ) -> Result<AnalysisOutput, Report> {
+    let key = sequence_key(ref_index, record);
+
+    let entry = self.results.lock().unwrap().get(&key).cloned();
+    if let Some(entry) = entry {
+      return match entry.as_ref() {
+        DedupEntry::Output(output) => {
+          let mut output = output.clone();
+          output.analysis_result.index = record.index;
+          output.analysis_result.seq_name = record.seq_name.clone();
+          Ok(output)
+        }
+        DedupEntry::Error {
+          index,
+          seq_name,
+          message,
+        } => Err(eyre!(message.clone()).wrap_err(format!(
+          "Sequence is identical to sequence #{index} '{seq_name}', analysis of which failed"
+        ))),
+      };
+    }
+
+    let result = run();
+    let entry = match &result {
+      Ok(output) => DedupEntry::Output(output.clone()),
+      Err(report) => DedupEntry::Error {
+        index: record.index,
+        seq_name: record.seq_name.clone(),
+        message: report_to_string(report),
+      },
+    };
+    self
+      .results
+      .lock()
+      .unwrap()
+      .entry(key)
+      .or_insert_with(|| Arc::new(entry));
+
+    result
+  }
+}
+
+/// Analyzes the sequence using `run`, reusing the results of an identical sequence if deduplication is enabled
+/// (`dedup` is `None` with `--no-dedup`)
+pub fn run_deduplicated(
+  dedup: Option<&SequenceDedup>,
+  ref_index: usize,
+  record: &FastaRecord,
+  run: impl FnOnce() -> Result<AnalysisOutput, Report>,
+) -> Result<AnalysisOutput, Report> {
+  match dedup {
+    Some(dedup) => dedup.get_or_run(ref_index, record, run),
+    None => run(),
+  }
+}
+
+fn sequence_key(ref_index: usize, record: &FastaRecord) -> SequenceKey {
+  (ref_index, Sha256::digest(record.seq.as_bytes()).into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nextclade::alphabet::nuc::from_nuc_seq;
+  use nextclade::o;
+  use nextclade::run::nextclade_wasm::{Nextclade, NextcladeParams, NextcladeParamsRaw};
+  use nextclade::run::params::NextcladeInputParamsOptional;
+  use nextclade::utils::random::random_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use std::cell::Cell;
+
+  const PATHOGEN_JSON: &str = r#"{
+    "schemaVersion": "3.0.0",
+    "files": { "reference": "reference.fasta", "pathogenJson": "pathogen.json" }
+  }"#;
+
+  fn create_nextclade(ref_seq: &str) -> Result<Nextclade, Report> {
+    let params = NextcladeParams::from_raw(NextcladeParamsRaw {
+      ref_seq: format!(">ref\n{ref_seq}\n"),
+      gene_map: None,
+      tree: None,
+      virus_properties: PATHOGEN_JSON.to_owned(),
+    })?;
+    Nextclade::new(params, &NextcladeInputParamsOptional::default())
+  }
+
+  fn record(index: usize, seq_name: &str, seq: &str) -> FastaRecord {
+    FastaRecord {
+      seq_name: seq_name.to_owned(),
+      seq: seq.to_owned(),
+      index,
+    }
+  }
+
+  /// Runs the analysis through the deduplication and counts how many times the sequence was actually analyzed
+  fn run_counted(
+    dedup: Option<&SequenceDedup>,
+    nextclade: &Nextclade,
+    record: &FastaRecord,
+    n_runs: &Cell<usize>,
+  ) -> Result<AnalysisOutput, Report> {
+    run_deduplicated(dedup, 0, record, || {
+      n_runs.set(n_runs.get() + 1);
+      nextclade.run(record)
+    })
+  }
+
+  #[test]
+  fn reuses_results_of_identical_sequence() -> Result<(), Report> {
+    let ref_seq = from_nuc_seq(&random_nuc_seq(&mut 42, 1000));
+    let nextclade = create_nextclade(&ref_seq)?;
+    let dedup = SequenceDedup::default();
+    let n_runs = Cell::new(0);
+
+    let first = run_counted(Some(&dedup), &nextclade, &record(0, "first", &ref_seq), &n_runs)?;
+    let duplicate = run_counted(Some(&dedup), &nextclade, &record(3, "duplicate", &ref_seq), &n_runs)?;
+
+    assert_eq!(n_runs.get(), 1);
+    assert_eq!(
+      (first.analysis_result.index, first.analysis_result.seq_name.as_str()),
+      (0, "first")
+    );
+    assert_eq!(
+      (
+        duplicate.analysis_result.index,
+        duplicate.analysis_result.seq_name.as_str()
+      ),
+      (3, "duplicate")
+    );
+    assert_eq!(
+      duplicate.analysis_result.alignment_score,
+      first.analysis_result.alignment_score
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn reuses_errors_of_identical_sequence() -> Result<(), Report> {
+    let ref_seq = from_nuc_seq(&random_nuc_seq(&mut 42, 1000));
+    let qry_seq = from_nuc_seq(&random_nuc_seq(&mut 7, 1000));
+    let nextclade = create_nextclade(&ref_seq)?;
+    let dedup = SequenceDedup::default();
+    let n_runs = Cell::new(0);
+
+    let first = run_counted(Some(&dedup), &nextclade, &record(0, "first", &qry_seq), &n_runs);
+    let duplicate = run_counted(Some(&dedup), &nextclade, &record(1, "duplicate", &qry_seq), &n_runs);
+
+    assert_eq!(n_runs.get(), 1);
+    let first_message = report_to_string(&first.unwrap_err());
+    assert_eq!(
+      report_to_string(&duplicate.unwrap_err()),
+      format!("Sequence is identical to sequence #0 'first', analysis of which failed: {first_message}")
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn analyzes_every_sequence_without_dedup() -> Result<(), Report> {
+    let ref_seq = from_nuc_seq(&random_nuc_seq(&mut 42, 1000));
+    let nextclade = create_nextclade(&ref_seq)?;
+    let n_runs = Cell::new(0);
+
+    run_counted(None, &nextclade, &record(0, "first", &ref_seq), &n_runs)?;
+    let duplicate = run_counted(None, &nextclade, &record(1, "duplicate", &ref_seq), &n_runs)?;
+
+    assert_eq!(n_runs.get(), 2);
+    assert_eq!(duplicate.analysis_result.seq_name, o!("duplicate"));
+    Ok(())
+  }
+}
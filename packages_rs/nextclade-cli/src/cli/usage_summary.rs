@@ -0,0 +1,276 @@
+use crate::cli::nextclade_cli::NextcladeRunOutputArgs;
+use eyre::Report;
+use itertools::Itertools;
+use log::LevelFilter;
+use nextclade::gene::gene_map::GeneMap;
+use nextclade::io::json::{json_write, JsonPretty};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Summary of resources used by a run, to help sizing of compute jobs.
+///
+/// All the values are collected locally, from the operating system and from the filesystem. Nothing is sent over the
+/// network.
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+  pub wall_time_sec: f64,
+  /// User and system CPU time of the process, summed over all threads. Only available on Linux.
+  pub cpu_time_sec: Option<f64>,
+  /// Peak resident set size of the process. Only available on Linux.
+  pub peak_rss_bytes: Option<u64>,
+  pub num_sequences: usize,
+  pub sequences_per_sec: f64,
+  /// Total size of input sequence files, as stored on disk. Not available when reading from standard input.
+  pub bytes_read: Option<u64>,
+  pub outputs: Vec<OutputUsage>,
+}
+
+/// Size of one of the output files, as stored on disk
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputUsage {
+  pub name: String,
+  pub filepath: PathBuf,
+  pub bytes_written: u64,
+}
+
+impl UsageSummary {
+  /// Collects the summary of a run which started at `start`. Outputs which were not written to a file (e.g. when
+  /// written to standard output) are omitted.
+  pub fn collect(start: Instant, num_sequences: usize, input_files: &[PathBuf], outputs: &[(&str, PathBuf)]) -> Self {
+    let wall_time_sec = start.elapsed().as_secs_f64();
+
+    let bytes_read = (!input_files.is_empty())
+      .then(|| input_files.iter().map(file_size).sum::<Option<u64>>())
+      .flatten();
+
+    let outputs = outputs
+      .iter()
+      .filter_map(|(name, filepath)| {
+        file_size(filepath).map(|bytes_written| OutputUsage {
+          name: (*name).to_owned(),
+          filepath: filepath.clone(),
+          bytes_written,
+        })
+      })
+      .collect_vec();
+
+    Self {
+      wall_time_sec,
+      cpu_time_sec: cpu_time_sec(),
+      peak_rss_bytes: peak_rss_bytes(),
+      num_sequences,
+      sequences_per_sec: if wall_time_sec > 0.0 {
+        num_sequences as f64 / wall_time_sec
+      } else {
+        0.0
+      },
+      bytes_read,
+      outputs,
+    }
+  }
+
+  /// Prints the summary to standard error, unless the log messages are silenced
+  pub fn print(&self) {
+    if log::max_level() < LevelFilter::Warn {
+      return;
+    }
+    eprint!("{}", self.to_table_string());
+  }
+
+  pub fn write_json(&self, filepath: impl AsRef<Path>) -> Result<(), Report> {
+    json_write(filepath, self, JsonPretty(true))
+  }
+
+  fn to_table_string(&self) -> String {
+    let mut rows = vec![
+      ("Wall time".to_owned(), format!("{:.1} s", self.wall_time_sec)),
+      (
+        "CPU time".to_owned(),
+        format_optional(self.cpu_time_sec.map(|t| format!("{t:.1} s"))),
+      ),
+      (
+        "Peak memory (RSS)".to_owned(),
+        format_optional(self.peak_rss_bytes.map(format_bytes)),
+      ),
+      (
+        "Sequences".to_owned(),
+        format!("{} ({:.1} per second)", self.num_sequences, self.sequences_per_sec),
+      ),
+      (
+        "Input read".to_owned(),
+        format_optional(self.bytes_read.map(format_bytes)),
+      ),
+    ];
+    rows.extend(self.outputs.iter().map(|output| {
+      (
+        format!("Output {}", output.name),
+        format!("{} ({})", format_bytes(output.bytes_written), output.filepath.display()),
+      )
+    }));
+
+    let key_width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or_default();
+    let mut table = "Resource usage:\n".to_owned();
+    for (key, value) in rows {
+      writeln!(table, "  {key:key_width$}  {value}").unwrap();
+    }
+    table
+  }
+}
+
+/// Lists the file outputs of `nextclade run` which are requested, along with their names for display. Translations,
+/// nucleotide sequences and codon alignments of CDSes are listed once for every CDS.
+pub fn run_output_paths(outputs: &NextcladeRunOutputArgs, gene_map: &GeneMap) -> Vec<(&'static str, PathBuf)> {
+  let NextcladeRunOutputArgs {
+    output_fasta,
+    output_translations,
+    output_nuc_cds_fasta,
+    output_codon_alignment,
+    output_ndjson,
+    output_json,
+    output_csv,
+    output_tsv,
+    output_parquet,
+    output_arrow,
+    output_graph,
+    output_tree,
+    output_tree_nwk,
+    output_tree_nexus,
+    output_tree_pb,
+    output_jplace,
+    output_haplotypes,
+    output_codon_usage,
+    output_support,
+    output_alignment_band,
+    output_maf,
+    output_batch_qc,
+    output_html,
+    output_vcf,
+    ..
+  } = outputs;
+
+  let translations = output_translations.iter().flat_map(|template| {
+    gene_map.iter_cdses().map(move |cds| {
+      (
+        "--output-translations",
+        PathBuf::from(template.replace("{cds}", &cds.name)),
+      )
+    })
+  });
+
+  let nuc_cdses = output_nuc_cds_fasta.iter().flat_map(|template| {
+    gene_map.iter_cdses().map(move |cds| {
+      (
+        "--output-nuc-cds-fasta",
+        PathBuf::from(template.replace("{cds}", &cds.name)),
+      )
+    })
+  });
+
+  let codon_alignments = output_codon_alignment.iter().flat_map(|template| {
+    gene_map.iter_cdses().map(move |cds| {
+      (
+        "--output-codon-alignment",
+        PathBuf::from(template.replace("{cds}", &cds.name)),
+      )
+    })
+  });
+
+  [
+    ("--output-fasta", output_fasta),
+    ("--output-ndjson", output_ndjson),
+    ("--output-json", output_json),
+    ("--output-csv", output_csv),
+    ("--output-tsv", output_tsv),
+    ("--output-parquet", output_parquet),
+    ("--output-arrow", output_arrow),
+    ("--output-graph", output_graph),
+    ("--output-tree", output_tree),
+    ("--output-tree-nwk", output_tree_nwk),
+    ("--output-tree-nexus", output_tree_nexus),
+    ("--output-tree-pb", output_tree_pb),
+    ("--output-jplace", output_jplace),
+    ("--output-haplotypes", output_haplotypes),
+    ("--output-codon-usage", output_codon_usage),
+    ("--output-support", output_support),
+    ("--output-alignment-band", output_alignment_band),
+    ("--output-maf", output_maf),
+    ("--output-batch-qc", output_batch_qc),
+    ("--output-html", output_html),
+    ("--output-vcf", output_vcf),
+  ]
+  .into_iter()
+  .filter_map(|(name, filepath)| filepath.clone().map(|filepath| (name, filepath)))
+  .chain(translations)
+  .chain(nuc_cdses)
+  .chain(codon_alignments)
+  .collect()
+}
+
+fn format_optional(value: Option<String>) -> String {
+  value.unwrap_or_else(|| "n/a".to_owned())
+}
+
+fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+  let mut value = bytes as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit + 1 < UNITS.len() {
+    value /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 {
+    format!("{bytes} B")
+  } else {
+    format!("{value:.1} {}", UNITS[unit])
+  }
+}
+
+fn file_size(filepath: impl AsRef<Path>) -> Option<u64> {
+  let metadata = std::fs::metadata(filepath).ok()?;
+  metadata.is_file().then_some(metadata.len())
+}
+
+/// Reads user and system CPU time of the current process from `/proc/self/stat`
+#[cfg(target_os = "linux")]
+fn cpu_time_sec() -> Option<f64> {
+  // Number of clock ticks per second in which the times are expressed (`USER_HZ`). It is fixed to 100 on all
+  // mainstream Linux platforms, and querying it would require a dependency on libc.
+  const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+  let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+  // Process name in parentheses may contain spaces, so the fields are counted from the closing parenthesis. The fields
+  // after it start from the 3rd field, while `utime` and `stime` are the 14th and 15th fields respectively.
+  let fields = stat.rsplit_once(')')?.1.split_whitespace().collect_vec();
+  let utime: u64 = fields.get(11)?.parse().ok()?;
+  let stime: u64 = fields.get(12)?.parse().ok()?;
+  Some((utime + stime) as f64 / CLOCK_TICKS_PER_SEC)
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn cpu_time_sec() -> Option<f64> {
+  None
+}
+
+/// Reads peak resident set size ("high water mark") of the current process from `/proc/self/status`
+#[cfg(target_os = "linux")]
+fn peak_rss_bytes() -> Option<u64> {
+  let status = std::fs::read_to_string("/proc/self/status").ok()?;
+  let kilobytes: u64 = status
+    .lines()
+    .find_map(|line| line.strip_prefix("VmHWM:"))?
+    .trim()
+    .strip_suffix("kB")?
+    .trim()
+    .parse()
+    .ok()?;
+  Some(kilobytes * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn peak_rss_bytes() -> Option<u64> {
+  None
+}
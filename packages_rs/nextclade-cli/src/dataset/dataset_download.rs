@@ -1,17 +1,20 @@
-use crate::cli::nextclade_cli::{NextcladeRunArgs, NextcladeRunInputArgs};
+use crate::cli::nextclade_cli::NextcladeRunInputArgs;
 use crate::cli::nextclade_dataset_get::{dataset_file_http_get, dataset_http_get};
 use crate::io::http_client::{HttpClient, ProxyConfig};
 use eyre::{eyre, ContextCompat, Report, WrapErr};
 use itertools::Itertools;
 use log::LevelFilter;
+use nextclade::analyze::phenotype::phenotype_data_load_coefficients;
 use nextclade::analyze::virus_properties::{LabelledMutationsConfig, VirusProperties};
 use nextclade::gene::gene_map::{filter_gene_map, GeneMap};
 use nextclade::io::dataset::{Dataset, DatasetFiles, DatasetMeta, DatasetsIndexJson};
 use nextclade::io::fasta::{read_one_fasta, read_one_fasta_str};
 use nextclade::io::file::create_file_or_stdout;
 use nextclade::io::fs::{ensure_dir, has_extension, read_file_to_string};
+use nextclade::io::usher_mat::{auspice_tree_from_usher_mat_path, is_usher_mat_path};
 use nextclade::run::nextclade_wasm::NextcladeParams;
 use nextclade::tree::tree::AuspiceTree;
+use nextclade::tree::tree_nwk::{auspice_tree_from_nwk_path, is_nwk_path};
 use nextclade::utils::option::OptionMapRefFallible;
 use nextclade::{make_error, make_internal_error, o};
 use rayon::iter::ParallelIterator;
@@ -25,18 +28,17 @@ use zip::ZipArchive;
 const PATHOGEN_JSON: &str = "pathogen.json";
 
 pub fn nextclade_get_inputs(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
-  if let Some(dataset_name) = run_args.inputs.dataset_name.as_ref() {
-    dataset_str_download_and_load(run_args, cdses)
-      .wrap_err_with(|| format!("When downloading dataset '{dataset_name}'"))
-  } else if let Some(input_dataset) = run_args.inputs.input_dataset.as_ref() {
+  if let Some(dataset_name) = inputs.dataset_name.as_ref() {
+    dataset_str_download_and_load(inputs, cdses).wrap_err_with(|| format!("When downloading dataset '{dataset_name}'"))
+  } else if let Some(input_dataset) = inputs.input_dataset.as_ref() {
     if input_dataset.is_file() && has_extension(input_dataset, "zip") {
-      dataset_zip_load(run_args, input_dataset, cdses)
+      dataset_zip_load(inputs, input_dataset, cdses)
         .wrap_err_with(|| format!("When loading dataset from {input_dataset:#?}"))
     } else if input_dataset.is_dir() {
-      dataset_dir_load(run_args, input_dataset, cdses)
+      dataset_dir_load(inputs, input_dataset, cdses)
         .wrap_err_with(|| format!("When loading dataset from {input_dataset:#?}"))
     } else {
       make_error!(
@@ -45,7 +47,7 @@ pub fn nextclade_get_inputs(
       )
     }
   } else {
-    dataset_individual_files_load(run_args, cdses)
+    dataset_individual_files_load(inputs, cdses)
   }
 }
 
@@ -90,8 +92,20 @@ pub fn read_from_path_or_zip(
   Ok(zip_read_str(zip, zip_filename).ok())
 }
 
+/// Reads reference tree from a file provided explicitly: either Auspice JSON, UShER mutation-annotated tree, or
+/// Newick, optionally accompanied by a node metadata file
+pub fn read_input_tree(input_tree: &Path, input_tree_metadata: &Option<PathBuf>) -> Result<AuspiceTree, Report> {
+  if is_nwk_path(input_tree) {
+    auspice_tree_from_nwk_path(input_tree, input_tree_metadata.as_ref())
+  } else if is_usher_mat_path(input_tree) {
+    auspice_tree_from_usher_mat_path(input_tree)
+  } else {
+    AuspiceTree::from_path(input_tree)
+  }
+}
+
 pub fn dataset_zip_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
   dataset_zip: impl AsRef<Path>,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
@@ -99,25 +113,31 @@ pub fn dataset_zip_load(
   let buf_file = BufReader::new(file);
   let mut zip = ZipArchive::new(buf_file)?;
 
-  let virus_properties = read_from_path_or_zip(&run_args.inputs.input_pathogen_json, &mut zip, "pathogen.json")?
+  let mut virus_properties = read_from_path_or_zip(&inputs.input_pathogen_json, &mut zip, "pathogen.json")?
     .map_ref_fallible(VirusProperties::from_str)
     .wrap_err("When reading pathogen JSON from dataset")?
     .ok_or_else(|| eyre!("Pathogen JSON must always be present in the dataset but not found."))?;
 
-  let ref_record = read_from_path_or_zip(&run_args.inputs.input_ref, &mut zip, &virus_properties.files.reference)?
+  phenotype_data_load_coefficients(&mut virus_properties, |filename| zip_read_str(&mut zip, filename))?;
+
+  let ref_record = read_from_path_or_zip(&inputs.input_ref, &mut zip, &virus_properties.files.reference)?
     .map_ref_fallible(read_one_fasta_str)
     .wrap_err("When reading reference sequence from dataset")?
     .ok_or_else(|| eyre!("Reference sequence must always be present in the dataset but not found."))?;
 
-  let gene_map = read_from_path_or_zip(&run_args.inputs.input_annotation, &mut zip, "genome_annotation.gff3")?
+  let gene_map = read_from_path_or_zip(&inputs.input_annotation, &mut zip, "genome_annotation.gff3")?
     .map_ref_fallible(GeneMap::from_str)
     .wrap_err("When reading genome annotation from dataset")?
     .map(|gene_map| filter_gene_map(gene_map, cdses))
     .unwrap_or_default();
 
-  let tree = read_from_path_or_zip(&run_args.inputs.input_tree, &mut zip, "tree.json")?
-    .map_ref_fallible(AuspiceTree::from_str)
-    .wrap_err("When reading reference tree JSON from dataset")?;
+  let tree = match &inputs.input_tree {
+    Some(input_tree) => Some(read_input_tree(input_tree, &inputs.input_tree_metadata)?),
+    None => zip_read_str(&mut zip, "tree.json")
+      .ok()
+      .map_ref_fallible(AuspiceTree::from_str)
+      .wrap_err("When reading reference tree JSON from dataset")?,
+  };
 
   Ok(NextcladeParams {
     ref_record,
@@ -140,7 +160,7 @@ pub fn dataset_dir_download(http: &mut HttpClient, dataset: &Dataset, output_dir
 }
 
 pub fn dataset_dir_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
   dataset_dir: impl AsRef<Path>,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
@@ -149,16 +169,21 @@ pub fn dataset_dir_load(
   let NextcladeRunInputArgs {
     input_ref,
     input_tree,
+    input_tree_metadata,
     input_pathogen_json,
     input_annotation,
     ..
-  } = &run_args.inputs;
+  } = inputs;
 
   let input_pathogen_json = input_pathogen_json
     .clone()
     .unwrap_or_else(|| dataset_dir.join("pathogen.json"));
 
-  let virus_properties = VirusProperties::from_path(input_pathogen_json)?;
+  let mut virus_properties = VirusProperties::from_path(input_pathogen_json)?;
+
+  phenotype_data_load_coefficients(&mut virus_properties, |filename| {
+    read_file_to_string(dataset_dir.join(filename))
+  })?;
 
   let input_ref = input_ref
     .clone()
@@ -188,8 +213,8 @@ pub fn dataset_dir_load(
         .as_ref()
         .map(|tree_json| dataset_dir.join(tree_json))
     })
-    .map_ref_fallible(AuspiceTree::from_path)
-    .wrap_err("When reading reference tree JSON")?;
+    .map_ref_fallible(|input_tree| read_input_tree(input_tree, input_tree_metadata))
+    .wrap_err("When reading reference tree")?;
 
   Ok(NextcladeParams {
     ref_record,
@@ -200,14 +225,13 @@ pub fn dataset_dir_load(
 }
 
 pub fn dataset_individual_files_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
-  match (&run_args.inputs.input_dataset, &run_args.inputs.input_ref) {
+  match (&inputs.input_dataset, &inputs.input_ref) {
     (None, None) => make_error!("When `--input-dataset` is not specified, --input-ref is required"),
     (_, Some(input_ref)) => {
-      let virus_properties = run_args
-        .inputs
+      let mut virus_properties = inputs
         .input_pathogen_json
         .as_ref()
         .and_then(|input_pathogen_json| read_file_to_string(input_pathogen_json).ok())
@@ -235,8 +259,11 @@ pub fn dataset_individual_files_load(
             },
             default_cds: None,
             cds_order_preference: vec![],
+            transl_table: None,
+            cds_transl_tables: BTreeMap::default(),
             mut_labels: LabelledMutationsConfig::default(),
             primers: vec![],
+            masked_ranges: vec![],
             qc: None,
             general_params: None,
             alignment_params: None,
@@ -250,10 +277,20 @@ pub fn dataset_individual_files_load(
           }
         });
 
+      // Coefficient files are looked up next to the pathogen JSON
+      let pathogen_json_dir = inputs
+        .input_pathogen_json
+        .as_ref()
+        .and_then(|input_pathogen_json| input_pathogen_json.parent())
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+      phenotype_data_load_coefficients(&mut virus_properties, |filename| {
+        read_file_to_string(pathogen_json_dir.join(filename))
+      })?;
+
       let ref_record = read_one_fasta(input_ref).wrap_err("When reading reference sequence")?;
 
-      let gene_map = run_args
-        .inputs
+      let gene_map = inputs
         .input_annotation
         .as_ref()
         .map_ref_fallible(GeneMap::from_path)
@@ -261,12 +298,10 @@ pub fn dataset_individual_files_load(
         .map(|gen_map| filter_gene_map(gen_map, cdses))
         .unwrap_or_default();
 
-      let tree = run_args
-        .inputs
+      let tree = inputs
         .input_tree
-        .as_ref()
-        .map_ref_fallible(AuspiceTree::from_path)
-        .wrap_err("When reading reference tree JSON")?;
+        .map_ref_fallible(|input_tree| read_input_tree(input_tree, &inputs.input_tree_metadata))
+        .wrap_err("When reading reference tree")?;
 
       Ok(NextcladeParams {
         ref_record,
@@ -301,34 +336,37 @@ pub fn read_from_path_or_url(
 }
 
 pub fn dataset_str_download_and_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
   let verbose = log::max_level() > LevelFilter::Info;
-  let mut http = HttpClient::new(&run_args.inputs.server, &ProxyConfig::default(), verbose)?;
+  let mut http = HttpClient::new(&inputs.server, &ProxyConfig::default(), verbose)?;
 
-  let name = run_args
-    .inputs
+  let name = inputs
     .dataset_name
     .as_ref()
     .expect("Dataset name is expected, but got 'None'");
 
   let dataset = dataset_http_get(&mut http, name, &None)?;
 
-  let virus_properties = read_from_path_or_url(
+  let mut virus_properties = read_from_path_or_url(
     &mut http,
     &dataset,
-    &run_args.inputs.input_pathogen_json,
+    &inputs.input_pathogen_json,
     &Some(o!("pathogen.json")),
   )?
   .map_ref_fallible(VirusProperties::from_str)
   .wrap_err("When reading pathogen JSON from dataset")?
   .ok_or_else(|| eyre!("Required file not found in dataset: 'pathogen.json'. Please report it to dataset authors."))?;
 
+  phenotype_data_load_coefficients(&mut virus_properties, |filename| {
+    dataset_file_http_get(&mut http, &dataset, filename)
+  })?;
+
   let ref_record = read_from_path_or_url(
     &mut http,
     &dataset,
-    &run_args.inputs.input_ref,
+    &inputs.input_ref,
     &Some(dataset.files.reference.clone()),
   )?
   .map_ref_fallible(read_one_fasta_str)?
@@ -337,7 +375,7 @@ pub fn dataset_str_download_and_load(
   let gene_map = read_from_path_or_url(
     &mut http,
     &dataset,
-    &run_args.inputs.input_annotation,
+    &inputs.input_annotation,
     &dataset.files.genome_annotation,
   )?
   .map_ref_fallible(GeneMap::from_str)
@@ -345,14 +383,12 @@ pub fn dataset_str_download_and_load(
   .map(|gene_map| filter_gene_map(gene_map, cdses))
   .unwrap_or_default();
 
-  let tree = read_from_path_or_url(
-    &mut http,
-    &dataset,
-    &run_args.inputs.input_tree,
-    &dataset.files.tree_json,
-  )?
-  .map_ref_fallible(AuspiceTree::from_str)
-  .wrap_err("When reading reference tree from dataset")?;
+  let tree = match &inputs.input_tree {
+    Some(input_tree) => Some(read_input_tree(input_tree, &inputs.input_tree_metadata)?),
+    None => read_from_path_or_url(&mut http, &dataset, &None::<PathBuf>, &dataset.files.tree_json)?
+      .map_ref_fallible(AuspiceTree::from_str)
+      .wrap_err("When reading reference tree from dataset")?,
+  };
 
   Ok(NextcladeParams {
     ref_record,
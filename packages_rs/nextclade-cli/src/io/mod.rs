@@ -1 +1,2 @@
 pub mod http_client;
+pub mod output_sink;
@@ -0,0 +1,222 @@
+use eyre::{eyre, Report};
+use itertools::Itertools;
+use log::warn;
+use nextclade::io::fs::add_extension;
+use nextclade::make_error;
+use nextclade::utils::error::report_to_string;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension of the marker file which is placed next to an output file which could not be written completely
+const PARTIAL_OUTPUT_MARKER_EXT: &str = "incomplete";
+
+/// Failure to write one of the outputs
+pub struct OutputError {
+  pub name: &'static str,
+  pub filepath: PathBuf,
+  pub report: Report,
+}
+
+/// Wraps writer of one of the outputs, such that a failure to write this output does not affect writing of other
+/// outputs.
+///
+/// After the first failure, the underlying writer is closed, all subsequent writes are ignored, and the error is kept
+/// to be reported at the end of the run.
+///
+/// Writes are performed synchronously, on the thread which owns the sink: a slow output still delays the outputs
+/// written after it, only failures are isolated.
+pub struct OutputSink<W> {
+  name: &'static str,
+  filepath: PathBuf,
+  writer: Option<W>,
+  error: Option<Report>,
+}
+
+impl<W> OutputSink<W> {
+  pub fn new(name: &'static str, filepath: impl AsRef<Path>, writer: W) -> Self {
+    Self {
+      name,
+      filepath: filepath.as_ref().to_owned(),
+      writer: Some(writer),
+      error: None,
+    }
+  }
+
+  pub fn write(&mut self, f: impl FnOnce(&mut W) -> Result<(), Report>) {
+    if let Some(writer) = &mut self.writer {
+      if let Err(report) = f(writer) {
+        warn!(
+          "Failed to write output {} to {:#?}: {}. This output will not be written further, but other outputs are not affected.",
+          self.name,
+          self.filepath,
+          report_to_string(&report)
+        );
+        self.writer = None;
+        self.error = Some(report);
+      }
+    }
+  }
+
+  /// Closes the writer and returns the error, if writing has failed at any point
+  pub fn finish(&mut self, f: impl FnOnce(&mut W) -> Result<(), Report>) -> Option<OutputError> {
+    self.write(f);
+    self.writer = None;
+    self.error.take().map(|report| OutputError {
+      name: self.name,
+      filepath: self.filepath.clone(),
+      report,
+    })
+  }
+}
+
+/// Writes an output which is produced at once, at the end of the run. The error, if any, is collected rather than
+/// returned, so that the remaining outputs can still be written.
+pub fn write_output_isolated(
+  errors: &mut Vec<OutputError>,
+  name: &'static str,
+  filepath: impl AsRef<Path>,
+  f: impl FnOnce() -> Result<(), Report>,
+) {
+  let filepath = filepath.as_ref();
+  if let Err(report) = f() {
+    warn!(
+      "Failed to write output {name} to {filepath:#?}: {}. Other outputs are not affected.",
+      report_to_string(&report)
+    );
+    errors.push(OutputError {
+      name,
+      filepath: filepath.to_owned(),
+      report,
+    });
+  }
+}
+
+/// Records failure of a step which several outputs depend on, such that each of the requested outputs among them is
+/// reported as failed, while the remaining outputs can still be written.
+pub fn fail_outputs_isolated(
+  errors: &mut Vec<OutputError>,
+  outputs: &[(&'static str, Option<&Path>)],
+  report: &Report,
+) {
+  let cause = report_to_string(report);
+  for &(name, filepath) in outputs {
+    if let Some(filepath) = filepath {
+      warn!("Failed to write output {name} to {filepath:#?}: {cause}. Other outputs are not affected.");
+      errors.push(OutputError {
+        name,
+        filepath: filepath.to_owned(),
+        report: eyre!(cause.clone()),
+      });
+    }
+  }
+}
+
+/// Marks each of the failed outputs with a marker file next to it and reports all failures at once
+pub fn report_output_errors(errors: &[OutputError]) -> Result<(), Report> {
+  if errors.is_empty() {
+    return Ok(());
+  }
+
+  let details = errors
+    .iter()
+    .map(|OutputError { name, filepath, report }| {
+      let cause = report_to_string(report);
+      let marker = write_partial_output_marker(name, filepath, &cause);
+      format!("  - {name} ({filepath:#?}): {cause}{marker}")
+    })
+    .join("\n");
+
+  make_error!(
+    "Failed to write {} output(s):\n{details}\n\nAll other outputs were written successfully.",
+    errors.len()
+  )
+}
+
+/// Creates a file `<output>.incomplete` containing the error message. Outputs written to standard output are skipped.
+fn write_partial_output_marker(name: &str, filepath: &Path, cause: &str) -> String {
+  if filepath == Path::new("-") {
+    return String::new();
+  }
+
+  let marker_path = add_extension(filepath, PARTIAL_OUTPUT_MARKER_EXT);
+  let content = format!("Output {name} is incomplete: {cause}\n");
+  match fs::write(&marker_path, content) {
+    Ok(()) => format!(". The output is marked as incomplete with {marker_path:#?}"),
+    Err(err) => format!(". Unable to create incomplete output marker {marker_path:#?}: {err}"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nextclade::o;
+  use pretty_assertions::assert_eq;
+
+  fn fail_on_second_write(writer: &mut Vec<String>, record: &str) -> Result<(), Report> {
+    if writer.len() == 1 {
+      return make_error!("Disk full");
+    }
+    writer.push(record.to_owned());
+    Ok(())
+  }
+
+  #[test]
+  fn failure_of_one_sink_does_not_affect_others() {
+    let mut failing = OutputSink::new("--output-tsv", "-", vec![]);
+    let mut healthy = OutputSink::new("--output-csv", "-", vec![]);
+
+    for record in ["seq1", "seq2", "seq3"] {
+      failing.write(|writer| fail_on_second_write(writer, record));
+      healthy.write(|writer: &mut Vec<String>| {
+        writer.push(record.to_owned());
+        Ok(())
+      });
+    }
+
+    let mut written = vec![];
+    let healthy_error = healthy.finish(|writer| {
+      written = writer.clone();
+      Ok(())
+    });
+    let failing_error = failing.finish(|_| Ok(()));
+
+    assert_eq!(written, vec!["seq1", "seq2", "seq3"]);
+    assert!(healthy_error.is_none());
+    let failing_error = failing_error.expect("Expected the failing sink to report an error");
+    assert_eq!(failing_error.name, "--output-tsv");
+    assert_eq!(report_to_string(&failing_error.report), "Disk full");
+  }
+
+  #[test]
+  fn failure_of_one_isolated_output_does_not_affect_others() {
+    let mut errors = vec![];
+    let mut written = vec![];
+
+    write_output_isolated(&mut errors, "--output-tree", "-", || make_error!("Disk full"));
+    write_output_isolated(&mut errors, "--output-tree-nwk", "-", || {
+      written.push("--output-tree-nwk");
+      Ok(())
+    });
+    fail_outputs_isolated(
+      &mut errors,
+      &[("--output-tree-nexus", Some(Path::new("-"))), ("--output-graph", None)],
+      &eyre!("Unable to build the tree"),
+    );
+
+    assert_eq!(written, vec!["--output-tree-nwk"]);
+    assert_eq!(
+      errors
+        .iter()
+        .map(|error| (error.name, report_to_string(&error.report)))
+        .collect::<Vec<_>>(),
+      vec![
+        ("--output-tree", o!("Disk full")),
+        ("--output-tree-nexus", o!("Unable to build the tree")),
+      ]
+    );
+    assert_eq!(
+      report_to_string(&report_output_errors(&errors).unwrap_err()),
+      "Failed to write 2 output(s):\n  - --output-tree (\"-\"): Disk full\n  - --output-tree-nexus (\"-\"): Unable to build the tree\n\nAll other outputs were written successfully."
+    );
+  }
+}
@@ -1,6 +1,7 @@
 pub mod cli;
 pub mod dataset;
 pub mod io;
+pub mod plugin;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1 @@
+pub mod sequence_plugin;
@@ -0,0 +1,279 @@
+use eyre::Report;
+use itertools::Itertools;
+use nextclade::make_error;
+use nextclade::tree::tree::CladeNodeAttrKeyDesc;
+use nextclade::types::outputs::NextcladeOutputs;
+use std::path::Path;
+
+#[cfg(feature = "plugins")]
+use eyre::{eyre, WrapErr};
+#[cfg(feature = "plugins")]
+use nextclade::io::fs::read_file_to_string;
+
+/// Name of the function of the plugin script which returns names of the computed fields
+#[cfg(feature = "plugins")]
+const FN_COLUMNS: &str = "columns";
+
+/// Name of the function of the plugin script which computes the fields from the analysis result of a sequence
+#[cfg(feature = "plugins")]
+const FN_PROCESS: &str = "process";
+
+/// Plugin script, which computes additional fields from the analysis result of each sequence (`--plugin-script`).
+///
+/// The computed fields are added to the custom node attributes of the analysis result, so that they are written into
+/// the outputs in the same way as the attributes of the reference tree nodes (e.g. as additional columns of the
+/// results table).
+pub struct SequencePlugin {
+  columns: Vec<String>,
+  #[cfg(feature = "plugins")]
+  engine: rhai::Engine,
+  #[cfg(feature = "plugins")]
+  ast: rhai::AST,
+}
+
+impl SequencePlugin {
+  #[cfg(feature = "plugins")]
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let script = read_file_to_string(filepath)?;
+    Self::from_script(&script).wrap_err_with(|| format!("When loading plugin script {filepath:#?}"))
+  }
+
+  #[cfg(feature = "plugins")]
+  pub fn from_script(script: &str) -> Result<Self, Report> {
+    let engine = rhai::Engine::new();
+    let ast = engine
+      .compile(script)
+      .map_err(|err| eyre!("{err}"))
+      .wrap_err("When compiling plugin script")?;
+
+    let columns = engine
+      .call_fn::<rhai::Array>(&mut rhai::Scope::new(), &ast, FN_COLUMNS, ())
+      .map_err(|err| eyre!("{err}"))
+      .wrap_err_with(|| format!("When calling function `{FN_COLUMNS}()` of plugin script"))?
+      .into_iter()
+      .map(|column| {
+        column.into_string().map_err(|type_name| {
+          eyre!("Expected function `{FN_COLUMNS}()` of plugin script to return an array of strings, but found an element of type '{type_name}'")
+        })
+      })
+      .collect::<Result<Vec<String>, Report>>()?;
+
+    Ok(Self { columns, engine, ast })
+  }
+
+  #[cfg(not(feature = "plugins"))]
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    make_error!(
+      "Plugin script {:#?} is provided, but this build of Nextclade does not support plugins. Build Nextclade with cargo feature `plugins` to enable them.",
+      filepath.as_ref()
+    )
+  }
+
+  /// Checks that none of the computed fields has the same name as one of the attributes of the reference tree nodes.
+  /// Both are written into the same custom node attributes, so the computed value would replace the attribute.
+  pub fn check_clade_attr_conflicts(&self, clade_attr_descs: &[CladeNodeAttrKeyDesc]) -> Result<(), Report> {
+    check_clade_attr_conflicts(&self.columns, clade_attr_descs)
+  }
+
+  /// Descriptions of the computed fields, in the form of the reference tree node attribute descriptions
+  pub fn attr_descs(&self) -> Vec<CladeNodeAttrKeyDesc> {
+    self
+      .columns
+      .iter()
+      .map(|column| CladeNodeAttrKeyDesc {
+        name: column.clone(),
+        display_name: column.clone(),
+        description: None,
+        hide_in_web: false,
+        other: serde_json::Value::Object(serde_json::Map::new()),
+      })
+      .collect()
+  }
+
+  /// Runs the plugin on the analysis result of a sequence and adds the computed fields to it. Fields for which the
+  /// plugin returns `()` are left empty.
+  #[cfg(feature = "plugins")]
+  pub fn process(&self, outputs: &mut NextcladeOutputs) -> Result<(), Report> {
+    let result = rhai::serde::to_dynamic(&*outputs)
+      .map_err(|err| eyre!("{err}"))
+      .wrap_err("When converting analysis result for plugin script")?;
+
+    let fields = self
+      .engine
+      .call_fn::<rhai::Map>(&mut rhai::Scope::new(), &self.ast, FN_PROCESS, (result,))
+      .map_err(|err| eyre!("{err}"))
+      .wrap_err_with(|| format!("When calling function `{FN_PROCESS}()` of plugin script"))?;
+
+    for (key, value) in fields {
+      if !self.columns.iter().any(|column| column == key.as_str()) {
+        return make_error!(
+          "Function `{FN_PROCESS}()` of plugin script returned field '{key}', which is not listed by function `{FN_COLUMNS}()`"
+        );
+      }
+      if outputs.custom_node_attributes.contains_key(key.as_str()) {
+        return make_error!(
+          "Function `{FN_PROCESS}()` of plugin script returned field '{key}', which has the same name as one of the clade attributes of the reference tree"
+        );
+      }
+      if !value.is_unit() {
+        outputs
+          .custom_node_attributes
+          .insert(key.to_string(), value.to_string());
+      }
+    }
+
+    Ok(())
+  }
+
+  #[cfg(not(feature = "plugins"))]
+  pub fn process(&self, _outputs: &mut NextcladeOutputs) -> Result<(), Report> {
+    Ok(())
+  }
+}
+
+fn check_clade_attr_conflicts(columns: &[String], clade_attr_descs: &[CladeNodeAttrKeyDesc]) -> Result<(), Report> {
+  let conflicts = columns
+    .iter()
+    .filter(|column| clade_attr_descs.iter().any(|desc| &desc.name == *column))
+    .map(|column| format!("'{column}'"))
+    .collect_vec();
+
+  if !conflicts.is_empty() {
+    return make_error!(
+      "Plugin script computes fields which have the same names as the clade attributes of the reference tree: {}. Rename these fields in the plugin script.",
+      conflicts.join(", ")
+    );
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nextclade::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+
+  fn clade_attr_desc(name: &str) -> CladeNodeAttrKeyDesc {
+    CladeNodeAttrKeyDesc {
+      name: name.to_owned(),
+      display_name: name.to_owned(),
+      description: None,
+      hide_in_web: false,
+      other: serde_json::Value::Object(serde_json::Map::new()),
+    }
+  }
+
+  #[test]
+  fn rejects_fields_conflicting_with_clade_attributes() {
+    let columns = ["GC content", "Nextclade_pango", "lineage"].map(str::to_owned);
+    let descs = [clade_attr_desc("Nextclade_pango"), clade_attr_desc("lineage")];
+    assert_eq!(
+      report_to_string(&check_clade_attr_conflicts(&columns, &descs).unwrap_err()),
+      "Plugin script computes fields which have the same names as the clade attributes of the reference tree: 'Nextclade_pango', 'lineage'. Rename these fields in the plugin script."
+    );
+  }
+
+  #[test]
+  fn accepts_fields_not_conflicting_with_clade_attributes() -> Result<(), Report> {
+    let columns = ["GC content"].map(str::to_owned);
+    check_clade_attr_conflicts(&columns, &[clade_attr_desc("Nextclade_pango")])
+  }
+
+  #[cfg(feature = "plugins")]
+  mod plugins {
+    use super::*;
+    use nextclade::alphabet::nuc::from_nuc_seq;
+    use nextclade::io::fasta::FastaRecord;
+    use nextclade::o;
+    use nextclade::run::nextclade_wasm::{Nextclade, NextcladeParams, NextcladeParamsRaw};
+    use nextclade::run::params::NextcladeInputParamsOptional;
+    use nextclade::utils::error::report_to_string;
+    use nextclade::utils::random::random_nuc_seq;
+    use pretty_assertions::assert_eq;
+    use std::collections::BTreeMap;
+
+    const PATHOGEN_JSON: &str = r#"{
+      "schemaVersion": "3.0.0",
+      "files": { "reference": "reference.fasta", "pathogenJson": "pathogen.json" }
+    }"#;
+
+    const SCRIPT: &str = r#"
+      fn columns() { ["substitutions", "note"] }
+      fn process(result) { #{ substitutions: result.totalSubstitutions, note: () } }
+    "#;
+
+    fn analysis_result() -> Result<NextcladeOutputs, Report> {
+      let ref_seq = from_nuc_seq(&random_nuc_seq(&mut 42, 1000));
+      let params = NextcladeParams::from_raw(NextcladeParamsRaw {
+        ref_seq: format!(">ref\n{ref_seq}\n"),
+        gene_map: None,
+        tree: None,
+        virus_properties: PATHOGEN_JSON.to_owned(),
+      })?;
+      let nextclade = Nextclade::new(params, &NextcladeInputParamsOptional::default())?;
+      let record = FastaRecord {
+        seq_name: o!("qry"),
+        seq: ref_seq,
+        index: 0,
+      };
+      Ok(nextclade.run(&record)?.analysis_result)
+    }
+
+    #[test]
+    fn adds_computed_fields() -> Result<(), Report> {
+      let plugin = SequencePlugin::from_script(SCRIPT)?;
+      assert_eq!(
+        plugin.attr_descs().into_iter().map(|desc| desc.name).collect_vec(),
+        vec!["substitutions", "note"]
+      );
+
+      let mut outputs = analysis_result()?;
+      plugin.process(&mut outputs)?;
+      assert_eq!(
+        outputs.custom_node_attributes,
+        BTreeMap::from([(o!("substitutions"), o!("0"))])
+      );
+      Ok(())
+    }
+
+    #[test]
+    fn rejects_columns_of_wrong_type() {
+      let plugin = SequencePlugin::from_script("fn columns() { [\"note\", 42] }");
+      assert_eq!(
+        report_to_string(&plugin.err().unwrap()),
+        "Expected function `columns()` of plugin script to return an array of strings, but found an element of type 'i64'"
+      );
+    }
+
+    #[test]
+    fn rejects_unlisted_field() -> Result<(), Report> {
+      let plugin = SequencePlugin::from_script(
+        r#"
+          fn columns() { ["note"] }
+          fn process(result) { #{ other: 1 } }
+        "#,
+      )?;
+      let mut outputs = analysis_result()?;
+      assert_eq!(
+        report_to_string(&plugin.process(&mut outputs).unwrap_err()),
+        "Function `process()` of plugin script returned field 'other', which is not listed by function `columns()`"
+      );
+      Ok(())
+    }
+
+    #[test]
+    fn rejects_field_conflicting_with_clade_attribute() -> Result<(), Report> {
+      let plugin = SequencePlugin::from_script(SCRIPT)?;
+      let mut outputs = analysis_result()?;
+      outputs.custom_node_attributes.insert(o!("substitutions"), o!("7"));
+      assert_eq!(
+        report_to_string(&plugin.process(&mut outputs).unwrap_err()),
+        "Function `process()` of plugin script returned field 'substitutions', which has the same name as one of the clade attributes of the reference tree"
+      );
+      assert_eq!(outputs.custom_node_attributes["substitutions"], "7");
+      Ok(())
+    }
+  }
+}
@@ -5,7 +5,6 @@ use nextclade::io::fasta::{FastaReader, FastaRecord};
 use nextclade::io::json::json_parse;
 use nextclade::sort::minimizer_index::MinimizerIndexJson;
 use nextclade::sort::minimizer_search::{run_minimizer_search, MinimizerSearchRecord};
-use nextclade::sort::params::NextcladeSeqSortParams;
 use nextclade::utils::datetime::date_now;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -49,7 +48,8 @@ impl NextcladeSeqAutodetectWasm {
   pub fn autodetect(&self, qry_fasta_str: &str, callback: &js_sys::Function) -> Result<(), JsError> {
     let mut reader = jserr(FastaReader::from_str(&qry_fasta_str).wrap_err_with(|| "When creating fasta reader"))?;
 
-    let search_params = NextcladeSeqSortParams::default();
+    // Use search parameters shipped with the minimizer index, if any
+    let search_params = None;
 
     let mut batch = vec![];
     let mut last_flush = date_now();
@@ -0,0 +1,83 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nextclade::alphabet::aa::Aa;
+use nextclade::analyze::aa_sub::AaSub;
+use nextclade::coord::position::AaRefPosition;
+use nextclade::utils::interned_str::InternedStr;
+use serde::{Deserialize, Serialize};
+
+const CDS_NAMES: &[&str] = &["ORF1a", "ORF1b", "S", "ORF3a", "E", "M", "ORF6", "ORF7a", "ORF8", "N"];
+
+/// Aminoacid substitution with an owned CDS name, as it was before the CDS names were interned. Serves as a baseline.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AaSubWithString {
+  cds_name: String,
+  pos: AaRefPosition,
+  ref_aa: Aa,
+  qry_aa: Aa,
+}
+
+/// Aminoacid substitutions, similar to what is produced for a large batch of sequences. Each of the substitutions
+/// refers to one of a few CDS names, which are shared rather than copied. As in the analysis, the names are interned
+/// once per CDS, and then cloned.
+fn create_substitutions(n: usize) -> Vec<AaSub> {
+  let cds_names = CDS_NAMES.iter().map(|&name| InternedStr::new(name)).collect::<Vec<_>>();
+  (0..n)
+    .map(|i| AaSub {
+      cds_name: cds_names[i % cds_names.len()].clone(),
+      pos: (i % 1000).into(),
+      ref_aa: Aa::A,
+      qry_aa: Aa::V,
+    })
+    .collect()
+}
+
+fn create_substitutions_with_string(n: usize) -> Vec<AaSubWithString> {
+  (0..n)
+    .map(|i| AaSubWithString {
+      cds_name: CDS_NAMES[i % CDS_NAMES.len()].to_owned(),
+      pos: (i % 1000).into(),
+      ref_aa: Aa::A,
+      qry_aa: Aa::V,
+    })
+    .collect()
+}
+
+pub fn bench_aa_mutations(c: &mut Criterion) {
+  let n = black_box(100_000);
+  let interned = create_substitutions(n);
+  let string = create_substitutions_with_string(n);
+  let json = serde_json::to_string(&interned).unwrap();
+
+  let mut group = c.benchmark_group("aa_mutations");
+  group.throughput(Throughput::Elements(n as u64));
+
+  group.bench_function(BenchmarkId::new("create", "string"), |b| {
+    b.iter(|| create_substitutions_with_string(n));
+  });
+  group.bench_function(BenchmarkId::new("create", "interned"), |b| {
+    b.iter(|| create_substitutions(n));
+  });
+
+  group.bench_function(BenchmarkId::new("clone", "string"), |b| b.iter(|| string.clone()));
+  group.bench_function(BenchmarkId::new("clone", "interned"), |b| b.iter(|| interned.clone()));
+
+  group.bench_function(BenchmarkId::new("serialize", "string"), |b| {
+    b.iter(|| serde_json::to_string(&string).unwrap());
+  });
+  group.bench_function(BenchmarkId::new("serialize", "interned"), |b| {
+    b.iter(|| serde_json::to_string(&interned).unwrap());
+  });
+
+  group.bench_function(BenchmarkId::new("deserialize", "string"), |b| {
+    b.iter(|| serde_json::from_str::<Vec<AaSubWithString>>(&json).unwrap());
+  });
+  group.bench_function(BenchmarkId::new("deserialize", "interned"), |b| {
+    b.iter(|| serde_json::from_str::<Vec<AaSub>>(&json).unwrap());
+  });
+
+  group.finish();
+}
+
+criterion_group!(benches, bench_aa_mutations);
+criterion_main!(benches);
@@ -0,0 +1,90 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nextclade::align::align::align_nuc;
+use nextclade::align::band_2d::simple_stripes;
+use nextclade::align::gap_open::get_gap_open_close_scores_flat;
+use nextclade::align::params::AlignPairwiseParams;
+use nextclade::align::score_matrix::score_matrix;
+use nextclade::align::score_simd::{qry_gap_scores, qry_gap_scores_scalar};
+use nextclade::align::seed_match2::CodonSpacedIndex;
+use nextclade::alphabet::nuc::Nuc;
+use nextclade::utils::random::random_nuc_seq;
+
+const GENOME_LEN: usize = 30_000;
+
+pub fn bench_score_kernels(c: &mut Criterion) {
+  let up = black_box((0..GENOME_LEN as i32).collect::<Vec<_>>());
+  let mut gaps = black_box((0..GENOME_LEN as i32).map(|x| x % 7 - 3).collect::<Vec<_>>());
+  let mut extended = vec![0; GENOME_LEN];
+
+  let mut group = c.benchmark_group("score_kernels");
+  group.throughput(Throughput::Elements(GENOME_LEN as u64));
+
+  group.bench_function("qry_gap_scores_scalar", |bench| {
+    bench.iter(|| qry_gap_scores_scalar(&mut gaps, &mut extended, &up, 0, 6));
+  });
+  group.bench_function("qry_gap_scores", |bench| {
+    bench.iter(|| qry_gap_scores(&mut gaps, &mut extended, &up, 0, 6));
+  });
+
+  group.finish();
+}
+
+pub fn bench_score_matrix(c: &mut Criterion) {
+  let params = AlignPairwiseParams::default();
+  let ref_seq = black_box(random_nuc_seq(&mut 42, GENOME_LEN));
+  let qry_seq = black_box(mutate(&ref_seq));
+  let gap_open_close = get_gap_open_close_scores_flat(&ref_seq, &params);
+
+  let mut group = c.benchmark_group("score_matrix");
+  for band_width in [16, 64] {
+    let stripes = simple_stripes(0, band_width, ref_seq.len(), qry_seq.len());
+    group.throughput(Throughput::Elements((ref_seq.len() * (2 * band_width + 1)) as u64));
+    group.bench_with_input(BenchmarkId::from_parameter(band_width), &stripes, |bench, stripes| {
+      bench.iter(|| score_matrix(&qry_seq, &ref_seq, &gap_open_close, stripes, &params, None).unwrap());
+    });
+  }
+  group.finish();
+}
+
+/// Aligns a query end to end, including seed matching and backtrace, to see how much the kernels above contribute to
+/// the whole alignment
+pub fn bench_align(c: &mut Criterion) {
+  let params = AlignPairwiseParams::default();
+  let ref_seq = black_box(random_nuc_seq(&mut 42, GENOME_LEN));
+  let qry_seq = black_box(mutate(&ref_seq));
+  let gap_open_close = get_gap_open_close_scores_flat(&ref_seq, &params);
+  let seed_index = CodonSpacedIndex::from_sequence(&ref_seq);
+
+  let mut group = c.benchmark_group("align");
+  group.throughput(Throughput::Elements(GENOME_LEN as u64));
+  group.bench_function("align_nuc", |bench| {
+    bench.iter(|| {
+      align_nuc(
+        0,
+        "query",
+        &qry_seq,
+        &ref_seq,
+        &seed_index,
+        None,
+        &gap_open_close,
+        &params,
+      )
+      .unwrap()
+    });
+  });
+  group.finish();
+}
+
+/// Introduces substitutions, a deletion and a stretch of missing data, as in a typical query sequence
+fn mutate(ref_seq: &[Nuc]) -> Vec<Nuc> {
+  let mut qry_seq = ref_seq.to_vec();
+  for pos in (100..qry_seq.len()).step_by(997) {
+    qry_seq[pos] = if qry_seq[pos] == Nuc::A { Nuc::T } else { Nuc::A };
+  }
+  qry_seq[5000..5200].fill(Nuc::N);
+  qry_seq.drain(20_000..20_009);
+  qry_seq
+}
+
+criterion_group!(benches, bench_score_kernels, bench_score_matrix, bench_align);
+criterion_main!(benches);
@@ -25,7 +25,7 @@ pub fn bench_seed_alignment(c: &mut Criterion) {
   group.bench_function("seed_match", |b| {
     b.iter(|| {
       let SeedMatchesResult { seed_matches, .. } =
-        get_seed_matches_maybe_reverse_complement(&qry_seq, &ref_seq, &seed_index, &params).unwrap();
+        get_seed_matches_maybe_reverse_complement(&qry_seq, &ref_seq, &seed_index, None, &params).unwrap();
 
       create_alignment_band(
         &seed_matches,
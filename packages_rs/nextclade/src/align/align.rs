@@ -1,10 +1,15 @@
+use crate::align::alignment_diagnostics::AlignmentStats;
 use crate::align::backtrace::{backtrace, AlignmentOutput};
 use crate::align::band_2d::Stripe;
 use crate::align::band_2d::{full_matrix, simple_stripes};
-use crate::align::params::AlignPairwiseParams;
-use crate::align::score_matrix::{score_matrix, ScoreMatrixResult};
+use crate::align::checkpointed_backtrace::{align_pairwise_checkpointed, full_matrix_memory};
+use crate::align::local_alignment::clip_to_local_alignment;
+use crate::align::params::{AlignPairwiseParams, AlignmentAlgorithm};
+use crate::align::score_matrix::{score_matrix, ScoreDropCheck, ScoreMatrixResult};
 use crate::align::seed_alignment::create_alignment_band;
 use crate::align::seed_match2::{get_seed_matches_maybe_reverse_complement, CodonSpacedIndex, SeedMatchesResult};
+use crate::align::seed_minimizer::MinimizerSeedIndex;
+use crate::align::wfa::align_wfa;
 use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
@@ -19,21 +24,63 @@ fn align_pairwise<T: Letter<T>>(
   gap_open_close: &[i32],
   params: &AlignPairwiseParams,
   stripes: &[Stripe],
-) -> AlignmentOutput<T> {
+  score_drop_check: Option<ScoreDropCheck>,
+) -> Result<AlignmentOutput<T>, Report> {
   trace!("Align pairwise: started. Params: {params:?}");
 
-  let ScoreMatrixResult { scores, paths } = score_matrix(qry_seq, ref_seq, gap_open_close, stripes, params);
+  let memory_required = full_matrix_memory(stripes);
+  if memory_required > params.alignment_memory_budget_mb * 1024 * 1024 {
+    trace!("Align pairwise: alignment matrix requires {memory_required} bytes, which exceeds the memory budget of {} MB. Using checkpointed backtrace.", params.alignment_memory_budget_mb);
+    return align_pairwise_checkpointed(qry_seq, ref_seq, gap_open_close, params, stripes, score_drop_check);
+  }
+
+  let ScoreMatrixResult { scores, paths } =
+    score_matrix(qry_seq, ref_seq, gap_open_close, stripes, params, score_drop_check)?;
 
-  backtrace(qry_seq, ref_seq, &scores, &paths)
+  Ok(backtrace(qry_seq, ref_seq, &scores, &paths))
 }
 
 /// align nucleotide sequences via seed alignment and banded smith watermann without penalizing terminal gaps
+///
+/// In local alignment mode (`params.local_alignment`), the poorly aligning ends of the query are clipped afterwards.
 pub fn align_nuc(
   index: usize,
   seq_name: &str,
   qry_seq: &[Nuc],
   ref_seq: &[Nuc],
   seed_index: &CodonSpacedIndex,
+  minimizer_index: Option<&MinimizerSeedIndex>,
+  gap_open_close: &[i32],
+  params: &AlignPairwiseParams,
+) -> Result<AlignmentOutput<Nuc>, Report> {
+  let mut alignment = align_nuc_global(
+    index,
+    seq_name,
+    qry_seq,
+    ref_seq,
+    seed_index,
+    minimizer_index,
+    gap_open_close,
+    params,
+  )?;
+
+  if params.local_alignment {
+    let (clipped_begin, clipped_end) = clip_to_local_alignment(&mut alignment.qry_seq, &mut alignment.ref_seq, params);
+    if clipped_begin > 0 || clipped_end > 0 {
+      info!("When processing sequence #{index} '{seq_name}': In nucleotide alignment: Local alignment: clipped {clipped_begin} nucleotides at the beginning and {clipped_end} nucleotides at the end of the sequence");
+    }
+  }
+
+  Ok(alignment)
+}
+
+fn align_nuc_global(
+  index: usize,
+  seq_name: &str,
+  qry_seq: &[Nuc],
+  ref_seq: &[Nuc],
+  seed_index: &CodonSpacedIndex,
+  minimizer_index: Option<&MinimizerSeedIndex>,
   gap_open_close: &[i32],
   params: &AlignPairwiseParams,
 ) -> Result<AlignmentOutput<Nuc>, Report> {
@@ -46,11 +93,34 @@ pub fn align_nuc(
     );
   }
 
+  if params.alignment_algorithm == AlignmentAlgorithm::Wfa {
+    let max_edits = (params.wfa_max_divergence * max(qry_len, ref_len) as f64).ceil() as usize;
+    if let Some(alignment) = align_wfa(qry_seq, ref_seq, max_edits, params) {
+      return Ok(alignment);
+    }
+    info!("When processing sequence #{index} '{seq_name}': In nucleotide alignment: Wavefront alignment exceeded {max_edits} differences. Falling back to banded alignment.");
+  }
+
   if ref_len + qry_len < (20 * params.kmer_length) {
     // for very short sequences, use full square
     let stripes = full_matrix(ref_len, qry_len);
     trace!("When processing sequence #{index} '{seq_name}': In nucleotide alignment: Band construction: short sequences, using full matrix");
-    return Ok(align_pairwise(qry_seq, ref_seq, gap_open_close, params, &stripes));
+    let mut alignment = align_pairwise(
+      qry_seq,
+      ref_seq,
+      gap_open_close,
+      params,
+      &stripes,
+      ScoreDropCheck::from_params(params),
+    )?;
+    alignment.stats = AlignmentStats {
+      num_seed_matches: 0,
+      num_attempts: 1,
+      band_width: band_width(&stripes),
+      cells_computed: band_cells(&stripes),
+    };
+    alignment.band = stripes;
+    return Ok(alignment);
   }
 
   // otherwise, determine seed matches roughly regularly spaced along the query sequence
@@ -58,7 +128,7 @@ pub fn align_nuc(
     qry_seq,
     seed_matches,
     is_reverse_complement,
-  } = get_seed_matches_maybe_reverse_complement(qry_seq, ref_seq, seed_index, params)
+  } = get_seed_matches_maybe_reverse_complement(qry_seq, ref_seq, seed_index, minimizer_index, params)
     .wrap_err("When calculating seed matches")?;
 
   let mut terminal_bandwidth = params.terminal_bandwidth as isize;
@@ -79,7 +149,16 @@ pub fn align_nuc(
     return make_error!("Alignment matrix size {band_area} exceeds maximum value {max_band_area}. The threshold can be adjusted using CLI flag '--max-band-area' or using 'maxBandArea' field in the dataset's pathogen.json");
   }
 
-  let mut alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, params, &stripes);
+  let mut alignment = align_pairwise(
+    &qry_seq,
+    ref_seq,
+    gap_open_close,
+    params,
+    &stripes,
+    ScoreDropCheck::from_params(params),
+  )?;
+  let mut num_attempts = 1;
+  let mut cells_computed = band_cells(&stripes);
 
   while alignment.hit_boundary && attempt < params.max_alignment_attempts {
     info!("When processing sequence #{index} '{seq_name}': In nucleotide alignment: Band boundary is hit on attempt {}. Retrying with relaxed parameters. Alignment score was: {}", attempt+1, alignment.alignment_score);
@@ -89,7 +168,8 @@ pub fn align_nuc(
     minimal_bandwidth = max(2 * minimal_bandwidth, 1);
     attempt += 1;
     // make new band
-    (stripes, band_area) = create_alignment_band(
+    let new_stripes;
+    (new_stripes, band_area) = create_alignment_band(
       &seed_matches,
       qry_len as isize,
       ref_len as isize,
@@ -102,7 +182,17 @@ pub fn align_nuc(
       break;
     }
     // realign
-    alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, params, &stripes);
+    stripes = new_stripes;
+    alignment = align_pairwise(
+      &qry_seq,
+      ref_seq,
+      gap_open_close,
+      params,
+      &stripes,
+      ScoreDropCheck::from_params(params),
+    )?;
+    num_attempts += 1;
+    cells_computed += band_cells(&stripes);
   }
   // report success/failure of broadening of band width
   if alignment.hit_boundary {
@@ -116,10 +206,29 @@ pub fn align_nuc(
     info!("When processing sequence #{index} '{seq_name}': In nucleotide alignment: Succeeded without hitting band boundary on attempt {}. Alignment score was: {}", attempt+1, alignment.alignment_score);
   }
   alignment.is_reverse_complement = is_reverse_complement;
+  alignment.stats = AlignmentStats {
+    num_seed_matches: seed_matches.len(),
+    num_attempts,
+    band_width: band_width(&stripes),
+    cells_computed,
+  };
+  alignment.band = stripes;
   Ok(alignment)
 }
 
+/// Maximum number of query positions considered for a reference position
+fn band_width(stripes: &[Stripe]) -> usize {
+  stripes.iter().map(Stripe::len).max().unwrap_or_default()
+}
+
+/// Number of cells of the alignment score matrix within the band
+fn band_cells(stripes: &[Stripe]) -> usize {
+  stripes.iter().map(Stripe::len).sum()
+}
+
 /// align amino acids using a fixed bandwidth banded alignment while penalizing terminal indels
+///
+/// Peptide alignments are not abandoned early: their reliability is assessed after the alignment instead.
 pub fn align_aa(
   qry_seq: &[Aa],
   ref_seq: &[Aa],
@@ -127,10 +236,10 @@ pub fn align_aa(
   params: &AlignPairwiseParams,
   band_width: usize,
   mean_shift: i32,
-) -> AlignmentOutput<Aa> {
+) -> Result<AlignmentOutput<Aa>, Report> {
   let stripes = simple_stripes(mean_shift, band_width, ref_seq.len(), qry_seq.len());
 
-  align_pairwise(qry_seq, ref_seq, gap_open_close, params, &stripes)
+  align_pairwise(qry_seq, ref_seq, gap_open_close, params, &stripes, None)
 }
 
 #[cfg(test)]
@@ -144,6 +253,8 @@ mod tests {
   use crate::align::gap_open::{get_gap_open_close_scores_codon_aware, GapScoreMap};
   use crate::align::params::GapAlignmentSide;
   use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use crate::analyze::nuc_changes::find_nuc_changes;
+  use crate::coord::range::NucRefGlobalRange;
   use crate::gene::gene_map::GeneMap;
   use eyre::Report;
   use pretty_assertions::assert_eq;
@@ -202,6 +313,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -224,6 +336,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -233,6 +346,40 @@ mod tests {
     Ok(())
   }
 
+  #[rstest]
+  #[case::free(true)]
+  #[case::penalized(false)]
+  fn excludes_terminal_gaps_from_alignment_range(ctx: Context, #[case] terminal_gaps_free: bool) -> Result<(), Report> {
+    #[rustfmt::skip]
+    let qry_seq = to_nuc_seq( "CGCTCGCT")?;
+    let ref_seq = to_nuc_seq("ACGCTCGCT")?;
+    let qry_aln = to_nuc_seq("-CGCTCGCT")?;
+
+    let params = AlignPairwiseParams {
+      left_terminal_gaps_free: terminal_gaps_free,
+      right_terminal_gaps_free: terminal_gaps_free,
+      ..ctx.params
+    };
+
+    let result = align_nuc(
+      0,
+      "",
+      &qry_seq,
+      &ref_seq,
+      &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
+      &ctx.gap_open_close,
+      &params,
+    )?;
+
+    assert_eq!(from_nuc_seq(&qry_aln), from_nuc_seq(&result.qry_seq));
+    assert_eq!(
+      NucRefGlobalRange::from_usize(1, 9),
+      find_nuc_changes(&result.qry_seq, &result.ref_seq).alignment_range
+    );
+    Ok(())
+  }
+
   #[rstest]
   fn pads_missing_left(ctx: Context) -> Result<(), Report> {
     #[rustfmt::skip]
@@ -246,6 +393,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -269,6 +417,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -292,6 +441,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -314,6 +464,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -337,6 +488,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -360,6 +512,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -382,6 +535,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -404,6 +558,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -426,6 +581,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -449,6 +605,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -471,6 +628,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -500,6 +658,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &params,
     )?;
@@ -522,6 +681,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -545,6 +705,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -568,6 +729,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -590,6 +752,7 @@ mod tests {
       &qry_seq,
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
+      None,
       &ctx.gap_open_close,
       &ctx.params,
     )?;
@@ -607,7 +770,7 @@ mod tests {
     let ref_aln = to_nuc_seq("CTTGGAGGTTCCGTGGCT----AGATAACAGAACATTCTTGGAATGCTGATCTTTATAAGCTCATGCGACACTTCGCATGGTG---AGCCTTTGT")?;
     let qry_aln = to_nuc_seq("CTTGGAGGTTCCGTGGCTATAAAGATAACAGAACATTCTTGGAATGCTGATC-----AAGCTCATGGGACANNNNNCATGGTGGACAGCCTTTGT")?;
 
-    let result = align_nuc(0, "", &qry_seq, &ref_seq, &CodonSpacedIndex::from_sequence(&ref_seq), &ctx.gap_open_close, &ctx.params)?;
+    let result = align_nuc(0, "", &qry_seq, &ref_seq, &CodonSpacedIndex::from_sequence(&ref_seq), None, &ctx.gap_open_close, &ctx.params)?;
 
     assert_eq!(from_nuc_seq(&ref_aln), from_nuc_seq(&result.ref_seq));
     assert_eq!(from_nuc_seq(&qry_aln), from_nuc_seq(&result.qry_seq));
@@ -0,0 +1,233 @@
+use crate::align::band_2d::Stripe;
+use crate::alphabet::letter::Letter;
+use crate::io::csv::CsvStructFileWriter;
+use crate::io::fs::has_extension;
+use crate::io::ndjson::NdjsonFileWriter;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Row of the alignment band: range of query positions `[qry_begin; qry_end)` of the score matrix which were
+/// considered by the aligner for a given reference position.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentBandStripe {
+  pub ref_pos: usize,
+  pub qry_begin: usize,
+  pub qry_end: usize,
+}
+
+/// Vertex of the traceback path through the score matrix
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentPathVertex {
+  pub ref_pos: usize,
+  pub qry_pos: usize,
+}
+
+/// Compact description of the alignment band and of the traceback path of a single sequence, for visualization.
+///
+/// Stripes which are a diagonal continuation of the preceding stripe (both boundaries shifted by one) are omitted.
+/// Path only contains the vertices where the direction changes (match, deletion or insertion), as well as the start
+/// and end points. Both can be restored exactly by linear interpolation between the listed entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentBand {
+  pub index: usize,
+  pub seq_name: String,
+  pub ref_len: usize,
+  pub qry_len: usize,
+  pub stripes: Vec<AlignmentBandStripe>,
+  pub path: Vec<AlignmentPathVertex>,
+}
+
+impl AlignmentBand {
+  pub fn new<T: Letter<T>>(index: usize, seq_name: &str, stripes: &[Stripe], ref_aln: &[T], qry_aln: &[T]) -> Self {
+    Self {
+      index,
+      seq_name: seq_name.to_owned(),
+      ref_len: ref_aln.iter().filter(|letter| !letter.is_gap()).count(),
+      qry_len: qry_aln.iter().filter(|letter| !letter.is_gap()).count(),
+      stripes: compact_stripes(stripes),
+      path: compact_path(ref_aln, qry_aln),
+    }
+  }
+}
+
+fn compact_stripes(stripes: &[Stripe]) -> Vec<AlignmentBandStripe> {
+  let last = stripes.len().saturating_sub(1);
+  stripes
+    .iter()
+    .enumerate()
+    .filter(|(ref_pos, stripe)| {
+      if *ref_pos == 0 || *ref_pos == last {
+        return true;
+      }
+      let prev = &stripes[ref_pos - 1];
+      stripe.begin != prev.begin + 1 || stripe.end != prev.end + 1
+    })
+    .map(|(ref_pos, stripe)| AlignmentBandStripe {
+      ref_pos,
+      qry_begin: stripe.begin,
+      qry_end: stripe.end,
+    })
+    .collect()
+}
+
+fn compact_path<T: Letter<T>>(ref_aln: &[T], qry_aln: &[T]) -> Vec<AlignmentPathVertex> {
+  let mut path = vec![AlignmentPathVertex { ref_pos: 0, qry_pos: 0 }];
+  let mut ref_pos = 0;
+  let mut qry_pos = 0;
+  let mut prev_step = None;
+
+  for (r, q) in ref_aln.iter().zip(qry_aln.iter()) {
+    let step = (usize::from(!r.is_gap()), usize::from(!q.is_gap()));
+    if prev_step.is_some() && prev_step != Some(step) {
+      path.push(AlignmentPathVertex { ref_pos, qry_pos });
+    }
+    ref_pos += step.0;
+    qry_pos += step.1;
+    prev_step = Some(step);
+  }
+
+  if prev_step.is_some() {
+    path.push(AlignmentPathVertex { ref_pos, qry_pos });
+  }
+
+  path
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AlignmentBandCsvRow<'a> {
+  index: usize,
+  seq_name: &'a str,
+  kind: &'a str,
+  ref_pos: usize,
+  qry_pos: usize,
+  qry_end: Option<usize>,
+}
+
+enum AlignmentBandWriterImpl {
+  Ndjson(NdjsonFileWriter),
+  Csv(Box<CsvStructFileWriter>),
+}
+
+/// Writes alignment bands, one sequence at a time. The format is deduced from file extension: semicolon-separated
+/// table for ".csv", tab-separated table for ".tsv", and newline-delimited JSON otherwise.
+///
+/// In tables, each stripe of the band is a row of kind "band" with query range in `qryPos` and `qryEnd` columns, and
+/// each vertex of the traceback path is a row of kind "path" with empty `qryEnd` column.
+pub struct AlignmentBandFileWriter {
+  filepath: PathBuf,
+  writer: AlignmentBandWriterImpl,
+}
+
+impl AlignmentBandFileWriter {
+  pub fn new(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let writer = if has_extension(filepath, "csv") {
+      AlignmentBandWriterImpl::Csv(Box::new(CsvStructFileWriter::new(filepath, b';')?))
+    } else if has_extension(filepath, "tsv") {
+      AlignmentBandWriterImpl::Csv(Box::new(CsvStructFileWriter::new(filepath, b'\t')?))
+    } else {
+      AlignmentBandWriterImpl::Ndjson(NdjsonFileWriter::new(filepath)?)
+    };
+    Ok(Self {
+      filepath: filepath.to_owned(),
+      writer,
+    })
+  }
+
+  pub fn write(&mut self, band: &AlignmentBand) -> Result<(), Report> {
+    let result = match &mut self.writer {
+      AlignmentBandWriterImpl::Ndjson(writer) => writer.write(band),
+      AlignmentBandWriterImpl::Csv(writer) => {
+        let stripes = band.stripes.iter().map(|stripe| AlignmentBandCsvRow {
+          index: band.index,
+          seq_name: &band.seq_name,
+          kind: "band",
+          ref_pos: stripe.ref_pos,
+          qry_pos: stripe.qry_begin,
+          qry_end: Some(stripe.qry_end),
+        });
+
+        let path = band.path.iter().map(|vertex| AlignmentBandCsvRow {
+          index: band.index,
+          seq_name: &band.seq_name,
+          kind: "path",
+          ref_pos: vertex.ref_pos,
+          qry_pos: vertex.qry_pos,
+          qry_end: None,
+        });
+
+        stripes.chain(path).try_for_each(|row| writer.write(&row))
+      }
+    };
+    result.wrap_err_with(|| format!("When writing alignment band to file {:#?}", &self.filepath))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  const fn vertex(ref_pos: usize, qry_pos: usize) -> AlignmentPathVertex {
+    AlignmentPathVertex { ref_pos, qry_pos }
+  }
+
+  #[rstest]
+  fn compacts_alignment_band() -> Result<(), Report> {
+    let ref_aln = to_nuc_seq("ACGT--ACGTAC")?;
+    let qry_aln = to_nuc_seq("ACGTTTAC--AC")?;
+    let stripes = vec![
+      Stripe::new(0, 3),
+      Stripe::new(1, 4),
+      Stripe::new(2, 5),
+      Stripe::new(2, 7),
+      Stripe::new(3, 8),
+      Stripe::new(4, 9),
+      Stripe::new(5, 11),
+      Stripe::new(6, 11),
+      Stripe::new(7, 11),
+      Stripe::new(8, 11),
+      Stripe::new(9, 11),
+    ];
+
+    let band = AlignmentBand::new(0, "seq", &stripes, &ref_aln, &qry_aln);
+
+    assert_eq!(band.ref_len, 10);
+    assert_eq!(band.qry_len, 10);
+    assert_eq!(
+      band
+        .stripes
+        .iter()
+        .map(|s| (s.ref_pos, s.qry_begin, s.qry_end))
+        .collect::<Vec<_>>(),
+      vec![
+        (0, 0, 3),
+        (3, 2, 7),
+        (6, 5, 11),
+        (7, 6, 11),
+        (8, 7, 11),
+        (9, 8, 11),
+        (10, 9, 11)
+      ]
+    );
+    assert_eq!(
+      band.path,
+      vec![
+        vertex(0, 0),
+        vertex(4, 4),
+        vertex(4, 6),
+        vertex(6, 8),
+        vertex(8, 8),
+        vertex(10, 10)
+      ]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,41 @@
+use crate::align::backtrace::AlignmentOutput;
+use crate::alphabet::nuc::Nuc;
+use serde::{Deserialize, Serialize};
+
+/// Amount of work done by the nucleotide alignment of a sequence
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentStats {
+  /// Number of seed matches the alignment band was constructed from. Zero if the band was not constructed from seeds
+  /// (for short sequences, the entire alignment matrix is computed instead).
+  pub num_seed_matches: usize,
+  /// Number of alignment attempts: the band is widened and the alignment is repeated when it hits the band boundary
+  pub num_attempts: usize,
+  /// Maximum width of the band of the final attempt, i.e. the maximum number of query positions considered for a
+  /// reference position
+  pub band_width: usize,
+  /// Number of cells of the alignment score matrix computed, summed over all attempts
+  pub cells_computed: usize,
+}
+
+/// Per-sequence diagnostics of nucleotide alignment, which help to tune alignment parameters for particular data
+/// (`--include-alignment-diagnostics`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentDiagnostics {
+  pub alignment_score: i32,
+  #[serde(flatten)]
+  pub stats: AlignmentStats,
+  /// Wall time taken by the alignment, in seconds
+  pub alignment_time_sec: f64,
+}
+
+impl AlignmentDiagnostics {
+  pub fn new(alignment: &AlignmentOutput<Nuc>, alignment_time_sec: f64) -> Self {
+    Self {
+      alignment_score: alignment.alignment_score,
+      stats: alignment.stats.clone(),
+      alignment_time_sec,
+    }
+  }
+}
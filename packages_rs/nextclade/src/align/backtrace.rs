@@ -1,3 +1,4 @@
+use crate::align::alignment_diagnostics::AlignmentStats;
 use crate::align::band_2d::{Band2d, Stripe};
 use crate::align::score_matrix::{BOUNDARY, MATCH, QRY_GAP_EXTEND, QRY_GAP_MATRIX, REF_GAP_EXTEND, REF_GAP_MATRIX};
 use crate::alphabet::letter::Letter;
@@ -17,6 +18,12 @@ pub struct AlignmentOutput<T> {
   pub alignment_score: i32,
   pub is_reverse_complement: bool,
   pub hit_boundary: bool,
+  /// Band of the score matrix used to produce this alignment. Only filled for nucleotide alignment.
+  #[serde(skip)]
+  pub band: Vec<Stripe>,
+  /// Amount of work done to produce this alignment. Only filled for nucleotide alignment.
+  #[serde(skip)]
+  pub stats: AlignmentStats,
 }
 
 pub fn backtrace<T: Letter<T>>(
@@ -27,11 +34,27 @@ pub fn backtrace<T: Letter<T>>(
 ) -> AlignmentOutput<T> {
   let num_cols = scores.num_cols();
   let num_rows = scores.num_rows();
-  let qry_len = qry_seq.len() as i32;
-  let ref_len = ref_seq.len() as i32;
+  let alignment_score = scores[(num_rows - 1, num_cols - 1)];
+  backtrace_with(qry_seq, ref_seq, num_rows, num_cols, alignment_score, |r_pos, q_pos| {
+    paths[(r_pos, q_pos)]
+  })
+}
 
+/// Same as `backtrace()`, but instead of the matrix of paths, takes a function which returns the path of a given cell.
+///
+/// Cells are requested in the order of the backtrace, i.e. from the bottom-right corner of the matrix to the top-left
+/// corner, with row indices never increasing. This allows the function to recompute the paths lazily, a block of rows
+/// at a time, rather than to keep the entire matrix in memory.
+pub fn backtrace_with<T: Letter<T>>(
+  qry_seq: &[T],
+  ref_seq: &[T],
+  num_rows: usize,
+  num_cols: usize,
+  alignment_score: i32,
+  mut path_at: impl FnMut(usize, usize) -> i8,
+) -> AlignmentOutput<T> {
   // max length of the alignment is the sum of query and reference length
-  let aln_capacity = scores.num_cols() + scores.num_rows();
+  let aln_capacity = num_cols + num_rows;
   let mut aln_ref = Vec::<T>::with_capacity(aln_capacity);
   let mut aln_qry = Vec::<T>::with_capacity(aln_capacity);
 
@@ -44,7 +67,7 @@ pub fn backtrace<T: Letter<T>>(
   let mut hit_boundary = false;
   // Do backtrace in the aligned region
   while r_pos > 0 || q_pos > 0 {
-    origin = paths[(r_pos, q_pos)];
+    origin = path_at(r_pos, q_pos);
     if (origin & BOUNDARY) > 0 {
       hit_boundary = true;
     }
@@ -95,9 +118,11 @@ pub fn backtrace<T: Letter<T>>(
   AlignmentOutput {
     qry_seq: aln_qry,
     ref_seq: aln_ref,
-    alignment_score: scores[(num_rows - 1, num_cols - 1)],
+    alignment_score,
     is_reverse_complement: false,
     hit_boundary,
+    band: vec![],
+    stats: AlignmentStats::default(),
   }
 }
 
@@ -172,6 +197,8 @@ mod tests {
       alignment_score: 18,
       is_reverse_complement: false,
       hit_boundary: false,
+      band: vec![],
+      stats: AlignmentStats::default(),
     };
 
     let output = backtrace(&qry_seq, &ref_seq, &scores, &paths);
@@ -118,6 +118,17 @@ where
     self.data.len()
   }
 
+  /// Contiguous storage of a row: cells from `stripe.begin` to `stripe.end` of the corresponding stripe
+  #[inline]
+  pub fn row(&self, row: usize) -> &[T] {
+    &self.data[self.row_start_points[row]..self.row_start_points[row + 1]]
+  }
+
+  #[inline]
+  pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+    &mut self.data[self.row_start_points[row]..self.row_start_points[row + 1]]
+  }
+
   #[inline]
   fn get_index<I: NumCast + Copy, J: NumCast + Copy>(&self, index2d: (I, J)) -> usize {
     let row = index2d.0.to_usize().unwrap();
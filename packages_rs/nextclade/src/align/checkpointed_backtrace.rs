@@ -0,0 +1,147 @@
+use crate::align::backtrace::{backtrace_with, AlignmentOutput};
+use crate::align::band_2d::{Band2d, Stripe};
+use crate::align::params::AlignPairwiseParams;
+use crate::align::score_matrix::{score_rows, RowCheckpoint, ScoreDropCheck};
+use crate::alphabet::letter::Letter;
+use crate::utils::progress::AlignmentProgress;
+use eyre::Report;
+use log::trace;
+use std::ops::ControlFlow;
+
+/// Bytes of memory per cell of the band required by `score_matrix()`: a score (i32) and a path (i8)
+const FULL_MATRIX_BYTES_PER_CELL: usize = 5;
+
+/// Estimates memory (in bytes) required to align within the given band, when the entire score matrix is kept in memory
+pub fn full_matrix_memory(stripes: &[Stripe]) -> usize {
+  stripes.iter().map(Stripe::len).sum::<usize>() * FULL_MATRIX_BYTES_PER_CELL
+}
+
+/// Aligns sequences within the band, keeping only a fraction of the score matrix in memory.
+///
+/// Produces the same alignment as `score_matrix()` followed by `backtrace()`, but trades time for memory, using a
+/// divide-and-conquer scheme with checkpoints. The rows of the matrix are split into blocks of roughly `sqrt(n_rows)`
+/// rows. The forward pass fills the matrix, keeping only the last row of each block (along with the state required to
+/// resume the filling from it). The backtrace then proceeds from the last block to the first one, recomputing the paths
+/// of one block at a time from the preceding checkpoint. Memory is then proportional to `sqrt(n_rows) * band_width`
+/// rather than to `n_rows * band_width`, while the matrix is filled twice.
+///
+/// If `score_drop_check` is provided, the forward pass is abandoned with an error as soon as the alignment turns out to
+/// be hopeless.
+pub fn align_pairwise_checkpointed<T: Letter<T>>(
+  qry_seq: &[T],
+  ref_seq: &[T],
+  gap_open_close: &[i32],
+  params: &AlignPairwiseParams,
+  stripes: &[Stripe],
+  mut score_drop_check: Option<ScoreDropCheck>,
+) -> Result<AlignmentOutput<T>, Report> {
+  let num_rows = stripes.len();
+  let num_cols = stripes.iter().map(|stripe| stripe.end).max().unwrap_or_default();
+  let last_row = num_rows - 1;
+  let block_size = ((num_rows as f64).sqrt().ceil() as usize).max(1);
+
+  trace!("Checkpointed alignment: started: num_rows={num_rows}, num_cols={num_cols}, block_size={block_size}");
+
+  let band_size = stripes.iter().map(Stripe::len).sum();
+  let mut progress = AlignmentProgress::new(ref_seq.len(), band_size);
+  let mut checkpoints = Vec::<RowCheckpoint>::with_capacity(num_rows / block_size + 1);
+  let mut alignment_score = 0;
+
+  let filled = score_rows(
+    qry_seq,
+    ref_seq,
+    gap_open_close,
+    stripes,
+    params,
+    None,
+    last_row,
+    |row, scores, _, qry_gaps| {
+      progress.add(scores.len());
+      if row == last_row {
+        alignment_score = scores[num_cols - 1 - stripes[row].begin];
+      } else if (row + 1) % block_size == 0 {
+        checkpoints.push(RowCheckpoint {
+          row,
+          scores: scores.to_vec(),
+          qry_gaps: qry_gaps.to_vec(),
+        });
+      }
+      score_drop_check.as_mut().map_or(ControlFlow::Continue(()), |check| {
+        check.check(row, &stripes[row], scores)
+      })
+    },
+  );
+
+  if let ControlFlow::Break(report) = filled {
+    return Err(report);
+  }
+
+  // Paths of the block of rows containing the current position of the backtrace
+  let mut block_first_row = num_rows;
+  let mut block_paths = Band2d::<i8>::new(&[]);
+
+  let alignment = backtrace_with(qry_seq, ref_seq, num_rows, num_cols, alignment_score, |r_pos, q_pos| {
+    if r_pos < block_first_row {
+      // The backtrace moves up by at most one row at a time, so `r_pos` is the last row of the preceding block
+      let start = checkpoints.iter().rev().find(|checkpoint| checkpoint.row < r_pos);
+      block_first_row = start.map_or(0, |checkpoint| checkpoint.row + 1);
+
+      block_paths = Band2d::<i8>::new(&stripes[block_first_row..=r_pos]);
+      // Rows are recomputed exactly as in the forward pass, which has already succeeded, so there is nothing to check
+      let _: ControlFlow<()> = score_rows(
+        qry_seq,
+        ref_seq,
+        gap_open_close,
+        stripes,
+        params,
+        start,
+        r_pos,
+        |row, _, paths, _| {
+          block_paths.row_mut(row - block_first_row).copy_from_slice(paths);
+          ControlFlow::Continue(())
+        },
+      );
+    }
+    block_paths[(r_pos - block_first_row, q_pos)]
+  });
+
+  Ok(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::align::backtrace::backtrace;
+  use crate::align::band_2d::simple_stripes;
+  use crate::align::gap_open::get_gap_open_close_scores_codon_aware;
+  use crate::align::score_matrix::score_matrix;
+  use crate::alphabet::nuc::{to_nuc_seq, Nuc};
+  use crate::gene::gene_map::GeneMap;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::terminal_gaps_free(true)]
+  #[case::terminal_gaps_penalized(false)]
+  fn produces_same_alignment_as_full_matrix(#[case] terminal_gaps_free: bool) -> Result<(), Report> {
+    let params = AlignPairwiseParams {
+      left_terminal_gaps_free: terminal_gaps_free,
+      right_terminal_gaps_free: terminal_gaps_free,
+      ..AlignPairwiseParams::default()
+    };
+
+    let ref_seq = to_nuc_seq("ACGCTCGCTGATTACAGGCTTACCGATAGCATTACGGACTTAGCATCGATCGGCTAGCATCAGCTACGATCGACTAG")?;
+    let qry_seq = to_nuc_seq("CTCGCTGATTACAGCTTACCGATAGCATTTTTACGGACTTAGCTTCGATCGGCTAGCATCAGCTACGATCG")?;
+
+    let gap_open_close = get_gap_open_close_scores_codon_aware(&[Nuc::Gap; 100], &GeneMap::new(), &params);
+    let stripes = simple_stripes(2, 8, ref_seq.len(), qry_seq.len());
+
+    let result = score_matrix(&qry_seq, &ref_seq, &gap_open_close, &stripes, &params, None)?;
+    let expected = backtrace(&qry_seq, &ref_seq, &result.scores, &result.paths);
+
+    let actual = align_pairwise_checkpointed(&qry_seq, &ref_seq, &gap_open_close, &params, &stripes, None)?;
+
+    assert_eq!(expected, actual);
+    Ok(())
+  }
+}
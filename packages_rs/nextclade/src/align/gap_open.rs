@@ -13,12 +13,20 @@ pub fn get_gap_open_close_scores_flat(ref_seq: &[Nuc], params: &AlignPairwisePar
   vec![value; len]
 }
 
+/// Gap opening penalties which favor gaps aligned with codons of CDSes.
+///
+/// Where CDSes overlap in different frames (e.g. ORF9b within N in SARS-CoV-2), a gap is penalized as in-frame if it is
+/// in-frame for at least one of the overlapping CDSes, i.e. the lowest penalty of all frames is taken. This way the
+/// result does not depend on the order of CDSes in the genome annotation.
 pub fn get_gap_open_close_scores_codon_aware(
   ref_seq: &[Nuc],
   gene_map: &GeneMap,
   params: &AlignPairwiseParams,
 ) -> GapScoreMap {
   let mut gap_open_close = get_gap_open_close_scores_flat(ref_seq, params);
+
+  // Lowest penalty among all CDSes covering the position, if any
+  let mut cds_scores = vec![None; gap_open_close.len()];
   for cds in gene_map.iter_cdses() {
     let mut cds_pos = 0;
     for segment in &cds.segments {
@@ -31,15 +39,23 @@ pub fn get_gap_open_close_scores_codon_aware(
       };
 
       for i in range {
-        if cds_pos % 3 == codon_start {
-          gap_open_close[i] = params.penalty_gap_open_in_frame;
+        let score = if cds_pos % 3 == codon_start {
+          params.penalty_gap_open_in_frame
         } else {
-          gap_open_close[i] = params.penalty_gap_open_out_of_frame;
-        }
+          params.penalty_gap_open_out_of_frame
+        };
+        cds_scores[i] = Some(cds_scores[i].map_or(score, |existing: i32| existing.min(score)));
         cds_pos += 1;
       }
     }
   }
+
+  for (gap_score, cds_score) in gap_open_close.iter_mut().zip(cds_scores) {
+    if let Some(cds_score) = cds_score {
+      *gap_score = cds_score;
+    }
+  }
+
   gap_open_close
 }
 
@@ -50,6 +66,7 @@ mod tests {
   use crate::coord::position::Position;
   use crate::coord::range::{NucRefGlobalRange, Range};
   use crate::gene::cds::Cds;
+  use crate::gene::cds_partial::CdsPartial;
   use crate::gene::cds_segment::{CdsSegment, WrappingPart};
   use crate::gene::frame::Frame;
   use crate::gene::gene::GeneStrand::{Forward, Reverse};
@@ -60,6 +77,7 @@ mod tests {
   use maplit::hashmap;
   use pretty_assertions::assert_eq;
   use rstest::{fixture, rstest};
+  use std::sync::OnceLock;
 
   fn create_test_genome_annotation(cdses: &[&[(isize, isize, GeneStrand)]]) -> Result<GeneMap, Report> {
     let genes = cdses
@@ -105,6 +123,11 @@ mod tests {
           attributes: hashmap! {},
           compat_is_gene: false,
           color: None,
+          transl_table: None,
+          ribosomal_slippage: None,
+          partial: CdsPartial::default(),
+          transl_except: vec![],
+          name_interned: OnceLock::new(),
         })
       })
       .collect::<Result<Vec<Gene>, Report>>()?;
@@ -284,4 +307,27 @@ mod tests {
     assert_eq!(actual, expect);
     Ok(())
   }
+
+  #[rstest]
+  fn test_gap_score_overlapping_in_different_frames(ctx: Context) -> Result<(), Report> {
+    #[rustfmt::skip]
+    let gene_map = create_test_genome_annotation(&[
+      &[
+        (3, 12, Forward)
+      ],
+      &[
+        (7, 16, Forward)
+      ],
+    ])?;
+
+    #[rustfmt::skip]
+    //                         |           |              |           |
+    //                0  1  2  3  4  5  6  7  8  9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 + 2 extra
+    let expect = vec![6, 6, 6, 7, 8, 8, 7, 7, 8, 7, 7, 8, 8, 7, 8, 8, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6];
+
+    let actual = get_gap_open_close_scores_codon_aware(&ctx.ref_seq, &gene_map, &ctx.params);
+
+    assert_eq!(actual, expect);
+    Ok(())
+  }
 }
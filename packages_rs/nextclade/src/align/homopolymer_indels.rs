@@ -0,0 +1,187 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::NucRefGlobalPosition;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum HomopolymerIndelKind {
+  Insertion,
+  Deletion,
+}
+
+impl Display for HomopolymerIndelKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      HomopolymerIndelKind::Insertion => write!(f, "insertion"),
+      HomopolymerIndelKind::Deletion => write!(f, "deletion"),
+    }
+  }
+}
+
+/// Single-nucleotide insertion or deletion inside of a homopolymer, which has been removed from the alignment
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HomopolymerIndel {
+  pub kind: HomopolymerIndelKind,
+
+  /// For a deletion, position of the deleted reference nucleotide. For an insertion, position of the reference
+  /// nucleotide after which the nucleotide has been inserted.
+  pub pos: NucRefGlobalPosition,
+
+  /// Nucleotide of the homopolymer
+  pub nuc: Nuc,
+
+  /// Length of the homopolymer in the reference sequence
+  pub homopolymer_length: usize,
+}
+
+/// Normalizes single-nucleotide insertions and deletions inside of homopolymers of the reference sequence (runs of the
+/// same nucleotide of at least `min_length`), which are typical artifacts of Nanopore sequencing: deleted nucleotides
+/// are restored from the reference and inserted nucleotides are removed from the alignment.
+///
+/// Only isolated indels are normalized, i.e. with aligned nucleotides on both sides, one of which belongs to the same
+/// homopolymer in the query. Longer indels, as well as indels at the ends of the alignment, are left intact. Without
+/// this normalization, such artifacts result in spurious frame shifts, which dominate QC of Nanopore datasets.
+///
+/// Returns the list of normalized indels.
+pub fn normalize_homopolymer_indels(
+  qry_aln: &mut Vec<Nuc>,
+  ref_aln: &mut Vec<Nuc>,
+  min_length: usize,
+) -> Vec<HomopolymerIndel> {
+  debug_assert_eq!(qry_aln.len(), ref_aln.len());
+
+  let mut normalized = vec![];
+  let mut qry_normalized = Vec::<Nuc>::with_capacity(qry_aln.len());
+  let mut ref_normalized = Vec::<Nuc>::with_capacity(ref_aln.len());
+  let mut ref_pos = 0_isize;
+
+  for i in 0..qry_aln.len() {
+    let (qry, reff) = (qry_aln[i], ref_aln[i]);
+
+    if let Some(indel) = find_homopolymer_indel(qry_aln, ref_aln, i, ref_pos, min_length) {
+      match indel.kind {
+        HomopolymerIndelKind::Deletion => {
+          qry_normalized.push(reff);
+          ref_normalized.push(reff);
+          ref_pos += 1;
+        }
+        HomopolymerIndelKind::Insertion => {}
+      }
+      normalized.push(indel);
+      continue;
+    }
+
+    qry_normalized.push(qry);
+    ref_normalized.push(reff);
+    if !reff.is_gap() {
+      ref_pos += 1;
+    }
+  }
+
+  *qry_aln = qry_normalized;
+  *ref_aln = ref_normalized;
+  normalized
+}
+
+/// Checks whether the alignment column `i` is a single-nucleotide indel inside of a homopolymer
+fn find_homopolymer_indel(
+  qry_aln: &[Nuc],
+  ref_aln: &[Nuc],
+  i: usize,
+  ref_pos: isize,
+  min_length: usize,
+) -> Option<HomopolymerIndel> {
+  if i == 0 || i + 1 >= qry_aln.len() {
+    return None;
+  }
+
+  let (qry, reff) = (qry_aln[i], ref_aln[i]);
+  let is_aligned = |j: usize| !qry_aln[j].is_gap() && !ref_aln[j].is_gap();
+  if !is_aligned(i - 1) || !is_aligned(i + 1) {
+    return None;
+  }
+
+  let (kind, nuc) = if qry.is_gap() && !reff.is_gap() {
+    (HomopolymerIndelKind::Deletion, reff)
+  } else if reff.is_gap() && !qry.is_gap() {
+    (HomopolymerIndelKind::Insertion, qry)
+  } else {
+    return None;
+  };
+
+  if !nuc.is_acgt() || (qry_aln[i - 1] != nuc && qry_aln[i + 1] != nuc) {
+    return None;
+  }
+
+  // Length of the homopolymer in the reference, including the current column in case of a deletion
+  let run_left = ref_aln[..i].iter().rev().take_while(|&&r| r == nuc).count();
+  let run_right = ref_aln[i + 1..].iter().take_while(|&&r| r == nuc).count();
+  let homopolymer_length = run_left + run_right + usize::from(kind == HomopolymerIndelKind::Deletion);
+  if homopolymer_length < min_length {
+    return None;
+  }
+
+  let pos = match kind {
+    HomopolymerIndelKind::Deletion => ref_pos,
+    HomopolymerIndelKind::Insertion => ref_pos - 1,
+  };
+
+  Some(HomopolymerIndel {
+    kind,
+    pos: pos.into(),
+    nuc,
+    homopolymer_length,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rustfmt::skip]
+  #[rstest]
+  #[case::deletion(          "ACGAAA-ACGT",  "ACGAAAAACGT", "ACGAAAAACGT", "ACGAAAAACGT", 1)]
+  #[case::insertion(         "ACGAAAAACGT",  "ACGAA-AACGT", "ACGAAAACGT",  "ACGAAAACGT",  1)]
+  #[case::short_homopolymer( "ACGA-CGT",     "ACGAACGT",    "ACGA-CGT",    "ACGAACGT",    0)]
+  #[case::long_deletion(     "ACGAAA--CGT",  "ACGAAAAACGT", "ACGAAA--CGT", "ACGAAAAACGT", 0)]
+  #[case::other_nucleotide(  "ACGAAATACGT",  "ACGAAA-ACGT", "ACGAAATACGT", "ACGAAA-ACGT", 0)]
+  fn normalizes_homopolymer_indels(
+    #[case] qry: &str,
+    #[case] reff: &str,
+    #[case] qry_expected: &str,
+    #[case] ref_expected: &str,
+    #[case] num_normalized: usize,
+  ) -> Result<(), Report> {
+    let mut qry_aln = to_nuc_seq(qry)?;
+    let mut ref_aln = to_nuc_seq(reff)?;
+    let normalized = normalize_homopolymer_indels(&mut qry_aln, &mut ref_aln, 3);
+    assert_eq!(from_nuc_seq(&qry_aln), qry_expected);
+    assert_eq!(from_nuc_seq(&ref_aln), ref_expected);
+    assert_eq!(normalized.len(), num_normalized);
+    Ok(())
+  }
+
+  #[rstest]
+  fn reports_reference_position_of_normalized_deletion() -> Result<(), Report> {
+    let mut qry_aln = to_nuc_seq("AC-GAAA-ACGT")?;
+    let mut ref_aln = to_nuc_seq("ACTGAAAAACGT")?;
+    let normalized = normalize_homopolymer_indels(&mut qry_aln, &mut ref_aln, 3);
+    assert_eq!(
+      normalized,
+      vec![HomopolymerIndel {
+        kind: HomopolymerIndelKind::Deletion,
+        pos: NucRefGlobalPosition::from(7_usize),
+        nuc: Nuc::A,
+        homopolymer_length: 5,
+      }]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,128 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::alphabet::letter::Letter;
+
+/// Clips the ends of the alignment which do not align to the reference better than they would be left unaligned, such
+/// that only the best-scoring contiguous segment of the alignment remains (local alignment).
+///
+/// Query letters outside of this segment are removed from the alignment and reference positions outside of it are
+/// marked as not covered by the query (gaps), so that they are treated the same way as unsequenced regions. This is
+/// useful for fragments of the genome (amplicons, partial genes), which may contain flanking sequence (primers,
+/// adapters, vector) that does not belong to the reference.
+///
+/// Returns the number of query letters clipped from the beginning and from the end of the alignment.
+pub fn clip_to_local_alignment<T: Letter<T>>(
+  qry_aln: &mut Vec<T>,
+  ref_aln: &mut Vec<T>,
+  params: &AlignPairwiseParams,
+) -> (usize, usize) {
+  let Some((begin, end)) = find_best_scoring_segment(qry_aln, ref_aln, params) else {
+    return (0, 0);
+  };
+
+  let mut clipped_begin = 0;
+  let mut clipped_end = 0;
+  let mut qry_clipped = Vec::<T>::with_capacity(qry_aln.len());
+  let mut ref_clipped = Vec::<T>::with_capacity(ref_aln.len());
+  for (i, (&qry, &reff)) in qry_aln.iter().zip(ref_aln.iter()).enumerate() {
+    if (begin..end).contains(&i) {
+      qry_clipped.push(qry);
+      ref_clipped.push(reff);
+    } else {
+      if !qry.is_gap() {
+        if i < begin {
+          clipped_begin += 1;
+        } else {
+          clipped_end += 1;
+        }
+      }
+      // Columns with insertions are dropped altogether, the rest of the reference is kept, but is not covered
+      if !reff.is_gap() {
+        qry_clipped.push(T::GAP);
+        ref_clipped.push(reff);
+      }
+    }
+  }
+
+  *qry_aln = qry_clipped;
+  *ref_aln = ref_clipped;
+  (clipped_begin, clipped_end)
+}
+
+/// Finds the range of alignment columns with the maximum total score. Returns `None` if there are no columns with
+/// positive score.
+fn find_best_scoring_segment<T: Letter<T>>(
+  qry_aln: &[T],
+  ref_aln: &[T],
+  params: &AlignPairwiseParams,
+) -> Option<(usize, usize)> {
+  let mut best: Option<(i32, usize, usize)> = None;
+  let mut score = 0;
+  let mut begin = 0;
+  let mut prev_gap: Option<bool> = None; // `Some(true)` for a gap in query, `Some(false)` for a gap in reference
+
+  for (i, (&qry, &reff)) in qry_aln.iter().zip(ref_aln).enumerate() {
+    let gap = if qry.is_gap() {
+      Some(true)
+    } else if reff.is_gap() {
+      Some(false)
+    } else {
+      None
+    };
+
+    let column_score = match gap {
+      None if qry.is_unknown() || reff.is_unknown() => 0,
+      None if T::lookup_match_score(qry, reff) > 0 => params.score_match,
+      None => -params.penalty_mismatch,
+      Some(_) if gap == prev_gap => -params.penalty_gap_extend,
+      Some(_) => -params.penalty_gap_open,
+    };
+    prev_gap = gap;
+
+    // Start a new segment if the preceding one does not add to the score
+    if score <= 0 {
+      score = 0;
+      begin = i;
+    }
+    score += column_score;
+
+    if score > 0 && best.map_or(true, |(best_score, _, _)| score > best_score) {
+      best = Some((score, begin, i + 1));
+    }
+  }
+
+  best.map(|(_, begin, end)| (begin, end))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::nothing_to_clip("ACGTACGTAC", "ACGTACGTAC", "ACGTACGTAC", "ACGTACGTAC", (0, 0))]
+  #[case::mismatching_flanks("TTTGTACGTAGGG", "CCAGTACGTACCC", "---GTACGTA---", "CCAGTACGTACCC", (3, 3))]
+  #[case::inserted_flank("TTTTTTACGTACGTAC", "------ACGTACGTAC", "ACGTACGTAC", "ACGTACGTAC", (6, 0))]
+  fn clips_to_local_alignment(
+    #[case] qry_aln: &str,
+    #[case] ref_aln: &str,
+    #[case] expected_qry_aln: &str,
+    #[case] expected_ref_aln: &str,
+    #[case] expected_clipped: (usize, usize),
+  ) -> Result<(), Report> {
+    let mut qry = to_nuc_seq(qry_aln)?;
+    let mut reff = to_nuc_seq(ref_aln)?;
+    let clipped = clip_to_local_alignment(&mut qry, &mut reff, &AlignPairwiseParams::default());
+    assert_eq!(
+      (from_nuc_seq(&qry), from_nuc_seq(&reff), clipped),
+      (
+        expected_qry_aln.to_owned(),
+        expected_ref_aln.to_owned(),
+        expected_clipped
+      )
+    );
+    Ok(())
+  }
+}
@@ -1,13 +1,21 @@
 pub mod align;
+pub mod alignment_band;
+pub mod alignment_diagnostics;
 pub mod backtrace;
 pub mod band_2d;
+pub mod checkpointed_backtrace;
 pub mod gap_open;
+pub mod homopolymer_indels;
 pub mod insertions_strip;
+pub mod local_alignment;
 pub mod params;
 pub mod remove_gaps;
 pub mod score_matrix;
 pub mod score_matrix_aa;
 pub mod score_matrix_nuc;
+pub mod score_simd;
 pub mod seed_alignment;
 pub mod seed_match;
 pub mod seed_match2;
+pub mod seed_minimizer;
+pub mod wfa;
@@ -13,6 +13,51 @@ pub enum GapAlignmentSide {
   Right,
 }
 
+/// How codons containing ambiguous nucleotides are translated
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmbiguousCodonPolicy {
+  /// Translate as `X`
+  Unknown,
+  /// Translate if all the codons represented encode the same aminoacid, otherwise as `X`
+  Resolve,
+  /// Same as `resolve`, but codons encoding one of two aminoacids are translated into IUPAC codes `B`, `Z` and `J`
+  ResolvePartial,
+}
+
+/// How frame-shifted regions of CDSes are translated
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrameShiftPolicy {
+  /// Mask the frame-shifted region with `X`
+  #[default]
+  Mask,
+  /// Translate the frame-shifted region as is, in the shifted reading frame
+  TranslateThrough,
+  /// Mask the peptide with `X` from the first frame shift to the end
+  Truncate,
+}
+
+/// Algorithm used for nucleotide alignment
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlignmentAlgorithm {
+  /// Seed-based banded alignment
+  Banded,
+  /// Wavefront alignment, falling back to banded alignment for divergent sequences
+  Wfa,
+}
+
+/// Thresholds under which peptide alignment of a CDS is considered unreliable. Thresholds which are not set are not
+/// checked.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PeptideReliabilityThresholds {
+  pub max_gap_fraction: Option<f64>,
+  pub max_unknown_fraction: Option<f64>,
+  pub min_score_per_aa: Option<f64>,
+}
+
 // NOTE: The `optfield` attribute creates a struct that have the same fields, but which are wrapped into `Option`,
 // as well as adds a method `.merge_opt(&opt)` to the original struct, which merges values from the optional counterpart
 // into self (mutably).
@@ -56,7 +101,23 @@ pub struct AlignPairwiseParams {
   #[clap(long)]
   pub max_band_area: usize,
 
-  /// Retry seed matching step with a reverse complement if the first attempt failed
+  /// Maximum memory (in megabytes) which the alignment of one sequence is allowed to use for keeping the entire alignment matrix in memory. Alignments with larger bands use a slower backtrace, which recomputes parts of the matrix instead, and requires memory proportional to the square root of the band area. Set to 0 to always use the slower, memory-saving backtrace.
+  ///
+  /// Each thread (see `--jobs`) aligns one sequence at a time, so the peak memory required for alignment is roughly the number of threads times this value.
+  #[clap(long)]
+  pub alignment_memory_budget_mb: usize,
+
+  /// Abandon nucleotide alignment as soon as it turns out to be hopeless, e.g. for sequences of a wrong virus or garbage, instead of filling the entire alignment matrix. Such sequences then fail early with an error.
+  ///
+  /// While the alignment matrix is filled, the alignment score is tracked relative to the number of query nucleotides aligned so far, counting half of `--score-match` per nucleotide as the expected baseline. This relative score grows along a good alignment and declines where the query does not resemble the reference. The alignment is abandoned when it drops by more than this value below its best value so far. Note that long insertions also reduce the relative score, so the value should be considerably larger than the longest insertion expected (in nucleotides).
+  ///
+  /// Not checked by default.
+  #[clap(long)]
+  pub alignment_abort_score_drop: Option<i32>,
+
+  /// Detect sequences submitted in reverse complement orientation: if seed matching fails for a sequence, retry it with the reverse complement of the sequence. If that succeeds, the sequence is analyzed in reverse complement, this is reported in the `isReverseComplement` output column and the "(reverse complement)" suffix is added to the sequence name in output FASTA.
+  ///
+  /// Enabled by default. Use `--retry-reverse-complement=false` to disable.
   #[clap(long)]
   #[clap(num_args=0..=1, default_missing_value = "true")]
   pub retry_reverse_complement: bool,
@@ -66,12 +127,36 @@ pub struct AlignPairwiseParams {
   #[clap(num_args=0..=1, default_missing_value = "true")]
   pub no_translate_past_stop: bool,
 
-  // Internal alignment parameter
-  #[clap(skip)]
+  /// How to translate codons containing ambiguous nucleotides (nucleotide codes other than A, C, G, T).
+  ///
+  /// With "unknown", such codons are translated as `X`. With "resolve", they are translated if all the codons they represent encode the same aminoacid (for example `CTN` is `L`), otherwise as `X`. With "resolve-partial", in addition, the codons encoding one of two aminoacids are translated into the corresponding IUPAC codes: `B` (`N` or `D`), `Z` (`Q` or `E`) and `J` (`I` or `L`), such that partially determined aminoacids are reported distinctly from unknown ones. Partially determined aminoacids agreeing with the reference are not reported as mutations.
+  #[clap(long, value_enum)]
+  pub ambiguous_codons: AmbiguousCodonPolicy,
+
+  /// How to translate frame-shifted regions of CDSes.
+  ///
+  /// With "mask" (default), the frame-shifted region is translated as unknown aminoacids (`X`), because it is likely misaligned. With "translate-through", it is translated as is, in the shifted reading frame, and the resulting aminoacid changes are reported (best effort). With "truncate", the peptide is considered unknown from the first frame shift to the end of the CDS, similarly to `--no-translate-past-stop`. In either case, frame shifts are reported, along with the policy applied to them.
+  #[clap(long, value_enum)]
+  pub frame_shift_policy: FrameShiftPolicy,
+
+  /// Maximum length (in nucleotides of the alignment) of a frame shift which is followed by a restoration of the reading frame, e.g. an insertion and a deletion close to each other, for it to be reported as a compensated frame shift. Such frame shifts only affect a few codons and can be exempted from the penalty of the "Frame shifts" QC rule (see `ignoreCompensated` field of the rule in pathogen.json).
+  #[clap(long)]
+  pub compensated_frame_shift_max_length: usize,
+
+  /// Do not penalize gaps at the beginning of the nucleotide alignment ("end-gap-free" alignment). This is appropriate for incomplete genomes, which often lack a part at the beginning. If disabled, leading gaps incur the usual gap opening and extension penalties, which favors alignments spanning the entire reference sequence.
+  ///
+  /// In either case, the leading gaps of the query are not considered part of the aligned region (see `alignmentStart` output column) and are not reported as deletions.
+  ///
+  /// Enabled by default. Use `--left-terminal-gaps-free=false` to disable. Does not affect the alignment of peptides, in which terminal gaps are only free when the nucleotide sequence of the CDS is incomplete.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
   pub left_terminal_gaps_free: bool,
 
-  // Internal alignment parameter
-  #[clap(skip)]
+  /// As `--left-terminal-gaps-free`, but for gaps at the end of the nucleotide alignment (see `alignmentEnd` output column).
+  ///
+  /// Enabled by default. Use `--right-terminal-gaps-free=false` to disable.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
   pub right_terminal_gaps_free: bool,
 
   /// Excess bandwidth for internal stripes.
@@ -87,11 +172,11 @@ pub struct AlignPairwiseParams {
   #[clap(long, value_enum)]
   pub gap_alignment_side: GapAlignmentSide,
 
-  /// Length of exactly matching k-mers used in the seed alignment of the query to the reference.
+  /// Length of exactly matching k-mers used in the seed alignment of the query to the reference. Shorter k-mers allow to find seed matches in more divergent sequences, at the cost of more spurious matches.
   #[clap(long)]
   pub kmer_length: usize,
 
-  /// Interval of successive k-mers on the query sequence. Should be small compared to the query length.
+  /// Interval of successive k-mers on the query sequence. Should be small compared to the query length. Smaller intervals help to find seed matches in short or fragmented sequences, at the cost of speed.
   #[clap(long)]
   pub kmer_distance: usize,
 
@@ -113,10 +198,70 @@ pub struct AlignPairwiseParams {
   #[clap(long)]
   pub min_seed_cover: f64,
 
+  /// Minimum number of seed matches (extended and chained k-mer matches, see `--min-match-length`) required to proceed with the banded alignment. Together with `--min-seed-cover`, this allows to reject sequences which only match the reference in a few places, e.g. sequences of a wrong virus. Decrease it to rescue alignment of short or fragmented sequences.
+  #[clap(long)]
+  pub min_seed_matches: usize,
+
   /// Number of times Nextclade will retry alignment with more relaxed results if alignment band boundaries are hit
   #[clap(long)]
   pub max_alignment_attempts: usize,
 
+  /// Align sequences locally: after alignment, clip the ends of the query sequence which do not align well to the reference.
+  ///
+  /// This is useful for fragments of the genome, such as single amplicons or partial genes, which may contain flanking sequence not present in the reference (for example primers, adapters or vector). Clipped nucleotides are removed from the aligned sequence and the corresponding reference positions are treated as not sequenced. The part of the reference covered by the remaining alignment is reported as `alignmentStart` and `alignmentEnd`.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub local_alignment: bool,
+
+  /// Normalize single-nucleotide insertions and deletions inside of homopolymers, which are common artifacts of Nanopore sequencing.
+  ///
+  /// After alignment, isolated 1-nucleotide deletions and insertions within runs of the same nucleotide in the reference (of length at least `--homopolymer-min-length`) are removed: deleted nucleotides are restored from the reference and inserted nucleotides are dropped. This happens before detection of mutations, frame shifts and QC, such that these artifacts do not produce spurious frame shifts. The normalized indels are listed in the `normalizedHomopolymerIndels` field of JSON and NDJSON outputs, and in the `normalizedHomopolymerIndels` column of CSV and TSV outputs. Note that genuine indels in homopolymers are normalized as well.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub normalize_homopolymer_indels: bool,
+
+  /// Minimum length of a homopolymer (in the reference sequence) for `--normalize-homopolymer-indels`.
+  #[clap(long)]
+  pub homopolymer_min_length: usize,
+
+  /// Algorithm used for nucleotide alignment.
+  ///
+  /// With "banded" (default), sequences are aligned using seed matching and banded alignment. With "wfa", closely related sequences are aligned using wavefront alignment, which is faster when the sequences have few differences. Wavefront alignment does not use codon-aware gap penalties and penalizes terminal gaps. Sequences differing from the reference by more than `--wfa-max-divergence` fall back to the banded alignment.
+  #[clap(long, value_enum)]
+  pub alignment_algorithm: AlignmentAlgorithm,
+
+  /// Maximum number of differences (mismatches and gap positions) per nucleotide for which wavefront alignment is attempted, when `--alignment-algorithm=wfa`. More divergent sequences are aligned using the banded alignment.
+  #[clap(long)]
+  pub wfa_max_divergence: f64,
+
+  /// Minimum length of the reference sequence for which seed matching uses chained minimizers instead of the k-mer index.
+  ///
+  /// Seed matching using the k-mer index becomes slow and unreliable for large genomes (above roughly 100 kb). For such genomes, minimizers (k-mers with the smallest hash in a sliding window) of the query sequence are matched exactly to the minimizers of the reference and the matches are chained into a colinear sequence of anchors, which then defines the band for the alignment. Both steps require time and memory linear in the length of the sequences.
+  #[clap(long)]
+  pub minimizer_seeding_min_ref_length: usize,
+
+  /// Maximum fraction of gaps (deletions) in the aligned part of a peptide. If exceeded, the peptide alignment is considered unreliable: aminoacid changes in the corresponding CDS are not reported, the CDS is listed among failed CDSes, a warning is emitted and the CDS is not considered in QC.
+  ///
+  /// Not checked by default. Can be overridden for individual CDSes using `cdsPeptideThresholds` field of the `alignmentParams` in pathogen.json.
+  #[clap(long)]
+  pub peptide_max_gap_fraction: Option<f64>,
+
+  /// Maximum fraction of unknown aminoacids (`X`) in the aligned part of a peptide. If exceeded, the peptide alignment is considered unreliable, same as with `--peptide-max-gap-fraction`.
+  ///
+  /// Not checked by default.
+  #[clap(long)]
+  pub peptide_max_unknown_fraction: Option<f64>,
+
+  /// Minimum score of peptide alignment, per aligned aminoacid of the query. If the score is lower, the peptide alignment is considered unreliable, same as with `--peptide-max-gap-fraction`.
+  ///
+  /// Not checked by default.
+  #[clap(long)]
+  pub peptide_min_score_per_aa: Option<f64>,
+
+  /// Thresholds for reliability of peptide alignment of individual CDSes, by CDS name. These override the corresponding global thresholds (`--peptide-max-gap-fraction`, `--peptide-max-unknown-fraction`, `--peptide-min-score-per-aa`). Can only be set in pathogen.json.
+  #[clap(skip)]
+  pub cds_peptide_thresholds: BTreeMap<String, PeptideReliabilityThresholds>,
+
   // The following args are deprecated and are kept for backwards compatibility (to emit errors if they are set)
   /// REMOVED
   #[clap(long, hide_long_help = true, hide_short_help = true)]
@@ -154,8 +299,13 @@ impl Default for AlignPairwiseParams {
       penalty_mismatch: 1,
       score_match: 3,
       max_band_area: 500_000_000, // requires around 500Mb for paths, 2GB for the scores
-      retry_reverse_complement: false,
+      alignment_memory_budget_mb: 1024,
+      alignment_abort_score_drop: None,
+      retry_reverse_complement: true,
       no_translate_past_stop: false,
+      ambiguous_codons: AmbiguousCodonPolicy::Resolve,
+      frame_shift_policy: FrameShiftPolicy::Mask,
+      compensated_frame_shift_max_length: 30,
       left_terminal_gaps_free: true,
       right_terminal_gaps_free: true,
       gap_alignment_side: GapAlignmentSide::Right,
@@ -167,7 +317,18 @@ impl Default for AlignPairwiseParams {
       min_match_length: 40,  // Experimentally determined, to keep off-target matches reasonably low
       allowed_mismatches: 8, // Ns count as mismatches
       window_size: 30,
+      min_seed_matches: 1,
       max_alignment_attempts: 3,
+      local_alignment: false,
+      normalize_homopolymer_indels: false,
+      homopolymer_min_length: 3,
+      alignment_algorithm: AlignmentAlgorithm::Banded,
+      wfa_max_divergence: 0.01,
+      minimizer_seeding_min_ref_length: 100_000,
+      peptide_max_gap_fraction: None,
+      peptide_max_unknown_fraction: None,
+      peptide_min_score_per_aa: None,
+      cds_peptide_thresholds: BTreeMap::new(),
 
       // The following args are deprecated and are kept for backwards compatibility (to emit errors if they are set)
       max_indel: None,
@@ -181,15 +342,32 @@ impl Default for AlignPairwiseParams {
 }
 
 impl AlignPairwiseParams {
+  /// Returns thresholds of peptide alignment reliability for a given CDS: CDS-specific values where these are set,
+  /// and global values otherwise
+  pub fn peptide_reliability_thresholds(&self, cds_name: &str) -> PeptideReliabilityThresholds {
+    let cds_thresholds = self.cds_peptide_thresholds.get(cds_name);
+    PeptideReliabilityThresholds {
+      max_gap_fraction: cds_thresholds
+        .and_then(|thresholds| thresholds.max_gap_fraction)
+        .or(self.peptide_max_gap_fraction),
+      max_unknown_fraction: cds_thresholds
+        .and_then(|thresholds| thresholds.max_unknown_fraction)
+        .or(self.peptide_max_unknown_fraction),
+      min_score_per_aa: cds_thresholds
+        .and_then(|thresholds| thresholds.min_score_per_aa)
+        .or(self.peptide_min_score_per_aa),
+    }
+  }
+
   pub fn validate(&self) -> Result<(), Report> {
     #[rustfmt::skip]
   let deprecated = BTreeMap::from([
-    (o!("--min-seeds            (minSeeds)"),             &self.min_seeds),
-    (o!("--seed-length          (seedLength)"),           &self.seed_length),
-    (o!("--seed-spacing         (seedSpacing)"),          &self.seed_spacing),
-    (o!("--min-match-rate       (minMatchRate)"),         &self.min_match_rate),
-    (o!("--mismatches-allowed   (mismatchesAllowed)"),    &self.mismatches_allowed),
-    (o!("--max-indel            (maxIndel)"),             &self.max_indel),
+    (o!("--min-seeds            (minSeeds)            use --min-seed-matches   (minSeedMatches) instead"),    &self.min_seeds),
+    (o!("--seed-length          (seedLength)          use --kmer-length        (kmerLength) instead"),        &self.seed_length),
+    (o!("--seed-spacing         (seedSpacing)         use --kmer-distance      (kmerDistance) instead"),      &self.seed_spacing),
+    (o!("--min-match-rate       (minMatchRate)        use --min-seed-cover     (minSeedCover) instead"),      &self.min_match_rate),
+    (o!("--mismatches-allowed   (mismatchesAllowed)   use --allowed-mismatches (allowedMismatches) instead"), &self.mismatches_allowed),
+    (o!("--max-indel            (maxIndel)"),                                                                 &self.max_indel),
   ]);
 
     if deprecated.values().any(|val| val.is_some()) {
@@ -206,6 +384,73 @@ Refer to user documentation for more details."#
       );
     }
 
+    let mut errors = vec![];
+
+    #[rustfmt::skip]
+    let non_negative = [
+      ("--penalty-gap-extend            (penaltyGapExtend)",          self.penalty_gap_extend),
+      ("--penalty-gap-open              (penaltyGapOpen)",            self.penalty_gap_open),
+      ("--penalty-gap-open-in-frame     (penaltyGapOpenInFrame)",     self.penalty_gap_open_in_frame),
+      ("--penalty-gap-open-out-of-frame (penaltyGapOpenOutOfFrame)",  self.penalty_gap_open_out_of_frame),
+      ("--penalty-mismatch              (penaltyMismatch)",           self.penalty_mismatch),
+      ("--excess-bandwidth              (excessBandwidth)",           self.excess_bandwidth),
+      ("--terminal-bandwidth            (terminalBandwidth)",         self.terminal_bandwidth),
+    ];
+    for (key, val) in non_negative {
+      if val < 0 {
+        errors.push(format!("{key}: expected a non-negative number, but found {val}"));
+      }
+    }
+
+    #[rustfmt::skip]
+    let positive = [
+      ("--kmer-length                   (kmerLength)",                self.kmer_length),
+      ("--kmer-distance                 (kmerDistance)",              self.kmer_distance),
+      ("--window-size                   (windowSize)",                self.window_size),
+      ("--max-alignment-attempts        (maxAlignmentAttempts)",      self.max_alignment_attempts),
+    ];
+    for (key, val) in positive {
+      if val == 0 {
+        errors.push(format!("{key}: expected a positive number, but found {val}"));
+      }
+    }
+    if self.score_match <= 0 {
+      errors.push(format!(
+        "--score-match (scoreMatch): expected a positive number, but found {}",
+        self.score_match
+      ));
+    }
+
+    if let Some(score_drop) = self.alignment_abort_score_drop {
+      if score_drop <= 0 {
+        errors.push(format!(
+          "--alignment-abort-score-drop (alignmentAbortScoreDrop): expected a positive number, but found {score_drop}"
+        ));
+      }
+    }
+
+    #[rustfmt::skip]
+    let fractions = [
+      ("--min-seed-cover                (minSeedCover)",              Some(self.min_seed_cover)),
+      ("--wfa-max-divergence            (wfaMaxDivergence)",          Some(self.wfa_max_divergence)),
+      ("--peptide-max-gap-fraction      (peptideMaxGapFraction)",     self.peptide_max_gap_fraction),
+      ("--peptide-max-unknown-fraction  (peptideMaxUnknownFraction)", self.peptide_max_unknown_fraction),
+    ];
+    for (key, val) in fractions {
+      if let Some(val) = val {
+        if !(0.0..=1.0).contains(&val) {
+          errors.push(format!("{key}: expected a number between 0 and 1, but found {val}"));
+        }
+      }
+    }
+
+    if !errors.is_empty() {
+      let errors = errors.iter().map(|error| format!("  {error}")).join("\n");
+      return make_error!(
+        "The following alignment parameters (CLI arguments and config file fields) have invalid values:\n\n{errors}\n\nPlease correct them in your dataset and/or in command-line invocation."
+      );
+    }
+
     Ok(())
   }
 }
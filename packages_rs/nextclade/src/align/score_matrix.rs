@@ -1,7 +1,12 @@
 use crate::align::band_2d::{Band2d, Stripe};
 use crate::align::params::{AlignPairwiseParams, GapAlignmentSide};
+use crate::align::score_simd::{add_rows, qry_gap_scores};
 use crate::alphabet::letter::Letter;
+use crate::utils::progress::AlignmentProgress;
+use eyre::{eyre, Report};
 use log::trace;
+use std::collections::BTreeMap;
+use std::ops::ControlFlow;
 
 // store direction info for backtrace as bits in paths matrix
 // these indicate the currently optimal move
@@ -20,19 +25,79 @@ pub struct ScoreMatrixResult {
   pub paths: Band2d<i8>,
 }
 
+/// State of the filling of the score matrix after one of its rows, from which the filling of the subsequent rows can be
+/// resumed
+#[derive(Clone, Debug)]
+pub struct RowCheckpoint {
+  pub row: usize,
+  /// Scores of the cells of the row, in the columns of the row's stripe
+  pub scores: Vec<i32>,
+  /// Best scores of the alignments ending with a gap in query, in the columns of the row's stripe
+  pub qry_gaps: Vec<i32>,
+}
+
+/// Detects alignments which are clearly hopeless (e.g. sequences of a wrong virus or garbage), while the score matrix is
+/// being filled, so that they can be abandoned early ("X-drop").
+///
+/// Each cell is assessed by its score minus half of the match score for every query letter consumed so far. This
+/// adjusted score grows along a good alignment and declines where the query does not resemble the reference and letters
+/// only match by chance. The alignment is abandoned as soon as the best adjusted score in a row falls more than
+/// `max_drop` below the best adjusted score of the preceding rows.
+#[derive(Clone, Debug)]
+pub struct ScoreDropCheck {
+  max_drop: i32,
+  score_match: i32,
+  /// Best adjusted score seen so far, doubled to avoid fractions
+  best: i64,
+}
+
+impl ScoreDropCheck {
+  /// Creates the check if it is enabled in the parameters (`--alignment-abort-score-drop`)
+  pub fn from_params(params: &AlignPairwiseParams) -> Option<Self> {
+    params.alignment_abort_score_drop.map(|max_drop| Self {
+      max_drop,
+      score_match: params.score_match,
+      best: 0,
+    })
+  }
+
+  /// Checks the scores of a row of the matrix. Rows are expected to be checked in order, starting from the first one.
+  pub fn check(&mut self, row: usize, stripe: &Stripe, scores: &[i32]) -> ControlFlow<Report> {
+    let row_best = scores
+      .iter()
+      .zip(stripe.begin..)
+      .map(|(&score, qpos)| 2 * i64::from(score) - i64::from(self.score_match) * qpos as i64)
+      .max()
+      .unwrap_or(i64::MIN);
+
+    self.best = self.best.max(row_best);
+
+    if self.best - row_best > 2 * i64::from(self.max_drop) {
+      return ControlFlow::Break(eyre!(
+        "Unable to align: alignment score dropped by more than {} below its best value near reference position {row}. This is likely because the sequence is unrelated to the reference sequence or is of very low quality. The threshold can be adjusted using CLI flag '--alignment-abort-score-drop' or using 'alignmentAbortScoreDrop' field in the dataset's pathogen.json",
+        self.max_drop
+      ));
+    }
+
+    ControlFlow::Continue(())
+  }
+}
+
+/// Fills the alignment score matrix and the matrix of paths for the backtrace.
+///
+/// If `score_drop_check` is provided, filling is abandoned with an error as soon as the alignment turns out to be
+/// hopeless.
 pub fn score_matrix<T: Letter<T>>(
   qry_seq: &[T],
   ref_seq: &[T],
   gap_open_close: &[i32],
   stripes: &[Stripe],
   params: &AlignPairwiseParams,
-) -> ScoreMatrixResult {
+  mut score_drop_check: Option<ScoreDropCheck>,
+) -> Result<ScoreMatrixResult, Report> {
   let query_size = qry_seq.len();
   let ref_len = ref_seq.len();
-  let n_rows = ref_len + 1;
-  let n_cols = query_size + 1;
-
-  trace!("Score matrix: started: query_size={query_size}, ref_len={ref_len}, n_rows={n_rows}, n_cols={n_cols}");
+  trace!("Score matrix: started: query_size={query_size}, ref_len={ref_len}");
 
   let mut paths = Band2d::<i8>::new(stripes);
   let mut scores = Band2d::<i32>::new(stripes);
@@ -40,6 +105,80 @@ pub fn score_matrix<T: Letter<T>>(
 
   trace!("Score matrix: allocated alignment band of size={band_size}");
 
+  let mut progress = AlignmentProgress::new(ref_len, band_size);
+
+  let filled = score_rows(
+    qry_seq,
+    ref_seq,
+    gap_open_close,
+    stripes,
+    params,
+    None,
+    ref_len,
+    |row, row_scores, row_paths, _| {
+      scores.row_mut(row).copy_from_slice(row_scores);
+      paths.row_mut(row).copy_from_slice(row_paths);
+      progress.add(row_scores.len());
+      score_drop_check.as_mut().map_or(ControlFlow::Continue(()), |check| {
+        check.check(row, &stripes[row], row_scores)
+      })
+    },
+  );
+
+  if let ControlFlow::Break(report) = filled {
+    return Err(report);
+  }
+
+  Ok(ScoreMatrixResult { scores, paths })
+}
+
+/// Scores of diagonal moves (match or mismatch), relative to the score of the upper-left cell, for every query letter
+/// aligned against one reference letter (a "query profile"). With the profile, the scores of the diagonal moves into a
+/// whole row are the element-wise sum of the previous row and of the profile, which is computed by a vectorized kernel.
+fn make_query_profile<T: Letter<T>>(qry_seq: &[T], ref_letter: T, params: &AlignPairwiseParams) -> Vec<i32> {
+  let is_ref_unknown = ref_letter.is_unknown();
+  qry_seq
+    .iter()
+    .map(|&qry_letter| {
+      if is_ref_unknown || qry_letter.is_unknown() {
+        // no need to look-up match score since unknown matches with everything.
+        // reduce match score by 1 to de-prioritize matches with unknown states.
+        params.score_match - 1
+      } else if T::lookup_match_score(qry_letter, ref_letter) > 0 {
+        params.score_match
+      } else {
+        -params.penalty_mismatch
+      }
+    })
+    .collect()
+}
+
+/// Fills rows of the alignment score matrix, starting after the row of the `start` checkpoint (or from the first row,
+/// if there is no checkpoint), up to and including `last_row`.
+///
+/// Only the previous row is kept in memory. Instead of storing the rows, `on_row` is called for each of them, with the
+/// row index, the scores and the paths of the row's cells, as well as the scores of query gaps, which are needed to
+/// create a `RowCheckpoint`. Filling stops early if `on_row` breaks, and the break value is returned.
+///
+/// Scores of diagonal moves and of query gaps only depend on the previous row, so they are computed for the whole row at
+/// once (see `align::score_simd`). Reference gaps depend on the cell to the left, so they, as well as the choice of the
+/// best move, are computed one cell at a time. This sequential part dominates the run time: the vectorized kernels make
+/// no measurable difference for the whole alignment (see `benches/bench_score_matrix.rs`).
+pub fn score_rows<T: Letter<T>, B, F: FnMut(usize, &[i32], &[i8], &[i32]) -> ControlFlow<B>>(
+  qry_seq: &[T],
+  ref_seq: &[T],
+  gap_open_close: &[i32],
+  stripes: &[Stripe],
+  params: &AlignPairwiseParams,
+  start: Option<&RowCheckpoint>,
+  last_row: usize,
+  mut on_row: F,
+) -> ControlFlow<B> {
+  let query_size = qry_seq.len();
+  let ref_len = ref_seq.len();
+  let n_rows = ref_len + 1;
+  let n_cols = query_size + 1;
+
   let left_align = match params.gap_alignment_side {
     GapAlignmentSide::Left => 1,
     GapAlignmentSide::Right => 0,
@@ -56,40 +195,127 @@ pub fn score_matrix<T: Letter<T>>(
   // 2) if X is a base and Y is '-', rPos advances the same and the shift increases
   //    -> diagonal step in the matrix from (ri,si-1) to (ri+1,si)
 
-  paths[(0, 0)] = 0;
-  scores[(0, 0)] = 0;
-
-  // Initialize first row (start at + 1 since [(0,0)] is already set)
-  for qpos in (stripes[0].begin + 1)..stripes[0].end {
-    paths[(0, qpos)] = REF_GAP_EXTEND + REF_GAP_MATRIX;
-    if params.left_terminal_gaps_free {
-      // Left terminal qry insertion  is free
-      scores[(0, qpos)] = 0;
-    } else {
-      // Left terminal qry insertion is not free
-      // TODO: Consider whether qry insertion should ever be free, not only qry deletion!
-      if qpos == 1 {
-        scores[(0, 1)] = -gap_open_close[0];
+  let max_stripe_len = stripes.iter().map(Stripe::len).max().unwrap_or_default();
+  let mut qry_gaps = vec![NO_ALIGN; n_cols];
+  let mut diagonal_scores = vec![NO_ALIGN; n_cols];
+  let mut qry_gaps_extended = vec![0; n_cols];
+  // Query profiles are computed on first use, for each distinct letter of the reference sequence
+  let mut query_profiles = BTreeMap::<T, Vec<i32>>::new();
+
+  // Scores of the previous and of the current row, in the columns of the corresponding stripes
+  let mut prev_scores = Vec::<i32>::with_capacity(max_stripe_len);
+  let mut row_scores = Vec::<i32>::with_capacity(max_stripe_len);
+  let mut row_paths = Vec::<i8>::with_capacity(max_stripe_len);
+
+  let first_row = if let Some(start) = start {
+    let stripe = &stripes[start.row];
+    prev_scores.extend_from_slice(&start.scores);
+    qry_gaps[stripe.begin..stripe.end].copy_from_slice(&start.qry_gaps);
+    start.row + 1
+  } else {
+    let stripe = &stripes[0];
+    row_scores.push(0);
+    row_paths.push(0);
+
+    // Initialize first row (start at + 1 since [(0,0)] is already set)
+    for qpos in (stripe.begin + 1)..stripe.end {
+      row_paths.push(REF_GAP_EXTEND + REF_GAP_MATRIX);
+      if params.left_terminal_gaps_free {
+        // Left terminal qry insertion  is free
+        row_scores.push(0);
       } else {
-        scores[(0, qpos)] = scores[(0, qpos - 1)] - params.penalty_gap_extend;
+        // Left terminal qry insertion is not free
+        // TODO: Consider whether qry insertion should ever be free, not only qry deletion!
+        if qpos == 1 {
+          row_scores.push(-gap_open_close[0]);
+        } else {
+          row_scores.push(row_scores[qpos - 1] - params.penalty_gap_extend);
+        }
       }
     }
-  }
-  let mut qry_gaps = vec![NO_ALIGN; n_cols];
+
+    on_row(0, &row_scores, &row_paths, &qry_gaps[stripe.begin..stripe.end])?;
+    std::mem::swap(&mut prev_scores, &mut row_scores);
+    1
+  };
 
   // Iterate over rows
-  for ri in 1..=ref_len {
+  for ri in first_row..=last_row {
     let mut ref_gaps = NO_ALIGN;
+    let stripe = &stripes[ri];
+    row_scores.clear();
+    row_paths.clear();
+
+    // Diagonal moves are possible into the cells which have the upper-left neighbour in the previous stripe
+    let prev_stripe = &stripes[ri - 1];
+    let diagonal_begin = stripe.begin.max(prev_stripe.begin + 1);
+    let diagonal_end = stripe.end.min(prev_stripe.end + 1);
+    if diagonal_begin < diagonal_end {
+      let ref_letter = ref_seq[ri - 1];
+      let profile = query_profiles
+        .entry(ref_letter)
+        .or_insert_with(|| make_query_profile(qry_seq, ref_letter, params));
+      add_rows(
+        &mut diagonal_scores[diagonal_begin..diagonal_end],
+        &prev_scores[(diagonal_begin - 1 - prev_stripe.begin)..(diagonal_end - 1 - prev_stripe.begin)],
+        &profile[(diagonal_begin - 1)..(diagonal_end - 1)],
+      );
+    }
+
+    // Query gaps (vertical moves) are possible into the cells which have the upper neighbour in the previous stripe.
+    // These only depend on the previous row, so they are computed for the whole row at once too.
+    let vertical_begin = stripe.begin.max(1);
+    let vertical_end = stripe.end.min(prev_stripe.end);
+    if vertical_begin < vertical_end {
+      let up_scores = &prev_scores[(vertical_begin - prev_stripe.begin)..(vertical_end - prev_stripe.begin)];
+      let gap_open = gap_open_close[ri - 1];
+
+      // At the end of query sequence, right terminal gaps can be free. This column is computed separately below.
+      let normal_end = if params.right_terminal_gaps_free {
+        vertical_end.min(query_size)
+      } else {
+        vertical_end
+      };
+
+      // Extension is allowed positionally only where the gap can continue from the stripe two rows above
+      let extend_end = if ri >= 2 {
+        stripes[ri - 2]
+          .end
+          .clamp(vertical_begin, normal_end.max(vertical_begin))
+      } else {
+        vertical_begin
+      };
+
+      qry_gap_scores(
+        &mut qry_gaps[vertical_begin..extend_end],
+        &mut qry_gaps_extended[vertical_begin..extend_end],
+        &up_scores[..(extend_end - vertical_begin)],
+        params.penalty_gap_extend,
+        gap_open,
+      );
+
+      for qpos in extend_end..normal_end {
+        qry_gaps[qpos] = up_scores[qpos - vertical_begin] - gap_open;
+        qry_gaps_extended[qpos] = 0;
+      }
+
+      if normal_end < vertical_end {
+        //end of query sequence make right terminal gap free
+        let qpos = query_size;
+        let q_gap_extend = qry_gaps[qpos];
+        let q_gap_open = up_scores[qpos - vertical_begin];
+        let is_extended = q_gap_extend >= q_gap_open && ri >= 2 && qpos < stripes[ri - 2].end;
+        qry_gaps[qpos] = if is_extended { q_gap_extend } else { q_gap_open };
+        qry_gaps_extended[qpos] = if is_extended { -1 } else { 0 };
+      }
+    }
 
-    for qpos in stripes[ri].begin..stripes[ri].end {
+    for qpos in stripe.begin..stripe.end {
       let mut tmp_path = 0;
       let mut score = NO_ALIGN; // Needs to be very negative so that one path is always the best
       let mut origin = 0;
-      let q_gap_extend: i32;
       let r_gap_extend: i32;
       let r_gap_open: i32;
-      let q_gap_open: i32;
-      let tmp_match: i32;
       let mut tmp_score: i32;
 
       if qpos == 0 {
@@ -105,7 +331,7 @@ pub fn score_matrix<T: Letter<T>>(
           if ri == 1 {
             score = -gap_open_close[0];
           } else {
-            score = scores[(ri - 1, 0)] - params.penalty_gap_extend;
+            score = prev_scores[0] - params.penalty_gap_extend;
           }
         }
       } else {
@@ -113,16 +339,8 @@ pub fn score_matrix<T: Letter<T>>(
         // no gap -- match case
 
         // ^ If stripes allow to move up diagonally to upper left
-        if qpos > stripes[ri - 1].begin && qpos - 1 < stripes[ri - 1].end {
-          score = if qry_seq[qpos - 1].is_unknown() || ref_seq[ri - 1].is_unknown() {
-            // no need to look-up match score since unknown matches with everything.
-            // reduce match score by 1 to de-prioritize matches with unknown states.
-            scores[(ri - 1, qpos - 1)] + params.score_match - 1
-          } else if T::lookup_match_score(qry_seq[qpos - 1], ref_seq[ri - 1]) > 0 {
-            scores[(ri - 1, qpos - 1)] + params.score_match
-          } else {
-            scores[(ri - 1, qpos - 1)] - params.penalty_mismatch
-          };
+        if qpos > prev_stripe.begin && qpos - 1 < prev_stripe.end {
+          score = diagonal_scores[qpos];
           origin = MATCH;
         } else {
           tmp_path = tmp_path | BOUNDARY; // mark boundary when possible moves are restricted. here: can't move up or left-up
@@ -131,18 +349,19 @@ pub fn score_matrix<T: Letter<T>>(
         // check the scores of a reference gap
         // if qpos == stripes.begin: ref gap not allowed
         // thus path skipped
-        if qpos > stripes[ri].begin {
+        if qpos > stripe.begin {
+          let left_score = row_scores[qpos - 1 - stripe.begin];
           if ri != ref_len || !params.right_terminal_gaps_free {
             //normal case, not at end of ref sequence
             r_gap_extend = ref_gaps - params.penalty_gap_extend;
-            r_gap_open = scores[(ri, qpos - 1)] - gap_open_close[ri];
+            r_gap_open = left_score - gap_open_close[ri];
           } else {
             // at end of ref sequence if right terminal gaps are free
             // TODO: Consider whether qry insertion should ever be free, not only qry deletion!
             r_gap_extend = ref_gaps;
-            r_gap_open = scores[(ri, qpos - 1)];
+            r_gap_open = left_score;
           }
-          if r_gap_extend >= r_gap_open && qpos > stripes[ri].begin + 1 {
+          if r_gap_extend >= r_gap_open && qpos > stripe.begin + 1 {
             // extension better than opening (and ^ extension allowed positionally)
             tmp_score = r_gap_extend;
             tmp_path += REF_GAP_EXTEND;
@@ -160,26 +379,12 @@ pub fn score_matrix<T: Letter<T>>(
           tmp_path = tmp_path | BOUNDARY; // mark boundary if no ref gap allowed due to stripes: can't move left
         }
 
-        // check the scores of a query gap
-        if qpos < stripes[ri - 1].end {
-          // need stripe above to move from, otherwise no scores[(ri-1, qpos)] not existing
-          if qpos != query_size || !params.right_terminal_gaps_free {
-            //normal case, not at end of query sequence
-            q_gap_extend = qry_gaps[qpos] - params.penalty_gap_extend;
-            q_gap_open = scores[(ri - 1, qpos)] - gap_open_close[ri - 1];
-          } else {
-            //end of query sequence make right terminal gap free
-            q_gap_extend = qry_gaps[qpos];
-            q_gap_open = scores[(ri - 1, qpos)];
-          }
-          if q_gap_extend >= q_gap_open && qpos < stripes[ri - 2].end {
-            // extension better than opening (and ^ extension allowed positionally)
-            tmp_score = q_gap_extend;
+        // check the scores of a query gap (computed for the whole row above)
+        if qpos < prev_stripe.end {
+          if qry_gaps_extended[qpos] != 0 {
             tmp_path += QRY_GAP_EXTEND;
-          } else {
-            tmp_score = q_gap_open;
           }
-          qry_gaps[qpos] = tmp_score;
+          tmp_score = qry_gaps[qpos];
           if score - left_align < tmp_score {
             score = tmp_score;
             origin = QRY_GAP_MATRIX;
@@ -191,11 +396,15 @@ pub fn score_matrix<T: Letter<T>>(
       }
 
       tmp_path += origin;
-      paths[(ri, qpos)] = tmp_path;
-      scores[(ri, qpos)] = score;
+      row_paths.push(tmp_path);
+      row_scores.push(score);
     }
+
+    on_row(ri, &row_scores, &row_paths, &qry_gaps[stripe.begin..stripe.end])?;
+    std::mem::swap(&mut prev_scores, &mut row_scores);
   }
-  ScoreMatrixResult { scores, paths }
+
+  ControlFlow::Continue(())
 }
 
 #[cfg(test)]
@@ -247,7 +456,7 @@ mod tests {
     let mut stripes = simple_stripes(mean_shift, band_width, ref_seq.len(), qry_seq.len());
     stripes[2].end = stripes[2].end - 1;
     stripes[8].begin = stripes[8].begin + 1;
-    let result = score_matrix(&qry_seq, &ref_seq, &ctx.gap_open_close, &stripes, &ctx.params);
+    let result = score_matrix(&qry_seq, &ref_seq, &ctx.gap_open_close, &stripes, &ctx.params, None)?;
 
     #[rustfmt::skip]
     let expected_scores = Band2d::<i32>::with_data(
@@ -290,4 +499,30 @@ mod tests {
 
     Ok(())
   }
+
+  #[rstest]
+  #[case::related("ACGTTGCA", false)]
+  #[case::unrelated("GGGGGGGG", true)]
+  fn aborts_hopeless_alignment(ctx: Context, #[case] qry_unit: &str, #[case] is_aborted: bool) -> Result<(), Report> {
+    let ref_seq = to_nuc_seq(&"ACGTTGCA".repeat(10))?;
+    let qry_seq = to_nuc_seq(&qry_unit.repeat(10))?;
+
+    let params = AlignPairwiseParams {
+      alignment_abort_score_drop: Some(20),
+      ..ctx.params
+    };
+
+    let stripes = simple_stripes(0, 5, ref_seq.len(), qry_seq.len());
+    let result = score_matrix(
+      &qry_seq,
+      &ref_seq,
+      &ctx.gap_open_close,
+      &stripes,
+      &params,
+      ScoreDropCheck::from_params(&params),
+    );
+
+    assert_eq!(is_aborted, result.is_err());
+    Ok(())
+  }
 }
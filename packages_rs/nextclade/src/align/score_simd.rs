@@ -0,0 +1,220 @@
+//! Vectorized kernels used when filling the alignment score matrix.
+//!
+//! The kernel variant is selected once at runtime, depending on the instruction set extensions supported by the CPU:
+//! AVX2 or SSE4.1 on x86, NEON on aarch64 (where it is always available). Other platforms use the scalar kernel.
+//!
+//! Only the steps which depend solely on the previous row are vectorized. The recurrence along the row (reference gaps
+//! and the choice of the best move) remains scalar, so the kernels speed up these steps, not the alignment as a whole.
+
+use std::sync::OnceLock;
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::{
+  __m128i, __m256i, _mm256_cmpeq_epi32, _mm256_loadu_si256, _mm256_max_epi32, _mm256_set1_epi32, _mm256_storeu_si256,
+  _mm256_sub_epi32, _mm_cmpeq_epi32, _mm_loadu_si128, _mm_max_epi32, _mm_set1_epi32, _mm_storeu_si128, _mm_sub_epi32,
+};
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::{
+  __m128i, __m256i, _mm256_cmpeq_epi32, _mm256_loadu_si256, _mm256_max_epi32, _mm256_set1_epi32, _mm256_storeu_si256,
+  _mm256_sub_epi32, _mm_cmpeq_epi32, _mm_loadu_si128, _mm_max_epi32, _mm_set1_epi32, _mm_storeu_si128, _mm_sub_epi32,
+};
+
+/// Adds two rows of scores element-wise: `out[i] = a[i] + b[i]`
+///
+/// NOTE: this is a plain loop on purpose. The compiler vectorizes it on its own, and in benchmarks (see
+/// `benches/bench_score_matrix.rs`) it was faster than the variants written with intrinsics.
+pub fn add_rows(out: &mut [i32], a: &[i32], b: &[i32]) {
+  assert!(a.len() == out.len() && b.len() == out.len());
+  for ((out, &a), &b) in out.iter_mut().zip(a).zip(b) {
+    *out = a + b;
+  }
+}
+
+/// Computes the scores of gaps in query (vertical moves in the score matrix) for the cells of a row: either extension of
+/// the gap ending in the cell above, `gaps[i] - penalty_extend`, or opening of a new gap after the cell above,
+/// `up[i] - penalty_open`, whichever is higher, with ties going to the extension. The scores are written back to `gaps`,
+/// and `extended[i]` is set to -1 (all bits set) where the gap is extended and to 0 otherwise.
+pub fn qry_gap_scores(gaps: &mut [i32], extended: &mut [i32], up: &[i32], penalty_extend: i32, penalty_open: i32) {
+  assert!(extended.len() == gaps.len() && up.len() == gaps.len());
+  // SAFETY: the kernel is only selected if the CPU supports it, and the lengths of the slices are checked above
+  unsafe { qry_gap_scores_kernel()(gaps, extended, up, penalty_extend, penalty_open) };
+}
+
+type QryGapScoresKernel = unsafe fn(&mut [i32], &mut [i32], &[i32], i32, i32);
+
+/// Selects the variant of `qry_gap_scores()` supported by the CPU. Detection runs once, rather than for every row.
+fn qry_gap_scores_kernel() -> QryGapScoresKernel {
+  static KERNEL: OnceLock<QryGapScoresKernel> = OnceLock::new();
+  *KERNEL.get_or_init(|| {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+      if is_x86_feature_detected!("avx2") {
+        return qry_gap_scores_avx2;
+      }
+      if is_x86_feature_detected!("sse4.1") {
+        return qry_gap_scores_sse41;
+      }
+    }
+
+    // NEON is part of the aarch64 baseline
+    #[cfg(target_arch = "aarch64")]
+    return qry_gap_scores_neon;
+
+    #[cfg(not(target_arch = "aarch64"))]
+    qry_gap_scores_scalar
+  })
+}
+
+/// Scalar variant of `qry_gap_scores()`
+pub fn qry_gap_scores_scalar(
+  gaps: &mut [i32],
+  extended: &mut [i32],
+  up: &[i32],
+  penalty_extend: i32,
+  penalty_open: i32,
+) {
+  for ((gap, extended), &up) in gaps.iter_mut().zip(extended).zip(up) {
+    let gap_extend = *gap - penalty_extend;
+    let gap_open = up - penalty_open;
+    if gap_extend >= gap_open {
+      *gap = gap_extend;
+      *extended = -1;
+    } else {
+      *gap = gap_open;
+      *extended = 0;
+    }
+  }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[allow(clippy::cast_ptr_alignment)] // Loads and stores are unaligned
+#[target_feature(enable = "avx2")]
+unsafe fn qry_gap_scores_avx2(
+  gaps: &mut [i32],
+  extended: &mut [i32],
+  up: &[i32],
+  penalty_extend: i32,
+  penalty_open: i32,
+) {
+  const LANES: usize = 8;
+  let len = gaps.len() - gaps.len() % LANES;
+  let v_penalty_extend = _mm256_set1_epi32(penalty_extend);
+  let v_penalty_open = _mm256_set1_epi32(penalty_open);
+  for i in (0..len).step_by(LANES) {
+    let v_gap = _mm256_loadu_si256(gaps.as_ptr().add(i).cast::<__m256i>());
+    let v_up = _mm256_loadu_si256(up.as_ptr().add(i).cast::<__m256i>());
+    let v_extend = _mm256_sub_epi32(v_gap, v_penalty_extend);
+    let v_open = _mm256_sub_epi32(v_up, v_penalty_open);
+    let v_best = _mm256_max_epi32(v_extend, v_open);
+    _mm256_storeu_si256(gaps.as_mut_ptr().add(i).cast::<__m256i>(), v_best);
+    _mm256_storeu_si256(
+      extended.as_mut_ptr().add(i).cast::<__m256i>(),
+      _mm256_cmpeq_epi32(v_best, v_extend),
+    );
+  }
+  qry_gap_scores_scalar(
+    &mut gaps[len..],
+    &mut extended[len..],
+    &up[len..],
+    penalty_extend,
+    penalty_open,
+  );
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[allow(clippy::cast_ptr_alignment)] // Loads and stores are unaligned
+#[target_feature(enable = "sse4.1")]
+unsafe fn qry_gap_scores_sse41(
+  gaps: &mut [i32],
+  extended: &mut [i32],
+  up: &[i32],
+  penalty_extend: i32,
+  penalty_open: i32,
+) {
+  const LANES: usize = 4;
+  let len = gaps.len() - gaps.len() % LANES;
+  let v_penalty_extend = _mm_set1_epi32(penalty_extend);
+  let v_penalty_open = _mm_set1_epi32(penalty_open);
+  for i in (0..len).step_by(LANES) {
+    let v_gap = _mm_loadu_si128(gaps.as_ptr().add(i).cast::<__m128i>());
+    let v_up = _mm_loadu_si128(up.as_ptr().add(i).cast::<__m128i>());
+    let v_extend = _mm_sub_epi32(v_gap, v_penalty_extend);
+    let v_open = _mm_sub_epi32(v_up, v_penalty_open);
+    let v_best = _mm_max_epi32(v_extend, v_open);
+    _mm_storeu_si128(gaps.as_mut_ptr().add(i).cast::<__m128i>(), v_best);
+    _mm_storeu_si128(
+      extended.as_mut_ptr().add(i).cast::<__m128i>(),
+      _mm_cmpeq_epi32(v_best, v_extend),
+    );
+  }
+  qry_gap_scores_scalar(
+    &mut gaps[len..],
+    &mut extended[len..],
+    &up[len..],
+    penalty_extend,
+    penalty_open,
+  );
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn qry_gap_scores_neon(
+  gaps: &mut [i32],
+  extended: &mut [i32],
+  up: &[i32],
+  penalty_extend: i32,
+  penalty_open: i32,
+) {
+  use std::arch::aarch64::{vceqq_s32, vdupq_n_s32, vld1q_s32, vmaxq_s32, vreinterpretq_s32_u32, vst1q_s32, vsubq_s32};
+
+  const LANES: usize = 4;
+  let len = gaps.len() - gaps.len() % LANES;
+  let v_penalty_extend = vdupq_n_s32(penalty_extend);
+  let v_penalty_open = vdupq_n_s32(penalty_open);
+  for i in (0..len).step_by(LANES) {
+    let v_gap = vld1q_s32(gaps.as_ptr().add(i));
+    let v_up = vld1q_s32(up.as_ptr().add(i));
+    let v_extend = vsubq_s32(v_gap, v_penalty_extend);
+    let v_open = vsubq_s32(v_up, v_penalty_open);
+    let v_best = vmaxq_s32(v_extend, v_open);
+    vst1q_s32(gaps.as_mut_ptr().add(i), v_best);
+    vst1q_s32(
+      extended.as_mut_ptr().add(i),
+      vreinterpretq_s32_u32(vceqq_s32(v_best, v_extend)),
+    );
+  }
+  qry_gap_scores_scalar(
+    &mut gaps[len..],
+    &mut extended[len..],
+    &up[len..],
+    penalty_extend,
+    penalty_open,
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::empty(0)]
+  #[case::shorter_than_vector(3)]
+  #[case::whole_vectors(16)]
+  #[case::with_remainder(21)]
+  fn computes_qry_gap_scores(#[case] len: usize) {
+    let up = (0..len as i32).map(|x| (x * 7) % 11 - 5).collect::<Vec<_>>();
+    let gaps = (0..len as i32).map(|x| (x * 5) % 13 - 10).collect::<Vec<_>>();
+
+    let mut expected_gaps = gaps.clone();
+    let mut expected_extended = vec![0; len];
+    qry_gap_scores_scalar(&mut expected_gaps, &mut expected_extended, &up, 1, 3);
+
+    let mut actual_gaps = gaps;
+    let mut actual_extended = vec![0; len];
+    qry_gap_scores(&mut actual_gaps, &mut actual_extended, &up, 1, 3);
+
+    assert_eq!((expected_gaps, expected_extended), (actual_gaps, actual_extended));
+  }
+}
@@ -49,6 +49,21 @@ fn abs_shift(seed1: &SeedMatch2, seed2: &SeedMatch2) -> isize {
   abs(seed2.offset - seed1.offset)
 }
 
+/// Estimates how far the alignment path can stray from the seed diagonals in the stretch between two consecutive seeds
+/// of the chain, in addition to the shift between them.
+///
+/// Indels in the unseeded stretch can move the path back and forth without changing the net shift between the seeds.
+/// Treating them as a random walk, the excursion grows as the square root of the length of the stretch. So the band is
+/// widened where seeds are far apart, while closely spaced seeds keep the minimum of `excess_bandwidth`, instead of
+/// using the same width everywhere and relying on retries when the band boundary is hit.
+fn estimate_excess_bandwidth(current_seed: &SeedMatch2, next_seed: &SeedMatch2, excess_bandwidth: isize) -> isize {
+  let unseeded_length = next_seed
+    .ref_pos
+    .saturating_sub(current_seed.ref_pos + current_seed.length);
+  let random_walk_excursion = (unseeded_length as f64).sqrt().ceil() as isize;
+  max(excess_bandwidth, random_walk_excursion)
+}
+
 // Function that processes a seed, adds a band, rewind the band vector as necessary
 // to accommodate any extended band width implied by look-back-length
 struct RewindResult {
@@ -151,7 +166,8 @@ pub fn create_alignment_band(
   for next_seed in chain.iter().skip(1) {
     let mean_offset = (next_seed.offset + current_seed.offset) / 2; // offset of gap seed
     let shift = abs_shift(current_seed, next_seed) / 2; // distance from mean offset
-    look_forward_length = shift + excess_bandwidth;
+    let junction_excess_bandwidth = estimate_excess_bandwidth(current_seed, next_seed, excess_bandwidth);
+    look_forward_length = shift + junction_excess_bandwidth;
 
     // attempt to add new a band for current_seed, then rewind as necessary to accommodate shift
     RewindResult {
@@ -163,7 +179,7 @@ pub fn create_alignment_band(
       &mut bands,
       mean_offset,
       look_forward_length,
-      shift + excess_bandwidth,
+      shift + junction_excess_bandwidth,
       minimal_bandwidth,
       ref_len,
     );
@@ -172,8 +188,8 @@ pub fn create_alignment_band(
     current_band = TrapezoidDirectParams {
       ref_start: current_ref_end,
       ref_end: next_seed.ref_pos as isize + look_forward_length,
-      min_offset: mean_offset - max(look_back_length, excess_bandwidth),
-      max_offset: mean_offset + max(look_back_length, excess_bandwidth),
+      min_offset: mean_offset - max(look_back_length, junction_excess_bandwidth),
+      max_offset: mean_offset + max(look_back_length, junction_excess_bandwidth),
     };
     current_seed = next_seed;
   }
@@ -334,4 +350,28 @@ mod tests {
 
     Ok(())
   }
+
+  #[rstest]
+  #[case::adjacent_seeds(100, 10, 9)]
+  #[case::short_stretch(140, 60, 9)]
+  #[case::long_stretch(10_100, 10_000, 100)]
+  fn estimates_excess_bandwidth_from_unseeded_stretch(
+    #[case] next_ref_pos: usize,
+    #[case] unseeded_length: usize,
+    #[case] expected: isize,
+  ) {
+    let current_seed = SeedMatch2 {
+      qry_pos: 50,
+      ref_pos: 50,
+      length: next_ref_pos - unseeded_length - 50,
+      offset: 0,
+    };
+    let next_seed = SeedMatch2 {
+      qry_pos: next_ref_pos,
+      ref_pos: next_ref_pos,
+      length: 40,
+      offset: 0,
+    };
+    assert_eq!(estimate_excess_bandwidth(&current_seed, &next_seed, 9), expected);
+  }
 }
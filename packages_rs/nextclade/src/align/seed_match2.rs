@@ -1,5 +1,6 @@
 use crate::align::params::AlignPairwiseParams;
 use crate::align::seed_alignment::write_matches_to_file;
+use crate::align::seed_minimizer::{get_minimizer_seed_matches, MinimizerSeedIndex};
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::{from_nuc_seq, Nuc};
 use crate::make_error;
@@ -481,6 +482,28 @@ pub fn get_seed_matches2(
   let seed_matches = chain_seeds(&matches);
   // write_matches_to_file(&seed_matches, "chained_matches.csv");
 
+  check_seed_cover(qry_seq, &seed_matches, params)?;
+
+  Ok(seed_matches)
+}
+
+/// Verifies that there are sufficiently many chained seed matches and that they cover sufficient fraction of the query
+/// sequence
+pub fn check_seed_cover(
+  qry_seq: &[Nuc],
+  seed_matches: &[SeedMatch2],
+  params: &AlignPairwiseParams,
+) -> Result<(), Report> {
+  if seed_matches.len() < params.min_seed_matches {
+    return make_error!(
+      "Unable to align: seed alignment found {} matching region(s), which is less than expected {} \
+      (configurable using 'min seed matches' CLI flag or dataset property). This is likely due to low quality of the \
+      provided sequence, or due to using incorrect reference sequence.",
+      seed_matches.len(),
+      params.min_seed_matches
+    );
+  }
+
   let sum_of_seed_length: usize = seed_matches.iter().map(|sm| sm.length).sum();
   if (sum_of_seed_length as f64 / qry_seq.len() as f64) < params.min_seed_cover {
     let query_knowns = qry_seq.iter().filter(|n| n.is_acgt()).count();
@@ -495,7 +518,7 @@ pub fn get_seed_matches2(
     }
   }
 
-  Ok(seed_matches)
+  Ok(())
 }
 
 pub struct SeedMatchesResult<'a> {
@@ -509,9 +532,15 @@ pub fn get_seed_matches_maybe_reverse_complement<'a>(
   qry_seq: &'a [Nuc],
   ref_seq: &[Nuc],
   seed_index: &CodonSpacedIndex,
+  minimizer_index: Option<&MinimizerSeedIndex>,
   params: &AlignPairwiseParams,
 ) -> Result<SeedMatchesResult<'a>, Report> {
-  match get_seed_matches2(qry_seq, ref_seq, seed_index, params) {
+  let get_seed_matches = |qry_seq: &[Nuc]| match minimizer_index {
+    Some(minimizer_index) => get_minimizer_seed_matches(qry_seq, minimizer_index, params),
+    None => get_seed_matches2(qry_seq, ref_seq, seed_index, params),
+  };
+
+  match get_seed_matches(qry_seq) {
     Ok(seed_matches) => Ok(SeedMatchesResult {
       qry_seq: Cow::Borrowed(qry_seq),
       seed_matches,
@@ -521,7 +550,7 @@ pub fn get_seed_matches_maybe_reverse_complement<'a>(
       if params.retry_reverse_complement {
         let mut rev_complement = qry_seq.to_owned();
         reverse_complement_in_place(&mut rev_complement);
-        let seed_matches = get_seed_matches2(&rev_complement, ref_seq, seed_index, params).map_err(|_| report)?;
+        let seed_matches = get_seed_matches(&rev_complement).map_err(|_| report)?;
         Ok(SeedMatchesResult {
           qry_seq: Cow::Owned(rev_complement),
           seed_matches,
@@ -538,6 +567,7 @@ pub fn get_seed_matches_maybe_reverse_complement<'a>(
 mod tests {
   use super::*;
   use crate::alphabet::nuc::to_nuc_seq;
+  use crate::utils::error::report_to_string;
   use eyre::Report;
   use pretty_assertions::assert_eq;
   use rstest::rstest;
@@ -561,4 +591,50 @@ mod tests {
     assert_eq!(expected, actual);
     Ok(())
   }
+
+  #[rstest]
+  fn detects_reverse_complement() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq(
+      "GCTAAAGACAATTACATAACATACACGTCAGCACGAAACTTGTTGGCCCAGTGTGAATCGCTTAAGGGTTAAGTAAGTGTGATGCATACGCCTTTACTTGCTGTG\
+      TCCACCCCATCGGACTGGCATTTTTATTACACTCAGAAACAGAACTCGGGTAATTTTGACAGGTCACGCAGAGGCGCGCCCTCCTGAAGTGCGTGGACACTCGCTA\
+      TGAATCTCTGATTTACCCACTCTGCCAAACTCCAGCGCGGTCAGTTCCATCACCCTAAGTAACCGAATAATGCGTTCGCTCTATTGACT",
+    )?;
+    let mut qry_seq = ref_seq[20..280].to_vec();
+    reverse_complement_in_place(&mut qry_seq);
+
+    let seed_index = CodonSpacedIndex::from_sequence(&ref_seq);
+    let params = AlignPairwiseParams::default();
+    let result = get_seed_matches_maybe_reverse_complement(&qry_seq, &ref_seq, &seed_index, None, &params)?;
+
+    assert!(result.is_reverse_complement);
+    assert_eq!(from_nuc_seq(&result.qry_seq), from_nuc_seq(&ref_seq[20..280]));
+    Ok(())
+  }
+
+  #[rstest]
+  fn requires_min_seed_matches() -> Result<(), Report> {
+    let qry_seq = to_nuc_seq("ACGTACGTACGTACGTACGT")?;
+    let seed_matches = vec![SeedMatch2 {
+      ref_pos: 0,
+      qry_pos: 0,
+      length: 20,
+      offset: 0,
+    }];
+
+    let params = AlignPairwiseParams {
+      min_seed_matches: 1,
+      ..AlignPairwiseParams::default()
+    };
+    check_seed_cover(&qry_seq, &seed_matches, &params)?;
+
+    let params = AlignPairwiseParams {
+      min_seed_matches: 2,
+      ..AlignPairwiseParams::default()
+    };
+    assert_eq!(
+      report_to_string(&check_seed_cover(&qry_seq, &seed_matches, &params).unwrap_err()),
+      "Unable to align: seed alignment found 1 matching region(s), which is less than expected 2 (configurable using 'min seed matches' CLI flag or dataset property). This is likely due to low quality of the provided sequence, or due to using incorrect reference sequence."
+    );
+    Ok(())
+  }
 }
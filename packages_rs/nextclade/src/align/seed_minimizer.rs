@@ -0,0 +1,298 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::align::seed_match2::{check_seed_cover, SeedMatch2};
+use crate::alphabet::nuc::Nuc;
+use crate::make_error;
+use eyre::Report;
+use itertools::Itertools;
+use std::cmp::max;
+use std::collections::HashMap;
+
+/// Length of k-mers from which minimizers are computed
+const MINIMIZER_K: usize = 19;
+
+/// Number of consecutive k-mers out of which one minimizer is selected
+const MINIMIZER_W: usize = 10;
+
+/// Minimizers occurring in the reference more often than this are not used as anchors. These are mostly repeats, which
+/// produce many spurious anchors.
+const MAX_OCCURRENCES: usize = 16;
+
+/// Number of preceding anchors (in query order) which are considered as predecessors of an anchor during chaining.
+/// This keeps the chaining time linear in the number of anchors.
+const MAX_CHAIN_LOOKBACK: usize = 50;
+
+/// Maximum distance between successive anchors of a chain, in query as well as in reference
+const MAX_CHAIN_GAP: usize = 5000;
+
+/// Index of positions of minimizers in the reference sequence.
+///
+/// Used for seed matching of long genomes, where the number of k-mer matches found by `CodonSpacedIndex` becomes
+/// impractical to extend and to chain.
+pub struct MinimizerSeedIndex {
+  positions: HashMap<u64, Vec<usize>>,
+}
+
+impl MinimizerSeedIndex {
+  pub fn from_sequence(ref_seq: &[Nuc]) -> Self {
+    let mut positions = HashMap::<u64, Vec<usize>>::new();
+    for (hash, pos) in find_minimizers(ref_seq) {
+      positions.entry(hash).or_default().push(pos);
+    }
+    positions.retain(|_, positions| positions.len() <= MAX_OCCURRENCES);
+    Self { positions }
+  }
+
+  /// Finds exact matches of query minimizers in the reference, sorted by query position, then by reference position
+  fn find_anchors(&self, qry_seq: &[Nuc]) -> Vec<Anchor> {
+    let mut anchors = find_minimizers(qry_seq)
+      .into_iter()
+      .flat_map(|(hash, qry_pos)| {
+        self
+          .positions
+          .get(&hash)
+          .into_iter()
+          .flatten()
+          .map(move |&ref_pos| Anchor { qry_pos, ref_pos })
+      })
+      .collect_vec();
+    anchors.sort_unstable_by_key(|anchor| (anchor.qry_pos, anchor.ref_pos));
+    anchors
+  }
+}
+
+/// Finds seed matches of the query in the reference by chaining exactly matching minimizers.
+///
+/// Time and memory required are linear in the lengths of the sequences, which makes this method suitable for large
+/// genomes (100 kb to a few Mb). The resulting seed matches anchor the alignment band in the same way as the seed
+/// matches produced by `get_seed_matches2`, so that the banded alignment only needs memory proportional to the area of
+/// the band along the chain, rather than to the area of the full alignment matrix.
+pub fn get_minimizer_seed_matches(
+  qry_seq: &[Nuc],
+  minimizer_index: &MinimizerSeedIndex,
+  params: &AlignPairwiseParams,
+) -> Result<Vec<SeedMatch2>, Report> {
+  let anchors = minimizer_index.find_anchors(qry_seq);
+  if anchors.is_empty() {
+    return make_error!(
+      "Unable to align: seed alignment was unable to find any minimizer matches. \
+      This is likely due to low quality of the provided sequence, or due to using incorrect reference sequence."
+    );
+  }
+
+  let chain = chain_anchors(&anchors);
+  let seed_matches = anchors_to_seed_matches(&chain, params);
+  check_seed_cover(qry_seq, &seed_matches, params)?;
+  Ok(seed_matches)
+}
+
+/// Exact match of a k-mer of length `MINIMIZER_K` between query and reference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Anchor {
+  qry_pos: usize,
+  ref_pos: usize,
+}
+
+/// Finds the highest-scoring chain of anchors, which are increasing in both query and reference (similar to minimap2).
+///
+/// Each anchor can only be preceded by one of the `MAX_CHAIN_LOOKBACK` anchors before it. Expects anchors sorted by
+/// query position.
+fn chain_anchors(anchors: &[Anchor]) -> Vec<Anchor> {
+  let mut scores = Vec::<f64>::with_capacity(anchors.len());
+  let mut predecessors = Vec::<Option<usize>>::with_capacity(anchors.len());
+
+  for (i, anchor) in anchors.iter().enumerate() {
+    let mut best_score = MINIMIZER_K as f64;
+    let mut best_predecessor = None;
+
+    for j in (i.saturating_sub(MAX_CHAIN_LOOKBACK)..i).rev() {
+      let prev = &anchors[j];
+      if prev.qry_pos >= anchor.qry_pos || prev.ref_pos >= anchor.ref_pos {
+        continue;
+      }
+
+      let qry_gap = anchor.qry_pos - prev.qry_pos;
+      let ref_gap = anchor.ref_pos - prev.ref_pos;
+      if qry_gap > MAX_CHAIN_GAP || ref_gap > MAX_CHAIN_GAP {
+        continue;
+      }
+
+      let score = scores[j] + chain_extension_score(qry_gap, ref_gap);
+      if score > best_score {
+        best_score = score;
+        best_predecessor = Some(j);
+      }
+    }
+
+    scores.push(best_score);
+    predecessors.push(best_predecessor);
+  }
+
+  let Some((mut i, _)) = scores.iter().enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b)) else {
+    return vec![];
+  };
+
+  let mut chain = vec![anchors[i]];
+  while let Some(prev) = predecessors[i] {
+    chain.push(anchors[prev]);
+    i = prev;
+  }
+  chain.reverse();
+  chain
+}
+
+/// Score gained by appending an anchor to the chain: number of newly matched nucleotides, minus the cost of the shift
+/// in diagonal (i.e. of the indel) between the anchors
+fn chain_extension_score(qry_gap: usize, ref_gap: usize) -> f64 {
+  let matched = qry_gap.min(ref_gap).min(MINIMIZER_K) as f64;
+  let shift = qry_gap.abs_diff(ref_gap);
+  if shift == 0 {
+    return matched;
+  }
+  let shift = shift as f64;
+  matched - 0.01 * MINIMIZER_K as f64 * shift - 0.5 * shift.log2()
+}
+
+/// Converts chained anchors to seed matches. Successive anchors on the same diagonal, which are separated by no more
+/// than `window_size` nucleotides, are merged into one seed match. Overlaps between seed matches on different diagonals
+/// are trimmed, such that the seed matches are strictly increasing in both query and reference.
+fn anchors_to_seed_matches(chain: &[Anchor], params: &AlignPairwiseParams) -> Vec<SeedMatch2> {
+  let mut seed_matches = Vec::<SeedMatch2>::with_capacity(chain.len());
+  for anchor in chain {
+    let mut seed = SeedMatch2 {
+      qry_pos: anchor.qry_pos,
+      ref_pos: anchor.ref_pos,
+      length: MINIMIZER_K,
+      offset: anchor.qry_pos as isize - anchor.ref_pos as isize,
+    };
+
+    if let Some(last) = seed_matches.last_mut() {
+      let last_qry_end = last.qry_pos + last.length;
+      let last_ref_end = last.ref_pos + last.length;
+
+      if seed.offset == last.offset && seed.qry_pos <= last_qry_end + params.window_size {
+        last.length = seed.qry_pos + seed.length - last.qry_pos;
+        continue;
+      }
+
+      let overlap = max(
+        last_qry_end.saturating_sub(seed.qry_pos),
+        last_ref_end.saturating_sub(seed.ref_pos),
+      );
+      if overlap >= seed.length {
+        continue;
+      }
+      seed.qry_pos += overlap;
+      seed.ref_pos += overlap;
+      seed.length -= overlap;
+    }
+
+    seed_matches.push(seed);
+  }
+  seed_matches
+}
+
+/// Finds (w,k)-minimizers of the sequence: in every window of `MINIMIZER_W` consecutive k-mers, the k-mer with the
+/// smallest hash. K-mers containing nucleotides other than A, C, G, T are skipped. Returns pairs of hash and position
+/// of the k-mer, in order of position.
+fn find_minimizers(seq: &[Nuc]) -> Vec<(u64, usize)> {
+  let hashes = kmer_hashes(seq);
+  let window_size = MINIMIZER_W.clamp(1, hashes.len().max(1));
+
+  let mut minimizers = Vec::<(u64, usize)>::with_capacity(2 * hashes.len() / (MINIMIZER_W + 1) + 1);
+  for window in hashes.windows(window_size) {
+    let Some(minimizer) = window.iter().flatten().min().copied() else {
+      continue;
+    };
+    if minimizers.last() != Some(&minimizer) {
+      minimizers.push(minimizer);
+    }
+  }
+  minimizers
+}
+
+/// Computes hashes of all k-mers of the sequence, along with their positions. K-mers containing nucleotides other than
+/// A, C, G, T are `None`.
+fn kmer_hashes(seq: &[Nuc]) -> Vec<Option<(u64, usize)>> {
+  let mask = (1_u64 << (2 * MINIMIZER_K)) - 1;
+  let mut code = 0_u64;
+  let mut num_acgt = 0; // Number of consecutive A, C, G, T nucleotides ending at the current position
+
+  let mut hashes = Vec::with_capacity(seq.len().saturating_sub(MINIMIZER_K - 1));
+  for (pos, &nuc) in seq.iter().enumerate() {
+    if let Some(bits) = nuc_to_bits(nuc) {
+      code = ((code << 2) | bits) & mask;
+      num_acgt += 1;
+    } else {
+      num_acgt = 0;
+    }
+
+    if pos + 1 >= MINIMIZER_K {
+      let start = pos + 1 - MINIMIZER_K;
+      let hash = hash_kmer(code, mask);
+      hashes.push((num_acgt >= MINIMIZER_K).then_some((hash, start)));
+    }
+  }
+  hashes
+}
+
+const fn nuc_to_bits(nuc: Nuc) -> Option<u64> {
+  match nuc {
+    Nuc::A => Some(0),
+    Nuc::C => Some(1),
+    Nuc::G => Some(2),
+    Nuc::T => Some(3),
+    _ => None,
+  }
+}
+
+/// Invertible integer hash of a 2-bit encoded k-mer (as in minimap2). Being a bijection on `mask`, it does not produce
+/// collisions between different k-mers.
+const fn hash_kmer(key: u64, mask: u64) -> u64 {
+  let mut key = (!key).wrapping_add(key << 21) & mask;
+  key ^= key >> 24;
+  key = key.wrapping_add(key << 3).wrapping_add(key << 8) & mask;
+  key ^= key >> 14;
+  key = key.wrapping_add(key << 2).wrapping_add(key << 4) & mask;
+  key ^= key >> 28;
+  key.wrapping_add(key << 31) & mask
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::random::random_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn chains_minimizers_across_indels() -> Result<(), Report> {
+    let mut state = 42;
+    let ref_seq = random_nuc_seq(&mut state, 200_000);
+
+    // Query is a part of the reference with a deletion of 30 nucleotides and an insertion of 12 nucleotides
+    let insertion = random_nuc_seq(&mut state, 12);
+    let qry_seq = [
+      &ref_seq[1_000..80_000],
+      &ref_seq[80_030..150_000],
+      &insertion,
+      &ref_seq[150_000..190_000],
+    ]
+    .concat();
+
+    let index = MinimizerSeedIndex::from_sequence(&ref_seq);
+    let seed_matches = get_minimizer_seed_matches(&qry_seq, &index, &AlignPairwiseParams::default())?;
+
+    let offsets = seed_matches.iter().map(|seed| seed.offset).dedup().collect_vec();
+    assert_eq!(offsets, vec![-1_000, -1_030, -1_018]);
+
+    let is_colinear = seed_matches
+      .iter()
+      .tuple_windows()
+      .all(|(a, b)| a.qry_pos + a.length <= b.qry_pos && a.ref_pos + a.length <= b.ref_pos);
+    assert!(is_colinear);
+
+    let covered: usize = seed_matches.iter().map(|seed| seed.length).sum();
+    assert!(covered as f64 > 0.99 * qry_seq.len() as f64);
+    Ok(())
+  }
+}
@@ -0,0 +1,261 @@
+use crate::align::alignment_diagnostics::AlignmentStats;
+use crate::align::backtrace::AlignmentOutput;
+use crate::align::params::AlignPairwiseParams;
+use crate::alphabet::letter::Letter;
+
+/// Marks diagonals which are not reached by a wavefront
+const NONE: isize = isize::MIN / 2;
+
+/// Wavefront of a given score (number of edits): for each diagonal `k = qry_pos - ref_pos` in `[-score; score]`, the
+/// furthest query position reachable with this number of edits
+struct Wavefront {
+  score: isize,
+  offsets: Vec<isize>,
+}
+
+impl Wavefront {
+  fn new(score: usize) -> Self {
+    Self {
+      score: score as isize,
+      offsets: vec![NONE; 2 * score + 1],
+    }
+  }
+
+  #[inline]
+  fn get(&self, k: isize) -> isize {
+    if k < -self.score || k > self.score {
+      return NONE;
+    }
+    self.offsets[(k + self.score) as usize]
+  }
+
+  #[inline]
+  fn set(&mut self, k: isize, offset: isize) {
+    self.offsets[(k + self.score) as usize] = offset;
+  }
+}
+
+/// Move which leads into a cell of a wavefront from the preceding wavefront
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Move {
+  Mismatch,
+  /// Query letter aligned to a gap in the reference
+  Insertion,
+  /// Reference letter aligned to a gap in the query
+  Deletion,
+}
+
+/// Aligns sequences with the wavefront alignment algorithm (WFA), using edit distance (unit cost of mismatches and of
+/// gaps of length 1). The time and memory required grow with the number of differences between the sequences rather
+/// than with their lengths, which makes it much faster than the banded alignment for closely related sequences.
+///
+/// Terminal gaps are not free and gap penalties are not codon-aware, so the result can differ from the result of the
+/// banded alignment. Returns `None` if the sequences differ by more than `max_edits`, in which case the caller is
+/// expected to use the banded alignment instead.
+pub fn align_wfa<T: Letter<T>>(
+  qry_seq: &[T],
+  ref_seq: &[T],
+  max_edits: usize,
+  params: &AlignPairwiseParams,
+) -> Option<AlignmentOutput<T>> {
+  let qry_len = qry_seq.len() as isize;
+  let ref_len = ref_seq.len() as isize;
+  let k_final = qry_len - ref_len;
+
+  let extend = |k: isize, mut h: isize| -> isize {
+    let mut v = h - k;
+    while h < qry_len && v < ref_len && letters_match(qry_seq[h as usize], ref_seq[v as usize]) {
+      h += 1;
+      v += 1;
+    }
+    h
+  };
+
+  let mut wavefronts = Vec::<Wavefront>::new();
+  let mut first = Wavefront::new(0);
+  first.set(0, extend(0, 0));
+  wavefronts.push(first);
+
+  loop {
+    let score = wavefronts.len() - 1;
+    if wavefronts[score].get(k_final) >= qry_len {
+      break;
+    }
+    if score >= max_edits {
+      return None;
+    }
+
+    let prev = &wavefronts[score];
+    let mut next = Wavefront::new(score + 1);
+    for k in -(score as isize + 1)..=(score as isize + 1) {
+      if let Some((_, h)) = best_move(prev, k, qry_len, ref_len) {
+        next.set(k, extend(k, h));
+      }
+    }
+    wavefronts.push(next);
+  }
+
+  let (qry_aln, ref_aln) = backtrace_wfa(&wavefronts, qry_seq, ref_seq, k_final);
+  let alignment_score = calculate_alignment_score(&qry_aln, &ref_aln, params);
+
+  Some(AlignmentOutput {
+    qry_seq: qry_aln,
+    ref_seq: ref_aln,
+    alignment_score,
+    is_reverse_complement: false,
+    hit_boundary: false,
+    band: vec![],
+    stats: AlignmentStats::default(),
+  })
+}
+
+#[inline]
+fn letters_match<T: Letter<T>>(qry: T, reff: T) -> bool {
+  qry.is_unknown() || reff.is_unknown() || T::lookup_match_score(qry, reff) > 0
+}
+
+/// Finds the move into diagonal `k` from the preceding wavefront which reaches the furthest, before extension along
+/// the diagonal. Mismatches are preferred over gaps when equally far.
+fn best_move(prev: &Wavefront, k: isize, qry_len: isize, ref_len: isize) -> Option<(Move, isize)> {
+  [
+    (Move::Mismatch, prev.get(k) + 1),
+    (Move::Insertion, prev.get(k - 1) + 1),
+    (Move::Deletion, prev.get(k + 1)),
+  ]
+  .into_iter()
+  .filter(|(_, h)| *h >= 0 && *h <= qry_len && h - k >= 0 && h - k <= ref_len)
+  .fold(None, |best, (mv, h)| match best {
+    Some((_, best_h)) if best_h >= h => best,
+    _ => Some((mv, h)),
+  })
+}
+
+fn backtrace_wfa<T: Letter<T>>(
+  wavefronts: &[Wavefront],
+  qry_seq: &[T],
+  ref_seq: &[T],
+  k_final: isize,
+) -> (Vec<T>, Vec<T>) {
+  let qry_len = qry_seq.len() as isize;
+  let ref_len = ref_seq.len() as isize;
+  let mut qry_aln = Vec::<T>::with_capacity(qry_seq.len() + ref_seq.len());
+  let mut ref_aln = Vec::<T>::with_capacity(qry_seq.len() + ref_seq.len());
+
+  let mut k = k_final;
+  let mut h = qry_len;
+  for score in (1..wavefronts.len()).rev() {
+    let (mv, h_begin) = best_move(&wavefronts[score - 1], k, qry_len, ref_len)
+      .expect("Wavefront cell is expected to be reachable from the preceding wavefront");
+
+    // Matches added by the extension along the diagonal
+    while h > h_begin {
+      h -= 1;
+      qry_aln.push(qry_seq[h as usize]);
+      ref_aln.push(ref_seq[(h - k) as usize]);
+    }
+
+    match mv {
+      Move::Mismatch => {
+        h -= 1;
+        qry_aln.push(qry_seq[h as usize]);
+        ref_aln.push(ref_seq[(h - k) as usize]);
+      }
+      Move::Insertion => {
+        h -= 1;
+        qry_aln.push(qry_seq[h as usize]);
+        ref_aln.push(T::GAP);
+        k -= 1;
+      }
+      Move::Deletion => {
+        qry_aln.push(T::GAP);
+        ref_aln.push(ref_seq[(h - k - 1) as usize]);
+        k += 1;
+      }
+    }
+  }
+
+  // Initial matches, on the main diagonal
+  while h > 0 {
+    h -= 1;
+    qry_aln.push(qry_seq[h as usize]);
+    ref_aln.push(ref_seq[h as usize]);
+  }
+
+  qry_aln.reverse();
+  ref_aln.reverse();
+  (qry_aln, ref_aln)
+}
+
+/// Scores the alignment using the same scoring scheme as the banded alignment (without codon-aware gap penalties),
+/// such that alignment scores are comparable
+fn calculate_alignment_score<T: Letter<T>>(qry_aln: &[T], ref_aln: &[T], params: &AlignPairwiseParams) -> i32 {
+  let mut score = 0;
+  let mut prev_gap: Option<bool> = None; // `Some(true)` for a gap in query, `Some(false)` for a gap in reference
+  for (&qry, &reff) in qry_aln.iter().zip(ref_aln) {
+    let gap = if qry.is_gap() {
+      Some(true)
+    } else if reff.is_gap() {
+      Some(false)
+    } else {
+      None
+    };
+
+    score += match gap {
+      None if letters_match(qry, reff) => params.score_match,
+      None => -params.penalty_mismatch,
+      Some(_) if gap == prev_gap => -params.penalty_gap_extend,
+      Some(_) => -params.penalty_gap_open,
+    };
+    prev_gap = gap;
+  }
+  score
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::identical("ACGCTCGCT", "ACGCTCGCT", "ACGCTCGCT", "ACGCTCGCT")]
+  #[case::mismatch("ACGCTCGCT", "ACGATCGCT", "ACGATCGCT", "ACGCTCGCT")]
+  #[case::deletion("ACGCTCGCTTA", "ACGCGCTTA", "ACGC--GCTTA", "ACGCTCGCTTA")]
+  #[case::insertion("ACGCGCTTA", "ACGCTTCGCTTA", "ACGCTTCGCTTA", "ACGC---GCTTA")]
+  fn aligns_with_wavefronts(
+    #[case] ref_seq: &str,
+    #[case] qry_seq: &str,
+    #[case] qry_aln: &str,
+    #[case] ref_aln: &str,
+  ) -> Result<(), Report> {
+    let result = align_wfa(
+      &to_nuc_seq(qry_seq)?,
+      &to_nuc_seq(ref_seq)?,
+      10,
+      &AlignPairwiseParams::default(),
+    )
+    .unwrap();
+
+    assert_eq!(from_nuc_seq(&result.qry_seq).replace('-', "").len(), qry_seq.len());
+    assert_eq!(from_nuc_seq(&result.ref_seq).replace('-', "").len(), ref_seq.len());
+    assert_eq!(
+      (from_nuc_seq(&result.qry_seq), from_nuc_seq(&result.ref_seq)),
+      (qry_aln.to_owned(), ref_aln.to_owned())
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  fn gives_up_when_too_divergent() -> Result<(), Report> {
+    let result = align_wfa(
+      &to_nuc_seq("ACGTACGTAC")?,
+      &to_nuc_seq("TGCATGCATG")?,
+      3,
+      &AlignPairwiseParams::default(),
+    );
+    assert!(result.is_none());
+    Ok(())
+  }
+}
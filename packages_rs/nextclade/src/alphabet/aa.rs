@@ -50,6 +50,17 @@ impl Aa {
   pub fn is_stop(self) -> bool {
     self == Aa::Stop
   }
+
+  /// Whether this aminoacid, possibly an IUPAC code of one of two aminoacids (`B`, `Z` or `J`), can represent the
+  /// given aminoacid
+  pub fn includes(self, other: Aa) -> bool {
+    match self {
+      Aa::B => matches!(other, Aa::B | Aa::D | Aa::N),
+      Aa::Z => matches!(other, Aa::Z | Aa::E | Aa::Q),
+      Aa::J => matches!(other, Aa::J | Aa::I | Aa::L),
+      _ => self == other,
+    }
+  }
 }
 
 impl ScoreMatrixLookup<Aa> for Aa {
@@ -142,6 +142,27 @@ pub const fn from_nuc(nuc: Nuc) -> char {
   }
 }
 
+/// Decomposes a nucleotide into the canonical bases it represents, according to IUPAC table
+pub const fn nuc_to_bases(nuc: Nuc) -> &'static [Nuc] {
+  match nuc {
+    Nuc::A => &[Nuc::A],
+    Nuc::C => &[Nuc::C],
+    Nuc::G => &[Nuc::G],
+    Nuc::T => &[Nuc::T],
+    Nuc::R => &[Nuc::A, Nuc::G],
+    Nuc::Y => &[Nuc::C, Nuc::T],
+    Nuc::S => &[Nuc::C, Nuc::G],
+    Nuc::W => &[Nuc::A, Nuc::T],
+    Nuc::K => &[Nuc::G, Nuc::T],
+    Nuc::M => &[Nuc::A, Nuc::C],
+    Nuc::B => &[Nuc::C, Nuc::G, Nuc::T],
+    Nuc::D => &[Nuc::A, Nuc::G, Nuc::T],
+    Nuc::H => &[Nuc::A, Nuc::C, Nuc::T],
+    Nuc::V => &[Nuc::A, Nuc::C, Nuc::G],
+    Nuc::N | Nuc::Gap => &[],
+  }
+}
+
 pub fn to_nuc_seq(str: &str) -> Result<Vec<Nuc>, Report> {
   str.chars().map(to_nuc).collect()
 }
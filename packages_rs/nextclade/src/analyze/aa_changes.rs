@@ -14,17 +14,20 @@ use crate::gene::gene::GeneStrand;
 use crate::gene::gene_map::GeneMap;
 use crate::translate::complement::reverse_complement_in_place;
 use crate::translate::translate_genes::{CdsTranslation, Translation};
+use crate::translate::translation_cache::CdsTranslationCache;
 use crate::utils::collections::extend_map_of_vecs;
+use crate::utils::interned_str::InternedStr;
 use either::Either;
 use eyre::Report;
 use itertools::{Itertools, MinMaxResult};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AaChangeWithContext {
-  pub cds_name: String,
+  pub cds_name: InternedStr,
   pub pos: AaRefPosition,
   pub ref_aa: Aa,
   pub qry_aa: Aa,
@@ -80,7 +83,7 @@ impl AaChangeWithContext {
     let nuc_ranges = nuc_ranges.into_iter().map(|(range, _)| range).collect_vec();
 
     Self {
-      cds_name: cds.name.clone(),
+      cds_name: cds.name_interned(),
       pos,
       ref_aa,
       qry_aa,
@@ -161,15 +164,28 @@ pub fn find_aa_changes(
   gene_map: &GeneMap,
   nuc_subs: &[NucSub],
   nuc_dels: &[NucDelRange],
+  cache: Option<&CdsTranslationCache>,
 ) -> Result<FindAaChangesOutput, Report> {
   let mut changes = qry_translation
     .iter_cdses()
     .map(|(qry_name, qry_cds_tr)| {
       let ref_cds_tr = ref_translation.get_cds(qry_name)?;
       let cds = gene_map.get_cds(&qry_cds_tr.name)?;
-      Ok(find_aa_changes_for_cds(
-        cds, qry_seq, ref_seq, ref_cds_tr, qry_cds_tr, nuc_subs, nuc_dels,
-      ))
+
+      // Aminoacid changes only depend on the translation, but the nucleotide changes attached to them depend on the
+      // entire query, so only the former are cached
+      let mut changes = if let Some(changes) = cache.and_then(|cache| cache.get_aa_changes(qry_cds_tr)) {
+        FindAaChangesOutput::clone(&changes)
+      } else {
+        let changes = find_aa_changes_for_cds(cds, qry_seq, ref_seq, ref_cds_tr, qry_cds_tr);
+        if let Some(cache) = cache {
+          cache.insert_aa_changes(qry_cds_tr, Arc::new(changes.clone()));
+        }
+        changes
+      };
+      attach_nuc_changes(cds, &mut changes.aa_changes_groups, nuc_subs, nuc_dels);
+
+      Ok(changes)
     })
     .collect::<Result<Vec<FindAaChangesOutput>, Report>>()?
     .into_iter()
@@ -212,8 +228,6 @@ fn find_aa_changes_for_cds(
   ref_seq: &[Nuc],
   ref_tr: &CdsTranslation,
   qry_tr: &CdsTranslation,
-  nuc_subs: &[NucSub],
-  nuc_dels: &[NucDelRange],
 ) -> FindAaChangesOutput {
   assert_eq!(ref_tr.seq.len(), qry_tr.seq.len());
   assert_eq!(qry_seq.len(), ref_seq.len());
@@ -326,30 +340,6 @@ fn find_aa_changes_for_cds(
   // Keep only non-empty groups
   aa_changes_groups.retain(|group| !group.range.is_empty() && !group.changes.is_empty());
 
-  aa_changes_groups.iter_mut().for_each(|group| {
-    let ranges = group
-      .range
-      .iter()
-      .flat_map(|codon| {
-        cds_codon_pos_to_ref_range(cds, codon)
-          .into_iter()
-          .map(|(range, _)| range)
-      })
-      .collect_vec();
-
-    group.nuc_subs = nuc_subs
-      .iter()
-      .filter(|nuc_sub| ranges.iter().any(|range| range.contains(nuc_sub.pos)))
-      .cloned()
-      .collect_vec();
-
-    group.nuc_dels = nuc_dels
-      .iter()
-      .filter(|nuc_del| ranges.iter().any(|range| have_intersection(range, nuc_del.range())))
-      .cloned()
-      .collect_vec();
-  });
-
   let (aa_substitutions, aa_deletions): (Vec<AaSub>, Vec<AaDel>) = aa_changes_groups
     .iter()
     .flat_map(|aa_changes_group| &aa_changes_group.changes)
@@ -357,13 +347,13 @@ fn find_aa_changes_for_cds(
     .partition_map(|change| {
       if change.qry_aa.is_gap() {
         Either::Right(AaDel {
-          cds_name: cds.name.clone(),
+          cds_name: cds.name_interned(),
           ref_aa: change.ref_aa,
           pos: change.pos,
         })
       } else {
         Either::Left(AaSub {
-          cds_name: cds.name.clone(),
+          cds_name: cds.name_interned(),
           ref_aa: change.ref_aa,
           pos: change.pos,
           qry_aa: change.qry_aa,
@@ -405,11 +395,43 @@ fn find_aa_changes_for_cds(
   }
 }
 
+/// Attaches nucleotide substitutions and deletions to the groups of aminoacid changes they overlap with
+fn attach_nuc_changes(
+  cds: &Cds,
+  aa_changes_groups: &mut [AaChangesGroup],
+  nuc_subs: &[NucSub],
+  nuc_dels: &[NucDelRange],
+) {
+  aa_changes_groups.iter_mut().for_each(|group| {
+    let ranges = group
+      .range
+      .iter()
+      .flat_map(|codon| {
+        cds_codon_pos_to_ref_range(cds, codon)
+          .into_iter()
+          .map(|(range, _)| range)
+      })
+      .collect_vec();
+
+    group.nuc_subs = nuc_subs
+      .iter()
+      .filter(|nuc_sub| ranges.iter().any(|range| range.contains(nuc_sub.pos)))
+      .cloned()
+      .collect_vec();
+
+    group.nuc_dels = nuc_dels
+      .iter()
+      .filter(|nuc_del| ranges.iter().any(|range| have_intersection(range, nuc_del.range())))
+      .cloned()
+      .collect_vec();
+  });
+}
+
 /// Check whether a given pair if reference and query aminoacids constitute a mutation or deletion
 #[inline]
-fn is_aa_mutated_or_deleted(ref_aa: Aa, qry_aa: Aa) -> bool {
-  // NOTE: We chose to ignore mutations to `X`.
-  qry_aa != ref_aa && qry_aa != Aa::X
+pub fn is_aa_mutated_or_deleted(ref_aa: Aa, qry_aa: Aa) -> bool {
+  // NOTE: We chose to ignore mutations to `X`, as well as partially resolved aminoacids agreeing with the reference.
+  !qry_aa.includes(ref_aa) && qry_aa != Aa::X
 }
 
 /// Check whether a given codon position corresponds to a sequenced aminoacid
@@ -2,13 +2,14 @@ use crate::alphabet::aa::{from_aa, Aa};
 use crate::analyze::aa_sub::AaSub;
 use crate::analyze::abstract_mutation::{AbstractMutation, MutParams, Pos, QryLetter, RefLetter};
 use crate::coord::position::AaRefPosition;
+use crate::utils::interned_str::InternedStr;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AaDel {
-  pub cds_name: String,
+  pub cds_name: InternedStr,
   pub pos: AaRefPosition,
   pub ref_aa: Aa,
 }
@@ -6,6 +6,7 @@ use crate::analyze::abstract_mutation::{AbstractMutation, MutParams, Pos, QryLet
 use crate::coord::position::AaRefPosition;
 use crate::io::parse_pos::parse_pos;
 use crate::make_error;
+use crate::utils::interned_str::InternedStr;
 use eyre::{Report, WrapErr};
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -17,7 +18,7 @@ use std::str::FromStr;
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AaSub {
-  pub cds_name: String,
+  pub cds_name: InternedStr,
   pub pos: AaRefPosition,
   pub ref_aa: Aa,
   pub qry_aa: Aa,
@@ -113,7 +114,7 @@ impl FromStr for AaSub {
         captures.name("qry"),
       ) {
         (Some(cds_name), Some(reff), Some(pos), Some(qry)) => {
-          let cds_name = cds_name.as_str().to_owned();
+          let cds_name = InternedStr::new(cds_name.as_str());
           let ref_aa = Aa::from_string(reff.as_str())?;
           let pos = parse_pos(pos.as_str())?.into();
           let qry_aa = Aa::from_string(qry.as_str())?;
@@ -0,0 +1,47 @@
+use crate::alphabet::aa::Aa;
+use crate::gene::gene_map::GeneMap;
+use crate::translate::translate_genes::Translation;
+use std::collections::BTreeMap;
+
+/// Calculates, for every CDS in the genome annotation, the fraction of codons which are determined in the query,
+/// i.e. which are neither unknown (`X`) nor deleted or unsequenced (`-`). CDSes which could not be translated have
+/// zero coverage.
+pub fn calculate_cds_coverage(translation: &Translation, gene_map: &GeneMap) -> BTreeMap<String, f64> {
+  gene_map
+    .iter_cdses()
+    .map(|cds| {
+      let coverage = translation
+        .get_cds(&cds.name)
+        .map(|cds_tr| peptide_coverage(&cds_tr.seq))
+        .unwrap_or_default();
+      (cds.name.clone(), coverage)
+    })
+    .collect()
+}
+
+fn peptide_coverage(peptide: &[Aa]) -> f64 {
+  if peptide.is_empty() {
+    return 0.0;
+  }
+  let num_determined = peptide.iter().filter(|aa| !matches!(aa, Aa::X | Aa::Gap)).count();
+  num_determined as f64 / peptide.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::to_aa_seq;
+  use eyre::Report;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::full("MKLV*", 1.0)]
+  #[case::unknown("MXXV", 0.5)]
+  #[case::unsequenced("--MKLVXX", 0.5)]
+  #[case::empty("", 0.0)]
+  fn calculates_peptide_coverage(#[case] peptide: &str, #[case] expected: f64) -> Result<(), Report> {
+    let actual = peptide_coverage(&to_aa_seq(peptide)?);
+    assert!((actual - expected).abs() < f64::EPSILON);
+    Ok(())
+  }
+}
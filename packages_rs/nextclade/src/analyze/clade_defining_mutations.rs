@@ -0,0 +1,192 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::graph::node::GraphNodeKey;
+use crate::tree::tree::AuspiceGraph;
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+
+/// Evidence for the assigned clade in the query sequence: which of the mutations defining the clade are present in the
+/// query, which of them cannot be checked, and which of them are contradicted by the query
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CladeDefiningMutations {
+  /// Clade-defining mutations present in the query
+  pub observed: Vec<NucSub>,
+  /// Clade-defining mutations at positions which are not sequenced or ambiguous in the query
+  pub missing: Vec<NucSub>,
+  /// Clade-defining mutations at positions where the query has another nucleotide or a deletion
+  pub contradicted: Vec<NucSub>,
+  pub total_observed: usize,
+  pub total_missing: usize,
+  pub total_contradicted: usize,
+}
+
+/// Finds the founder node of the clade of the given node, i.e. the node closest to the root which belongs to the same
+/// clade as the given node
+pub fn find_clade_founder(graph: &AuspiceGraph, node_key: GraphNodeKey) -> Result<GraphNodeKey, Report> {
+  let clade = graph.get_node(node_key)?.payload().clade();
+
+  let mut founder_key = node_key;
+  while let Some(parent) = graph.parent_of_by_key(founder_key) {
+    if parent.payload().clade() != clade {
+      break;
+    }
+    founder_key = parent.key();
+  }
+
+  Ok(founder_key)
+}
+
+/// Checks which of the mutations defining the clade of the nearest node are found in the (aligned, with insertions
+/// stripped) query sequence.
+///
+/// The clade-defining mutations are the substitutions on the branch leading to the founder node of the clade, i.e. to
+/// the node closest to the root which belongs to the same clade as the nearest node.
+pub fn find_clade_defining_mutations(
+  graph: &AuspiceGraph,
+  nearest_node_key: GraphNodeKey,
+  qry_seq: &[Nuc],
+  alignment_range: &NucRefGlobalRange,
+  ref_seq: &[Nuc],
+) -> Result<CladeDefiningMutations, Report> {
+  let founder_key = find_clade_founder(graph, nearest_node_key)?;
+  let founder = graph.get_node(founder_key)?.payload();
+  let parent = graph.parent_of_by_key(founder_key).map(|parent| &parent.payload().tmp);
+
+  let mut result = CladeDefiningMutations::default();
+  for (&pos, &nuc) in &founder.tmp.substitutions {
+    let parent_nuc = parent.map_or_else(|| ref_seq[pos.as_usize()], |parent| parent.nuc_at(pos, ref_seq));
+
+    // Inherited from an ancestral clade, rather than acquired on the branch leading to the founder
+    if parent_nuc == nuc {
+      continue;
+    }
+
+    let sub = NucSub {
+      pos,
+      ref_nuc: parent_nuc,
+      qry_nuc: nuc,
+    };
+
+    let qry_nuc = qry_seq[pos.as_usize()];
+    if !alignment_range.contains(pos) || !(qry_nuc.is_acgt() || qry_nuc.is_gap()) {
+      result.missing.push(sub);
+    } else if qry_nuc == nuc {
+      result.observed.push(sub);
+    } else {
+      result.contradicted.push(sub);
+    }
+  }
+
+  result.total_observed = result.observed.len();
+  result.total_missing = result.missing.len();
+  result.total_contradicted = result.contradicted.len();
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::graph::node::Node;
+  use crate::tree::tree::TreeNodeAttr;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn sub(pos: usize, ref_nuc: Nuc, qry_nuc: Nuc) -> NucSub {
+    NucSub {
+      pos: pos.into(),
+      ref_nuc,
+      qry_nuc,
+    }
+  }
+
+  fn find_node_key(graph: &AuspiceGraph, name: &str) -> GraphNodeKey {
+    graph
+      .iter_nodes()
+      .find(|node| node.payload().name == name)
+      .map_or_else(|| panic!("Expected node '{name}' in the tree"), Node::key)
+  }
+
+  /// Builds a tree where node 'X' founds clade "B" with substitutions 1:C and 3:T, and where the root belongs to
+  /// clade "A" and has substitution 3:T itself
+  fn create_graph() -> Result<AuspiceGraph, Report> {
+    let tree = auspice_tree_from_nwk_str("((A:1,B:1)X:1,C:1)R;", None)?;
+    let mut graph = convert_auspice_tree_to_graph(tree)?;
+    for node in graph.iter_nodes_mut() {
+      let payload = node.payload_mut();
+      let (clade, substitutions) = match payload.name.as_str() {
+        "R" | "C" => ("A", vec![(3, Nuc::T)]),
+        _ => ("B", vec![(1, Nuc::C), (3, Nuc::T), (5, Nuc::G)]),
+      };
+      payload.node_attrs.clade_membership = TreeNodeAttr::new(clade);
+      payload.tmp.substitutions = substitutions.iter().map(|&(pos, nuc)| (pos.into(), nuc)).collect();
+      payload.tmp.mutations = payload.tmp.substitutions.clone();
+    }
+    Ok(graph)
+  }
+
+  #[rstest]
+  fn finds_founder_of_clade() -> Result<(), Report> {
+    let graph = create_graph()?;
+    let founder_key = find_clade_founder(&graph, find_node_key(&graph, "A"))?;
+    assert_eq!(founder_key, find_node_key(&graph, "X"));
+    Ok(())
+  }
+
+  #[rstest]
+  fn classifies_observed_missing_and_contradicted() -> Result<(), Report> {
+    let graph = create_graph()?;
+    let ref_seq = to_nuc_seq("AAAAAAAA")?;
+    let qry_seq = to_nuc_seq("ACANATAA")?;
+    let alignment_range = NucRefGlobalRange::from_usize(0, 8);
+
+    let actual =
+      find_clade_defining_mutations(&graph, find_node_key(&graph, "A"), &qry_seq, &alignment_range, &ref_seq)?;
+
+    assert_eq!(actual.observed, vec![sub(1, Nuc::A, Nuc::C)]);
+    assert_eq!(actual.missing, vec![]);
+    assert_eq!(actual.contradicted, vec![sub(5, Nuc::A, Nuc::G)]);
+    Ok(())
+  }
+
+  #[rstest]
+  fn reports_unsequenced_and_ambiguous_positions_as_missing() -> Result<(), Report> {
+    let graph = create_graph()?;
+    let ref_seq = to_nuc_seq("AAAAAAAA")?;
+    let qry_seq = to_nuc_seq("ANAAAGAA")?;
+    let alignment_range = NucRefGlobalRange::from_usize(0, 4);
+
+    let actual =
+      find_clade_defining_mutations(&graph, find_node_key(&graph, "B"), &qry_seq, &alignment_range, &ref_seq)?;
+
+    assert_eq!(actual.observed, vec![]);
+    assert_eq!(actual.missing, vec![sub(1, Nuc::A, Nuc::C), sub(5, Nuc::A, Nuc::G)]);
+    assert_eq!(actual.contradicted, vec![]);
+    assert_eq!(actual.total_missing, 2);
+    Ok(())
+  }
+
+  #[rstest]
+  fn compares_against_reference_when_founder_is_root() -> Result<(), Report> {
+    let graph = create_graph()?;
+    let ref_seq = to_nuc_seq("AAAAAAAA")?;
+    let qry_seq = to_nuc_seq("AAA-AAAA")?;
+    let alignment_range = NucRefGlobalRange::from_usize(0, 8);
+
+    let node_key = find_node_key(&graph, "C");
+    assert_eq!(find_clade_founder(&graph, node_key)?, find_node_key(&graph, "R"));
+
+    let actual = find_clade_defining_mutations(&graph, node_key, &qry_seq, &alignment_range, &ref_seq)?;
+
+    assert_eq!(actual.observed, vec![]);
+    assert_eq!(actual.missing, vec![]);
+    assert_eq!(actual.contradicted, vec![sub(3, Nuc::A, Nuc::T)]);
+    Ok(())
+  }
+}
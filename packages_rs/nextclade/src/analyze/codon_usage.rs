@@ -0,0 +1,227 @@
+use crate::alphabet::aa::Aa;
+use crate::alphabet::nuc::{from_nuc_seq, Nuc};
+use crate::gene::cds::Cds;
+use crate::gene::gene_map::GeneMap;
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::fs::has_extension;
+use crate::io::json::{json_write, JsonPretty};
+use crate::translate::extract::extract_cds_from_ref;
+use crate::translate::genetic_code::{GeneticCode, STANDARD_TRANSL_TABLE};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Usage of one codon in one CDS, in the reference and summed over all query sequences.
+///
+/// Fractions are relative to all the codons encoding the same aminoacid (synonymous codons) in the same CDS.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CodonUsageEntry {
+  pub cds: String,
+  pub codon: String,
+  pub aa: Aa,
+  pub ref_count: usize,
+  pub ref_fraction: f64,
+  pub qry_count: usize,
+  pub qry_fraction: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CdsCodonCounts {
+  ref_counts: BTreeMap<[Nuc; 3], usize>,
+  qry_counts: BTreeMap<[Nuc; 3], usize>,
+}
+
+/// Accumulates codon usage of each CDS, one sequence at a time.
+///
+/// Only codons consisting of unambiguous nucleotides (A, C, G, T) are counted. Codons containing gaps and ambiguous
+/// nucleotides, as well as the trailing incomplete codon, are skipped. Query codons are read in the reading frame of
+/// the reference, from the aligned query sequence (with insertions stripped).
+#[derive(Clone, Debug)]
+pub struct CodonUsageCounter {
+  cdses: Vec<Cds>,
+  counts: BTreeMap<String, CdsCodonCounts>,
+}
+
+impl CodonUsageCounter {
+  pub fn new(ref_seq: &[Nuc], gene_map: &GeneMap) -> Self {
+    let cdses = gene_map.iter_cdses().cloned().collect_vec();
+
+    let counts = cdses
+      .iter()
+      .map(|cds| {
+        let mut cds_counts = CdsCodonCounts::default();
+        count_codons(&extract_cds_from_ref(ref_seq, cds), &mut cds_counts.ref_counts);
+        (cds.name.clone(), cds_counts)
+      })
+      .collect();
+
+    Self { cdses, counts }
+  }
+
+  /// Adds codons of an aligned query sequence
+  pub fn add(&mut self, qry_seq_aligned: &[Nuc]) {
+    for cds in &self.cdses {
+      if let Some(cds_counts) = self.counts.get_mut(&cds.name) {
+        count_codons(&extract_cds_from_ref(qry_seq_aligned, cds), &mut cds_counts.qry_counts);
+      }
+    }
+  }
+
+  /// Produces the table of codon usage, ordered by CDS (in order of genome annotation), aminoacid and codon
+  pub fn finish(self) -> Result<Vec<CodonUsageEntry>, Report> {
+    let Self { cdses, counts } = self;
+
+    cdses
+      .iter()
+      .filter_map(|cds| counts.get(&cds.name).map(|cds_counts| (cds, cds_counts)))
+      .map(|(cds, CdsCodonCounts { ref_counts, qry_counts })| {
+        let genetic_code = GeneticCode::from_transl_table(cds.transl_table.unwrap_or(STANDARD_TRANSL_TABLE))
+          .wrap_err_with(|| format!("When counting codon usage in CDS '{}'", cds.name))?;
+        let decode = |codon: &[Nuc; 3]| genetic_code.decode(codon);
+
+        let codons = ref_counts
+          .keys()
+          .chain(qry_counts.keys())
+          .unique()
+          .copied()
+          .collect_vec();
+
+        let totals_per_aa = |counts: &BTreeMap<[Nuc; 3], usize>| {
+          let mut totals = BTreeMap::<Aa, usize>::new();
+          for (codon, count) in counts {
+            *totals.entry(decode(codon)).or_default() += count;
+          }
+          totals
+        };
+        let ref_totals = totals_per_aa(ref_counts);
+        let qry_totals = totals_per_aa(qry_counts);
+
+        let entries = codons
+          .into_iter()
+          .map(|codon| {
+            let aa = decode(&codon);
+            let ref_count = ref_counts.get(&codon).copied().unwrap_or_default();
+            let qry_count = qry_counts.get(&codon).copied().unwrap_or_default();
+            CodonUsageEntry {
+              cds: cds.name.clone(),
+              codon: from_nuc_seq(&codon),
+              aa,
+              ref_count,
+              ref_fraction: fraction(ref_count, ref_totals.get(&aa).copied().unwrap_or_default()),
+              qry_count,
+              qry_fraction: fraction(qry_count, qry_totals.get(&aa).copied().unwrap_or_default()),
+            }
+          })
+          .sorted_by(|a, b| a.aa.cmp(&b.aa).then_with(|| a.codon.cmp(&b.codon)))
+          .collect_vec();
+
+        Ok(entries)
+      })
+      .flatten_ok()
+      .collect()
+  }
+}
+
+fn count_codons(cds_seq: &[Nuc], counts: &mut BTreeMap<[Nuc; 3], usize>) {
+  for codon in cds_seq.chunks_exact(3) {
+    if codon.iter().all(|nuc| nuc.is_acgt()) {
+      *counts.entry([codon[0], codon[1], codon[2]]).or_default() += 1;
+    }
+  }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn fraction(count: usize, total: usize) -> f64 {
+  if total == 0 {
+    0.0
+  } else {
+    count as f64 / total as f64
+  }
+}
+
+/// Writes codon usage table. The format is deduced from file extension: JSON for ".json", semicolon-separated table
+/// for ".csv", and tab-separated table otherwise.
+pub fn codon_usage_write(filepath: impl AsRef<Path>, entries: &[CodonUsageEntry]) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+
+  if has_extension(filepath, "json") {
+    return json_write(filepath, &entries, JsonPretty(true));
+  }
+
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+  let headers = [
+    "cds",
+    "codon",
+    "aa",
+    "refCount",
+    "refFraction",
+    "qryCount",
+    "qryFraction",
+  ]
+  .iter()
+  .map(|&header| header.to_owned())
+  .collect_vec();
+
+  let mut writer = CsvVecFileWriter::new(filepath, delimiter, &headers)?;
+  for entry in entries {
+    writer
+      .write([
+        entry.cds.clone(),
+        entry.codon.clone(),
+        entry.aa.to_string(),
+        entry.ref_count.to_string(),
+        format!("{:.4}", entry.ref_fraction),
+        entry.qry_count.to_string(),
+        format!("{:.4}", entry.qry_fraction),
+      ])
+      .wrap_err_with(|| format!("When writing codon usage table: {filepath:#?}"))?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::o;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  const GFF: &str = "##gff-version 3
+##sequence-region seq 1 12
+seq\t.\tgene\t1\t12\t.\t+\t.\tName=g1;ID=gene-g1
+seq\t.\tCDS\t1\t12\t.\t+\t.\tName=g1;Parent=gene-g1;ID=cds-g1
+";
+
+  #[rstest]
+  fn counts_codon_usage() -> Result<(), Report> {
+    let gene_map = GeneMap::from_str(GFF)?;
+    let ref_seq = to_nuc_seq("ATGCTTCTGTAA")?;
+
+    let mut counter = CodonUsageCounter::new(&ref_seq, &gene_map);
+    counter.add(&to_nuc_seq("ATGCTTCTTTAA")?);
+    counter.add(&to_nuc_seq("ATGCTT---TAG")?);
+
+    let usage = counter
+      .finish()?
+      .into_iter()
+      .map(|entry| (entry.codon, entry.ref_count, entry.qry_count))
+      .collect_vec();
+
+    assert_eq!(
+      usage,
+      vec![
+        (o!("CTG"), 1, 0),
+        (o!("CTT"), 1, 3),
+        (o!("ATG"), 1, 2),
+        (o!("TAA"), 1, 1),
+        (o!("TAG"), 0, 1),
+      ]
+    );
+    Ok(())
+  }
+}
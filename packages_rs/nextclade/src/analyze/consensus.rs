@@ -0,0 +1,279 @@
+use crate::alphabet::nuc::{from_nuc, nuc_to_bases, Nuc};
+use crate::io::csv::CsvStructFileWriter;
+use crate::io::fs::has_extension;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Finds IUPAC code which represents a given set of canonical bases (given as flags in A, C, G, T order)
+pub const fn bases_to_nuc(bases: [bool; 4]) -> Nuc {
+  match bases {
+    [true, false, false, false] => Nuc::A,
+    [false, true, false, false] => Nuc::C,
+    [false, false, true, false] => Nuc::G,
+    [false, false, false, true] => Nuc::T,
+    [true, false, true, false] => Nuc::R,
+    [false, true, false, true] => Nuc::Y,
+    [false, true, true, false] => Nuc::S,
+    [true, false, false, true] => Nuc::W,
+    [false, false, true, true] => Nuc::K,
+    [true, true, false, false] => Nuc::M,
+    [false, true, true, true] => Nuc::B,
+    [true, false, true, true] => Nuc::D,
+    [true, true, false, true] => Nuc::H,
+    [true, true, true, false] => Nuc::V,
+    _ => Nuc::N,
+  }
+}
+
+const fn base_index(nuc: Nuc) -> usize {
+  match nuc {
+    Nuc::A => 0,
+    Nuc::C => 1,
+    Nuc::G => 2,
+    _ => 3,
+  }
+}
+
+/// Nucleotide counts at one position of the alignment. Ambiguous nucleotides contribute equal fractions to each of
+/// the bases they represent. `N`s are counted as missing and do not contribute to the frequencies.
+#[derive(Clone, Debug, Default)]
+struct SiteCounts {
+  bases: [f64; 4],
+  gaps: f64,
+  missing: usize,
+}
+
+impl SiteCounts {
+  fn add(&mut self, nuc: Nuc) {
+    if nuc == Nuc::Gap {
+      self.gaps += 1.0;
+      return;
+    }
+
+    let bases = nuc_to_bases(nuc);
+    if bases.is_empty() {
+      self.missing += 1;
+      return;
+    }
+
+    let weight = 1.0 / bases.len() as f64;
+    for &base in bases {
+      self.bases[base_index(base)] += weight;
+    }
+  }
+
+  fn depth(&self) -> f64 {
+    self.bases.iter().sum::<f64>() + self.gaps
+  }
+
+  /// Frequencies of A, C, G, T and gap, in this order
+  fn frequencies(&self) -> [f64; 5] {
+    let depth = self.depth();
+    if depth <= 0.0 {
+      return [0.0; 5];
+    }
+    let [a, c, g, t] = self.bases;
+    [a / depth, c / depth, g / depth, t / depth, self.gaps / depth]
+  }
+
+  fn is_polymorphic(&self) -> bool {
+    self.frequencies().iter().filter(|&&freq| freq > 0.0).count() > 1
+  }
+
+  /// Consensus nucleotide at this site.
+  ///
+  /// Gap wins if it is the most frequent character. Otherwise, all bases with frequency at or above the threshold are
+  /// merged into an IUPAC ambiguity code. If none of the bases reaches the threshold, the most frequent base is taken.
+  fn consensus(&self, min_frequency: f64) -> Nuc {
+    if self.depth() <= 0.0 {
+      return Nuc::N;
+    }
+
+    let freqs = self.frequencies();
+    let max_base_freq = freqs[..4].iter().copied().fold(0.0, f64::max);
+    if freqs[4] > max_base_freq {
+      return Nuc::Gap;
+    }
+
+    let mut included = [false; 4];
+    for (i, &freq) in freqs[..4].iter().enumerate() {
+      included[i] = freq > 0.0 && freq >= min_frequency;
+    }
+
+    if !included.contains(&true) {
+      let most_frequent = (0..4)
+        .max_by(|&i, &j| freqs[i].total_cmp(&freqs[j]))
+        .unwrap_or_default();
+      included[most_frequent] = true;
+    }
+
+    bases_to_nuc(included)
+  }
+}
+
+/// Accumulates aligned sequences of a group and computes their consensus
+#[derive(Clone, Debug, Default)]
+pub struct ConsensusBuilder {
+  sites: Vec<SiteCounts>,
+  num_seqs: usize,
+}
+
+impl ConsensusBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub const fn num_seqs(&self) -> usize {
+    self.num_seqs
+  }
+
+  /// Adds an aligned sequence. All sequences of a group are expected to be aligned to the same reference.
+  pub fn add(&mut self, aligned_seq: &[Nuc]) -> Result<(), Report> {
+    if self.num_seqs == 0 {
+      self.sites = vec![SiteCounts::default(); aligned_seq.len()];
+    } else if aligned_seq.len() != self.sites.len() {
+      return make_error!(
+        "Aligned sequences are expected to be of the same length, but found lengths {} and {}. Make sure the sequences are aligned to the same reference, with insertions stripped.",
+        self.sites.len(),
+        aligned_seq.len()
+      );
+    }
+
+    for (site, &nuc) in self.sites.iter_mut().zip(aligned_seq) {
+      site.add(nuc);
+    }
+    self.num_seqs += 1;
+
+    Ok(())
+  }
+
+  pub fn consensus(&self, min_frequency: f64) -> Vec<Nuc> {
+    self.sites.iter().map(|site| site.consensus(min_frequency)).collect()
+  }
+
+  /// Lists sites where more than one character (a base or a gap) is observed
+  pub fn variability(&self, group: &str, min_frequency: f64) -> Vec<ConsensusVariableSite> {
+    self
+      .sites
+      .iter()
+      .enumerate()
+      .filter(|(_, site)| site.is_polymorphic())
+      .map(|(pos, site)| {
+        let [freq_a, freq_c, freq_g, freq_t, freq_gap] = site.frequencies();
+        ConsensusVariableSite {
+          group: group.to_owned(),
+          pos: pos + 1,
+          consensus: from_nuc(site.consensus(min_frequency)),
+          depth: site.depth().round() as usize,
+          missing: site.missing,
+          freq_a,
+          freq_c,
+          freq_g,
+          freq_t,
+          freq_gap,
+        }
+      })
+      .collect()
+  }
+}
+
+/// Row of the variability table. Positions are 1-based.
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusVariableSite {
+  pub group: String,
+  pub pos: usize,
+  pub consensus: char,
+  pub depth: usize,
+  pub missing: usize,
+  pub freq_a: f64,
+  pub freq_c: f64,
+  pub freq_g: f64,
+  pub freq_t: f64,
+  pub freq_gap: f64,
+}
+
+/// Accumulates aligned sequences into groups (for example, clades) and computes consensus per group
+#[derive(Clone, Debug, Default)]
+pub struct ConsensusGrouper {
+  groups: BTreeMap<String, ConsensusBuilder>,
+}
+
+impl ConsensusGrouper {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn add(&mut self, group: &str, aligned_seq: &[Nuc]) -> Result<(), Report> {
+    self
+      .groups
+      .entry(group.to_owned())
+      .or_default()
+      .add(aligned_seq)
+      .wrap_err_with(|| format!("When adding sequence to consensus group '{group}'"))
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&String, &ConsensusBuilder)> + '_ {
+    self.groups.iter()
+  }
+}
+
+/// Writes variability table of all groups. Tab-separated, unless the file path ends with ".csv".
+pub fn consensus_variability_write(
+  filepath: impl AsRef<Path>,
+  grouper: &ConsensusGrouper,
+  min_frequency: f64,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+  let mut writer = CsvStructFileWriter::new(filepath, delimiter)?;
+  for (group, builder) in grouper.iter() {
+    for site in builder.variability(group, min_frequency) {
+      writer
+        .write(&site)
+        .wrap_err_with(|| format!("When writing variability table: {filepath:#?}"))?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn computes_consensus_with_ambiguity_codes() -> Result<(), Report> {
+    let mut builder = ConsensusBuilder::new();
+    builder.add(&to_nuc_seq("ACGTAN-A")?)?;
+    builder.add(&to_nuc_seq("ACGTGN-A")?)?;
+    builder.add(&to_nuc_seq("ACGAAN-A")?)?;
+    builder.add(&to_nuc_seq("ACRTANCA")?)?;
+
+    assert_eq!(from_nuc_seq(&builder.consensus(0.2)), "ACGWRN-A");
+    assert_eq!(from_nuc_seq(&builder.consensus(0.3)), "ACGTAN-A");
+    assert_eq!(
+      builder
+        .variability("all", 0.2)
+        .iter()
+        .map(|site| site.pos)
+        .collect::<Vec<_>>(),
+      vec![3, 4, 5, 7]
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_sequences_of_different_lengths() -> Result<(), Report> {
+    let mut builder = ConsensusBuilder::new();
+    builder.add(&to_nuc_seq("ACGT")?)?;
+    assert_eq!(report_to_string(&builder.add(&to_nuc_seq("ACG")?).unwrap_err()), "Aligned sequences are expected to be of the same length, but found lengths 4 and 3. Make sure the sequences are aligned to the same reference, with insertions stripped.");
+    Ok(())
+  }
+}
@@ -1,8 +1,9 @@
 use crate::alphabet::aa::from_aa_seq;
 use crate::analyze::find_aa_motifs_changes::AaMotifsMap;
-use crate::analyze::virus_properties::{AaMotifsDesc, CountAaMotifsGeneDesc};
+use crate::analyze::virus_properties::{AaMotifSyntax, AaMotifsDesc, CountAaMotifsGeneDesc};
 use crate::coord::position::AaRefPosition;
 use crate::coord::range::{intersect_or_none, AaRefRange};
+use crate::make_error;
 use crate::translate::translate_genes::{CdsTranslation, Translation};
 use eyre::{eyre, Report, WrapErr};
 use itertools::Itertools;
@@ -53,10 +54,21 @@ fn process_one_aa_motifs_desc(
   let AaMotifsDesc {
     name,
     motifs,
+    syntax,
     include_genes,
     ..
   } = aa_motifs_desc;
 
+  // Convert all patterns to regular expressions
+  let motifs: Result<Vec<String>, Report> = match syntax {
+    AaMotifSyntax::Regex => Ok(motifs.clone()),
+    AaMotifSyntax::Prosite => motifs.iter().map(|motif| prosite_to_regex(motif)).collect(),
+  };
+  let motifs = match motifs {
+    Ok(motifs) => motifs,
+    Err(report) => return vec![Err(report.wrap_err(format!("When processing AA motifs '{name}'")))],
+  };
+
   // If no genes specified, process all genes
   let include_genes = if include_genes.is_empty() {
     translation
@@ -76,7 +88,7 @@ fn process_one_aa_motifs_desc(
       translation
         .cdses()
         .filter(|CdsTranslation { name, .. }| name == gene)
-        .flat_map(|translation| process_one_translation(translation, name, motifs, ranges))
+        .flat_map(|translation| process_one_translation(translation, name, &motifs, ranges))
         .collect_vec()
     })
     .collect_vec()
@@ -137,6 +149,81 @@ fn process_one_motif(
     .collect_vec()
 }
 
+/// Converts PROSITE pattern (for example `N-{P}-[ST]-{P}` for N-linked glycosylation sites) to an equivalent regular
+/// expression. Pattern elements are separated by `-`: a single aminoacid, `x` (any aminoacid), `[...]` (any of the
+/// listed aminoacids), `{...}` (any aminoacid except the listed ones), optionally followed by a repetition count `(n)`
+/// or a range of counts `(n,m)`. Patterns can be anchored to the beginning (`<`) or to the end (`>`) of the sequence
+/// and can end with a period.
+///
+/// See: https://prosite.expasy.org/scanprosite/scanprosite_doc.html
+pub fn prosite_to_regex(pattern: &str) -> Result<String, Report> {
+  let mut pattern = pattern.trim().trim_end_matches('.');
+
+  let mut re = String::new();
+  if let Some(rest) = pattern.strip_prefix('<') {
+    re.push('^');
+    pattern = rest;
+  }
+  let anchored_end = match pattern.strip_suffix('>') {
+    Some(rest) => {
+      pattern = rest;
+      true
+    }
+    None => false,
+  };
+
+  for element in pattern.split('-') {
+    let (letters, repetition) = match element.split_once('(') {
+      Some((letters, repetition)) => (letters, Some(repetition)),
+      None => (element, None),
+    };
+
+    let is_aa = |c: char| c.is_ascii_uppercase() || c == '*';
+    let letters_regex = match letters.chars().collect_vec().as_slice() {
+      ['x'] => ".".to_owned(),
+      [c] if is_aa(*c) => regex::escape(&c.to_string()),
+      ['[', inner @ .., ']'] if !inner.is_empty() && inner.iter().all(|c| is_aa(*c)) => {
+        format!("[{}]", regex::escape(&String::from_iter(inner)))
+      }
+      ['{', inner @ .., '}'] if !inner.is_empty() && inner.iter().all(|c| is_aa(*c)) => {
+        format!("[^{}]", regex::escape(&String::from_iter(inner)))
+      }
+      _ => return make_error!("Unable to parse PROSITE pattern element '{element}' in pattern '{pattern}'"),
+    };
+    re.push_str(&letters_regex);
+
+    if let Some(repetition) = repetition {
+      let counts = repetition
+        .strip_suffix(')')
+        .map(|counts| counts.split(',').map(str::trim).collect_vec())
+        .filter(|counts| {
+          counts.len() <= 2
+            && counts
+              .iter()
+              .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        });
+      match counts {
+        Some(counts) => {
+          re.push('{');
+          re.push_str(&counts.join(","));
+          re.push('}');
+        }
+        None => {
+          return make_error!(
+            "Unable to parse repetition in PROSITE pattern element '{element}' in pattern '{pattern}'"
+          )
+        }
+      }
+    }
+  }
+
+  if anchored_end {
+    re.push('$');
+  }
+
+  Ok(re)
+}
+
 // Wrapper for `struct AaMotif` which disregards `.seq` during comparison.
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, PartialOrd)]
 pub struct AaMotifWithoutSeq(pub AaMotif);
@@ -164,3 +251,31 @@ impl PartialEq<Self> for AaMotifWithoutSeq {
     // NOTE: `.seq` is disregarded
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case("N-{P}-[ST]-{P}", "N[^P][ST][^P]")]
+  #[case("C-x(2,4)-C-x(3)-[LIVMFYWC].", "C.{2,4}C.{3}[LIVMFYWC]")]
+  #[case("<M-x-K>", "^M.K$")]
+  fn converts_prosite_pattern_to_regex(#[case] pattern: &str, #[case] expected: &str) -> Result<(), Report> {
+    assert_eq!(prosite_to_regex(pattern)?, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("N-{}-[ST]", "Unable to parse PROSITE pattern element '{}' in pattern 'N-{}-[ST]'")]
+  #[case(
+    "N-x(2",
+    "Unable to parse repetition in PROSITE pattern element 'x(2' in pattern 'N-x(2'"
+  )]
+  #[case("N-p-[ST]", "Unable to parse PROSITE pattern element 'p' in pattern 'N-p-[ST]'")]
+  fn rejects_invalid_prosite_pattern(#[case] pattern: &str, #[case] message: &str) {
+    assert_eq!(report_to_string(&prosite_to_regex(pattern).unwrap_err()), message);
+  }
+}
@@ -161,7 +161,7 @@ fn process_seq_substitutions(
         // Case 3: Mutation in sequence but not in node, i.e. a newly occurred mutation.
         // Action: Add the sequence mutation itself.
         non_reversion_substitutions.push(AaSub {
-          cds_name: cds.name.clone(),
+          cds_name: cds.name_interned(),
           ref_aa: seq_mut.ref_aa,
           pos,
           qry_aa: seq_mut.qry_aa,
@@ -172,7 +172,7 @@ fn process_seq_substitutions(
           // Case 2: Mutation in sequence and in node, but the query character is not the same.
           // Action: Add mutation from node query character to sequence query character.
           non_reversion_substitutions.push(AaSub {
-            cds_name: cds.name.clone(),
+            cds_name: cds.name_interned(),
             ref_aa: *node_qry,
             pos,
             qry_aa: seq_mut.qry_aa,
@@ -214,7 +214,7 @@ fn process_seq_deletions(
         // Case 3: Mutation in sequence but not in node, i.e. a newly occurred mutation.
         // Action: Add the sequence mutation itself.
         non_reversion_deletions.push(AaDel {
-          cds_name: cds.name.clone(),
+          cds_name: cds.name_interned(),
           ref_aa: del.ref_aa,
           pos,
         });
@@ -224,7 +224,7 @@ fn process_seq_deletions(
           // Case 2: Mutation in sequence and in node, but the query character is not the same.
           // Action: Add mutation from node query character to sequence query character.
           non_reversion_deletions.push(AaDel {
-            cds_name: cds.name.clone(),
+            cds_name: cds.name_interned(),
             ref_aa: *node_qry,
             pos,
           });
@@ -263,7 +263,7 @@ fn find_reversions(
       // handled in process_seq_substitutions)
       // Action: Add mutation from node query character to character in reference sequence.
       reversion_substitutions.push(AaSub {
-        cds_name: cds.name.clone(),
+        cds_name: cds.name_interned(),
         ref_aa: *node_qry,
         pos,
         qry_aa: ref_peptide[pos.as_usize()],
@@ -0,0 +1,241 @@
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::range::{have_intersection, NucRefGlobalRange};
+use crate::gene::gene_map::GeneMap;
+use crate::io::compression::{guess_compression_from_filepath, CompressionType};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::fs::has_extension;
+use crate::io::json::{json_write, JsonPretty};
+use crate::io::nextclade_csv::{format_nuc_deletions, format_nuc_substitutions};
+use crate::make_error;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Group of sequences sharing the same mutation profile
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Haplotype {
+  pub haplotype_id: String,
+  pub substitutions: Vec<NucSub>,
+  pub deletions: Vec<NucDelRange>,
+  pub seq_names: Vec<String>,
+  pub count: usize,
+}
+
+/// Restricts which mutations are taken into account when grouping sequences into haplotypes.
+///
+/// If no restrictions are given, all nucleotide mutations are taken into account.
+#[derive(Clone, Debug, Default)]
+pub struct HaplotypeMask {
+  ranges: Option<Vec<NucRefGlobalRange>>,
+}
+
+impl HaplotypeMask {
+  /// Creates a mask from a list of CDS names and a list of 1-based nucleotide positions
+  pub fn new(gene_map: &GeneMap, cdses: &Option<Vec<String>>, positions: &Option<Vec<usize>>) -> Result<Self, Report> {
+    if cdses.is_none() && positions.is_none() {
+      return Ok(Self { ranges: None });
+    }
+
+    let mut ranges = vec![];
+
+    if let Some(cdses) = cdses {
+      for cds_name in cdses {
+        let cds = gene_map
+          .get_cds(cds_name)
+          .wrap_err_with(|| format!("When restricting haplotypes to CDS '{cds_name}'"))?;
+        ranges.extend(cds.segments.iter().map(|segment| segment.range.clone()));
+      }
+    }
+
+    if let Some(positions) = positions {
+      for &pos in positions {
+        if pos == 0 {
+          return make_error!("When restricting haplotypes to positions: positions are 1-based, but found position 0");
+        }
+        ranges.push(NucRefGlobalRange::from_usize(pos - 1, pos));
+      }
+    }
+
+    Ok(Self { ranges: Some(ranges) })
+  }
+
+  fn includes(&self, range: &NucRefGlobalRange) -> bool {
+    match &self.ranges {
+      None => true,
+      Some(ranges) => ranges.iter().any(|mask_range| have_intersection(mask_range, range)),
+    }
+  }
+}
+
+/// Mutation profile shared by all sequences of a haplotype: substitutions and deletions
+type HaplotypeKey = (Vec<NucSub>, Vec<NucDelRange>);
+
+/// Accumulates sequences into haplotypes, one sequence at a time.
+#[derive(Clone, Debug, Default)]
+pub struct HaplotypeGrouper {
+  mask: HaplotypeMask,
+  groups: BTreeMap<HaplotypeKey, Vec<(usize, String)>>,
+}
+
+impl HaplotypeGrouper {
+  pub const fn new(mask: HaplotypeMask) -> Self {
+    Self {
+      mask,
+      groups: BTreeMap::new(),
+    }
+  }
+
+  pub fn add(&mut self, outputs: &NextcladeOutputs) {
+    self.add_profile(
+      outputs.index,
+      &outputs.seq_name,
+      &outputs.substitutions,
+      &outputs.deletions,
+    );
+  }
+
+  pub fn add_profile(&mut self, index: usize, seq_name: &str, substitutions: &[NucSub], deletions: &[NucDelRange]) {
+    let substitutions = substitutions
+      .iter()
+      .filter(|sub| self.mask.includes(&NucRefGlobalRange::new(sub.pos, sub.pos + 1)))
+      .sorted()
+      .cloned()
+      .collect_vec();
+
+    let deletions = deletions
+      .iter()
+      .filter(|del| self.mask.includes(del.range()))
+      .sorted()
+      .cloned()
+      .collect_vec();
+
+    self
+      .groups
+      .entry((substitutions, deletions))
+      .or_default()
+      .push((index, seq_name.to_owned()));
+  }
+
+  /// Produces the list of haplotypes, most populous first. Haplotype IDs are assigned in this order.
+  pub fn finish(self) -> Vec<Haplotype> {
+    self
+      .groups
+      .into_iter()
+      .sorted_by(|(key1, members1), (key2, members2)| members2.len().cmp(&members1.len()).then(key1.cmp(key2)))
+      .enumerate()
+      .map(|(i, ((substitutions, deletions), members))| {
+        let seq_names = members
+          .into_iter()
+          .sorted_by_key(|(index, _)| *index)
+          .map(|(_, seq_name)| seq_name)
+          .collect_vec();
+
+        Haplotype {
+          haplotype_id: format!("H{}", i + 1),
+          substitutions,
+          deletions,
+          count: seq_names.len(),
+          seq_names,
+        }
+      })
+      .collect_vec()
+  }
+}
+
+/// Writes haplotype table. The format is deduced from file extension, possibly followed by an extension of a compression
+/// format: JSON for ".json", semicolon-separated table for ".csv", and tab-separated table otherwise.
+pub fn haplotypes_write(filepath: impl AsRef<Path>, haplotypes: &[Haplotype]) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let format_filepath = match guess_compression_from_filepath(filepath).0 {
+    CompressionType::None => filepath.to_owned(),
+    _ => filepath.with_extension(""),
+  };
+
+  if has_extension(&format_filepath, "json") {
+    return json_write(filepath, &haplotypes, JsonPretty(true));
+  }
+
+  let delimiter = if has_extension(&format_filepath, "csv") {
+    b';'
+  } else {
+    b'\t'
+  };
+  let headers = ["haplotype", "count", "substitutions", "deletions", "seqNames"]
+    .iter()
+    .map(|&header| header.to_owned())
+    .collect_vec();
+
+  let mut writer = CsvVecFileWriter::new(filepath, delimiter, &headers)?;
+  for haplotype in haplotypes {
+    writer
+      .write([
+        haplotype.haplotype_id.clone(),
+        haplotype.count.to_string(),
+        format_nuc_substitutions(&haplotype.substitutions, ","),
+        format_nuc_deletions(&haplotype.deletions, ","),
+        haplotype.seq_names.join(","),
+      ])
+      .wrap_err_with(|| format!("When writing haplotype table: {filepath:#?}"))?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::Nuc;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn sub(pos: usize, ref_nuc: Nuc, qry_nuc: Nuc) -> NucSub {
+    NucSub {
+      pos: pos.into(),
+      ref_nuc,
+      qry_nuc,
+    }
+  }
+
+  #[rstest]
+  fn groups_sequences_with_identical_mutations() {
+    let mut grouper = HaplotypeGrouper::new(HaplotypeMask::default());
+    grouper.add_profile(0, "a", &[sub(10, Nuc::A, Nuc::T)], &[]);
+    grouper.add_profile(1, "b", &[sub(10, Nuc::A, Nuc::T), sub(20, Nuc::C, Nuc::G)], &[]);
+    grouper.add_profile(2, "c", &[sub(10, Nuc::A, Nuc::T)], &[]);
+    grouper.add_profile(3, "d", &[], &[NucDelRange::from_usize(30, 33)]);
+
+    let haplotypes = grouper.finish();
+
+    assert_eq!(
+      haplotypes
+        .iter()
+        .map(|h| (h.haplotype_id.as_str(), h.count, h.seq_names.join(",")))
+        .collect_vec(),
+      vec![
+        ("H1", 2, "a,c".to_owned()),
+        ("H2", 1, "d".to_owned()),
+        ("H3", 1, "b".to_owned())
+      ]
+    );
+  }
+
+  #[rstest]
+  fn groups_sequences_within_restricted_positions() -> Result<(), Report> {
+    let mask = HaplotypeMask::new(&GeneMap::new(), &None, &Some(vec![11]))?;
+    let mut grouper = HaplotypeGrouper::new(mask);
+    grouper.add_profile(0, "a", &[sub(10, Nuc::A, Nuc::T)], &[]);
+    grouper.add_profile(1, "b", &[sub(10, Nuc::A, Nuc::T), sub(20, Nuc::C, Nuc::G)], &[]);
+
+    let haplotypes = grouper.finish();
+
+    assert_eq!(haplotypes.len(), 1);
+    assert_eq!(haplotypes[0].seq_names, vec!["a", "b"]);
+    assert_eq!(haplotypes[0].substitutions, vec![sub(10, Nuc::A, Nuc::T)]);
+    Ok(())
+  }
+}
@@ -0,0 +1,329 @@
+use crate::align::insertions_strip::{AaIns, Insertion};
+use crate::alphabet::aa::Aa;
+use crate::alphabet::nuc::{from_nuc, from_nuc_seq, Nuc};
+use crate::analyze::aa_del::AaDel;
+use crate::analyze::aa_sub::AaSub;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::gene::cds::Cds;
+use crate::gene::gene::GeneStrand;
+use crate::gene::gene_map::GeneMap;
+use crate::translate::complement::complement;
+use crate::translate::translate_genes::Translation;
+use itertools::{chain, Itertools};
+use serde::{Deserialize, Serialize};
+
+/// Mutations of the query sequence in HGVS nomenclature (https://varnomen.hgvs.org)
+///
+/// The reference sequence identifier is omitted from the genomic changes (see the `refName` of the results). Coding
+/// and protein changes are prefixed with the CDS name instead of the identifier of the reference transcript or protein.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HgvsMutations {
+  /// Nucleotide substitutions, deletions, insertions and duplications, relative to the reference genome (`g.`)
+  pub genomic: Vec<String>,
+  /// Nucleotide substitutions and deletions, relative to the coding sequences (`c.`)
+  pub coding: Vec<String>,
+  /// Aminoacid substitutions, deletions and insertions (`p.`)
+  pub protein: Vec<String>,
+}
+
+/// Formats mutations of the query sequence in HGVS nomenclature.
+///
+/// Insertions immediately adjacent to the beginning or the end of the reference sequence (or of the reference peptide)
+/// cannot be expressed as insertions between two residues and are omitted.
+pub fn format_hgvs_mutations(
+  substitutions: &[NucSub],
+  deletions: &[NucDelRange],
+  insertions: &[Insertion<Nuc>],
+  aa_substitutions: &[AaSub],
+  aa_deletions: &[AaDel],
+  aa_insertions: &[AaIns],
+  ref_seq: &[Nuc],
+  ref_translation: &Translation,
+  gene_map: &GeneMap,
+) -> HgvsMutations {
+  let genomic = chain!(
+    substitutions.iter().map(format_hgvs_nuc_sub),
+    deletions.iter().map(format_hgvs_nuc_del),
+    insertions.iter().filter_map(|ins| format_hgvs_nuc_ins(ins, ref_seq)),
+  )
+  .collect_vec();
+
+  let coding = gene_map
+    .iter_cdses()
+    .flat_map(|cds| {
+      let subs = substitutions
+        .iter()
+        .filter_map(move |sub| format_hgvs_coding_sub(sub, cds));
+      let dels = deletions.iter().flat_map(move |del| format_hgvs_coding_del(del, cds));
+      subs.chain(dels)
+    })
+    .collect_vec();
+
+  let protein = chain!(
+    aa_substitutions.iter().map(format_hgvs_aa_sub),
+    format_hgvs_aa_dels(aa_deletions),
+    aa_insertions.iter().filter_map(|ins| {
+      let ref_peptide = &ref_translation.get_cds(&ins.gene).ok()?.seq;
+      format_hgvs_aa_ins(ins, ref_peptide)
+    }),
+  )
+  .collect_vec();
+
+  HgvsMutations {
+    genomic,
+    coding,
+    protein,
+  }
+}
+
+/// Formats a position range (0-based, end exclusive) in HGVS notation: 1-based and inclusive, with a single position
+/// written once
+fn format_hgvs_range(begin: usize, end: usize) -> String {
+  if end - begin <= 1 {
+    format!("{}", begin + 1)
+  } else {
+    format!("{}_{}", begin + 1, end)
+  }
+}
+
+fn format_hgvs_nuc_sub(sub: &NucSub) -> String {
+  format!(
+    "g.{}{}>{}",
+    sub.pos.as_usize() + 1,
+    from_nuc(sub.ref_nuc),
+    from_nuc(sub.qry_nuc)
+  )
+}
+
+fn format_hgvs_nuc_del(del: &NucDelRange) -> String {
+  let range = del.range();
+  format!(
+    "g.{}del",
+    format_hgvs_range(range.begin.as_usize(), range.end.as_usize())
+  )
+}
+
+/// Formats a nucleotide insertion. An insertion which repeats the reference sequence immediately preceding it is a
+/// duplication in HGVS terms.
+fn format_hgvs_nuc_ins(ins: &Insertion<Nuc>, ref_seq: &[Nuc]) -> Option<String> {
+  // Position of the reference nucleotide after which the insertion happened
+  let pos = usize::try_from(ins.pos).ok()?;
+  if pos + 1 >= ref_seq.len() {
+    return None;
+  }
+
+  if let Some(begin) = (pos + 1).checked_sub(ins.ins.len()) {
+    if ref_seq[begin..=pos] == ins.ins[..] {
+      return Some(format!("g.{}dup", format_hgvs_range(begin, pos + 1)));
+    }
+  }
+
+  Some(format!("g.{}_{}ins{}", pos + 1, pos + 2, from_nuc_seq(&ins.ins)))
+}
+
+/// Converts a position in the reference genome to the position in the coding sequence of a CDS, if the CDS covers it.
+/// Also returns the strand, such that the nucleotides can be complemented for CDSes on the reverse strand.
+fn genome_to_coding_pos(pos: NucRefGlobalPosition, cds: &Cds) -> Option<(usize, GeneStrand)> {
+  cds
+    .segments
+    .iter()
+    .find(|segment| segment.range.contains(pos))
+    .map(|segment| {
+      let offset = match segment.strand {
+        GeneStrand::Reverse => (segment.range.end - 1 - pos).as_usize(),
+        GeneStrand::Forward => (pos - segment.range.begin).as_usize(),
+      };
+      (segment.range_local.begin.as_usize() + offset, segment.strand)
+    })
+}
+
+fn format_hgvs_coding_sub(sub: &NucSub, cds: &Cds) -> Option<String> {
+  let (coding_pos, strand) = genome_to_coding_pos(sub.pos, cds)?;
+  let (ref_nuc, qry_nuc) = if strand == GeneStrand::Reverse {
+    (complement(sub.ref_nuc), complement(sub.qry_nuc))
+  } else {
+    (sub.ref_nuc, sub.qry_nuc)
+  };
+  Some(format!(
+    "{}:c.{}{}>{}",
+    cds.name,
+    coding_pos + 1,
+    from_nuc(ref_nuc),
+    from_nuc(qry_nuc)
+  ))
+}
+
+/// Formats the part of a nucleotide deletion which falls into a CDS. The deleted coding positions are grouped into
+/// contiguous ranges, because a deletion can span multiple segments of the CDS.
+fn format_hgvs_coding_del(del: &NucDelRange, cds: &Cds) -> Vec<String> {
+  let coding_positions = del
+    .range()
+    .iter()
+    .filter_map(|pos| genome_to_coding_pos(pos, cds))
+    .map(|(coding_pos, _)| coding_pos)
+    .sorted()
+    .collect_vec();
+
+  group_contiguous(&coding_positions)
+    .into_iter()
+    .map(|(begin, end)| format!("{}:c.{}del", cds.name, format_hgvs_range(begin, end)))
+    .collect()
+}
+
+/// Groups sorted positions into contiguous ranges (end exclusive)
+fn group_contiguous(positions: &[usize]) -> Vec<(usize, usize)> {
+  let mut ranges: Vec<(usize, usize)> = vec![];
+  for &pos in positions {
+    match ranges.last_mut() {
+      Some((_, end)) if *end == pos => *end += 1,
+      _ => ranges.push((pos, pos + 1)),
+    }
+  }
+  ranges
+}
+
+/// Three-letter aminoacid code, as recommended by HGVS
+const fn aa_to_hgvs(aa: Aa) -> &'static str {
+  match aa {
+    Aa::A => "Ala",
+    Aa::B => "Asx",
+    Aa::C => "Cys",
+    Aa::D => "Asp",
+    Aa::E => "Glu",
+    Aa::F => "Phe",
+    Aa::G => "Gly",
+    Aa::H => "His",
+    Aa::I => "Ile",
+    Aa::J => "Xle",
+    Aa::K => "Lys",
+    Aa::L => "Leu",
+    Aa::M => "Met",
+    Aa::N => "Asn",
+    Aa::O => "Pyl",
+    Aa::P => "Pro",
+    Aa::Q => "Gln",
+    Aa::R => "Arg",
+    Aa::S => "Ser",
+    Aa::T => "Thr",
+    Aa::U => "Sec",
+    Aa::V => "Val",
+    Aa::W => "Trp",
+    Aa::Y => "Tyr",
+    Aa::Z => "Glx",
+    Aa::X => "Xaa",
+    Aa::Stop => "Ter",
+    Aa::Gap => "-",
+  }
+}
+
+fn format_hgvs_aa_sub(sub: &AaSub) -> String {
+  format!(
+    "{}:p.{}{}{}",
+    sub.cds_name.as_str(),
+    aa_to_hgvs(sub.ref_aa),
+    sub.pos.as_usize() + 1,
+    aa_to_hgvs(sub.qry_aa)
+  )
+}
+
+/// Formats aminoacid deletions, with deletions of adjacent aminoacids of the same CDS merged into one
+fn format_hgvs_aa_dels(deletions: &[AaDel]) -> Vec<String> {
+  let mut groups: Vec<Vec<&AaDel>> = vec![];
+  for del in deletions {
+    match groups.last_mut() {
+      Some(group)
+        if group
+          .last()
+          .map_or(false, |last| last.cds_name == del.cds_name && last.pos + 1 == del.pos) =>
+      {
+        group.push(del);
+      }
+      _ => groups.push(vec![del]),
+    }
+  }
+
+  groups
+    .into_iter()
+    .filter_map(|group| {
+      let first = group.first()?;
+      let last = group.last()?;
+      let range = if group.len() == 1 {
+        format!("{}{}", aa_to_hgvs(first.ref_aa), first.pos.as_usize() + 1)
+      } else {
+        format!(
+          "{}{}_{}{}",
+          aa_to_hgvs(first.ref_aa),
+          first.pos.as_usize() + 1,
+          aa_to_hgvs(last.ref_aa),
+          last.pos.as_usize() + 1
+        )
+      };
+      Some(format!("{}:p.{range}del", first.cds_name.as_str()))
+    })
+    .collect()
+}
+
+fn format_hgvs_aa_ins(ins: &AaIns, ref_peptide: &[Aa]) -> Option<String> {
+  // Position of the reference aminoacid after which the insertion happened
+  let pos = usize::try_from(ins.pos).ok()?;
+  let (before, after) = (ref_peptide.get(pos)?, ref_peptide.get(pos + 1)?);
+  let inserted = ins.ins.iter().map(|aa| aa_to_hgvs(*aa)).join("");
+  Some(format!(
+    "{}:p.{}{}_{}{}ins{inserted}",
+    ins.gene,
+    aa_to_hgvs(*before),
+    pos + 1,
+    aa_to_hgvs(*after),
+    pos + 2
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn formats_nucleotide_changes() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq("ACGTTGCA")?;
+
+    let sub = NucSub {
+      pos: 2.into(),
+      ref_nuc: Nuc::G,
+      qry_nuc: Nuc::A,
+    };
+    assert_eq!(format_hgvs_nuc_sub(&sub), "g.3G>A");
+    assert_eq!(format_hgvs_nuc_del(&NucDelRange::from_usize(3, 5)), "g.4_5del");
+    assert_eq!(format_hgvs_nuc_del(&NucDelRange::from_usize(3, 4)), "g.4del");
+
+    let dup = Insertion {
+      pos: 4,
+      ins: to_nuc_seq("TT")?,
+    };
+    assert_eq!(format_hgvs_nuc_ins(&dup, &ref_seq), Some("g.4_5dup".to_owned()));
+
+    let ins = Insertion {
+      pos: 4,
+      ins: to_nuc_seq("CC")?,
+    };
+    assert_eq!(format_hgvs_nuc_ins(&ins, &ref_seq), Some("g.5_6insCC".to_owned()));
+    Ok(())
+  }
+
+  #[rstest]
+  fn merges_adjacent_aminoacid_deletions() {
+    let del = |pos: isize, ref_aa: Aa| AaDel {
+      cds_name: "S".into(),
+      pos: pos.into(),
+      ref_aa,
+    };
+    let actual = format_hgvs_aa_dels(&[del(68, Aa::H), del(69, Aa::V), del(143, Aa::Y)]);
+    assert_eq!(actual, vec!["S:p.His69_Val70del", "S:p.Tyr144del"]);
+  }
+}
@@ -0,0 +1,54 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::{is_nuc_match, Nuc};
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::coord::range::NucRefGlobalRange;
+use crate::tree::tree::AuspiceGraphNodePayload;
+use serde::{Deserialize, Serialize};
+
+/// Ambiguous nucleotide of the query sequence, resolved using the state of the node of the reference tree the query
+/// is attached to
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImputedNuc {
+  pub pos: NucRefGlobalPosition,
+  /// Ambiguous nucleotide in the query sequence
+  pub qry_nuc: Nuc,
+  /// Nucleotide of the attachment node, which replaced the ambiguous nucleotide
+  pub imputed_nuc: Nuc,
+}
+
+/// Finds ambiguous nucleotides in the (aligned, with insertions stripped) query sequence, which can be resolved using
+/// the sequence of the given node of the reference tree, i.e. where the nucleotide of the node is one of the
+/// nucleotides the ambiguity code stands for (for example `R` where the node has `A` or `G`).
+///
+/// Only the aligned part of the query is considered. `N` is missing data rather than an ambiguous call, so it is not
+/// imputed.
+pub fn find_imputable_nucs(
+  node: &AuspiceGraphNodePayload,
+  qry_seq: &[Nuc],
+  alignment_range: &NucRefGlobalRange,
+  ref_seq: &[Nuc],
+) -> Vec<ImputedNuc> {
+  alignment_range
+    .iter()
+    .filter_map(|pos| {
+      let qry_nuc = qry_seq[pos.as_usize()];
+      if qry_nuc.is_acgtn() || qry_nuc.is_gap() {
+        return None;
+      }
+      let node_nuc = node.tmp.nuc_at(pos, ref_seq);
+      (node_nuc.is_acgt() && is_nuc_match(qry_nuc, node_nuc)).then_some(ImputedNuc {
+        pos,
+        qry_nuc,
+        imputed_nuc: node_nuc,
+      })
+    })
+    .collect()
+}
+
+/// Replaces ambiguous nucleotides of the query sequence with the imputed nucleotides
+pub fn impute_nucs_in_place(qry_seq: &mut [Nuc], imputed: &[ImputedNuc]) {
+  for ImputedNuc { pos, imputed_nuc, .. } in imputed {
+    qry_seq[pos.as_usize()] = *imputed_nuc;
+  }
+}
@@ -0,0 +1,295 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::io::csv::read_csv_file_with_delimiter;
+use crate::io::fs::has_extension;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Lineage and the nucleotide substitutions it acquired relative to its parent lineage
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineageDefinition {
+  pub lineage: String,
+  pub parent: Option<String>,
+  pub mutations: Vec<NucSub>,
+}
+
+/// Reads lineage definitions from a CSV or TSV file, with columns `lineage`, `parent` (empty for top-level lineages)
+/// and `mutations` (comma-separated nucleotide substitutions relative to the parent lineage, e.g. `C241T,A23403G`).
+pub fn lineage_definitions_read(filepath: impl AsRef<Path>) -> Result<Vec<LineageDefinition>, Report> {
+  let filepath = filepath.as_ref();
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+
+  let rows: Vec<BTreeMap<String, String>> = read_csv_file_with_delimiter(filepath, delimiter)
+    .wrap_err_with(|| format!("When reading lineage definitions from file {filepath:#?}"))?;
+
+  rows
+    .into_iter()
+    .map(|mut row| {
+      let Some(lineage) = row.remove("lineage") else {
+        return make_error!("Column 'lineage' is not found in file {filepath:#?}");
+      };
+      let parent = row.remove("parent").filter(|parent| !parent.is_empty());
+      let mutations = row
+        .remove("mutations")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|mutation| !mutation.is_empty())
+        .map(NucSub::from_str)
+        .collect::<Result<Vec<_>, Report>>()
+        .wrap_err_with(|| format!("When parsing mutations of lineage '{lineage}' in file {filepath:#?}"))?;
+      Ok(LineageDefinition {
+        lineage,
+        parent,
+        mutations,
+      })
+    })
+    .collect()
+}
+
+/// Lineage assigned to a query sequence by the lineage caller
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LineageCall {
+  /// Assigned lineage. Empty if none of the top-level lineages is supported by the query.
+  pub lineage: String,
+  /// Fraction of the defining mutations of the assigned lineage and of its ancestors which are present in the query,
+  /// out of those which are covered by the query
+  pub confidence: f64,
+  /// Defining mutations of the assigned lineage and of its ancestors, contradicted by the query
+  pub conflicts: Vec<NucSub>,
+  /// Number of defining mutations of the assigned lineage and of its ancestors, not covered by the query
+  pub total_missing: usize,
+  /// Sublineages of the assigned lineage, which are equally well supported by the query, such that the descent stopped
+  pub ambiguous_with: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+struct LineageNode {
+  name: String,
+  children: Vec<usize>,
+  mutations: Vec<NucSub>,
+}
+
+/// Assigns lineages to query sequences, given hierarchical lineage definitions, independently of the reference tree
+#[derive(Clone, Debug)]
+pub struct LineageCaller {
+  nodes: Vec<LineageNode>,
+  roots: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct MutationsSupport {
+  observed: usize,
+  missing: usize,
+  conflicts: Vec<NucSub>,
+}
+
+impl LineageCaller {
+  pub fn new(definitions: Vec<LineageDefinition>) -> Result<Self, Report> {
+    let mut indices = HashMap::<String, usize>::new();
+    for (i, definition) in definitions.iter().enumerate() {
+      if indices.insert(definition.lineage.clone(), i).is_some() {
+        return make_error!("Lineage '{}' is defined more than once", definition.lineage);
+      }
+    }
+
+    let parents = definitions
+      .iter()
+      .map(|definition| {
+        definition
+          .parent
+          .as_ref()
+          .map(|parent| match indices.get(parent) {
+            Some(&parent) => Ok(parent),
+            None => make_error!(
+              "Parent lineage '{parent}' of lineage '{}' is not defined",
+              definition.lineage
+            ),
+          })
+          .transpose()
+      })
+      .collect::<Result<Vec<Option<usize>>, Report>>()?;
+
+    // Walking up from any lineage should reach a top-level lineage in fewer steps than there are lineages
+    for (i, definition) in definitions.iter().enumerate() {
+      let mut current = parents[i];
+      for _ in 0..definitions.len() {
+        match current {
+          None => break,
+          Some(parent) => current = parents[parent],
+        }
+      }
+      if current.is_some() {
+        return make_error!(
+          "Lineage definitions contain a cycle involving lineage '{}'",
+          definition.lineage
+        );
+      }
+    }
+
+    let mut nodes = definitions
+      .into_iter()
+      .map(|definition| LineageNode {
+        name: definition.lineage,
+        children: vec![],
+        mutations: definition.mutations,
+      })
+      .collect_vec();
+
+    let mut roots = vec![];
+    for (i, parent) in parents.into_iter().enumerate() {
+      match parent {
+        None => roots.push(i),
+        Some(parent) => nodes[parent].children.push(i),
+      }
+    }
+
+    Ok(Self { nodes, roots })
+  }
+
+  /// Assigns lineage to the (aligned, with insertions stripped) query sequence.
+  ///
+  /// Starting from the top-level lineages, descends into the sublineage which is best supported by the query: the one
+  /// with the largest excess of present over contradicted defining mutations, requiring the present mutations to
+  /// outnumber the contradicted ones. The descent stops when no sublineage is supported, or when several sublineages
+  /// are supported equally well. Lineages without defining mutations are never assigned.
+  pub fn call(&self, qry_seq: &[Nuc], alignment_range: &NucRefGlobalRange) -> LineageCall {
+    let mut result = LineageCall::default();
+    let mut num_observed = 0_usize;
+    let mut candidates = &self.roots;
+
+    loop {
+      let supports = candidates
+        .iter()
+        .map(|&i| (i, mutations_support(&self.nodes[i].mutations, qry_seq, alignment_range)))
+        .filter(|(_, support)| support.observed > support.conflicts.len())
+        .collect_vec();
+
+      let Some(best_score) = supports.iter().map(|(_, support)| score(support)).max() else {
+        break;
+      };
+
+      let best = supports
+        .into_iter()
+        .filter(|(_, support)| score(support) == best_score)
+        .collect_vec();
+
+      if best.len() > 1 {
+        result.ambiguous_with = best.iter().map(|(i, _)| self.nodes[*i].name.clone()).collect();
+        break;
+      }
+
+      let (i, support) = best.into_iter().next().expect("Expected a single best sublineage");
+      num_observed += support.observed;
+      result.total_missing += support.missing;
+      result.conflicts.extend(support.conflicts);
+      result.lineage = self.nodes[i].name.clone();
+      candidates = &self.nodes[i].children;
+    }
+
+    let num_covered = num_observed + result.conflicts.len();
+    result.confidence = if num_covered > 0 {
+      num_observed as f64 / num_covered as f64
+    } else {
+      0.0
+    };
+    result
+  }
+}
+
+#[inline]
+fn score(support: &MutationsSupport) -> isize {
+  support.observed as isize - support.conflicts.len() as isize
+}
+
+fn mutations_support(mutations: &[NucSub], qry_seq: &[Nuc], alignment_range: &NucRefGlobalRange) -> MutationsSupport {
+  let mut support = MutationsSupport::default();
+  for mutation in mutations {
+    let qry_nuc = qry_seq[mutation.pos.as_usize()];
+    if !alignment_range.contains(mutation.pos) || !(qry_nuc.is_acgt() || qry_nuc.is_gap()) {
+      support.missing += 1;
+    } else if qry_nuc == mutation.qry_nuc {
+      support.observed += 1;
+    } else {
+      support.conflicts.push(mutation.clone());
+    }
+  }
+  support
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn definition(lineage: &str, parent: Option<&str>, mutations: &[&str]) -> Result<LineageDefinition, Report> {
+    Ok(LineageDefinition {
+      lineage: lineage.to_owned(),
+      parent: parent.map(str::to_owned),
+      mutations: mutations
+        .iter()
+        .map(|m| NucSub::from_str(m))
+        .collect::<Result<_, _>>()?,
+    })
+  }
+
+  fn caller() -> Result<LineageCaller, Report> {
+    LineageCaller::new(vec![
+      definition("A", None, &["A1C", "A2C"])?,
+      definition("A.1", Some("A"), &["A3G", "A4G", "A9G"])?,
+      definition("A.2", Some("A"), &["A5T", "A6T"])?,
+      definition("A.1.1", Some("A.1"), &["A7C", "A8C"])?,
+    ])
+  }
+
+  #[rstest]
+  #[case::sublineage("CCGGAACCGA", "A.1.1", 1.0, 0)]
+  #[case::conflict("CCGAAACCGA", "A.1.1", 6.0 / 7.0, 1)]
+  #[case::stops_at_parent("CCGGAAAAGA", "A.1", 1.0, 0)]
+  #[case::unassigned("AAAAAAAAAA", "", 0.0, 0)]
+  fn assigns_lineage(
+    #[case] qry: &str,
+    #[case] lineage: &str,
+    #[case] confidence: f64,
+    #[case] num_conflicts: usize,
+  ) -> Result<(), Report> {
+    let qry_seq = to_nuc_seq(qry)?;
+    let call = caller()?.call(&qry_seq, &NucRefGlobalRange::from_usize(0, 10));
+    assert_eq!(call.lineage, lineage);
+    assert!((call.confidence - confidence).abs() < 1e-9);
+    assert_eq!(call.conflicts.len(), num_conflicts);
+    Ok(())
+  }
+
+  #[rstest]
+  fn reports_ambiguous_sublineages() -> Result<(), Report> {
+    let qry_seq = to_nuc_seq("CCGGTTAANA")?;
+    let call = caller()?.call(&qry_seq, &NucRefGlobalRange::from_usize(0, 10));
+    assert_eq!(call.lineage, "A");
+    assert_eq!(call.ambiguous_with, vec![o!("A.1"), o!("A.2")]);
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_cyclic_definitions() -> Result<(), Report> {
+    let result = LineageCaller::new(vec![definition("A", Some("B"), &[])?, definition("B", Some("A"), &[])?]);
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "Lineage definitions contain a cycle involving lineage 'A'"
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,80 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::NucRefGlobalPosition;
+use crate::coord::range::NucRefGlobalRange;
+
+/// Masks regions of the reference sequence (for example problematic sites) in the aligned query: query nucleotides
+/// aligned to the masked reference positions are replaced with `N`, such that they are excluded from mutation calling
+/// and phylogenetic placement. Gaps (deletions and unsequenced ends) are kept.
+///
+/// The `masked_ranges` should be sorted and non-overlapping. Returns the ranges of reference positions which have
+/// been masked in the query.
+pub fn mask_alignment_in_place(
+  qry_aln: &mut [Nuc],
+  ref_aln: &[Nuc],
+  masked_ranges: &[NucRefGlobalRange],
+) -> Vec<NucRefGlobalRange> {
+  debug_assert_eq!(qry_aln.len(), ref_aln.len());
+
+  let mut masked = Vec::<NucRefGlobalRange>::new();
+  if masked_ranges.is_empty() {
+    return masked;
+  }
+
+  let mut ranges = masked_ranges.iter().peekable();
+  let mut ref_pos = NucRefGlobalPosition::from(0_usize);
+  for (qry, reff) in qry_aln.iter_mut().zip(ref_aln) {
+    if reff.is_gap() {
+      continue;
+    }
+
+    while ranges.peek().is_some_and(|range| range.end <= ref_pos) {
+      ranges.next();
+    }
+
+    if !qry.is_gap() && ranges.peek().is_some_and(|range| range.contains(ref_pos)) {
+      *qry = Nuc::N;
+      match masked.last_mut() {
+        Some(last) if last.end == ref_pos => last.end = ref_pos + 1,
+        _ => masked.push(NucRefGlobalRange::new(ref_pos, ref_pos + 1)),
+      }
+    }
+
+    ref_pos += 1;
+  }
+
+  masked
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn masks_query_nucleotides_in_reference_coordinates() -> Result<(), Report> {
+    let mut qry_aln = to_nuc_seq("--GTTACGA-GTAC")?;
+    let ref_aln = to_nuc_seq("ACGT-ACGATGTAC")?;
+    let masked = mask_alignment_in_place(
+      &mut qry_aln,
+      &ref_aln,
+      &[
+        NucRefGlobalRange::from_usize(0, 3),
+        NucRefGlobalRange::from_usize(6, 11),
+      ],
+    );
+    assert_eq!(from_nuc_seq(&qry_aln), "--NTTACNN-NNAC");
+    assert_eq!(
+      masked,
+      vec![
+        NucRefGlobalRange::from_usize(2, 3),
+        NucRefGlobalRange::from_usize(6, 8),
+        NucRefGlobalRange::from_usize(9, 11),
+      ]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,232 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::io::fs::read_file_to_string;
+use crate::io::parse_pos::parse_pos;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Frequency of an alternative nucleotide at a position, as called from the reads of a sample
+#[derive(Clone, Debug, PartialEq)]
+struct AlleleFrequency {
+  pos: NucRefGlobalPosition,
+  ref_nuc: Nuc,
+  alt_nuc: Nuc,
+  frequency: f64,
+}
+
+/// Allele frequencies of single-nucleotide variants, read from a VCF file, per sample
+#[derive(Clone, Debug, Default)]
+pub struct AlleleFrequencies {
+  by_sample: BTreeMap<String, Vec<AlleleFrequency>>,
+  /// Frequencies from the `INFO` column of a VCF file without sample columns, applying to every sample
+  unnamed: Vec<AlleleFrequency>,
+}
+
+impl AlleleFrequencies {
+  /// Reads allele frequencies from a VCF file (see [AlleleFrequencies::from_vcf_str])
+  pub fn from_vcf_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let data = read_file_to_string(filepath)?;
+    Self::from_vcf_str(data).wrap_err_with(|| format!("When reading allele frequencies from VCF file {filepath:#?}"))
+  }
+
+  /// Parses allele frequencies of single-nucleotide variants from VCF data. The frequencies are taken from the `AF`
+  /// field of each sample column, or computed from the allelic depths in the `AD` field. If there are no sample
+  /// columns (as in the output of single-sample variant callers), the `AF` field of the `INFO` column is used.
+  /// Multi-nucleotide variants and indels are skipped.
+  pub fn from_vcf_str(data: impl AsRef<str>) -> Result<Self, Report> {
+    let mut result = Self::default();
+    let mut samples = Vec::<String>::new();
+
+    for (i, line) in data.as_ref().lines().enumerate() {
+      if line.starts_with("##") || line.trim().is_empty() {
+        continue;
+      }
+      let columns = line.split('\t').collect_vec();
+      if line.starts_with('#') {
+        samples = columns.iter().skip(9).map(|&sample| sample.to_owned()).collect();
+        continue;
+      }
+
+      let (Some(pos), Some(reff), Some(alts), Some(info)) = (columns.get(1), columns.get(3), columns.get(4), columns.get(7))
+      else {
+        return make_error!("VCF line {}: expected at least 8 columns, but found {}", i + 1, columns.len());
+      };
+      let pos: NucRefGlobalPosition = parse_pos(pos)
+        .wrap_err_with(|| format!("VCF line {}: unable to parse position '{pos}'", i + 1))?
+        .into();
+      let alts = alts.split(',').collect_vec();
+
+      if samples.is_empty() {
+        let frequencies = find_field(info, ';', "AF").map(parse_frequencies).unwrap_or_default();
+        result.unnamed.extend(snvs(pos, reff, &alts, &frequencies)?);
+      } else {
+        let format = columns
+          .get(8)
+          .map(|format| format.split(':').collect_vec())
+          .unwrap_or_default();
+        for (sample, values) in samples.iter().zip(columns.iter().skip(9)) {
+          let values = values.split(':').collect_vec();
+          let field = |key: &str| {
+            format
+              .iter()
+              .position(|&k| k == key)
+              .and_then(|j| values.get(j).copied())
+          };
+          let frequencies = field("AF")
+            .map(parse_frequencies)
+            .or_else(|| field("AD").map(frequencies_from_depths))
+            .unwrap_or_default();
+          result
+            .by_sample
+            .entry(sample.clone())
+            .or_default()
+            .extend(snvs(pos, reff, &alts, &frequencies)?);
+        }
+      }
+    }
+
+    Ok(result)
+  }
+
+  fn get(&self, seq_name: &str) -> &[AlleleFrequency] {
+    self
+      .by_sample
+      .get(seq_name)
+      .map_or(&self.unnamed, |frequencies| frequencies)
+  }
+}
+
+fn find_field<'a>(s: &'a str, delimiter: char, key: &str) -> Option<&'a str> {
+  s.split(delimiter)
+    .find_map(|entry| entry.strip_prefix(key).and_then(|rest| rest.strip_prefix('=')))
+}
+
+/// Parses comma-separated frequencies, one per alternative allele. Missing values (`.`) are kept as `None`.
+fn parse_frequencies(s: &str) -> Vec<Option<f64>> {
+  s.split(',').map(|af| af.parse::<f64>().ok()).collect()
+}
+
+/// Computes frequencies of alternative alleles from comma-separated allelic depths: reference, then alternatives
+fn frequencies_from_depths(s: &str) -> Vec<Option<f64>> {
+  let depths = s.split(',').map(|ad| ad.parse::<f64>().ok()).collect_vec();
+  let total: f64 = depths.iter().flatten().sum();
+  if total <= 0.0 {
+    return vec![];
+  }
+  depths
+    .iter()
+    .skip(1)
+    .map(|depth| depth.map(|depth| depth / total))
+    .collect()
+}
+
+fn snvs(
+  pos: NucRefGlobalPosition,
+  reff: &str,
+  alts: &[&str],
+  frequencies: &[Option<f64>],
+) -> Result<Vec<AlleleFrequency>, Report> {
+  if reff.len() != 1 {
+    return Ok(vec![]);
+  }
+  let ref_nuc = Nuc::from_string(reff)?;
+  alts
+    .iter()
+    .zip(frequencies)
+    .filter(|(alt, _)| alt.len() == 1 && **alt != ".")
+    .filter_map(|(alt, frequency)| frequency.map(|frequency| (alt, frequency)))
+    .map(|(alt, frequency)| {
+      Ok(AlleleFrequency {
+        pos,
+        ref_nuc,
+        alt_nuc: Nuc::from_string(alt)?,
+        frequency,
+      })
+    })
+    .collect()
+}
+
+/// Sub-consensus variant: a nucleotide present in a fraction of the reads of the sample, which is too small for it to
+/// appear in the consensus sequence
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MinorVariant {
+  pub pos: NucRefGlobalPosition,
+  pub ref_nuc: Nuc,
+  pub alt_nuc: Nuc,
+  /// Fraction of the reads supporting the alternative nucleotide
+  pub frequency: f64,
+  /// Nucleotide of the (aligned) consensus sequence at this position
+  pub qry_nuc: Nuc,
+}
+
+/// Finds the sub-consensus variants of the sample, with frequencies at least `min_frequency` and less than
+/// `max_frequency`
+pub fn find_minor_variants(
+  allele_frequencies: &AlleleFrequencies,
+  seq_name: &str,
+  qry_seq: &[Nuc],
+  min_frequency: f64,
+  max_frequency: f64,
+) -> Vec<MinorVariant> {
+  allele_frequencies
+    .get(seq_name)
+    .iter()
+    .filter(|af| af.frequency >= min_frequency && af.frequency < max_frequency)
+    .filter_map(|af| {
+      let qry_nuc = *qry_seq.get(af.pos.as_usize())?;
+      Some(MinorVariant {
+        pos: af.pos,
+        ref_nuc: af.ref_nuc,
+        alt_nuc: af.alt_nuc,
+        frequency: af.frequency,
+        qry_nuc,
+      })
+    })
+    .sorted_by_key(|variant| (variant.pos, variant.alt_nuc))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  const VCF: &str = "##fileformat=VCFv4.2
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tseq1\tseq2
+ref\t2\t.\tC\tT,G\t.\tPASS\t.\tGT:AD\t0/1:80,15,5\t0/0:100,0,0
+ref\t4\t.\tG\tA\t.\tPASS\t.\tGT:AF\t1/1:0.9\t0/1:0.2
+ref\t6\t.\tAC\tA\t.\tPASS\t.\tGT:AF\t0/1:0.3\t0/0:0
+";
+
+  #[rstest]
+  fn finds_minor_variants_from_vcf() -> Result<(), Report> {
+    let allele_frequencies = AlleleFrequencies::from_vcf_str(VCF)?;
+    let qry_seq = to_nuc_seq("ACGAAC")?;
+
+    let variants = find_minor_variants(&allele_frequencies, "seq1", &qry_seq, 0.05, 0.5);
+    assert_eq!(
+      variants
+        .iter()
+        .map(|v| (v.pos.as_usize(), v.alt_nuc, v.qry_nuc))
+        .collect_vec(),
+      vec![(1, Nuc::T, Nuc::C), (1, Nuc::G, Nuc::C)]
+    );
+    assert!((variants[0].frequency - 0.15).abs() < 1e-9);
+
+    let variants = find_minor_variants(&allele_frequencies, "seq2", &qry_seq, 0.05, 0.5);
+    assert_eq!(
+      variants.iter().map(|v| (v.pos.as_usize(), v.alt_nuc)).collect_vec(),
+      vec![(3, Nuc::A)]
+    );
+    Ok(())
+  }
+}
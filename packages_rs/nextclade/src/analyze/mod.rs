@@ -2,18 +2,34 @@ pub mod aa_changes;
 pub mod aa_del;
 pub mod aa_sub;
 pub mod abstract_mutation;
+pub mod cds_coverage;
+pub mod clade_defining_mutations;
+pub mod codon_usage;
+pub mod consensus;
 pub mod count_gaps;
 pub mod divergence;
 pub mod find_aa_motifs;
 pub mod find_aa_motifs_changes;
 pub mod find_private_aa_mutations;
 pub mod find_private_nuc_mutations;
+pub mod haplotypes;
+pub mod hgvs;
+pub mod impute;
 pub mod is_sequenced;
 pub mod letter_composition;
 pub mod letter_ranges;
+pub mod lineage_caller;
+pub mod masking;
+pub mod minor_variants;
+pub mod mutation_annotations;
+pub mod mutation_path;
 pub mod nuc_changes;
 pub mod nuc_del;
 pub mod nuc_sub;
 pub mod pcr_primer_changes;
 pub mod phenotype;
+pub mod position_support;
+pub mod recombination;
+pub mod relative_mutations;
+pub mod structural_events;
 pub mod virus_properties;
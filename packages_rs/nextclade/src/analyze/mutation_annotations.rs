@@ -0,0 +1,134 @@
+use crate::alphabet::aa::Aa;
+use crate::analyze::aa_sub::AaSub;
+use crate::coord::position::AaRefPosition;
+use crate::io::csv::parse_csv_with_delimiter;
+use crate::io::fs::{has_extension, read_file_to_string};
+use crate::io::json::json_parse;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Entry of the knowledge base of known aminoacid mutations, as it appears in the input file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KnownMutationRow {
+  mutation: String,
+  annotation: String,
+  #[serde(default)]
+  source: Option<String>,
+}
+
+/// Known aminoacid mutation (for example conferring antiviral resistance or antibody escape), matched against the
+/// aminoacid substitutions of the query
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MutationAnnotation {
+  /// Aminoacid substitution of the query
+  pub mutation: AaSub,
+  pub annotation: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source: Option<String>,
+}
+
+/// CDS name, position and query aminoacid of a known mutation
+type MutationKey = (String, AaRefPosition, Aa);
+
+/// Knowledge base of known aminoacid mutations, indexed by CDS, position and query aminoacid
+#[derive(Clone, Debug, Default)]
+pub struct MutationKnowledgeBase {
+  /// Annotations of each mutation, with their sources
+  entries: BTreeMap<MutationKey, Vec<(String, Option<String>)>>,
+}
+
+impl MutationKnowledgeBase {
+  /// Reads the knowledge base from a JSON file (array of objects), or from a CSV or TSV file, with fields `mutation`
+  /// (aminoacid substitution including the CDS name, e.g. `S:E484K`), `annotation` and optionally `source`.
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let data = read_file_to_string(filepath)?;
+    let rows: Vec<KnownMutationRow> = if has_extension(filepath, "json") {
+      json_parse(&data)
+    } else {
+      let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+      parse_csv_with_delimiter(&data, delimiter)
+    }
+    .wrap_err_with(|| format!("When reading mutation annotations from file {filepath:#?}"))?;
+    Self::from_rows(rows).wrap_err_with(|| format!("When reading mutation annotations from file {filepath:#?}"))
+  }
+
+  fn from_rows(rows: Vec<KnownMutationRow>) -> Result<Self, Report> {
+    let mut entries = BTreeMap::<_, Vec<_>>::new();
+    for KnownMutationRow {
+      mutation,
+      annotation,
+      source,
+    } in rows
+    {
+      let sub = AaSub::from_str(mutation.trim())?;
+      entries
+        .entry((sub.cds_name.to_string(), sub.pos, sub.qry_aa))
+        .or_default()
+        .push((annotation, source));
+    }
+    Ok(Self { entries })
+  }
+
+  /// Finds the known mutations among the aminoacid substitutions of the query. Mutations are matched by CDS, position
+  /// and query aminoacid, such that the reference aminoacid is allowed to differ between the knowledge base and the
+  /// reference sequence of the dataset.
+  pub fn annotate(&self, aa_substitutions: &[AaSub]) -> Vec<MutationAnnotation> {
+    aa_substitutions
+      .iter()
+      .flat_map(|sub| {
+        self
+          .entries
+          .get(&(sub.cds_name.to_string(), sub.pos, sub.qry_aa))
+          .into_iter()
+          .flatten()
+          .map(|(annotation, source)| MutationAnnotation {
+            mutation: sub.clone(),
+            annotation: annotation.clone(),
+            source: source.clone(),
+          })
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn annotates_known_mutations() -> Result<(), Report> {
+    let rows: Vec<KnownMutationRow> = parse_csv_with_delimiter(
+      "mutation\tannotation\tsource\nS:E484K\tmAb escape\tStanford\nS:E484K\tReduced neutralization\t\nnsp5:E166V\tNirmatrelvir resistance\t\n",
+      b'\t',
+    )?;
+    let knowledge_base = MutationKnowledgeBase::from_rows(rows)?;
+
+    let query = vec![
+      AaSub::from_str("S:E484K")?,
+      AaSub::from_str("S:E484Q")?,
+      AaSub::from_str("nsp5:E166V")?,
+    ];
+    let annotations = knowledge_base.annotate(&query);
+
+    assert_eq!(
+      annotations
+        .iter()
+        .map(|a| (a.mutation.to_string(), a.annotation.as_str(), a.source.as_deref()))
+        .collect::<Vec<_>>(),
+      vec![
+        ("S:E484K".to_owned(), "mAb escape", Some("Stanford")),
+        ("S:E484K".to_owned(), "Reduced neutralization", None),
+        ("nsp5:E166V".to_owned(), "Nirmatrelvir resistance", None),
+      ]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,51 @@
+use crate::analyze::aa_sub::AaSub;
+use crate::analyze::nuc_sub::NucSub;
+use crate::graph::node::GraphNodeKey;
+use crate::tree::tree::AuspiceGraph;
+use eyre::Report;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Mutations on one of the branches of the path from the root of the reference tree to the nearest node
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MutationPathBranch {
+  /// Name of the node at the end of the branch
+  pub node_name: String,
+  pub clade: String,
+  pub nuc_substitutions: Vec<NucSub>,
+  pub aa_substitutions: Vec<AaSub>,
+}
+
+/// Finds mutations along the path from the root of the reference tree to the nearest node, branch by branch, starting
+/// from the root. Branches without mutations are omitted. Followed by the private mutations of the query, this is the
+/// reconstructed ancestral history of the query.
+pub fn find_mutation_path(
+  graph: &AuspiceGraph,
+  nearest_node_key: GraphNodeKey,
+) -> Result<Vec<MutationPathBranch>, Report> {
+  let mut node = graph.get_node(nearest_node_key)?;
+  let mut path = vec![node];
+  while let Some(parent) = graph.parent_of(node) {
+    path.push(parent);
+    node = parent;
+  }
+
+  Ok(
+    path
+      .into_iter()
+      .rev()
+      .map(|node| {
+        let payload = node.payload();
+        let mutations = &payload.tmp.private_mutations;
+        MutationPathBranch {
+          node_name: payload.name.clone(),
+          clade: payload.clade(),
+          nuc_substitutions: mutations.nuc_muts.clone(),
+          aa_substitutions: mutations.aa_muts.values().flatten().cloned().collect_vec(),
+        }
+      })
+      .filter(|branch| !branch.nuc_substitutions.is_empty() || !branch.aa_substitutions.is_empty())
+      .collect(),
+  )
+}
@@ -2,7 +2,9 @@ use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::nuc_del::NucDelRange;
 use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::NucRefGlobalPosition;
 use crate::coord::range::NucRefGlobalRange;
+use std::iter::zip;
 
 pub struct FindNucChangesOutput {
   pub substitutions: Vec<NucSub>,
@@ -13,45 +15,41 @@ pub struct FindNucChangesOutput {
 /// Finds nucleotide changes (nucleotide substitutions and deletions) as well
 /// as the beginning and end of the alignment range.
 ///
+/// Leading and trailing gaps of the query are outside of the alignment range and are not reported as deletions,
+/// regardless of whether terminal gaps were penalized during alignment.
+///
 /// @pre Precondition: sequences are expected to be aligned and stripped from insertions.
 pub fn find_nuc_changes(qry_aln: &[Nuc], ref_aln: &[Nuc]) -> FindNucChangesOutput {
   assert_eq!(ref_aln.len(), qry_aln.len());
 
-  let mut n_del: i64 = 0;
-  let mut del_pos: i64 = -1;
-  let mut before_alignment = true;
-
   let mut substitutions = Vec::<NucSub>::new();
   let mut deletions = Vec::<NucDelRange>::new();
-  let mut alignment_start: i64 = -1;
-  let mut alignment_end: i64 = -1;
+  let mut alignment_range: Option<NucRefGlobalRange> = None;
+  let mut del_begin: Option<NucRefGlobalPosition> = None;
 
-  for i in 0..qry_aln.len() {
-    let d = qry_aln[i];
+  // Sequences are stripped from insertions, so the positions in the alignment are the positions in the reference
+  for (i, (&qry_nuc, &ref_nuc)) in zip(qry_aln, ref_aln).enumerate() {
+    let pos = NucRefGlobalPosition::from(i);
 
-    if !d.is_gap() {
-      if before_alignment {
-        alignment_start = i as i64;
-        before_alignment = false;
-      } else if n_del > 0 {
-        deletions.push(NucDelRange::from_usize(del_pos as usize, (del_pos + n_del) as usize));
-        n_del = 0;
+    if qry_nuc.is_gap() {
+      // Leading gaps are outside of the alignment range and are not deletions
+      if alignment_range.is_some() && del_begin.is_none() {
+        del_begin = Some(pos);
       }
-      alignment_end = (i + 1) as i64;
+      continue;
     }
 
-    let ref_nuc = ref_aln[i];
-    if !d.is_gap() && (d != ref_nuc) && d.is_acgt() {
-      substitutions.push(NucSub {
-        ref_nuc,
-        pos: i.into(),
-        qry_nuc: d,
-      });
-    } else if d.is_gap() && !before_alignment {
-      if n_del == 0 {
-        del_pos = i as i64;
-      }
-      n_del += 1;
+    match &mut alignment_range {
+      None => alignment_range = Some(NucRefGlobalRange::new(pos, pos + 1)),
+      Some(alignment_range) => alignment_range.end = pos + 1,
+    }
+
+    if let Some(begin) = del_begin.take() {
+      deletions.push(NucDelRange::new(begin, pos));
+    }
+
+    if qry_nuc != ref_nuc && qry_nuc.is_acgt() {
+      substitutions.push(NucSub { pos, ref_nuc, qry_nuc });
     }
   }
 
@@ -61,6 +59,40 @@ pub fn find_nuc_changes(qry_aln: &[Nuc], ref_aln: &[Nuc]) -> FindNucChangesOutpu
   FindNucChangesOutput {
     substitutions,
     deletions,
-    alignment_range: NucRefGlobalRange::from_usize(alignment_start as usize, alignment_end as usize),
+    alignment_range: alignment_range.unwrap_or_else(|| NucRefGlobalRange::from_usize(0, 0)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn finds_nuc_changes() -> Result<(), Report> {
+    let ref_aln = to_nuc_seq("ACGTACGTACGTACGT")?;
+    let qry_aln = to_nuc_seq("--GTTCG--CGTNCG-")?;
+
+    let FindNucChangesOutput {
+      substitutions,
+      deletions,
+      alignment_range,
+    } = find_nuc_changes(&qry_aln, &ref_aln);
+
+    assert_eq!(
+      substitutions,
+      vec![NucSub {
+        pos: 4.into(),
+        ref_nuc: Nuc::A,
+        qry_nuc: Nuc::T,
+      }]
+    );
+    // Leading and trailing gaps are not deletions
+    assert_eq!(deletions, vec![NucDelRange::from_usize(7, 9)]);
+    assert_eq!(alignment_range, NucRefGlobalRange::from_usize(2, 15));
+    Ok(())
   }
 }
@@ -1,7 +1,18 @@
+use crate::alphabet::aa::Aa;
+use crate::alphabet::letter::Letter;
 use crate::analyze::aa_sub::AaSub;
-use crate::analyze::virus_properties::{PhenotypeAttrDesc, PhenotypeData, VirusProperties};
+use crate::analyze::virus_properties::{
+  PhenotypeAggregation, PhenotypeAttrDesc, PhenotypeCoeff, PhenotypeData, PhenotypeDataEntry, VirusProperties,
+};
+use crate::coord::position::AaRefPosition;
+use crate::io::csv::parse_csv_with_delimiter;
+use crate::make_error;
+use eyre::{Report, WrapErr};
 use itertools::Itertools;
 use num_traits::real::Real;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 pub fn calculate_phenotype(phenotype_data: &PhenotypeData, aa_substitutions: &[AaSub]) -> f64 {
   let aa_substitutions = aa_substitutions
@@ -9,19 +20,112 @@ pub fn calculate_phenotype(phenotype_data: &PhenotypeData, aa_substitutions: &[A
     .filter_map(|sub| (sub.cds_name == phenotype_data.gene && phenotype_data.aa_range.contains(sub.pos)).then_some(sub))
     .collect_vec();
 
-  let phenotype: f64 = phenotype_data
-    .data
-    .iter()
-    .map(|phenotype_data| {
-      let phenotype_for_antibody: f64 = aa_substitutions
-        .iter()
-        .map(|AaSub { pos, qry_aa: qry, .. }| phenotype_data.get_coeff(*pos, *qry))
+  let phenotypes_for_entries = phenotype_data.data.iter().map(|phenotype_data| {
+    let phenotype_for_entry: f64 = aa_substitutions
+      .iter()
+      .map(|AaSub { pos, qry_aa: qry, .. }| phenotype_data.get_coeff(*pos, *qry))
+      .sum();
+    (phenotype_data.weight, phenotype_for_entry)
+  });
+
+  match phenotype_data.aggregation {
+    PhenotypeAggregation::Escape => {
+      let phenotype: f64 = phenotypes_for_entries
+        .map(|(weight, phenotype_for_antibody)| weight * (-phenotype_for_antibody).exp())
         .sum();
-      phenotype_data.weight * (-phenotype_for_antibody).exp()
-    })
-    .sum();
+      -phenotype.ln()
+    }
+    PhenotypeAggregation::Sum => phenotypes_for_entries
+      .map(|(weight, phenotype_for_entry)| weight * phenotype_for_entry)
+      .sum(),
+  }
+}
 
-  -phenotype.ln()
+/// Row of a phenotype coefficients file
+#[derive(Debug, Deserialize)]
+struct PhenotypeCoeffRow {
+  /// Name of the data entry (e.g. antibody class). Rows without name belong to the entry `default`.
+  #[serde(default)]
+  entry: Option<String>,
+  /// Weight of the data entry. Defaults to 1.
+  #[serde(default)]
+  weight: Option<f64>,
+  /// 1-based aminoacid position
+  site: usize,
+  /// Aminoacid the coefficient applies to. Rows without aminoacid apply to all aminoacids at the site, unless there is
+  /// a more specific row.
+  #[serde(default)]
+  mutant: Option<String>,
+  coefficient: f64,
+}
+
+/// Parses phenotype coefficients from CSV or TSV data, with columns `site`, `coefficient` and optionally `mutant`,
+/// `entry` and `weight`
+pub fn parse_phenotype_coefficients(data: &str, delimiter: u8) -> Result<Vec<PhenotypeDataEntry>, Report> {
+  let rows: Vec<PhenotypeCoeffRow> = parse_csv_with_delimiter(data, delimiter)?;
+
+  let mut entries = BTreeMap::<String, PhenotypeDataEntry>::new();
+  for row in rows {
+    let name = row.entry.unwrap_or_else(|| "default".to_owned());
+    let entry = entries.entry(name.clone()).or_insert_with(|| PhenotypeDataEntry {
+      name: name.clone(),
+      weight: row.weight.unwrap_or(1.0),
+      locations: BTreeMap::new(),
+    });
+
+    if row.site == 0 {
+      return make_error!("Phenotype coefficients of entry '{name}': sites are 1-based, but found site 0");
+    }
+    let pos = AaRefPosition::from((row.site - 1) as isize);
+
+    let aa = match row.mutant {
+      None => "default".to_owned(),
+      Some(mutant) => Aa::from_string(&mutant)
+        .wrap_err_with(|| format!("Phenotype coefficients of entry '{name}' at site {}", row.site))?
+        .to_string(),
+    };
+
+    let location = entry
+      .locations
+      .entry(pos)
+      .or_insert_with(|| PhenotypeCoeff::ByPositionAndAa(BTreeMap::new()));
+    if let PhenotypeCoeff::ByPositionAndAa(coeffs) = location {
+      coeffs.insert(aa, row.coefficient);
+    }
+  }
+
+  Ok(entries.into_values().collect_vec())
+}
+
+/// Reads coefficient files of phenotypes (`coefficientsFile` in pathogen JSON), using the provided function to read
+/// dataset files, and adds their entries to the phenotype data
+pub fn phenotype_data_load_coefficients(
+  virus_properties: &mut VirusProperties,
+  mut read_dataset_file: impl FnMut(&str) -> Result<String, Report>,
+) -> Result<(), Report> {
+  let Some(phenotype_data) = &mut virus_properties.phenotype_data else {
+    return Ok(());
+  };
+
+  for phenotype in phenotype_data {
+    if let Some(coefficients_file) = &phenotype.coefficients_file {
+      let is_csv = Path::new(coefficients_file)
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("csv"));
+      let delimiter = if is_csv { b';' } else { b'\t' };
+      let entries = read_dataset_file(coefficients_file)
+        .and_then(|data| parse_phenotype_coefficients(&data, delimiter))
+        .wrap_err_with(|| {
+          format!(
+            "When reading coefficients of phenotype '{}' from '{coefficients_file}'",
+            phenotype.name
+          )
+        })?;
+      phenotype.data.extend(entries);
+    }
+  }
+
+  Ok(())
 }
 
 pub fn get_phenotype_attr_descs(virus_properties: &VirusProperties) -> Vec<PhenotypeAttrDesc> {
@@ -46,3 +150,24 @@ pub fn get_phenotype_attr_keys(virus_properties: &VirusProperties) -> Vec<String
     .map(|ph| ph.name.clone())
     .collect_vec()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn parses_phenotype_coefficients() -> Result<(), Report> {
+    let data =
+      "entry\tweight\tsite\tmutant\tcoefficient\nA\t0.5\t484\tK\t1.5\nA\t0.5\t484\t\t0.2\nB\t2\t501\tY\t-0.3\n";
+    let entries = parse_phenotype_coefficients(data, b'\t')?;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, "A");
+    assert!((entries[0].get_coeff(AaRefPosition::from(483_isize), Aa::K) - 1.5).abs() < 1e-9);
+    assert!((entries[0].get_coeff(AaRefPosition::from(483_isize), Aa::Q) - 0.2).abs() < 1e-9);
+    assert!((entries[1].weight - 2.0).abs() < 1e-9);
+    assert!((entries[1].get_coeff(AaRefPosition::from(500_isize), Aa::Y) + 0.3).abs() < 1e-9);
+    Ok(())
+  }
+}
@@ -0,0 +1,164 @@
+use crate::analyze::letter_ranges::NucRange;
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::io::file::create_file_or_stdout;
+use crate::io::fs::has_extension;
+use eyre::{Report, WrapErr};
+use std::io::Write;
+use std::path::Path;
+
+/// Accumulates, for every reference position, the number of sequences having a call (a nucleotide or a deletion) at
+/// this position, one sequence at a time. Positions outside of the alignment range and positions with missing data
+/// (`N`) are not counted.
+///
+/// This is similar to the sequencing depth, but across sequences of a batch rather than reads of a sample: positions
+/// with low support reveal regions which are systematically dropped out, for example due to failing amplicons.
+#[derive(Clone, Debug)]
+pub struct PositionSupportCounter {
+  /// Differences of support between adjacent positions. Converted into support by a prefix sum.
+  diffs: Vec<isize>,
+  num_seqs: usize,
+}
+
+impl PositionSupportCounter {
+  pub fn new(ref_seq_len: usize) -> Self {
+    Self {
+      diffs: vec![0; ref_seq_len + 1],
+      num_seqs: 0,
+    }
+  }
+
+  /// Adds calls of an analyzed sequence, given its alignment range and ranges of missing data
+  pub fn add(&mut self, alignment_range: &NucRefGlobalRange, missing: &[NucRange]) {
+    let ref_len = self.diffs.len() - 1;
+    let aln_begin = alignment_range.begin.as_usize().min(ref_len);
+    let aln_end = alignment_range.end.as_usize().min(ref_len);
+
+    self.add_range(aln_begin, aln_end, 1);
+    for missing in missing {
+      let begin = missing.range.begin.as_usize().max(aln_begin);
+      let end = missing.range.end.as_usize().min(aln_end);
+      self.add_range(begin, end, -1);
+    }
+
+    self.num_seqs += 1;
+  }
+
+  fn add_range(&mut self, begin: usize, end: usize, delta: isize) {
+    if begin < end {
+      self.diffs[begin] += delta;
+      self.diffs[end] -= delta;
+    }
+  }
+
+  /// Produces the support track, as a list of intervals of adjacent positions having the same support
+  pub fn finish(self) -> PositionSupport {
+    let mut intervals = Vec::<PositionSupportInterval>::new();
+    let mut support = 0_isize;
+    for (pos, diff) in self.diffs.iter().take(self.diffs.len() - 1).enumerate() {
+      support += diff;
+      let value = support.unsigned_abs();
+      match intervals.last_mut() {
+        Some(last) if last.value == value => last.end = pos + 1,
+        _ => intervals.push(PositionSupportInterval {
+          begin: pos,
+          end: pos + 1,
+          value,
+        }),
+      }
+    }
+
+    PositionSupport {
+      intervals,
+      num_seqs: self.num_seqs,
+    }
+  }
+}
+
+/// Interval of reference positions `[begin, end)` (0-based) supported by the same number of sequences
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionSupportInterval {
+  pub begin: usize,
+  pub end: usize,
+  pub value: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PositionSupport {
+  pub intervals: Vec<PositionSupportInterval>,
+  pub num_seqs: usize,
+}
+
+/// Writes support track. The format is deduced from file extension: wiggle (fixed step) for ".wig", and bedGraph
+/// otherwise. The chromosome column contains the name of the reference sequence.
+pub fn position_support_write(
+  filepath: impl AsRef<Path>,
+  ref_name: &str,
+  support: &PositionSupport,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut writer = create_file_or_stdout(filepath)?;
+
+  let description = format!("Number of sequences with calls, out of {}", support.num_seqs);
+
+  if has_extension(filepath, "wig") {
+    writeln!(
+      writer,
+      r#"track type=wiggle_0 name="support" description="{description}""#
+    )?;
+    writeln!(writer, "fixedStep chrom={ref_name} start=1 step=1")?;
+    for interval in &support.intervals {
+      for _ in interval.begin..interval.end {
+        writeln!(writer, "{}", interval.value)?;
+      }
+    }
+  } else {
+    writeln!(
+      writer,
+      r#"track type=bedGraph name="support" description="{description}""#
+    )?;
+    for PositionSupportInterval { begin, end, value } in &support.intervals {
+      writeln!(writer, "{ref_name}\t{begin}\t{end}\t{value}")?;
+    }
+  }
+
+  writer
+    .flush()
+    .wrap_err_with(|| format!("When writing support track: {filepath:#?}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::Nuc;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn counts_position_support() {
+    let mut counter = PositionSupportCounter::new(10);
+    counter.add(
+      &NucRefGlobalRange::from_usize(0, 10),
+      &[NucRange {
+        range: NucRefGlobalRange::from_usize(3, 5),
+        letter: Nuc::N,
+      }],
+    );
+    counter.add(&NucRefGlobalRange::from_usize(2, 8), &[]);
+
+    let support = counter.finish();
+
+    let interval = |begin, end, value| PositionSupportInterval { begin, end, value };
+    assert_eq!(support.num_seqs, 2);
+    assert_eq!(
+      support.intervals,
+      vec![
+        interval(0, 2, 1),
+        interval(2, 3, 2),
+        interval(3, 5, 1),
+        interval(5, 8, 2),
+        interval(8, 10, 1),
+      ]
+    );
+  }
+}
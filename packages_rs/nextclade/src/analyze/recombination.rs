@@ -0,0 +1,249 @@
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::coord::range::NucRefGlobalRange;
+use crate::tree::tree::{AuspiceGraph, TreeNodeTempData};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Minimum number of informative sites at which each of the parents matches the query and the other parent does not.
+/// Guards against calling recombinants from a few sequencing errors or reversions.
+const MIN_SUPPORTING_SITES: usize = 3;
+
+/// Nucleotides of the clades of the reference tree at the clade-informative positions, i.e. at the positions where
+/// the founder nodes of at least two clades differ
+#[derive(Clone, Debug, Default)]
+pub struct CladeProfiles {
+  positions: Vec<NucRefGlobalPosition>,
+  clades: Vec<String>,
+  /// Nucleotides of each clade (outer) at each of the informative positions (inner)
+  nucs: Vec<Vec<Nuc>>,
+}
+
+impl CladeProfiles {
+  /// Collects the sequences of the founder nodes of all clades of the reference tree, i.e. of the nodes closest to the
+  /// root which belong to each clade
+  pub fn from_graph(graph: &AuspiceGraph, ref_seq: &[Nuc]) -> Self {
+    let mut founders = BTreeMap::<String, &TreeNodeTempData>::new();
+    for node in graph.iter_nodes() {
+      let clade = node.payload().clade();
+      if clade.is_empty() || founders.contains_key(&clade) {
+        continue;
+      }
+      let is_founder = graph
+        .parent_of_by_key(node.key())
+        .map_or(true, |parent| parent.payload().clade() != clade);
+      if is_founder {
+        founders.insert(clade, &node.payload().tmp);
+      }
+    }
+
+    let candidate_positions: BTreeSet<NucRefGlobalPosition> = founders
+      .values()
+      .flat_map(|founder| founder.mutations.keys().copied())
+      .collect();
+
+    let positions = candidate_positions
+      .into_iter()
+      .filter(|&pos| {
+        founders
+          .values()
+          .map(|founder| founder.nuc_at(pos, ref_seq))
+          .unique()
+          .count()
+          > 1
+      })
+      .collect_vec();
+
+    let (clades, nucs) = founders
+      .into_iter()
+      .map(|(clade, founder)| {
+        let nucs = positions.iter().map(|&pos| founder.nuc_at(pos, ref_seq)).collect_vec();
+        (clade, nucs)
+      })
+      .unzip();
+
+    Self {
+      positions,
+      clades,
+      nucs,
+    }
+  }
+
+  /// Clade-informative positions, sorted
+  pub fn positions(&self) -> &[NucRefGlobalPosition] {
+    &self.positions
+  }
+}
+
+/// One of the two putative parents of a recombinant
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecombinantParent {
+  pub clade: String,
+  /// Part of the query sequence attributed to this parent
+  pub range: NucRefGlobalRange,
+  /// Number of informative sites in this part where the query matches this parent, but not the other one
+  pub num_supporting_sites: usize,
+}
+
+/// Putative recombinant: the query is explained considerably better by two clades, one on each side of a breakpoint,
+/// than by any single clade
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Recombinant {
+  /// Parents in the order along the genome
+  pub parents: Vec<RecombinantParent>,
+  /// The breakpoint lies within this range: from the last site supporting the first parent to the first site
+  /// supporting the second parent
+  pub breakpoint: NucRefGlobalRange,
+  /// Number of informative sites which are matched by the two parents, but not by the best matching single clade
+  pub num_sites_gained: usize,
+}
+
+/// Scans the clade-informative sites of the (aligned, with insertions stripped) query sequence along the genome, and
+/// checks whether the query is a mosaic of two clades with a single breakpoint.
+///
+/// For every possible breakpoint, the clade matching the most informative sites on the left is combined with the
+/// clade matching the most informative sites on the right. The query is flagged as a putative recombinant if the best
+/// such combination explains more sites than the best single clade, with at least a few sites supporting each parent.
+/// Only the aligned positions where the query has one of `A`, `C`, `G`, `T` are considered.
+pub fn find_recombination(
+  profiles: &CladeProfiles,
+  qry_seq: &[Nuc],
+  alignment_range: &NucRefGlobalRange,
+) -> Option<Recombinant> {
+  let num_clades = profiles.clades.len();
+  if num_clades < 2 {
+    return None;
+  }
+
+  // Informative sites covered by the query: index into the profile and the query nucleotide
+  let sites = profiles
+    .positions
+    .iter()
+    .enumerate()
+    .filter_map(|(i, &pos)| {
+      let qry_nuc = qry_seq[pos.as_usize()];
+      (alignment_range.contains(pos) && qry_nuc.is_acgt()).then_some((i, qry_nuc))
+    })
+    .collect_vec();
+
+  let matches = |clade: usize, (i, qry_nuc): (usize, Nuc)| profiles.nucs[clade][i] == qry_nuc;
+
+  let totals = (0..num_clades)
+    .map(|clade| sites.iter().filter(|&&site| matches(clade, site)).count())
+    .collect_vec();
+  let best_single = totals.iter().copied().max().unwrap_or(0);
+
+  // Number of matching sites of each clade to the left of the current breakpoint
+  let mut left = vec![0_usize; num_clades];
+  let mut best: Option<(usize, usize, usize, usize)> = None; // (score, breakpoint index, left clade, right clade)
+  for k in 1..sites.len() {
+    for (clade, count) in left.iter_mut().enumerate() {
+      if matches(clade, sites[k - 1]) {
+        *count += 1;
+      }
+    }
+
+    let (left_clade, left_score) = argmax((0..num_clades).map(|clade| left[clade]));
+    let (right_clade, right_score) = argmax((0..num_clades).map(|clade| totals[clade] - left[clade]));
+    let score = left_score + right_score;
+    if left_clade != right_clade && score > best_single && best.map_or(true, |(best_score, ..)| score > best_score) {
+      best = Some((score, k, left_clade, right_clade));
+    }
+  }
+
+  let (score, k, left_clade, right_clade) = best?;
+
+  // Sites discriminating between the two parents, in favor of the left parent on the left and of the right parent on
+  // the right
+  let left_supporting = sites[..k]
+    .iter()
+    .filter(|&&site| matches(left_clade, site) && !matches(right_clade, site))
+    .map(|&(i, _)| profiles.positions[i])
+    .collect_vec();
+  let right_supporting = sites[k..]
+    .iter()
+    .filter(|&&site| matches(right_clade, site) && !matches(left_clade, site))
+    .map(|&(i, _)| profiles.positions[i])
+    .collect_vec();
+
+  if left_supporting.len() < MIN_SUPPORTING_SITES || right_supporting.len() < MIN_SUPPORTING_SITES {
+    return None;
+  }
+
+  let last_left = *left_supporting.last()?;
+  let first_right = *right_supporting.first()?;
+
+  Some(Recombinant {
+    parents: vec![
+      RecombinantParent {
+        clade: profiles.clades[left_clade].clone(),
+        range: NucRefGlobalRange::new(alignment_range.begin, last_left + 1),
+        num_supporting_sites: left_supporting.len(),
+      },
+      RecombinantParent {
+        clade: profiles.clades[right_clade].clone(),
+        range: NucRefGlobalRange::new(first_right, alignment_range.end),
+        num_supporting_sites: right_supporting.len(),
+      },
+    ],
+    breakpoint: NucRefGlobalRange::new(last_left, first_right + 1),
+    num_sites_gained: score - best_single,
+  })
+}
+
+/// Finds index and value of the first maximum
+fn argmax(values: impl Iterator<Item = usize>) -> (usize, usize) {
+  values.enumerate().fold(
+    (0, 0),
+    |best, (i, value)| if value > best.1 { (i, value) } else { best },
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::o;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn profiles() -> Result<CladeProfiles, Report> {
+    Ok(CladeProfiles {
+      positions: (0..10_usize).map(NucRefGlobalPosition::from).collect_vec(),
+      clades: vec![o!("A"), o!("B")],
+      nucs: vec![to_nuc_seq("AAAAAAAAAA")?, to_nuc_seq("CCCCCCCCCC")?],
+    })
+  }
+
+  #[rstest]
+  fn finds_recombinant_with_breakpoint() -> Result<(), Report> {
+    let qry_seq = to_nuc_seq("AAAACCCCCC")?;
+    let recombinant = find_recombination(&profiles()?, &qry_seq, &NucRefGlobalRange::from_usize(0, 10));
+    let recombinant = recombinant.expect("Expected a recombinant");
+    assert_eq!(
+      recombinant
+        .parents
+        .iter()
+        .map(|parent| parent.clade.as_str())
+        .collect_vec(),
+      vec!["A", "B"]
+    );
+    assert_eq!(recombinant.breakpoint, NucRefGlobalRange::from_usize(3, 5));
+    assert_eq!(recombinant.num_sites_gained, 4);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case::single_clade("CCCCCCCCCC")]
+  #[case::few_supporting_sites("AACCCCCCCC")]
+  fn does_not_flag_non_recombinant(#[case] qry: &str) -> Result<(), Report> {
+    let qry_seq = to_nuc_seq(qry)?;
+    let recombinant = find_recombination(&profiles()?, &qry_seq, &NucRefGlobalRange::from_usize(0, 10));
+    assert_eq!(recombinant, None);
+    Ok(())
+  }
+}
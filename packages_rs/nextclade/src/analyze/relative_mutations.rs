@@ -0,0 +1,85 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::nuc_del::NucDel;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::graph::node::GraphNodeKey;
+use crate::make_error;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload};
+use eyre::Report;
+use serde::{Deserialize, Serialize};
+
+/// Value of `--mutations-relative-to` which requests mutations relative to the founder node of the clade assigned to
+/// each query sequence
+pub const RELATIVE_TO_CLADE_FOUNDER: &str = "clade-founder";
+
+/// Nucleotide mutations of the query sequence relative to a node of the reference tree, rather than relative to the
+/// reference sequence
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelativeNucMutations {
+  /// Name of the node of the reference tree the mutations are relative to
+  pub node_name: String,
+  pub substitutions: Vec<NucSub>,
+  pub deletions: Vec<NucDel>,
+  pub total_substitutions: usize,
+  pub total_deletions: usize,
+}
+
+/// Finds the node of the reference tree, named by the user in `--mutations-relative-to`. Returns `None` if mutations
+/// relative to the clade founder are requested instead, because the founder depends on the query sequence.
+pub fn find_relative_mutations_node(graph: &AuspiceGraph, relative_to: &str) -> Result<Option<GraphNodeKey>, Report> {
+  if relative_to == RELATIVE_TO_CLADE_FOUNDER {
+    return Ok(None);
+  }
+
+  match graph.iter_nodes().find(|node| node.payload().name == relative_to) {
+    Some(node) => Ok(Some(node.key())),
+    None => make_error!(
+      "Node '{relative_to}' requested using `--mutations-relative-to` is not found in the reference tree. Use the name of one of the nodes of the reference tree or '{RELATIVE_TO_CLADE_FOUNDER}'"
+    ),
+  }
+}
+
+/// Finds nucleotide substitutions and deletions in the (aligned, with insertions stripped) query sequence relative to
+/// the sequence of the given node of the reference tree.
+///
+/// Only the aligned part of the query is considered. Positions where the query has an ambiguous nucleotide are skipped,
+/// as well as the positions which are deleted in the node, but present in the query.
+pub fn find_relative_nuc_mutations(
+  node: &AuspiceGraphNodePayload,
+  qry_seq: &[Nuc],
+  alignment_range: &NucRefGlobalRange,
+  ref_seq: &[Nuc],
+) -> RelativeNucMutations {
+  let mut substitutions = vec![];
+  let mut deletions = vec![];
+
+  for pos in alignment_range.iter() {
+    let node_nuc = node.tmp.nuc_at(pos, ref_seq);
+
+    let qry_nuc = qry_seq[pos.as_usize()];
+    if qry_nuc == node_nuc || !(qry_nuc.is_acgt() || qry_nuc.is_gap()) || node_nuc.is_gap() {
+      continue;
+    }
+
+    if qry_nuc.is_gap() {
+      deletions.push(NucDel { pos, ref_nuc: node_nuc });
+    } else {
+      substitutions.push(NucSub {
+        pos,
+        ref_nuc: node_nuc,
+        qry_nuc,
+      });
+    }
+  }
+
+  RelativeNucMutations {
+    node_name: node.name.clone(),
+    total_substitutions: substitutions.len(),
+    total_deletions: deletions.len(),
+    substitutions,
+    deletions,
+  }
+}
@@ -0,0 +1,192 @@
+use crate::analyze::nuc_del::NucDelRange;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::coord::range::{intersect_or_none, AaRefRange, NucRefGlobalRange};
+use crate::gene::cds::Cds;
+use crate::gene::gene::GeneStrand;
+use crate::gene::gene_map::GeneMap;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum StructuralEventKind {
+  /// Deletion which is long or which spans more than one CDS
+  LargeDeletion,
+  /// Deletion which joins the beginning of one CDS with the end of another CDS in the same reading frame
+  PossibleFusion,
+}
+
+impl Display for StructuralEventKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StructuralEventKind::LargeDeletion => write!(f, "deletion"),
+      StructuralEventKind::PossibleFusion => write!(f, "fusion"),
+    }
+  }
+}
+
+/// Large-scale rearrangement of the genome, reported separately from the ordinary deletions, because it usually
+/// affects multiple genes at once
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuralEvent {
+  pub kind: StructuralEventKind,
+
+  /// Deleted range of the reference sequence. The breakpoints are the boundaries of this range.
+  pub range: NucRefGlobalRange,
+
+  /// CDSes overlapping the deleted range, in order of genome annotation
+  pub affected_cdses: Vec<String>,
+
+  /// Ranges of codons of the affected CDSes overlapping the deleted range, in the same order as `affected_cdses`
+  #[serde(default)]
+  pub affected_codons: Vec<AaRefRange>,
+
+  /// For a possible fusion: the CDS providing the beginning of the fused CDS, followed by the CDS providing its end
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub fused_cdses: Vec<String>,
+}
+
+/// Finds deletions which are likely structural events rather than ordinary deletions: deletions longer than
+/// `min_length`, deletions spanning more than one CDS and deletions joining two CDSes in-frame.
+pub fn find_structural_events(
+  deletions: &[NucDelRange],
+  gene_map: &GeneMap,
+  min_length: usize,
+) -> Vec<StructuralEvent> {
+  deletions
+    .iter()
+    .filter_map(|deletion| {
+      let range = deletion.range();
+
+      let (affected_cdses, affected_codons): (Vec<_>, Vec<_>) = gene_map
+        .iter_cdses()
+        .filter_map(|cds| Some((cds.name.clone(), affected_codon_range(cds, range)?)))
+        .unzip();
+
+      if let Some((upstream, downstream)) = find_fusion(range, gene_map) {
+        return Some(StructuralEvent {
+          kind: StructuralEventKind::PossibleFusion,
+          range: range.clone(),
+          affected_cdses,
+          affected_codons,
+          fused_cdses: vec![upstream.name.clone(), downstream.name.clone()],
+        });
+      }
+
+      (deletion.len() >= min_length || affected_cdses.len() > 1).then(|| StructuralEvent {
+        kind: StructuralEventKind::LargeDeletion,
+        range: range.clone(),
+        affected_cdses,
+        affected_codons,
+        fused_cdses: vec![],
+      })
+    })
+    .collect()
+}
+
+/// Checks whether the nucleotides flanking the deletion belong to two different CDSes on the same strand, such that
+/// reading continues from one into the other without changing the frame. Returns the CDSes in the order of reading.
+fn find_fusion<'a>(range: &NucRefGlobalRange, gene_map: &'a GeneMap) -> Option<(&'a Cds, &'a Cds)> {
+  let left = NucRefGlobalPosition::from(range.begin.as_usize().checked_sub(1)?);
+  let right = range.end;
+
+  gene_map.iter_cdses().find_map(|left_cds| {
+    let left_local = cds_local_pos(left_cds, left)?;
+
+    gene_map.iter_cdses().find_map(|right_cds| {
+      if right_cds.name == left_cds.name {
+        return None;
+      }
+      let right_local = cds_local_pos(right_cds, right)?;
+
+      let strands = (cds_strand(left_cds), cds_strand(right_cds));
+      let (upstream, upstream_local, downstream, downstream_local) = match strands {
+        (Some(GeneStrand::Forward), Some(GeneStrand::Forward)) => (left_cds, left_local, right_cds, right_local),
+        (Some(GeneStrand::Reverse), Some(GeneStrand::Reverse)) => (right_cds, right_local, left_cds, left_local),
+        _ => return None,
+      };
+
+      // The last retained nucleotide of the upstream CDS and the first retained nucleotide of the downstream CDS
+      // should be adjacent in the reading frame
+      ((upstream_local + 1) % 3 == downstream_local % 3).then_some((upstream, downstream))
+    })
+  })
+}
+
+/// Range of codons of the CDS overlapping the deleted range, if any
+fn affected_codon_range(cds: &Cds, range: &NucRefGlobalRange) -> Option<AaRefRange> {
+  let (begin, end) = cds
+    .segments
+    .iter()
+    .filter_map(|segment| intersect_or_none(&segment.range, range))
+    .flat_map(|overlap| [overlap.begin, overlap.end - 1])
+    .filter_map(|pos| cds_local_pos(cds, pos))
+    .minmax()
+    .into_option()?;
+  Some(AaRefRange::from_usize(begin / 3, end / 3 + 1))
+}
+
+fn cds_strand(cds: &Cds) -> Option<GeneStrand> {
+  cds.segments.first().map(|segment| segment.strand)
+}
+
+/// Position in the coordinates of the CDS (counting from the start of the CDS), if the CDS contains the position
+fn cds_local_pos(cds: &Cds, pos: NucRefGlobalPosition) -> Option<usize> {
+  cds
+    .segments
+    .iter()
+    .find(|segment| segment.range.contains(pos))
+    .map(|segment| {
+      let pos_in_segment = match segment.strand {
+        GeneStrand::Forward => pos.as_usize() - segment.range.begin.as_usize(),
+        GeneStrand::Reverse => segment.range.end.as_usize() - 1 - pos.as_usize(),
+      };
+      segment.range_local.begin.as_usize() + pos_in_segment
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  const GFF: &str = "##gff-version 3
+##sequence-region seq 1 100
+seq\t.\tgene\t1\t30\t.\t+\t.\tName=g1;ID=gene-g1
+seq\t.\tCDS\t1\t30\t.\t+\t.\tName=g1;Parent=gene-g1;ID=cds-g1
+seq\t.\tgene\t41\t70\t.\t+\t.\tName=g2;ID=gene-g2
+seq\t.\tCDS\t41\t70\t.\t+\t.\tName=g2;Parent=gene-g2;ID=cds-g2
+";
+
+  #[rstest]
+  #[case::fusion(12, 49, StructuralEventKind::PossibleFusion, vec!["g1", "g2"], 3)]
+  #[case::out_of_frame(12, 50, StructuralEventKind::LargeDeletion, vec![], 4)]
+  fn finds_structural_events(
+    #[case] begin: usize,
+    #[case] end: usize,
+    #[case] kind: StructuralEventKind,
+    #[case] fused_cdses: Vec<&str>,
+    #[case] g2_codons_end: usize,
+  ) -> Result<(), Report> {
+    let gene_map = GeneMap::from_str(GFF)?;
+    let deletions = [NucDelRange::from_usize(begin, end), NucDelRange::from_usize(80, 83)];
+
+    let events = find_structural_events(&deletions, &gene_map, 1000);
+
+    assert_eq!(
+      events,
+      vec![StructuralEvent {
+        kind,
+        range: NucRefGlobalRange::from_usize(begin, end),
+        affected_cdses: vec!["g1".to_owned(), "g2".to_owned()],
+        affected_codons: vec![AaRefRange::from_usize(4, 10), AaRefRange::from_usize(0, g2_codons_end)],
+        fused_cdses: fused_cdses.into_iter().map(ToOwned::to_owned).collect(),
+      }]
+    );
+    Ok(())
+  }
+}
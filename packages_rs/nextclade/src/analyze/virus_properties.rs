@@ -3,7 +3,7 @@ use crate::alphabet::aa::Aa;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::pcr_primer_changes::PcrPrimer;
 use crate::coord::position::AaRefPosition;
-use crate::coord::range::AaRefRange;
+use crate::coord::range::{AaRefRange, NucRefGlobalRange};
 use crate::gene::genotype::Genotype;
 use crate::io::dataset::{DatasetCompatibility, DatasetFiles, DatasetMeta, DatasetVersion};
 use crate::io::fs::read_file_to_string;
@@ -46,12 +46,26 @@ pub struct VirusProperties {
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub cds_order_preference: Vec<String>,
 
+  /// NCBI translation table (genetic code) to use for all CDSes. Overrides `transl_table` attributes of the genome
+  /// annotation.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transl_table: Option<u8>,
+
+  /// NCBI translation tables (genetic codes) for individual CDSes, by CDS name. Takes precedence over `transl_table`.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub cds_transl_tables: BTreeMap<String, u8>,
+
   #[serde(default)]
   pub mut_labels: LabelledMutationsConfig,
 
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub primers: Vec<PcrPrimer>,
 
+  /// Regions of the reference sequence masked in the query sequences before mutation calling and phylogenetic
+  /// placement, for example problematic sites. Sorted and non-overlapping.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub masked_ranges: Vec<NucRefGlobalRange>,
+
   pub qc: Option<QcConfig>,
 
   pub general_params: Option<NextcladeGeneralParamsOptional>,
@@ -145,9 +159,27 @@ pub struct PhenotypeData {
   pub aa_range: AaRefRange,
   #[serde(default)]
   pub ignore: PhenotypeDataIgnore,
+  /// How coefficients of the substitutions are combined into the phenotype value
+  #[serde(default)]
+  pub aggregation: PhenotypeAggregation,
+  /// Path to a CSV or TSV file with coefficients, relative to the dataset. Its entries are added to `data`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub coefficients_file: Option<String>,
+  #[serde(default)]
   pub data: Vec<PhenotypeDataEntry>,
 }
 
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum PhenotypeAggregation {
+  /// Escape from a mix of antibodies: `-ln(sum(weight * exp(-sum(coefficients))))` over the data entries
+  #[default]
+  Escape,
+
+  /// Additive effects (e.g. receptor binding): `sum(weight * sum(coefficients))` over the data entries
+  Sum,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PhenotypeAttrDesc {
@@ -165,10 +197,26 @@ pub struct AaMotifsDesc {
   pub description: String,
   pub motifs: Vec<String>,
 
+  /// Syntax of the patterns in `motifs`
+  #[serde(default)]
+  pub syntax: AaMotifSyntax,
+
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub include_genes: Vec<CountAaMotifsGeneDesc>,
 }
 
+/// Syntax of aminoacid motif patterns
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum AaMotifSyntax {
+  /// Regular expression, for example `N[^P][ST]`
+  #[default]
+  Regex,
+
+  /// PROSITE pattern, for example `N-{P}-[ST]-{P}`. See: https://prosite.expasy.org/scanprosite/scanprosite_doc.html
+  Prosite,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct CountAaMotifsGeneDesc {
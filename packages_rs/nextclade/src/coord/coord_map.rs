@@ -14,19 +14,16 @@ use serde::{Deserialize, Serialize};
 /// lookup the position of the corresponding letter in the reference sequence.
 pub fn make_aln_to_ref_map<L: SpaceMarker>(ref_seq: &[Nuc]) -> Vec<Position<ReferenceCoords, L, NucSpace>> {
   let mut rev_coord_map = Vec::<Position<ReferenceCoords, L, NucSpace>>::with_capacity(ref_seq.len());
-  let mut ref_pos = 0_isize;
+  let mut ref_pos = Position::<ReferenceCoords, L, NucSpace>::new(0);
 
   for nuc in ref_seq {
     if nuc.is_gap() {
-      if rev_coord_map.is_empty() {
-        rev_coord_map.push(0_isize.into());
-      } else {
-        let prev = *(rev_coord_map.last().unwrap());
-        rev_coord_map.push(prev);
-      }
+      // Gaps in the reference map to the preceding reference position (or to the first, for leading gaps)
+      let prev = rev_coord_map.last().copied().unwrap_or(ref_pos);
+      rev_coord_map.push(prev);
     } else {
-      rev_coord_map.push(ref_pos.into());
-      ref_pos += 1;
+      rev_coord_map.push(ref_pos);
+      ref_pos = (ref_pos.as_isize() + 1).into();
     }
   }
 
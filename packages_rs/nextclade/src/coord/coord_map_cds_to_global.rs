@@ -59,20 +59,20 @@ pub fn cds_codon_pos_to_ref_range(cds: &Cds, codon: AaRefPosition) -> Vec<(NucRe
 }
 
 pub fn global_ref_pos_to_local(cds: &Cds, pos: NucRefGlobalPosition) -> Vec<NucRefLocalPosition> {
-  let mut cds_segment_start = 0_isize;
-  let mut cds_positions = vec![];
-  for segment in &cds.segments {
-    if segment.range.contains(pos) {
-      let cds_position = NucRefLocalPosition::new(if segment.strand == GeneStrand::Forward {
-        cds_segment_start + pos.as_isize() - segment.range.begin.as_isize()
-      } else {
-        cds_segment_start + (segment.range.end.as_isize() - 1 - pos.as_isize())
-      });
-      cds_positions.push(cds_position);
-    }
-    cds_segment_start += segment.len() as isize;
-  }
-  cds_positions
+  // NOTE: segments of a CDS which wraps around the origin of a circular genome are already split into linear parts
+  // (see `WrappingPart`), so the same global position can map to multiple local positions.
+  cds
+    .segments
+    .iter()
+    .filter(|segment| segment.range.contains(pos))
+    .map(|segment| {
+      let pos_in_segment = match segment.strand {
+        GeneStrand::Forward => pos.as_isize() - segment.range.begin.as_isize(),
+        GeneStrand::Reverse => segment.range.end.as_isize() - 1 - pos.as_isize(),
+      };
+      segment.range_local.begin + pos_in_segment
+    })
+    .collect_vec()
 }
 
 #[cfg(test)]
@@ -80,6 +80,7 @@ mod coord_map_tests {
   use super::*;
   use crate::coord::position::Position;
   use crate::coord::range::Range;
+  use crate::gene::cds_partial::CdsPartial;
   use crate::gene::cds_segment::{CdsSegment, WrappingPart};
   use crate::gene::frame::Frame;
   use crate::gene::gene::GeneStrand::{Forward, Reverse};
@@ -87,6 +88,7 @@ mod coord_map_tests {
   use maplit::hashmap;
   use pretty_assertions::assert_eq;
   use rstest::rstest;
+  use std::sync::OnceLock;
 
   fn create_fake_cds(segment_ranges: &[(isize, isize, GeneStrand)]) -> Cds {
     Cds {
@@ -129,6 +131,11 @@ mod coord_map_tests {
       attributes: hashmap! {},
       compat_is_gene: false,
       color: None,
+      transl_table: None,
+      ribosomal_slippage: None,
+      partial: CdsPartial::default(),
+      transl_except: vec![],
+      name_interned: OnceLock::new(),
     }
   }
 
@@ -159,6 +166,27 @@ mod coord_map_tests {
     assert_eq!(cds_nuc_pos_to_ref(&cds, Position::new(7)), 19);
   }
 
+  #[rstest]
+  fn converts_cds_nuc_pos_to_ref_with_wrapping_segments() {
+    // CDS wraps around the origin of a circular genome of length 100
+    let cds = create_fake_cds(&[(90, 100, Forward), (0, 20, Forward)]);
+    assert_eq!(cds_nuc_pos_to_ref(&cds, Position::new(9)), 99);
+    assert_eq!(cds_nuc_pos_to_ref(&cds, Position::new(10)), 0);
+    assert_eq!(cds_nuc_pos_to_ref(&cds, Position::new(29)), 19);
+  }
+
+  #[rstest]
+  fn converts_cds_range_to_ref_ranges_with_wrapping_segments() {
+    let cds = create_fake_cds(&[(90, 100, Forward), (0, 20, Forward)]);
+    assert_eq!(
+      cds_range_to_ref_ranges(&cds, &NucRefLocalRange::from_isize(8, 14)),
+      vec![
+        (NucRefGlobalRange::from_isize(98, 100), Forward),
+        (NucRefGlobalRange::from_isize(0, 4), Forward),
+      ]
+    );
+  }
+
   #[rstest]
   fn converts_global_ref_pos_to_local_with_one_segment() {
     let cds = create_fake_cds(&[(4, 37, Forward)]);
@@ -198,4 +226,12 @@ mod coord_map_tests {
     assert_eq!(global_ref_pos_to_local(&cds, Position::new(45)), vec![5]);
     assert_eq!(global_ref_pos_to_local(&cds, Position::new(50)), vec![0]);
   }
+
+  #[rstest]
+  fn converts_global_ref_pos_to_local_with_wrapping_segments() {
+    let cds = create_fake_cds(&[(90, 100, Forward), (0, 20, Forward)]);
+    assert_eq!(global_ref_pos_to_local(&cds, Position::new(50)), EMPTY);
+    assert_eq!(global_ref_pos_to_local(&cds, Position::new(95)), vec![5]);
+    assert_eq!(global_ref_pos_to_local(&cds, Position::new(3)), vec![13]);
+  }
 }
@@ -25,6 +25,12 @@ impl CoordMapGlobal {
     }
   }
 
+  /// Length of the reference sequence (without gaps)
+  #[inline]
+  pub fn ref_len(&self) -> usize {
+    self.ref_to_aln_table.len()
+  }
+
   #[inline]
   pub fn aln_to_ref_position(&self, aln: NucAlnGlobalPosition) -> NucRefGlobalPosition {
     self.aln_to_ref_table[aln.as_usize()]
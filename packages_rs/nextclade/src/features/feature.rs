@@ -21,6 +21,7 @@ pub struct Feature {
   pub range: NucRefGlobalRange,
   pub landmark: Option<Landmark>,
   pub strand: GeneStrand,
+  pub phase: Option<u8>,
   pub parent_ids: Vec<String>,
   pub seqid: String, // Column 0 in the GFF file
   pub exceptions: Vec<String>,
@@ -38,6 +39,7 @@ impl Feature {
       name,
       range,
       strand,
+      phase,
       exceptions,
       notes,
       is_circular,
@@ -68,6 +70,7 @@ impl Feature {
       range,
       landmark: None,
       strand,
+      phase,
       parent_ids,
       seqid,
       exceptions,
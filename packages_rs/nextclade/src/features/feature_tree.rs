@@ -1,9 +1,12 @@
+use crate::coord::position::PositionLike;
 use crate::coord::range::NucRefGlobalRange;
 use crate::features::feature::{Feature, Landmark};
 use crate::features::feature_group::FeatureGroup;
 use crate::features::feature_tree_format::format_sequence_region_features;
 use crate::features::sequence_region::SequenceRegion;
+use crate::io::fasta::{read_many_fasta_str, FastaRecord};
 use crate::io::file::open_file_or_stdin;
+use crate::io::gff3::split_gff3_fasta_section;
 use crate::make_error;
 use crate::utils::error::to_eyre_error;
 use bio::io::gff::{GffType, Reader as GffReader, Record as GffRecord};
@@ -31,7 +34,13 @@ impl FeatureTree {
   }
 
   pub fn from_gff3_str(content: impl AsRef<str>) -> Result<Self, Report> {
-    let seq_regions = read_gff3_feature_tree_str(content.as_ref())?;
+    let (content, fasta) = split_gff3_fasta_section(content.as_ref());
+    let seq_regions = read_gff3_feature_tree_str(content)?;
+    if let Some(fasta) = fasta {
+      let records = read_many_fasta_str(fasta).wrap_err("When reading sequences embedded into GFF3 file")?;
+      validate_embedded_sequences(&seq_regions, &records)
+        .wrap_err("When checking sequences embedded into GFF3 file against the genome annotation")?;
+    }
     Ok(Self { seq_regions })
   }
 
@@ -136,6 +145,62 @@ fn read_gff3_feature_tree_str(content: impl AsRef<str>) -> Result<Vec<SequenceRe
   }
 }
 
+/// Checks that sequences embedded into the '##FASTA' section of the GFF3 file are consistent with the annotation: each
+/// sequence region should have a sequence with the same name, which is long enough to contain the region and all of its
+/// features. A single sequence is matched to a single sequence region regardless of name.
+fn validate_embedded_sequences(seq_regions: &[SequenceRegion], records: &[FastaRecord]) -> Result<(), Report> {
+  if records.is_empty() {
+    return make_error!("The '##FASTA' section is present, but contains no sequences");
+  }
+
+  for seq_region in seq_regions {
+    let record = records
+      .iter()
+      .find(|record| record.seq_name.split_whitespace().next() == Some(seq_region.id.as_str()))
+      .or_else(|| records.first().filter(|_| records.len() == 1 && seq_regions.len() == 1));
+
+    let Some(record) = record else {
+      return make_error!(
+        "Sequence region '{}' has no corresponding sequence in the '##FASTA' section. Sequences found: {}",
+        seq_region.id,
+        records.iter().map(|record| format!("'{}'", record.seq_name)).join(", ")
+      );
+    };
+
+    let seq_len = record.seq.len();
+    let region_end = seq_region.range.end.as_usize();
+    if region_end > seq_len {
+      return make_error!(
+        "Sequence region '{}' ends at position {region_end}, but the corresponding sequence '{}' has length {seq_len}",
+        seq_region.id,
+        record.seq_name
+      );
+    }
+
+    let features_end = find_max_feature_end(&seq_region.children);
+    if features_end > seq_len {
+      return make_error!(
+        "Features of sequence region '{}' extend to position {features_end}, but the corresponding sequence '{}' has length {seq_len}",
+        seq_region.id,
+        record.seq_name
+      );
+    }
+  }
+
+  Ok(())
+}
+
+fn find_max_feature_end(feature_groups: &[FeatureGroup]) -> usize {
+  feature_groups
+    .iter()
+    .map(|group| {
+      let own_end = group.features.iter().map(|feature| feature.range.end.as_usize()).max();
+      own_end.unwrap_or_default().max(find_max_feature_end(&group.children))
+    })
+    .max()
+    .unwrap_or_default()
+}
+
 fn parse_sequence_region_header(line: &str) -> Result<(String, usize, usize), Report> {
   const SEQ_REGION_REGEX: &str = r"^##sequence-region\s+(?P<id>\S+?)\s+(?P<start>\d{1,10})\s+(?P<end>\d{1,10})$";
 
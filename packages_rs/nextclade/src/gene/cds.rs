@@ -1,10 +1,19 @@
-use crate::coord::range::{NucRefLocalRange, Range};
+use crate::coord::position::PositionLike;
+use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange, Range};
 use crate::features::feature::Feature;
 use crate::features::feature_group::FeatureGroup;
+use crate::gene::cds_partial::{trim_partial_cds_segments, CdsPartial};
 use crate::gene::cds_segment::{CdsSegment, WrappingPart};
 use crate::gene::frame::Frame;
+use crate::gene::gene::GeneStrand;
 use crate::gene::phase::Phase;
 use crate::gene::protein::{Protein, ProteinSegment};
+use crate::gene::ribosomal_slippage::{
+  apply_ribosomal_slippage, ribosomal_slippage_from_attributes, RibosomalSlippage,
+};
+use crate::gene::transl_except::{transl_exceptions_from_attributes, TranslException};
+use crate::translate::genetic_code::parse_transl_table;
+use crate::utils::interned_str::InternedStr;
 use crate::{make_error, make_internal_error};
 use eyre::{eyre, Report, WrapErr};
 use itertools::Itertools;
@@ -14,6 +23,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::OnceLock;
 
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -27,6 +37,32 @@ pub struct Cds {
   pub attributes: HashMap<String, Vec<String>>,
   pub compat_is_gene: bool,
   pub color: Option<String>,
+
+  /// NCBI translation table (genetic code) number. Standard genetic code is used if not set.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transl_table: Option<u8>,
+
+  /// Programmed ribosomal frameshift, if declared explicitly. The segments are already split at the slippage site.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub ribosomal_slippage: Option<RibosomalSlippage>,
+
+  /// Which ends of the CDS are missing in the reference sequence, if any
+  #[serde(default, skip_serializing_if = "is_cds_complete")]
+  pub partial: CdsPartial,
+
+  /// Codons translated regardless of the genetic code, for example selenocysteine and pyrrolysine
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub transl_except: Vec<TranslException>,
+
+  /// Name of the CDS, interned on first use (see `Cds::name_interned()`)
+  #[serde(skip)]
+  #[schemars(skip)]
+  pub name_interned: OnceLock<InternedStr>,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // Signature is required by serde
+const fn is_cds_complete(partial: &CdsPartial) -> bool {
+  partial.is_complete()
 }
 
 impl Cds {
@@ -42,16 +78,18 @@ impl Cds {
           let mut begin = 0;
 
           move |feature| {
-            let range_local = Range::from_usize(begin, begin + feature.range.len());
+            let range = trim_range_to_declared_phase(&feature.range, feature.strand, feature.phase, begin)
+              .wrap_err_with(|| eyre!("When processing CDS segment '{}'", feature.name))?;
+            let range_local = Range::from_usize(begin, begin + range.len());
             let phase = Phase::from_begin(range_local.begin)?;
-            let frame = Frame::from_begin(feature.range.begin)?;
+            let frame = Frame::from_begin(range.begin)?;
 
             let segment = CdsSegment {
               index: feature.index,
               id: feature.id.clone(),
               name: feature.name.clone(),
-              range: feature.range.clone(),
-              range_local: Range::from_usize(begin, begin + feature.range.len()),
+              range: range.clone(),
+              range_local,
               landmark: feature.landmark.clone(),
               wrapping_part: WrappingPart::NonWrapping,
               strand: feature.strand,
@@ -64,7 +102,7 @@ impl Cds {
               color: None,
             };
 
-            begin += feature.range.len();
+            begin += range.len();
 
             Ok(segment)
           }
@@ -76,6 +114,32 @@ impl Cds {
       return make_internal_error!("CDS contains no segments")?;
     }
 
+    let ribosomal_slippage = {
+      let exceptions = feature_group
+        .features
+        .iter()
+        .flat_map(|feature| feature.exceptions.clone())
+        .collect_vec();
+      let attributes = merge_attributes(feature_group.features.iter().map(|feature| &feature.attributes));
+      ribosomal_slippage_from_attributes(&exceptions, &attributes)
+        .wrap_err_with(|| eyre!("When reading ribosomal slippage of CDS '{}'", feature_group.name))?
+    };
+
+    let segments = match &ribosomal_slippage {
+      Some(slippage) => apply_ribosomal_slippage(&segments, slippage)
+        .wrap_err_with(|| eyre!("When applying ribosomal slippage to CDS '{}'", feature_group.name))?,
+      None => segments,
+    };
+
+    let partial = {
+      let attributes = merge_attributes(feature_group.features.iter().map(|feature| &feature.attributes));
+      let strand = segments.first().map(|segment| segment.strand).unwrap_or_default();
+      CdsPartial::from_attributes(&attributes, strand)
+    };
+
+    let segments = trim_partial_cds_segments(segments, partial)
+      .wrap_err_with(|| eyre!("When processing partial CDS '{}'", feature_group.name))?;
+
     let segments = split_circular_cds_segments(&segments)?;
 
     let mut proteins = vec![];
@@ -84,18 +148,7 @@ impl Cds {
       .iter()
       .try_for_each(|child_feature_group| find_proteins_recursive(child_feature_group, &mut proteins))?;
 
-    let attributes: HashMap<String, Vec<String>> = {
-      let mut attributes: HashMap<String, Vec<String>> = hashmap! {};
-      for segment in &segments {
-        for (k, vs) in &segment.attributes {
-          attributes.entry(k.clone()).or_default().extend_from_slice(vs);
-        }
-      }
-      attributes
-        .into_iter()
-        .map(|(k, vs)| (k, vs.into_iter().unique().collect_vec()))
-        .collect()
-    };
+    let attributes = merge_attributes(segments.iter().map(|segment| &segment.attributes));
 
     let exceptions = segments
       .iter()
@@ -103,6 +156,12 @@ impl Cds {
       .unique()
       .collect_vec();
 
+    let transl_table = transl_table_from_attributes(&attributes)
+      .wrap_err_with(|| eyre!("When reading translation table of CDS '{}'", feature_group.name))?;
+
+    let transl_except = transl_exceptions_from_attributes(&attributes, &segments)
+      .wrap_err_with(|| eyre!("When reading translation exceptions of CDS '{}'", feature_group.name))?;
+
     Ok(Self {
       id: feature_group.id.clone(),
       name: feature_group.name.clone(),
@@ -113,6 +172,11 @@ impl Cds {
       attributes,
       compat_is_gene: false,
       color: None,
+      transl_table,
+      ribosomal_slippage,
+      partial,
+      transl_except,
+      name_interned: OnceLock::new(),
     })
   }
 
@@ -162,9 +226,27 @@ impl Cds {
       color: None,
     };
 
+    let ribosomal_slippage = ribosomal_slippage_from_attributes(&feature.exceptions, &feature.attributes)
+      .wrap_err_with(|| eyre!("When reading ribosomal slippage of gene '{}'", feature.name))?;
+
     let segments = vec![cds_segment];
+    let segments = match &ribosomal_slippage {
+      Some(slippage) => apply_ribosomal_slippage(&segments, slippage)
+        .wrap_err_with(|| eyre!("When applying ribosomal slippage to gene '{}'", feature.name))?,
+      None => segments,
+    };
+
+    let partial = CdsPartial::from_attributes(&feature.attributes, feature.strand);
+    let segments = trim_partial_cds_segments(segments, partial)
+      .wrap_err_with(|| eyre!("When processing partial gene '{}'", feature.name))?;
     let segments = split_circular_cds_segments(&segments)?;
 
+    let transl_table = transl_table_from_attributes(&feature.attributes)
+      .wrap_err_with(|| eyre!("When reading translation table of gene '{}'", feature.name))?;
+
+    let transl_except = transl_exceptions_from_attributes(&feature.attributes, &segments)
+      .wrap_err_with(|| eyre!("When reading translation exceptions of gene '{}'", feature.name))?;
+
     Ok(Self {
       id: format!("cds-from-gene-{}", feature.id),
       name: feature.name.clone(),
@@ -175,6 +257,11 @@ impl Cds {
       attributes: feature.attributes.clone(),
       compat_is_gene: true,
       color: None,
+      transl_table,
+      ribosomal_slippage,
+      partial,
+      transl_except,
+      name_interned: OnceLock::new(),
     })
   }
 
@@ -182,6 +269,12 @@ impl Cds {
     format!("CDS '{}'", self.name)
   }
 
+  /// Name of the CDS as an interned string, to be shared by all mutations found in this CDS. The name is interned only
+  /// once, and then cloned, such that the global pool of interned strings is not locked for every mutation.
+  pub fn name_interned(&self) -> InternedStr {
+    self.name_interned.get_or_init(|| InternedStr::new(&self.name)).clone()
+  }
+
   #[inline]
   pub fn len(&self) -> usize {
     self.segments.iter().map(CdsSegment::len).sum()
@@ -193,6 +286,79 @@ impl Cds {
   }
 }
 
+/// Merges attributes of multiple features, removing duplicate values
+fn merge_attributes<'a>(
+  attributes_list: impl Iterator<Item = &'a HashMap<String, Vec<String>>>,
+) -> HashMap<String, Vec<String>> {
+  let mut attributes: HashMap<String, Vec<String>> = hashmap! {};
+  for attrs in attributes_list {
+    for (k, vs) in attrs {
+      attributes.entry(k.clone()).or_default().extend_from_slice(vs);
+    }
+  }
+  attributes
+    .into_iter()
+    .map(|(k, vs)| (k, vs.into_iter().unique().collect_vec()))
+    .collect()
+}
+
+/// Reads NCBI translation table number from the `transl_table` attribute, if present
+fn transl_table_from_attributes(attributes: &HashMap<String, Vec<String>>) -> Result<Option<u8>, Report> {
+  let Some(values) = attributes.get("transl_table") else {
+    return Ok(None);
+  };
+
+  let transl_tables: Vec<u8> = values
+    .iter()
+    .map(|value| parse_transl_table(value))
+    .collect::<Result<Vec<u8>, Report>>()?
+    .into_iter()
+    .unique()
+    .collect_vec();
+
+  match transl_tables.as_slice() {
+    [] => Ok(None),
+    [transl_table] => Ok(Some(*transl_table)),
+    _ => make_error!(
+      "Expected a single translation table, but found multiple: {}",
+      transl_tables.iter().join(", ")
+    ),
+  }
+}
+
+/// Trims the 5' end of a CDS segment, such that the reading frame agrees with the phase declared in the annotation.
+///
+/// The phase is the number of nucleotides at the 5' end of the segment which complete the codon started in the
+/// previous segment (or, for the first segment, which do not belong to any codon). Without trimming, the reading frame
+/// is only determined by the total length of the preceding segments. If these two disagree, the nucleotides in
+/// between are excluded from the CDS. If the phase is not declared, the range is returned unchanged.
+pub fn trim_range_to_declared_phase(
+  range: &NucRefGlobalRange,
+  strand: GeneStrand,
+  declared_phase: Option<u8>,
+  local_begin: usize,
+) -> Result<NucRefGlobalRange, Report> {
+  let Some(declared_phase) = declared_phase else {
+    return Ok(range.clone());
+  };
+
+  let implied_phase = (3 - local_begin % 3) % 3;
+  let trim = (declared_phase as usize + 3 - implied_phase) % 3;
+  if trim >= range.len() {
+    return make_error!(
+      "Phase {declared_phase} is declared, but the segment is too short ({} nucleotides) to be trimmed by {trim} nucleotides to match the reading frame",
+      range.len()
+    );
+  }
+
+  let mut range = range.clone();
+  match strand {
+    GeneStrand::Forward => range.begin += trim as isize,
+    GeneStrand::Reverse => range.end -= trim as isize,
+  }
+  Ok(range)
+}
+
 /// Split features, which attached to circular landmark features, to strictly linear segments, without wraparound.
 /// Each feature which goes beyond the landmark end will be split into at least 2 segments:
 ///   - the part from segment start to landmark end, before the wrap around
@@ -209,8 +375,9 @@ fn split_circular_cds_segments(segments: &[CdsSegment]) -> Result<Vec<CdsSegment
 
         let landmark_start = landmark.range.begin;
         let landmark_end = landmark.range.end;
+        let landmark_len = landmark.range.len() as isize;
 
-        let mut segment_local_begin = 0;
+        let mut segment_local_begin = segment.range_local.begin.as_usize();
         let mut segment_end = segment.range.end;
 
         // The first part, which is before the first wraparound
@@ -228,7 +395,9 @@ fn split_circular_cds_segments(segments: &[CdsSegment]) -> Result<Vec<CdsSegment
         // The followup parts, beyond the first wraparound. Note that the segment can wrap more then once.
         let mut part_counter = 1;
         while segment_end > landmark_end {
-          segment_end = segment_end % landmark_end;
+          // Shift the end back by one landmark length. Note that the landmark does not necessarily start at 0, so
+          // simple modulo by landmark end would be incorrect here.
+          segment_end -= landmark_len;
 
           linear_segments.push({
             let mut segment = segment.clone();
@@ -239,6 +408,10 @@ fn split_circular_cds_segments(segments: &[CdsSegment]) -> Result<Vec<CdsSegment
               NucRefLocalRange::from_usize(segment_local_begin, segment_local_begin + segment.len());
             segment_local_begin += segment.len();
 
+            // The part now starts at a different position, so the reading frame and phase need to be recalculated
+            segment.frame = Frame::from_begin(segment.range.begin)?;
+            segment.phase = Phase::from_begin(segment.range_local.begin)?;
+
             segment.wrapping_part = WrappingPart::WrappingCentral(part_counter); // Mark this part as one of the
                                                                                  // follow up parts in the wrapping
                                                                                  // group, beyond the first wraparound.
@@ -0,0 +1,219 @@
+use crate::gene::cds_segment::{cds_segments_recalculate_local, CdsSegment};
+use crate::gene::gene::GeneStrand;
+use crate::make_error;
+use eyre::Report;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Describes which ends of a CDS are missing in the annotated sequence, for example when a gene is truncated at the
+/// edge of a contig. The annotation declares this with `partial=true` attribute, and optionally with `start_range` and
+/// `end_range` attributes which tell which end (in genomic coordinates) extends beyond the sequence.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsPartial {
+  /// 5' end of the CDS (containing the start codon) is missing
+  pub missing_start: bool,
+  /// 3' end of the CDS (containing the stop codon) is missing
+  pub missing_stop: bool,
+}
+
+impl CdsPartial {
+  #[inline]
+  pub const fn is_partial(self) -> bool {
+    self.missing_start || self.missing_stop
+  }
+
+  #[inline]
+  pub const fn is_complete(self) -> bool {
+    !self.is_partial()
+  }
+
+  pub fn from_attributes(attributes: &HashMap<String, Vec<String>>, strand: GeneStrand) -> Self {
+    let is_partial = attributes
+      .get("partial")
+      .is_some_and(|values| values.iter().any(|value| value.trim().eq_ignore_ascii_case("true")));
+
+    if !is_partial {
+      return Self::default();
+    }
+
+    // NOTE: `start_range` and `end_range` refer to the lower and to the upper genomic coordinate respectively
+    let lower_end_missing = attributes.contains_key("start_range");
+    let upper_end_missing = attributes.contains_key("end_range");
+    if !lower_end_missing && !upper_end_missing {
+      // Ends are not specified: assume that both of them can be missing
+      return Self {
+        missing_start: true,
+        missing_stop: true,
+      };
+    }
+
+    match strand {
+      GeneStrand::Forward => Self {
+        missing_start: lower_end_missing,
+        missing_stop: upper_end_missing,
+      },
+      GeneStrand::Reverse => Self {
+        missing_start: upper_end_missing,
+        missing_stop: lower_end_missing,
+      },
+    }
+  }
+}
+
+/// Trims partial CDS, such that its length is divisible by 3 and the reading frame is preserved.
+///
+/// If only the start is missing, the reading frame is determined by the stop codon, so the incomplete codon is removed
+/// from the 5' end. Otherwise the frame is determined by the start codon (or by the declared phase), so the incomplete
+/// codon is removed from the 3' end. Complete CDSes are returned unchanged.
+pub fn trim_partial_cds_segments(segments: Vec<CdsSegment>, partial: CdsPartial) -> Result<Vec<CdsSegment>, Report> {
+  let len: usize = segments.iter().map(CdsSegment::len).sum();
+  let excess = len % 3;
+  if partial.is_complete() || excess == 0 {
+    return Ok(segments);
+  }
+
+  let mut segments = segments;
+  let trim_start = partial.missing_start && !partial.missing_stop;
+  let segment = if trim_start {
+    segments.first_mut()
+  } else {
+    segments.last_mut()
+  };
+
+  let Some(segment) = segment else {
+    return Ok(segments);
+  };
+
+  if segment.len() <= excess {
+    return make_error!(
+      "Partial CDS segment '{}' is too short ({} nucleotides) to be trimmed to full codons",
+      segment.name,
+      segment.len()
+    );
+  }
+
+  let excess = excess as isize;
+  match (segment.strand, trim_start) {
+    (GeneStrand::Forward, true) | (GeneStrand::Reverse, false) => segment.range.begin += excess,
+    (GeneStrand::Forward, false) | (GeneStrand::Reverse, true) => segment.range.end -= excess,
+  }
+
+  cds_segments_recalculate_local(segments)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coord::position::PositionLike;
+  use crate::coord::range::{NucRefGlobalRange, Range};
+  use crate::gene::cds_segment::WrappingPart;
+  use crate::gene::frame::Frame;
+  use crate::gene::phase::Phase;
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use itertools::Itertools;
+  use maplit::hashmap;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn create_segment(begin: usize, end: usize, strand: GeneStrand) -> CdsSegment {
+    CdsSegment {
+      index: 0,
+      id: String::new(),
+      name: o!("S"),
+      range: NucRefGlobalRange::from_usize(begin, end),
+      range_local: Range::from_usize(0, end - begin),
+      landmark: None,
+      wrapping_part: WrappingPart::NonWrapping,
+      strand,
+      frame: Frame::_0,
+      phase: Phase::_0,
+      exceptions: vec![],
+      attributes: hashmap! {},
+      source_record: None,
+      compat_is_gene: false,
+      color: None,
+    }
+  }
+
+  const MISSING_START: CdsPartial = CdsPartial {
+    missing_start: true,
+    missing_stop: false,
+  };
+
+  const MISSING_STOP: CdsPartial = CdsPartial {
+    missing_start: false,
+    missing_stop: true,
+  };
+
+  #[rstest]
+  #[case::forward_start(GeneStrand::Forward, "start_range", (true, false))]
+  #[case::forward_end(GeneStrand::Forward, "end_range", (false, true))]
+  #[case::reverse_start(GeneStrand::Reverse, "start_range", (false, true))]
+  #[case::reverse_end(GeneStrand::Reverse, "end_range", (true, false))]
+  fn reads_partial_cds_ends(#[case] strand: GeneStrand, #[case] range_attr: &str, #[case] expected: (bool, bool)) {
+    let (missing_start, missing_stop) = expected;
+    let attributes = hashmap! {
+      o!("partial") => vec![o!("true")],
+      range_attr.to_owned() => vec![o!(".,1")],
+    };
+    assert_eq!(
+      CdsPartial::from_attributes(&attributes, strand),
+      CdsPartial {
+        missing_start,
+        missing_stop
+      }
+    );
+  }
+
+  #[rstest]
+  #[case::forward_missing_start(GeneStrand::Forward, MISSING_START, vec![(12, 20), (30, 40)])]
+  #[case::forward_missing_stop(GeneStrand::Forward, MISSING_STOP, vec![(10, 20), (30, 38)])]
+  #[case::reverse_missing_start(GeneStrand::Reverse, MISSING_START, vec![(10, 20), (30, 38)])]
+  #[case::reverse_missing_stop(GeneStrand::Reverse, MISSING_STOP, vec![(12, 20), (30, 40)])]
+  #[case::complete(GeneStrand::Forward, CdsPartial::default(), vec![(10, 20), (30, 40)])]
+  fn trims_partial_cds_to_full_codons(
+    #[case] strand: GeneStrand,
+    #[case] partial: CdsPartial,
+    #[case] expected: Vec<(usize, usize)>,
+  ) -> Result<(), Report> {
+    let segments = vec![create_segment(10, 20, strand), create_segment(30, 40, strand)];
+    let segments = if strand == GeneStrand::Reverse {
+      segments.into_iter().rev().collect_vec()
+    } else {
+      segments
+    };
+
+    let actual = trim_partial_cds_segments(segments, partial)?
+      .iter()
+      .map(|segment| (segment.range.begin.as_usize(), segment.range.end.as_usize()))
+      .sorted()
+      .collect_vec();
+
+    assert_eq!(actual, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  fn keeps_local_ranges_contiguous_after_trimming() -> Result<(), Report> {
+    let segments = vec![
+      create_segment(10, 20, GeneStrand::Forward),
+      create_segment(30, 40, GeneStrand::Forward),
+    ];
+    let segments = trim_partial_cds_segments(segments, MISSING_START)?;
+    assert_eq!(segments[0].range_local, Range::from_usize(0, 8));
+    assert_eq!(segments[1].range_local, Range::from_usize(8, 18));
+    Ok(())
+  }
+
+  #[rstest]
+  fn fails_to_trim_too_short_segment() {
+    let segments = vec![create_segment(10, 12, GeneStrand::Forward)];
+    assert_eq!(
+      report_to_string(&trim_partial_cds_segments(segments, MISSING_STOP).unwrap_err()),
+      "Partial CDS segment 'S' is too short (2 nucleotides) to be trimmed to full codons"
+    );
+  }
+}
@@ -1,8 +1,9 @@
-use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange};
+use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange, Range};
 use crate::features::feature::Landmark;
 use crate::gene::frame::Frame;
 use crate::gene::gene::GeneStrand;
 use crate::gene::phase::Phase;
+use eyre::Report;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -14,7 +15,7 @@ use std::collections::HashMap;
 ///   WrappingCentral(1) : |----------|
 ///   WrappingCentral(2) : |----------|
 ///   WrappingEnd(3)     : |---->     |
-#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum WrappingPart {
   NonWrapping,            // This is not a part of a circular, wrapping feature.
@@ -59,3 +60,21 @@ impl CdsSegment {
     self.len() == 0
   }
 }
+
+/// Recalculates local ranges, phases and frames of segments after their global ranges were changed
+pub fn cds_segments_recalculate_local(segments: Vec<CdsSegment>) -> Result<Vec<CdsSegment>, Report> {
+  let mut begin = 0;
+  segments
+    .into_iter()
+    .map(|segment| {
+      let range_local = Range::from_usize(begin, begin + segment.range.len());
+      begin += segment.range.len();
+      Ok(CdsSegment {
+        phase: Phase::from_begin(range_local.begin)?,
+        frame: Frame::from_begin(segment.range.begin)?,
+        range_local,
+        ..segment
+      })
+    })
+    .collect()
+}
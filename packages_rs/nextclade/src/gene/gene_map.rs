@@ -4,6 +4,8 @@ use crate::features::sequence_region::SequenceRegion;
 use crate::gene::cds::Cds;
 use crate::gene::cds_segment::CdsSegment;
 use crate::gene::gene::{find_cdses, Gene};
+use crate::gene::gene_map_repair::gene_map_repair_suggestions;
+use crate::gene::non_coding_feature::{find_non_coding_features, NonCodingFeature};
 use crate::io::file::open_file_or_stdin;
 use crate::io::yaml::yaml_parse;
 use crate::utils::collections::take_exactly_one;
@@ -23,6 +25,11 @@ use std::path::Path;
 #[must_use]
 pub struct GeneMap {
   pub genes: Vec<Gene>,
+
+  /// Features which are not parts of gene models (UTRs, stem loops, regulatory regions etc.). They are not used in
+  /// the analysis, but are retained for display and cross-referencing.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub non_coding_features: Vec<NonCodingFeature>,
 }
 
 impl GeneMap {
@@ -31,7 +38,14 @@ impl GeneMap {
   }
 
   pub fn from_genes(genes: Vec<Gene>) -> Self {
-    Self { genes }
+    Self {
+      genes,
+      non_coding_features: vec![],
+    }
+  }
+
+  pub fn iter_non_coding_features(&self) -> impl Iterator<Item = &NonCodingFeature> + '_ {
+    self.non_coding_features.iter()
   }
 
   pub fn from_feature_tree(feature_tree: &FeatureTree) -> Result<Self, Report> {
@@ -52,18 +66,24 @@ impl GeneMap {
     let gene_map_yaml: Result<GeneMap, Report> = Self::from_yaml_str(content);
     let gene_map_gff: Result<GeneMap, Report> = Self::from_gff3_str(content);
 
-    let gene_map = match (gene_map_yaml, gene_map_gff) {
+    let (gene_map, is_gff) = match (gene_map_yaml, gene_map_gff) {
       (Err(json_err), Err(gff_err)) => {
         return make_error!("Attempted to parse the genome annotation as JSON and as GFF, but both attempts failed:\nJSON error: {}\n\nGFF3 error: {}\n",
           report_to_string(&json_err),
           report_to_string(&gff_err),
         )
       },
-      (Ok(gene_map), _) => gene_map,
-      (_, Ok(gene_map)) => gene_map,
+      (Ok(gene_map), _) => (gene_map, false),
+      (_, Ok(gene_map)) => (gene_map, true),
     };
 
-    gene_map.validate()?;
+    if let Err(report) = gene_map.validate() {
+      return if is_gff {
+        Err(append_repair_suggestions(report, content))
+      } else {
+        Err(report)
+      };
+    }
     Ok(gene_map)
   }
 
@@ -199,7 +219,10 @@ pub fn filter_gene_map(mut gene_map: GeneMap, cdses: &Option<Vec<String>>) -> Ge
       .filter(|gene| !gene.cdses.is_empty())
       .collect_vec();
 
-    return GeneMap::from_genes(genes);
+    return GeneMap {
+      genes,
+      non_coding_features: gene_map.non_coding_features,
+    };
   }
   gene_map
 }
@@ -227,7 +250,12 @@ fn convert_seq_region_to_gene_map(seq_region: &SequenceRegion) -> Result<GeneMap
     );
   }
 
-  Ok(GeneMap::from_genes(genes))
+  let non_coding_features = find_non_coding_features(&seq_region.children);
+
+  Ok(GeneMap {
+    genes,
+    non_coding_features,
+  })
 }
 
 fn find_genes(feature_groups: &[FeatureGroup]) -> Result<Vec<Gene>, Report> {
@@ -260,9 +288,35 @@ fn find_genes_recursive(feature_group: &FeatureGroup, genes: &mut Vec<Gene>) ->
     .try_for_each(|child_feature_group| find_genes_recursive(child_feature_group, genes))
 }
 
+/// Adds concrete suggestions on how to fix the genome annotation to the validation error, if there are any
+fn append_repair_suggestions(report: Report, gff_content: &str) -> Report {
+  let Ok(feature_tree) = FeatureTree::from_gff3_str(gff_content) else {
+    return report;
+  };
+
+  let suggestions = gene_map_repair_suggestions(&feature_tree);
+  if suggestions.is_empty() {
+    return report;
+  }
+
+  let suggestions = suggestions
+    .iter()
+    .map(|suggestion| format!("  - {suggestion}"))
+    .join("\n");
+
+  eyre!(
+    "{}\n\nSuggested changes to the genome annotation:\n{suggestions}\n\n\
+    Use 'nextclade read-annotation --fix-annotation-into=<file>' to write the annotation with these changes applied, \
+    review it and use it instead of the original annotation.",
+    report_to_string(&report)
+  )
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange};
+  use crate::gene::cds_segment::WrappingPart;
   use eyre::Report;
   use pretty_assertions::assert_eq;
   use rstest::rstest;
@@ -337,4 +391,142 @@ MN908947	GenBank	CDS	27894	28259	.	+	.	Name=N;Parent=9
 
     Ok(())
   }
+
+  #[rstest]
+  fn genome_annotation_splits_cds_wrapping_around_origin() -> Result<(), Report> {
+    let gene_map = GeneMap::from_str(
+      r#"##gff-version 3
+##sequence-region HBV 1 3182
+HBV	RefSeq	region	1	3182	.	+	.	ID=HBV;Is_circular=true
+HBV	RefSeq	gene	2307	3401	.	+	.	Name=P;ID=gene-P
+HBV	RefSeq	CDS	2307	3401	.	+	.	Name=P;Parent=gene-P;ID=cds-P
+
+"#,
+    )?;
+
+    let cds = gene_map.get_cds("P")?;
+
+    assert_eq!(
+      cds
+        .segments
+        .iter()
+        .map(|segment| (
+          segment.range.clone(),
+          segment.range_local.clone(),
+          segment.wrapping_part.clone()
+        ))
+        .collect_vec(),
+      vec![
+        (
+          NucRefGlobalRange::from_usize(2306, 3182),
+          NucRefLocalRange::from_usize(0, 876),
+          WrappingPart::WrappingStart
+        ),
+        (
+          NucRefGlobalRange::from_usize(0, 219),
+          NucRefLocalRange::from_usize(876, 1095),
+          WrappingPart::WrappingEnd(1)
+        ),
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[rstest]
+  fn genome_annotation_trims_cds_segments_to_declared_phase() -> Result<(), Report> {
+    let gene_map = GeneMap::from_str(
+      r#"##gff-version 3
+##sequence-region seq 1 1000
+seq	.	gene	10	200	.	+	.	Name=A;ID=gene-A
+seq	.	CDS	10	99	.	+	2	Name=A;Parent=gene-A;ID=cds-A
+seq	.	CDS	100	200	.	+	2	Name=A;Parent=gene-A;ID=cds-A
+seq	.	gene	300	400	.	-	.	Name=B;ID=gene-B
+seq	.	CDS	300	400	.	-	2	Name=B;Parent=gene-B;ID=cds-B
+seq	.	gene	500	598	.	+	.	Name=C;ID=gene-C
+seq	.	CDS	500	598	.	+	.	Name=C;Parent=gene-C;ID=cds-C
+"#,
+    )?;
+
+    let ranges = |cds_name: &str| -> Result<Vec<(NucRefGlobalRange, NucRefLocalRange)>, Report> {
+      Ok(
+        gene_map
+          .get_cds(cds_name)?
+          .segments
+          .iter()
+          .map(|segment| (segment.range.clone(), segment.range_local.clone()))
+          .collect_vec(),
+      )
+    };
+
+    assert_eq!(
+      ranges("A")?,
+      vec![
+        (
+          NucRefGlobalRange::from_usize(11, 99),
+          NucRefLocalRange::from_usize(0, 88)
+        ),
+        (
+          NucRefGlobalRange::from_usize(99, 200),
+          NucRefLocalRange::from_usize(88, 189)
+        ),
+      ]
+    );
+
+    assert_eq!(
+      ranges("B")?,
+      vec![(
+        NucRefGlobalRange::from_usize(299, 398),
+        NucRefLocalRange::from_usize(0, 99)
+      )]
+    );
+
+    assert_eq!(
+      ranges("C")?,
+      vec![(
+        NucRefGlobalRange::from_usize(499, 598),
+        NucRefLocalRange::from_usize(0, 99)
+      )]
+    );
+
+    Ok(())
+  }
+
+  #[rstest]
+  fn genome_annotation_retains_non_coding_features() -> Result<(), Report> {
+    let gene_map = GeneMap::from_str(
+      r#"##gff-version 3
+##sequence-region seq 1 1000
+seq	.	region	1	1000	.	+	.	ID=seq
+seq	.	five_prime_UTR	1	99	.	+	.	Name=5UTR;ID=utr-5
+seq	.	gene	100	399	.	+	.	Name=A;ID=gene-A
+seq	.	CDS	100	399	.	+	.	Name=A;Parent=gene-A;ID=cds-A
+seq	.	stem_loop	350	380	.	+	.	Name=SL1;Parent=gene-A;ID=sl-1
+seq	.	three_prime_UTR	400	1000	.	+	.	Name=3UTR;ID=utr-3
+"#,
+    )?;
+
+    assert_eq!(
+      gene_map.iter_genes().map(|gene| gene.name.as_str()).collect_vec(),
+      vec!["A"]
+    );
+
+    assert_eq!(
+      gene_map
+        .iter_non_coding_features()
+        .map(|feature| (
+          feature.feature_type.as_str(),
+          feature.name.as_str(),
+          feature.range.clone()
+        ))
+        .collect_vec(),
+      vec![
+        ("five_prime_UTR", "5UTR", NucRefGlobalRange::from_usize(0, 99)),
+        ("stem_loop", "SL1", NucRefGlobalRange::from_usize(349, 380)),
+        ("three_prime_UTR", "3UTR", NucRefGlobalRange::from_usize(399, 1000)),
+      ]
+    );
+
+    Ok(())
+  }
 }
@@ -0,0 +1,406 @@
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::gene::cds::Cds;
+use crate::gene::gene::GeneStrand;
+use crate::gene::gene_map::GeneMap;
+use eyre::Report;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// CDS which is present in only one of the compared genome annotations
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsDiffEntry {
+  pub gene: String,
+  pub cds: String,
+  pub ranges: Vec<NucRefGlobalRange>,
+}
+
+/// CDS which has the same coordinates and strand in both genome annotations, but a different name
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsRename {
+  pub old_name: String,
+  pub new_name: String,
+  pub ranges: Vec<NucRefGlobalRange>,
+}
+
+/// CDS which has different coordinates in the two genome annotations.
+///
+/// The `shift` is set if all segment boundaries have moved by the same amount and the number of segments is unchanged.
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsCoordinateShift {
+  pub cds: String,
+  pub old_ranges: Vec<NucRefGlobalRange>,
+  pub new_ranges: Vec<NucRefGlobalRange>,
+  pub shift: Option<isize>,
+}
+
+/// CDS which has segments on a different strand in the two genome annotations
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CdsStrandChange {
+  pub cds: String,
+  pub old_strands: Vec<GeneStrand>,
+  pub new_strands: Vec<GeneStrand>,
+}
+
+/// Differences between two genome annotations
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneMapDiff {
+  pub genes_added: Vec<String>,
+  pub genes_removed: Vec<String>,
+  pub cdses_added: Vec<CdsDiffEntry>,
+  pub cdses_removed: Vec<CdsDiffEntry>,
+  pub cdses_renamed: Vec<CdsRename>,
+  pub coordinate_shifts: Vec<CdsCoordinateShift>,
+  pub strand_changes: Vec<CdsStrandChange>,
+}
+
+impl GeneMapDiff {
+  pub fn is_empty(&self) -> bool {
+    self.genes_added.is_empty()
+      && self.genes_removed.is_empty()
+      && self.cdses_added.is_empty()
+      && self.cdses_removed.is_empty()
+      && self.cdses_renamed.is_empty()
+      && self.coordinate_shifts.is_empty()
+      && self.strand_changes.is_empty()
+  }
+}
+
+fn cds_ranges(cds: &Cds) -> Vec<NucRefGlobalRange> {
+  cds.segments.iter().map(|segment| segment.range.clone()).collect_vec()
+}
+
+fn cds_strands(cds: &Cds) -> Vec<GeneStrand> {
+  cds.segments.iter().map(|segment| segment.strand).collect_vec()
+}
+
+/// Finds uniform shift between two sets of ranges, if any
+fn find_shift(old_ranges: &[NucRefGlobalRange], new_ranges: &[NucRefGlobalRange]) -> Option<isize> {
+  if old_ranges.len() != new_ranges.len() || old_ranges.is_empty() {
+    return None;
+  }
+
+  old_ranges
+    .iter()
+    .zip(new_ranges.iter())
+    .flat_map(|(old, new)| {
+      [
+        new.begin.as_isize() - old.begin.as_isize(),
+        new.end.as_isize() - old.end.as_isize(),
+      ]
+    })
+    .all_equal_value()
+    .ok()
+}
+
+/// Compares two genome annotations. Genes and CDSes are matched by name. CDSes which are present in only one of the
+/// annotations, but have identical coordinates and strands, are reported as renamed rather than as added and removed.
+pub fn gene_map_diff(old: &GeneMap, new: &GeneMap) -> GeneMapDiff {
+  let old_genes = old.iter_genes().map(|gene| &gene.name).collect_vec();
+  let new_genes = new.iter_genes().map(|gene| &gene.name).collect_vec();
+
+  let genes_added = new_genes
+    .iter()
+    .filter(|name| !old_genes.contains(name))
+    .map(|&name| name.clone())
+    .sorted()
+    .collect_vec();
+
+  let genes_removed = old_genes
+    .iter()
+    .filter(|name| !new_genes.contains(name))
+    .map(|&name| name.clone())
+    .sorted()
+    .collect_vec();
+
+  let old_cdses: BTreeMap<&str, (&str, &Cds)> = old
+    .iter_genes()
+    .flat_map(|gene| {
+      gene
+        .cdses
+        .iter()
+        .map(move |cds| (cds.name.as_str(), (gene.name.as_str(), cds)))
+    })
+    .collect();
+
+  let new_cdses: BTreeMap<&str, (&str, &Cds)> = new
+    .iter_genes()
+    .flat_map(|gene| {
+      gene
+        .cdses
+        .iter()
+        .map(move |cds| (cds.name.as_str(), (gene.name.as_str(), cds)))
+    })
+    .collect();
+
+  let mut cdses_removed = old_cdses
+    .iter()
+    .filter(|(name, _)| !new_cdses.contains_key(*name))
+    .map(|(_, (gene, cds))| CdsDiffEntry {
+      gene: (*gene).to_owned(),
+      cds: cds.name.clone(),
+      ranges: cds_ranges(cds),
+    })
+    .collect_vec();
+
+  let mut cdses_added = new_cdses
+    .iter()
+    .filter(|(name, _)| !old_cdses.contains_key(*name))
+    .map(|(_, (gene, cds))| CdsDiffEntry {
+      gene: (*gene).to_owned(),
+      cds: cds.name.clone(),
+      ranges: cds_ranges(cds),
+    })
+    .collect_vec();
+
+  let mut cdses_renamed = vec![];
+  cdses_removed.retain(|removed| {
+    let removed_strands = cds_strands(old_cdses[removed.cds.as_str()].1);
+    let renamed_to = cdses_added.iter().position(|added| {
+      added.ranges == removed.ranges && cds_strands(new_cdses[added.cds.as_str()].1) == removed_strands
+    });
+
+    match renamed_to {
+      Some(i) => {
+        let added = cdses_added.remove(i);
+        cdses_renamed.push(CdsRename {
+          old_name: removed.cds.clone(),
+          new_name: added.cds,
+          ranges: added.ranges,
+        });
+        false
+      }
+      None => true,
+    }
+  });
+
+  let mut coordinate_shifts = vec![];
+  let mut strand_changes = vec![];
+  for (name, (_, old_cds)) in &old_cdses {
+    let Some((_, new_cds)) = new_cdses.get(name) else {
+      continue;
+    };
+
+    let old_ranges = cds_ranges(old_cds);
+    let new_ranges = cds_ranges(new_cds);
+    if old_ranges != new_ranges {
+      coordinate_shifts.push(CdsCoordinateShift {
+        cds: (*name).to_owned(),
+        shift: find_shift(&old_ranges, &new_ranges),
+        old_ranges,
+        new_ranges,
+      });
+    }
+
+    let old_strands = cds_strands(old_cds);
+    let new_strands = cds_strands(new_cds);
+    if old_strands != new_strands {
+      strand_changes.push(CdsStrandChange {
+        cds: (*name).to_owned(),
+        old_strands,
+        new_strands,
+      });
+    }
+  }
+
+  GeneMapDiff {
+    genes_added,
+    genes_removed,
+    cdses_added,
+    cdses_removed,
+    cdses_renamed,
+    coordinate_shifts,
+    strand_changes,
+  }
+}
+
+fn format_ranges(ranges: &[NucRefGlobalRange]) -> String {
+  ranges.iter().map(ToString::to_string).join(", ")
+}
+
+fn format_strands(strands: &[GeneStrand]) -> String {
+  strands.iter().map(ToString::to_string).join(", ")
+}
+
+pub fn gene_map_diff_to_table_string(diff: &GeneMapDiff) -> Result<String, Report> {
+  let mut buf = Vec::<u8>::new();
+  format_gene_map_diff(&mut buf, diff)?;
+  Ok(String::from_utf8(buf)?)
+}
+
+/// Writes human-readable table of differences between two genome annotations. Ranges are 1-based and inclusive.
+pub fn format_gene_map_diff<W: Write>(w: &mut W, diff: &GeneMapDiff) -> Result<(), Report> {
+  if diff.is_empty() {
+    writeln!(w, "Genome annotations are identical")?;
+    return Ok(());
+  }
+
+  let mut rows: Vec<[String; 4]> = vec![];
+
+  for gene in &diff.genes_added {
+    rows.push(["gene added".to_owned(), gene.clone(), String::new(), String::new()]);
+  }
+
+  for gene in &diff.genes_removed {
+    rows.push(["gene removed".to_owned(), gene.clone(), String::new(), String::new()]);
+  }
+
+  for entry in &diff.cdses_added {
+    rows.push([
+      "CDS added".to_owned(),
+      entry.cds.clone(),
+      String::new(),
+      format_ranges(&entry.ranges),
+    ]);
+  }
+
+  for entry in &diff.cdses_removed {
+    rows.push([
+      "CDS removed".to_owned(),
+      entry.cds.clone(),
+      format_ranges(&entry.ranges),
+      String::new(),
+    ]);
+  }
+
+  for rename in &diff.cdses_renamed {
+    rows.push([
+      "CDS renamed".to_owned(),
+      format_ranges(&rename.ranges),
+      rename.old_name.clone(),
+      rename.new_name.clone(),
+    ]);
+  }
+
+  for shift in &diff.coordinate_shifts {
+    let new_ranges = match shift.shift {
+      Some(shift_by) => format!("{} ({shift_by:+})", format_ranges(&shift.new_ranges)),
+      None => format_ranges(&shift.new_ranges),
+    };
+    rows.push([
+      "CDS coordinates".to_owned(),
+      shift.cds.clone(),
+      format_ranges(&shift.old_ranges),
+      new_ranges,
+    ]);
+  }
+
+  for change in &diff.strand_changes {
+    rows.push([
+      "CDS strand".to_owned(),
+      change.cds.clone(),
+      format_strands(&change.old_strands),
+      format_strands(&change.new_strands),
+    ]);
+  }
+
+  let headers = ["Change", "Feature", "Old", "New"];
+  let widths = (0..headers.len())
+    .map(|i| {
+      rows
+        .iter()
+        .map(|row| row[i].len())
+        .chain([headers[i].len()])
+        .max()
+        .unwrap_or_default()
+    })
+    .collect_vec();
+
+  let format_row = |cells: &[&str]| {
+    cells
+      .iter()
+      .zip(&widths)
+      .map(|(cell, &width)| format!("{cell:width$}"))
+      .join(" │ ")
+  };
+
+  writeln!(w, "{}", format_row(&headers))?;
+  writeln!(w, "{}", widths.iter().map(|width| "─".repeat(*width)).join("─┼─"))?;
+  for row in &rows {
+    writeln!(w, "{}", format_row(&row.iter().map(String::as_str).collect_vec()))?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn diffs_genome_annotations() -> Result<(), Report> {
+    let old = GeneMap::from_str(
+      r#"##gff-version 3
+##sequence-region seq 1 1000
+seq	.	gene	10	99	.	+	.	Name=A;ID=gene-A
+seq	.	CDS	10	99	.	+	.	Name=A;Parent=gene-A;ID=cds-A
+seq	.	gene	100	198	.	+	.	Name=B;ID=gene-B
+seq	.	CDS	100	198	.	+	.	Name=B;Parent=gene-B;ID=cds-B
+seq	.	gene	200	298	.	+	.	Name=C;ID=gene-C
+seq	.	CDS	200	298	.	+	.	Name=C;Parent=gene-C;ID=cds-C
+seq	.	gene	300	398	.	+	.	Name=D;ID=gene-D
+seq	.	CDS	300	398	.	+	.	Name=D;Parent=gene-D;ID=cds-D
+"#,
+    )?;
+
+    let new = GeneMap::from_str(
+      r#"##gff-version 3
+##sequence-region seq 1 1000
+seq	.	gene	10	99	.	+	.	Name=A;ID=gene-A
+seq	.	CDS	10	99	.	-	.	Name=A;Parent=gene-A;ID=cds-A
+seq	.	gene	103	201	.	+	.	Name=B;ID=gene-B
+seq	.	CDS	103	201	.	+	.	Name=B;Parent=gene-B;ID=cds-B
+seq	.	gene	200	298	.	+	.	Name=C;ID=gene-C
+seq	.	CDS	200	298	.	+	.	Name=C1;Parent=gene-C;ID=cds-C
+seq	.	gene	500	598	.	+	.	Name=E;ID=gene-E
+seq	.	CDS	500	598	.	+	.	Name=E;Parent=gene-E;ID=cds-E
+"#,
+    )?;
+
+    let diff = gene_map_diff(&old, &new);
+
+    assert_eq!(diff.genes_added, vec!["E"]);
+    assert_eq!(diff.genes_removed, vec!["D"]);
+    assert_eq!(diff.cdses_added.iter().map(|e| e.cds.as_str()).collect_vec(), vec!["E"]);
+    assert_eq!(
+      diff.cdses_removed.iter().map(|e| e.cds.as_str()).collect_vec(),
+      vec!["D"]
+    );
+    assert_eq!(
+      diff.cdses_renamed,
+      vec![CdsRename {
+        old_name: "C".to_owned(),
+        new_name: "C1".to_owned(),
+        ranges: vec![NucRefGlobalRange::from_usize(199, 298)],
+      }]
+    );
+    assert_eq!(
+      diff.coordinate_shifts,
+      vec![CdsCoordinateShift {
+        cds: "B".to_owned(),
+        old_ranges: vec![NucRefGlobalRange::from_usize(99, 198)],
+        new_ranges: vec![NucRefGlobalRange::from_usize(102, 201)],
+        shift: Some(3),
+      }]
+    );
+    assert_eq!(
+      diff.strand_changes,
+      vec![CdsStrandChange {
+        cds: "A".to_owned(),
+        old_strands: vec![GeneStrand::Forward],
+        new_strands: vec![GeneStrand::Reverse],
+      }]
+    );
+
+    Ok(())
+  }
+}
@@ -4,6 +4,7 @@ use crate::gene::cds::Cds;
 use crate::gene::cds_segment::{CdsSegment, WrappingPart};
 use crate::gene::gene::Gene;
 use crate::gene::gene_map::GeneMap;
+use crate::gene::non_coding_feature::NonCodingFeature;
 use crate::gene::protein::{Protein, ProteinSegment};
 use crate::utils::string::truncate_with_ellipsis;
 use eyre::Report;
@@ -61,9 +62,16 @@ pub fn format_gene_map<W: Write>(w: &mut W, gene_map: &GeneMap) -> Result<(), Re
     .max()
     .unwrap_or_default();
 
+  let max_non_coding_feature_name_len = gene_map
+    .iter_non_coding_features()
+    .map(|feature| feature.name_and_type().len() + INDENT_WIDTH)
+    .max()
+    .unwrap_or_default();
+
   let max_name_len = clamp(
     iter_max([
       max_gene_name_len,
+      max_non_coding_feature_name_len,
       max_cds_name_len,
       max_cds_segment_name_len,
       max_protein_name_len,
@@ -104,6 +112,11 @@ pub fn format_gene_map<W: Write>(w: &mut W, gene_map: &GeneMap) -> Result<(), Re
       }
     }
   }
+
+  for feature in gene_map.iter_non_coding_features() {
+    write_non_coding_feature(w, max_name_len, feature)?;
+  }
+
   Ok(())
 }
 
@@ -214,6 +227,36 @@ fn write_protein_segment<W: Write>(
   Ok(())
 }
 
+fn write_non_coding_feature<W: Write>(
+  w: &mut W,
+  max_name_len: usize,
+  feature: &NonCodingFeature,
+) -> Result<(), Report> {
+  let NonCodingFeature {
+    range,
+    strand,
+    feature_type,
+    notes,
+    ..
+  } = feature;
+
+  let indent_width = INDENT_WIDTH;
+  let indent = INDENT.repeat(indent_width);
+  let max_name_len = max_name_len.saturating_sub(indent_width);
+  let name = truncate_with_ellipsis(feature.name_and_type(), max_name_len);
+  let start = range.begin;
+  let end = range.end;
+  let nuc_len = feature.len();
+  let notes = notes.join(", ");
+  writeln!(
+    w,
+    "{indent}{:max_name_len$} │ {strand:} │   │   │     │ {start:>7} │ {end:>7} │ {nuc_len:>7} │             │ {notes}",
+    name.style(style_for_feature_type(feature_type)?)
+  )?;
+
+  Ok(())
+}
+
 pub fn format_codon_length(nuc_len: usize) -> String {
   let codons = nuc_len / 3;
   let codons_decimal = match nuc_len % 3 {
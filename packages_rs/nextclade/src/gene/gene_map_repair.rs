@@ -0,0 +1,312 @@
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::features::feature::Feature;
+use crate::features::feature_group::FeatureGroup;
+use crate::features::feature_tree::FeatureTree;
+use crate::gene::cds::trim_range_to_declared_phase;
+use crate::gene::cds_partial::CdsPartial;
+use crate::gene::gene::GeneStrand;
+use crate::gene::ribosomal_slippage::ribosomal_slippage_from_attributes;
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum GeneMapRepairKind {
+  /// Segment extends past the end of the sequence region (or of the landmark feature)
+  SegmentOutOfBounds,
+  /// Total length of the CDS is not divisible by 3
+  LengthNotMultipleOfThree,
+}
+
+/// Proposed change of coordinates of one GFF3 record of a CDS. Coordinates are as in GFF3: 1-based, inclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneMapRepairSuggestion {
+  pub kind: GeneMapRepairKind,
+  pub cds: String,
+  pub seqid: String,
+  pub feature_type: String,
+  pub strand: GeneStrand,
+  pub old_start: usize,
+  pub old_end: usize,
+  pub new_start: usize,
+  pub new_end: usize,
+  pub message: String,
+}
+
+impl Display for GeneMapRepairSuggestion {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}: change {} record '{}' {}..{} ({}) to {}..{}",
+      self.message,
+      self.feature_type,
+      self.cds,
+      self.old_start,
+      self.old_end,
+      self.strand,
+      self.new_start,
+      self.new_end
+    )
+  }
+}
+
+/// Finds problems in CDS coordinates which would make genome annotation invalid or unusable, and proposes minimal
+/// changes of coordinates which fix them:
+///
+///  - segments extending past the end of the sequence region are clamped to the end of the region (unless the region
+///    is circular)
+///  - if total length of a CDS is not divisible by 3, the excess nucleotides are trimmed from the 3' end of the CDS
+///
+/// Features of type "gene" which contain no CDSes are treated as CDSes, the same way as when reading genome annotation.
+pub fn gene_map_repair_suggestions(feature_tree: &FeatureTree) -> Vec<GeneMapRepairSuggestion> {
+  let mut suggestions = vec![];
+  for seq_region in &feature_tree.seq_regions {
+    let mut cds_groups = vec![];
+    for feature_group in &seq_region.children {
+      find_cds_groups_recursive(feature_group, &mut cds_groups);
+    }
+
+    for cds_group in cds_groups {
+      suggest_cds_repairs(cds_group, &seq_region.range, &mut suggestions);
+    }
+  }
+  suggestions
+}
+
+fn find_cds_groups_recursive<'a>(feature_group: &'a FeatureGroup, cds_groups: &mut Vec<&'a FeatureGroup>) {
+  if feature_group.feature_type == "CDS" || (feature_group.feature_type == "gene" && !contains_cds(feature_group)) {
+    cds_groups.push(feature_group);
+  }
+  for child in &feature_group.children {
+    find_cds_groups_recursive(child, cds_groups);
+  }
+}
+
+fn contains_cds(feature_group: &FeatureGroup) -> bool {
+  feature_group
+    .children
+    .iter()
+    .any(|child| child.feature_type == "CDS" || contains_cds(child))
+}
+
+fn suggest_cds_repairs(
+  cds_group: &FeatureGroup,
+  seq_region_range: &NucRefGlobalRange,
+  suggestions: &mut Vec<GeneMapRepairSuggestion>,
+) {
+  // Ranges of segments after the bounds are fixed
+  let mut ranges = cds_group
+    .features
+    .iter()
+    .map(|feature| {
+      let (bounds, is_circular) = feature.landmark.as_ref().map_or((seq_region_range, false), |landmark| {
+        (&landmark.range, landmark.is_circular)
+      });
+
+      let mut range = feature.range.clone();
+      if !is_circular && range.end > bounds.end && bounds.end > range.begin {
+        range.end = bounds.end;
+        suggestions.push(make_suggestion(
+          GeneMapRepairKind::SegmentOutOfBounds,
+          cds_group,
+          feature,
+          &range,
+          format!(
+            "Segment of CDS '{}' ends at {}, after the end of the sequence region at {}",
+            cds_group.name, feature.range.end, bounds.end
+          ),
+        ));
+      }
+      range
+    })
+    .collect_vec();
+
+  // Partial CDSes are trimmed to full codons when reading the annotation
+  let is_partial = cds_group
+    .features
+    .iter()
+    .any(|feature| CdsPartial::from_attributes(&feature.attributes, feature.strand).is_partial());
+  if is_partial {
+    return;
+  }
+
+  // Length of the CDS as it will be after reading the annotation, taking into account declared phases
+  let mut local_begin = 0;
+  for (feature, range) in cds_group.features.iter().zip(&ranges) {
+    let Ok(trimmed) = trim_range_to_declared_phase(range, feature.strand, feature.phase, local_begin) else {
+      return;
+    };
+    local_begin += trimmed.len();
+  }
+
+  // Explicitly declared ribosomal slippage changes the length of the CDS: by re-reading or skipping a nucleotide
+  let slippage = cds_group.features.iter().find_map(|feature| {
+    ribosomal_slippage_from_attributes(&feature.exceptions, &feature.attributes)
+      .ok()
+      .flatten()
+  });
+  if let Some(slippage) = slippage {
+    local_begin = local_begin.saturating_add_signed(-isize::from(slippage.shift));
+  }
+
+  let excess = local_begin % 3;
+  if excess == 0 {
+    return;
+  }
+
+  let (Some(feature), Some(range)) = (cds_group.features.last(), ranges.last_mut()) else {
+    return;
+  };
+
+  if range.len() <= excess {
+    return;
+  }
+
+  match feature.strand {
+    GeneStrand::Forward => range.end -= excess as isize,
+    GeneStrand::Reverse => range.begin += excess as isize,
+  }
+
+  let message = format!(
+    "Length of CDS '{}' is {local_begin}, which is not divisible by 3. Trim {excess} nucleotide(s) from its 3' end",
+    cds_group.name
+  );
+  let suggestion = make_suggestion(
+    GeneMapRepairKind::LengthNotMultipleOfThree,
+    cds_group,
+    feature,
+    range,
+    message,
+  );
+
+  // If the same record was already clamped, merge the suggestions, such that each record has at most one
+  let existing = suggestions
+    .iter_mut()
+    .find(|s| s.cds == suggestion.cds && s.old_start == suggestion.old_start && s.old_end == suggestion.old_end);
+  if let Some(existing) = existing {
+    existing.new_start = suggestion.new_start;
+    existing.new_end = suggestion.new_end;
+    existing.message = format!("{}. {}", existing.message, suggestion.message);
+  } else {
+    suggestions.push(suggestion);
+  }
+}
+
+fn make_suggestion(
+  kind: GeneMapRepairKind,
+  cds_group: &FeatureGroup,
+  feature: &Feature,
+  new_range: &NucRefGlobalRange,
+  message: String,
+) -> GeneMapRepairSuggestion {
+  GeneMapRepairSuggestion {
+    kind,
+    cds: cds_group.name.clone(),
+    seqid: feature.seqid.clone(),
+    feature_type: feature.feature_type.clone(),
+    strand: feature.strand,
+    old_start: feature.range.begin.as_usize() + 1,
+    old_end: feature.range.end.as_usize(),
+    new_start: new_range.begin.as_usize() + 1,
+    new_end: new_range.end.as_usize(),
+    message,
+  }
+}
+
+/// Applies repair suggestions to the content of a GFF3 file.
+///
+/// Records are identified by sequence ID, feature type, coordinates and strand. Only the start and end columns are
+/// changed, the rest of the file is preserved as is.
+pub fn gff3_apply_repairs(content: &str, suggestions: &[GeneMapRepairSuggestion]) -> String {
+  let mut lines = content
+    .lines()
+    .map(|line| {
+      if line.starts_with('#') || line.trim().is_empty() {
+        return line.to_owned();
+      }
+
+      let mut columns = line.split('\t').map(ToOwned::to_owned).collect_vec();
+      if columns.len() < 9 {
+        return line.to_owned();
+      }
+
+      let suggestion = suggestions.iter().find(|s| {
+        columns[0] == s.seqid
+          && columns[2] == s.feature_type
+          && columns[3] == s.old_start.to_string()
+          && columns[4] == s.old_end.to_string()
+          && columns[6] == s.strand.to_string()
+      });
+
+      if let Some(suggestion) = suggestion {
+        columns[3] = suggestion.new_start.to_string();
+        columns[4] = suggestion.new_end.to_string();
+        columns.join("\t")
+      } else {
+        line.to_owned()
+      }
+    })
+    .join("\n");
+
+  if content.ends_with('\n') {
+    lines.push('\n');
+  }
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  const GFF: &str = "##gff-version 3
+##sequence-region seq 1 1000
+seq\t.\tgene\t10\t200\t.\t+\t.\tName=A;ID=gene-A
+seq\t.\tCDS\t10\t200\t.\t+\t.\tName=A;Parent=gene-A;ID=cds-A
+seq\t.\tgene\t300\t400\t.\t-\t.\tName=B;ID=gene-B
+seq\t.\tCDS\t300\t400\t.\t-\t.\tName=B;Parent=gene-B;ID=cds-B
+seq\t.\tgene\t901\t1010\t.\t+\t.\tName=C;ID=gene-C
+seq\t.\tCDS\t901\t1010\t.\t+\t.\tName=C;Parent=gene-C;ID=cds-C
+seq\t.\tgene\t500\t600\t.\t+\t.\tName=D;ID=gene-D
+seq\t.\tCDS\t500\t598\t.\t+\t.\tName=D;Parent=gene-D;ID=cds-D
+";
+
+  #[rstest]
+  fn suggests_gene_map_repairs() -> Result<(), Report> {
+    let suggestions = gene_map_repair_suggestions(&FeatureTree::from_gff3_str(GFF)?);
+
+    assert_eq!(
+      suggestions
+        .iter()
+        .map(|s| (s.kind, s.cds.as_str(), s.old_start, s.old_end, s.new_start, s.new_end))
+        .collect_vec(),
+      vec![
+        (GeneMapRepairKind::LengthNotMultipleOfThree, "A", 10, 200, 10, 198),
+        (GeneMapRepairKind::LengthNotMultipleOfThree, "B", 300, 400, 302, 400),
+        (GeneMapRepairKind::SegmentOutOfBounds, "C", 901, 1010, 901, 999),
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[rstest]
+  fn applies_gene_map_repairs() -> Result<(), Report> {
+    let suggestions = gene_map_repair_suggestions(&FeatureTree::from_gff3_str(GFF)?);
+    let repaired = gff3_apply_repairs(GFF, &suggestions);
+
+    assert!(repaired.contains("seq\t.\tCDS\t10\t198\t.\t+\t.\tName=A;Parent=gene-A;ID=cds-A\n"));
+    assert!(repaired.contains("seq\t.\tCDS\t302\t400\t.\t-\t.\tName=B;Parent=gene-B;ID=cds-B\n"));
+    assert!(repaired.contains("seq\t.\tCDS\t901\t999\t.\t+\t.\tName=C;Parent=gene-C;ID=cds-C\n"));
+    assert!(repaired.contains("seq\t.\tgene\t10\t200\t.\t+\t.\tName=A;ID=gene-A\n"));
+    assert!(gene_map_repair_suggestions(&FeatureTree::from_gff3_str(&repaired)?).is_empty());
+
+    Ok(())
+  }
+}
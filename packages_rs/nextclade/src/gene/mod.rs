@@ -1,9 +1,15 @@
 pub mod cds;
+pub mod cds_partial;
 pub mod cds_segment;
 pub mod frame;
 pub mod gene;
 pub mod gene_map;
+pub mod gene_map_diff;
 pub mod gene_map_display;
+pub mod gene_map_repair;
 pub mod genotype;
+pub mod non_coding_feature;
 pub mod phase;
 pub mod protein;
+pub mod ribosomal_slippage;
+pub mod transl_except;
@@ -0,0 +1,121 @@
+use crate::coord::range::NucRefGlobalRange;
+use crate::features::feature_group::FeatureGroup;
+use crate::features::feature_type::shorten_feature_type;
+use crate::gene::gene::GeneStrand;
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Feature types which describe gene models and are converted into genes, CDSes and proteins, as well as the types
+/// describing the entire sequence. Features of all other types are retained as non-coding features.
+const CODING_FEATURE_TYPES: &[&str] = &[
+  "gene",
+  "CDS",
+  "mRNA",
+  "transcript",
+  "exon",
+  "mature_protein_region_of_CDS",
+  "signal_peptide_region_of_CDS",
+  "region",
+  "source",
+  "sequence_region",
+];
+
+/// Genome annotation feature which is not a part of a gene model, for example UTR, stem loop or regulatory region.
+/// These features are not used in the analysis, but are retained so that users can cross-reference them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NonCodingFeature {
+  pub index: usize,
+  pub id: String,
+  pub name: String,
+  pub feature_type: String,
+  pub range: NucRefGlobalRange,
+  pub strand: GeneStrand,
+  pub parent_ids: Vec<String>,
+  pub exceptions: Vec<String>,
+  pub notes: Vec<String>,
+  pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl NonCodingFeature {
+  pub fn from_feature_group(feature_group: &FeatureGroup) -> Self {
+    let begin = feature_group
+      .features
+      .iter()
+      .map(|feature| feature.range.begin)
+      .min()
+      .unwrap_or_default();
+
+    let end = feature_group
+      .features
+      .iter()
+      .map(|feature| feature.range.end)
+      .max()
+      .unwrap_or_default();
+
+    let strand = feature_group
+      .features
+      .first()
+      .map(|feature| feature.strand)
+      .unwrap_or_default();
+
+    let attributes = feature_group
+      .features
+      .first()
+      .map(|feature| feature.attributes.clone())
+      .unwrap_or_default();
+
+    Self {
+      index: feature_group.index,
+      id: feature_group.id.clone(),
+      name: feature_group.name.clone(),
+      feature_type: feature_group.feature_type.clone(),
+      range: NucRefGlobalRange::new(begin, end),
+      strand,
+      parent_ids: feature_group.parent_ids.clone(),
+      exceptions: feature_group.exceptions.clone(),
+      notes: feature_group.notes.clone(),
+      attributes,
+    }
+  }
+
+  #[inline]
+  pub fn name_and_type(&self) -> String {
+    format!("{} '{}'", shorten_feature_type(&self.feature_type), self.name)
+  }
+
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.range.len()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+/// Collects features which are not parts of gene models, at any level of nesting, ordered by position
+pub fn find_non_coding_features(feature_groups: &[FeatureGroup]) -> Vec<NonCodingFeature> {
+  let mut features = vec![];
+  feature_groups
+    .iter()
+    .for_each(|feature_group| find_non_coding_features_recursive(feature_group, &mut features));
+  features
+    .into_iter()
+    .sorted_by_key(|feature| (feature.range.begin, feature.index))
+    .collect_vec()
+}
+
+fn find_non_coding_features_recursive(feature_group: &FeatureGroup, features: &mut Vec<NonCodingFeature>) {
+  if !CODING_FEATURE_TYPES.contains(&feature_group.feature_type.as_str()) {
+    features.push(NonCodingFeature::from_feature_group(feature_group));
+  }
+
+  feature_group
+    .children
+    .iter()
+    .for_each(|child_feature_group| find_non_coding_features_recursive(child_feature_group, features));
+}
@@ -0,0 +1,218 @@
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::coord::range::NucRefGlobalRange;
+use crate::gene::cds_segment::{cds_segments_recalculate_local, CdsSegment};
+use crate::gene::gene::GeneStrand;
+use crate::make_error;
+use eyre::{eyre, Report, WrapErr};
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Value of the `exception` attribute which declares a programmed ribosomal frameshift. Underscores are treated as
+/// spaces, so that both `ribosomal slippage` (as in INSDC) and `ribosomal_slippage` are accepted.
+const RIBOSOMAL_SLIPPAGE_EXCEPTION: &str = "ribosomal slippage";
+
+/// Attribute containing 1-based position of the last nucleotide which is read before the ribosome shifts
+const SLIPPAGE_SITE_ATTR: &str = "slippage_site";
+
+/// Attribute containing direction of the shift: -1 (the nucleotide at the site is read twice) or +1 (the nucleotide
+/// following the site is skipped). Defaults to -1.
+const SLIPPAGE_SHIFT_ATTR: &str = "slippage_shift";
+
+/// Programmed ribosomal frameshift declared in a CDS.
+///
+/// Instead of annotating the CDS as 2 overlapping segments, the CDS can be annotated with a single record spanning the
+/// entire coding region, with `exception=ribosomal slippage` and `slippage_site=<position>` attributes. The CDS is then
+/// split at the slippage site, such that translation continues in the shifted reading frame.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RibosomalSlippage {
+  /// Position of the last nucleotide which is read before the shift (0-based, global)
+  pub site: NucRefGlobalPosition,
+  /// Shift of the reading frame: -1 or +1
+  pub shift: i8,
+}
+
+/// Reads the slippage declaration from attributes of a CDS. Returns `None` if the CDS does not declare ribosomal
+/// slippage exception, or if it declares it but does not specify the slippage site (it is then assumed that the
+/// slippage is already encoded in the CDS segments, as is traditional).
+pub fn ribosomal_slippage_from_attributes(
+  exceptions: &[String],
+  attributes: &HashMap<String, Vec<String>>,
+) -> Result<Option<RibosomalSlippage>, Report> {
+  let has_exception = exceptions
+    .iter()
+    .any(|exception| exception.replace('_', " ").trim() == RIBOSOMAL_SLIPPAGE_EXCEPTION);
+
+  let Some(sites) = attributes.get(SLIPPAGE_SITE_ATTR) else {
+    return Ok(None);
+  };
+
+  if !has_exception {
+    return make_error!(
+      "Attribute '{SLIPPAGE_SITE_ATTR}' is only allowed together with 'exception={RIBOSOMAL_SLIPPAGE_EXCEPTION}'"
+    );
+  }
+
+  let site = match sites.iter().unique().collect_vec().as_slice() {
+    [site] => site
+      .trim()
+      .parse::<usize>()
+      .ok()
+      .filter(|&site| site > 0)
+      .ok_or_else(|| eyre!("Expected a positive integer, but found '{site}'"))
+      .wrap_err_with(|| format!("When reading attribute '{SLIPPAGE_SITE_ATTR}'"))?,
+    _ => return make_error!("Expected a single slippage site, but found: {}", sites.join(", ")),
+  };
+
+  let shift = match attributes.get(SLIPPAGE_SHIFT_ATTR).map(Vec::as_slice) {
+    None => -1,
+    Some([shift]) => match shift.trim() {
+      "-1" => -1,
+      "+1" | "1" => 1,
+      _ => return make_error!("Attribute '{SLIPPAGE_SHIFT_ATTR}': expected '-1' or '+1', but found '{shift}'"),
+    },
+    Some(shifts) => return make_error!("Expected a single slippage shift, but found: {}", shifts.join(", ")),
+  };
+
+  Ok(Some(RibosomalSlippage {
+    site: NucRefGlobalPosition::from(site - 1),
+    shift,
+  }))
+}
+
+/// Splits the CDS segment containing the slippage site into 2 segments: the part before the shift and the part after
+/// it, in the shifted reading frame. Local ranges, phases and frames of all segments are recomputed.
+pub fn apply_ribosomal_slippage(
+  segments: &[CdsSegment],
+  slippage: &RibosomalSlippage,
+) -> Result<Vec<CdsSegment>, Report> {
+  let RibosomalSlippage { site, shift } = slippage;
+  let shift = isize::from(*shift);
+
+  let mut found = false;
+  let mut result = vec![];
+  for segment in segments {
+    let range = &segment.range;
+    if !range.contains(*site) {
+      result.push(segment.clone());
+      continue;
+    }
+
+    // Ranges of the parts before and after the shift, in the order of reading
+    let (before, after) = match segment.strand {
+      GeneStrand::Forward => (
+        NucRefGlobalRange::new(range.begin, *site + 1),
+        NucRefGlobalRange::new(*site + 1 + shift, range.end),
+      ),
+      GeneStrand::Reverse => (
+        NucRefGlobalRange::new(*site, range.end),
+        NucRefGlobalRange::new(range.begin, *site - shift),
+      ),
+    };
+
+    if after.is_empty() || after.begin < range.begin || after.end > range.end {
+      return make_error!(
+        "Slippage site at position {} is too close to the end of the CDS segment spanning {}..{}",
+        site.as_usize() + 1,
+        range.begin.as_usize() + 1,
+        range.end
+      );
+    }
+
+    result.push(CdsSegment {
+      range: before,
+      ..segment.clone()
+    });
+    result.push(CdsSegment {
+      range: after,
+      ..segment.clone()
+    });
+    found = true;
+  }
+
+  if !found {
+    return make_error!(
+      "Slippage site at position {} is outside of the CDS",
+      site.as_usize() + 1
+    );
+  }
+
+  cds_segments_recalculate_local(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::coord::range::Range;
+  use crate::gene::cds_segment::WrappingPart;
+  use crate::gene::frame::Frame;
+  use crate::gene::phase::Phase;
+  use crate::utils::error::report_to_string;
+  use maplit::hashmap;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn create_segment(begin: usize, end: usize, strand: GeneStrand) -> CdsSegment {
+    CdsSegment {
+      index: 0,
+      id: String::new(),
+      name: String::new(),
+      range: NucRefGlobalRange::from_usize(begin, end),
+      range_local: Range::from_usize(0, end - begin),
+      landmark: None,
+      wrapping_part: WrappingPart::NonWrapping,
+      strand,
+      frame: Frame::_0,
+      phase: Phase::_0,
+      exceptions: vec![],
+      attributes: hashmap! {},
+      source_record: None,
+      compat_is_gene: false,
+      color: None,
+    }
+  }
+
+  #[rstest]
+  #[case::forward_minus_one(GeneStrand::Forward, -1, vec![(10, 21), (20, 40)])]
+  #[case::forward_plus_one(GeneStrand::Forward, 1, vec![(10, 21), (22, 40)])]
+  #[case::reverse_minus_one(GeneStrand::Reverse, -1, vec![(20, 40), (10, 21)])]
+  #[case::reverse_plus_one(GeneStrand::Reverse, 1, vec![(20, 40), (10, 19)])]
+  fn splits_segment_at_slippage_site(
+    #[case] strand: GeneStrand,
+    #[case] shift: i8,
+    #[case] expected: Vec<(usize, usize)>,
+  ) -> Result<(), Report> {
+    let slippage = RibosomalSlippage {
+      site: NucRefGlobalPosition::from(20_usize),
+      shift,
+    };
+    let segments = apply_ribosomal_slippage(&[create_segment(10, 40, strand)], &slippage)?;
+    let actual = segments
+      .iter()
+      .map(|segment| (segment.range.begin.as_usize(), segment.range.end.as_usize()))
+      .collect_vec();
+    assert_eq!(actual, expected);
+    assert_eq!(segments[1].range_local.begin.as_usize(), segments[0].len());
+    Ok(())
+  }
+
+  #[rstest]
+  fn reads_slippage_from_attributes() -> Result<(), Report> {
+    let attributes = hashmap! { SLIPPAGE_SITE_ATTR.to_owned() => vec!["13468".to_owned()] };
+    let exceptions = vec!["ribosomal_slippage".to_owned()];
+    assert_eq!(
+      ribosomal_slippage_from_attributes(&exceptions, &attributes)?,
+      Some(RibosomalSlippage {
+        site: NucRefGlobalPosition::from(13467_usize),
+        shift: -1,
+      })
+    );
+    assert_eq!(
+      report_to_string(&ribosomal_slippage_from_attributes(&[], &attributes).unwrap_err()),
+      "Attribute 'slippage_site' is only allowed together with 'exception=ribosomal slippage'"
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,295 @@
+use crate::alphabet::aa::{to_aa, Aa};
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::gene::cds_segment::CdsSegment;
+use crate::gene::gene::GeneStrand;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Attribute declaring codons which are translated differently than the genetic code prescribes, as in INSDC:
+/// `transl_except=(pos:1234..1236,aa:Sec)` or `transl_except=(pos:complement(1234..1236),aa:Pyl)`
+const TRANSL_EXCEPT_ATTR: &str = "transl_except";
+
+const TRANSL_EXCEPT_REGEX: &str = r"\(\s*pos:\s*(?P<complement>complement\()?\s*(?P<begin>\d+)\s*\.\.\s*(?P<end>\d+)\s*\)?\s*,\s*aa:\s*(?P<aa>[A-Za-z*]+)\s*\)";
+
+/// Codon of a CDS which is translated into a particular aminoacid regardless of the genetic code. This is how
+/// selenocysteine (Sec, U) and pyrrolysine (Pyl, O) are encoded: by a stop codon (UGA and UAG respectively) in a
+/// special sequence context.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TranslException {
+  /// Index of the codon in the CDS (0-based)
+  pub codon: usize,
+  pub aa: Aa,
+}
+
+impl TranslException {
+  /// Decides what a codon at the exception site translates to. Sec and Pyl are only inserted in place of a stop
+  /// codon: if the stop codon is mutated, the mutated codon is translated as usual. Other exceptions (for example,
+  /// alternative start codons translated as Met) apply to any complete codon.
+  pub const fn apply(&self, decoded: Aa) -> Aa {
+    match (self.aa, decoded) {
+      (_, Aa::Gap | Aa::X) => decoded,
+      (Aa::U | Aa::O, Aa::Stop) => self.aa,
+      (Aa::U | Aa::O, _) => decoded,
+      _ => self.aa,
+    }
+  }
+
+  /// Whether the stop codon at this site is read through, such that translation continues past it
+  pub const fn is_stop_recoded(&self) -> bool {
+    matches!(self.aa, Aa::U | Aa::O)
+  }
+}
+
+/// Replaces aminoacids at exception sites. The peptide is expected to be in coordinates of the reference peptide,
+/// i.e. with insertions stripped.
+pub fn transl_exceptions_apply_in_place(peptide: &mut [Aa], transl_except: &[TranslException]) {
+  for exception in transl_except {
+    if let Some(aa) = peptide.get_mut(exception.codon) {
+      *aa = exception.apply(*aa);
+    }
+  }
+}
+
+/// Finds codons of the query at which stop codons are recoded, such that translation continues past them.
+///
+/// Exception sites are given as codons of the reference, while the query is translated after its gaps are removed, so
+/// deletions and insertions upstream shift the codon index. Sequences are expected to be aligned, with insertions in
+/// the query appearing as gaps in the reference. A site is only mapped if the query has a complete codon there, in
+/// frame with the reference codon.
+pub fn transl_exceptions_find_recoded_stops(
+  transl_except: &[TranslException],
+  ref_seq: &[Nuc],
+  qry_seq: &[Nuc],
+) -> Vec<usize> {
+  transl_except
+    .iter()
+    .filter(|exception| exception.is_stop_recoded())
+    .filter_map(|exception| {
+      // Alignment column of the first nucleotide of the reference codon
+      let begin = ref_seq.iter().positions(|nuc| !nuc.is_gap()).nth(exception.codon * 3)?;
+      let end = begin + 3;
+
+      let ref_codon = ref_seq.get(begin..end)?;
+      let qry_codon = qry_seq.get(begin..end)?;
+      if ref_codon.iter().chain(qry_codon).any(Nuc::is_gap) {
+        return None;
+      }
+
+      let qry_begin = qry_seq[..begin].iter().filter(|nuc| !nuc.is_gap()).count();
+      (qry_begin % 3 == 0).then_some(qry_begin / 3)
+    })
+    .collect()
+}
+
+/// Reads translation exceptions from the `transl_except` attribute of a CDS, if present
+pub fn transl_exceptions_from_attributes(
+  attributes: &HashMap<String, Vec<String>>,
+  segments: &[CdsSegment],
+) -> Result<Vec<TranslException>, Report> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(TRANSL_EXCEPT_REGEX)
+      .wrap_err_with(|| format!("When compiling regular expression '{TRANSL_EXCEPT_REGEX}'"))
+      .unwrap();
+  }
+
+  let Some(values) = attributes.get(TRANSL_EXCEPT_ATTR) else {
+    return Ok(vec![]);
+  };
+
+  // The value itself contains commas, which in GFF3 separate multiple values of an attribute, unless escaped
+  let value = values
+    .join(",")
+    .replace("%2C", ",")
+    .replace("%28", "(")
+    .replace("%29", ")");
+
+  let exceptions = RE
+    .captures_iter(&value)
+    .map(|captures| {
+      let is_complement = captures.name("complement").is_some();
+      let begin = parse_position(&captures["begin"])?;
+      let end = parse_position(&captures["end"])?;
+      let aa = aa_from_insdc_code(&captures["aa"])?;
+
+      if end < begin || end - begin != 2 {
+        return make_error!(
+          "Expected the position to span exactly one codon, but found {begin}..{end}. Codons split between CDS segments are not supported."
+        );
+      }
+
+      // Position of the first nucleotide of the codon, in the direction of reading
+      let first_nuc = if is_complement { end - 1 } else { begin - 1 };
+      let codon = codon_index(segments, NucRefGlobalPosition::from(first_nuc)).wrap_err_with(|| {
+        format!("When mapping translation exception at {begin}..{end} to the CDS")
+      })?;
+
+      Ok(TranslException { codon, aa })
+    })
+    .collect::<Result<Vec<TranslException>, Report>>()
+    .wrap_err_with(|| format!("When reading attribute '{TRANSL_EXCEPT_ATTR}={value}'"))?;
+
+  if exceptions.is_empty() {
+    return make_error!("Expected format '(pos:<begin>..<end>,aa:<aminoacid>)', but found '{value}'");
+  }
+
+  Ok(
+    exceptions
+      .into_iter()
+      .unique_by(|exception| exception.codon)
+      .collect_vec(),
+  )
+}
+
+fn parse_position(s: &str) -> Result<usize, Report> {
+  match s.parse::<usize>() {
+    Ok(pos) if pos > 0 => Ok(pos),
+    _ => make_error!("Expected a positive integer position, but found '{s}'"),
+  }
+}
+
+fn codon_index(segments: &[CdsSegment], pos: NucRefGlobalPosition) -> Result<usize, Report> {
+  let local = segments
+    .iter()
+    .find(|segment| segment.range.contains(pos))
+    .map(|segment| {
+      let pos_in_segment = match segment.strand {
+        GeneStrand::Forward => pos.as_usize() - segment.range.begin.as_usize(),
+        GeneStrand::Reverse => segment.range.end.as_usize() - 1 - pos.as_usize(),
+      };
+      segment.range_local.begin.as_usize() + pos_in_segment
+    });
+
+  match local {
+    None => make_error!("Position {} is outside of the CDS", pos.as_usize() + 1),
+    Some(local) if local % 3 != 0 => make_error!(
+      "Position {} is not at the beginning of a codon in the reading frame of the CDS",
+      pos.as_usize() + 1
+    ),
+    Some(local) => Ok(local / 3),
+  }
+}
+
+/// Converts aminoacid code used in INSDC `transl_except` qualifier: a three-letter abbreviation, `TERM` for stop codon
+/// or `OTHER` for any other aminoacid. Single-letter codes are accepted too.
+pub fn aa_from_insdc_code(code: &str) -> Result<Aa, Report> {
+  let aa = match code.to_ascii_uppercase().as_str() {
+    "ALA" => Aa::A,
+    "ASX" => Aa::B,
+    "CYS" => Aa::C,
+    "ASP" => Aa::D,
+    "GLU" => Aa::E,
+    "PHE" => Aa::F,
+    "GLY" => Aa::G,
+    "HIS" => Aa::H,
+    "ILE" => Aa::I,
+    "XLE" => Aa::J,
+    "LYS" => Aa::K,
+    "LEU" => Aa::L,
+    "MET" => Aa::M,
+    "ASN" => Aa::N,
+    "PYL" => Aa::O,
+    "PRO" => Aa::P,
+    "GLN" => Aa::Q,
+    "ARG" => Aa::R,
+    "SER" => Aa::S,
+    "THR" => Aa::T,
+    "SEC" => Aa::U,
+    "VAL" => Aa::V,
+    "TRP" => Aa::W,
+    "TYR" => Aa::Y,
+    "GLX" => Aa::Z,
+    "XAA" | "OTHER" => Aa::X,
+    "TER" | "TERM" => Aa::Stop,
+    code if code.len() == 1 => to_aa(code.chars().next().unwrap_or_default())?,
+    _ => return make_error!("Unknown aminoacid code: '{code}'"),
+  };
+  Ok(aa)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::coord::range::{NucRefGlobalRange, Range};
+  use crate::gene::cds_segment::WrappingPart;
+  use crate::gene::frame::Frame;
+  use crate::gene::phase::Phase;
+  use crate::o;
+  use maplit::hashmap;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn create_segment(begin: usize, end: usize, strand: GeneStrand) -> CdsSegment {
+    CdsSegment {
+      index: 0,
+      id: String::new(),
+      name: String::new(),
+      range: NucRefGlobalRange::from_usize(begin, end),
+      range_local: Range::from_usize(0, end - begin),
+      landmark: None,
+      wrapping_part: WrappingPart::NonWrapping,
+      strand,
+      frame: Frame::_0,
+      phase: Phase::_0,
+      exceptions: vec![],
+      attributes: hashmap! {},
+      source_record: None,
+      compat_is_gene: false,
+      color: None,
+    }
+  }
+
+  #[rstest]
+  #[case::forward(GeneStrand::Forward, "(pos:107..109,aa:Sec)", TranslException { codon: 2, aa: Aa::U })]
+  #[case::reverse(GeneStrand::Reverse, "(pos:complement(194..196),aa:Pyl)", TranslException { codon: 1, aa: Aa::O })]
+  #[case::escaped(GeneStrand::Forward, "(pos:101..103%2Caa:Met)", TranslException { codon: 0, aa: Aa::M })]
+  fn reads_transl_except(
+    #[case] strand: GeneStrand,
+    #[case] value: &str,
+    #[case] expected: TranslException,
+  ) -> Result<(), Report> {
+    let attributes = hashmap! { o!(TRANSL_EXCEPT_ATTR) => vec![value.to_owned()] };
+    let actual = transl_exceptions_from_attributes(&attributes, &[create_segment(100, 199, strand)])?;
+    assert_eq!(actual, vec![expected]);
+    Ok(())
+  }
+
+  #[rstest]
+  fn inserts_selenocysteine_only_in_place_of_stop_codon() {
+    let exception = TranslException { codon: 0, aa: Aa::U };
+    assert_eq!(exception.apply(Aa::Stop), Aa::U);
+    assert_eq!(exception.apply(Aa::W), Aa::W);
+    assert_eq!(exception.apply(Aa::Gap), Aa::Gap);
+  }
+
+  #[rstest]
+  #[case::no_indels("ATGTGAAAA", "ATGTGAAAA", 1, vec![1])]
+  #[case::upstream_deletion("ATGCCCTGAAAA", "ATG---TGAAAA", 2, vec![1])]
+  #[case::upstream_insertion("ATG---TGAAAA", "ATGCCCTGAAAA", 1, vec![2])]
+  #[case::leading_gaps("ATGCCCTGAAAA", "------TGAAAA", 2, vec![0])]
+  #[case::frame_shift("ATGCCCTGAAAA", "ATG-CCTGAAAA", 2, vec![])]
+  #[case::deleted_site("ATGCCCTGAAAA", "ATGCCC---AAA", 2, vec![])]
+  fn maps_recoded_stops_to_query_codons(
+    #[case] ref_seq: &str,
+    #[case] qry_seq: &str,
+    #[case] codon: usize,
+    #[case] expected: Vec<usize>,
+  ) -> Result<(), Report> {
+    let transl_except = [
+      TranslException { codon, aa: Aa::U },
+      TranslException { codon: 0, aa: Aa::M },
+    ];
+    let actual = transl_exceptions_find_recoded_stops(&transl_except, &to_nuc_seq(ref_seq)?, &to_nuc_seq(qry_seq)?);
+    assert_eq!(actual, expected);
+    Ok(())
+  }
+}
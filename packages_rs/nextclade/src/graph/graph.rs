@@ -13,6 +13,7 @@ use num_traits::Float;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use traversal::DftPre;
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 #[allow(clippy::partial_pub_fields)]
@@ -555,7 +556,29 @@ impl Graph<AuspiceGraphNodePayload, AuspiceGraphEdgePayload, AuspiceGraphMeta> {
 
 pub fn convert_graph_to_auspice_tree(graph: &AuspiceGraph) -> Result<AuspiceTree, Report> {
   let root = graph.get_exactly_one_root()?;
-  let tree = convert_graph_to_auspice_tree_recursive(graph, root)?;
+
+  // Nodes are converted in reverse preorder, without recursion, such that children are always converted before their
+  // parents
+  let node_keys = DftPre::new(root, |node| graph.iter_children_of(node))
+    .map(|(_, node)| node.key())
+    .collect_vec();
+  let mut converted = HashMap::<GraphNodeKey, AuspiceTreeNode>::with_capacity(node_keys.len());
+  for node_key in node_keys.into_iter().rev() {
+    let children = graph
+      .iter_child_keys_of_by_key(node_key)
+      .map(|child_key| {
+        converted
+          .remove(&child_key)
+          .ok_or_else(|| make_internal_report!("Child node {child_key} is not converted before its parent"))
+      })
+      .collect::<Result<Vec<_>, Report>>()?;
+    let node = AuspiceTreeNode::from_graph_node_payload(graph.get_node(node_key)?.payload(), children);
+    converted.insert(node_key, node);
+  }
+  let Some(tree) = converted.remove(&root.key()) else {
+    return make_internal_error!("Root node is not converted");
+  };
+
   Ok(AuspiceTree {
     version: graph.data.auspice_tree_version.clone(),
     meta: graph.data.meta.clone(),
@@ -564,17 +587,6 @@ pub fn convert_graph_to_auspice_tree(graph: &AuspiceGraph) -> Result<AuspiceTree
   })
 }
 
-fn convert_graph_to_auspice_tree_recursive(
-  graph: &AuspiceGraph,
-  node: &Node<AuspiceGraphNodePayload>,
-) -> Result<AuspiceTreeNode, Report> {
-  let children = graph
-    .iter_children_of(node)
-    .map(|child| convert_graph_to_auspice_tree_recursive(graph, child))
-    .collect::<Result<Vec<_>, Report>>()?;
-  Ok(AuspiceTreeNode::from_graph_node_payload(node.payload(), children))
-}
-
 pub fn convert_auspice_tree_to_graph(tree: AuspiceTree) -> Result<AuspiceGraph, Report> {
   let mut graph = AuspiceGraph::new(AuspiceGraphMeta {
     auspice_tree_version: tree.version,
@@ -583,7 +595,17 @@ pub fn convert_auspice_tree_to_graph(tree: AuspiceTree) -> Result<AuspiceGraph,
     other: tree.other,
   });
 
-  convert_auspice_tree_to_graph_recursive(&tree.tree, &mut graph)?;
+  // Nodes are moved from the tree into the graph in preorder, without recursion, such that trees of any depth can be
+  // converted, and the node keys follow the preorder of the tree
+  let mut stack = vec![(tree.tree, None)];
+  while let Some((mut node, parent_key)) = stack.pop() {
+    let children = std::mem::take(&mut node.children);
+    let graph_node_key = graph.add_node(node.into());
+    if let Some(parent_key) = parent_key {
+      graph.add_edge(parent_key, graph_node_key, AuspiceGraphEdgePayload::new())?;
+    }
+    stack.extend(children.into_iter().rev().map(|child| (child, Some(graph_node_key))));
+  }
   let mut graph = graph.build()?;
 
   {
@@ -595,20 +617,6 @@ pub fn convert_auspice_tree_to_graph(tree: AuspiceTree) -> Result<AuspiceGraph,
   Ok(graph)
 }
 
-fn convert_auspice_tree_to_graph_recursive(
-  node: &AuspiceTreeNode,
-  graph: &mut AuspiceGraph,
-) -> Result<GraphNodeKey, Report> {
-  let graph_node_key = graph.add_node(node.into());
-
-  for child in &node.children {
-    let graph_child_key = convert_auspice_tree_to_graph_recursive(child, graph)?;
-    graph.add_edge(graph_node_key, graph_child_key, AuspiceGraphEdgePayload::new())?;
-  }
-
-  Ok(graph_node_key)
-}
-
 fn get_max_divergence<N: GraphNode + HasDivergence, E: GraphEdge, D>(graph: &Graph<N, E, D>) -> f64 {
   graph
     .iter_nodes()
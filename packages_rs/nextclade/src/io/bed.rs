@@ -0,0 +1,103 @@
+use crate::coord::range::NucRefGlobalRange;
+use crate::io::fs::read_file_to_string;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::path::Path;
+
+/// Parses regions from BED data. Only the first 3 columns are used: chromosome (ignored, the reference sequence is
+/// assumed), start and end. Following the BED convention, positions are 0-based and ends are exclusive. Header lines
+/// (`track`, `browser`, `#`) and empty lines are skipped.
+///
+/// The resulting ranges are sorted, with overlapping and adjacent ranges merged.
+///
+/// See: https://en.wikipedia.org/wiki/BED_(file_format)
+pub fn parse_bed(data: impl AsRef<str>) -> Result<Vec<NucRefGlobalRange>, Report> {
+  let ranges = data
+    .as_ref()
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| {
+      let line = line.trim();
+      !(line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser"))
+    })
+    .map(|(i, line)| parse_bed_line(line).wrap_err_with(|| format!("When parsing BED line {}: '{line}'", i + 1)))
+    .collect::<Result<Vec<_>, Report>>()?;
+
+  Ok(merge_ranges(ranges))
+}
+
+/// Reads regions from a BED file. See [parse_bed].
+pub fn read_bed_file(filepath: impl AsRef<Path>) -> Result<Vec<NucRefGlobalRange>, Report> {
+  let filepath = filepath.as_ref();
+  let data = read_file_to_string(filepath)?;
+  parse_bed(data).wrap_err_with(|| format!("When reading BED file {filepath:#?}"))
+}
+
+fn parse_bed_line(line: &str) -> Result<NucRefGlobalRange, Report> {
+  let columns = line.split_whitespace().collect_vec();
+  let (Some(start), Some(end)) = (columns.get(1), columns.get(2)) else {
+    return make_error!("Expected at least 3 columns: chromosome, start and end");
+  };
+  let start = start
+    .parse::<usize>()
+    .wrap_err_with(|| format!("Unable to parse start position: '{start}'"))?;
+  let end = end
+    .parse::<usize>()
+    .wrap_err_with(|| format!("Unable to parse end position: '{end}'"))?;
+  if end < start {
+    return make_error!("End position {end} is before the start position {start}");
+  }
+  Ok(NucRefGlobalRange::from_usize(start, end))
+}
+
+fn merge_ranges(mut ranges: Vec<NucRefGlobalRange>) -> Vec<NucRefGlobalRange> {
+  ranges.sort();
+  let mut merged: Vec<NucRefGlobalRange> = Vec::with_capacity(ranges.len());
+  for range in ranges.into_iter().filter(|range| !range.is_empty()) {
+    match merged.last_mut() {
+      Some(last) if range.begin <= last.end => last.end = last.end.max(range.end),
+      _ => merged.push(range),
+    }
+  }
+  merged
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn parses_and_merges_bed_regions() -> Result<(), Report> {
+    let data = "track name=mask\n# problematic sites\nMN908947\t200\t300\tamplicon\nMN908947\t0\t55\nMN908947\t250\t320\n\nMN908947\t500\t501\n";
+    assert_eq!(
+      parse_bed(data)?,
+      vec![
+        NucRefGlobalRange::from_usize(0, 55),
+        NucRefGlobalRange::from_usize(200, 320),
+        NucRefGlobalRange::from_usize(500, 501),
+      ]
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(
+    "MN908947\t100",
+    "When parsing BED line 1: 'MN908947\t100': Expected at least 3 columns: chromosome, start and end"
+  )]
+  #[case(
+    "MN908947\t100\tx",
+    "When parsing BED line 1: 'MN908947\t100\tx': Unable to parse end position: 'x': invalid digit found in string"
+  )]
+  #[case(
+    "MN908947\t100\t50",
+    "When parsing BED line 1: 'MN908947\t100\t50': End position 50 is before the start position 100"
+  )]
+  fn rejects_invalid_bed_line(#[case] data: &str, #[case] message: &str) {
+    assert_eq!(report_to_string(&parse_bed(data).unwrap_err()), message);
+  }
+}
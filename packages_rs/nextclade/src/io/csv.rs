@@ -104,8 +104,17 @@ impl VecWriter for CsvVecFileWriter {
 
 /// Parses CSV data from string.
 pub fn parse_csv<T: for<'de> Deserialize<'de>, S: AsRef<str>>(data: S) -> Result<Vec<T>, Report> {
+  parse_csv_with_delimiter(data, b',')
+}
+
+/// Parses CSV data from string, using a given delimiter.
+pub fn parse_csv_with_delimiter<T: for<'de> Deserialize<'de>, S: AsRef<str>>(
+  data: S,
+  delimiter: u8,
+) -> Result<Vec<T>, Report> {
   let reader = CsvReaderBuilder::new()
     .has_headers(true)
+    .delimiter(delimiter)
     .from_reader(data.as_ref().as_bytes());
   reader
     .into_deserialize::<T>()
@@ -119,3 +128,13 @@ pub fn read_csv_file<T: for<'de> Deserialize<'de>>(filepath: impl AsRef<Path>) -
   let data = read_file_to_string(filepath)?;
   parse_csv(data)
 }
+
+/// Parses CSV file, using a given delimiter.
+pub fn read_csv_file_with_delimiter<T: for<'de> Deserialize<'de>>(
+  filepath: impl AsRef<Path>,
+  delimiter: u8,
+) -> Result<Vec<T>, Report> {
+  let filepath = filepath.as_ref();
+  let data = read_file_to_string(filepath)?;
+  parse_csv_with_delimiter(data, delimiter)
+}
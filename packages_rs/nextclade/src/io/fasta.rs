@@ -1,9 +1,14 @@
 use crate::alphabet::aa::from_aa_seq;
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::{from_nuc_seq, Nuc};
 use crate::constants::REVERSE_COMPLEMENT_SUFFIX;
+use crate::gene::cds::Cds;
 use crate::gene::gene_map::GeneMap;
 use crate::io::compression::Decompressor;
 use crate::io::concat::Concat;
 use crate::io::file::{create_file_or_stdout, open_file_or_stdin, open_stdin};
+use crate::io::gff3::{is_gff3_str, split_gff3_fasta_section};
+use crate::translate::extract::extract_cds_from_ref;
 use crate::translate::translate_genes::CdsTranslation;
 use crate::{make_error, make_internal_error};
 use eyre::{Report, WrapErr};
@@ -133,12 +138,16 @@ impl<'a> FastaReader<'a> {
   }
 }
 
+/// Reads the first sequence from a FASTA file. GFF3 files with sequences embedded into the '##FASTA' section are
+/// also accepted.
 pub fn read_one_fasta(filepath: impl AsRef<Path>) -> Result<FastaRecord, Report> {
   let filepath = filepath.as_ref();
-  let mut reader = FastaReader::from_path(filepath)?;
-  let mut record = FastaRecord::default();
-  reader.read(&mut record)?;
-  Ok(record)
+  let mut file = open_file_or_stdin(&Some(filepath))?;
+  let mut contents = String::new();
+  file
+    .read_to_string(&mut contents)
+    .wrap_err_with(|| format!("When reading file: {filepath:?}"))?;
+  read_one_fasta_str(contents).wrap_err_with(|| format!("When reading file: {filepath:?}"))
 }
 
 pub fn read_many_fasta<P: AsRef<Path>>(filepaths: &[P]) -> Result<Vec<FastaRecord>, Report> {
@@ -158,12 +167,43 @@ pub fn read_many_fasta<P: AsRef<Path>>(filepaths: &[P]) -> Result<Vec<FastaRecor
 }
 
 pub fn read_one_fasta_str(contents: impl AsRef<str>) -> Result<FastaRecord, Report> {
+  let contents = fasta_part_of_str(contents.as_ref())?;
   let mut reader = FastaReader::from_str(&contents)?;
   let mut record = FastaRecord::default();
   reader.read(&mut record)?;
   Ok(record)
 }
 
+pub fn read_many_fasta_str(contents: impl AsRef<str>) -> Result<Vec<FastaRecord>, Report> {
+  let contents = fasta_part_of_str(contents.as_ref())?;
+  let mut reader = FastaReader::from_str(&contents)?;
+  let mut fasta_records = Vec::<FastaRecord>::new();
+
+  loop {
+    let mut record = FastaRecord::default();
+    reader.read(&mut record)?;
+    if record.is_empty() {
+      break;
+    }
+    fasta_records.push(record);
+  }
+
+  Ok(fasta_records)
+}
+
+/// Extracts sequences embedded into a GFF3 file. Contents of other files are returned unchanged.
+fn fasta_part_of_str(contents: &str) -> Result<&str, Report> {
+  if !is_gff3_str(contents) {
+    return Ok(contents);
+  }
+  match split_gff3_fasta_section(contents) {
+    (_, Some(fasta)) => Ok(fasta),
+    (_, None) => make_error!(
+      "Expected a FASTA file, but found a GFF3 file without embedded sequences. GFF3 files can only be used in place of FASTA files if they contain a '##FASTA' section with sequences."
+    ),
+  }
+}
+
 // Writes sequences into given fasta file
 pub struct FastaWriter {
   writer: Box<dyn std::io::Write>,
@@ -202,6 +242,28 @@ struct OutputTranslationsTemplateContext<'a> {
 
 pub type FastaPeptideWritersMap = BTreeMap<String, FastaWriter>;
 
+/// Creates a fasta writer for every CDS, with the file path rendered from the given template containing variable `{cds}`
+fn create_cds_fasta_writers(gene_map: &GeneMap, output_template: &str) -> Result<FastaPeptideWritersMap, Report> {
+  let mut tt = TinyTemplate::new();
+  tt.add_template("output_translations", output_template)
+    .wrap_err_with(|| format!("When parsing template: {output_template}"))?;
+
+  gene_map
+    .iter_cdses()
+    .map(|cds| -> Result<_, Report> {
+      let template_context = OutputTranslationsTemplateContext { cds: &cds.name };
+      let rendered_path = tt.render("output_translations", &template_context).wrap_err_with(|| {
+        format!("When rendering output path template: '{output_template}', using context: {template_context:?}")
+      })?;
+      let out_gene_fasta_path =
+        PathBuf::from_str(&rendered_path).wrap_err_with(|| format!("Invalid output path: '{rendered_path}'"))?;
+      trace!("Creating fasta writer to file {out_gene_fasta_path:#?}");
+      let writer = FastaWriter::from_path(&out_gene_fasta_path)?;
+      Ok((cds.name.clone(), writer))
+    })
+    .collect()
+}
+
 /// Writes peptides, each into a separate fasta file
 pub struct FastaPeptideWriter {
   writers: FastaPeptideWritersMap,
@@ -209,33 +271,70 @@ pub struct FastaPeptideWriter {
 
 impl FastaPeptideWriter {
   pub fn new(gene_map: &GeneMap, output_translations: impl AsRef<str>) -> Result<Self, Report> {
-    let output_translations = output_translations.as_ref();
+    let writers = create_cds_fasta_writers(gene_map, output_translations.as_ref())?;
+    Ok(Self { writers })
+  }
+
+  pub fn write(&mut self, seq_name: &str, translation: &CdsTranslation) -> Result<(), Report> {
+    match self.writers.get_mut(&translation.name) {
+      None => make_internal_error!("Fasta file writer not found for gene '{}'", &translation.name),
+      Some(writer) => writer.write(seq_name, &from_aa_seq(&translation.seq), false),
+    }
+  }
+}
 
-    let mut tt = TinyTemplate::new();
-    tt.add_template("output_translations", output_translations)
-      .wrap_err_with(|| format!("When parsing template: {output_translations}"))?;
+/// Writes nucleotide sequences of CDSes, each into a separate fasta file
+pub struct FastaNucCdsWriter {
+  writers: FastaPeptideWritersMap,
+  cdses: BTreeMap<String, Cds>,
+  strip_gaps: bool,
+}
 
-    let writers = gene_map
+impl FastaNucCdsWriter {
+  pub fn new(gene_map: &GeneMap, output_nuc_cds: impl AsRef<str>, strip_gaps: bool) -> Result<Self, Report> {
+    let writers = create_cds_fasta_writers(gene_map, output_nuc_cds.as_ref())?;
+    let cdses = gene_map
       .iter_cdses()
-      .map(|cds| -> Result<_, Report> {
-        let template_context = OutputTranslationsTemplateContext { cds: &cds.name };
-        let rendered_path = tt
-          .render("output_translations", &template_context)
-          .wrap_err_with(|| format!("When rendering output translations path template: '{output_translations}', using context: {template_context:?}"))?;
-        let out_gene_fasta_path = PathBuf::from_str(&rendered_path).wrap_err_with(|| format!("Invalid output translations path: '{rendered_path}'"))?;
-        trace!("Creating fasta writer to file {out_gene_fasta_path:#?}");
-        let writer = FastaWriter::from_path(&out_gene_fasta_path)?;
-        Ok((cds.name.clone(), writer))
-      })
-      .collect::<Result<FastaPeptideWritersMap, Report>>()?;
+      .map(|cds| (cds.name.clone(), cds.clone()))
+      .collect();
+    Ok(Self {
+      writers,
+      cdses,
+      strip_gaps,
+    })
+  }
 
+  /// Writes nucleotide sequence of a CDS, extracted from the given sequence in reference coordinates (aligned, with
+  /// insertions stripped). Deletions are kept as gaps, unless gap stripping is requested.
+  pub fn write(&mut self, seq_name: &str, seq: &[Nuc], cds_name: &str) -> Result<(), Report> {
+    let (Some(cds), Some(writer)) = (self.cdses.get(cds_name), self.writers.get_mut(cds_name)) else {
+      return make_internal_error!("Fasta file writer not found for CDS '{cds_name}'");
+    };
+
+    let mut cds_seq = extract_cds_from_ref(seq, cds);
+    if self.strip_gaps {
+      cds_seq.retain(|nuc| !nuc.is_gap());
+    }
+
+    writer.write(seq_name, &from_nuc_seq(&cds_seq), false)
+  }
+}
+
+/// Writes codon alignments of CDSes, each into a separate fasta file
+pub struct FastaCodonAlignmentWriter {
+  writers: FastaPeptideWritersMap,
+}
+
+impl FastaCodonAlignmentWriter {
+  pub fn new(gene_map: &GeneMap, output_codon_alignment: impl AsRef<str>) -> Result<Self, Report> {
+    let writers = create_cds_fasta_writers(gene_map, output_codon_alignment.as_ref())?;
     Ok(Self { writers })
   }
 
   pub fn write(&mut self, seq_name: &str, translation: &CdsTranslation) -> Result<(), Report> {
     match self.writers.get_mut(&translation.name) {
-      None => make_internal_error!("Fasta file writer not found for gene '{}'", &translation.name),
-      Some(writer) => writer.write(seq_name, &from_aa_seq(&translation.seq), false),
+      None => make_internal_error!("Fasta file writer not found for CDS '{}'", &translation.name),
+      Some(writer) => writer.write(seq_name, &from_nuc_seq(&translation.codon_alignment), false),
     }
   }
 }
@@ -81,6 +81,7 @@ pub struct GffCommonInfo {
   pub name: Option<String>,
   pub range: NucRefGlobalRange,
   pub strand: GeneStrand,
+  pub phase: Option<u8>,
   pub exceptions: Vec<String>,
   pub notes: Vec<String>,
   pub is_circular: bool,
@@ -106,6 +107,8 @@ impl GffCommonInfo {
     // NOTE: assume 'forward' strand by default because 'unknown' does not make sense in this application
     let strand = record.strand().map_or(GeneStrand::Forward, GeneStrand::from);
 
+    let phase = parse_gff_phase(&gff_record_str)?;
+
     let attr_keys = record.attributes().keys().sorted().unique().collect_vec();
 
     let exception_attr_keys = {
@@ -153,6 +156,7 @@ impl GffCommonInfo {
       name,
       range,
       strand,
+      phase,
       exceptions,
       notes,
       is_circular,
@@ -162,6 +166,22 @@ impl GffCommonInfo {
   }
 }
 
+/// Retrieve phase (column 8) from a GFF record line. Phase is the number of nucleotides to be removed from the 5' end
+/// of a CDS feature to reach the first base of the next codon. Returns `None` if phase is not specified.
+fn parse_gff_phase(gff_record_str: &str) -> Result<Option<u8>, Report> {
+  let phase = gff_record_str.trim_end().split('\t').nth(7).unwrap_or(".").trim();
+  match phase {
+    "" | "." => Ok(None),
+    "0" => Ok(Some(0)),
+    "1" => Ok(Some(1)),
+    "2" => Ok(Some(2)),
+    _ => Err(eyre!(
+      "Unexpected value in 'phase' column of GFF record: '{phase}'. Expected values are: '.', '0', '1', '2'"
+    ))
+    .with_section(|| gff_record_str.to_owned().header("GFF record:")),
+  }
+}
+
 /// Retrieve GFF records of a certain "feature_type"
 fn get_records_by_feature_type<'r>(
   records: &'r [(usize, GffRecord)],
@@ -232,6 +252,32 @@ pub fn get_all_attributes(record: &GffRecord, attr_names: &[&str]) -> Result<Vec
     .collect::<Result<Vec<String>, Report>>()
 }
 
+/// Checks whether the content looks like a GFF3 file (starts with the mandatory '##gff-version' directive)
+pub fn is_gff3_str(content: &str) -> bool {
+  content.trim_start().starts_with("##gff-version")
+}
+
+/// Splits content of a GFF3 file into the annotation part and the part containing sequences embedded into the file.
+///
+/// The sequences follow the '##FASTA' directive at the end of the file. Some tools omit the directive, so the first
+/// line starting with '>' is also treated as the beginning of the sequences part. Returns `None` in place of the
+/// sequences part if there are no embedded sequences.
+#[allow(clippy::string_slice)]
+pub fn split_gff3_fasta_section(content: &str) -> (&str, Option<&str>) {
+  let mut offset = 0;
+  for line in content.split_inclusive('\n') {
+    let line_trimmed = line.trim();
+    if line_trimmed.starts_with("##FASTA") {
+      return (&content[..offset], Some(&content[offset + line.len()..]));
+    }
+    if line_trimmed.starts_with('>') {
+      return (&content[..offset], Some(&content[offset..]));
+    }
+    offset += line.len();
+  }
+  (content, None)
+}
+
 /// Prints GFF record as it is in the input file
 pub fn gff_record_to_string(record: &GffRecord) -> Result<String, Report> {
   let mut buf = Vec::<u8>::new();
@@ -245,6 +291,7 @@ pub fn gff_record_to_string(record: &GffRecord) -> Result<String, Report> {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::features::feature_tree::FeatureTree;
   use crate::gene::gene_map::GeneMap;
   use crate::utils::error::report_to_string;
   use pretty_assertions::assert_eq;
@@ -263,9 +310,43 @@ EPI1857216	feature	gene	1036	1698	.	+	.	gene_name="HA2"
 
     assert_eq!(
       report_to_string(&result.unwrap_err()),
-      "Length of a CDS is expected to be divisible by 3, but the length of CDS 'SigPep' is 47 (it consists of 1 fragment(s) of length(s) 47). This is likely a mistake in genome annotation."
+      "Length of a CDS is expected to be divisible by 3, but the length of CDS 'SigPep' is 47 (it consists of 1 fragment(s) of length(s) 47). This is likely a mistake in genome annotation.\n\n\
+      Suggested changes to the genome annotation:\n  \
+      - Length of CDS 'SigPep' is 47, which is not divisible by 3. Trim 2 nucleotide(s) from its 3' end: change gene record 'SigPep' 1..47 (+) to 1..45\n  \
+      - Length of CDS 'HA1' is 988, which is not divisible by 3. Trim 1 nucleotide(s) from its 3' end: change gene record 'HA1' 48..1035 (+) to 48..1034\n\n\
+      Use 'nextclade read-annotation --fix-annotation-into=<file>' to write the annotation with these changes applied, review it and use it instead of the original annotation."
+    );
+
+    Ok(())
+  }
+
+  #[rstest]
+  fn gff3_checks_embedded_sequence_length() -> Result<(), Report> {
+    let result = FeatureTree::from_gff3_str(
+      r#"##gff-version 3
+##sequence-region seq 1 12
+seq	feature	gene	1	12	.	+	.	gene_name="A"
+##FASTA
+>seq
+ACGTACGT
+"#,
+    );
+
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "When checking sequences embedded into GFF3 file against the genome annotation: Sequence region 'seq' ends at position 12, but the corresponding sequence 'seq' has length 8"
     );
 
     Ok(())
   }
+
+  #[rstest]
+  #[case::directive("##gff-version 3\nseq\t.\tgene\t1\t3\n##FASTA\n>seq\nACG\n", Some(">seq\nACG\n"))]
+  #[case::no_directive("##gff-version 3\nseq\t.\tgene\t1\t3\n>seq\nACG\n", Some(">seq\nACG\n"))]
+  #[case::no_sequences("##gff-version 3\nseq\t.\tgene\t1\t3\n", None)]
+  fn gff3_splits_fasta_section(#[case] content: &str, #[case] expected: Option<&str>) {
+    let (gff, fasta) = split_gff3_fasta_section(content);
+    assert_eq!(gff, "##gff-version 3\nseq\t.\tgene\t1\t3\n");
+    assert_eq!(fasta, expected);
+  }
 }
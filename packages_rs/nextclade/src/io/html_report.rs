@@ -0,0 +1,403 @@
+use crate::io::file::create_file_or_stdout;
+use crate::io::result_summary::{has_qc_results, summary_properties};
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::info::this_package_version_str;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Width of the bins of the histogram of overall QC scores. Scores above the last bin are counted in the last bin.
+const QC_SCORE_BIN_WIDTH: f64 = 10.0;
+
+const NUM_QC_SCORE_BINS: usize = 10;
+
+/// Row of the table of sequences of the report
+#[derive(Clone, Debug)]
+struct HtmlReportSample {
+  index: usize,
+  seq_name: String,
+  clade: String,
+  qc_status: String,
+  qc_score: Option<f64>,
+  coverage: Option<f64>,
+  total_substitutions: Option<usize>,
+  total_deletions: Option<usize>,
+  total_insertions: Option<usize>,
+  details: Vec<(&'static str, String)>,
+}
+
+/// Contents of the HTML report, sorted by sequence index
+#[derive(Clone, Debug, Default)]
+pub struct HtmlReport {
+  samples: Vec<HtmlReportSample>,
+}
+
+/// Collects the parts of analysis results shown in the HTML report, one sequence at a time
+#[derive(Clone, Debug, Default)]
+pub struct HtmlReportBuilder {
+  samples: Vec<HtmlReportSample>,
+}
+
+impl HtmlReportBuilder {
+  pub fn add(&mut self, output: &NextcladeOutputs) {
+    let has_qc = has_qc_results(&output.qc);
+    self.samples.push(HtmlReportSample {
+      index: output.index,
+      seq_name: output.seq_name.clone(),
+      clade: output.clade.clone(),
+      qc_status: if has_qc {
+        output.qc.overall_status.to_string()
+      } else {
+        String::new()
+      },
+      qc_score: has_qc.then_some(output.qc.overall_score),
+      coverage: Some(output.coverage),
+      total_substitutions: Some(output.total_substitutions),
+      total_deletions: Some(output.total_deletions),
+      total_insertions: Some(output.total_insertions),
+      details: summary_properties(output),
+    });
+  }
+
+  /// Adds a sequence which failed to be analyzed
+  pub fn add_failed(&mut self, index: usize, seq_name: &str, error: &str) {
+    self.samples.push(HtmlReportSample {
+      index,
+      seq_name: seq_name.to_owned(),
+      clade: String::new(),
+      qc_status: "failed".to_owned(),
+      qc_score: None,
+      coverage: None,
+      total_substitutions: None,
+      total_deletions: None,
+      total_insertions: None,
+      details: vec![("Error", error.to_owned())],
+    });
+  }
+
+  pub fn finish(mut self) -> HtmlReport {
+    self.samples.sort_by_key(|sample| sample.index);
+    HtmlReport { samples: self.samples }
+  }
+}
+
+pub fn html_report_write(filepath: impl AsRef<Path>, report: &HtmlReport, title: &str) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut file = create_file_or_stdout(filepath)?;
+  write!(file, "{}", html_report_to_string(report, title))
+    .wrap_err_with(|| format!("When writing HTML report: {filepath:#?}"))
+}
+
+/// Renders a self-contained HTML page, with styles and scripts inline, such that it can be opened from disk or shared
+/// without a web server: summary, clade counts, distribution of QC statuses and scores, and a table of sequences which
+/// can be filtered and sorted, with details of each sequence.
+pub fn html_report_to_string(report: &HtmlReport, title: &str) -> String {
+  let samples = &report.samples;
+  let num_failed = samples.iter().filter(|sample| sample.qc_status == "failed").count();
+  let qc_status_counts = ["good", "mediocre", "bad", "failed"]
+    .into_iter()
+    .map(|status| {
+      let count = samples.iter().filter(|sample| sample.qc_status == status).count();
+      (status.to_owned(), count)
+    })
+    .collect_vec();
+
+  let summary = html_table(
+    &["Property", "Value"],
+    &[
+      vec![html_escape("Sequences"), samples.len().to_string()],
+      vec![html_escape("Analyzed"), (samples.len() - num_failed).to_string()],
+      vec![html_escape("Failed"), num_failed.to_string()],
+    ],
+  );
+
+  let clade_counts = samples
+    .iter()
+    .filter(|sample| !sample.clade.is_empty())
+    .counts_by(|sample| sample.clade.as_str())
+    .into_iter()
+    .collect::<BTreeMap<_, _>>()
+    .into_iter()
+    .map(|(clade, count)| (clade.to_owned(), count))
+    .collect_vec();
+
+  let qc_score_bins = qc_score_histogram(samples.iter().filter_map(|sample| sample.qc_score))
+    .into_iter()
+    .enumerate()
+    .map(|(i, count)| (qc_score_bin_label(i), count))
+    .collect_vec();
+
+  format!(
+    r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>{HTML_REPORT_STYLE}</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p class="meta">Generated by Nextclade {version}</p>
+<h2>Summary</h2>
+{summary}
+<h2>Clades</h2>
+{clades}
+<h2>Quality control</h2>
+<h3>QC status</h3>
+{qc_statuses}
+<h3>Overall QC score</h3>
+{qc_scores}
+<h2>Sequences</h2>
+<div class="controls">
+<input id="filter" type="search" placeholder="Filter by sequence name or clade">
+<select id="status">
+<option value="">All QC statuses</option>
+<option value="good">good</option>
+<option value="mediocre">mediocre</option>
+<option value="bad">bad</option>
+<option value="failed">failed</option>
+</select>
+</div>
+{sequences}
+<script>{HTML_REPORT_SCRIPT}</script>
+</body>
+</html>
+"#,
+    title = html_escape(title),
+    version = this_package_version_str(),
+    clades = html_bar_table("Clade", &clade_counts),
+    qc_statuses = html_bar_table("Status", &qc_status_counts),
+    qc_scores = html_bar_table("Score", &qc_score_bins),
+    sequences = html_samples_table(samples),
+  )
+}
+
+/// Counts overall QC scores in bins of fixed width
+fn qc_score_histogram(scores: impl Iterator<Item = f64>) -> [usize; NUM_QC_SCORE_BINS] {
+  let mut bins = [0; NUM_QC_SCORE_BINS];
+  for score in scores {
+    let bin = (score.max(0.0) / QC_SCORE_BIN_WIDTH).floor() as usize;
+    bins[bin.min(NUM_QC_SCORE_BINS - 1)] += 1;
+  }
+  bins
+}
+
+fn qc_score_bin_label(bin: usize) -> String {
+  let begin = bin as f64 * QC_SCORE_BIN_WIDTH;
+  if bin == NUM_QC_SCORE_BINS - 1 {
+    format!("{begin} and above")
+  } else {
+    format!("{begin} to {}", begin + QC_SCORE_BIN_WIDTH)
+  }
+}
+
+/// Table of counts, with a bar proportional to the fraction of the total in every row
+fn html_bar_table(label: &str, counts: &[(String, usize)]) -> String {
+  let total: usize = counts.iter().map(|(_, count)| count).sum();
+  let rows = counts
+    .iter()
+    .map(|(name, count)| {
+      let percent = if total > 0 {
+        *count as f64 * 100.0 / total as f64
+      } else {
+        0.0
+      };
+      vec![
+        html_escape(name),
+        count.to_string(),
+        format!(r#"<div class="bar" style="width: {percent:.1}%"></div> {percent:.1}%"#),
+      ]
+    })
+    .collect_vec();
+  html_table(&[label, "Count", "Fraction"], &rows)
+}
+
+/// Table with a header row. Cells are expected to be escaped.
+fn html_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+  let headers = headers
+    .iter()
+    .map(|header| format!("<th>{}</th>", html_escape(header)))
+    .join("");
+  let rows = rows
+    .iter()
+    .map(|row| {
+      format!(
+        "<tr>{}</tr>",
+        row.iter().map(|cell| format!("<td>{cell}</td>")).join("")
+      )
+    })
+    .join("\n");
+  format!("<table>\n<thead><tr>{headers}</tr></thead>\n<tbody>\n{rows}\n</tbody>\n</table>")
+}
+
+/// Table of sequences. Cells carry their raw values for sorting, and rows carry the text to filter by.
+fn html_samples_table(samples: &[HtmlReportSample]) -> String {
+  let headers = [
+    ("Index", "number"),
+    ("Sequence", "text"),
+    ("Clade", "text"),
+    ("QC status", "text"),
+    ("QC score", "number"),
+    ("Coverage", "number"),
+    ("Substitutions", "number"),
+    ("Deletions", "number"),
+    ("Insertions", "number"),
+  ]
+  .iter()
+  .map(|(header, kind)| format!(r#"<th data-type="{kind}">{header}</th>"#))
+  .join("");
+
+  let rows = samples
+    .iter()
+    .map(|sample| {
+      let details = sample
+        .details
+        .iter()
+        .map(|(name, value)| format!("<dt>{}</dt><dd>{}</dd>", html_escape(name), html_escape(value)))
+        .join("");
+
+      let cells = [
+        html_cell(&sample.index.to_string(), &sample.index.to_string()),
+        format!(
+          r#"<td data-value="{name}"><details><summary>{name}</summary><dl>{details}</dl></details></td>"#,
+          name = html_escape(&sample.seq_name)
+        ),
+        html_cell(&sample.clade, &sample.clade),
+        format!(
+          r#"<td data-value="{status}" class="status-{status}">{status}</td>"#,
+          status = html_escape(&sample.qc_status)
+        ),
+        html_optional_cell(sample.qc_score, |score| format!("{score:.1}")),
+        html_optional_cell(sample.coverage, |coverage| format!("{:.1}%", coverage * 100.0)),
+        html_optional_cell(sample.total_substitutions, |n| n.to_string()),
+        html_optional_cell(sample.total_deletions, |n| n.to_string()),
+        html_optional_cell(sample.total_insertions, |n| n.to_string()),
+      ]
+      .join("");
+
+      format!(
+        r#"<tr data-search="{search}" data-status="{status}">{cells}</tr>"#,
+        search = html_escape(&format!("{} {}", sample.seq_name, sample.clade).to_lowercase()),
+        status = html_escape(&sample.qc_status),
+      )
+    })
+    .join("\n");
+
+  format!(
+    r#"<table id="samples">
+<thead><tr>{headers}</tr></thead>
+<tbody>
+{rows}
+</tbody>
+</table>"#
+  )
+}
+
+fn html_cell(value: &str, display: &str) -> String {
+  format!(
+    r#"<td data-value="{}">{}</td>"#,
+    html_escape(value),
+    html_escape(display)
+  )
+}
+
+fn html_optional_cell<T: ToString + Copy>(value: Option<T>, format: impl Fn(T) -> String) -> String {
+  match value {
+    Some(value) => html_cell(&value.to_string(), &format(value)),
+    None => html_cell("", ""),
+  }
+}
+
+fn html_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&#39;")
+}
+
+const HTML_REPORT_STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.6em; }
+.meta { color: #777; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+th { background: #f4f4f4; }
+#samples th { cursor: pointer; user-select: none; }
+#samples th[data-order="asc"]::after { content: " \25B2"; }
+#samples th[data-order="desc"]::after { content: " \25BC"; }
+.bar { display: inline-block; height: 0.8em; background: #4a90d9; vertical-align: middle; max-width: 20em; min-width: 1px; }
+.controls { margin-bottom: 0.8em; }
+.controls input { width: 20em; }
+.status-good { color: #2a8a2a; }
+.status-mediocre { color: #c08000; }
+.status-bad, .status-failed { color: #c02020; }
+details summary { cursor: pointer; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; margin: 0.5em 0; }
+dt { font-weight: bold; }
+dd { margin: 0; }
+"#;
+
+const HTML_REPORT_SCRIPT: &str = r#"
+(function () {
+  const table = document.getElementById("samples");
+  const tbody = table.tBodies[0];
+  const headers = Array.from(table.tHead.rows[0].cells);
+  const filter = document.getElementById("filter");
+  const status = document.getElementById("status");
+
+  function applyFilter() {
+    const text = filter.value.toLowerCase();
+    for (const row of tbody.rows) {
+      const matches = row.dataset.search.includes(text) && (!status.value || row.dataset.status === status.value);
+      row.hidden = !matches;
+    }
+  }
+  filter.addEventListener("input", applyFilter);
+  status.addEventListener("change", applyFilter);
+
+  headers.forEach(function (th, col) {
+    th.addEventListener("click", function () {
+      const asc = th.dataset.order !== "asc";
+      headers.forEach(function (other) { delete other.dataset.order; });
+      th.dataset.order = asc ? "asc" : "desc";
+      const numeric = th.dataset.type === "number";
+      const key = function (row) {
+        const value = row.cells[col].dataset.value;
+        return numeric ? (value === "" ? -Infinity : Number(value)) : value.toLowerCase();
+      };
+      const rows = Array.from(tbody.rows).sort(function (a, b) {
+        const x = key(a);
+        const y = key(b);
+        const order = x < y ? -1 : x > y ? 1 : 0;
+        return asc ? order : -order;
+      });
+      rows.forEach(function (row) { tbody.appendChild(row); });
+    });
+  });
+})();
+"#;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn counts_qc_scores_in_bins() {
+    let bins = qc_score_histogram([0.0, 9.9, 10.0, 55.0, 99.0, 100.0, 1500.0].into_iter());
+    assert_eq!(bins, [2, 1, 0, 0, 0, 1, 0, 0, 0, 3]);
+  }
+
+  #[rstest]
+  fn escapes_html() {
+    assert_eq!(
+      html_escape(r#"<a href="x">B&'C'</a>"#),
+      "&lt;a href=&quot;x&quot;&gt;B&amp;&#39;C&#39;&lt;/a&gt;"
+    );
+  }
+}
@@ -0,0 +1,225 @@
+use crate::graph::node::GraphNodeKey;
+use crate::graph::traits::{HasDivergence, HasName};
+use crate::io::json::{json_write, JsonPretty};
+use crate::io::nwk_writer::nwk_quote_name;
+use crate::tree::tree::{AuspiceGraph, DivergenceUnits};
+use crate::tree::tree_find_nearest_node::PlacementCandidates;
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::info::this_package_version_str;
+use crate::{make_error, make_internal_error, make_internal_report};
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use traversal::DftPost;
+
+const JPLACE_VERSION: u32 = 3;
+
+const JPLACE_FIELDS: &[&str] = &["edge_num", "like_weight_ratio", "distal_length", "pendant_length"];
+
+/// Phylogenetic placements in jplace format (version 3).
+///
+/// See: Matsen et al. (2012) "A Format for Phylogenetic Placements", https://doi.org/10.1371/journal.pone.0031009
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Jplace {
+  pub version: u32,
+  /// Reference tree in Newick format, with edge numbers in curly braces after branch lengths
+  pub tree: String,
+  pub fields: Vec<String>,
+  pub placements: Vec<JplacePlacement>,
+  pub metadata: BTreeMap<String, String>,
+}
+
+/// Placements of one query sequence: a row per candidate edge, with values in the order of `fields`, and the name of
+/// the sequence
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JplacePlacement {
+  pub p: Vec<(usize, f64, f64, f64)>,
+  pub n: Vec<String>,
+}
+
+/// Parts of the results of a query sequence needed for the jplace output, such that the full results do not need to be
+/// held in memory until the end of the run
+#[derive(Clone, Debug)]
+pub struct JplaceQuery {
+  pub seq_name: String,
+  pub nearest_node_id: GraphNodeKey,
+  pub divergence: f64,
+  pub placement_candidates: Option<PlacementCandidates>,
+}
+
+impl From<&NextcladeOutputs> for JplaceQuery {
+  fn from(output: &NextcladeOutputs) -> Self {
+    Self {
+      seq_name: output.seq_name.clone(),
+      nearest_node_id: output.nearest_node_id,
+      divergence: output.divergence,
+      placement_candidates: output.placement_candidates.clone(),
+    }
+  }
+}
+
+/// Writes placements of the query sequences onto the reference tree in jplace format. The graph should be the reference
+/// tree, before the query sequences are attached to it.
+pub fn jplace_write_to_file(
+  filepath: impl AsRef<Path>,
+  graph: &AuspiceGraph,
+  queries: &[JplaceQuery],
+  ref_seq_len: usize,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let jplace = jplace_from_queries(graph, queries, ref_seq_len)?;
+  json_write(filepath, &jplace, JsonPretty(true)).wrap_err_with(|| format!("When writing jplace file: {filepath:#?}"))
+}
+
+/// Converts placements of the query sequences onto the reference tree to jplace format.
+///
+/// Query sequences are attached to the tree at their nearest nodes, i.e. at the distal end of the edge above the nearest
+/// node, so the distal length is 0 (the root has no edge above it, so placements onto the root are at the proximal end of
+/// one of its child edges instead). The pendant length is the branch length of the query. If candidate placements are
+/// requested (`--num-placement-candidates`), they are all listed along with their relative weights and with the
+/// placement distances converted to the units of divergence, otherwise only the nearest node is listed, with the weight
+/// of 1.
+pub fn jplace_from_queries(
+  graph: &AuspiceGraph,
+  queries: &[JplaceQuery],
+  ref_seq_len: usize,
+) -> Result<Jplace, Report> {
+  let mut edges = BTreeMap::<GraphNodeKey, usize>::new();
+  let tree = jplace_tree(graph, &mut edges)?;
+
+  let node_keys_by_name: BTreeMap<&str, GraphNodeKey> = graph
+    .iter_nodes()
+    .map(|node| (node.payload().name(), node.key()))
+    .collect();
+
+  let distance_to_branch_length = |distance: i64| {
+    let distance = distance.max(0) as f64;
+    if graph.data.tmp.divergence_units == DivergenceUnits::NumSubstitutionsPerYearPerSite {
+      distance / ref_seq_len as f64
+    } else {
+      distance
+    }
+  };
+
+  let placements = queries
+    .iter()
+    .map(|query| {
+      let p = if let Some(placement_candidates) = &query.placement_candidates {
+        placement_candidates
+          .candidates
+          .iter()
+          .map(|candidate| {
+            let Some(&node_key) = node_keys_by_name.get(candidate.node_name.as_str()) else {
+              return make_error!("Node '{}' is not found in the reference tree", candidate.node_name);
+            };
+            let (edge_num, distal_length) = jplace_edge_of(graph, node_key, &edges)?;
+            Ok((
+              edge_num,
+              candidate.weight,
+              distal_length,
+              distance_to_branch_length(candidate.distance),
+            ))
+          })
+          .collect::<Result<Vec<_>, Report>>()?
+      } else {
+        let (edge_num, distal_length) = jplace_edge_of(graph, query.nearest_node_id, &edges)?;
+        let nearest_node_div = graph.get_node(query.nearest_node_id)?.payload().divergence();
+        let pendant_length = (query.divergence - nearest_node_div).max(0.0);
+        vec![(edge_num, 1.0, distal_length, pendant_length)]
+      };
+      Ok(JplacePlacement {
+        p,
+        n: vec![query.seq_name.clone()],
+      })
+    })
+    .collect::<Result<Vec<_>, Report>>()?;
+
+  Ok(Jplace {
+    version: JPLACE_VERSION,
+    tree: format!("{tree};"),
+    fields: JPLACE_FIELDS.iter().map(|&field| field.to_owned()).collect(),
+    placements,
+    metadata: BTreeMap::from([(
+      "invocation".to_owned(),
+      format!("nextclade {}", this_package_version_str()),
+    )]),
+  })
+}
+
+/// Finds the edge number and the distal length of a placement onto the given node
+fn jplace_edge_of(
+  graph: &AuspiceGraph,
+  node_key: GraphNodeKey,
+  edges: &BTreeMap<GraphNodeKey, usize>,
+) -> Result<(usize, f64), Report> {
+  if let Some(&edge_num) = edges.get(&node_key) {
+    return Ok((edge_num, 0.0));
+  }
+
+  // The root: place at the proximal end of the edge of its first child
+  let node = graph.get_node(node_key)?;
+  let Some((child_key, &edge_num)) = graph
+    .iter_child_keys_of_by_key(node_key)
+    .find_map(|child_key| edges.get(&child_key).map(|edge_num| (child_key, edge_num)))
+  else {
+    return make_error!("Unable to find an edge for placement onto node '{}'", node.payload().name());
+  };
+  let branch_length = graph.get_node(child_key)?.payload().divergence() - node.payload().divergence();
+  Ok((edge_num, branch_length))
+}
+
+/// Converts the tree to Newick format, numbering the edges in postorder.
+///
+/// Nodes are visited in postorder, without recursion, such that the subtrees of the children are always ready when
+/// their parent is visited.
+fn jplace_tree(graph: &AuspiceGraph, edges: &mut BTreeMap<GraphNodeKey, usize>) -> Result<String, Report> {
+  let root = graph.get_exactly_one_root()?;
+  let mut subtrees = HashMap::<GraphNodeKey, String>::new();
+  for (_, node) in DftPost::new(root, |node| graph.iter_children_of(node)) {
+    let payload = node.payload();
+    let name = nwk_quote_name(payload.name());
+
+    let children = graph
+      .iter_child_keys_of(node)
+      .map(|child_key| {
+        subtrees
+          .remove(&child_key)
+          .ok_or_else(|| make_internal_report!("Child node {child_key} is not converted before its parent"))
+      })
+      .collect::<Result<Vec<String>, Report>>()?;
+    let subtree = if children.is_empty() {
+      name.into_owned()
+    } else {
+      format!("({}){name}", children.join(","))
+    };
+
+    let Some(parent) = graph.parent_of(node) else {
+      // The root is the last node in postorder
+      return Ok(subtree);
+    };
+
+    let edge_num = edges.len();
+    edges.insert(node.key(), edge_num);
+    let branch_length = payload.divergence() - parent.payload().divergence();
+    subtrees.insert(node.key(), format!("{subtree}:{branch_length}{{{edge_num}}}"));
+  }
+  make_internal_error!("Root node is not converted")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn numbers_edges_of_jplace_tree_in_postorder() -> Result<(), Report> {
+    let graph = convert_auspice_tree_to_graph(auspice_tree_from_nwk_str("((A:1,B:2)X:1,C:3)R;", None)?)?;
+    let jplace = jplace_from_queries(&graph, &[], 100)?;
+    assert_eq!(jplace.tree, "((A:1{0},B:2{1})X:1{2},C:3{3})R;");
+    Ok(())
+  }
+}
@@ -0,0 +1,279 @@
+use crate::align::insertions_strip::Insertion;
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::{from_nuc, Nuc};
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::io::file::create_file_or_stdout;
+use crate::make_error;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::Report;
+use itertools::Itertools;
+use std::io::Write;
+use std::path::Path;
+
+/// Sequence line (`s` line) of a block of Multiple Alignment Format (MAF)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MafRow {
+  pub src: String,
+  /// Start of the aligned region, 0-based. For the reverse strand, counted from the end of the source sequence.
+  pub start: usize,
+  /// Number of non-gap letters in the aligned region
+  pub size: usize,
+  pub is_reverse_complement: bool,
+  /// Length of the entire source sequence
+  pub src_size: usize,
+  pub text: String,
+}
+
+/// Alignment block (`a` paragraph) of Multiple Alignment Format (MAF): pairwise alignment of a query sequence to the
+/// reference
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MafBlock {
+  pub score: i32,
+  pub reference: MafRow,
+  pub query: MafRow,
+}
+
+impl MafBlock {
+  /// Restores the pairwise alignment of the query to the reference, including insertions, from the query aligned and
+  /// stripped of insertions. The block spans the aligned region of the reference (`alignment_range`), so that the
+  /// unsequenced ends of the query are not included.
+  pub fn new(
+    ref_name: &str,
+    ref_seq: &[Nuc],
+    seq_name: &str,
+    qry_seq: &[Nuc],
+    insertions: &[Insertion<Nuc>],
+    alignment_range: &NucRefGlobalRange,
+    alignment_score: i32,
+    is_reverse_complement: bool,
+  ) -> Self {
+    let begin = alignment_range.begin.as_isize();
+    let end = alignment_range.end.as_isize();
+
+    // Insertions at position `pos` are located after the reference nucleotide at this position
+    let insertions = insertions.iter().sorted_by_key(|insertion| insertion.pos).collect_vec();
+
+    let qry_src_size = qry_seq.iter().filter(|nuc| !nuc.is_gap()).count()
+      + insertions.iter().map(|insertion| insertion.ins.len()).sum::<usize>();
+
+    // Query nucleotides inserted before the aligned region are not part of the block
+    let qry_start = insertions
+      .iter()
+      .filter(|insertion| (insertion.pos as isize) < begin - 1)
+      .map(|insertion| insertion.ins.len())
+      .sum();
+
+    let capacity = alignment_range.len() + insertions.iter().map(|insertion| insertion.ins.len()).sum::<usize>();
+    let mut ref_text = String::with_capacity(capacity);
+    let mut qry_text = String::with_capacity(capacity);
+
+    let mut insertions = insertions
+      .iter()
+      .filter(|insertion| (begin - 1..end).contains(&(insertion.pos as isize)))
+      .peekable();
+
+    while let Some(insertion) = insertions.next_if(|insertion| insertion.pos as isize == begin - 1) {
+      push_insertion(insertion, &mut ref_text, &mut qry_text);
+    }
+
+    for pos in begin..end {
+      ref_text.push(from_nuc(ref_seq[pos as usize]));
+      qry_text.push(from_nuc(qry_seq[pos as usize]));
+      while let Some(insertion) = insertions.next_if(|insertion| insertion.pos as isize == pos) {
+        push_insertion(insertion, &mut ref_text, &mut qry_text);
+      }
+    }
+
+    let qry_size = qry_text.chars().filter(|&c| c != from_nuc(Nuc::Gap)).count();
+
+    Self {
+      score: alignment_score,
+      reference: MafRow {
+        src: maf_src_name(ref_name),
+        start: alignment_range.begin.as_usize(),
+        size: alignment_range.len(),
+        is_reverse_complement: false,
+        src_size: ref_seq.len(),
+        text: ref_text,
+      },
+      query: MafRow {
+        src: maf_src_name(seq_name),
+        start: qry_start,
+        size: qry_size,
+        is_reverse_complement,
+        src_size: qry_src_size,
+        text: qry_text,
+      },
+    }
+  }
+}
+
+/// Inserted nucleotides are aligned to gaps in the reference
+fn push_insertion(insertion: &Insertion<Nuc>, ref_text: &mut String, qry_text: &mut String) {
+  for &nuc in &insertion.ins {
+    ref_text.push(from_nuc(Nuc::Gap));
+    qry_text.push(from_nuc(nuc));
+  }
+}
+
+/// MAF does not allow whitespace in sequence names
+fn maf_src_name(name: &str) -> String {
+  name.split_whitespace().join("_")
+}
+
+/// Reference sequence, against which the queries are aligned
+struct MafReference {
+  name: String,
+  seq: Vec<Nuc>,
+}
+
+/// Writes pairwise alignments of the query sequences to the reference in Multiple Alignment Format (MAF), one
+/// alignment block per sequence
+pub struct MafFileWriter {
+  writer: Box<dyn Write + Send>,
+  references: Vec<MafReference>,
+}
+
+impl MafFileWriter {
+  /// Creates the writer. Records are aligned to one of the `references` (name and sequence), given by its index.
+  pub fn new(filepath: impl AsRef<Path>, references: &[(&str, &[Nuc])]) -> Result<Self, Report> {
+    let mut writer = create_file_or_stdout(filepath)?;
+    writeln!(writer, "##maf version=1 scoring=nextclade")?;
+    writeln!(writer)?;
+    Ok(Self {
+      writer,
+      references: references
+        .iter()
+        .map(|(name, seq)| MafReference {
+          name: (*name).to_owned(),
+          seq: seq.to_vec(),
+        })
+        .collect(),
+    })
+  }
+
+  /// Writes alignment of a query (aligned and stripped of insertions) to the reference with index `ref_index`
+  pub fn write(&mut self, ref_index: usize, qry_seq: &[Nuc], outputs: &NextcladeOutputs) -> Result<(), Report> {
+    let Some(MafReference { name, seq }) = self.references.get(ref_index) else {
+      return make_error!("MAF output: reference with index {ref_index} is not known");
+    };
+
+    let NextcladeOutputs {
+      seq_name,
+      insertions,
+      alignment_range,
+      alignment_score,
+      is_reverse_complement,
+      ..
+    } = outputs;
+
+    let block = MafBlock::new(
+      name,
+      seq,
+      seq_name,
+      qry_seq,
+      insertions,
+      alignment_range,
+      *alignment_score,
+      *is_reverse_complement,
+    );
+    self.write_block(&block)
+  }
+
+  pub fn write_block(&mut self, block: &MafBlock) -> Result<(), Report> {
+    let MafBlock {
+      score,
+      reference,
+      query,
+    } = block;
+
+    let rows = [reference, query];
+    let src_width = rows.iter().map(|row| row.src.len()).max().unwrap_or_default();
+    let start_width = rows
+      .iter()
+      .map(|row| row.start.to_string().len())
+      .max()
+      .unwrap_or_default();
+    let size_width = rows
+      .iter()
+      .map(|row| row.size.to_string().len())
+      .max()
+      .unwrap_or_default();
+    let src_size_width = rows
+      .iter()
+      .map(|row| row.src_size.to_string().len())
+      .max()
+      .unwrap_or_default();
+
+    writeln!(self.writer, "a score={score}")?;
+    for row in rows {
+      let strand = if row.is_reverse_complement { '-' } else { '+' };
+      writeln!(
+        self.writer,
+        "s {:src_width$} {:>start_width$} {:>size_width$} {strand} {:>src_size_width$} {}",
+        row.src, row.start, row.size, row.src_size, row.text
+      )?;
+    }
+    writeln!(self.writer)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn restores_insertions_within_aligned_region() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq("ACGTACGTAC")?;
+    let qry_seq = to_nuc_seq("--GTA-GTA-")?;
+    let insertions = vec![
+      Insertion {
+        pos: -1,
+        ins: to_nuc_seq("TT")?,
+      },
+      Insertion {
+        pos: 4,
+        ins: to_nuc_seq("CCC")?,
+      },
+    ];
+
+    let block = MafBlock::new(
+      "ref",
+      &ref_seq,
+      "my seq",
+      &qry_seq,
+      &insertions,
+      &NucRefGlobalRange::from_usize(2, 9),
+      25,
+      false,
+    );
+
+    let expected = MafBlock {
+      score: 25,
+      reference: MafRow {
+        src: "ref".to_owned(),
+        start: 2,
+        size: 7,
+        is_reverse_complement: false,
+        src_size: 10,
+        text: "GTA---CGTA".to_owned(),
+      },
+      query: MafRow {
+        src: "my_seq".to_owned(),
+        start: 2,
+        size: 9,
+        is_reverse_complement: false,
+        src_size: 11,
+        text: "GTACCC-GTA".to_owned(),
+      },
+    };
+
+    assert_eq!(expected, block);
+    Ok(())
+  }
+}
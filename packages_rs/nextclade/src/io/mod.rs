@@ -1,3 +1,4 @@
+pub mod bed;
 pub mod compression;
 pub mod concat;
 pub mod console;
@@ -7,11 +8,22 @@ pub mod fasta;
 pub mod file;
 pub mod fs;
 pub mod gff3;
+pub mod html_report;
+pub mod jplace;
 pub mod json;
+pub mod maf;
 pub mod ndjson;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod nextclade_arrow;
 pub mod nextclade_csv;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod nextclade_parquet;
+pub mod nexus_writer;
 pub mod nwk_writer;
 pub mod parse_pos;
+pub mod result_summary;
 pub mod results_json;
 pub mod schema_version;
+pub mod usher_mat;
+pub mod vcf_writer;
 pub mod yaml;
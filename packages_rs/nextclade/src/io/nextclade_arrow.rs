@@ -0,0 +1,259 @@
+use crate::io::csv::VecWriter;
+use crate::io::file::create_file_or_stdout;
+use crate::io::nextclade_csv::{
+  format_aa_deletions, format_aa_insertions, format_aa_substitutions, format_nuc_deletions, format_nuc_insertions,
+  format_nuc_substitutions, prepare_headers, CsvColumnConfig, NextcladeResultsCsvWriter,
+};
+use crate::types::outputs::NextcladeOutputs;
+use arrow_array::builder::{ListBuilder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use eyre::{Report, WrapErr};
+use itertools::{chain, Itertools};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Number of rows buffered in memory before they are written to the file as one record batch (or row group)
+pub(crate) const ARROW_BATCH_SIZE: usize = 64 * 1024;
+
+const NUM_ARROW_LIST_COLUMNS: usize = 6;
+
+/// Typed list columns, in addition to the string columns of CSV and TSV outputs: one list item per mutation
+const ARROW_LIST_COLUMNS: [&str; NUM_ARROW_LIST_COLUMNS] = [
+  "substitutionsList",
+  "deletionsList",
+  "insertionsList",
+  "aaSubstitutionsList",
+  "aaDeletionsList",
+  "aaInsertionsList",
+];
+
+/// Collects rows of CSV values into Arrow columns. Empty values become nulls.
+struct ArrowColumns {
+  strings: Vec<StringBuilder>,
+  lists: Vec<ListBuilder<StringBuilder>>,
+  num_rows: usize,
+}
+
+impl ArrowColumns {
+  fn new(num_string_columns: usize) -> Self {
+    Self {
+      strings: (0..num_string_columns).map(|_| StringBuilder::new()).collect(),
+      lists: ARROW_LIST_COLUMNS
+        .iter()
+        .map(|_| ListBuilder::new(StringBuilder::new()))
+        .collect(),
+      num_rows: 0,
+    }
+  }
+
+  /// Adds values of the list columns of the current row. Rows of sequences which failed to be analyzed have no lists.
+  fn add_lists(&mut self, lists: Option<[Vec<String>; NUM_ARROW_LIST_COLUMNS]>) {
+    match lists {
+      Some(lists) => {
+        for (builder, values) in self.lists.iter_mut().zip(lists) {
+          for value in values {
+            builder.values().append_value(value);
+          }
+          builder.append(true);
+        }
+      }
+      None => self.lists.iter_mut().for_each(|builder| builder.append(false)),
+    }
+  }
+
+  /// Moves the collected rows into a record batch, leaving the columns empty
+  fn finish_batch(&mut self, schema: &SchemaRef) -> Result<RecordBatch, Report> {
+    self.num_rows = 0;
+    let strings = self
+      .strings
+      .iter_mut()
+      .map(|builder| Arc::new(builder.finish()) as ArrayRef);
+    let lists = self
+      .lists
+      .iter_mut()
+      .map(|builder| Arc::new(builder.finish()) as ArrayRef);
+    Ok(RecordBatch::try_new(
+      Arc::clone(schema),
+      chain!(strings, lists).collect_vec(),
+    )?)
+  }
+}
+
+impl VecWriter for ArrowColumns {
+  fn write<I: IntoIterator<Item = T>, T: AsRef<[u8]>>(&mut self, values: I) -> Result<(), Report> {
+    for (builder, value) in self.strings.iter_mut().zip(values) {
+      let value = String::from_utf8_lossy(value.as_ref());
+      if value.is_empty() {
+        builder.append_null();
+      } else {
+        builder.append_value(value);
+      }
+    }
+    self.num_rows += 1;
+    Ok(())
+  }
+}
+
+/// Builds Arrow record batches of results: the columns of nextclade.tsv, as strings, followed by the typed list columns
+/// with the mutations. Shared by the columnar output formats (Arrow IPC and Parquet).
+pub struct NextcladeResultsArrowBuilder {
+  schema: SchemaRef,
+  writer: NextcladeResultsCsvWriter<ArrowColumns>,
+}
+
+impl NextcladeResultsArrowBuilder {
+  pub fn new(
+    clade_attr_keys: &[String],
+    phenotype_attr_keys: &[String],
+    aa_motifs_keys: &[String],
+    cds_names: &[String],
+    column_config: &CsvColumnConfig,
+  ) -> Result<Self, Report> {
+    let headers: Vec<String> = prepare_headers(
+      clade_attr_keys,
+      phenotype_attr_keys,
+      aa_motifs_keys,
+      cds_names,
+      column_config,
+    );
+
+    let string_fields = headers.iter().map(|header| Field::new(header, DataType::Utf8, true));
+    let list_fields = ARROW_LIST_COLUMNS.iter().map(|&column| {
+      Field::new(
+        column,
+        DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+        true,
+      )
+    });
+    let schema = Arc::new(Schema::new(chain!(string_fields, list_fields).collect_vec()));
+
+    let writer = NextcladeResultsCsvWriter::new(ArrowColumns::new(headers.len()), &headers)?;
+
+    Ok(Self { schema, writer })
+  }
+
+  pub const fn schema(&self) -> &SchemaRef {
+    &self.schema
+  }
+
+  /// Number of rows added since the last batch was taken
+  pub fn num_rows(&mut self) -> usize {
+    self.writer.writer_mut().num_rows
+  }
+
+  pub fn write(&mut self, nextclade_outputs: &NextcladeOutputs) -> Result<(), Report> {
+    self.writer.write(nextclade_outputs)?;
+    self.writer.writer_mut().add_lists(Some([
+      list_of(&nextclade_outputs.substitutions, format_nuc_substitutions),
+      list_of(&nextclade_outputs.deletions, format_nuc_deletions),
+      list_of(&nextclade_outputs.insertions, format_nuc_insertions),
+      list_of(&nextclade_outputs.aa_substitutions, format_aa_substitutions),
+      list_of(&nextclade_outputs.aa_deletions, format_aa_deletions),
+      list_of(&nextclade_outputs.aa_insertions, format_aa_insertions),
+    ]));
+    Ok(())
+  }
+
+  /// Adds one row for the case of error
+  pub fn write_nuc_error(&mut self, index: usize, seq_name: &str, errors: &str) -> Result<(), Report> {
+    self.writer.write_nuc_error(index, seq_name, errors)?;
+    self.writer.writer_mut().add_lists(None);
+    Ok(())
+  }
+
+  /// Takes the rows added so far, as a record batch
+  pub fn finish_batch(&mut self) -> Result<RecordBatch, Report> {
+    let schema = Arc::clone(&self.schema);
+    self.writer.writer_mut().finish_batch(&schema)
+  }
+}
+
+/// Formats each item separately, the same way as in the corresponding CSV column
+fn list_of<T>(items: &[T], format: impl Fn(&[T], &str) -> String) -> Vec<String> {
+  items
+    .iter()
+    .map(|item| format(std::slice::from_ref(item), ""))
+    .collect()
+}
+
+/// Writes results into an Arrow IPC file (also known as Feather V2), in record batches of fixed size, such that memory
+/// consumption does not depend on the number of sequences.
+pub struct NextcladeResultsArrowFileWriter {
+  filepath: PathBuf,
+  builder: NextcladeResultsArrowBuilder,
+  file_writer: Option<FileWriter<Box<dyn Write + Send>>>,
+}
+
+impl NextcladeResultsArrowFileWriter {
+  pub fn new(
+    filepath: impl AsRef<Path>,
+    clade_attr_keys: &[String],
+    phenotype_attr_keys: &[String],
+    aa_motifs_keys: &[String],
+    cds_names: &[String],
+    column_config: &CsvColumnConfig,
+  ) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let builder = NextcladeResultsArrowBuilder::new(
+      clade_attr_keys,
+      phenotype_attr_keys,
+      aa_motifs_keys,
+      cds_names,
+      column_config,
+    )?;
+    let file = create_file_or_stdout(filepath)?;
+    let file_writer = FileWriter::try_new(file, builder.schema())
+      .wrap_err_with(|| format!("When creating Arrow IPC file writer: {filepath:#?}"))?;
+    Ok(Self {
+      filepath: filepath.to_owned(),
+      builder,
+      file_writer: Some(file_writer),
+    })
+  }
+
+  pub fn write(&mut self, nextclade_outputs: &NextcladeOutputs) -> Result<(), Report> {
+    self.builder.write(nextclade_outputs)?;
+    self.write_batch_maybe()
+  }
+
+  /// Writes one row for the case of error
+  pub fn write_nuc_error(&mut self, index: usize, seq_name: &str, errors: &str) -> Result<(), Report> {
+    self.builder.write_nuc_error(index, seq_name, errors)?;
+    self.write_batch_maybe()
+  }
+
+  /// Writes the remaining rows and the file footer. The file is not valid until this is called.
+  pub fn finish(&mut self) -> Result<(), Report> {
+    let Some(mut file_writer) = self.file_writer.take() else {
+      return Ok(());
+    };
+    let batch = self.builder.finish_batch()?;
+    file_writer
+      .write(&batch)
+      .and_then(|_| file_writer.finish())
+      .wrap_err_with(|| format!("When writing Arrow IPC file: {:#?}", self.filepath))
+  }
+
+  fn write_batch_maybe(&mut self) -> Result<(), Report> {
+    if self.builder.num_rows() < ARROW_BATCH_SIZE {
+      return Ok(());
+    }
+    if let Some(file_writer) = &mut self.file_writer {
+      let batch = self.builder.finish_batch()?;
+      file_writer
+        .write(&batch)
+        .wrap_err_with(|| format!("When writing Arrow IPC file: {:#?}", self.filepath))?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for NextcladeResultsArrowFileWriter {
+  #[allow(unused_must_use)]
+  fn drop(&mut self) {
+    self.finish();
+  }
+}
@@ -1,16 +1,27 @@
+use crate::align::alignment_diagnostics::AlignmentDiagnostics;
+use crate::align::homopolymer_indels::HomopolymerIndel;
 use crate::align::insertions_strip::{AaIns, Insertion};
 use crate::alphabet::aa::from_aa_seq;
 use crate::alphabet::nuc::{from_nuc, from_nuc_seq, Nuc};
 use crate::analyze::aa_del::AaDel;
 use crate::analyze::aa_sub::AaSub;
 use crate::analyze::find_aa_motifs::AaMotif;
+use crate::analyze::find_aa_motifs_changes::AaMotifMutation;
+use crate::analyze::hgvs::HgvsMutations;
+use crate::analyze::impute::ImputedNuc;
 use crate::analyze::letter_ranges::{GeneAaRange, NucRange};
-use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::minor_variants::MinorVariant;
+use crate::analyze::mutation_annotations::MutationAnnotation;
+use crate::analyze::nuc_del::{NucDel, NucDelRange};
 use crate::analyze::nuc_sub::{NucSub, NucSubLabeled};
 use crate::analyze::pcr_primer_changes::PcrPrimerChange;
+use crate::analyze::relative_mutations::RelativeNucMutations;
+use crate::analyze::structural_events::StructuralEvent;
 use crate::coord::range::NucRefGlobalRange;
 use crate::io::csv::{CsvVecFileWriter, CsvVecWriter, VecWriter};
 use crate::qc::qc_config::StopCodonLocation;
+use crate::qc::qc_rule_indel_clusters::ClusteredIndel;
+use crate::qc::qc_rule_mixed_sites::MixedSite;
 use crate::qc::qc_rule_snp_clusters::ClusteredSnp;
 use crate::translate::frame_shifts_translate::FrameShift;
 use crate::types::outputs::{
@@ -19,6 +30,7 @@ use crate::types::outputs::{
 };
 use crate::utils::num::is_int;
 use crate::{make_error, o};
+use clap::ValueEnum;
 use edit_distance::edit_distance;
 use eyre::Report;
 use indexmap::{indexmap, IndexMap};
@@ -48,11 +60,91 @@ pub enum CsvColumnCategory {
   ErrsWarns,
   Qc,
   Primers,
+  AlignmentDiagnostics,
+  RelativeMuts,
+  Hgvs,
+  Recombination,
+  Placement,
+  MutationPath,
+  Lineage,
   Dynamic,
 }
 
 pub type CsvColumnConfigMap = IndexMap<CsvColumnCategory, IndexMap<String, bool>>;
 
+/// Compatibility mode of CSV and TSV outputs
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum CsvColumnsCompat {
+  /// Columns of the current version
+  #[default]
+  Current,
+
+  /// Columns of Nextclade v2: the columns which were introduced later are omitted, such that the parsers written for
+  /// v2 outputs can consume the file
+  V2,
+}
+
+/// Columns which did not exist in Nextclade v2 outputs
+const CSV_COLUMNS_ABSENT_IN_V2: &[&str] = &[
+  "index",
+  "isReverseComplement",
+  "treeDependentSkipped",
+  "refName",
+  "structuralEvents",
+  "normalizedHomopolymerIndels",
+  "masked",
+  "totalMasked",
+  "imputedNucs",
+  "qc.mixedSites.totalDiscriminatingMixedSites",
+  "qc.mixedSites.discriminatingMixedSites",
+  "annotations",
+  "minorVariants",
+  "cladeDefiningMutations.observed",
+  "cladeDefiningMutations.missing",
+  "cladeDefiningMutations.contradicted",
+  "cladeDefiningMutations.totalObserved",
+  "cladeDefiningMutations.totalMissing",
+  "cladeDefiningMutations.totalContradicted",
+  "alignmentDiagnostics.alignmentScore",
+  "alignmentDiagnostics.numSeedMatches",
+  "alignmentDiagnostics.numAttempts",
+  "alignmentDiagnostics.bandWidth",
+  "alignmentDiagnostics.cellsComputed",
+  "alignmentDiagnostics.alignmentTimeSec",
+  "recombinant.isRecombinant",
+  "recombinant.parents",
+  "recombinant.breakpoint",
+  "placement.candidates",
+  "placement.confidence",
+  "mutationPath",
+  "lineage.lineage",
+  "lineage.confidence",
+  "lineage.conflicts",
+  "lineage.totalMissing",
+  "lineage.ambiguousWith",
+  "relativeNucMutations.nodeName",
+  "relativeNucMutations.substitutions",
+  "relativeNucMutations.deletions",
+  "relativeNucMutations.totalSubstitutions",
+  "relativeNucMutations.totalDeletions",
+  "hgvs.genomic",
+  "hgvs.coding",
+  "hgvs.protein",
+  "qc.customRules",
+  "qc.geneMissingData.score",
+  "qc.geneMissingData.status",
+  "qc.geneMissingData.missingFractions",
+  "qc.indelClusters.clusteredIndels",
+  "qc.indelClusters.score",
+  "qc.indelClusters.status",
+  "qc.indelClusters.totalIndels",
+  "qc.primerMutations.affectedPrimers",
+  "qc.primerMutations.affectedAssays",
+  "qc.primerMutations.score",
+  "qc.primerMutations.status",
+];
+
 // Configuration for enabling/disabling CSV columns or categories of them
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -60,10 +152,12 @@ pub struct CsvColumnConfig {
   pub categories: CsvColumnConfigMap,
   pub individual: Vec<String>,
   pub include_dynamic: bool,
+  #[serde(default)]
+  pub compat: CsvColumnsCompat,
 }
 
 impl CsvColumnConfig {
-  pub fn new(output_columns_selection: &[String]) -> Result<Self, Report> {
+  pub fn new(output_columns_selection: &[String], compat: CsvColumnsCompat) -> Result<Self, Report> {
     let (categories, individual): (Vec<CsvColumnCategory>, Vec<String>) = output_columns_selection
       .iter()
       .partition_map(|candidate| match CsvColumnCategory::from_str(candidate) {
@@ -91,7 +185,10 @@ impl CsvColumnConfig {
     })?;
 
     if output_columns_selection.is_empty() || categories.contains(&CsvColumnCategory::All) {
-      Ok(Self::default())
+      Ok(Self {
+        compat,
+        ..Self::default()
+      })
     } else {
       let include_dynamic = categories.contains(&CsvColumnCategory::Dynamic);
 
@@ -99,7 +196,13 @@ impl CsvColumnConfig {
         .into_iter()
         .filter(|category| !matches!(category, CsvColumnCategory::Dynamic)) // Dynamic columns are handled specially
         .map(|category| {
-          let columns = CSV_COLUMN_CONFIG_MAP_DEFAULT.get(&category).unwrap().clone();
+          // Explicitly selected categories include all of their columns, even the ones disabled by default
+          let columns = CSV_COLUMN_CONFIG_MAP_DEFAULT
+            .get(&category)
+            .unwrap()
+            .keys()
+            .map(|column| (column.clone(), true))
+            .collect();
           (category, columns)
         })
         .collect();
@@ -108,9 +211,29 @@ impl CsvColumnConfig {
         categories,
         individual,
         include_dynamic,
+        compat,
       })
     }
   }
+
+  /// Enables all columns of a category, including the ones disabled by default
+  pub fn enable_category(&mut self, category: CsvColumnCategory) {
+    if let Some(columns) = CSV_COLUMN_CONFIG_MAP_DEFAULT.get(&category) {
+      let columns = columns.keys().map(|column| (column.clone(), true)).collect();
+      self.categories.insert(category, columns);
+    }
+  }
+
+  /// Enables a column which is disabled by default, if its category is selected
+  pub fn enable_column(&mut self, category: &CsvColumnCategory, column: &str) {
+    if let Some(enabled) = self
+      .categories
+      .get_mut(category)
+      .and_then(|columns| columns.get_mut(column))
+    {
+      *enabled = true;
+    }
+  }
 }
 
 impl Default for CsvColumnConfig {
@@ -119,6 +242,7 @@ impl Default for CsvColumnConfig {
       categories: CSV_COLUMN_CONFIG_MAP_DEFAULT.clone(),
       individual: vec![],
       include_dynamic: true,
+      compat: CsvColumnsCompat::default(),
     }
   }
 }
@@ -145,15 +269,23 @@ lazy_static! {
       o!("alignmentEnd") => true,
       o!("coverage") => true,
       o!("isReverseComplement") => true,
+      o!("treeDependentSkipped") => true,
+      o!("refName") => true,
     },
     CsvColumnCategory::RefMuts => indexmap! {
       o!("substitutions") => true,
       o!("deletions") => true,
       o!("insertions") => true,
       o!("frameShifts") => true,
+      o!("structuralEvents") => true,
+      o!("normalizedHomopolymerIndels") => false,
       o!("aaSubstitutions") => true,
       o!("aaDeletions") => true,
       o!("aaInsertions") => true,
+      // Disabled by default: only filled when requested using `--input-mutation-annotations`
+      o!("annotations") => false,
+      // Disabled by default: only filled when requested using `--input-minor-variants`
+      o!("minorVariants") => false,
     },
     CsvColumnCategory::PrivMuts => indexmap! {
       o!("privateNucMutations.reversionSubstitutions") => true,
@@ -163,21 +295,35 @@ lazy_static! {
       o!("privateNucMutations.totalLabeledSubstitutions") => true,
       o!("privateNucMutations.totalUnlabeledSubstitutions") => true,
       o!("privateNucMutations.totalPrivateSubstitutions") => true,
+      o!("cladeDefiningMutations.observed") => true,
+      o!("cladeDefiningMutations.missing") => true,
+      o!("cladeDefiningMutations.contradicted") => true,
+      o!("cladeDefiningMutations.totalObserved") => true,
+      o!("cladeDefiningMutations.totalMissing") => true,
+      o!("cladeDefiningMutations.totalContradicted") => true,
     },
     CsvColumnCategory::Qc => indexmap! {
       o!("missing") => true,
       o!("unknownAaRanges") => true,
       o!("nonACGTNs") => true,
+      o!("imputedNucs") => false,
+      o!("masked") => false,
+      o!("totalMasked") => false,
       o!("qc.overallScore") => true,
       o!("qc.overallStatus") => true,
       o!("qc.missingData.missingDataThreshold") => true,
       o!("qc.missingData.score") => true,
       o!("qc.missingData.status") => true,
       o!("qc.missingData.totalMissing") => true,
+      o!("qc.geneMissingData.score") => true,
+      o!("qc.geneMissingData.status") => true,
+      o!("qc.geneMissingData.missingFractions") => true,
       o!("qc.mixedSites.mixedSitesThreshold") => true,
       o!("qc.mixedSites.score") => true,
       o!("qc.mixedSites.status") => true,
       o!("qc.mixedSites.totalMixedSites") => true,
+      o!("qc.mixedSites.totalDiscriminatingMixedSites") => true,
+      o!("qc.mixedSites.discriminatingMixedSites") => true,
       o!("qc.privateMutations.cutoff") => true,
       o!("qc.privateMutations.excess") => true,
       o!("qc.privateMutations.score") => true,
@@ -187,6 +333,10 @@ lazy_static! {
       o!("qc.snpClusters.score") => true,
       o!("qc.snpClusters.status") => true,
       o!("qc.snpClusters.totalSNPs") => true,
+      o!("qc.indelClusters.clusteredIndels") => true,
+      o!("qc.indelClusters.score") => true,
+      o!("qc.indelClusters.status") => true,
+      o!("qc.indelClusters.totalIndels") => true,
       o!("qc.frameShifts.frameShifts") => true,
       o!("qc.frameShifts.totalFrameShifts") => true,
       o!("qc.frameShifts.frameShiftsIgnored") => true,
@@ -197,6 +347,12 @@ lazy_static! {
       o!("qc.stopCodons.totalStopCodons") => true,
       o!("qc.stopCodons.score") => true,
       o!("qc.stopCodons.status") => true,
+      o!("qc.primerMutations.affectedPrimers") => true,
+      o!("qc.primerMutations.affectedAssays") => true,
+      o!("qc.primerMutations.score") => true,
+      o!("qc.primerMutations.status") => true,
+      // Disabled by default: only filled when the QC config declares custom rules
+      o!("qc.customRules") => false,
     },
     CsvColumnCategory::Primers => indexmap! {
       o!("totalPcrPrimerChanges") => true,
@@ -206,7 +362,53 @@ lazy_static! {
       o!("failedGenes") => true,
       o!("warnings") => true,
       o!("errors") => true,
-    }
+    },
+    // Disabled by default: only filled when requested using `--include-alignment-diagnostics`
+    CsvColumnCategory::AlignmentDiagnostics => indexmap! {
+      o!("alignmentDiagnostics.alignmentScore") => false,
+      o!("alignmentDiagnostics.numSeedMatches") => false,
+      o!("alignmentDiagnostics.numAttempts") => false,
+      o!("alignmentDiagnostics.bandWidth") => false,
+      o!("alignmentDiagnostics.cellsComputed") => false,
+      o!("alignmentDiagnostics.alignmentTimeSec") => false,
+    },
+    // Disabled by default: only filled when requested using `--mutations-relative-to`
+    CsvColumnCategory::RelativeMuts => indexmap! {
+      o!("relativeNucMutations.nodeName") => false,
+      o!("relativeNucMutations.substitutions") => false,
+      o!("relativeNucMutations.deletions") => false,
+      o!("relativeNucMutations.totalSubstitutions") => false,
+      o!("relativeNucMutations.totalDeletions") => false,
+    },
+    // Disabled by default: only filled when requested using `--include-hgvs`
+    CsvColumnCategory::Hgvs => indexmap! {
+      o!("hgvs.genomic") => false,
+      o!("hgvs.coding") => false,
+      o!("hgvs.protein") => false,
+    },
+    // Disabled by default: only filled when requested using `--detect-recombinants`
+    CsvColumnCategory::Recombination => indexmap! {
+      o!("recombinant.isRecombinant") => false,
+      o!("recombinant.parents") => false,
+      o!("recombinant.breakpoint") => false,
+    },
+    // Disabled by default: only filled when requested using `--num-placement-candidates`
+    CsvColumnCategory::Placement => indexmap! {
+      o!("placement.candidates") => false,
+      o!("placement.confidence") => false,
+    },
+    // Disabled by default: only filled when requested using `--include-mutation-path`
+    CsvColumnCategory::MutationPath => indexmap! {
+      o!("mutationPath") => false,
+    },
+    // Disabled by default: only filled when requested using `--input-lineages`
+    CsvColumnCategory::Lineage => indexmap! {
+      o!("lineage.lineage") => false,
+      o!("lineage.confidence") => false,
+      o!("lineage.conflicts") => false,
+      o!("lineage.totalMissing") => false,
+      o!("lineage.ambiguousWith") => false,
+    },
   };
 
   pub static ref CSV_POSSIBLE_CATEGORIES: Vec<String> = CsvColumnCategory::VARIANTS.iter()
@@ -221,10 +423,11 @@ lazy_static! {
     .collect_vec();
 }
 
-fn prepare_headers(
+pub(crate) fn prepare_headers(
   custom_node_attr_keys: &[String],
   phenotype_attr_keys: &[String],
   aa_motifs_keys: &[String],
+  cds_names: &[String],
   column_config: &CsvColumnConfig,
 ) -> Vec<String> {
   // Get names of enabled columns
@@ -263,10 +466,33 @@ fn prepare_headers(
     });
     insert_custom_cols_at_index += phenotype_attr_keys.len();
 
-    aa_motifs_keys.iter().rev().for_each(|key| {
-      headers.insert(insert_custom_cols_at_index + 1, key.clone());
-      insert_custom_cols_at_index += aa_motifs_keys.len();
-    });
+    // AA motif columns did not exist in Nextclade v2
+    if column_config.compat != CsvColumnsCompat::V2 {
+      aa_motifs_keys.iter().rev().for_each(|key| {
+        headers.insert(
+          insert_custom_cols_at_index + 1,
+          format_aa_motifs_changes_column(key, "lost"),
+        );
+        headers.insert(
+          insert_custom_cols_at_index + 1,
+          format_aa_motifs_changes_column(key, "gained"),
+        );
+        headers.insert(insert_custom_cols_at_index + 1, key.clone());
+      });
+    }
+
+    // Per-CDS coverage columns did not exist in Nextclade v2
+    if column_config.compat != CsvColumnsCompat::V2 {
+      if let Some(coverage_index) = headers.iter().position(|header| header == "coverage") {
+        cds_names.iter().rev().for_each(|cds_name| {
+          headers.insert(coverage_index + 1, format_cds_coverage_column(cds_name));
+        });
+      }
+    }
+  }
+
+  if column_config.compat == CsvColumnsCompat::V2 {
+    headers.retain(|header| !CSV_COLUMNS_ABSENT_IN_V2.contains(&header.as_str()));
   }
 
   headers
@@ -318,11 +544,15 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       total_unknown_aa,
       alignment_range,
       alignment_score,
+      alignment_diagnostics,
       pcr_primer_changes,
       total_pcr_primer_changes,
       clade,
+      clade_defining_mutations,
       private_nuc_mutations,
       // private_aa_mutations,
+      relative_nuc_mutations,
+      hgvs,
       missing_genes,
       // divergence,
       coverage,
@@ -330,8 +560,22 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       qc,
       custom_node_attributes,
       is_reverse_complement,
+      tree_dependent_skipped,
+      ref_name,
       warnings,
       aa_motifs,
+      aa_motifs_changes,
+      structural_events,
+      normalized_homopolymer_indels,
+      masked_ranges,
+      imputed_nucs,
+      recombinant,
+      placement_candidates,
+      mutation_path,
+      lineage_call,
+      mutation_annotations,
+      minor_variants,
+      cds_coverage,
       ..
     } = nextclade_outputs;
 
@@ -349,6 +593,17 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       .iter()
       .try_for_each(|(name, motifs)| self.add_entry(name, &format_aa_motifs(motifs)))?;
 
+    aa_motifs_changes.iter().try_for_each(|(name, changes)| {
+      self.add_entry(
+        format_aa_motifs_changes_column(name, "gained"),
+        &format_aa_motif_mutations(&changes.gained, |mutation| &mutation.qry_seq),
+      )?;
+      self.add_entry(
+        format_aa_motifs_changes_column(name, "lost"),
+        &format_aa_motif_mutations(&changes.lost, |mutation| &mutation.ref_seq),
+      )
+    })?;
+
     self.add_entry("index", index)?;
     self.add_entry("seqName", seq_name)?;
 
@@ -403,7 +658,39 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "privateNucMutations.totalPrivateSubstitutions",
       &private_nuc_mutations.total_private_substitutions.to_string(),
     )?;
+    self.add_entry(
+      "cladeDefiningMutations.observed",
+      &format_nuc_substitutions_minimal(&clade_defining_mutations.observed, ARRAY_ITEM_DELIMITER),
+    )?;
+    self.add_entry(
+      "cladeDefiningMutations.missing",
+      &format_nuc_substitutions_minimal(&clade_defining_mutations.missing, ARRAY_ITEM_DELIMITER),
+    )?;
+    self.add_entry(
+      "cladeDefiningMutations.contradicted",
+      &format_nuc_substitutions_minimal(&clade_defining_mutations.contradicted, ARRAY_ITEM_DELIMITER),
+    )?;
+    self.add_entry(
+      "cladeDefiningMutations.totalObserved",
+      &clade_defining_mutations.total_observed.to_string(),
+    )?;
+    self.add_entry(
+      "cladeDefiningMutations.totalMissing",
+      &clade_defining_mutations.total_missing.to_string(),
+    )?;
+    self.add_entry(
+      "cladeDefiningMutations.totalContradicted",
+      &clade_defining_mutations.total_contradicted.to_string(),
+    )?;
     self.add_entry("frameShifts", &format_frame_shifts(frame_shifts, ARRAY_ITEM_DELIMITER))?;
+    self.add_entry(
+      "structuralEvents",
+      &format_structural_events(structural_events, ARRAY_ITEM_DELIMITER),
+    )?;
+    self.add_entry(
+      "normalizedHomopolymerIndels",
+      &format_homopolymer_indels(normalized_homopolymer_indels, ARRAY_ITEM_DELIMITER),
+    )?;
     self.add_entry(
       "aaSubstitutions",
       &format_aa_substitutions(aa_substitutions, ARRAY_ITEM_DELIMITER),
@@ -413,12 +700,30 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "aaInsertions",
       &format_aa_insertions(aa_insertions, ARRAY_ITEM_DELIMITER),
     )?;
+    self.add_entry(
+      "annotations",
+      &format_mutation_annotations(mutation_annotations, ARRAY_ITEM_DELIMITER),
+    )?;
+    self.add_entry(
+      "minorVariants",
+      &format_minor_variants(minor_variants, ARRAY_ITEM_DELIMITER),
+    )?;
     self.add_entry(
       "unknownAaRanges",
       &format_unknown_aa_ranges(unknown_aa_ranges, ARRAY_ITEM_DELIMITER),
     )?;
     self.add_entry("missing", &format_missings(missing, ARRAY_ITEM_DELIMITER))?;
+    self.add_entry("masked", &format_ranges(masked_ranges, ARRAY_ITEM_DELIMITER))?;
+    self.add_entry(
+      "totalMasked",
+      &masked_ranges
+        .iter()
+        .map(NucRefGlobalRange::len)
+        .sum::<usize>()
+        .to_string(),
+    )?;
     self.add_entry("nonACGTNs", &format_non_acgtns(non_acgtns, ARRAY_ITEM_DELIMITER))?;
+    self.add_entry("imputedNucs", &format_imputed_nucs(imputed_nucs, ARRAY_ITEM_DELIMITER))?;
     self.add_entry(
       "pcrPrimerChanges",
       &format_pcr_primer_changes(pcr_primer_changes, ARRAY_ITEM_DELIMITER),
@@ -426,7 +731,102 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
     self.add_entry("alignmentScore", &alignment_score)?;
     self.add_entry("alignmentStart", &(alignment_range.begin + 1).to_string())?;
     self.add_entry("alignmentEnd", &alignment_range.end.to_string())?;
+    if let Some(AlignmentDiagnostics {
+      alignment_score,
+      stats,
+      alignment_time_sec,
+    }) = alignment_diagnostics
+    {
+      self.add_entry("alignmentDiagnostics.alignmentScore", alignment_score)?;
+      self.add_entry("alignmentDiagnostics.numSeedMatches", &stats.num_seed_matches)?;
+      self.add_entry("alignmentDiagnostics.numAttempts", &stats.num_attempts)?;
+      self.add_entry("alignmentDiagnostics.bandWidth", &stats.band_width)?;
+      self.add_entry("alignmentDiagnostics.cellsComputed", &stats.cells_computed)?;
+      self.add_entry("alignmentDiagnostics.alignmentTimeSec", alignment_time_sec)?;
+    }
+    if let Some(RelativeNucMutations {
+      node_name,
+      substitutions,
+      deletions,
+      total_substitutions,
+      total_deletions,
+    }) = relative_nuc_mutations
+    {
+      self.add_entry("relativeNucMutations.nodeName", node_name)?;
+      self.add_entry(
+        "relativeNucMutations.substitutions",
+        &format_nuc_substitutions_minimal(substitutions, ARRAY_ITEM_DELIMITER),
+      )?;
+      self.add_entry(
+        "relativeNucMutations.deletions",
+        &deletions.iter().map(NucDel::to_string).join(ARRAY_ITEM_DELIMITER),
+      )?;
+      self.add_entry("relativeNucMutations.totalSubstitutions", total_substitutions)?;
+      self.add_entry("relativeNucMutations.totalDeletions", total_deletions)?;
+    }
+    if let Some(HgvsMutations {
+      genomic,
+      coding,
+      protein,
+    }) = hgvs
+    {
+      self.add_entry("hgvs.genomic", &genomic.join(ARRAY_ITEM_DELIMITER))?;
+      self.add_entry("hgvs.coding", &coding.join(ARRAY_ITEM_DELIMITER))?;
+      self.add_entry("hgvs.protein", &protein.join(ARRAY_ITEM_DELIMITER))?;
+    }
+    self.add_entry("recombinant.isRecombinant", &recombinant.is_some())?;
+    if let Some(recombinant) = recombinant {
+      self.add_entry(
+        "recombinant.parents",
+        &recombinant
+          .parents
+          .iter()
+          .map(|parent| &parent.clade)
+          .join(ARRAY_ITEM_DELIMITER),
+      )?;
+      self.add_entry("recombinant.breakpoint", &recombinant.breakpoint)?;
+    }
+    if let Some(placement_candidates) = placement_candidates {
+      self.add_entry(
+        "placement.candidates",
+        &placement_candidates
+          .candidates
+          .iter()
+          .map(|candidate| format!("{}:{}", candidate.node_name, candidate.distance))
+          .join(ARRAY_ITEM_DELIMITER),
+      )?;
+      self.add_entry(
+        "placement.confidence",
+        &format!("{:.4}", placement_candidates.confidence),
+      )?;
+    }
+    if let Some(mutation_path) = mutation_path {
+      self.add_entry(
+        "mutationPath",
+        &mutation_path
+          .iter()
+          .flat_map(|branch| &branch.nuc_substitutions)
+          .map(NucSub::to_string)
+          .join(ARRAY_ITEM_DELIMITER),
+      )?;
+    }
+    if let Some(lineage_call) = lineage_call {
+      self.add_entry("lineage.lineage", &lineage_call.lineage)?;
+      self.add_entry("lineage.confidence", &format!("{:.4}", lineage_call.confidence))?;
+      self.add_entry(
+        "lineage.conflicts",
+        &format_nuc_substitutions_minimal(&lineage_call.conflicts, ARRAY_ITEM_DELIMITER),
+      )?;
+      self.add_entry("lineage.totalMissing", &lineage_call.total_missing)?;
+      self.add_entry(
+        "lineage.ambiguousWith",
+        &lineage_call.ambiguous_with.join(ARRAY_ITEM_DELIMITER),
+      )?;
+    }
     self.add_entry("coverage", coverage)?;
+    cds_coverage
+      .iter()
+      .try_for_each(|(cds_name, coverage)| self.add_entry(format_cds_coverage_column(cds_name), coverage))?;
     self.add_entry_maybe(
       "qc.missingData.missingDataThreshold",
       qc.missing_data.as_ref().map(|md| md.missing_data_threshold.to_string()),
@@ -443,6 +843,24 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "qc.missingData.totalMissing",
       qc.missing_data.as_ref().map(|md| md.total_missing.to_string()),
     )?;
+    self.add_entry_maybe(
+      "qc.geneMissingData.score",
+      qc.gene_missing_data.as_ref().map(|gmd| format_qc_score(gmd.score)),
+    )?;
+    self.add_entry_maybe(
+      "qc.geneMissingData.status",
+      qc.gene_missing_data.as_ref().map(|gmd| gmd.status.to_string()),
+    )?;
+    self.add_entry_maybe(
+      "qc.geneMissingData.missingFractions",
+      qc.gene_missing_data.as_ref().map(|gmd| {
+        gmd
+          .genes
+          .iter()
+          .map(|gene| format!("{}:{}", gene.gene_name, format_qc_score(gene.missing_fraction)))
+          .join(ARRAY_ITEM_DELIMITER)
+      }),
+    )?;
     self.add_entry_maybe(
       "qc.mixedSites.mixedSitesThreshold",
       qc.mixed_sites.as_ref().map(|ms| ms.mixed_sites_threshold.to_string()),
@@ -459,6 +877,18 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "qc.mixedSites.totalMixedSites",
       qc.mixed_sites.as_ref().map(|ms| ms.total_mixed_sites.to_string()),
     )?;
+    self.add_entry_maybe(
+      "qc.mixedSites.totalDiscriminatingMixedSites",
+      qc.mixed_sites
+        .as_ref()
+        .map(|ms| ms.total_discriminating_mixed_sites.to_string()),
+    )?;
+    self.add_entry_maybe(
+      "qc.mixedSites.discriminatingMixedSites",
+      qc.mixed_sites
+        .as_ref()
+        .map(|ms| format_mixed_sites(&ms.discriminating_mixed_sites, ARRAY_ITEM_DELIMITER)),
+    )?;
     self.add_entry_maybe(
       "qc.privateMutations.cutoff",
       qc.private_mutations.as_ref().map(|pm| pm.cutoff.to_string()),
@@ -497,6 +927,24 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "qc.snpClusters.totalSNPs",
       qc.snp_clusters.as_ref().map(|sc| sc.total_snps.to_string()),
     )?;
+    self.add_entry_maybe(
+      "qc.indelClusters.clusteredIndels",
+      qc.indel_clusters
+        .as_ref()
+        .map(|ic| format_clustered_indels(&ic.clustered_indels, ARRAY_ITEM_DELIMITER)),
+    )?;
+    self.add_entry_maybe(
+      "qc.indelClusters.score",
+      qc.indel_clusters.as_ref().map(|ic| format_qc_score(ic.score)),
+    )?;
+    self.add_entry_maybe(
+      "qc.indelClusters.status",
+      qc.indel_clusters.as_ref().map(|ic| ic.status.to_string()),
+    )?;
+    self.add_entry_maybe(
+      "qc.indelClusters.totalIndels",
+      qc.indel_clusters.as_ref().map(|ic| ic.total_indels.to_string()),
+    )?;
     self.add_entry_maybe(
       "qc.frameShifts.frameShifts",
       qc.frame_shifts
@@ -545,7 +993,47 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
       "qc.stopCodons.status",
       qc.stop_codons.as_ref().map(|sc| sc.status.to_string()),
     )?;
+    self.add_entry_maybe(
+      "qc.primerMutations.affectedPrimers",
+      qc.primer_mutations.as_ref().map(|pm| {
+        pm.affected_primers
+          .iter()
+          .map(|primer| &primer.name)
+          .join(ARRAY_ITEM_DELIMITER)
+      }),
+    )?;
+    self.add_entry_maybe(
+      "qc.primerMutations.affectedAssays",
+      qc.primer_mutations
+        .as_ref()
+        .map(|pm| pm.affected_assays.join(ARRAY_ITEM_DELIMITER)),
+    )?;
+    self.add_entry_maybe(
+      "qc.primerMutations.score",
+      qc.primer_mutations.as_ref().map(|pm| format_qc_score(pm.score)),
+    )?;
+    self.add_entry_maybe(
+      "qc.primerMutations.status",
+      qc.primer_mutations.as_ref().map(|pm| pm.status.to_string()),
+    )?;
+    self.add_entry(
+      "qc.customRules",
+      &qc
+        .custom_rules
+        .iter()
+        .map(|rule| {
+          format!(
+            "{}:{}:{}",
+            rule.name,
+            format_qc_score(rule.score),
+            rule.status.to_string()
+          )
+        })
+        .join(ARRAY_ITEM_DELIMITER),
+    )?;
     self.add_entry("isReverseComplement", &is_reverse_complement.to_string())?;
+    self.add_entry("treeDependentSkipped", &tree_dependent_skipped.to_string())?;
+    self.add_entry("refName", ref_name)?;
     self.add_entry("failedGenes", &format_failed_genes(missing_genes, ARRAY_ITEM_DELIMITER))?;
     self.add_entry(
       "warnings",
@@ -595,6 +1083,11 @@ impl<W: VecWriter> NextcladeResultsCsvWriter<W> {
     self.add_entry(key, &val)
   }
 
+  /// Underlying writer, for writers which store additional data along with the rows
+  pub(crate) fn writer_mut(&mut self) -> &mut W {
+    &mut self.writer
+  }
+
   /// Writes current row and clears it
   fn write_row(&mut self) -> Result<(), Report> {
     self.writer.write(&self.row)?;
@@ -615,9 +1108,16 @@ impl NextcladeResultsCsvFileWriter {
     clade_attr_keys: &[String],
     phenotype_attr_keys: &[String],
     aa_motifs_keys: &[String],
+    cds_names: &[String],
     column_config: &CsvColumnConfig,
   ) -> Result<Self, Report> {
-    let headers: Vec<String> = prepare_headers(clade_attr_keys, phenotype_attr_keys, aa_motifs_keys, column_config);
+    let headers: Vec<String> = prepare_headers(
+      clade_attr_keys,
+      phenotype_attr_keys,
+      aa_motifs_keys,
+      cds_names,
+      column_config,
+    );
     let csv_writer = CsvVecFileWriter::new(filepath, delimiter, &headers)?;
     let writer = NextcladeResultsCsvWriter::new(csv_writer, &headers)?;
     Ok(Self { writer })
@@ -692,6 +1192,11 @@ pub fn format_missings(missings: &[NucRange], delimiter: &str) -> String {
     .join(delimiter)
 }
 
+#[inline]
+pub fn format_ranges(ranges: &[NucRefGlobalRange], delimiter: &str) -> String {
+  ranges.iter().map(NucRefGlobalRange::to_string).join(delimiter)
+}
+
 #[inline]
 pub fn format_pcr_primer_changes(pcr_primer_changes: &[PcrPrimerChange], delimiter: &str) -> String {
   pcr_primer_changes
@@ -751,6 +1256,90 @@ pub fn format_frame_shifts(frame_shifts: &[FrameShift], delimiter: &str) -> Stri
     .join(delimiter)
 }
 
+/// Formats normalized homopolymer indels as `<kind>:<position>:<nucleotide>`, for example `deletion:1234:A`. For
+/// insertions, the position is the one of the reference nucleotide after which the nucleotide has been inserted.
+#[inline]
+pub fn format_homopolymer_indels(indels: &[HomopolymerIndel], delimiter: &str) -> String {
+  indels
+    .iter()
+    .map(|indel| format!("{}:{}:{}", indel.kind, indel.pos + 1, from_nuc(indel.nuc)))
+    .join(delimiter)
+}
+
+/// Formats imputed nucleotides as `<ambiguous nuc><pos><imputed nuc>`, for example `R123A`
+#[inline]
+pub fn format_mutation_annotations(annotations: &[MutationAnnotation], delimiter: &str) -> String {
+  annotations
+    .iter()
+    .map(
+      |MutationAnnotation {
+         mutation, annotation, ..
+       }| format!("{mutation} ({annotation})"),
+    )
+    .join(delimiter)
+}
+
+pub fn format_minor_variants(minor_variants: &[MinorVariant], delimiter: &str) -> String {
+  minor_variants
+    .iter()
+    .map(|variant| {
+      format!(
+        "{}{}{}:{:.3}",
+        from_nuc(variant.ref_nuc),
+        variant.pos + 1,
+        from_nuc(variant.alt_nuc),
+        variant.frequency
+      )
+    })
+    .join(delimiter)
+}
+
+pub fn format_mixed_sites(mixed_sites: &[MixedSite], delimiter: &str) -> String {
+  mixed_sites
+    .iter()
+    .map(|MixedSite { pos, nuc }| format!("{}{}", from_nuc(*nuc), pos + 1))
+    .join(delimiter)
+}
+
+pub fn format_imputed_nucs(imputed_nucs: &[ImputedNuc], delimiter: &str) -> String {
+  imputed_nucs
+    .iter()
+    .map(|imputed| {
+      format!(
+        "{}{}{}",
+        from_nuc(imputed.qry_nuc),
+        imputed.pos + 1,
+        from_nuc(imputed.imputed_nuc)
+      )
+    })
+    .join(delimiter)
+}
+
+/// Formats structural events as `<kind>:<range>:<CDSes>`, where CDSes are the fused CDSes for possible fusions and the
+/// affected CDSes with the ranges of affected codons otherwise, for example `fusion:13-49:g1>g2` or
+/// `deletion:13-50:g1(5-10)|g2(1-4)`
+#[inline]
+pub fn format_structural_events(structural_events: &[StructuralEvent], delimiter: &str) -> String {
+  structural_events
+    .iter()
+    .map(|event| {
+      let kind = event.kind;
+      let range = event.range.to_string();
+      let cdses = if event.fused_cdses.is_empty() {
+        event
+          .affected_cdses
+          .iter()
+          .zip(&event.affected_codons)
+          .map(|(cds, codons)| format!("{cds}({codons})"))
+          .join("|")
+      } else {
+        event.fused_cdses.join(">")
+      };
+      format!("{kind}:{range}:{cdses}")
+    })
+    .join(delimiter)
+}
+
 #[inline]
 pub fn format_clustered_snps(snps: &[ClusteredSnp], delimiter: &str) -> String {
   snps
@@ -763,6 +1352,18 @@ pub fn format_clustered_snps(snps: &[ClusteredSnp], delimiter: &str) -> String {
     .join(delimiter)
 }
 
+#[inline]
+pub fn format_clustered_indels(indels: &[ClusteredIndel], delimiter: &str) -> String {
+  indels
+    .iter()
+    .map(|indel| {
+      let range = NucRefGlobalRange::from_usize(indel.start, indel.end).to_string();
+      let number_of_indels = indel.number_of_indels;
+      format!("{range}:{number_of_indels}")
+    })
+    .join(delimiter)
+}
+
 #[inline]
 pub fn format_stop_codons(stop_codons: &[StopCodonLocation], delimiter: &str) -> String {
   stop_codons
@@ -811,6 +1412,26 @@ fn format_aa_motifs(motifs: &[AaMotif]) -> String {
     .join(";")
 }
 
+/// Formats motifs gained or lost in the query as `<cds>:<position>:<motif>`, with the motif sequence taken from the
+/// query or from the reference respectively
+#[inline]
+fn format_aa_motif_mutations(mutations: &[AaMotifMutation], seq: impl Fn(&AaMotifMutation) -> &String) -> String {
+  mutations
+    .iter()
+    .map(|mutation| format!("{}:{}:{}", mutation.gene, mutation.position + 1, seq(mutation)))
+    .join(";")
+}
+
+/// Name of the column containing AA motifs of a given kind, gained or lost in the query sequence
+pub fn format_aa_motifs_changes_column(motif_name: &str, kind: &str) -> String {
+  format!("{motif_name}.{kind}")
+}
+
+/// Name of the column containing coverage of a given CDS
+pub fn format_cds_coverage_column(cds_name: &str) -> String {
+  format!("coverage.{cds_name}")
+}
+
 pub fn results_to_csv_string(
   outputs: &[NextcladeOutputs],
   errors: &[NextcladeErrorOutputs],
@@ -822,8 +1443,22 @@ pub fn results_to_csv_string(
 ) -> Result<String, Report> {
   let mut buf = Vec::<u8>::new();
 
+  // CDS names are not passed explicitly here, so they are gathered from the results themselves
+  let cds_names = outputs
+    .iter()
+    .flat_map(|output| output.cds_coverage.keys())
+    .unique()
+    .cloned()
+    .collect_vec();
+
   {
-    let headers: Vec<String> = prepare_headers(clade_attr_keys, phenotype_attr_keys, aa_motifs_keys, column_config);
+    let headers: Vec<String> = prepare_headers(
+      clade_attr_keys,
+      phenotype_attr_keys,
+      aa_motifs_keys,
+      &cds_names,
+      column_config,
+    );
     let csv_writer = CsvVecWriter::new(&mut buf, delimiter, &headers)?;
     let mut writer = NextcladeResultsCsvWriter::new(csv_writer, &headers)?;
 
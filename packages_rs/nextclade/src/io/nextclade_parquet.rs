@@ -0,0 +1,99 @@
+use crate::io::file::create_file_or_stdout;
+use crate::io::nextclade_arrow::{NextcladeResultsArrowBuilder, ARROW_BATCH_SIZE};
+use crate::io::nextclade_csv::CsvColumnConfig;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Writes results into a Parquet file: the columns of nextclade.tsv, as strings, followed by the typed list columns
+/// with the mutations. Rows are written in row groups of fixed size, such that memory consumption does not depend on
+/// the number of sequences.
+pub struct NextcladeResultsParquetFileWriter {
+  filepath: PathBuf,
+  builder: NextcladeResultsArrowBuilder,
+  file_writer: Option<ArrowWriter<Box<dyn Write + Send>>>,
+}
+
+impl NextcladeResultsParquetFileWriter {
+  pub fn new(
+    filepath: impl AsRef<Path>,
+    clade_attr_keys: &[String],
+    phenotype_attr_keys: &[String],
+    aa_motifs_keys: &[String],
+    cds_names: &[String],
+    column_config: &CsvColumnConfig,
+  ) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+
+    let builder = NextcladeResultsArrowBuilder::new(
+      clade_attr_keys,
+      phenotype_attr_keys,
+      aa_motifs_keys,
+      cds_names,
+      column_config,
+    )?;
+
+    let props = WriterProperties::builder()
+      .set_compression(Compression::SNAPPY)
+      .set_max_row_group_size(ARROW_BATCH_SIZE)
+      .build();
+    let file = create_file_or_stdout(filepath)?;
+    let file_writer = ArrowWriter::try_new(file, Arc::clone(builder.schema()), Some(props))
+      .wrap_err_with(|| format!("When creating Parquet file writer: {filepath:#?}"))?;
+
+    Ok(Self {
+      filepath: filepath.to_owned(),
+      builder,
+      file_writer: Some(file_writer),
+    })
+  }
+
+  pub fn write(&mut self, nextclade_outputs: &NextcladeOutputs) -> Result<(), Report> {
+    self.builder.write(nextclade_outputs)?;
+    self.write_row_group_maybe()
+  }
+
+  /// Writes one row for the case of error
+  pub fn write_nuc_error(&mut self, index: usize, seq_name: &str, errors: &str) -> Result<(), Report> {
+    self.builder.write_nuc_error(index, seq_name, errors)?;
+    self.write_row_group_maybe()
+  }
+
+  /// Writes the remaining rows and the file footer. The file is not valid until this is called.
+  pub fn finish(&mut self) -> Result<(), Report> {
+    let Some(mut file_writer) = self.file_writer.take() else {
+      return Ok(());
+    };
+    let batch = self.builder.finish_batch()?;
+    file_writer.write(&batch)?;
+    file_writer
+      .close()
+      .wrap_err_with(|| format!("When writing Parquet file: {:#?}", self.filepath))?;
+    Ok(())
+  }
+
+  fn write_row_group_maybe(&mut self) -> Result<(), Report> {
+    if self.builder.num_rows() < ARROW_BATCH_SIZE {
+      return Ok(());
+    }
+    if let Some(file_writer) = &mut self.file_writer {
+      let batch = self.builder.finish_batch()?;
+      file_writer
+        .write(&batch)
+        .wrap_err_with(|| format!("When writing Parquet file: {:#?}", self.filepath))?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for NextcladeResultsParquetFileWriter {
+  #[allow(unused_must_use)]
+  fn drop(&mut self) {
+    self.finish();
+  }
+}
@@ -0,0 +1,134 @@
+use crate::graph::node::GraphNodeKey;
+use crate::graph::traits::HasDivergence;
+use crate::io::file::create_file_or_stdout;
+use crate::io::nwk_writer::nwk_quote_name;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload};
+use crate::{make_internal_error, make_internal_report};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use traversal::DftPost;
+
+pub fn nexus_write_to_file(filepath: impl AsRef<Path>, graph: &AuspiceGraph) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let file = create_file_or_stdout(filepath)?;
+  nexus_write_to_writer(file, graph).wrap_err_with(|| format!("When writing graph to NEXUS file: {filepath:#?}"))
+}
+
+pub fn nexus_write_to_writer<W: Write>(mut writer: W, graph: &AuspiceGraph) -> Result<(), Report> {
+  Ok(write!(writer, "{}", convert_graph_to_nexus_string(graph)?)?)
+}
+
+/// Converts the tree to NEXUS format, with a taxa block listing the leaves, and a trees block containing the tree in
+/// Newick format. Node names are followed by BEAST-style comments (`[&key="value",...]`), readable by FigTree and
+/// similar tools, with the clade of the node and the nucleotide and aminoacid mutations on the branch leading to it.
+pub fn convert_graph_to_nexus_string(graph: &AuspiceGraph) -> Result<String, Report> {
+  let tree = convert_graph_to_nexus_tree(graph).wrap_err("When converting graph to NEXUS tree string")?;
+
+  let taxa = graph
+    .iter_nodes()
+    .filter(|node| node.is_leaf())
+    .map(|node| format!("    {}", nwk_quote_name(&node.payload().name)))
+    .collect_vec();
+
+  Ok(format!(
+    "#NEXUS\nBegin Taxa;\n  Dimensions NTax={};\n  TaxLabels\n{}\n  ;\nEnd;\nBegin Trees;\n  Tree tree1 = [&R] {tree};\nEnd;\n",
+    taxa.len(),
+    taxa.join("\n")
+  ))
+}
+
+/// Converts the tree to Newick format with node comments. Nodes are visited in postorder, without recursion, such that
+/// the subtrees of the children are always ready when their parent is visited.
+fn convert_graph_to_nexus_tree(graph: &AuspiceGraph) -> Result<String, Report> {
+  let root = graph.get_exactly_one_root()?;
+  let mut subtrees = HashMap::<GraphNodeKey, String>::new();
+  for (_, node) in DftPost::new(root, |node| graph.iter_children_of(node)) {
+    let payload = node.payload();
+    let parent_div = graph
+      .parent_of(node)
+      .map_or(0.0, |parent| parent.payload().divergence());
+    let branch_length = payload.divergence() - parent_div;
+    let name = nwk_quote_name(&payload.name);
+    let comment = nexus_node_comment(payload);
+
+    let subtree = if node.is_leaf() {
+      format!("{name}{comment}:{branch_length}")
+    } else {
+      let children = graph
+        .iter_child_keys_of(node)
+        .map(|child_key| {
+          subtrees
+            .remove(&child_key)
+            .ok_or_else(|| make_internal_report!("Child node {child_key} is not converted before its parent"))
+        })
+        .collect::<Result<Vec<String>, Report>>()?
+        .join(",");
+      format!("({children}){name}{comment}:{branch_length}")
+    };
+    subtrees.insert(node.key(), subtree);
+  }
+
+  let Some(tree) = subtrees.remove(&root.key()) else {
+    return make_internal_error!("Root node is not converted");
+  };
+  Ok(tree)
+}
+
+/// Formats node annotations as a BEAST-style comment. Aminoacid mutations are prefixed with the CDS name.
+fn nexus_node_comment(node: &AuspiceGraphNodePayload) -> String {
+  let mutations = &node.branch_attrs.mutations;
+  let nuc_mutations = mutations.get("nuc").into_iter().flatten().join(",");
+  let aa_mutations = mutations
+    .iter()
+    .filter(|(cds, _)| cds.as_str() != "nuc")
+    .flat_map(|(cds, muts)| muts.iter().map(move |mutation| format!("{cds}:{mutation}")))
+    .join(",");
+
+  let attrs = [
+    ("clade", node.clade()),
+    ("nuc_mutations", nuc_mutations),
+    ("aa_mutations", aa_mutations),
+  ]
+  .into_iter()
+  .filter(|(_, value)| !value.is_empty())
+  .map(|(key, value)| format!("{key}=\"{}\"", value.replace('"', "'")))
+  .join(",");
+
+  if attrs.is_empty() {
+    String::new()
+  } else {
+    format!("[&{attrs}]")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn writes_nexus_tree() -> Result<(), Report> {
+    let mut graph = convert_auspice_tree_to_graph(auspice_tree_from_nwk_str("((A:1,B:2)X:1,C:3)R;", None)?)?;
+    let node_a = graph
+      .iter_nodes_mut()
+      .find(|node| node.payload().name == "A")
+      .expect("Expected node 'A' in the tree");
+    let mutations = &mut node_a.payload_mut().branch_attrs.mutations;
+    mutations.insert("nuc".to_owned(), vec!["C241T".to_owned(), "A23403G".to_owned()]);
+    mutations.insert("S".to_owned(), vec!["D614G".to_owned()]);
+
+    let nexus = convert_graph_to_nexus_string(&graph)?;
+
+    assert_eq!(
+      nexus,
+      "#NEXUS\nBegin Taxa;\n  Dimensions NTax=3;\n  TaxLabels\n    A\n    B\n    C\n  ;\nEnd;\nBegin Trees;\n  Tree tree1 = [&R] ((A[&nuc_mutations=\"C241T,A23403G\",aa_mutations=\"S:D614G\"]:1,B:2)X:1,C:3)R:0;\nEnd;\n"
+    );
+    Ok(())
+  }
+}
@@ -5,6 +5,7 @@ use crate::graph::traits::{HasDivergence, HasName};
 use crate::io::file::create_file_or_stdout;
 use eyre::{Report, WrapErr};
 use itertools::Itertools;
+use std::borrow::Cow;
 use std::io::Write;
 use std::path::Path;
 
@@ -34,7 +35,9 @@ where
 {
   let root_node_key = graph.get_exactly_one_root()?.key();
   let parent_div = 0.0;
-  convert_graph_to_nwk_recursive(graph, root_node_key, parent_div).wrap_err("When converting graph to Newick string")
+  let nwk = convert_graph_to_nwk_recursive(graph, root_node_key, parent_div)
+    .wrap_err("When converting graph to Newick string")?;
+  Ok(format!("{nwk};"))
 }
 
 fn convert_graph_to_nwk_recursive<N, E, D>(
@@ -48,9 +51,9 @@ where
 {
   let node = graph.get_node(node_key)?.payload();
   let branch_length = node.divergence() - parent_div;
+  let name = nwk_quote_name(node.name());
 
   Ok(if graph.is_leaf_key(node_key) {
-    let name = node.name();
     format!("{name}:{branch_length}")
   } else {
     let children = graph
@@ -58,6 +61,35 @@ where
       .map(|child_key| convert_graph_to_nwk_recursive(graph, child_key, node.divergence()))
       .collect::<Result<Vec<String>, Report>>()?
       .join(",");
-    format!("({children}):{branch_length}")
+    format!("({children}){name}:{branch_length}")
   })
 }
+
+/// Quotes the node name if it contains characters which have special meaning in Newick format. Single quotes inside of
+/// the name are escaped by doubling them.
+pub(crate) fn nwk_quote_name(name: &str) -> Cow<'_, str> {
+  let needs_quotes = name
+    .chars()
+    .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | ',' | ':' | ';' | '\''));
+  if needs_quotes {
+    Cow::Owned(format!("'{}'", name.replace('\'', "''")))
+  } else {
+    Cow::Borrowed(name)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::plain("hCoV-19/USA/CA-1/2020", "hCoV-19/USA/CA-1/2020")]
+  #[case::spaces("NODE 1", "'NODE 1'")]
+  #[case::special("a,b:c", "'a,b:c'")]
+  #[case::quotes("it's", "'it''s'")]
+  fn quotes_newick_names(#[case] name: &str, #[case] expected: &str) {
+    assert_eq!(nwk_quote_name(name), expected);
+  }
+}
@@ -1,3 +1,4 @@
+use crate::make_error;
 use crate::utils::error::to_eyre_error;
 use eyre::{Report, WrapErr};
 
@@ -7,5 +8,31 @@ use eyre::{Report, WrapErr};
 /// more common to see 1-based indexing. We perform the conversion here.
 pub fn parse_pos(s: &str) -> Result<isize, Report> {
   let pos = to_eyre_error(s.parse::<usize>()).wrap_err_with(|| format!("Unable to parse position: '{s}'"))?;
+  if pos == 0 {
+    return make_error!("Unable to parse position: '{s}': positions are 1-based, so the first position is 1");
+  }
   Ok((pos - 1) as isize)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn converts_to_0_based_position() -> Result<(), Report> {
+    assert_eq!(parse_pos("1")?, 0);
+    assert_eq!(parse_pos("27")?, 26);
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_position_0() {
+    assert_eq!(
+      report_to_string(&parse_pos("0").unwrap_err()),
+      "Unable to parse position: '0': positions are 1-based, so the first position is 1"
+    );
+  }
+}
@@ -0,0 +1,206 @@
+use crate::io::nextclade_csv::{
+  format_aa_deletions, format_aa_substitutions, format_frame_shifts, format_nuc_deletions, format_nuc_insertions,
+  format_nuc_substitutions,
+};
+use crate::qc::qc_run::QcResult;
+use crate::types::outputs::NextcladeOutputs;
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of items shown in lists (of mutations etc.). Remaining items are only counted.
+const MAX_LIST_ITEMS: usize = 20;
+
+const LIST_DELIMITER: &str = ", ";
+
+/// Format of the human-readable summary of an analysis result
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResultSummaryFormat {
+  /// Plain text, one property per line
+  #[default]
+  Text,
+  /// Markdown table
+  Markdown,
+}
+
+/// Renders a compact human-readable summary of the analysis result of one sequence: clade, QC status, coverage and
+/// the most important mutations. Long lists are truncated.
+pub fn format_result_summary(output: &NextcladeOutputs, format: ResultSummaryFormat) -> String {
+  let properties = summary_properties(output);
+  match format {
+    ResultSummaryFormat::Text => {
+      let width = properties.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+      let lines = properties
+        .iter()
+        .map(|(name, value)| format!("  {name:<width$}  {value}"))
+        .join("\n");
+      format!("Sequence #{} '{}'\n{lines}\n", output.index, output.seq_name)
+    }
+    ResultSummaryFormat::Markdown => {
+      let rows = properties
+        .iter()
+        .map(|(name, value)| format!("| {name} | {} |", escape_markdown_table_cell(value)))
+        .join("\n");
+      format!(
+        "### {}\n\n| Property | Value |\n| --- | --- |\n{rows}\n",
+        escape_markdown_table_cell(&output.seq_name)
+      )
+    }
+  }
+}
+
+/// Whether any of the QC rules ran. Otherwise the overall QC status is meaningless.
+pub(crate) const fn has_qc_results(qc: &QcResult) -> bool {
+  qc.missing_data.is_some()
+    || qc.mixed_sites.is_some()
+    || qc.private_mutations.is_some()
+    || qc.snp_clusters.is_some()
+    || qc.frame_shifts.is_some()
+    || qc.stop_codons.is_some()
+}
+
+/// Names and formatted values of the most important properties of the analysis result of one sequence
+pub(crate) fn summary_properties(output: &NextcladeOutputs) -> Vec<(&'static str, String)> {
+  let NextcladeOutputs {
+    clade,
+    qc,
+    coverage,
+    alignment_range,
+    alignment_score,
+    is_reverse_complement,
+    substitutions,
+    total_substitutions,
+    deletions,
+    total_deletions,
+    insertions,
+    total_insertions,
+    total_missing,
+    frame_shifts,
+    total_frame_shifts,
+    aa_substitutions,
+    total_aminoacid_substitutions,
+    aa_deletions,
+    total_aminoacid_deletions,
+    private_nuc_mutations,
+    clade_defining_mutations,
+    warnings,
+    ..
+  } = output;
+
+  let mut properties = vec![];
+
+  if !clade.is_empty() {
+    properties.push(("Clade", clade.clone()));
+  }
+
+  if has_qc_results(qc) {
+    properties.push((
+      "QC",
+      format!("{} (score {:.1})", qc.overall_status.to_string(), qc.overall_score),
+    ));
+  }
+
+  properties.push(("Coverage", format!("{:.1}%", coverage * 100.0)));
+  properties.push(("Alignment", format!("{alignment_range} (score {alignment_score})")));
+  if *is_reverse_complement {
+    properties.push(("Orientation", "reverse complement".to_owned()));
+  }
+
+  properties.push((
+    "Substitutions",
+    format_counted(*total_substitutions, substitutions, format_nuc_substitutions),
+  ));
+  properties.push((
+    "Deletions",
+    format_counted(*total_deletions, deletions, format_nuc_deletions),
+  ));
+  properties.push((
+    "Insertions",
+    format_counted(*total_insertions, insertions, format_nuc_insertions),
+  ));
+  properties.push(("Missing", total_missing.to_string()));
+
+  if *total_frame_shifts > 0 {
+    properties.push((
+      "Frame shifts",
+      format_counted(*total_frame_shifts, frame_shifts, format_frame_shifts),
+    ));
+  }
+
+  if !aa_substitutions.is_empty() || !aa_deletions.is_empty() {
+    properties.push((
+      "AA substitutions",
+      format_counted(
+        *total_aminoacid_substitutions,
+        aa_substitutions,
+        format_aa_substitutions,
+      ),
+    ));
+    properties.push((
+      "AA deletions",
+      format_counted(*total_aminoacid_deletions, aa_deletions, format_aa_deletions),
+    ));
+  }
+
+  if !clade.is_empty() {
+    properties.push((
+      "Private substitutions",
+      private_nuc_mutations.total_private_substitutions.to_string(),
+    ));
+    properties.push((
+      "Clade-defining mutations",
+      format!(
+        "{} observed, {} missing, {} contradicted",
+        clade_defining_mutations.total_observed,
+        clade_defining_mutations.total_missing,
+        clade_defining_mutations.total_contradicted
+      ),
+    ));
+  }
+
+  if !warnings.is_empty() {
+    properties.push(("Warnings", warnings.iter().map(|warning| &warning.warning).join("; ")));
+  }
+
+  properties
+}
+
+/// Formats total number of items, followed by a (possibly truncated) list of the items
+fn format_counted<T>(total: usize, items: &[T], format_items: impl Fn(&[T], &str) -> String) -> String {
+  if items.is_empty() {
+    return total.to_string();
+  }
+  let shown = &items[..items.len().min(MAX_LIST_ITEMS)];
+  let list = format_items(shown, LIST_DELIMITER);
+  let num_hidden = items.len() - shown.len();
+  if num_hidden > 0 {
+    format!("{total}: {list} and {num_hidden} more")
+  } else {
+    format!("{total}: {list}")
+  }
+}
+
+fn escape_markdown_table_cell(s: &str) -> String {
+  s.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+  #![allow(clippy::needless_pass_by_value)]
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn format_numbers(numbers: &[usize], delimiter: &str) -> String {
+    numbers.iter().join(delimiter)
+  }
+
+  #[rstest]
+  #[case::empty(0, vec![], "0")]
+  #[case::short(3, vec![1, 2, 3], "3: 1, 2, 3")]
+  #[case::truncated(25, (1..=25).collect(), "25: 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20 and 5 more")]
+  fn formats_counted_list(#[case] total: usize, #[case] items: Vec<usize>, #[case] expected: &str) {
+    assert_eq!(format_counted(total, &items, format_numbers), expected);
+  }
+}
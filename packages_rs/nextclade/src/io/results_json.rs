@@ -1,15 +1,17 @@
+use crate::align::params::AlignPairwiseParams;
 use crate::analyze::virus_properties::PhenotypeAttrDesc;
-use crate::io::json::{json_stringify, json_write, JsonPretty};
+use crate::io::fs::{has_extension, read_file_to_string};
+use crate::io::json::{json_parse, json_stringify, json_write, JsonPretty};
 use crate::io::ndjson::NdjsonWriter;
 use crate::tree::tree::CladeNodeAttrKeyDesc;
 use crate::types::outputs::{
   combine_outputs_and_errors_sorted, NextcladeErrorOutputs, NextcladeOutputOrError, NextcladeOutputs,
 };
 use crate::utils::datetime::date_iso_now;
-use eyre::Report;
+use crate::utils::info::this_package_version_str;
+use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use crate::utils::info::this_package_version_str;
 
 #[derive(Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -23,6 +25,14 @@ pub struct ResultsJson {
 
   pub created_at: String,
 
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub run_id: Option<String>,
+
+  /// Effective alignment parameters of the run: defaults, overridden by the dataset and then by the command-line
+  /// arguments
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub alignment_params: Option<AlignPairwiseParams>,
+
   pub clade_node_attr_keys: Vec<CladeNodeAttrKeyDesc>,
 
   pub phenotype_attr_keys: Vec<PhenotypeAttrDesc>,
@@ -39,6 +49,8 @@ impl ResultsJson {
       nextclade_algo_version: this_package_version_str().to_owned(),
       nextclade_web_version: None,
       created_at: date_iso_now(),
+      run_id: None,
+      alignment_params: None,
       clade_node_attr_keys: clade_node_attrs.to_vec(),
       phenotype_attr_keys: phenotype_attr_keys.to_vec(),
       results: vec![],
@@ -71,10 +83,15 @@ impl ResultsJsonWriter {
     filepath: impl AsRef<Path>,
     clade_node_attrs: &[CladeNodeAttrKeyDesc],
     phenotype_attr_keys: &[PhenotypeAttrDesc],
+    run_id: &Option<String>,
+    alignment_params: &AlignPairwiseParams,
   ) -> Result<Self, Report> {
+    let mut result = ResultsJson::new(clade_node_attrs, phenotype_attr_keys);
+    result.run_id = run_id.clone();
+    result.alignment_params = Some(alignment_params.clone());
     Ok(Self {
       filepath: filepath.as_ref().to_owned(),
-      result: ResultsJson::new(clade_node_attrs, phenotype_attr_keys),
+      result,
     })
   }
 
@@ -138,3 +155,78 @@ pub fn results_to_ndjson_string(
   }
   Ok(String::from_utf8(buf)?)
 }
+
+/// Properties of a results JSON file, other than the results themselves
+#[derive(Clone, Debug)]
+pub struct ResultsJsonHeader {
+  pub nextclade_algo_version: String,
+  pub clade_node_attr_keys: Vec<CladeNodeAttrKeyDesc>,
+  pub phenotype_attr_keys: Vec<PhenotypeAttrDesc>,
+}
+
+/// Contents of a results file of a previous run
+pub struct ResultsFileContents {
+  /// Header of the file. Only JSON files have a header, NDJSON files don't.
+  pub header: Option<ResultsJsonHeader>,
+  pub results: Vec<NextcladeOutputs>,
+  pub errors: Vec<NextcladeErrorOutputs>,
+}
+
+/// Reads results of a previous Nextclade run, written with `--output-json` or `--output-ndjson`. The format is deduced
+/// from file extension: NDJSON for ".ndjson", JSON otherwise. Entries for sequences which failed to be analyzed are
+/// returned separately.
+pub fn results_read(filepath: impl AsRef<Path>) -> Result<(Vec<NextcladeOutputs>, Vec<NextcladeErrorOutputs>), Report> {
+  let ResultsFileContents { results, errors, .. } = results_read_with_header(filepath)?;
+  Ok((results, errors))
+}
+
+/// Same as `results_read()`, but also returns the header of the file
+pub fn results_read_with_header(filepath: impl AsRef<Path>) -> Result<ResultsFileContents, Report> {
+  let filepath = filepath.as_ref();
+  let content = read_file_to_string(filepath)?;
+
+  if !has_extension(filepath, "ndjson") {
+    let ResultsJson {
+      nextclade_algo_version,
+      clade_node_attr_keys,
+      phenotype_attr_keys,
+      results,
+      errors,
+      ..
+    } = json_parse(&content).wrap_err_with(|| format!("When parsing Nextclade results JSON file {filepath:#?}"))?;
+    let header = ResultsJsonHeader {
+      nextclade_algo_version,
+      clade_node_attr_keys,
+      phenotype_attr_keys,
+    };
+    return Ok(ResultsFileContents {
+      header: Some(header),
+      results,
+      errors,
+    });
+  }
+
+  let mut results = vec![];
+  let mut errors = vec![];
+  for (i, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+    match json_parse::<NextcladeOutputs>(line) {
+      Ok(result) => results.push(result),
+      Err(report) => {
+        let error = json_parse::<NextcladeErrorOutputs>(line)
+          .wrap_err(report)
+          .wrap_err_with(|| {
+            format!(
+              "When parsing line {} of Nextclade results NDJSON file {filepath:#?}",
+              i + 1
+            )
+          })?;
+        errors.push(error);
+      }
+    }
+  }
+  Ok(ResultsFileContents {
+    header: None,
+    results,
+    errors,
+  })
+}
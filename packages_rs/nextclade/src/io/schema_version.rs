@@ -26,13 +26,13 @@ impl SchemaVersion {
     let sv: SchemaVersion = json_parse(json_str)?;
 
     if let Some(ver_to) = ver_to {
-      if sv.schema_version.as_str() > ver_to {
+      if sv.schema_version.as_str() > *ver_to {
         return make_error!("The format version of '{}' file (schemaVersion={}) is newer than maximum version supported by this version of Nextclade (schemaVersion={}). This likely means that there are newer versions of Nextclade available which support this new format. In case of issues, please upgrade Nextclade to avoid incompatibility and to receive the latest features and bug fixes. Alternatively, you might try to use earlier versions of the dataset (not recommended).", name, sv.schema_version, ver_to);
       }
     }
 
     if let Some(ver_from) = ver_from {
-      if sv.schema_version.as_str() < ver_from {
+      if sv.schema_version.as_str() < *ver_from {
         return make_error!("The format version of '{}' file (schemaVersion={}) is older than minimum version supported by this version of Nextclade (schemaVersion={}). This likely means that this version of Nextclade will have problems reading and understanding this file. In case of issues, please upgrade the dataset to avoid incompatibility and to receive the latest features and bug fixes. Alternatively, you might try to use earlier versions of Nextclade (not recommended).", name, sv.schema_version, ver_from);
       }
     }
@@ -0,0 +1,593 @@
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::graph::node::Node;
+use crate::io::compression::{guess_compression_from_filepath, CompressionType};
+use crate::io::file::{create_file_or_stdout, open_file_or_stdin};
+use crate::io::fs::has_extension;
+use crate::io::nwk_writer::convert_graph_to_nwk_string;
+use crate::make_error;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload, AuspiceTree, AuspiceTreeNode};
+use crate::tree::tree_nwk::{auspice_tree_from_nwk_str_preorder, CLADE_COLUMN, NUC_MUTATIONS_COLUMN};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use traversal::DftPre;
+
+/// Node attribute holding the second clade annotation of UShER trees, which is conventionally the Pango lineage
+const LINEAGE_ATTR: &str = "lineage";
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_I64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_I32: u64 = 5;
+
+/// Mutation on a branch of UShER mutation-annotated tree. Nucleotides are encoded as 0, 1, 2, 3 for A, C, G, T.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct MatMutation {
+  /// 1-based position in the reference sequence
+  position: i32,
+  ref_nuc: i32,
+  par_nuc: i32,
+  mut_nuc: Vec<i32>,
+  chromosome: String,
+}
+
+/// Leaf of the Newick tree of UShER mutation-annotated tree, standing for several identical samples
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct MatCondensedNode {
+  node_name: String,
+  condensed_leaves: Vec<String>,
+}
+
+/// Mutation-annotated tree (MAT), as stored by UShER in protobuf format. Mutations and clade annotations are listed
+/// for each node of the Newick tree, in preorder.
+///
+/// See: https://usher-wiki.readthedocs.io/en/latest/matUtils.html and `parsimony.proto` in the UShER repository
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Mat {
+  newick: String,
+  node_mutations: Vec<Vec<MatMutation>>,
+  condensed_nodes: Vec<MatCondensedNode>,
+  metadata: Vec<Vec<String>>,
+}
+
+impl Mat {
+  fn decode(buf: &[u8]) -> Result<Self, Report> {
+    let mut mat = Self::default();
+    let mut reader = ProtoReader::new(buf);
+    while let Some((field, wire_type)) = reader.field()? {
+      match (field, wire_type) {
+        (1, WIRE_LEN) => mat.newick = reader.string()?,
+        (2, WIRE_LEN) => {
+          let mut list = ProtoReader::new(reader.bytes()?);
+          let mut mutations = vec![];
+          while let Some((field, wire_type)) = list.field()? {
+            match (field, wire_type) {
+              (1, WIRE_LEN) => mutations.push(MatMutation::decode(list.bytes()?)?),
+              _ => list.skip(wire_type)?,
+            }
+          }
+          mat.node_mutations.push(mutations);
+        }
+        (3, WIRE_LEN) => mat.condensed_nodes.push(MatCondensedNode::decode(reader.bytes()?)?),
+        (4, WIRE_LEN) => {
+          let mut metadata = ProtoReader::new(reader.bytes()?);
+          let mut annotations = vec![];
+          while let Some((field, wire_type)) = metadata.field()? {
+            match (field, wire_type) {
+              (1, WIRE_LEN) => annotations.push(metadata.string()?),
+              _ => metadata.skip(wire_type)?,
+            }
+          }
+          mat.metadata.push(annotations);
+        }
+        _ => reader.skip(wire_type)?,
+      }
+    }
+    Ok(mat)
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut writer = ProtoWriter::default();
+    writer.bytes_field(1, self.newick.as_bytes());
+    for mutations in &self.node_mutations {
+      let mut list = ProtoWriter::default();
+      for mutation in mutations {
+        list.bytes_field(1, &mutation.encode());
+      }
+      writer.bytes_field(2, &list.buf);
+    }
+    for condensed_node in &self.condensed_nodes {
+      writer.bytes_field(3, &condensed_node.encode());
+    }
+    for annotations in &self.metadata {
+      let mut metadata = ProtoWriter::default();
+      for annotation in annotations {
+        metadata.bytes_field(1, annotation.as_bytes());
+      }
+      writer.bytes_field(4, &metadata.buf);
+    }
+    writer.buf
+  }
+}
+
+impl MatMutation {
+  fn decode(buf: &[u8]) -> Result<Self, Report> {
+    let mut mutation = Self::default();
+    let mut reader = ProtoReader::new(buf);
+    while let Some((field, wire_type)) = reader.field()? {
+      match (field, wire_type) {
+        (1, WIRE_VARINT) => mutation.position = reader.varint()? as i32,
+        (2, WIRE_VARINT) => mutation.ref_nuc = reader.varint()? as i32,
+        (3, WIRE_VARINT) => mutation.par_nuc = reader.varint()? as i32,
+        (4, WIRE_VARINT) => mutation.mut_nuc.push(reader.varint()? as i32),
+        (4, WIRE_LEN) => {
+          // Packed repeated field
+          let mut packed = ProtoReader::new(reader.bytes()?);
+          while !packed.is_empty() {
+            mutation.mut_nuc.push(packed.varint()? as i32);
+          }
+        }
+        (5, WIRE_LEN) => mutation.chromosome = reader.string()?,
+        _ => reader.skip(wire_type)?,
+      }
+    }
+    Ok(mutation)
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut writer = ProtoWriter::default();
+    writer.int32_field(1, self.position);
+    writer.int32_field(2, self.ref_nuc);
+    writer.int32_field(3, self.par_nuc);
+    let mut packed = ProtoWriter::default();
+    for &nuc in &self.mut_nuc {
+      packed.varint(nuc as i64 as u64);
+    }
+    writer.bytes_field(4, &packed.buf);
+    writer.bytes_field(5, self.chromosome.as_bytes());
+    writer.buf
+  }
+}
+
+impl MatCondensedNode {
+  fn decode(buf: &[u8]) -> Result<Self, Report> {
+    let mut condensed_node = Self::default();
+    let mut reader = ProtoReader::new(buf);
+    while let Some((field, wire_type)) = reader.field()? {
+      match (field, wire_type) {
+        (1, WIRE_LEN) => condensed_node.node_name = reader.string()?,
+        (2, WIRE_LEN) => condensed_node.condensed_leaves.push(reader.string()?),
+        _ => reader.skip(wire_type)?,
+      }
+    }
+    Ok(condensed_node)
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut writer = ProtoWriter::default();
+    writer.bytes_field(1, self.node_name.as_bytes());
+    for leaf in &self.condensed_leaves {
+      writer.bytes_field(2, leaf.as_bytes());
+    }
+    writer.buf
+  }
+}
+
+/// Minimal reader of protobuf wire format
+struct ProtoReader<'a> {
+  buf: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+  const fn new(buf: &'a [u8]) -> Self {
+    Self { buf, pos: 0 }
+  }
+
+  const fn is_empty(&self) -> bool {
+    self.pos >= self.buf.len()
+  }
+
+  /// Reads key of the next field: field number and wire type
+  fn field(&mut self) -> Result<Option<(u64, u64)>, Report> {
+    if self.is_empty() {
+      return Ok(None);
+    }
+    let key = self.varint()?;
+    Ok(Some((key >> 3, key & 0x7)))
+  }
+
+  fn varint(&mut self) -> Result<u64, Report> {
+    let mut value = 0_u64;
+    for shift in (0..64).step_by(7) {
+      let Some(&byte) = self.buf.get(self.pos) else {
+        return make_error!("Unexpected end of protobuf data");
+      };
+      self.pos += 1;
+      value |= u64::from(byte & 0x7F) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    make_error!("Invalid varint in protobuf data")
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], Report> {
+    let Some(bytes) = self.pos.checked_add(len).and_then(|end| self.buf.get(self.pos..end)) else {
+      return make_error!("Unexpected end of protobuf data");
+    };
+    self.pos += len;
+    Ok(bytes)
+  }
+
+  /// Reads length-delimited field
+  fn bytes(&mut self) -> Result<&'a [u8], Report> {
+    let len = self.varint()? as usize;
+    self.take(len)
+  }
+
+  fn string(&mut self) -> Result<String, Report> {
+    let bytes = self.bytes()?;
+    String::from_utf8(bytes.to_vec()).wrap_err("When reading string from protobuf data")
+  }
+
+  fn skip(&mut self, wire_type: u64) -> Result<(), Report> {
+    match wire_type {
+      WIRE_VARINT => {
+        self.varint()?;
+      }
+      WIRE_I64 => {
+        self.take(8)?;
+      }
+      WIRE_LEN => {
+        self.bytes()?;
+      }
+      WIRE_I32 => {
+        self.take(4)?;
+      }
+      _ => return make_error!("Unsupported protobuf wire type: {wire_type}"),
+    }
+    Ok(())
+  }
+}
+
+/// Minimal writer of protobuf wire format
+#[derive(Default)]
+struct ProtoWriter {
+  buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+  fn varint(&mut self, mut value: u64) {
+    while value >= 0x80 {
+      self.buf.push((value & 0x7F) as u8 | 0x80);
+      value >>= 7;
+    }
+    self.buf.push(value as u8);
+  }
+
+  fn key(&mut self, field: u64, wire_type: u64) {
+    self.varint((field << 3) | wire_type);
+  }
+
+  fn int32_field(&mut self, field: u64, value: i32) {
+    self.key(field, WIRE_VARINT);
+    // Negative values are sign-extended to 64 bits, as required by protobuf
+    self.varint(value as i64 as u64);
+  }
+
+  fn bytes_field(&mut self, field: u64, bytes: &[u8]) {
+    self.key(field, WIRE_LEN);
+    self.varint(bytes.len() as u64);
+    self.buf.extend_from_slice(bytes);
+  }
+}
+
+fn nuc_from_mat_code(code: i32) -> Result<char, Report> {
+  match code {
+    0 => Ok('A'),
+    1 => Ok('C'),
+    2 => Ok('G'),
+    3 => Ok('T'),
+    _ => make_error!("Unknown nucleotide code in UShER mutation-annotated tree: {code}"),
+  }
+}
+
+const fn nuc_to_mat_code(nuc: Nuc) -> Option<i32> {
+  match nuc {
+    Nuc::A => Some(0),
+    Nuc::C => Some(1),
+    Nuc::G => Some(2),
+    Nuc::T => Some(3),
+    _ => None,
+  }
+}
+
+/// Checks whether the path looks like UShER mutation-annotated tree (extension `.pb`, possibly followed by an extension
+/// of a compression format)
+pub fn is_usher_mat_path(filepath: impl AsRef<Path>) -> bool {
+  let filepath = filepath.as_ref();
+  match guess_compression_from_filepath(filepath).0 {
+    CompressionType::None => has_extension(filepath, "pb"),
+    _ => has_extension(filepath.with_extension(""), "pb"),
+  }
+}
+
+/// Reads reference tree from UShER mutation-annotated tree file (see [auspice_tree_from_usher_mat])
+pub fn auspice_tree_from_usher_mat_path(filepath: impl AsRef<Path>) -> Result<AuspiceTree, Report> {
+  let filepath = filepath.as_ref();
+  let mut data = vec![];
+  open_file_or_stdin(&Some(filepath))?
+    .read_to_end(&mut data)
+    .wrap_err_with(|| format!("When reading file: {filepath:#?}"))?;
+  auspice_tree_from_usher_mat(&data)
+    .wrap_err_with(|| format!("When reading UShER mutation-annotated tree from file {filepath:#?}"))
+}
+
+/// Converts UShER mutation-annotated tree (protobuf) into Auspice tree, such that it can be used as the reference tree.
+///
+/// The first clade annotation of the nodes becomes the clade, and the second one (conventionally the Pango lineage)
+/// becomes the clade-like node attribute `lineage`. UShER only annotates the nodes where the clades originate, so the
+/// annotations are inherited by the descendant nodes. Condensed nodes are expanded into the samples they stand for.
+pub fn auspice_tree_from_usher_mat(data: &[u8]) -> Result<AuspiceTree, Report> {
+  let mat = Mat::decode(data)?;
+
+  let rows = mat
+    .node_mutations
+    .iter()
+    .enumerate()
+    .map(|(i, mutations)| {
+      let mutations = mutations
+        .iter()
+        .filter_map(|mutation| mutation.mut_nuc.first().map(|&mut_nuc| (mutation, mut_nuc)))
+        .map(|(mutation, mut_nuc)| {
+          Ok(format!(
+            "{}{}{}",
+            nuc_from_mat_code(mutation.par_nuc)?,
+            mutation.position,
+            nuc_from_mat_code(mut_nuc)?
+          ))
+        })
+        .collect::<Result<Vec<String>, Report>>()?;
+
+      let mut row = BTreeMap::from([(NUC_MUTATIONS_COLUMN.to_owned(), mutations.join(","))]);
+      let annotations = mat.metadata.get(i).map(Vec::as_slice).unwrap_or_default();
+      if let Some(clade) = annotations.first() {
+        row.insert(CLADE_COLUMN.to_owned(), clade.clone());
+      }
+      if let Some(lineage) = annotations.get(1).filter(|lineage| !lineage.is_empty()) {
+        row.insert(LINEAGE_ATTR.to_owned(), lineage.clone());
+      }
+      Ok(row)
+    })
+    .collect::<Result<Vec<_>, Report>>()?;
+
+  let mut tree = auspice_tree_from_nwk_str_preorder(&mat.newick, &rows)?;
+
+  propagate_annotations(&mut tree.tree);
+
+  if !mat.condensed_nodes.is_empty() {
+    let condensed_nodes: BTreeMap<&str, &[String]> = mat
+      .condensed_nodes
+      .iter()
+      .map(|node| (node.node_name.as_str(), node.condensed_leaves.as_slice()))
+      .collect();
+    expand_condensed_nodes(&mut tree.tree, &condensed_nodes);
+  }
+
+  Ok(tree)
+}
+
+/// Assigns the clade and the lineage of the parent node to the nodes which have none, starting from the root
+fn propagate_annotations(root: &mut AuspiceTreeNode) {
+  // Nodes along with the clade and the lineage of their parents. Iterative, such that deep trees do not overflow the
+  // stack.
+  let mut stack: Vec<(&mut AuspiceTreeNode, String, Option<serde_json::Value>)> = vec![(root, String::new(), None)];
+  while let Some((node, parent_clade, parent_lineage)) = stack.pop() {
+    if node.node_attrs.clade_membership.value.is_empty() {
+      node.node_attrs.clade_membership.value = parent_clade;
+    }
+    if let Some(parent_lineage) = parent_lineage {
+      if node.node_attrs.other.get(LINEAGE_ATTR).is_none() {
+        node.node_attrs.other[LINEAGE_ATTR] = parent_lineage;
+      }
+    }
+
+    let clade = node.node_attrs.clade_membership.value.clone();
+    let lineage = node.node_attrs.other.get(LINEAGE_ATTR).cloned();
+    stack.extend(
+      node
+        .children
+        .iter_mut()
+        .map(|child| (child, clade.clone(), lineage.clone())),
+    );
+  }
+}
+
+/// Replaces the condensed nodes with the samples they stand for, in the entire tree
+fn expand_condensed_nodes(root: &mut AuspiceTreeNode, condensed_nodes: &BTreeMap<&str, &[String]>) {
+  let mut stack = vec![root];
+  while let Some(node) = stack.pop() {
+    node.children = std::mem::take(&mut node.children)
+      .into_iter()
+      .flat_map(|child| match condensed_nodes.get(child.name.as_str()) {
+        Some(leaves) => leaves
+          .iter()
+          .map(|leaf| AuspiceTreeNode {
+            name: leaf.clone(),
+            ..child.clone()
+          })
+          .collect_vec(),
+        None => vec![child],
+      })
+      .collect();
+
+    stack.extend(node.children.iter_mut());
+  }
+}
+
+/// Writes the tree in UShER mutation-annotated tree format (protobuf).
+///
+/// Nucleotide substitutions on the branches are written, whereas deletions and substitutions to or from ambiguous
+/// nucleotides, which cannot be represented in this format, are omitted. Clades are written as the first clade
+/// annotation and the `lineage` node attribute (if present) as the second one, on the nodes where they differ from
+/// the parent node.
+pub fn usher_mat_write_to_file(
+  filepath: impl AsRef<Path>,
+  graph: &AuspiceGraph,
+  ref_seq: &[Nuc],
+  chromosome: &str,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mat =
+    mat_from_graph(graph, ref_seq, chromosome).wrap_err("When converting graph to UShER mutation-annotated tree")?;
+  let mut file = create_file_or_stdout(filepath)?;
+  file
+    .write_all(&mat.encode())
+    .and_then(|()| file.flush())
+    .wrap_err_with(|| format!("When writing UShER mutation-annotated tree to file: {filepath:#?}"))
+}
+
+fn mat_from_graph(graph: &AuspiceGraph, ref_seq: &[Nuc], chromosome: &str) -> Result<Mat, Report> {
+  let mut mat = Mat {
+    newick: convert_graph_to_nwk_string(graph)?,
+    ..Mat::default()
+  };
+
+  // Nodes are added in preorder, which is the order in which they appear in the Newick string
+  for (_, node) in DftPre::new(graph.get_exactly_one_root()?, |node| graph.iter_children_of(node)) {
+    let parent = graph.parent_of(node).map(Node::payload);
+    mat_add_node(node.payload(), parent, ref_seq, chromosome, &mut mat)?;
+  }
+  Ok(mat)
+}
+
+/// Adds mutations and annotations of the node to the tree
+fn mat_add_node(
+  node: &AuspiceGraphNodePayload,
+  parent: Option<&AuspiceGraphNodePayload>,
+  ref_seq: &[Nuc],
+  chromosome: &str,
+  mat: &mut Mat,
+) -> Result<(), Report> {
+  let mut mutations = vec![];
+  for mutation in node.branch_attrs.mutations.get("nuc").into_iter().flatten() {
+    let sub = NucSub::from_str(mutation)?;
+    let (Some(par_nuc), Some(mut_nuc)) = (nuc_to_mat_code(sub.ref_nuc), nuc_to_mat_code(sub.qry_nuc)) else {
+      continue;
+    };
+    let ref_nuc = ref_seq
+      .get(sub.pos.as_usize())
+      .copied()
+      .and_then(nuc_to_mat_code)
+      .unwrap_or(par_nuc);
+    mutations.push(MatMutation {
+      position: sub.pos.as_usize() as i32 + 1,
+      ref_nuc,
+      par_nuc,
+      mut_nuc: vec![mut_nuc],
+      chromosome: chromosome.to_owned(),
+    });
+  }
+  mat.node_mutations.push(mutations);
+
+  let clade = node.clade();
+  let lineage = lineage_of(node);
+  let is_new_clade = parent.map_or(true, |parent| parent.clade() != clade);
+  let is_new_lineage = parent.map_or(true, |parent| lineage_of(parent) != lineage);
+  let clade_annotation = if is_new_clade { clade } else { String::new() };
+  let annotations = match lineage.filter(|_| is_new_lineage) {
+    Some(lineage) => vec![clade_annotation, lineage],
+    None => vec![clade_annotation],
+  };
+  mat.metadata.push(annotations);
+  Ok(())
+}
+
+fn lineage_of(node: &AuspiceGraphNodePayload) -> Option<String> {
+  node
+    .node_attrs
+    .other
+    .get(LINEAGE_ATTR)
+    .and_then(|attr| attr.get("value"))
+    .and_then(serde_json::Value::as_str)
+    .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn mutation(position: i32, par_nuc: i32, mut_nuc: i32) -> MatMutation {
+    MatMutation {
+      position,
+      ref_nuc: par_nuc,
+      par_nuc,
+      mut_nuc: vec![mut_nuc],
+      chromosome: o!("ref"),
+    }
+  }
+
+  fn mat() -> Mat {
+    Mat {
+      newick: o!("((A:1,B:1)node_2:1,C:2)node_1;"),
+      node_mutations: vec![
+        vec![],
+        vec![mutation(241, 1, 3)],
+        vec![mutation(3037, 1, 3)],
+        vec![],
+        vec![mutation(23403, 0, 2)],
+      ],
+      condensed_nodes: vec![MatCondensedNode {
+        node_name: o!("B"),
+        condensed_leaves: vec![o!("B1"), o!("B2")],
+      }],
+      metadata: vec![
+        vec![o!("19A"), o!("A")],
+        vec![o!("20A"), o!("B.1")],
+        vec![o!(""), o!("")],
+        vec![o!(""), o!("")],
+        vec![o!(""), o!("")],
+      ],
+    }
+  }
+
+  #[rstest]
+  fn roundtrips_usher_mat_protobuf() -> Result<(), Report> {
+    let mut mat = mat();
+    mat.node_mutations[3].push(mutation(-1, 0, 1));
+    assert_eq!(Mat::decode(&mat.encode())?, mat);
+    Ok(())
+  }
+
+  #[rstest]
+  fn converts_usher_mat_to_auspice_tree() -> Result<(), Report> {
+    let tree = auspice_tree_from_usher_mat(&mat().encode())?;
+
+    let node_2 = &tree.tree.children[0];
+    assert_eq!(node_2.name, "node_2");
+    assert_eq!(node_2.branch_attrs.mutations["nuc"], vec![o!("C241T")]);
+    assert_eq!(node_2.node_attrs.clade_membership.value, "20A");
+
+    assert_eq!(
+      node_2.children.iter().map(|child| child.name.as_str()).collect_vec(),
+      vec!["A", "B1", "B2"]
+    );
+    let a = &node_2.children[0];
+    assert_eq!(a.node_attrs.clade_membership.value, "20A");
+    assert_eq!(a.node_attrs.other[LINEAGE_ATTR]["value"], "B.1");
+
+    let c = &tree.tree.children[1];
+    assert_eq!(c.node_attrs.clade_membership.value, "19A");
+    assert_eq!(c.branch_attrs.mutations["nuc"], vec![o!("A23403G")]);
+    Ok(())
+  }
+}
@@ -0,0 +1,470 @@
+use crate::align::insertions_strip::Insertion;
+use crate::alphabet::nuc::{from_nuc, from_nuc_seq, Nuc};
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::coord::range::{have_intersection, NucRefGlobalRange};
+use crate::io::file::create_file_or_stdout;
+use crate::make_error;
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::info::this_package_version_str;
+use eyre::{Report, WrapErr};
+use itertools::{chain, Itertools};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Variant site in VCF representation: 0-based reference position and reference allele. Indels include the preceding
+/// (anchor) base of the reference, as required by the VCF format.
+type VcfSite = (usize, String);
+
+/// Nucleotide variants of one sequence, relative to the reference
+#[allow(clippy::partial_pub_fields)]
+#[derive(Clone, Debug)]
+pub struct VcfSample {
+  pub index: usize,
+  pub seq_name: String,
+  /// Alternative allele at each of the variant sites of the sequence
+  alleles: BTreeMap<VcfSite, String>,
+  /// Range of the reference covered by the alignment. `None` for sequences which failed to be analyzed.
+  alignment_range: Option<NucRefGlobalRange>,
+  /// Ranges of missing data (`N`) and of ambiguous nucleotides within the alignment range
+  unknown: Vec<NucRefGlobalRange>,
+  /// Ranges of the reference deleted in the sequence
+  deleted: Vec<NucRefGlobalRange>,
+}
+
+impl VcfSample {
+  /// Whether the reference allele can be assumed over the whole reference allele span of a site, in absence of a
+  /// variant at this site. This is not the case if any of the positions is outside of the alignment, missing, ambiguous
+  /// or deleted (by a deletion reported at another site).
+  fn is_covered(&self, site_range: &NucRefGlobalRange) -> bool {
+    self
+      .alignment_range
+      .as_ref()
+      .is_some_and(|alignment_range| alignment_range.begin <= site_range.begin && site_range.end <= alignment_range.end)
+      && !chain!(&self.unknown, &self.deleted).any(|range| have_intersection(range, site_range))
+  }
+}
+
+/// Collects nucleotide variants of analyzed sequences, one sequence at a time
+#[derive(Debug)]
+pub struct VcfSampleCollector {
+  ref_seq: String,
+  samples: Vec<VcfSample>,
+  /// First error of conversion of the variants of a sequence, reported by `finish()`
+  error: Option<Report>,
+}
+
+impl VcfSampleCollector {
+  pub fn new(ref_seq: &[Nuc]) -> Self {
+    Self {
+      ref_seq: from_nuc_seq(ref_seq),
+      samples: vec![],
+      error: None,
+    }
+  }
+
+  pub fn add(&mut self, output: &NextcladeOutputs) {
+    let alleles = find_vcf_alleles(
+      &output.substitutions,
+      &output.deletions,
+      &output.insertions,
+      &self.ref_seq,
+    )
+    .wrap_err_with(|| {
+      format!(
+        "When converting nucleotide changes of sequence #{} '{}' to VCF",
+        output.index, output.seq_name
+      )
+    });
+    let alleles = match alleles {
+      Ok(alleles) => alleles,
+      Err(report) => {
+        self.error.get_or_insert(report);
+        return;
+      }
+    };
+
+    self.samples.push(VcfSample {
+      index: output.index,
+      seq_name: output.seq_name.clone(),
+      alleles,
+      alignment_range: Some(output.alignment_range.clone()),
+      unknown: chain!(&output.missing, &output.non_acgtns)
+        .map(|range| range.range().clone())
+        .collect(),
+      deleted: output.deletions.iter().map(|del| del.range().clone()).collect(),
+    });
+  }
+
+  /// Adds a sequence which failed to be analyzed. Its genotypes are unknown at every site.
+  pub fn add_failed(&mut self, index: usize, seq_name: &str) {
+    self.samples.push(VcfSample {
+      index,
+      seq_name: seq_name.to_owned(),
+      alleles: BTreeMap::new(),
+      alignment_range: None,
+      unknown: vec![],
+      deleted: vec![],
+    });
+  }
+
+  /// Returns the collected samples, sorted by sequence index, or the error if variants of any of the sequences could
+  /// not be converted
+  pub fn finish(mut self) -> Result<Vec<VcfSample>, Report> {
+    if let Some(report) = self.error {
+      return Err(report);
+    }
+    self.samples.sort_by_key(|sample| sample.index);
+    Ok(self.samples)
+  }
+}
+
+/// Converts substitutions, deletions and insertions of a sequence into VCF alleles. Indels are anchored at the
+/// preceding reference base (or at the following one, at the start of the genome). If the anchor base is itself
+/// substituted, the substitution is merged into the indel allele. An insertion and a deletion at the same anchor are
+/// merged into one allele.
+fn find_vcf_alleles(
+  substitutions: &[NucSub],
+  deletions: &[NucDelRange],
+  insertions: &[Insertion<Nuc>],
+  ref_seq: &str,
+) -> Result<BTreeMap<VcfSite, String>, Report> {
+  let mut alleles: BTreeMap<VcfSite, String> = substitutions
+    .iter()
+    .map(|sub| {
+      (
+        (sub.pos.as_usize(), from_nuc(sub.ref_nuc).to_string()),
+        from_nuc(sub.qry_nuc).to_string(),
+      )
+    })
+    .collect();
+
+  // Deletion sites by position
+  let mut deletion_sites = BTreeMap::<usize, VcfSite>::new();
+  for del in deletions {
+    let begin = del.range().begin.as_usize();
+    let end = del.range().end.as_usize();
+    let (anchor, site) = if begin > 0 {
+      (begin - 1, (begin - 1, ref_slice(ref_seq, begin - 1, end)?))
+    } else if end < ref_seq.len() {
+      (end, (0, ref_slice(ref_seq, 0, end + 1)?))
+    } else {
+      continue;
+    };
+    let alt = take_anchor(&mut alleles, ref_seq, anchor)?;
+    alleles.insert(site.clone(), alt);
+    deletion_sites.insert(site.0, site);
+  }
+
+  for ins in insertions {
+    let inserted = from_nuc_seq(&ins.ins);
+    let anchor = if ins.pos >= 0 { ins.pos as usize } else { 0 };
+
+    // Insertion at the anchor of a deletion is merged into the deletion allele
+    let (site, alt) = match deletion_sites.get(&anchor) {
+      Some(site) => (site.clone(), alleles.remove(site).unwrap_or_default()),
+      None => (
+        (anchor, ref_base(ref_seq, anchor)?),
+        take_anchor(&mut alleles, ref_seq, anchor)?,
+      ),
+    };
+    let alt = if ins.pos >= 0 {
+      format!("{alt}{inserted}")
+    } else {
+      format!("{inserted}{alt}")
+    };
+    alleles.insert(site, alt);
+  }
+
+  Ok(alleles)
+}
+
+/// Removes the substitution at the anchor base of an indel, if any, and returns the anchor base of the query
+fn take_anchor(alleles: &mut BTreeMap<VcfSite, String>, ref_seq: &str, anchor: usize) -> Result<String, Report> {
+  let reff = ref_base(ref_seq, anchor)?;
+  Ok(alleles.remove(&(anchor, reff.clone())).unwrap_or(reff))
+}
+
+fn ref_base(ref_seq: &str, pos: usize) -> Result<String, Report> {
+  ref_slice(ref_seq, pos, pos + 1)
+}
+
+fn ref_slice(ref_seq: &str, begin: usize, end: usize) -> Result<String, Report> {
+  match ref_seq.get(begin..end) {
+    Some(slice) => Ok(slice.to_owned()),
+    None => make_error!(
+      "Unable to extract reference allele at positions {}..{}: the reference sequence has length {}",
+      begin + 1,
+      end,
+      ref_seq.len()
+    ),
+  }
+}
+
+pub fn vcf_write_to_file(
+  filepath: impl AsRef<Path>,
+  samples: &[VcfSample],
+  ref_name: &str,
+  ref_len: usize,
+  masked_ranges: &[NucRefGlobalRange],
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut file = create_file_or_stdout(filepath)?;
+  write!(file, "{}", vcf_to_string(samples, ref_name, ref_len, masked_ranges))
+    .wrap_err_with(|| format!("When writing VCF file: {filepath:#?}"))
+}
+
+/// Renders a VCF (v4.2) file with one haploid genotype column per sample. Sites are the distinct combinations of
+/// position and reference allele among the variants of all samples, with all of the alternative alleles observed at the
+/// site. Samples without a variant at a site have the reference allele, unless any of the positions of the reference
+/// allele is outside of their alignment, in a range of missing data or of ambiguous nucleotides, or deleted by another
+/// of their variants, in which case the genotype is unknown (`.`). Sites overlapping the masked ranges,
+/// which are excluded from mutation calling and QC, have the filter `MASKED`.
+pub fn vcf_to_string(
+  samples: &[VcfSample],
+  ref_name: &str,
+  ref_len: usize,
+  masked_ranges: &[NucRefGlobalRange],
+) -> String {
+  // CHROM column cannot contain whitespace
+  let chrom = ref_name.split_whitespace().next().unwrap_or("reference");
+
+  let mut sites = BTreeMap::<&VcfSite, BTreeSet<&str>>::new();
+  for sample in samples {
+    for (site, alt) in &sample.alleles {
+      sites.entry(site).or_default().insert(alt);
+    }
+  }
+
+  let header = [
+    "##fileformat=VCFv4.2".to_owned(),
+    format!("##source=Nextclade {}", this_package_version_str()),
+    format!("##contig=<ID={chrom},length={ref_len}>"),
+    r#"##FILTER=<ID=MASKED,Description="Site overlaps a masked range of the reference, excluded from mutation calling and QC">"#.to_owned(),
+    r#"##INFO=<ID=AC,Number=A,Type=Integer,Description="Number of samples with each of the alternative alleles">"#.to_owned(),
+    r#"##INFO=<ID=NS,Number=1,Type=Integer,Description="Number of samples with data">"#.to_owned(),
+    r#"##FORMAT=<ID=GT,Number=1,Type=String,Description="Genotype">"#.to_owned(),
+    chain!(
+      ["#CHROM", "POS", "ID", "REF", "ALT", "QUAL", "FILTER", "INFO", "FORMAT"].map(str::to_owned),
+      samples.iter().map(|sample| sample.seq_name.replace('\t', " "))
+    )
+    .join("\t"),
+  ];
+
+  let records = sites.iter().map(|(site, alts)| {
+    let (pos, reff) = site;
+    let site_range = NucRefGlobalRange::from_usize(*pos, pos + reff.len());
+
+    // Index of the allele of every sample: 0 for reference, 1.. for the alternative alleles, `None` if unknown
+    let genotypes = samples
+      .iter()
+      .map(|sample| match sample.alleles.get(*site) {
+        Some(alt) => alts
+          .iter()
+          .position(|candidate| *candidate == alt.as_str())
+          .map(|i| i + 1),
+        None => sample.is_covered(&site_range).then_some(0),
+      })
+      .collect_vec();
+
+    let allele_counts = (1..=alts.len())
+      .map(|allele| genotypes.iter().filter(|gt| **gt == Some(allele)).count())
+      .join(",");
+    let num_samples_with_data = genotypes.iter().filter(|gt| gt.is_some()).count();
+
+    let filter = if masked_ranges
+      .iter()
+      .any(|masked| have_intersection(masked, &site_range))
+    {
+      "MASKED"
+    } else {
+      "PASS"
+    };
+
+    let genotypes = genotypes
+      .iter()
+      .map(|gt| gt.map_or_else(|| ".".to_owned(), |gt| gt.to_string()))
+      .join("\t");
+
+    format!(
+      "{chrom}\t{}\t.\t{reff}\t{}\t.\t{filter}\tAC={allele_counts};NS={num_samples_with_data}\tGT\t{genotypes}",
+      pos + 1,
+      alts.iter().join(","),
+    )
+  });
+
+  chain!(header, records).map(|line| format!("{line}\n")).join("")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::io::fasta::FastaRecord;
+  use crate::o;
+  use crate::run::nextclade_wasm::{Nextclade, NextcladeParams, NextcladeParamsRaw};
+  use crate::run::params::NextcladeInputParamsOptional;
+  use crate::utils::error::report_to_string;
+  use crate::utils::random::random_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::str::FromStr;
+
+  fn sample(
+    seq_name: &str,
+    alleles: &[(usize, &str, &str)],
+    unknown: &[(usize, usize)],
+    deleted: &[(usize, usize)],
+  ) -> VcfSample {
+    VcfSample {
+      index: 0,
+      seq_name: seq_name.to_owned(),
+      alleles: alleles
+        .iter()
+        .map(|(pos, reff, alt)| ((*pos, (*reff).to_owned()), (*alt).to_owned()))
+        .collect(),
+      alignment_range: Some(NucRefGlobalRange::from_usize(0, 20)),
+      unknown: unknown
+        .iter()
+        .map(|(begin, end)| NucRefGlobalRange::from_usize(*begin, *end))
+        .collect(),
+      deleted: deleted
+        .iter()
+        .map(|(begin, end)| NucRefGlobalRange::from_usize(*begin, *end))
+        .collect(),
+    }
+  }
+
+  #[rstest]
+  fn writes_multi_sample_vcf() {
+    let samples = [
+      sample("A", &[(2, "C", "T"), (4, "GAT", "G")], &[], &[(5, 7)]),
+      sample("B", &[(2, "C", "G")], &[(4, 8)], &[]),
+    ];
+    let masked_ranges = [NucRefGlobalRange::from_usize(5, 6)];
+
+    let vcf = vcf_to_string(&samples, "MN908947 (Wuhan-Hu-1)", 20, &masked_ranges);
+    let records = vcf.lines().filter(|line| !line.starts_with('#')).collect_vec();
+
+    assert_eq!(
+      records,
+      vec![
+        "MN908947\t3\t.\tC\tG,T\t.\tPASS\tAC=1,1;NS=2\tGT\t2\t1",
+        "MN908947\t5\t.\tGAT\tG\t.\tMASKED\tAC=1;NS=1\tGT\t1\t.",
+      ]
+    );
+  }
+
+  #[rstest]
+  fn writes_unknown_genotype_for_overlapping_deletion() {
+    let samples = [
+      // Deletion of positions 5..7 (1-based 6..7), anchored at position 4
+      sample("A", &[(4, "GAT", "G")], &[], &[(5, 7)]),
+      // Substitution inside of the deletion of the sample A
+      sample("B", &[(6, "T", "C")], &[], &[]),
+      // Missing data inside of the reference allele span of the deletion site, but not at its anchor
+      sample("C", &[], &[(5, 6)], &[]),
+    ];
+
+    let vcf = vcf_to_string(&samples, "ref", 20, &[]);
+    let records = vcf.lines().filter(|line| !line.starts_with('#')).collect_vec();
+
+    assert_eq!(
+      records,
+      vec![
+        "ref\t5\t.\tGAT\tG\t.\tPASS\tAC=1;NS=2\tGT\t1\t0\t.",
+        "ref\t7\t.\tT\tC\t.\tPASS\tAC=1;NS=2\tGT\t.\t1\t0",
+      ]
+    );
+  }
+
+  fn nuc_sub(s: &str) -> NucSub {
+    NucSub::from_str(s).unwrap()
+  }
+
+  fn insertion(pos: i32, ins: &str) -> Insertion<Nuc> {
+    Insertion {
+      pos,
+      ins: to_nuc_seq(ins).unwrap(),
+    }
+  }
+
+  #[rstest]
+  fn merges_insertion_and_deletion_at_same_anchor() -> Result<(), Report> {
+    // 0-based positions 3..5 deleted and "TT" inserted after position 2, which is also substituted
+    let alleles = find_vcf_alleles(
+      &[nuc_sub("G3C")],
+      &[NucDelRange::from_usize(3, 5)],
+      &[insertion(2, "TT")],
+      "ACGTACGT",
+    )?;
+    assert_eq!(alleles, BTreeMap::from([((2, o!("GTA")), o!("CTT"))]));
+    Ok(())
+  }
+
+  #[rstest]
+  fn merges_insertion_and_deletion_at_start_of_genome() -> Result<(), Report> {
+    let alleles = find_vcf_alleles(
+      &[],
+      &[NucDelRange::from_usize(0, 2)],
+      &[insertion(-1, "TT")],
+      "ACGTACGT",
+    )?;
+    assert_eq!(alleles, BTreeMap::from([((0, o!("ACG")), o!("TTG"))]));
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_variant_outside_of_reference() {
+    let result = find_vcf_alleles(&[], &[NucDelRange::from_usize(10, 12)], &[], "ACGTACGT");
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "Unable to extract reference allele at positions 10..12: the reference sequence has length 8"
+    );
+  }
+
+  #[test]
+  fn writes_unknown_genotype_for_ambiguous_nucleotides() -> Result<(), Report> {
+    const PATHOGEN_JSON: &str = r#"{
+      "schemaVersion": "3.0.0",
+      "files": { "reference": "reference.fasta", "pathogenJson": "pathogen.json" }
+    }"#;
+
+    let ref_nucs = random_nuc_seq(&mut 42, 1000);
+    let ref_seq = from_nuc_seq(&ref_nucs);
+    let params = NextcladeParams::from_raw(NextcladeParamsRaw {
+      ref_seq: format!(">ref\n{ref_seq}\n"),
+      gene_map: None,
+      tree: None,
+      virus_properties: PATHOGEN_JSON.to_owned(),
+    })?;
+    let nextclade = Nextclade::new(params, &NextcladeInputParamsOptional::default())?;
+
+    // Deletion of 3 nucleotides in one sequence, and an ambiguous nucleotide inside of it in the other
+    let with_deletion = from_nuc_seq(&[&ref_nucs[..500], &ref_nucs[503..]].concat());
+    let mut with_ambiguous = ref_nucs.clone();
+    with_ambiguous[501] = Nuc::R;
+    let with_ambiguous = from_nuc_seq(&with_ambiguous);
+
+    let mut collector = VcfSampleCollector::new(&ref_nucs);
+    for (index, seq) in [with_deletion, with_ambiguous].into_iter().enumerate() {
+      let record = FastaRecord {
+        seq_name: format!("qry{index}"),
+        seq,
+        index,
+      };
+      collector.add(&nextclade.run(&record)?.analysis_result);
+    }
+
+    let vcf = vcf_to_string(&collector.finish()?, "ref", ref_seq.len(), &[]);
+    let deletion_record = vcf
+      .lines()
+      .filter(|line| !line.starts_with('#'))
+      .find(|line| line.split('\t').nth(3).is_some_and(|reff| reff.len() == 4))
+      .expect("Expected a record of the deletion");
+    assert!(deletion_record.ends_with("\tAC=1;NS=1\tGT\t1\t."));
+    Ok(())
+  }
+}
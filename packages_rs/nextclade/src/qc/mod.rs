@@ -1,7 +1,14 @@
+pub mod qc_batch;
+pub mod qc_calibrate;
 pub mod qc_config;
+pub mod qc_exceptions;
+pub mod qc_rule_custom;
 pub mod qc_rule_frame_shifts;
+pub mod qc_rule_gene_missing_data;
+pub mod qc_rule_indel_clusters;
 pub mod qc_rule_missing_data;
 pub mod qc_rule_mixed_sites;
+pub mod qc_rule_primer_mutations;
 pub mod qc_rule_private_mutations;
 pub mod qc_rule_snp_clusters;
 pub mod qc_rule_stop_codons;
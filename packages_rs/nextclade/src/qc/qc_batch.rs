@@ -0,0 +1,314 @@
+use crate::io::csv::{read_csv_file_with_delimiter, CsvVecFileWriter, VecWriter};
+use crate::io::fs::has_extension;
+use crate::io::json::{json_write, JsonPretty};
+use crate::make_error;
+use crate::qc::qc_run::{QcResult, QcStatus};
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// CDS with coverage below this value is considered failed in a given sequence
+const FAILING_CDS_COVERAGE: f64 = 0.5;
+
+/// CDS is reported as systematically failing in a batch if it fails in at least this fraction of the batch's sequences
+const FAILING_CDS_BATCH_FRACTION: f64 = 0.5;
+
+/// Batch is suspected to be contaminated if at least this fraction of its sequences are flagged by the "mixed sites"
+/// or by the "SNP clusters" QC rule
+const CONTAMINATION_BATCH_FRACTION: f64 = 0.25;
+
+/// Reads mapping from sequence name to batch (plate, sequencing run etc.) from a CSV or TSV file, with columns
+/// `seqName` and `batch_column`. Sequences with empty batch are omitted.
+pub fn qc_batch_map_read(filepath: impl AsRef<Path>, batch_column: &str) -> Result<HashMap<String, String>, Report> {
+  let filepath = filepath.as_ref();
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+
+  let rows: Vec<BTreeMap<String, String>> = read_csv_file_with_delimiter(filepath, delimiter)
+    .wrap_err_with(|| format!("When reading batch map from file {filepath:#?}"))?;
+
+  rows
+    .into_iter()
+    .filter_map(|mut row| {
+      let seq_name = row.remove("seqName");
+      let batch = row.remove(batch_column);
+      match (seq_name, batch) {
+        (None, _) => Some(make_error!("Column 'seqName' is not found in file {filepath:#?}")),
+        (Some(_), None) => Some(make_error!(
+          "Column '{batch_column}' is not found in file {filepath:#?}"
+        )),
+        (Some(_), Some(batch)) if batch.is_empty() => None,
+        (Some(seq_name), Some(batch)) => Some(Ok((seq_name, batch))),
+      }
+    })
+    .collect()
+}
+
+#[derive(Clone, Debug, Default)]
+struct QcBatchAccumulator {
+  num_seqs: usize,
+  num_failed: usize,
+  num_good: usize,
+  num_mediocre: usize,
+  num_bad: usize,
+  sum_qc_score: f64,
+  sum_coverage: f64,
+  sum_missing: usize,
+  num_mixed_sites_flagged: usize,
+  num_snp_clusters_flagged: usize,
+  num_cds_failures: BTreeMap<String, usize>,
+}
+
+/// Accumulates QC results of analyzed sequences, grouped by batch (plate, sequencing run etc.), one sequence at a
+/// time. Problems of a batch, such as contamination or failing amplicons, are often not apparent from individual
+/// sequences, but show as the same QC issue recurring in many sequences of the batch.
+#[derive(Clone, Debug, Default)]
+pub struct QcBatchCounter {
+  batches: BTreeMap<String, QcBatchAccumulator>,
+}
+
+impl QcBatchCounter {
+  /// Adds analysis result of a sequence belonging to the given batch
+  pub fn add(&mut self, batch: &str, output: &NextcladeOutputs) {
+    self.add_qc(
+      batch,
+      &output.qc,
+      output.coverage,
+      output.total_missing,
+      &output.cds_coverage,
+    );
+  }
+
+  fn add_qc(
+    &mut self,
+    batch: &str,
+    qc: &QcResult,
+    coverage: f64,
+    total_missing: usize,
+    cds_coverage: &BTreeMap<String, f64>,
+  ) {
+    let acc = self.batches.entry(batch.to_owned()).or_default();
+    acc.num_seqs += 1;
+
+    match qc.overall_status {
+      QcStatus::Good => acc.num_good += 1,
+      QcStatus::Mediocre => acc.num_mediocre += 1,
+      QcStatus::Bad => acc.num_bad += 1,
+    }
+    acc.sum_qc_score += qc.overall_score;
+    acc.sum_coverage += coverage;
+    acc.sum_missing += total_missing;
+
+    if qc.mixed_sites.as_ref().is_some_and(|r| is_flagged(&r.status)) {
+      acc.num_mixed_sites_flagged += 1;
+    }
+    if qc.snp_clusters.as_ref().is_some_and(|r| is_flagged(&r.status)) {
+      acc.num_snp_clusters_flagged += 1;
+    }
+
+    for (cds, coverage) in cds_coverage {
+      if *coverage < FAILING_CDS_COVERAGE {
+        *acc.num_cds_failures.entry(cds.clone()).or_default() += 1;
+      }
+    }
+  }
+
+  /// Adds a sequence of the given batch, which failed to be analyzed
+  pub fn add_failed(&mut self, batch: &str) {
+    let acc = self.batches.entry(batch.to_owned()).or_default();
+    acc.num_seqs += 1;
+    acc.num_failed += 1;
+  }
+
+  /// Produces the batch QC report, one entry per batch, ordered by batch name
+  pub fn finish(self) -> Vec<QcBatchReport> {
+    self
+      .batches
+      .into_iter()
+      .map(|(batch, acc)| {
+        let num_analyzed = acc.num_seqs - acc.num_failed;
+        let mean = |sum: f64| {
+          if num_analyzed > 0 {
+            sum / num_analyzed as f64
+          } else {
+            0.0
+          }
+        };
+
+        let fraction_mixed_sites_flagged = mean(acc.num_mixed_sites_flagged as f64);
+        let fraction_snp_clusters_flagged = mean(acc.num_snp_clusters_flagged as f64);
+        let contamination_suspected = fraction_mixed_sites_flagged >= CONTAMINATION_BATCH_FRACTION
+          || fraction_snp_clusters_flagged >= CONTAMINATION_BATCH_FRACTION;
+
+        let failing_cdses = acc
+          .num_cds_failures
+          .into_iter()
+          .map(|(cds, num_failures)| QcBatchFailingCds {
+            cds,
+            fraction: mean(num_failures as f64),
+          })
+          .filter(|failing_cds| failing_cds.fraction >= FAILING_CDS_BATCH_FRACTION)
+          .collect_vec();
+
+        QcBatchReport {
+          batch,
+          num_seqs: acc.num_seqs,
+          num_failed: acc.num_failed,
+          num_good: acc.num_good,
+          num_mediocre: acc.num_mediocre,
+          num_bad: acc.num_bad,
+          mean_qc_score: mean(acc.sum_qc_score),
+          mean_coverage: mean(acc.sum_coverage),
+          mean_missing: mean(acc.sum_missing as f64),
+          fraction_mixed_sites_flagged,
+          fraction_snp_clusters_flagged,
+          contamination_suspected,
+          failing_cdses,
+        }
+      })
+      .collect()
+  }
+}
+
+#[inline]
+const fn is_flagged(status: &QcStatus) -> bool {
+  matches!(status, QcStatus::Mediocre | QcStatus::Bad)
+}
+
+/// CDS failing (having low coverage) in a large fraction of sequences of a batch
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcBatchFailingCds {
+  pub cds: String,
+  /// Fraction of the successfully analyzed sequences of the batch, in which the CDS fails
+  pub fraction: f64,
+}
+
+/// Aggregate QC of a batch of sequences. Means and fractions are over the successfully analyzed sequences.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcBatchReport {
+  pub batch: String,
+  pub num_seqs: usize,
+  pub num_failed: usize,
+  pub num_good: usize,
+  pub num_mediocre: usize,
+  pub num_bad: usize,
+  pub mean_qc_score: f64,
+  pub mean_coverage: f64,
+  pub mean_missing: f64,
+  pub fraction_mixed_sites_flagged: f64,
+  pub fraction_snp_clusters_flagged: f64,
+  pub contamination_suspected: bool,
+  pub failing_cdses: Vec<QcBatchFailingCds>,
+}
+
+/// Writes batch QC report. The format is deduced from file extension: JSON for ".json", CSV with semicolon delimiters
+/// for ".csv" and TSV otherwise.
+pub fn qc_batch_write(filepath: impl AsRef<Path>, reports: &[QcBatchReport]) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+
+  if has_extension(filepath, "json") {
+    return json_write(filepath, &reports, JsonPretty(true));
+  }
+
+  let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+  let headers = [
+    "batch",
+    "numSeqs",
+    "numFailed",
+    "numGood",
+    "numMediocre",
+    "numBad",
+    "meanQcScore",
+    "meanCoverage",
+    "meanMissing",
+    "fractionMixedSitesFlagged",
+    "fractionSnpClustersFlagged",
+    "contaminationSuspected",
+    "failingCdses",
+  ]
+  .iter()
+  .map(|&header| header.to_owned())
+  .collect_vec();
+
+  let mut writer = CsvVecFileWriter::new(filepath, delimiter, &headers)?;
+  for report in reports {
+    writer
+      .write([
+        report.batch.clone(),
+        report.num_seqs.to_string(),
+        report.num_failed.to_string(),
+        report.num_good.to_string(),
+        report.num_mediocre.to_string(),
+        report.num_bad.to_string(),
+        format!("{:.2}", report.mean_qc_score),
+        format!("{:.4}", report.mean_coverage),
+        format!("{:.1}", report.mean_missing),
+        format!("{:.4}", report.fraction_mixed_sites_flagged),
+        format!("{:.4}", report.fraction_snp_clusters_flagged),
+        report.contamination_suspected.to_string(),
+        report
+          .failing_cdses
+          .iter()
+          .map(|failing_cds| format!("{}:{:.2}", failing_cds.cds, failing_cds.fraction))
+          .join(","),
+      ])
+      .wrap_err_with(|| format!("When writing batch QC report: {filepath:#?}"))?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use crate::qc::qc_rule_mixed_sites::QcResultMixedSites;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn add(counter: &mut QcBatchCounter, batch: &str, status: QcStatus, mixed_sites_status: QcStatus, s_coverage: f64) {
+    let qc = QcResult {
+      overall_status: status,
+      mixed_sites: Some(QcResultMixedSites {
+        status: mixed_sites_status,
+        ..QcResultMixedSites::default()
+      }),
+      ..QcResult::default()
+    };
+    let cds_coverage = BTreeMap::from([(o!("S"), s_coverage), (o!("N"), 1.0)]);
+    counter.add_qc(batch, &qc, 1.0, 0, &cds_coverage);
+  }
+
+  #[rstest]
+  fn aggregates_qc_per_batch() {
+    let mut counter = QcBatchCounter::default();
+    add(&mut counter, "plate1", QcStatus::Good, QcStatus::Good, 0.2);
+    add(&mut counter, "plate1", QcStatus::Bad, QcStatus::Bad, 0.3);
+    add(&mut counter, "plate1", QcStatus::Good, QcStatus::Good, 1.0);
+    counter.add_failed("plate1");
+    add(&mut counter, "plate2", QcStatus::Mediocre, QcStatus::Good, 1.0);
+
+    let reports = counter.finish();
+
+    assert_eq!(reports.len(), 2);
+
+    let plate1 = &reports[0];
+    assert_eq!(plate1.batch, "plate1");
+    assert_eq!(
+      (plate1.num_seqs, plate1.num_failed, plate1.num_good, plate1.num_bad),
+      (4, 1, 2, 1)
+    );
+    assert!(plate1.contamination_suspected);
+    assert_eq!(plate1.failing_cdses.len(), 1);
+    assert_eq!(plate1.failing_cdses[0].cds, "S");
+
+    let plate2 = &reports[1];
+    assert_eq!(plate2.num_mediocre, 1);
+    assert!(!plate2.contamination_suspected);
+    assert!(plate2.failing_cdses.is_empty());
+  }
+}
@@ -0,0 +1,149 @@
+use crate::make_error;
+use crate::qc::qc_config::QcConfig;
+use crate::qc::qc_rule_private_mutations::find_deletion_ranges;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::Report;
+use log::info;
+use ordered_float::OrderedFloat;
+
+/// QC-relevant quantities of one known-good sequence
+#[derive(Clone, Debug, Default)]
+pub struct QcCalibrationSample {
+  pub total_missing: usize,
+  pub total_mixed_sites: usize,
+  /// Absent if the results were produced without a reference tree
+  pub private_mutations: Option<QcCalibrationPrivateMutations>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QcCalibrationPrivateMutations {
+  pub num_reversion_substitutions: usize,
+  pub num_labeled_substitutions: usize,
+  pub num_unlabeled_substitutions: usize,
+  pub total_deletion_ranges: usize,
+}
+
+impl QcCalibrationSample {
+  pub fn from_outputs(outputs: &NextcladeOutputs) -> Self {
+    // Without reference tree there is no clade and no private mutations
+    let has_tree = !outputs.clade.is_empty();
+    let private = &outputs.private_nuc_mutations;
+    Self {
+      total_missing: outputs.total_missing,
+      total_mixed_sites: outputs.total_non_acgtns,
+      private_mutations: has_tree.then(|| QcCalibrationPrivateMutations {
+        num_reversion_substitutions: private.reversion_substitutions.len(),
+        num_labeled_substitutions: private.labeled_substitutions.len(),
+        num_unlabeled_substitutions: private.unlabeled_substitutions.len(),
+        total_deletion_ranges: find_deletion_ranges(&private.private_deletions).len(),
+      }),
+    }
+  }
+}
+
+/// Suggests parameters of QC rules from the results of known-good sequences. Parameters are chosen such that a sequence
+/// at the given percentile of the good sequences is at the boundary between "good" and "mediocre" QC status, and a
+/// sequence at the median is not penalized at all. The remaining parameters are taken from the base config.
+///
+/// Calibrated rules: "missing data", "mixed sites" and "private mutations" (the latter only if the samples contain
+/// private mutations, i.e. if the results were produced with a reference tree).
+pub fn qc_calibrate(samples: &[QcCalibrationSample], base: &QcConfig, percentile: f64) -> Result<QcConfig, Report> {
+  if samples.is_empty() {
+    return make_error!("QC calibration: no results of successfully analyzed sequences found in the inputs");
+  }
+  if !(50.0..=100.0).contains(&percentile) {
+    return make_error!("QC calibration: percentile should be between 50 and 100, but found {percentile}");
+  }
+
+  let mut config = base.clone();
+  let mediocre = config.status_thresholds.mediocre;
+
+  // Scores grow linearly, such that the distance between the median and the percentile corresponds to the score of
+  // the "mediocre" status threshold
+  let scale = 100.0 / mediocre;
+
+  let missing = samples.iter().map(|s| s.total_missing as f64).collect();
+  let (median, high) = median_and_percentile(missing, percentile);
+  config.missing_data.enabled = true;
+  config.missing_data.score_bias = median;
+  config.missing_data.missing_data_threshold = (high - median).max(1.0) * scale;
+  info!(
+    "QC calibration: missing data: median {median}, {percentile}th percentile {high}: scoreBias = {}, missingDataThreshold = {}",
+    config.missing_data.score_bias, config.missing_data.missing_data_threshold
+  );
+
+  let mixed = samples.iter().map(|s| s.total_mixed_sites as f64).collect();
+  let (_, high) = median_and_percentile(mixed, percentile);
+  config.mixed_sites.enabled = true;
+  config.mixed_sites.mixed_sites_threshold = (high.max(1.0) * scale).ceil() as usize;
+  info!(
+    "QC calibration: mixed sites: {percentile}th percentile {high}: mixedSitesThreshold = {}",
+    config.mixed_sites.mixed_sites_threshold
+  );
+
+  let private = &config.private_mutations;
+  let weighted: Vec<f64> = samples
+    .iter()
+    .filter_map(|s| s.private_mutations.as_ref())
+    .map(|p| {
+      private.weight_reversion_substitutions * p.num_reversion_substitutions as f64
+        + private.weight_labeled_substitutions * p.num_labeled_substitutions as f64
+        + private.weight_unlabeled_substitutions * p.num_unlabeled_substitutions as f64
+        + p.total_deletion_ranges as f64
+    })
+    .collect();
+  if weighted.is_empty() {
+    info!("QC calibration: private mutations: not found in the inputs (results produced without reference tree?). Keeping the parameters unchanged.");
+  } else {
+    let (median, high) = median_and_percentile(weighted, percentile);
+    config.private_mutations.enabled = true;
+    config.private_mutations.typical = median;
+    config.private_mutations.cutoff = (high - median).max(1.0) * scale;
+    info!(
+      "QC calibration: private mutations: weighted total median {median}, {percentile}th percentile {high}: typical = {}, cutoff = {}",
+      config.private_mutations.typical, config.private_mutations.cutoff
+    );
+  }
+
+  Ok(config)
+}
+
+/// Returns median and the value at the given percentile (nearest rank method)
+fn median_and_percentile(mut values: Vec<f64>, percentile: f64) -> (f64, f64) {
+  values.sort_unstable_by_key(|x| OrderedFloat(*x));
+  let nearest_rank = |p: f64| {
+    let rank = ((p / 100.0) * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+  };
+  (nearest_rank(50.0), nearest_rank(percentile))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn calculates_nearest_rank_percentiles() {
+    let values = (1..=100).map(f64::from).collect();
+    assert_eq!(median_and_percentile(values, 99.0), (50.0, 99.0));
+  }
+
+  #[rstest]
+  fn puts_percentile_at_mediocre_boundary() -> Result<(), Report> {
+    let samples = (0..100)
+      .map(|i| QcCalibrationSample {
+        total_missing: i * 10,
+        ..QcCalibrationSample::default()
+      })
+      .collect::<Vec<_>>();
+
+    let config = qc_calibrate(&samples, &QcConfig::default(), 90.0)?;
+    let score = |total_missing: f64| {
+      (total_missing - config.missing_data.score_bias) * 100.0 / config.missing_data.missing_data_threshold
+    };
+    assert!((score(890.0) - 30.0).abs() < 1e-9);
+    Ok(())
+  }
+}
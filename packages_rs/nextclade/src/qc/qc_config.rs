@@ -1,8 +1,12 @@
 use crate::coord::range::AaRefRange;
 use crate::io::fs::read_file_to_string;
 use crate::io::json::json_parse;
+use crate::make_error;
+use crate::qc::qc_exceptions::QcException;
+use crate::qc::qc_run::qc_builtin_rule_names;
 use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::str::FromStr;
 use validator::Validate;
@@ -16,12 +20,38 @@ pub struct QcRulesConfigMissingData {
   pub score_bias: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct QcRulesConfigGeneMissingData {
+  pub enabled: bool,
+  /// Names of the CDSes to check. All CDSes are checked if empty.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub genes: Vec<String>,
+  /// Fraction of unknown codons in a CDS at which the CDS gets a score of 100 ("bad")
+  pub missing_fraction_threshold: f64,
+}
+
+impl Default for QcRulesConfigGeneMissingData {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      genes: vec![],
+      missing_fraction_threshold: 0.1,
+    }
+  }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 #[serde(default)]
 pub struct QcRulesConfigMixedSites {
   pub enabled: bool,
   pub mixed_sites_threshold: usize,
+
+  /// Multiplier of the contribution of mixed sites at the clade-discriminating positions of the reference tree
+  #[serde(default = "one")]
+  pub weight_discriminating_sites: f64,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
@@ -66,6 +96,30 @@ pub struct QcRulesConfigSnpClusters {
   pub score_weight: f64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct QcRulesConfigIndelClusters {
+  pub enabled: bool,
+  pub window_size: usize,
+  pub cluster_cut_off: usize,
+  /// Longer insertions and deletions are not counted, because they are less likely to be sequencing artifacts
+  pub max_indel_length: usize,
+  pub score_weight: f64,
+}
+
+impl Default for QcRulesConfigIndelClusters {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      window_size: 100,
+      cluster_cut_off: 3,
+      max_indel_length: 10,
+      score_weight: 50.0,
+    }
+  }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 #[serde(default)]
@@ -81,6 +135,8 @@ pub struct QcRulesConfigFrameShifts {
   pub enabled: bool,
   #[serde(default, skip_serializing_if = "Vec::is_empty")]
   pub ignored_frame_shifts: Vec<FrameShiftLocation>,
+  /// Do not penalize compensated frame shifts, i.e. those after which the reading frame is restored shortly
+  pub ignore_compensated: bool,
   pub score_weight: f64,
 }
 
@@ -89,11 +145,33 @@ impl Default for QcRulesConfigFrameShifts {
     Self {
       enabled: false,
       ignored_frame_shifts: vec![],
+      ignore_compensated: false,
       score_weight: 75.0,
     }
   }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct QcRulesConfigPrimerMutations {
+  pub enabled: bool,
+  /// Names of the primers, mutations in which are not penalized
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub ignored_primers: Vec<String>,
+  pub score_weight: f64,
+}
+
+impl Default for QcRulesConfigPrimerMutations {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      ignored_primers: vec![],
+      score_weight: 50.0,
+    }
+  }
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct StopCodonLocation {
@@ -121,17 +199,96 @@ impl Default for QcRulesConfigStopCodons {
   }
 }
 
+/// Quantity calculated for every query sequence, which a custom QC rule can be based on
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::enum_variant_names)] // Variant names are the metric names used in QC configs
+pub enum QcMetric {
+  #[default]
+  TotalMissing,
+  TotalMixedSites,
+  TotalFrameShifts,
+  TotalPrivateSubstitutions,
+  TotalPrivateDeletions,
+  TotalReversionSubstitutions,
+  TotalLabeledSubstitutions,
+  TotalUnlabeledSubstitutions,
+}
+
+/// Simple QC rule declared in the QC config: the score grows linearly with the value of the metric, such that it is 0
+/// when the value is at or below `scoreBias` and 100 (bad) when the value exceeds `scoreBias` by `threshold`
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct QcRulesConfigCustom {
+  pub name: String,
+  pub enabled: bool,
+  pub metric: QcMetric,
+  pub threshold: f64,
+  pub score_bias: f64,
+}
+
+impl Default for QcRulesConfigCustom {
+  fn default() -> Self {
+    Self {
+      name: String::new(),
+      enabled: true,
+      metric: QcMetric::default(),
+      threshold: 1.0,
+      score_bias: 0.0,
+    }
+  }
+}
+
+/// Overall QC scores at which the overall QC status becomes "mediocre" and "bad"
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct QcStatusThresholds {
+  pub mediocre: f64,
+  pub bad: f64,
+}
+
+impl Default for QcStatusThresholds {
+  fn default() -> Self {
+    Self {
+      mediocre: 30.0,
+      bad: 100.0,
+    }
+  }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, schemars::JsonSchema, Validate)]
 #[serde(rename_all = "camelCase")]
 #[serde(default)]
 pub struct QcConfig {
   pub schema_version: String,
   pub missing_data: QcRulesConfigMissingData,
+  pub gene_missing_data: QcRulesConfigGeneMissingData,
   pub mixed_sites: QcRulesConfigMixedSites,
   pub private_mutations: QcRulesConfigPrivateMutations,
   pub snp_clusters: QcRulesConfigSnpClusters,
+  pub indel_clusters: QcRulesConfigIndelClusters,
   pub frame_shifts: QcRulesConfigFrameShifts,
   pub stop_codons: QcRulesConfigStopCodons,
+  pub primer_mutations: QcRulesConfigPrimerMutations,
+
+  /// Rules declared in the config, in addition to the built-in rules
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub custom_rules: Vec<QcRulesConfigCustom>,
+
+  /// Expressions excluding individual stop codons and frame shifts from the QC score, for example
+  /// `ignore frameShift ORF8:61` or `ignore stopCodon S:27 for clade 21K`
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  #[schemars(with = "Vec<String>")]
+  pub exceptions: Vec<QcException>,
+
+  /// Multipliers of the contributions of individual rules to the overall QC score, by rule name. The rules not listed
+  /// here have weight 1.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub rule_weights: BTreeMap<String, f64>,
+
+  pub status_thresholds: QcStatusThresholds,
 }
 
 impl FromStr for QcConfig {
@@ -143,6 +300,58 @@ impl FromStr for QcConfig {
 }
 
 impl QcConfig {
+  /// Checks that the custom rules and the rule weights refer to the rules consistently
+  pub fn validate_rules(&self) -> Result<(), Report> {
+    let mut rule_names: Vec<&str> = qc_builtin_rule_names().collect();
+    for rule in &self.custom_rules {
+      if rule.name.is_empty() {
+        return make_error!("QC config: custom rules must have a name");
+      }
+      if rule_names.contains(&rule.name.as_str()) {
+        return make_error!(
+          "QC config: custom rule name '{}' is used more than once or conflicts with a name of a built-in rule",
+          rule.name
+        );
+      }
+      if rule.threshold <= 0.0 {
+        return make_error!(
+          "QC config: threshold of custom rule '{}' should be positive, but found {}",
+          rule.name,
+          rule.threshold
+        );
+      }
+      rule_names.push(&rule.name);
+    }
+
+    if let Some(unknown) = self
+      .rule_weights
+      .keys()
+      .find(|name| !rule_names.contains(&name.as_str()))
+    {
+      return make_error!(
+        "QC config: weight is given for unknown rule '{unknown}'. Possible rule names: {}",
+        rule_names.join(", ")
+      );
+    }
+
+    if self.gene_missing_data.missing_fraction_threshold <= 0.0 {
+      return make_error!(
+        "QC config: 'missingFractionThreshold' of 'geneMissingData' rule should be positive, but found {}",
+        self.gene_missing_data.missing_fraction_threshold
+      );
+    }
+
+    if self.status_thresholds.mediocre > self.status_thresholds.bad {
+      return make_error!(
+        "QC config: status threshold 'mediocre' ({}) should not be greater than 'bad' ({})",
+        self.status_thresholds.mediocre,
+        self.status_thresholds.bad
+      );
+    }
+
+    Ok(())
+  }
+
   pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
     let filepath = filepath.as_ref();
     let data = read_file_to_string(filepath).wrap_err_with(|| format!("When reading QC config file {filepath:#?}"))?;
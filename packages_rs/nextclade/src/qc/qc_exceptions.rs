@@ -0,0 +1,252 @@
+use crate::coord::position::AaRefPosition;
+use crate::coord::range::{have_intersection, AaRefRange};
+use crate::io::parse_pos::parse_pos;
+use crate::make_error;
+use crate::qc::qc_config::StopCodonLocation;
+use crate::translate::frame_shifts_translate::FrameShift;
+use eyre::{Report, WrapErr};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// QC rule, findings of which can be excluded using a QC exception
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QcExceptionRule {
+  StopCodon,
+  FrameShift,
+}
+
+impl QcExceptionRule {
+  pub const fn as_str(self) -> &'static str {
+    match self {
+      QcExceptionRule::StopCodon => "stopCodon",
+      QcExceptionRule::FrameShift => "frameShift",
+    }
+  }
+}
+
+/// Exception from a QC rule, declared in the QC config as a small expression, for example:
+///
+///  - `ignore frameShift ORF8:61` - frame shifts in CDS ORF8 overlapping codon 61 are not counted
+///  - `ignore stopCodon S:27 for clade 21K` - stop codon at codon 27 of CDS S is not counted in sequences of clade 21K
+///  - `ignore frameShift ORF3a` - frame shifts anywhere in CDS ORF3a are not counted
+///
+/// Codon positions are 1-based and ranges (`<begin>-<end>`) are closed, as commonly used in bioinformatics.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QcException {
+  pub rule: QcExceptionRule,
+  pub cds_name: String,
+  pub codon_range: Option<AaRefRange>,
+  pub clade: Option<String>,
+}
+
+impl QcException {
+  /// Checks whether the exception applies to sequences of the given clade
+  pub fn applies_to_clade(&self, clade: Option<&str>) -> bool {
+    match &self.clade {
+      None => true,
+      Some(exception_clade) => clade == Some(exception_clade.as_str()),
+    }
+  }
+
+  pub fn matches_stop_codon(&self, stop_codon: &StopCodonLocation) -> bool {
+    self.rule == QcExceptionRule::StopCodon
+      && self.cds_name == stop_codon.gene_name
+      && self.codon_range.as_ref().map_or(true, |range| {
+        range.contains(AaRefPosition::from(stop_codon.codon as isize))
+      })
+  }
+
+  pub fn matches_frame_shift(&self, frame_shift: &FrameShift) -> bool {
+    self.rule == QcExceptionRule::FrameShift
+      && self.cds_name == frame_shift.gene_name
+      && self
+        .codon_range
+        .as_ref()
+        .map_or(true, |range| have_intersection(range, &frame_shift.codon))
+  }
+}
+
+const QC_EXCEPTION_REGEX: &str = r"^\s*ignore\s+(?P<rule>stopCodon|frameShift)\s+(?P<cds>[^\s:]+)(:(?P<begin>\d{1,10})(-(?P<end>\d{1,10}))?)?(\s+for\s+clade\s+(?P<clade>\S+))?\s*$";
+
+impl FromStr for QcException {
+  type Err = Report;
+
+  /// Parses QC exception from string of the form `ignore <stopCodon|frameShift> <cds>[:<begin>[-<end>]] [for clade <clade>]`
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    lazy_static! {
+      static ref RE: Regex = Regex::new(QC_EXCEPTION_REGEX)
+        .wrap_err_with(|| format!("When compiling regular expression '{QC_EXCEPTION_REGEX}'"))
+        .unwrap();
+    }
+
+    let Some(captures) = RE.captures(s) else {
+      return make_error!(
+        "Unable to parse QC exception: '{s}'. Expected: 'ignore <stopCodon|frameShift> <cds>[:<begin>[-<end>]] [for clade <clade>]'"
+      );
+    };
+
+    let rule = match captures.name("rule").map(|m| m.as_str()) {
+      Some("stopCodon") => QcExceptionRule::StopCodon,
+      Some("frameShift") => QcExceptionRule::FrameShift,
+      _ => return make_error!("Unable to parse QC exception: '{s}': unknown rule"),
+    };
+
+    let cds_name = captures.name("cds").map(|m| m.as_str().to_owned()).unwrap_or_default();
+
+    let codon_range = match captures.name("begin") {
+      None => None,
+      Some(begin) => {
+        let parse_codon = |codon: &str| {
+          if codon.bytes().all(|c| c == b'0') {
+            return make_error!(
+              "Unable to parse QC exception: '{s}': codons are numbered starting from 1, but found codon 0"
+            );
+          }
+          parse_pos(codon).wrap_err_with(|| format!("When parsing QC exception: '{s}'"))
+        };
+        let begin = parse_codon(begin.as_str())?;
+        let end = match captures.name("end") {
+          None => begin,
+          Some(end) => parse_codon(end.as_str())?,
+        };
+        if begin > end {
+          return make_error!("Unable to parse QC exception: '{s}': end of the codon range is before its beginning");
+        }
+        Some(AaRefRange::from_isize(begin, end + 1))
+      }
+    };
+
+    let clade = captures.name("clade").map(|m| m.as_str().to_owned());
+
+    Ok(Self {
+      rule,
+      cds_name,
+      codon_range,
+      clade,
+    })
+  }
+}
+
+impl Display for QcException {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "ignore {} {}", self.rule.as_str(), self.cds_name)?;
+    if let Some(codon_range) = &self.codon_range {
+      write!(f, ":{codon_range}")?;
+    }
+    if let Some(clade) = &self.clade {
+      write!(f, " for clade {clade}")?;
+    }
+    Ok(())
+  }
+}
+
+impl TryFrom<String> for QcException {
+  type Error = Report;
+
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    Self::from_str(&s)
+  }
+}
+
+impl From<QcException> for String {
+  fn from(exception: QcException) -> Self {
+    exception.to_string()
+  }
+}
+
+/// Selects the exceptions which apply to a sequence of the given clade
+pub fn qc_exceptions_for_clade<'a>(exceptions: &'a [QcException], clade: Option<&str>) -> Vec<&'a QcException> {
+  exceptions
+    .iter()
+    .filter(|exception| exception.applies_to_clade(clade))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn parses_frame_shift_exception() -> Result<(), Report> {
+    let actual = QcException::from_str("ignore frameShift ORF8:61")?;
+    let expected = QcException {
+      rule: QcExceptionRule::FrameShift,
+      cds_name: o!("ORF8"),
+      codon_range: Some(AaRefRange::from_isize(60, 61)),
+      clade: None,
+    };
+    assert_eq!(actual, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  fn parses_stop_codon_exception_with_range_and_clade() -> Result<(), Report> {
+    let actual = QcException::from_str("ignore stopCodon S:27-30 for clade 21K")?;
+    let expected = QcException {
+      rule: QcExceptionRule::StopCodon,
+      cds_name: o!("S"),
+      codon_range: Some(AaRefRange::from_isize(26, 30)),
+      clade: Some(o!("21K")),
+    };
+    assert_eq!(actual, expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case("ignore frameShift ORF3a")]
+  #[case("ignore stopCodon S:27")]
+  #[case("ignore stopCodon S:27-30 for clade 21K")]
+  fn formats_exception_as_parsed(#[case] input: &str) -> Result<(), Report> {
+    assert_eq!(QcException::from_str(input)?.to_string(), input);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case(
+    "ignore insertion S:27",
+    "Unable to parse QC exception: 'ignore insertion S:27'. Expected: 'ignore <stopCodon|frameShift> <cds>[:<begin>[-<end>]] [for clade <clade>]'"
+  )]
+  #[case(
+    "ignore stopCodon S:30-27",
+    "Unable to parse QC exception: 'ignore stopCodon S:30-27': end of the codon range is before its beginning"
+  )]
+  #[case(
+    "ignore stopCodon S:0",
+    "Unable to parse QC exception: 'ignore stopCodon S:0': codons are numbered starting from 1, but found codon 0"
+  )]
+  #[case(
+    "ignore frameShift ORF8:0-3",
+    "Unable to parse QC exception: 'ignore frameShift ORF8:0-3': codons are numbered starting from 1, but found codon 0"
+  )]
+  #[case(
+    "ignore stopCodon S:27-00",
+    "Unable to parse QC exception: 'ignore stopCodon S:27-00': codons are numbered starting from 1, but found codon 0"
+  )]
+  #[case(
+    "stopCodon S:27",
+    "Unable to parse QC exception: 'stopCodon S:27'. Expected: 'ignore <stopCodon|frameShift> <cds>[:<begin>[-<end>]] [for clade <clade>]'"
+  )]
+  fn rejects_invalid_exception(#[case] input: &str, #[case] message: &str) {
+    assert_eq!(report_to_string(&QcException::from_str(input).unwrap_err()), message);
+  }
+
+  #[rstest]
+  fn matches_stop_codon_in_range_only() -> Result<(), Report> {
+    let exception = QcException::from_str("ignore stopCodon S:27")?;
+    let stop_codon = |codon| StopCodonLocation {
+      gene_name: o!("S"),
+      codon,
+    };
+    assert!(exception.matches_stop_codon(&stop_codon(26)));
+    assert!(!exception.matches_stop_codon(&stop_codon(27)));
+    Ok(())
+  }
+}
@@ -0,0 +1,64 @@
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
+use crate::qc::qc_config::{QcMetric, QcRulesConfigCustom, QcStatusThresholds};
+use crate::qc::qc_rule_mixed_sites::count_mixed_sites;
+use crate::qc::qc_run::{QcRule, QcStatus};
+use crate::translate::frame_shifts_translate::FrameShift;
+use num::traits::clamp_min;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcResultCustom {
+  pub name: String,
+  pub score: f64,
+  pub status: QcStatus,
+  pub metric: QcMetric,
+  pub value: usize,
+  pub threshold: f64,
+}
+
+impl QcRule for QcResultCustom {
+  fn score(&self) -> f64 {
+    self.score
+  }
+}
+
+/// Runs a QC rule declared in the QC config. Rules based on private mutations are skipped if private mutations are not
+/// available.
+pub fn rule_custom(
+  private_nuc_mutations: Option<&PrivateNucMutations>,
+  nucleotide_composition: &BTreeMap<Nuc, usize>,
+  total_missing: usize,
+  frame_shifts: &[FrameShift],
+  config: &QcRulesConfigCustom,
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultCustom> {
+  if !config.enabled {
+    return None;
+  }
+
+  let value = match config.metric {
+    QcMetric::TotalMissing => total_missing,
+    QcMetric::TotalMixedSites => count_mixed_sites(nucleotide_composition),
+    QcMetric::TotalFrameShifts => frame_shifts.len(),
+    QcMetric::TotalPrivateSubstitutions => private_nuc_mutations?.total_private_substitutions,
+    QcMetric::TotalPrivateDeletions => private_nuc_mutations?.total_private_deletions,
+    QcMetric::TotalReversionSubstitutions => private_nuc_mutations?.total_reversion_substitutions,
+    QcMetric::TotalLabeledSubstitutions => private_nuc_mutations?.total_labeled_substitutions,
+    QcMetric::TotalUnlabeledSubstitutions => private_nuc_mutations?.total_unlabeled_substitutions,
+  };
+
+  let score = clamp_min(((value as f64 - config.score_bias) * 100.0) / config.threshold, 0.0);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
+
+  Some(QcResultCustom {
+    name: config.name.clone(),
+    score,
+    status,
+    metric: config.metric,
+    value,
+    threshold: config.threshold + config.score_bias,
+  })
+}
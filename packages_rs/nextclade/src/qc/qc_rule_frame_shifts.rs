@@ -1,15 +1,24 @@
-use crate::qc::qc_config::QcRulesConfigFrameShifts;
+use crate::qc::qc_config::{QcRulesConfigFrameShifts, QcStatusThresholds};
+use crate::qc::qc_exceptions::QcException;
 use crate::qc::qc_run::{QcRule, QcStatus};
 use crate::translate::frame_shifts_translate::FrameShift;
 use serde::{Deserialize, Serialize};
 
 #[inline]
 #[allow(clippy::suspicious_operation_groupings)]
-pub fn is_frame_shift_ignored(frame_shift: &FrameShift, config: &QcRulesConfigFrameShifts) -> bool {
-  config
-    .ignored_frame_shifts
-    .iter()
-    .any(|ignored| ignored.gene_name == frame_shift.gene_name && ignored.codon_range == frame_shift.codon)
+pub fn is_frame_shift_ignored(
+  frame_shift: &FrameShift,
+  config: &QcRulesConfigFrameShifts,
+  exceptions: &[&QcException],
+) -> bool {
+  (config.ignore_compensated && frame_shift.is_compensated)
+    || config
+      .ignored_frame_shifts
+      .iter()
+      .any(|ignored| ignored.gene_name == frame_shift.gene_name && ignored.codon_range == frame_shift.codon)
+    || exceptions
+      .iter()
+      .any(|exception| exception.matches_frame_shift(frame_shift))
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
@@ -32,6 +41,8 @@ impl QcRule for QcResultFrameShifts {
 pub fn rule_frame_shifts(
   all_frame_shifts: &[FrameShift],
   config: &QcRulesConfigFrameShifts,
+  exceptions: &[&QcException],
+  thresholds: &QcStatusThresholds,
 ) -> Option<QcResultFrameShifts> {
   if !config.enabled {
     return None;
@@ -40,13 +51,13 @@ pub fn rule_frame_shifts(
   let (frame_shifts_ignored, frame_shifts): (Vec<FrameShift>, Vec<FrameShift>) = all_frame_shifts
     .iter()
     .cloned()
-    .partition(|frame_shift| is_frame_shift_ignored(frame_shift, config));
+    .partition(|frame_shift| is_frame_shift_ignored(frame_shift, config, exceptions));
 
   let total_frame_shifts = frame_shifts.len();
   let total_frame_shifts_ignored = frame_shifts_ignored.len();
 
   let score = total_frame_shifts as f64 * config.score_weight;
-  let status = QcStatus::from_score(score);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
 
   Some(QcResultFrameShifts {
     score,
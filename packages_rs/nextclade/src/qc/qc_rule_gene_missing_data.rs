@@ -0,0 +1,86 @@
+use crate::alphabet::letter::Letter;
+use crate::gene::gene_map::GeneMap;
+use crate::qc::qc_config::{QcRulesConfigGeneMissingData, QcStatusThresholds};
+use crate::qc::qc_run::{QcRule, QcStatus};
+use crate::translate::translate_genes::Translation;
+use num::traits::clamp_min;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcGeneMissingData {
+  pub gene_name: String,
+  pub score: f64,
+  pub status: QcStatus,
+  pub total_codons: usize,
+  pub total_missing_codons: usize,
+  pub missing_fraction: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcResultGeneMissingData {
+  pub score: f64,
+  pub status: QcStatus,
+  pub genes: Vec<QcGeneMissingData>,
+  pub missing_fraction_threshold: f64,
+}
+
+impl QcRule for QcResultGeneMissingData {
+  fn score(&self) -> f64 {
+    self.score
+  }
+}
+
+/// Checks the fraction of unknown codons (translated into `X`) in each of the configured CDSes. CDSes which could not
+/// be translated are entirely missing. The score of the rule is the score of the worst CDS, such that a single CDS
+/// with too much missing data is enough to flag the sequence.
+pub fn rule_gene_missing_data(
+  translation: &Translation,
+  gene_map: &GeneMap,
+  config: &QcRulesConfigGeneMissingData,
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultGeneMissingData> {
+  if !config.enabled {
+    return None;
+  }
+
+  let genes: Vec<QcGeneMissingData> = gene_map
+    .iter_cdses()
+    .filter(|cds| config.genes.is_empty() || config.genes.contains(&cds.name))
+    .map(|cds| {
+      let (total_codons, total_missing_codons) = match translation.get_cds(&cds.name) {
+        Ok(cds_tr) => (cds_tr.seq.len(), cds_tr.seq.iter().filter(|aa| aa.is_unknown()).count()),
+        Err(_) => (cds.len() / 3, cds.len() / 3),
+      };
+
+      let missing_fraction = if total_codons == 0 {
+        0.0
+      } else {
+        total_missing_codons as f64 / total_codons as f64
+      };
+
+      let score = clamp_min(missing_fraction * 100.0 / config.missing_fraction_threshold, 0.0);
+      let status = QcStatus::from_score_with_thresholds(score, thresholds);
+
+      QcGeneMissingData {
+        gene_name: cds.name.clone(),
+        score,
+        status,
+        total_codons,
+        total_missing_codons,
+        missing_fraction,
+      }
+    })
+    .collect();
+
+  let score = genes.iter().map(|gene| gene.score).fold(0.0, f64::max);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
+
+  Some(QcResultGeneMissingData {
+    score,
+    status,
+    genes,
+    missing_fraction_threshold: config.missing_fraction_threshold,
+  })
+}
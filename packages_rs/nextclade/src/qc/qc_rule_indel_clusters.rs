@@ -0,0 +1,102 @@
+use crate::align::insertions_strip::NucIns;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::coord::position::PositionLike;
+use crate::qc::qc_config::{QcRulesConfigIndelClusters, QcStatusThresholds};
+use crate::qc::qc_run::{QcRule, QcStatus};
+use itertools::Itertools;
+use num::traits::clamp_min;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusteredIndel {
+  pub start: usize,
+  pub end: usize,
+  pub number_of_indels: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcResultIndelClusters {
+  pub score: f64,
+  pub status: QcStatus,
+  pub total_indels: usize,
+  pub clustered_indels: Vec<ClusteredIndel>,
+}
+
+impl QcRule for QcResultIndelClusters {
+  fn score(&self) -> f64 {
+    self.score
+  }
+}
+
+/// Finds clusters of small insertions and deletions: windows of `windowSize` nucleotides containing more than
+/// `clusterCutOff` indels not longer than `maxIndelLength`. Overlapping windows are merged into one cluster.
+pub fn rule_indel_clusters(
+  deletions: &[NucDelRange],
+  insertions: &[NucIns],
+  config: &QcRulesConfigIndelClusters,
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultIndelClusters> {
+  if !config.enabled {
+    return None;
+  }
+
+  // Positions of small indels: the first deleted nucleotide, or the reference nucleotide after which the insertion is
+  let positions = deletions
+    .iter()
+    .filter(|del| del.len() <= config.max_indel_length)
+    .map(|del| del.range().begin.as_usize())
+    .chain(
+      insertions
+        .iter()
+        .filter(|ins| ins.len() <= config.max_indel_length)
+        .filter_map(|ins| usize::try_from(ins.pos).ok()),
+    )
+    .sorted_unstable()
+    .collect_vec();
+
+  let clustered_indels = find_indel_clusters(&positions, config.window_size, config.cluster_cut_off);
+  let total_clusters = clustered_indels.len();
+  let total_indels = clustered_indels.iter().map(|cluster| cluster.number_of_indels).sum();
+
+  let score = clamp_min(total_clusters as f64 * config.score_weight, 0.0);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
+
+  Some(QcResultIndelClusters {
+    score,
+    status,
+    total_indels,
+    clustered_indels,
+  })
+}
+
+fn find_indel_clusters(positions: &[usize], window_size: usize, cluster_cut_off: usize) -> Vec<ClusteredIndel> {
+  let mut clusters: Vec<ClusteredIndel> = vec![];
+  let mut window_begin = 0;
+  // Index of the first position which is not yet counted in any cluster
+  let mut next_unclustered = 0;
+  for (i, &pos) in positions.iter().enumerate() {
+    while positions[window_begin] + window_size <= pos {
+      window_begin += 1;
+    }
+
+    if i + 1 - window_begin <= cluster_cut_off {
+      continue;
+    }
+
+    match clusters.last_mut() {
+      Some(cluster) if window_begin < next_unclustered => {
+        cluster.end = pos;
+        cluster.number_of_indels += i + 1 - next_unclustered;
+      }
+      _ => clusters.push(ClusteredIndel {
+        start: positions[window_begin],
+        end: pos,
+        number_of_indels: i + 1 - window_begin,
+      }),
+    }
+    next_unclustered = i + 1;
+  }
+  clusters
+}
@@ -1,4 +1,4 @@
-use crate::qc::qc_config::QcRulesConfigMissingData;
+use crate::qc::qc_config::{QcRulesConfigMissingData, QcStatusThresholds};
 use crate::qc::qc_run::{QcRule, QcStatus};
 use num::traits::clamp_min;
 use serde::{Deserialize, Serialize};
@@ -18,7 +18,11 @@ impl QcRule for QcResultMissingData {
   }
 }
 
-pub fn rule_missing_data(total_missing: usize, config: &QcRulesConfigMissingData) -> Option<QcResultMissingData> {
+pub fn rule_missing_data(
+  total_missing: usize,
+  config: &QcRulesConfigMissingData,
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultMissingData> {
   if !config.enabled {
     return None;
   }
@@ -27,7 +31,7 @@ pub fn rule_missing_data(total_missing: usize, config: &QcRulesConfigMissingData
     ((total_missing as f64 - config.score_bias) * 100.0) / config.missing_data_threshold,
     0.0,
   );
-  let status = QcStatus::from_score(score);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
 
   Some(QcResultMissingData {
     score,
@@ -1,11 +1,21 @@
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
-use crate::qc::qc_config::{QcConfig, QcRulesConfigMixedSites};
+use crate::analyze::letter_ranges::NucRange;
+use crate::coord::position::NucRefGlobalPosition;
+use crate::qc::qc_config::{QcConfig, QcRulesConfigMixedSites, QcStatusThresholds};
 use crate::qc::qc_run::{QcRule, QcStatus};
 use num::traits::clamp_min;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+/// Mixed (ambiguous) nucleotide of the query sequence
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MixedSite {
+  pub pos: NucRefGlobalPosition,
+  pub nuc: Nuc,
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QcResultMixedSites {
@@ -13,6 +23,12 @@ pub struct QcResultMixedSites {
   pub status: QcStatus,
   pub total_mixed_sites: usize,
   pub mixed_sites_threshold: usize,
+  /// Mixed sites at the positions where the clades of the reference tree differ. These are more likely to indicate
+  /// co-infection or contamination with a different lineage, than sequencing noise.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub discriminating_mixed_sites: Vec<MixedSite>,
+  #[serde(default)]
+  pub total_discriminating_mixed_sites: usize,
 }
 
 impl QcRule for QcResultMixedSites {
@@ -21,31 +37,118 @@ impl QcRule for QcResultMixedSites {
   }
 }
 
+/// Scores mixed sites. If the clade-discriminating positions are known (when there is a reference tree), the mixed
+/// sites at these positions contribute with the weight `weightDiscriminatingSites`.
 pub fn rule_mixed_sites(
   nucleotide_composition: &BTreeMap<Nuc, usize>,
+  non_acgtns: &[NucRange],
+  discriminating_positions: &[NucRefGlobalPosition],
   config: &QcRulesConfigMixedSites,
+  thresholds: &QcStatusThresholds,
 ) -> Option<QcResultMixedSites> {
   if !config.enabled {
     return None;
   }
 
-  // Calculate total of mixed (ambiguous) nucleotides: non-ACGT, non-N, non-Gap
-  let total_mixed_sites = nucleotide_composition
-    .iter()
-    .filter(|(nuc, total)| !(nuc.is_acgtn() || nuc.is_gap()))
-    .map(|(_, total)| total)
-    .sum();
+  let total_mixed_sites = count_mixed_sites(nucleotide_composition);
+
+  let discriminating_mixed_sites = find_discriminating_mixed_sites(non_acgtns, discriminating_positions);
+  let total_discriminating_mixed_sites = discriminating_mixed_sites.len();
+
+  let weighted_mixed_sites = total_mixed_sites.saturating_sub(total_discriminating_mixed_sites) as f64
+    + config.weight_discriminating_sites * total_discriminating_mixed_sites as f64;
 
   let score = clamp_min(
-    100.0 * (total_mixed_sites as f64 / config.mixed_sites_threshold as f64),
+    100.0 * (weighted_mixed_sites / config.mixed_sites_threshold as f64),
     0.0,
   );
-  let status = QcStatus::from_score(score);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
 
   Some(QcResultMixedSites {
     score,
     status,
     total_mixed_sites,
     mixed_sites_threshold: config.mixed_sites_threshold,
+    discriminating_mixed_sites,
+    total_discriminating_mixed_sites,
   })
 }
+
+/// Calculates total of mixed (ambiguous) nucleotides: non-ACGT, non-N, non-Gap
+pub fn count_mixed_sites(nucleotide_composition: &BTreeMap<Nuc, usize>) -> usize {
+  nucleotide_composition
+    .iter()
+    .filter(|(nuc, _)| !(nuc.is_acgtn() || nuc.is_gap()))
+    .map(|(_, total)| total)
+    .sum()
+}
+
+/// Finds mixed sites at the given (sorted) positions
+fn find_discriminating_mixed_sites(
+  non_acgtns: &[NucRange],
+  discriminating_positions: &[NucRefGlobalPosition],
+) -> Vec<MixedSite> {
+  non_acgtns
+    .iter()
+    .flat_map(|non_acgtn| {
+      non_acgtn.range.iter().filter_map(|pos| {
+        discriminating_positions
+          .binary_search(&pos)
+          .is_ok()
+          .then_some(MixedSite {
+            pos,
+            nuc: non_acgtn.letter,
+          })
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::analyze::letter_ranges::find_letter_ranges_by;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn weights_mixed_sites_at_discriminating_positions() -> Result<(), Report> {
+    let qry_seq = to_nuc_seq("ARGTYCNNKA")?;
+    let non_acgtns: Vec<NucRange> = find_letter_ranges_by(&qry_seq, |nuc: Nuc| !(nuc.is_acgtn() || nuc.is_gap()));
+    let nucleotide_composition = BTreeMap::from([(Nuc::R, 1), (Nuc::Y, 1), (Nuc::K, 1)]);
+    let discriminating_positions = [1_usize, 5, 8].map(NucRefGlobalPosition::from);
+    let config = QcRulesConfigMixedSites {
+      enabled: true,
+      mixed_sites_threshold: 10,
+      weight_discriminating_sites: 3.0,
+    };
+
+    let result = rule_mixed_sites(
+      &nucleotide_composition,
+      &non_acgtns,
+      &discriminating_positions,
+      &config,
+      &QcStatusThresholds::default(),
+    )
+    .expect("Expected the rule to be enabled");
+
+    assert_eq!(result.total_mixed_sites, 3);
+    assert_eq!(
+      result.discriminating_mixed_sites,
+      vec![
+        MixedSite {
+          pos: NucRefGlobalPosition::from(1_usize),
+          nuc: Nuc::R
+        },
+        MixedSite {
+          pos: NucRefGlobalPosition::from(8_usize),
+          nuc: Nuc::K
+        },
+      ]
+    );
+    assert!((result.score - 70.0).abs() < 1e-9);
+    Ok(())
+  }
+}
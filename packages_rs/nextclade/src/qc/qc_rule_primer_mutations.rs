@@ -0,0 +1,73 @@
+use crate::analyze::pcr_primer_changes::PcrPrimerChange;
+use crate::qc::qc_config::{QcRulesConfigPrimerMutations, QcStatusThresholds};
+use crate::qc::qc_run::{QcRule, QcStatus};
+use itertools::Itertools;
+use num::traits::clamp_min;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcAffectedPrimer {
+  pub name: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub source: Option<String>,
+  pub total_substitutions: usize,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct QcResultPrimerMutations {
+  pub score: f64,
+  pub status: QcStatus,
+  pub affected_primers: Vec<QcAffectedPrimer>,
+  pub total_affected_primers: usize,
+  /// Sources (assays) of the affected primers, as given in the dataset
+  pub affected_assays: Vec<String>,
+}
+
+impl QcRule for QcResultPrimerMutations {
+  fn score(&self) -> f64 {
+    self.score
+  }
+}
+
+/// Penalizes substitutions in the binding sites of diagnostic PCR primers and probes, which might cause the sequence to
+/// escape detection by the corresponding assays.
+pub fn rule_primer_mutations(
+  pcr_primer_changes: &[PcrPrimerChange],
+  config: &QcRulesConfigPrimerMutations,
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultPrimerMutations> {
+  if !config.enabled {
+    return None;
+  }
+
+  let affected_primers = pcr_primer_changes
+    .iter()
+    .filter(|change| !config.ignored_primers.contains(&change.primer.name))
+    .map(|change| QcAffectedPrimer {
+      name: change.primer.name.clone(),
+      source: change.primer.source.clone(),
+      total_substitutions: change.substitutions.len(),
+    })
+    .collect_vec();
+
+  let total_affected_primers = affected_primers.len();
+
+  let affected_assays = affected_primers
+    .iter()
+    .filter_map(|primer| primer.source.clone())
+    .unique()
+    .collect_vec();
+
+  let score = clamp_min(total_affected_primers as f64 * config.score_weight, 0.0);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
+
+  Some(QcResultPrimerMutations {
+    score,
+    status,
+    affected_primers,
+    total_affected_primers,
+    affected_assays,
+  })
+}
@@ -4,7 +4,7 @@ use crate::analyze::letter_ranges::NucRange;
 use crate::analyze::nuc_del::NucDel;
 use crate::coord::position::PositionLike;
 use crate::coord::range::Range;
-use crate::qc::qc_config::QcRulesConfigPrivateMutations;
+use crate::qc::qc_config::{QcRulesConfigPrivateMutations, QcStatusThresholds};
 use crate::qc::qc_run::{QcRule, QcStatus};
 use num::traits::clamp_min;
 use serde::{Deserialize, Serialize};
@@ -32,6 +32,7 @@ impl QcRule for QcResultPrivateMutations {
 pub fn rule_private_mutations(
   private_nuc_mutations: &PrivateNucMutations,
   config: &QcRulesConfigPrivateMutations,
+  thresholds: &QcStatusThresholds,
 ) -> Option<QcResultPrivateMutations> {
   if !config.enabled {
     return None;
@@ -53,7 +54,7 @@ pub fn rule_private_mutations(
 
   // the score hits 100 if the excess mutations equals the cutoff value
   let score = (clamp_min(weighted_total - config.typical, 0.0) * 100.0) / config.cutoff;
-  let status = QcStatus::from_score(score);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
 
   Some(QcResultPrivateMutations {
     score,
@@ -74,7 +75,7 @@ pub fn rule_private_mutations(
 /// form of ranges, private nucleotide deletions are listed
 /// individually. We compute the ranges for private deletions here.
 ///
-fn find_deletion_ranges(dels: &[NucDel]) -> Vec<NucRange> {
+pub fn find_deletion_ranges(dels: &[NucDel]) -> Vec<NucRange> {
   if dels.is_empty() {
     return vec![];
   }
@@ -1,4 +1,6 @@
-use crate::qc::qc_config::{QcRulesConfigStopCodons, StopCodonLocation};
+use crate::gene::gene_map::GeneMap;
+use crate::qc::qc_config::{QcRulesConfigStopCodons, QcStatusThresholds, StopCodonLocation};
+use crate::qc::qc_exceptions::QcException;
 use crate::qc::qc_run::{QcRule, QcStatus};
 use crate::translate::translate_genes::{CdsTranslation, Translation};
 use serde::{Deserialize, Serialize};
@@ -20,7 +22,13 @@ impl QcRule for QcResultStopCodons {
   }
 }
 
-pub fn rule_stop_codons(translation: &Translation, config: &QcRulesConfigStopCodons) -> Option<QcResultStopCodons> {
+pub fn rule_stop_codons(
+  translation: &Translation,
+  gene_map: &GeneMap,
+  config: &QcRulesConfigStopCodons,
+  exceptions: &[&QcException],
+  thresholds: &QcStatusThresholds,
+) -> Option<QcResultStopCodons> {
   if !config.enabled {
     return None;
   }
@@ -30,15 +38,27 @@ pub fn rule_stop_codons(translation: &Translation, config: &QcRulesConfigStopCod
   for cds_tr in translation.cdses() {
     let CdsTranslation { name, seq: peptide, .. } = cds_tr;
 
-    let len_minus_one = peptide.len() - 1; // Minus one to ignore valid stop codon at the end
-    for (codon, aa) in peptide.iter().enumerate().take(len_minus_one) {
+    // If the 3' end of the CDS is missing in the annotation, then there is no valid stop codon at the end, and a stop
+    // codon in the last position is premature too. Otherwise, minus one to ignore valid stop codon at the end.
+    let is_missing_stop = gene_map.get_cds(name).is_ok_and(|cds| cds.partial.missing_stop);
+    let n_codons_checked = if is_missing_stop {
+      peptide.len()
+    } else {
+      peptide.len().saturating_sub(1)
+    };
+
+    for (codon, aa) in peptide.iter().enumerate().take(n_codons_checked) {
       if aa.is_stop() {
         let stop_codon = StopCodonLocation {
           gene_name: name.clone(),
           codon,
         };
 
-        if is_ignored_stop_codon(&stop_codon, &config.ignored_stop_codons) {
+        if is_ignored_stop_codon(&stop_codon, &config.ignored_stop_codons)
+          || exceptions
+            .iter()
+            .any(|exception| exception.matches_stop_codon(&stop_codon))
+        {
           stop_codons_ignored.push(stop_codon);
         } else {
           stop_codons.push(stop_codon);
@@ -51,7 +71,7 @@ pub fn rule_stop_codons(translation: &Translation, config: &QcRulesConfigStopCod
   let total_stop_codons_ignored = stop_codons_ignored.len();
 
   let score = total_stop_codons as f64 * config.score_weight;
-  let status = QcStatus::from_score(score);
+  let status = QcStatus::from_score_with_thresholds(score, thresholds);
 
   Some(QcResultStopCodons {
     score,
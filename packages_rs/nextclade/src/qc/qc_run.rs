@@ -1,9 +1,20 @@
+use crate::align::insertions_strip::NucIns;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
-use crate::qc::qc_config::QcConfig;
+use crate::analyze::letter_ranges::NucRange;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::pcr_primer_changes::PcrPrimerChange;
+use crate::coord::position::NucRefGlobalPosition;
+use crate::gene::gene_map::GeneMap;
+use crate::qc::qc_config::{QcConfig, QcStatusThresholds};
+use crate::qc::qc_exceptions::qc_exceptions_for_clade;
+use crate::qc::qc_rule_custom::{rule_custom, QcResultCustom};
 use crate::qc::qc_rule_frame_shifts::{rule_frame_shifts, QcResultFrameShifts};
+use crate::qc::qc_rule_gene_missing_data::{rule_gene_missing_data, QcResultGeneMissingData};
+use crate::qc::qc_rule_indel_clusters::{rule_indel_clusters, QcResultIndelClusters};
 use crate::qc::qc_rule_missing_data::{rule_missing_data, QcResultMissingData};
 use crate::qc::qc_rule_mixed_sites::{rule_mixed_sites, QcResultMixedSites};
+use crate::qc::qc_rule_primer_mutations::{rule_primer_mutations, QcResultPrimerMutations};
 use crate::qc::qc_rule_private_mutations::{rule_private_mutations, QcResultPrivateMutations};
 use crate::qc::qc_rule_snp_clusters::{rule_snp_clusters, QcResultSnpClusters};
 use crate::qc::qc_rule_stop_codons::{rule_stop_codons, QcResultStopCodons};
@@ -35,10 +46,14 @@ impl ToString for QcStatus {
 
 impl QcStatus {
   pub fn from_score(score: f64) -> QcStatus {
-    if (30.0..100.0).contains(&score) {
-      QcStatus::Mediocre
-    } else if score >= 100.0 {
+    Self::from_score_with_thresholds(score, &QcStatusThresholds::default())
+  }
+
+  pub fn from_score_with_thresholds(score: f64, thresholds: &QcStatusThresholds) -> QcStatus {
+    if score >= thresholds.bad {
       QcStatus::Bad
+    } else if score >= thresholds.mediocre {
+      QcStatus::Mediocre
     } else {
       QcStatus::Good
     }
@@ -49,11 +64,19 @@ impl QcStatus {
 #[serde(rename_all = "camelCase")]
 pub struct QcResult {
   pub missing_data: Option<QcResultMissingData>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub gene_missing_data: Option<QcResultGeneMissingData>,
   pub mixed_sites: Option<QcResultMixedSites>,
   pub private_mutations: Option<QcResultPrivateMutations>,
   pub snp_clusters: Option<QcResultSnpClusters>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub indel_clusters: Option<QcResultIndelClusters>,
   pub frame_shifts: Option<QcResultFrameShifts>,
   pub stop_codons: Option<QcResultStopCodons>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub primer_mutations: Option<QcResultPrimerMutations>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub custom_rules: Vec<QcResultCustom>,
   pub overall_score: f64,
   pub overall_status: QcStatus,
 }
@@ -62,41 +85,158 @@ pub trait QcRule {
   fn score(&self) -> f64;
 }
 
+/// Runs QC rules. Rules based on private mutations are skipped if private mutations are not available (when there is
+/// no reference tree to find them against). QC exceptions restricted to a clade only apply if the clade is known.
 pub fn qc_run(
-  private_nuc_mutations: &PrivateNucMutations,
+  private_nuc_mutations: Option<&PrivateNucMutations>,
   nucleotide_composition: &BTreeMap<Nuc, usize>,
+  non_acgtns: &[NucRange],
+  discriminating_positions: &[NucRefGlobalPosition],
   total_missing: usize,
+  deletions: &[NucDelRange],
+  insertions: &[NucIns],
   translation: &Translation,
+  gene_map: &GeneMap,
   frame_shifts: &[FrameShift],
+  pcr_primer_changes: &[PcrPrimerChange],
+  clade: Option<&str>,
   config: &QcConfig,
 ) -> QcResult {
+  let exceptions = qc_exceptions_for_clade(&config.exceptions, clade);
+  let thresholds = &config.status_thresholds;
+
   let mut result = QcResult {
-    missing_data: rule_missing_data(total_missing, &config.missing_data),
-    mixed_sites: rule_mixed_sites(nucleotide_composition, &config.mixed_sites),
-    private_mutations: rule_private_mutations(private_nuc_mutations, &config.private_mutations),
-    snp_clusters: rule_snp_clusters(private_nuc_mutations, &config.snp_clusters),
-    frame_shifts: rule_frame_shifts(frame_shifts, &config.frame_shifts),
-    stop_codons: rule_stop_codons(translation, &config.stop_codons),
+    missing_data: rule_missing_data(total_missing, &config.missing_data, thresholds),
+    gene_missing_data: rule_gene_missing_data(translation, gene_map, &config.gene_missing_data, thresholds),
+    mixed_sites: rule_mixed_sites(
+      nucleotide_composition,
+      non_acgtns,
+      discriminating_positions,
+      &config.mixed_sites,
+      thresholds,
+    ),
+    private_mutations: private_nuc_mutations.and_then(|private_nuc_mutations| {
+      rule_private_mutations(private_nuc_mutations, &config.private_mutations, thresholds)
+    }),
+    snp_clusters: private_nuc_mutations
+      .and_then(|private_nuc_mutations| rule_snp_clusters(private_nuc_mutations, &config.snp_clusters, thresholds)),
+    indel_clusters: rule_indel_clusters(deletions, insertions, &config.indel_clusters, thresholds),
+    frame_shifts: rule_frame_shifts(frame_shifts, &config.frame_shifts, &exceptions, thresholds),
+    stop_codons: rule_stop_codons(translation, gene_map, &config.stop_codons, &exceptions, thresholds),
+    primer_mutations: rule_primer_mutations(pcr_primer_changes, &config.primer_mutations, thresholds),
+    custom_rules: config
+      .custom_rules
+      .iter()
+      .filter_map(|rule_config| {
+        rule_custom(
+          private_nuc_mutations,
+          nucleotide_composition,
+          total_missing,
+          frame_shifts,
+          rule_config,
+          thresholds,
+        )
+      })
+      .collect(),
     overall_score: 0.0,
     overall_status: QcStatus::Good,
   };
 
-  result.overall_score += add_score(&result.missing_data);
-  result.overall_score += add_score(&result.mixed_sites);
-  result.overall_score += add_score(&result.private_mutations);
-  result.overall_score += add_score(&result.snp_clusters);
-  result.overall_score += add_score(&result.frame_shifts);
-  result.overall_score += add_score(&result.stop_codons);
+  result.overall_score = result
+    .iter_rules()
+    .map(|(name, rule_result)| {
+      let weight = config.rule_weights.get(name).copied().unwrap_or(1.0);
+      weight * rule_result.score().pow(2.0) * 0.01
+    })
+    .sum();
 
-  result.overall_status = QcStatus::from_score(result.overall_score);
+  result.overall_status = QcStatus::from_score_with_thresholds(result.overall_score, thresholds);
 
   result
 }
 
-fn add_score<R: QcRule>(rule_result: &Option<R>) -> f64 {
-  if let Some(rule_result) = rule_result {
-    rule_result.score().pow(2.0) * 0.01
-  } else {
-    0.0
+type QcRuleResultGetter = fn(&QcResult) -> Option<&dyn QcRule>;
+
+/// Built-in QC rules: names, as they appear in the QC config and in the QC results, along with accessors of the results
+const QC_BUILTIN_RULES: [(&str, QcRuleResultGetter); 9] = [
+  ("missingData", |qc| qc.missing_data.as_ref().map(|r| r as &dyn QcRule)),
+  ("geneMissingData", |qc| {
+    qc.gene_missing_data.as_ref().map(|r| r as &dyn QcRule)
+  }),
+  ("mixedSites", |qc| qc.mixed_sites.as_ref().map(|r| r as &dyn QcRule)),
+  ("privateMutations", |qc| {
+    qc.private_mutations.as_ref().map(|r| r as &dyn QcRule)
+  }),
+  ("snpClusters", |qc| qc.snp_clusters.as_ref().map(|r| r as &dyn QcRule)),
+  ("indelClusters", |qc| {
+    qc.indel_clusters.as_ref().map(|r| r as &dyn QcRule)
+  }),
+  ("frameShifts", |qc| qc.frame_shifts.as_ref().map(|r| r as &dyn QcRule)),
+  ("stopCodons", |qc| qc.stop_codons.as_ref().map(|r| r as &dyn QcRule)),
+  ("primerMutations", |qc| {
+    qc.primer_mutations.as_ref().map(|r| r as &dyn QcRule)
+  }),
+];
+
+/// Names of the built-in QC rules, as they appear in the QC config and in the QC results
+pub fn qc_builtin_rule_names() -> impl Iterator<Item = &'static str> {
+  QC_BUILTIN_RULES.iter().map(|(name, _)| *name)
+}
+
+impl QcResult {
+  /// Iterates over results of the rules which ran, built-in and custom, along with the rule names
+  pub fn iter_rules(&self) -> impl Iterator<Item = (&str, &dyn QcRule)> {
+    let custom = self.custom_rules.iter().map(|r| (r.name.as_str(), r as &dyn QcRule));
+
+    QC_BUILTIN_RULES
+      .iter()
+      .filter_map(|(name, get_result)| Some((*name, get_result(self)?)))
+      .chain(custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::qc::qc_config::QcRulesConfigMissingData;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn applies_configured_status_thresholds_to_rules() {
+    let config = QcConfig {
+      missing_data: QcRulesConfigMissingData {
+        enabled: true,
+        missing_data_threshold: 100.0,
+        score_bias: 0.0,
+      },
+      status_thresholds: QcStatusThresholds {
+        mediocre: 10.0,
+        bad: 50.0,
+      },
+      ..QcConfig::default()
+    };
+
+    let result = qc_run(
+      None,
+      &BTreeMap::new(),
+      &[],
+      &[],
+      20,
+      &[],
+      &[],
+      &Translation::default(),
+      &GeneMap::default(),
+      &[],
+      &[],
+      None,
+      &config,
+    );
+
+    let missing_data = result.missing_data.expect("Expected the rule to be enabled");
+    assert!((missing_data.score - 20.0).abs() < f64::EPSILON);
+    assert_eq!(missing_data.status.to_string(), "mediocre");
+    assert!((result.overall_score - 4.0).abs() < f64::EPSILON);
+    assert_eq!(result.overall_status.to_string(), "good");
   }
 }
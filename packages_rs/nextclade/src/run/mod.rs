@@ -1,3 +1,4 @@
+pub mod nextclade_run_aa;
 pub mod nextclade_run_one;
 pub mod nextclade_wasm;
 pub mod params;
@@ -0,0 +1,148 @@
+use crate::align::align::align_aa;
+use crate::align::insertions_strip::{insertions_strip, AaIns};
+use crate::align::remove_gaps::remove_gaps_in_place;
+use crate::alphabet::aa::Aa;
+use crate::alphabet::letter::Letter;
+use crate::analyze::aa_changes::is_aa_mutated_or_deleted;
+use crate::analyze::aa_del::AaDel;
+use crate::analyze::aa_sub::AaSub;
+use crate::coord::position::AaRefPosition;
+use crate::coord::range::AaRefRange;
+use crate::make_error;
+use crate::run::nextclade_wasm::Nextclade;
+use crate::translate::translate_genes::CdsTranslation;
+use crate::utils::interned_str::InternedStr;
+use eyre::Report;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Results of the analysis of a query peptide (when input sequences are aminoacid sequences of one CDS)
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NextcladeAaOutputs {
+  pub index: usize,
+  pub seq_name: String,
+  pub cds_name: String,
+  pub alignment_range: AaRefRange,
+  pub alignment_score: i32,
+  pub aa_substitutions: Vec<AaSub>,
+  pub total_aminoacid_substitutions: usize,
+  pub aa_deletions: Vec<AaDel>,
+  pub total_aminoacid_deletions: usize,
+  pub aa_insertions: Vec<AaIns>,
+  pub total_aminoacid_insertions: usize,
+  pub total_unknown_aa: usize,
+}
+
+pub struct AaAnalysisOutput {
+  pub outputs: NextcladeAaOutputs,
+  /// Query peptide aligned to the reference peptide, with insertions stripped
+  pub translation: CdsTranslation,
+}
+
+/// Runs the analysis of a query peptide of a given CDS: aligns it to the reference peptide of this CDS and finds
+/// aminoacid substitutions, deletions and insertions. Nucleotide alignment and all the nucleotide-based analysis steps
+/// are skipped.
+pub fn nextclade_run_aa_one(
+  index: usize,
+  seq_name: &str,
+  qry_seq: &[Aa],
+  cds_name: &str,
+  state: &Nextclade,
+) -> Result<AaAnalysisOutput, Report> {
+  let Nextclade {
+    gap_open_close_aa,
+    ref_translation,
+    params,
+    ..
+  } = state;
+
+  let ref_seq = &ref_translation.get_cds(cds_name)?.seq;
+
+  let mut qry_seq = qry_seq.to_vec();
+  remove_gaps_in_place(&mut qry_seq);
+  if qry_seq.is_empty() {
+    return make_error!("Unable to align: peptide is empty or consists entirely of gaps");
+  }
+
+  // Unlike for peptides translated from a nucleotide alignment, there is no prior knowledge about indels, so the band
+  // covers the entire alignment matrix. Peptides are short enough for this to be affordable.
+  let band_width = ref_seq.len().max(qry_seq.len());
+  let alignment = align_aa(&qry_seq, ref_seq, gap_open_close_aa, &params.alignment, band_width, 0)?;
+
+  let stripped = insertions_strip(&alignment.qry_seq, &alignment.ref_seq);
+
+  let begin = stripped.qry_seq.iter().position(|aa| !aa.is_gap()).unwrap_or_default();
+  let end = stripped
+    .qry_seq
+    .iter()
+    .rposition(|aa| !aa.is_gap())
+    .map_or(begin, |last| last + 1);
+  let alignment_range = AaRefRange::from_usize(begin, end);
+
+  let cds_name_interned = InternedStr::new(cds_name);
+  let mut aa_substitutions = vec![];
+  let mut aa_deletions = vec![];
+  let mut total_unknown_aa = 0;
+  for (pos, (&qry_aa, &ref_aa)) in stripped.qry_seq.iter().zip(ref_seq).enumerate().take(end).skip(begin) {
+    let pos = AaRefPosition::from(pos);
+    if qry_aa.is_unknown() {
+      total_unknown_aa += 1;
+    }
+    if !is_aa_mutated_or_deleted(ref_aa, qry_aa) {
+      continue;
+    }
+    if qry_aa.is_gap() {
+      aa_deletions.push(AaDel {
+        cds_name: cds_name_interned.clone(),
+        pos,
+        ref_aa,
+      });
+    } else {
+      aa_substitutions.push(AaSub {
+        cds_name: cds_name_interned.clone(),
+        pos,
+        ref_aa,
+        qry_aa,
+      });
+    }
+  }
+
+  let aa_insertions = stripped
+    .insertions
+    .iter()
+    .map(|insertion| AaIns {
+      gene: cds_name.to_owned(),
+      pos: insertion.pos,
+      ins: insertion.ins.clone(),
+    })
+    .collect_vec();
+
+  let outputs = NextcladeAaOutputs {
+    index,
+    seq_name: seq_name.to_owned(),
+    cds_name: cds_name.to_owned(),
+    alignment_range: alignment_range.clone(),
+    alignment_score: alignment.alignment_score,
+    total_aminoacid_substitutions: aa_substitutions.len(),
+    aa_substitutions,
+    total_aminoacid_deletions: aa_deletions.len(),
+    aa_deletions,
+    total_aminoacid_insertions: aa_insertions.len(),
+    aa_insertions,
+    total_unknown_aa,
+  };
+
+  let translation = CdsTranslation {
+    name: cds_name.to_owned(),
+    seq: stripped.qry_seq,
+    insertions: stripped.insertions,
+    frame_shifts: vec![],
+    alignment_ranges: vec![alignment_range],
+    unsequenced_ranges: vec![],
+    codon_alignment: vec![],
+    cache_key: None,
+  };
+
+  Ok(AaAnalysisOutput { outputs, translation })
+}
@@ -1,4 +1,7 @@
 use crate::align::align::align_nuc;
+use crate::align::alignment_band::AlignmentBand;
+use crate::align::alignment_diagnostics::AlignmentDiagnostics;
+use crate::align::homopolymer_indels::normalize_homopolymer_indels;
 use crate::align::insertions_strip::{get_aa_insertions, insertions_strip, AaIns, NucIns};
 use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::Letter;
@@ -6,22 +9,34 @@ use crate::alphabet::nuc::Nuc;
 use crate::analyze::aa_changes::{find_aa_changes, AaChangesGroup, FindAaChangesOutput};
 use crate::analyze::aa_del::AaDel;
 use crate::analyze::aa_sub::AaSub;
+use crate::analyze::cds_coverage::calculate_cds_coverage;
+use crate::analyze::clade_defining_mutations::{
+  find_clade_defining_mutations, find_clade_founder, CladeDefiningMutations,
+};
 use crate::analyze::divergence::calculate_branch_length;
 use crate::analyze::find_aa_motifs::find_aa_motifs;
 use crate::analyze::find_aa_motifs_changes::find_aa_motifs_changes;
 use crate::analyze::find_private_aa_mutations::{find_private_aa_mutations, PrivateAaMutations};
 use crate::analyze::find_private_nuc_mutations::{find_private_nuc_mutations, PrivateNucMutations};
+use crate::analyze::hgvs::format_hgvs_mutations;
+use crate::analyze::impute::{find_imputable_nucs, impute_nucs_in_place, ImputedNuc};
 use crate::analyze::letter_composition::get_letter_composition;
 use crate::analyze::letter_ranges::{
   find_aa_letter_ranges, find_letter_ranges, find_letter_ranges_by, GeneAaRange, NucRange,
 };
+use crate::analyze::masking::mask_alignment_in_place;
+use crate::analyze::minor_variants::find_minor_variants;
+use crate::analyze::mutation_path::{find_mutation_path, MutationPathBranch};
 use crate::analyze::nuc_changes::{find_nuc_changes, FindNucChangesOutput};
 use crate::analyze::nuc_del::NucDelRange;
 use crate::analyze::pcr_primer_changes::get_pcr_primer_changes;
 use crate::analyze::phenotype::calculate_phenotype;
+use crate::analyze::recombination::{find_recombination, CladeProfiles, Recombinant};
+use crate::analyze::relative_mutations::{find_relative_nuc_mutations, RelativeNucMutations};
+use crate::analyze::structural_events::find_structural_events;
 use crate::analyze::virus_properties::PhenotypeData;
 use crate::coord::coord_map_global::CoordMapGlobal;
-use crate::coord::range::AaRefRange;
+use crate::coord::range::{AaRefRange, NucRefGlobalRange};
 use crate::graph::node::GraphNodeKey;
 use crate::qc::qc_run::qc_run;
 use crate::run::nextclade_wasm::{AnalysisOutput, Nextclade};
@@ -29,8 +44,9 @@ use crate::translate::aa_alignment_ranges::{gather_aa_alignment_ranges, GatherAa
 use crate::translate::frame_shifts_flatten::frame_shifts_flatten;
 use crate::translate::frame_shifts_translate::FrameShift;
 use crate::translate::translate_genes::{translate_genes, Translation};
-use crate::tree::tree_find_nearest_node::graph_find_nearest_nodes;
+use crate::tree::tree_find_nearest_node::{find_placement_candidates, graph_find_nearest_nodes, PlacementCandidates};
 use crate::types::outputs::{NextcladeOutputs, PeptideWarning, PhenotypeValue};
+use crate::utils::datetime::date_now;
 use eyre::Report;
 use itertools::Itertools;
 use std::collections::{BTreeMap, HashSet};
@@ -55,18 +71,24 @@ struct NextcladeResultWithAa {
   total_unknown_aa: usize,
   aa_alignment_ranges: BTreeMap<String, Vec<AaRefRange>>,
   aa_unsequenced_ranges: BTreeMap<String, Vec<AaRefRange>>,
+  cds_coverage: BTreeMap<String, f64>,
 }
 
 #[derive(Default)]
 struct NextcladeResultWithGraph {
   clade: String,
+  clade_defining_mutations: CladeDefiningMutations,
   private_nuc_mutations: PrivateNucMutations,
   private_aa_mutations: BTreeMap<String, PrivateAaMutations>,
-  phenotype_values: Option<Vec<PhenotypeValue>>,
+  relative_nuc_mutations: Option<RelativeNucMutations>,
   divergence: f64,
   custom_node_attributes: BTreeMap<String, String>,
   nearest_node_id: GraphNodeKey,
   nearest_nodes: Option<Vec<String>>,
+  imputed_nucs: Vec<ImputedNuc>,
+  recombinant: Option<Recombinant>,
+  placement_candidates: Option<PlacementCandidates>,
+  mutation_path: Option<Vec<MutationPathBranch>>,
 }
 
 pub fn nextclade_run_one(
@@ -76,30 +98,69 @@ pub fn nextclade_run_one(
   state: &Nextclade,
 ) -> Result<AnalysisOutput, Report> {
   let Nextclade {
+    ref_record,
     ref_seq,
     seed_index,
+    minimizer_index,
     gap_open_close_nuc,
     virus_properties,
     params,
     gene_map,
     gap_open_close_aa,
+    translation_cache,
     ref_translation,
     aa_motifs_ref,
     graph,
+    relative_mutations_node,
+    clade_profiles,
+    lineage_caller,
+    mutation_knowledge_base,
+    allele_frequencies,
     ..
   } = &state;
 
-  let alignment = align_nuc(
+  let alignment_started_at = date_now();
+
+  let mut alignment = align_nuc(
     index,
     seq_name,
     qry_seq,
     ref_seq,
     seed_index,
+    minimizer_index.as_ref(),
     gap_open_close_nuc,
     &params.alignment,
   )?;
 
-  let stripped = insertions_strip(&alignment.qry_seq, &alignment.ref_seq);
+  let alignment_diagnostics = params.general.include_alignment_diagnostics.then(|| {
+    let alignment_time_sec = (date_now() - alignment_started_at)
+      .to_std()
+      .map_or(0.0, |duration| duration.as_secs_f64());
+    AlignmentDiagnostics::new(&alignment, alignment_time_sec)
+  });
+
+  let alignment_band = state
+    .include_alignment_band
+    .then(|| AlignmentBand::new(index, seq_name, &alignment.band, &alignment.ref_seq, &alignment.qry_seq));
+
+  let normalized_homopolymer_indels = if params.alignment.normalize_homopolymer_indels {
+    normalize_homopolymer_indels(
+      &mut alignment.qry_seq,
+      &mut alignment.ref_seq,
+      params.alignment.homopolymer_min_length,
+    )
+  } else {
+    vec![]
+  };
+
+  let masked_ranges = mask_alignment_in_place(
+    &mut alignment.qry_seq,
+    &alignment.ref_seq,
+    &virus_properties.masked_ranges,
+  );
+  let total_masked: usize = masked_ranges.iter().map(NucRefGlobalRange::len).sum();
+
+  let mut stripped = insertions_strip(&alignment.qry_seq, &alignment.ref_seq);
   let alignment_score = alignment.alignment_score;
 
   let FindNucChangesOutput {
@@ -111,11 +172,13 @@ pub fn nextclade_run_one(
   let total_substitutions = substitutions.len();
   let total_deletions = deletions.iter().map(NucDelRange::len).sum();
 
+  let structural_events = find_structural_events(&deletions, gene_map, params.general.min_structural_deletion_length);
+
   let insertions = stripped.insertions.clone();
   let total_insertions = insertions.iter().map(NucIns::len).sum();
 
   let missing = find_letter_ranges(&stripped.qry_seq, Nuc::N);
-  let total_missing = missing.iter().map(NucRange::len).sum();
+  let total_missing: usize = missing.iter().map(NucRange::len).sum();
 
   let non_acgtns = find_letter_ranges_by(&stripped.qry_seq, |nuc: Nuc| !(nuc.is_acgtn() || nuc.is_gap()));
   let total_non_acgtns = non_acgtns.iter().map(NucRange::len).sum();
@@ -147,6 +210,7 @@ pub fn nextclade_run_one(
     total_unknown_aa,
     aa_alignment_ranges,
     aa_unsequenced_ranges,
+    cds_coverage,
     ..
   } = if !gene_map.is_empty() {
     let coord_map_global = CoordMapGlobal::new(&alignment.ref_seq);
@@ -160,6 +224,7 @@ pub fn nextclade_run_one(
       &alignment_range,
       gap_open_close_aa,
       &params.alignment,
+      translation_cache.as_ref(),
     )?;
 
     let present_genes: HashSet<String> = translation
@@ -207,6 +272,7 @@ pub fn nextclade_run_one(
       gene_map,
       &substitutions,
       &deletions,
+      translation_cache.as_ref(),
     )?;
 
     let total_aminoacid_substitutions = aa_substitutions.len();
@@ -221,6 +287,8 @@ pub fn nextclade_run_one(
       aa_unsequenced_ranges,
     } = gather_aa_alignment_ranges(&translation, gene_map);
 
+    let cds_coverage = calculate_cds_coverage(&translation, gene_map);
+
     NextcladeResultWithAa {
       translation,
       aa_changes_groups,
@@ -240,6 +308,7 @@ pub fn nextclade_run_one(
       total_unknown_aa,
       aa_alignment_ranges,
       aa_unsequenced_ranges,
+      cds_coverage,
     }
   } else {
     NextcladeResultWithAa::default()
@@ -247,13 +316,18 @@ pub fn nextclade_run_one(
 
   let NextcladeResultWithGraph {
     clade,
+    clade_defining_mutations,
     private_nuc_mutations,
     private_aa_mutations,
-    phenotype_values,
+    relative_nuc_mutations,
     divergence,
     custom_node_attributes,
     nearest_node_id,
     nearest_nodes,
+    imputed_nucs,
+    recombinant,
+    placement_candidates,
+    mutation_path,
   } = if let Some(graph) = graph {
     let nearest_node_candidates = graph_find_nearest_nodes(graph, &substitutions, &missing, &alignment_range)?;
     let nearest_node_key = nearest_node_candidates[0].node_key;
@@ -268,7 +342,26 @@ pub fn nextclade_run_one(
     .collect::<Result<Vec<String>, Report>>()?,
     );
 
+    let placement_candidates = (params.general.num_placement_candidates > 0)
+      .then(|| {
+        find_placement_candidates(
+          graph,
+          &nearest_node_candidates,
+          &substitutions,
+          params.general.num_placement_candidates,
+        )
+      })
+      .transpose()?;
+
+    let mutation_path = params
+      .general
+      .include_mutation_path
+      .then(|| find_mutation_path(graph, nearest_node_key))
+      .transpose()?;
+
     let clade = nearest_node.clade();
+    let clade_defining_mutations =
+      find_clade_defining_mutations(graph, nearest_node_key, &stripped.qry_seq, &alignment_range, ref_seq)?;
 
     let clade_node_attr_keys = graph.data.meta.clade_node_attr_descs();
     let clade_node_attrs = nearest_node.get_clade_node_attrs(clade_node_attr_keys);
@@ -293,6 +386,37 @@ pub fn nextclade_run_one(
       ref_translation,
       gene_map,
     );
+
+    let relative_nuc_mutations = params
+      .general
+      .mutations_relative_to
+      .as_ref()
+      .map(|_| -> Result<_, Report> {
+        let node_key = match relative_mutations_node {
+          Some(node_key) => *node_key,
+          None => find_clade_founder(graph, nearest_node_key)?,
+        };
+        let node = graph.get_node(node_key)?.payload();
+        Ok(find_relative_nuc_mutations(
+          node,
+          &stripped.qry_seq,
+          &alignment_range,
+          ref_seq,
+        ))
+      })
+      .transpose()?;
+
+    let imputed_nucs = if params.general.impute_ambiguous {
+      find_imputable_nucs(nearest_node, &stripped.qry_seq, &alignment_range, ref_seq)
+    } else {
+      vec![]
+    };
+
+    let recombinant = clade_profiles
+      .as_ref()
+      .filter(|_| params.general.detect_recombinants)
+      .and_then(|profiles| find_recombination(profiles, &stripped.qry_seq, &alignment_range));
+
     let parent_div = nearest_node.node_attrs.div.unwrap_or(0.0);
     let masked_ranges = graph.data.meta.placement_mask_ranges();
     let divergence = parent_div
@@ -303,38 +427,66 @@ pub fn nextclade_run_one(
         ref_seq.len(),
       );
 
-    let phenotype_values = virus_properties.phenotype_data.as_ref().map(|phenotype_data| {
-      phenotype_data
-        .iter()
-        .filter_map(|phenotype_data| {
-          let PhenotypeData { name, gene, ignore, .. } = phenotype_data;
-          if ignore.clades.contains(&clade) {
-            return None;
-          }
-          let phenotype = calculate_phenotype(phenotype_data, &aa_substitutions);
-          Some(PhenotypeValue {
-            name: name.clone(),
-            gene: gene.clone(),
-            value: phenotype,
-          })
-        })
-        .collect_vec()
-    });
-
     NextcladeResultWithGraph {
       clade,
+      clade_defining_mutations,
       private_nuc_mutations,
       private_aa_mutations,
-      phenotype_values,
+      relative_nuc_mutations,
       divergence,
       custom_node_attributes: clade_node_attrs,
       nearest_node_id: nearest_node_key,
       nearest_nodes,
+      imputed_nucs,
+      recombinant,
+      placement_candidates,
+      mutation_path,
     }
   } else {
     NextcladeResultWithGraph::default()
   };
 
+  // Phenotypes only depend on the clade for the list of clades to ignore, so they can be calculated without the tree
+  let phenotype_values = virus_properties.phenotype_data.as_ref().map(|phenotype_data| {
+    phenotype_data
+      .iter()
+      .filter_map(|phenotype_data| {
+        let PhenotypeData { name, gene, ignore, .. } = phenotype_data;
+        if ignore.clades.contains(&clade) {
+          return None;
+        }
+        let phenotype = calculate_phenotype(phenotype_data, &aa_substitutions);
+        Some(PhenotypeValue {
+          name: name.clone(),
+          gene: gene.clone(),
+          value: phenotype,
+        })
+      })
+      .collect_vec()
+  });
+
+  let lineage_call = lineage_caller
+    .as_ref()
+    .map(|lineage_caller| lineage_caller.call(&stripped.qry_seq, &alignment_range));
+
+  let mutation_annotations = mutation_knowledge_base
+    .as_ref()
+    .map(|knowledge_base| knowledge_base.annotate(&aa_substitutions))
+    .unwrap_or_default();
+
+  let minor_variants = allele_frequencies
+    .as_ref()
+    .map(|allele_frequencies| {
+      find_minor_variants(
+        allele_frequencies,
+        seq_name,
+        &stripped.qry_seq,
+        params.general.minor_variant_min_frequency,
+        params.general.minor_variant_max_frequency,
+      )
+    })
+    .unwrap_or_default();
+
   let aa_motifs = find_aa_motifs(&virus_properties.aa_motifs, &translation)?;
   let aa_motifs_changes = find_aa_motifs_changes(aa_motifs_ref, &aa_motifs, ref_translation, &translation)?;
 
@@ -343,17 +495,46 @@ pub fn nextclade_run_one(
     .as_ref()
     .map(|qc_config| {
       qc_run(
-        &private_nuc_mutations,
+        graph.is_some().then_some(&private_nuc_mutations),
         &nucleotide_composition,
-        total_missing,
+        &non_acgtns,
+        clade_profiles
+          .as_ref()
+          .map(CladeProfiles::positions)
+          .unwrap_or_default(),
+        // Masked positions are excluded from QC
+        total_missing.saturating_sub(total_masked),
+        &deletions,
+        &insertions,
         &translation,
+        gene_map,
         &frame_shifts,
+        &pcr_primer_changes,
+        graph.is_some().then_some(clade.as_str()),
         qc_config,
       )
     })
     .unwrap_or_default();
 
+  let hgvs = params.general.include_hgvs.then(|| {
+    format_hgvs_mutations(
+      &substitutions,
+      &deletions,
+      &insertions,
+      &aa_substitutions,
+      &aa_deletions,
+      &aa_insertions,
+      ref_seq,
+      ref_translation,
+      gene_map,
+    )
+  });
+
   let is_reverse_complement = alignment.is_reverse_complement;
+  let tree_dependent_skipped = graph.is_none();
+
+  // Imputation only affects the output sequence: all of the analysis above is done on the original query
+  impute_nucs_in_place(&mut stripped.qry_seq, &imputed_nucs);
 
   Ok(AnalysisOutput {
     query: stripped.qry_seq,
@@ -386,6 +567,7 @@ pub fn nextclade_run_one(
       nuc_to_aa_muts,
       alignment_range,
       alignment_score,
+      alignment_diagnostics,
       aa_alignment_ranges,
       aa_unsequenced_ranges,
       pcr_primer_changes,
@@ -393,18 +575,133 @@ pub fn nextclade_run_one(
       warnings,
       missing_genes,
       coverage,
+      cds_coverage,
       aa_motifs,
       aa_motifs_changes,
       qc,
       clade,
+      clade_defining_mutations,
       private_nuc_mutations,
       private_aa_mutations,
+      relative_nuc_mutations,
+      hgvs,
       phenotype_values,
       divergence,
       custom_node_attributes,
       nearest_node_id,
       nearest_nodes,
       is_reverse_complement,
+      tree_dependent_skipped,
+      ref_name: ref_record.seq_name.clone(),
+      structural_events,
+      normalized_homopolymer_indels,
+      masked_ranges,
+      imputed_nucs,
+      recombinant,
+      placement_candidates,
+      mutation_path,
+      lineage_call,
+      mutation_annotations,
+      minor_variants,
     },
+    alignment_band,
   })
 }
+
+/// Aligns a query sequence and translates its CDSes, skipping the rest of the analysis (mutation calling, clade
+/// assignment, QC and phylogenetic placement)
+pub fn nextclade_translate_one(
+  index: usize,
+  seq_name: &str,
+  qry_seq: &[Nuc],
+  state: &Nextclade,
+) -> Result<Translation, Report> {
+  let Nextclade {
+    ref_seq,
+    seed_index,
+    minimizer_index,
+    gap_open_close_nuc,
+    params,
+    gene_map,
+    gap_open_close_aa,
+    translation_cache,
+    ref_translation,
+    ..
+  } = &state;
+
+  let alignment = align_nuc(
+    index,
+    seq_name,
+    qry_seq,
+    ref_seq,
+    seed_index,
+    minimizer_index.as_ref(),
+    gap_open_close_nuc,
+    &params.alignment,
+  )?;
+
+  let stripped = insertions_strip(&alignment.qry_seq, &alignment.ref_seq);
+  let FindNucChangesOutput { alignment_range, .. } = find_nuc_changes(&stripped.qry_seq, ref_seq);
+
+  let coord_map_global = CoordMapGlobal::new(&alignment.ref_seq);
+
+  translate_genes(
+    &alignment.qry_seq,
+    &alignment.ref_seq,
+    ref_translation,
+    gene_map,
+    &coord_map_global,
+    &alignment_range,
+    gap_open_close_aa,
+    &params.alignment,
+    translation_cache.as_ref(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::from_nuc_seq;
+  use crate::coord::position::PositionLike;
+  use crate::io::fasta::FastaRecord;
+  use crate::run::nextclade_wasm::{NextcladeParams, NextcladeParamsRaw};
+  use crate::run::params::NextcladeInputParamsOptional;
+  use crate::utils::random::random_nuc_seq;
+  use pretty_assertions::assert_eq;
+
+  const PATHOGEN_JSON: &str = r#"{
+    "schemaVersion": "3.0.0",
+    "files": { "reference": "reference.fasta", "pathogenJson": "pathogen.json" }
+  }"#;
+
+  #[test]
+  fn runs_without_reference_tree() -> Result<(), Report> {
+    let ref_seq = from_nuc_seq(&random_nuc_seq(&mut 42, 1000));
+    let params = NextcladeParams::from_raw(NextcladeParamsRaw {
+      ref_seq: format!(">ref\n{ref_seq}\n"),
+      gene_map: None,
+      tree: None,
+      virus_properties: PATHOGEN_JSON.to_owned(),
+    })?;
+    let nextclade = Nextclade::new(params, &NextcladeInputParamsOptional::default())?;
+    assert!(nextclade.graph.is_none());
+
+    let mut qry_seq = ref_seq.into_bytes();
+    qry_seq[500] = if qry_seq[500] == b'A' { b'C' } else { b'A' };
+    let record = FastaRecord {
+      seq_name: "qry".to_owned(),
+      seq: String::from_utf8(qry_seq)?,
+      index: 0,
+    };
+
+    let result = nextclade.run(&record)?.analysis_result;
+
+    assert!(result.tree_dependent_skipped);
+    assert_eq!(result.total_substitutions, 1);
+    assert_eq!(result.substitutions[0].pos.as_usize(), 500);
+    assert_eq!(result.clade, "");
+    assert!(result.private_nuc_mutations.private_substitutions.is_empty());
+    assert!(result.nearest_nodes.is_none());
+    Ok(())
+  }
+}
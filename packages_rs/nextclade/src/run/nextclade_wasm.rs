@@ -1,20 +1,32 @@
+use crate::align::alignment_band::AlignmentBand;
 use crate::align::gap_open::{get_gap_open_close_scores_codon_aware, get_gap_open_close_scores_flat, GapScoreMap};
 use crate::align::seed_match2::CodonSpacedIndex;
+use crate::align::seed_minimizer::MinimizerSeedIndex;
+use crate::alphabet::aa::to_aa_seq;
 use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq};
 use crate::alphabet::nuc::{to_nuc_seq, to_nuc_seq_replacing, Nuc};
 use crate::analyze::find_aa_motifs::find_aa_motifs;
 use crate::analyze::find_aa_motifs_changes::AaMotifsMap;
+use crate::analyze::lineage_caller::LineageCaller;
+use crate::analyze::minor_variants::AlleleFrequencies;
+use crate::analyze::mutation_annotations::MutationKnowledgeBase;
 use crate::analyze::phenotype::get_phenotype_attr_descs;
+use crate::analyze::recombination::CladeProfiles;
+use crate::analyze::relative_mutations::find_relative_mutations_node;
 use crate::analyze::virus_properties::{AaMotifsDesc, PhenotypeAttrDesc, VirusProperties};
 use crate::gene::gene_map::GeneMap;
 use crate::graph::graph::{convert_auspice_tree_to_graph, convert_graph_to_auspice_tree};
+use crate::graph::node::GraphNodeKey;
 use crate::io::fasta::{read_one_fasta_str, FastaRecord};
 use crate::io::nextclade_csv::CsvColumnConfig;
 use crate::io::nwk_writer::convert_graph_to_nwk_string;
-use crate::run::nextclade_run_one::nextclade_run_one;
+use crate::run::nextclade_run_aa::{nextclade_run_aa_one, AaAnalysisOutput};
+use crate::run::nextclade_run_one::{nextclade_run_one, nextclade_translate_one};
 use crate::run::params::{NextcladeInputParams, NextcladeInputParamsOptional};
+use crate::translate::genetic_code::gene_map_override_transl_tables;
 use crate::translate::translate_genes::Translation;
 use crate::translate::translate_genes_ref::translate_genes_ref;
+use crate::translate::translation_cache::CdsTranslationCache;
 use crate::tree::tree::{AuspiceGraph, AuspiceTree, CladeNodeAttrKeyDesc};
 use crate::tree::tree_builder::graph_attach_new_nodes_in_place;
 use crate::tree::tree_preprocess::graph_preprocess_in_place;
@@ -104,6 +116,8 @@ pub struct AnalysisOutput {
   pub query: Vec<Nuc>,
   pub translation: Translation,
   pub analysis_result: NextcladeOutputs,
+  #[serde(skip)]
+  pub alignment_band: Option<AlignmentBand>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
@@ -120,6 +134,7 @@ pub struct Nextclade {
   pub ref_record: FastaRecord,
   pub ref_seq: Vec<Nuc>,
   pub seed_index: CodonSpacedIndex,
+  pub minimizer_index: Option<MinimizerSeedIndex>,
   pub gap_open_close_nuc: Vec<i32>,
   pub virus_properties: VirusProperties,
   pub params: NextcladeInputParams,
@@ -131,9 +146,24 @@ pub struct Nextclade {
   pub aa_motifs_ref: AaMotifsMap,
   pub aa_motifs_descs: Vec<AaMotifsDesc>,
   pub aa_motifs_keys: Vec<String>,
+  pub translation_cache: Option<CdsTranslationCache>,
+
+  // If lineage definitions are provided
+  pub lineage_caller: Option<LineageCaller>,
+
+  // If a knowledge base of known mutations is provided
+  pub mutation_knowledge_base: Option<MutationKnowledgeBase>,
+
+  // If allele frequencies of the samples are provided
+  pub allele_frequencies: Option<AlleleFrequencies>,
+
+  // If alignment band output is requested
+  pub include_alignment_band: bool,
 
   // If ref tree is provided
   pub graph: Option<AuspiceGraph>,
+  pub relative_mutations_node: Option<GraphNodeKey>,
+  pub clade_profiles: Option<CladeProfiles>,
   pub clade_attr_descs: Vec<CladeNodeAttrKeyDesc>,
   pub phenotype_attr_descs: Vec<PhenotypeAttrDesc>,
 }
@@ -155,14 +185,26 @@ impl Nextclade {
   pub fn new(inputs: NextcladeParams, params: &NextcladeInputParamsOptional) -> Result<Self, Report> {
     let NextcladeParams {
       ref_record,
-      gene_map,
+      mut gene_map,
       tree,
-      virus_properties,
+      mut virus_properties,
     } = inputs;
 
+    // Masking expects the masked ranges to be sorted
+    virus_properties.masked_ranges.sort();
+
+    gene_map_override_transl_tables(
+      &mut gene_map,
+      virus_properties.transl_table,
+      &virus_properties.cds_transl_tables,
+    )
+    .wrap_err("When applying translation tables from pathogen JSON")?;
+
     let params = NextcladeInputParams::from_optional(params, &virus_properties)?;
     let ref_seq = to_nuc_seq(&ref_record.seq).wrap_err("When converting reference sequence")?;
     let seed_index = CodonSpacedIndex::from_sequence(&ref_seq);
+    let minimizer_index = (ref_seq.len() >= params.alignment.minimizer_seeding_min_ref_length)
+      .then(|| MinimizerSeedIndex::from_sequence(&ref_seq));
 
     // If genome annotation is present, calculate AA-related parameters
     let InitialStateWithAa {
@@ -200,6 +242,9 @@ impl Nextclade {
       .map(|tree| -> Result<AuspiceGraph, Report> {
         let mut graph =
           convert_auspice_tree_to_graph(tree).wrap_err("When converting Auspice tree to Nextclade graph")?;
+        if let Some(divergence_units) = params.tree_builder.divergence_units {
+          graph.data.tmp.divergence_units = divergence_units;
+        }
 
         graph_preprocess_in_place(&mut graph, &ref_seq, &ref_translation)
           .wrap_err("When preprocessing Nextclade graph")?;
@@ -208,6 +253,17 @@ impl Nextclade {
       })
       .transpose()?;
 
+    if let Some(qc_config) = &virus_properties.qc {
+      qc_config.validate_rules()?;
+    }
+
+    let relative_mutations_node = match (&graph, &params.general.mutations_relative_to) {
+      (Some(graph), Some(relative_to)) => find_relative_mutations_node(graph, relative_to)?,
+      _ => None,
+    };
+
+    let clade_profiles = graph.as_ref().map(|graph| CladeProfiles::from_graph(graph, &ref_seq));
+
     let clade_attr_descs = graph
       .as_ref()
       .map(|graph| graph.data.meta.clade_node_attr_descs().to_vec())
@@ -215,6 +271,9 @@ impl Nextclade {
 
     let phenotype_attr_descs = get_phenotype_attr_descs(&virus_properties);
 
+    let translation_cache = (!gene_map.is_empty() && params.general.translation_cache_size > 0)
+      .then(|| CdsTranslationCache::new(params.general.translation_cache_size));
+
     let aa_motifs_descs = virus_properties.aa_motifs.clone();
     let aa_motifs_keys = aa_motifs_descs.iter().map(|desc| desc.name.clone()).collect_vec();
 
@@ -222,6 +281,7 @@ impl Nextclade {
       ref_record,
       ref_seq,
       seed_index,
+      minimizer_index,
       gap_open_close_nuc,
       virus_properties,
       params,
@@ -231,7 +291,14 @@ impl Nextclade {
       aa_motifs_ref,
       aa_motifs_descs,
       aa_motifs_keys,
+      translation_cache,
+      lineage_caller: None,
+      mutation_knowledge_base: None,
+      allele_frequencies: None,
+      include_alignment_band: false,
       graph,
+      relative_mutations_node,
+      clade_profiles,
       clade_attr_descs,
       phenotype_attr_descs,
     })
@@ -260,6 +327,22 @@ impl Nextclade {
     .and_then(|qry_seq| nextclade_run_one(input.index, &input.seq_name, &qry_seq, self))
   }
 
+  /// Aligns and translates a query sequence, without the rest of the analysis
+  pub fn translate(&self, input: &FastaRecord) -> Result<Translation, Report> {
+    if self.params.general.replace_unknown {
+      Ok(to_nuc_seq_replacing(&input.seq))
+    } else {
+      to_nuc_seq(&input.seq)
+    }
+    .and_then(|qry_seq| nextclade_translate_one(input.index, &input.seq_name, &qry_seq, self))
+  }
+
+  /// Analyzes a query peptide of a given CDS, skipping nucleotide alignment and analysis
+  pub fn run_aa(&self, input: &FastaRecord, cds_name: &str) -> Result<AaAnalysisOutput, Report> {
+    to_aa_seq(&input.seq)
+      .and_then(|qry_seq| nextclade_run_aa_one(input.index, &input.seq_name, &qry_seq, cds_name, self))
+  }
+
   pub fn get_output_trees(&mut self, results: Vec<NextcladeOutputs>) -> Result<Option<OutputTrees>, Report> {
     if let Some(graph) = &mut self.graph {
       graph_attach_new_nodes_in_place(graph, results, self.ref_seq.len(), &self.params.tree_builder)?;
@@ -17,6 +17,20 @@ pub struct NextcladeGeneralParams {
   #[clap(num_args=0..=1, default_missing_value = "true")]
   pub include_nearest_node_info: bool,
 
+  /// Whether to include per-sequence diagnostics of nucleotide alignment to the outputs: alignment score, number of seed matches, number of alignment attempts, width of the alignment band, number of alignment matrix cells computed and wall time taken by the alignment.
+  ///
+  /// The diagnostics are added to the `alignmentDiagnostics` field of JSON and NDJSON outputs, and as `alignmentDiagnostics.*` columns of CSV and TSV outputs. This is useful for tuning of alignment parameters for particular data. Note that the wall time varies between runs, so the outputs are no longer reproducible.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub include_alignment_diagnostics: bool,
+
+  /// Whether to include nucleotide and aminoacid mutations in HGVS nomenclature to the outputs, in addition to the compact notation.
+  ///
+  /// Nucleotide changes are reported relative to the reference genome (`g.`) and to the coding sequences (`c.`), aminoacid changes are reported relative to the peptides (`p.`), using three-letter aminoacid codes. Coding and protein changes are prefixed with the CDS name. The changes are added to the `hgvs` field of JSON and NDJSON outputs, and as `hgvs.*` columns of CSV and TSV outputs.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub include_hgvs: bool,
+
   /// Emit output sequences in-order.
   ///
   /// With this flag the program will wait for results from the previous sequences to be written to the output files before writing the results of the next sequences, preserving the same order as in the input file. Due to variable sequence processing times, this might introduce unnecessary waiting times, but ensures that the resulting sequences are written in the same order as they occur in the inputs (except for sequences which have errors).
@@ -39,6 +53,59 @@ pub struct NextcladeGeneralParams {
   #[clap(long)]
   #[clap(num_args=0..=1, default_missing_value = "true")]
   pub replace_unknown: bool,
+
+  /// Minimum length of a deletion to be reported as a structural event (large deletion).
+  ///
+  /// Deletions spanning more than one CDS and deletions joining two CDSes in the same reading frame (possible gene fusions) are reported as structural events regardless of their length.
+  #[clap(long)]
+  pub min_structural_deletion_length: usize,
+
+  /// Maximum number of CDS translations kept for reuse. Set to 0 to disable.
+  ///
+  /// Closely related sequences often have identical nucleotide sequences of some of the CDSes. Such CDSes are translated and aligned only once, and the translations are reused for the subsequent sequences. When the limit is reached, the least recently used translations are discarded.
+  #[clap(long)]
+  pub translation_cache_size: usize,
+
+  /// Additionally calculate nucleotide substitutions and deletions relative to a node of the reference tree, rather than only relative to the reference sequence.
+  ///
+  /// Either the name of a node of the reference tree, or `clade-founder`, in which case the mutations are calculated relative to the founder node of the clade assigned to each sequence (the node closest to the root which belongs to the same clade as the nearest node). The mutations are added to the `relativeNucMutations` field of JSON and NDJSON outputs, and as `relativeNucMutations.*` columns of CSV and TSV outputs. Requires reference tree.
+  #[clap(long)]
+  pub mutations_relative_to: Option<String>,
+
+  /// Resolve ambiguous nucleotides of the query sequences using the nucleotides of the nodes of the reference tree the sequences are attached to.
+  ///
+  /// After phylogenetic placement, an ambiguous nucleotide (for example `R` or `Y`) is replaced in the output aligned sequence with the nucleotide of the nearest node, if the ambiguity code is compatible with it. `N` is missing data and is not imputed. Mutations, QC and placement are calculated before imputation and are not affected. The imputed positions are added to the `imputedNucs` field of JSON and NDJSON outputs, and as `imputedNucs` column of CSV and TSV outputs. Requires reference tree.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub impute_ambiguous: bool,
+
+  /// Whether to detect putative recombinants: sequences which are a mosaic of two clades of the reference tree.
+  ///
+  /// The clade-informative sites (positions where the founder nodes of the clades differ) are scanned along the genome, and a sequence is flagged if two clades, one on each side of a breakpoint, explain the sequence better than any single clade. A single breakpoint is considered. The parental clades and the breakpoint estimate are added to the `recombinant` field of JSON and NDJSON outputs, and as `recombinant.*` columns of CSV and TSV outputs. Requires reference tree.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub detect_recombinants: bool,
+
+  /// Number of best candidate attachment points on the reference tree to report for each sequence. Set to 0 to disable.
+  ///
+  /// For each candidate node, its name, clade, distance to the sequence, number of shared nucleotide substitutions and relative weight are reported, along with the placement confidence: the relative weight of the nearest node, which is low when several nodes are about equally close to the sequence, i.e. when the placement is ambiguous. The candidates are added to the `placementCandidates` field of JSON and NDJSON outputs, and as `placement.*` columns of CSV and TSV outputs. Requires reference tree.
+  #[clap(long)]
+  pub num_placement_candidates: usize,
+
+  /// Whether to include mutations along the path from the root of the reference tree to the nearest node into the outputs.
+  ///
+  /// Mutations are listed branch by branch, starting from the root, along with the names and clades of the nodes at the ends of the branches. Branches without mutations are omitted. Followed by the private mutations, this is the reconstructed ancestral history of the sequence, which helps to understand how a particular clade was assigned. The path is added to the `mutationPath` field of JSON and NDJSON outputs, and the nucleotide substitutions along it as the `mutationPath` column of CSV and TSV outputs. Requires reference tree.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub include_mutation_path: bool,
+
+  /// Minimum frequency of a variant in the reads of a sample to be reported as a minor (sub-consensus) variant. See `--input-minor-variants`.
+  #[clap(long)]
+  pub minor_variant_min_frequency: f64,
+
+  /// Frequency of a variant in the reads of a sample, starting from which it is considered a consensus-level variant rather than a minor variant. See `--input-minor-variants`.
+  #[clap(long)]
+  pub minor_variant_max_frequency: f64,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -47,8 +114,19 @@ impl Default for NextcladeGeneralParams {
     Self {
       include_reference: false,
       include_nearest_node_info: false,
+      include_alignment_diagnostics: false,
+      include_hgvs: false,
       in_order: false,
       replace_unknown: false,
+      min_structural_deletion_length: 1000,
+      translation_cache_size: 1000,
+      mutations_relative_to: None,
+      impute_ambiguous: false,
+      detect_recombinants: false,
+      num_placement_candidates: 0,
+      include_mutation_path: false,
+      minor_variant_min_frequency: 0.05,
+      minor_variant_max_frequency: 0.5,
     }
   }
 }
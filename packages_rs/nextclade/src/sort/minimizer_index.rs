@@ -1,6 +1,7 @@
 use crate::io::fs::read_file_to_string;
 use crate::io::json::json_parse;
 use crate::io::schema_version::{SchemaVersion, SchemaVersionParams};
+use crate::sort::params::NextcladeSeqSortParamsOptional;
 use eyre::{Report, WrapErr};
 use log::warn;
 use schemars::JsonSchema;
@@ -70,6 +71,10 @@ pub struct MinimizerIndexParams {
 
   pub cutoff: i64,
 
+  /// Defaults of search parameters for all datasets in the index. These take precedence over the built-in defaults.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub search_params: Option<NextcladeSeqSortParamsOptional>,
+
   #[serde(flatten)]
   pub other: serde_json::Value,
 }
@@ -81,6 +86,11 @@ pub struct MinimizerIndexRefInfo {
   pub name: String,
   pub n_minimizers: i64,
 
+  /// Search parameters specific to this dataset, for example a lower score threshold for pathogens with short or
+  /// highly repetitive genomes. These take precedence over the defaults of the index.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub search_params: Option<NextcladeSeqSortParamsOptional>,
+
   #[serde(flatten)]
   pub other: serde_json::Value,
 }
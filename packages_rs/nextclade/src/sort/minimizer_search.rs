@@ -1,6 +1,6 @@
 use crate::io::fasta::FastaRecord;
 use crate::sort::minimizer_index::{MinimizerIndexJson, MinimizerIndexParams};
-use crate::sort::params::NextcladeSeqSortParams;
+use crate::sort::params::{NextcladeSeqSortParams, NextcladeSeqSortParamsOptional};
 use eyre::Report;
 use itertools::{izip, Itertools};
 use ordered_float::OrderedFloat;
@@ -32,11 +32,16 @@ pub struct MinimizerSearchRecord {
   pub result: MinimizerSearchResult,
 }
 
+/// Finds datasets matching the sequence.
+///
+/// Thresholds for detection are resolved for each dataset separately: parameters provided by the user (if any) take
+/// precedence over the dataset-specific parameters shipped in the minimizer index, which in turn take precedence over
+/// the index-wide defaults and then over the built-in defaults.
 #[allow(clippy::string_slice)]
 pub fn run_minimizer_search(
   fasta_record: &FastaRecord,
   index: &MinimizerIndexJson,
-  search_params: &NextcladeSeqSortParams,
+  search_params: &Option<NextcladeSeqSortParamsOptional>,
 ) -> Result<MinimizerSearchResult, Report> {
   let normalization = &index.normalization;
   let n_refs = index.references.len();
@@ -64,7 +69,9 @@ pub fn run_minimizer_search(
 
   let datasets = izip!(&index.references, hit_counts, scores)
     .filter_map(|(ref_info, n_hits, score)| {
-      (n_hits >= search_params.min_hits && score >= search_params.min_score).then_some(MinimizerSearchDatasetResult {
+      let NextcladeSeqSortParams { min_score, min_hits } =
+        NextcladeSeqSortParams::resolve([&index.params.search_params, &ref_info.search_params, search_params]);
+      (n_hits >= min_hits && score >= min_score).then_some(MinimizerSearchDatasetResult {
         name: ref_info.name.clone(),
         length: ref_info.length,
         n_hits,
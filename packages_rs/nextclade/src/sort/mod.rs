@@ -1,3 +1,4 @@
 pub mod minimizer_index;
 pub mod minimizer_search;
 pub mod params;
+pub mod reference_selection;
@@ -1,19 +1,26 @@
 use clap::Parser;
+use optfield::optfield;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+// NOTE: The `optfield` attribute creates a struct that have the same fields, but which are wrapped into `Option`,
+// as well as adds a method `.merge_opt(&opt)` to the original struct, which merges values from the optional counterpart
+// into self (mutably).
 #[allow(clippy::struct_excessive_bools)]
+#[optfield(pub NextcladeSeqSortParamsOptional, attrs, doc, field_attrs, field_doc, merge_fn = pub)]
 #[derive(Parser, Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NextcladeSeqSortParams {
-  /// Minimum value of the score being considered for a detection
+  /// Minimum value of the score being considered for a detection. Default: 0.3.
+  ///
+  /// Overrides the value provided by the minimizer index, if any.
   #[clap(long)]
-  #[clap(default_value_t = NextcladeSeqSortParams::default().min_score)]
   pub min_score: f64,
 
-  /// Minimum number of the index hits required for a detection
+  /// Minimum number of the index hits required for a detection. Default: 10.
+  ///
+  /// Overrides the value provided by the minimizer index, if any.
   #[clap(long)]
-  #[clap(default_value_t = NextcladeSeqSortParams::default().min_hits)]
   pub min_hits: u64,
 }
 
@@ -26,3 +33,15 @@ impl Default for NextcladeSeqSortParams {
     }
   }
 }
+
+impl NextcladeSeqSortParams {
+  /// Resolves search parameters, from the lowest to the highest priority: built-in defaults, defaults of the
+  /// minimizer index, overrides for a particular dataset in the minimizer index, parameters provided by the user.
+  pub fn resolve<'a>(overrides: impl IntoIterator<Item = &'a Option<NextcladeSeqSortParamsOptional>>) -> Self {
+    let mut params = Self::default();
+    for params_opt in overrides.into_iter().flatten() {
+      params.merge_opt(params_opt.clone());
+    }
+    params
+  }
+}
@@ -0,0 +1,102 @@
+use crate::io::fasta::FastaRecord;
+use crate::sort::minimizer_index::MinimizerIndexParams;
+use crate::sort::minimizer_search::get_ref_search_minimizers;
+use std::collections::HashSet;
+
+/// Length of k-mers from which minimizers are computed. Same as in the minimizer index of the official datasets.
+const MINIMIZER_K: i64 = 17;
+
+/// Only hashes below this value are used as minimizers. Same as in the minimizer index of the official datasets.
+const MINIMIZER_CUTOFF: i64 = 1 << 28;
+
+/// Selects, for each query sequence, the most similar of several candidate reference sequences, using the same
+/// minimizer matching as `nextclade sort`.
+pub struct ReferenceSelector {
+  params: MinimizerIndexParams,
+  references: Vec<ReferenceMinimizers>,
+}
+
+struct ReferenceMinimizers {
+  minimizers: HashSet<u64>,
+  normalization: f64,
+}
+
+impl ReferenceSelector {
+  pub fn new<'a>(references: impl IntoIterator<Item = &'a FastaRecord>) -> Self {
+    let params = MinimizerIndexParams {
+      k: MINIMIZER_K,
+      cutoff: MINIMIZER_CUTOFF,
+      search_params: None,
+      other: serde_json::Value::default(),
+    };
+
+    let references = references
+      .into_iter()
+      .map(|reference| {
+        let minimizers: HashSet<u64> = get_ref_search_minimizers(reference, &params).into_iter().collect();
+        // Hits are expected to be proportional to the number of minimizers per nucleotide of the reference
+        let normalization = reference.seq.len() as f64 / minimizers.len().max(1) as f64;
+        ReferenceMinimizers {
+          minimizers,
+          normalization,
+        }
+      })
+      .collect();
+
+    Self { params, references }
+  }
+
+  /// Returns index of the reference with the highest score for the query. The first reference is returned if none of
+  /// the references share any minimizers with the query.
+  pub fn select(&self, qry: &FastaRecord) -> usize {
+    let minimizers = get_ref_search_minimizers(qry, &self.params);
+
+    let mut best_index = 0;
+    let mut best_score = 0.0;
+    for (index, reference) in self.references.iter().enumerate() {
+      let n_hits = minimizers
+        .iter()
+        .filter(|minimizer| reference.minimizers.contains(minimizer))
+        .count();
+      let score = n_hits as f64 * reference.normalization;
+      if score > best_score {
+        best_index = index;
+        best_score = score;
+      }
+    }
+    best_index
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::from_nuc_seq;
+  use crate::utils::random::random_nuc_seq;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn random_seq(state: &mut u64, len: usize) -> String {
+    from_nuc_seq(&random_nuc_seq(state, len))
+  }
+
+  fn record(seq: &str) -> FastaRecord {
+    FastaRecord {
+      seq_name: String::new(),
+      seq: seq.to_owned(),
+      index: 0,
+    }
+  }
+
+  #[rstest]
+  fn selects_most_similar_reference() {
+    let mut state = 42;
+    let ref_a = random_seq(&mut state, 3000);
+    let ref_b = random_seq(&mut state, 3000);
+    let selector = ReferenceSelector::new(&[record(&ref_a), record(&ref_b)]);
+
+    #[allow(clippy::string_slice)]
+    let qry = &ref_b[500..2500];
+    assert_eq!(selector.select(&record(qry)), 1);
+  }
+}
@@ -1,6 +1,7 @@
 use crate::coord::coord_map::local_to_codon_range_exclusive;
 use crate::coord::position::PositionLike;
 use crate::coord::range::{intersect, AaRefRange, NucRefGlobalRange, NucRefLocalRange};
+use crate::gene::cds_segment::WrappingPart;
 use crate::gene::gene_map::GeneMap;
 use crate::translate::translate_genes::Translation;
 use eyre::Report;
@@ -18,21 +19,39 @@ pub fn calculate_aa_alignment_ranges_in_place(
     // Get CDS annotation
     let cds = gene_map.get_cds(&cds_tr.name)?;
 
-    let mut aa_alignment_ranges = vec![];
-    let mut prev_segment_end = 0;
+    let mut aa_alignment_ranges: Vec<AaRefRange> = vec![];
+    let mut prev_included_range_local: Option<NucRefLocalRange> = None;
     // For each segment
     for segment in &cds.segments {
       // Trim segment to include only what's inside alignment
       let included_range_global = intersect(global_alignment_range, &segment.range);
-      if !included_range_global.is_empty() {
-        // Convert to coordinates local to CDS (not local to segment!)
-        let included_range_local = NucRefLocalRange::from_range(
-          included_range_global - segment.range.begin.as_isize() + prev_segment_end as isize,
-        );
-        aa_alignment_ranges.push(local_to_codon_range_exclusive(&included_range_local));
+      if included_range_global.is_empty() {
+        prev_included_range_local = None;
+        continue;
       }
-      // CDS consists of concatenated segments; remember by how far we went along the CDS so far
-      prev_segment_end += segment.len();
+
+      // Convert to coordinates local to CDS (not local to segment!)
+      let included_range_local = NucRefLocalRange::from_range(
+        included_range_global - segment.range.begin.as_isize() + segment.range_local.begin.as_isize(),
+      );
+      let aa_range = local_to_codon_range_exclusive(&included_range_local);
+
+      // Parts of a CDS wrapping around the origin of a circular genome are split into separate segments. If the
+      // alignment continues across the origin, the parts are merged back, so that the sequenced part of the CDS is
+      // reported as a single range.
+      let continues_across_origin = matches!(
+        segment.wrapping_part,
+        WrappingPart::WrappingCentral(_) | WrappingPart::WrappingEnd(_)
+      ) && prev_included_range_local
+        .as_ref()
+        .is_some_and(|prev| prev.end == included_range_local.begin);
+
+      match aa_alignment_ranges.last_mut() {
+        Some(prev_aa_range) if continues_across_origin => prev_aa_range.end = aa_range.end,
+        _ => aa_alignment_ranges.push(aa_range),
+      }
+
+      prev_included_range_local = Some(included_range_local);
     }
 
     // Record computed AA alignment ranges on CDS translation
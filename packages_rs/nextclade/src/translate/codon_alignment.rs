@@ -0,0 +1,84 @@
+use crate::alphabet::aa::Aa;
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+
+/// Builds nucleotide alignment of the query CDS to the reference CDS, codon by codon, by back-translating the peptide
+/// alignment: every aminoacid of the aligned query peptide is replaced with the codon it is translated from, and every
+/// gap is replaced with a gap codon. Codons inserted relative to the reference are omitted, such that the result is in
+/// reference coordinates, 3 nucleotides per reference aminoacid.
+///
+/// The `qry_cds_seq` is the query CDS sequence, which has been translated into the (unaligned) query peptide, such that
+/// i-th aminoacid is translated from the i-th codon.
+pub fn codon_align(qry_cds_seq: &[Nuc], qry_peptide_aln: &[Aa], ref_peptide_aln: &[Aa]) -> Vec<Nuc> {
+  debug_assert_eq!(qry_peptide_aln.len(), ref_peptide_aln.len());
+
+  let mut codons = Vec::with_capacity(ref_peptide_aln.len() * 3);
+  let mut i_codon = 0;
+  for (qry_aa, ref_aa) in qry_peptide_aln.iter().zip(ref_peptide_aln) {
+    if qry_aa.is_gap() {
+      if !ref_aa.is_gap() {
+        codons.extend_from_slice(&[Nuc::Gap; 3]);
+      }
+      continue;
+    }
+
+    if !ref_aa.is_gap() {
+      let codon = qry_cds_seq.get(i_codon * 3..i_codon * 3 + 3).unwrap_or(&[Nuc::N; 3]);
+      codons.extend_from_slice(codon);
+    }
+    i_codon += 1;
+  }
+
+  codons
+}
+
+/// Masks codons of the codon alignment, the aminoacids of which have been masked in the aligned peptide afterwards (for
+/// example in frame shifts): codons of aminoacids which became unknown are replaced with `NNN` and codons of aminoacids
+/// which became gaps are replaced with `---`.
+pub fn codon_alignment_mask_in_place(codons: &mut [Nuc], peptide_unmasked: &[Aa], peptide_masked: &[Aa]) {
+  for (i, (before, after)) in peptide_unmasked.iter().zip(peptide_masked).enumerate() {
+    if before == after {
+      continue;
+    }
+
+    let replacement = if after.is_gap() { Nuc::Gap } else { Nuc::N };
+    if let Some(codon) = codons.get_mut(i * 3..i * 3 + 3) {
+      codon.fill(replacement);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::to_aa_seq;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rustfmt::skip]
+  #[rstest]
+  #[case::no_indels(   "ATGAAACCC",    "MKP",  "MKP",  "ATGAAACCC"   )]
+  #[case::deletion(    "ATGCCC",       "M-P",  "MKP",  "ATG---CCC"   )]
+  #[case::insertion(   "ATGGGGAAACCC", "MGKP", "M-KP", "ATGAAACCC"   )]
+  #[case::trailing_gap("ATGAAA",       "MK-",  "MKP",  "ATGAAA---"   )]
+  fn back_translates_peptide_alignment(
+    #[case] qry_cds: &str,
+    #[case] qry_aln: &str,
+    #[case] ref_aln: &str,
+    #[case] expected: &str,
+  ) -> Result<(), Report> {
+    let actual = codon_align(&to_nuc_seq(qry_cds)?, &to_aa_seq(qry_aln)?, &to_aa_seq(ref_aln)?);
+    assert_eq!(actual, to_nuc_seq(expected)?);
+    Ok(())
+  }
+
+  #[rstest]
+  fn masks_codons_of_masked_aminoacids() -> Result<(), Report> {
+    let mut codons = to_nuc_seq("ATGAAACCCGGG")?;
+    codon_alignment_mask_in_place(&mut codons, &to_aa_seq("MKPG")?, &to_aa_seq("MX-G")?);
+    assert_eq!(codons, to_nuc_seq("ATGNNN---GGG")?);
+    Ok(())
+  }
+}
@@ -1,5 +1,6 @@
 use crate::alphabet::nuc::Nuc;
 use crate::coord::coord_map_global::CoordMapGlobal;
+use crate::coord::position::{NucAlnGlobalPosition, PositionLike};
 use crate::coord::range::NucAlnGlobalRange;
 use crate::gene::cds::Cds;
 use crate::gene::cds_segment::WrappingPart;
@@ -10,27 +11,42 @@ use itertools::Itertools;
 pub fn extract_cds_from_aln(seq_aln: &[Nuc], cds: &Cds, coord_map_global: &CoordMapGlobal) -> Vec<Nuc> {
   let mut cds_aln_seq = vec![];
   for segment in &cds.segments {
-    // TODO: should we use `landmark.range.end` (converted to aln coords) instead of `seq_aln.len()`?
+    // Parts of a segment which wraps around the origin of a circular genome touch the ends of the reference sequence.
+    // At these ends, the range is extended to the ends of the alignment, such that the insertions adjacent to the
+    // origin are not lost. If the landmark feature does not span the entire reference, the range is kept within it.
+    let aln_begin = || {
+      if segment.range.begin.as_usize() == 0 {
+        NucAlnGlobalPosition::new(0)
+      } else {
+        coord_map_global.ref_to_aln_position(segment.range.begin)
+      }
+    };
+
+    let aln_end = || {
+      if segment.range.end.as_usize() >= coord_map_global.ref_len() {
+        NucAlnGlobalPosition::from(seq_aln.len())
+      } else {
+        coord_map_global.ref_to_aln_position(segment.range.end - 1) + 1
+      }
+    };
+
     let range = match segment.wrapping_part {
       WrappingPart::NonWrapping => coord_map_global.ref_to_aln_range(&segment.range),
       WrappingPart::WrappingStart => {
         // If segment is the first part of a segment that wraps around the origin,
-        // limit the range to end of alignment (trim the overflowing parts)
-        NucAlnGlobalRange::new(
-          coord_map_global.ref_to_aln_position(segment.range.begin),
-          seq_aln.len().into(),
-        )
+        // limit the range to end of the landmark (trim the overflowing parts)
+        NucAlnGlobalRange::new(coord_map_global.ref_to_aln_position(segment.range.begin), aln_end())
       }
       WrappingPart::WrappingCentral(_) => {
         // If segment is one of the the middle parts of a segment that wraps around the origin,
-        // it spans the entire aligned sequence.
-        NucAlnGlobalRange::from_usize(0, seq_aln.len())
+        // it spans the entire landmark.
+        NucAlnGlobalRange::new(aln_begin(), aln_end())
       }
       WrappingPart::WrappingEnd(_) => {
         // If segment is the last part of a segment that wraps around the origin,
-        // start range at the beginning of the alignment (trim the underflowing parts)
+        // start range at the beginning of the landmark (trim the underflowing parts)
         NucAlnGlobalRange::new(
-          0.into(),
+          aln_begin(),
           coord_map_global.ref_to_aln_position(segment.range.end - 1) + 1,
         )
       }
@@ -66,6 +82,7 @@ mod coord_map_tests {
   use crate::alphabet::nuc::to_nuc_seq;
   use crate::coord::position::Position;
   use crate::coord::range::{NucRefGlobalRange, Range};
+  use crate::gene::cds_partial::CdsPartial;
   use crate::gene::cds_segment::{CdsSegment, WrappingPart};
   use crate::gene::frame::Frame;
   use crate::gene::phase::Phase;
@@ -74,6 +91,7 @@ mod coord_map_tests {
   use maplit::hashmap;
   use pretty_assertions::assert_eq;
   use rstest::rstest;
+  use std::sync::OnceLock;
 
   fn create_fake_cds(segment_ranges: &[(isize, isize)]) -> Cds {
     let foo = 0;
@@ -118,6 +136,11 @@ mod coord_map_tests {
       attributes: hashmap! {},
       compat_is_gene: false,
       color: None,
+      transl_table: None,
+      ribosomal_slippage: None,
+      partial: CdsPartial::default(),
+      transl_except: vec![],
+      name_interned: OnceLock::new(),
     }
   }
 
@@ -164,4 +187,27 @@ mod coord_map_tests {
 
     Ok(())
   }
+
+  //noinspection SpellCheckingInspection
+  #[rustfmt::skip]
+  #[rstest]
+  fn extracts_cds_alignment_wrapping_around_origin() -> Result<(), Report> {
+    // CDS range              222    111
+    // index                  012345678901
+    let ref_aln = to_nuc_seq("ACGTACGTAC--")?;
+    let qry_aln = to_nuc_seq("ACGTACGTACTT")?;
+
+    let mut cds = create_fake_cds(&[(7, 10), (0, 3)]);
+    cds.segments[0].wrapping_part = WrappingPart::WrappingStart;
+    cds.segments[1].wrapping_part = WrappingPart::WrappingEnd(1);
+    let global_coord_map = CoordMapGlobal::new(&ref_aln);
+
+    let ref_cds_aln = extract_cds_from_aln(&ref_aln, &cds, &global_coord_map);
+    assert_eq!(ref_cds_aln, to_nuc_seq("TAC--ACG")?);
+
+    let qry_cds_aln = extract_cds_from_aln(&qry_aln, &cds, &global_coord_map);
+    assert_eq!(qry_cds_aln, to_nuc_seq("TACTTACG")?);
+
+    Ok(())
+  }
 }
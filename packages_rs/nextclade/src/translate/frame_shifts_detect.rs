@@ -1,30 +1,28 @@
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
+use crate::coord::position::NucAlnLocalPosition;
 use crate::coord::range::NucAlnLocalRange;
 use crate::utils::wraparound::wraparound;
 
-// Invalid/unset positions are set with this value
-static POSITION_INVALID: i32 = -1;
-
 struct FrameShiftDetector {
-  frame_shifts: Vec<NucAlnLocalRange>, // List of detected frame shifts
-  frame: i32,                          // Frame of the previously processed character (not necessarily n-1!)
-  old_frame: i32,                      // Frame of the character before previous (not necessarily n-2!)
-  begin: i32,                          // Remembers potential begin of the current frame shift range
-  end: i32,                            // Remembers potential end of the current frame shift range
-  last_indel: i32,                     // Remembers position of the last insertion of deletion
-  dirty: bool,                         // Allows to avoid full run in `advance()` on every character
+  frame_shifts: Vec<NucAlnLocalRange>,     // List of detected frame shifts
+  frame: i32,                              // Frame of the previously processed character (not necessarily n-1!)
+  old_frame: i32,                          // Frame of the character before previous (not necessarily n-2!)
+  begin: Option<NucAlnLocalPosition>,      // Remembers potential begin of the current frame shift range
+  end: Option<NucAlnLocalPosition>,        // Remembers potential end of the current frame shift range
+  last_indel: Option<NucAlnLocalPosition>, // Remembers position of the last insertion of deletion
+  dirty: bool,                             // Allows to avoid full run in `advance()` on every character
 }
 
 impl FrameShiftDetector {
-  pub fn new(start_frame: i32) -> Self {
+  pub const fn new(start_frame: i32) -> Self {
     Self {
       frame_shifts: vec![],
       frame: start_frame,
       old_frame: 0,
-      begin: POSITION_INVALID,
-      end: POSITION_INVALID,
-      last_indel: POSITION_INVALID,
+      begin: None,
+      end: None,
+      last_indel: None,
       dirty: false,
     }
   }
@@ -35,63 +33,60 @@ impl FrameShiftDetector {
   }
 
   /// Call this for every insertion */
-  pub fn add_insertion(&mut self, pos: i32) {
+  pub fn add_insertion(&mut self, pos: NucAlnLocalPosition) {
     self.update(-1, pos);
   }
 
   /// Call this for every deletion */
-  pub fn add_deletion(&mut self, pos: i32) {
+  pub fn add_deletion(&mut self, pos: NucAlnLocalPosition) {
     self.update(1, pos);
   }
 
   /// Call this for every non-shifting character (not an indel) */
-  pub fn advance(&mut self, pos: i32) {
+  pub fn advance(&mut self, pos: NucAlnLocalPosition) {
     // Avoid full run in advance() on every character.
     // Only run 1 character when requested by setting `dirty = true` in `update()`.
     if !self.dirty {
       return;
     }
 
-    if self.frame == 0 && self.begin != POSITION_INVALID {
-      // We are not in shift and `begin` was set previously. This is the end of the shift range. Remember the range.
-      debug_assert!(self.begin >= 0);
-      debug_assert!(self.begin <= self.end);
-      self
-        .frame_shifts
-        .push(NucAlnLocalRange::from_usize(self.begin as usize, self.end as usize));
-      self.reset();
+    if self.frame == 0 {
+      if let Some(begin) = self.begin {
+        // We are not in shift and `begin` was set previously. This is the end of the shift range. Remember the range.
+        let end = self.end.unwrap_or(begin);
+        debug_assert!(begin <= end);
+        self.frame_shifts.push(NucAlnLocalRange::new(begin, end));
+        self.reset();
+      }
     }
 
-    if self.frame != 0 && self.begin == POSITION_INVALID {
+    if self.frame != 0 && self.begin.is_none() {
       // We are in the frame shift. This *might* be the the beginning of a shifted range. Note that it might also not
       // be, because there might be more non-shifting characters ahead.
-      self.begin = pos;
+      self.begin = Some(pos);
     }
 
     self.dirty = false;
   }
 
   /// Run this after sequence iteration is over, with the length of the sequence */
-  pub fn done(&mut self, pos: i32) {
-    if self.begin != POSITION_INVALID {
-      debug_assert!(self.begin >= 0);
-      debug_assert!(self.begin <= pos);
-      self
-        .frame_shifts
-        .push(NucAlnLocalRange::from_usize(self.begin as usize, pos as usize));
+  pub fn done(&mut self, end: NucAlnLocalPosition) {
+    if let Some(begin) = self.begin {
+      debug_assert!(begin <= end);
+      self.frame_shifts.push(NucAlnLocalRange::new(begin, end));
       self.reset();
     }
   }
 
   /** Resets the state of the detector */
   pub fn reset(&mut self) {
-    self.begin = POSITION_INVALID;
-    self.end = POSITION_INVALID;
-    self.last_indel = POSITION_INVALID;
+    self.begin = None;
+    self.end = None;
+    self.last_indel = None;
   }
 
   /// Updates detector's state
-  fn update(&mut self, shift: i32, pos: i32) {
+  fn update(&mut self, shift: i32, pos: NucAlnLocalPosition) {
     self.old_frame = self.frame;
     self.frame += shift;
     self.frame = wraparound(self.frame, 3);
@@ -102,13 +97,15 @@ impl FrameShiftDetector {
     // Whether transitioned from shift to no shift
     let to_no_shift = self.old_frame != 0 && self.frame == 0;
 
-    // Whether the previous character is an insertion or a deletion
-    let prev_is_indel = pos - self.last_indel == 1;
+    // Whether the previous character is an insertion or a deletion. Before the first indel, the character before the
+    // beginning of the sequence is considered to be one.
+    let last_indel = self.last_indel.unwrap_or_else(|| NucAlnLocalPosition::from(-1_isize));
+    let prev_is_indel = pos - last_indel == 1;
 
     if !prev_is_indel {
       // Previous character is non-shifting, so it *might* be the end of the shift. Note that it might also not be,
       // because there might be more non-shifting characters ahead in the same shifted range.
-      self.end = pos;
+      self.end = Some(pos);
     }
 
     if to_shift || to_no_shift {
@@ -116,7 +113,7 @@ impl FrameShiftDetector {
       self.dirty = true;
     }
 
-    self.last_indel = pos;
+    self.last_indel = Some(pos);
   }
 }
 
@@ -124,19 +121,48 @@ impl FrameShiftDetector {
 /// and the corresponding aminoacid frame shifts in the query peptide
 pub fn frame_shifts_detect(qry_gene_seq: &[Nuc], ref_gene_seq: &[Nuc]) -> Vec<NucAlnLocalRange> {
   debug_assert_eq!(ref_gene_seq.len(), qry_gene_seq.len());
-  let length = ref_gene_seq.len() as i32;
 
   let mut detector = FrameShiftDetector::new(0);
-  for pos in 0..length {
-    if ref_gene_seq[pos as usize].is_gap() {
+  for (i, (reff, qry)) in ref_gene_seq.iter().zip(qry_gene_seq).enumerate() {
+    let pos = NucAlnLocalPosition::from(i);
+    if reff.is_gap() {
       detector.add_insertion(pos);
-    } else if qry_gene_seq[pos as usize].is_gap() {
+    } else if qry.is_gap() {
       detector.add_deletion(pos);
     } else {
       detector.advance(pos);
     }
   }
-  detector.done(length);
+  detector.done(NucAlnLocalPosition::from(ref_gene_seq.len()));
 
   detector.get_frame_shifts().to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rustfmt::skip]
+  #[rstest]
+  #[case::no_indels(     "ACGTACGTACGTACGTACGTACGTACGTAC", "ACGTACGTACGTACGTACGTACGTACGTAC", vec![])]
+  #[case::codon_deletion("ACGTAC---CGTACGTACGTACGTACGTAC", "ACGTACGTACGTACGTACGTACGTACGTAC", vec![])]
+  #[case::deletion(      "ACGTAC-TACGTACGTACGTACGTACGTAC", "ACGTACGTACGTACGTACGTACGTACGTAC", vec![(7, 30)])]
+  #[case::compensated(   "ACGTAC-TACGTACGTACGTACGTACGTAC", "ACGTACGTACGTACG-ACGTACGTACGTAC", vec![(7, 15)])]
+  fn detects_frame_shifts(
+    #[case] qry: &str,
+    #[case] reff: &str,
+    #[case] expected: Vec<(usize, usize)>,
+  ) -> Result<(), Report> {
+    let expected = expected
+      .into_iter()
+      .map(|(begin, end)| NucAlnLocalRange::from_usize(begin, end))
+      .collect::<Vec<_>>();
+    let actual = frame_shifts_detect(&to_nuc_seq(qry)?, &to_nuc_seq(reff)?);
+    assert_eq!(expected, actual);
+    Ok(())
+  }
+}
@@ -1,3 +1,4 @@
+use crate::align::params::{AlignPairwiseParams, FrameShiftPolicy};
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
 use crate::coord::coord_map_cds_to_global::cds_range_to_ref_ranges;
@@ -27,8 +28,9 @@ pub fn find_mask_begin(seq: &[Nuc], frame_shift_nuc_range_rel: &NucAlnLocalRange
 /// Find ending nucleotide position of a deletion that immediately follows and adjacent to the frame shift
 pub fn find_mask_end(seq: &[Nuc], frame_shift_nuc_range_rel: &NucAlnLocalRange) -> NucAlnLocalPosition {
   // From end, rewind backwards to find the last adjacent nuc deletion
+  let seq_end = NucAlnLocalPosition::from(seq.len());
   let mut end = frame_shift_nuc_range_rel.end;
-  while end < seq.len() as isize && seq[end.as_usize()].is_gap() {
+  while end < seq_end && seq[end.as_usize()].is_gap() {
     end += 1;
   }
 
@@ -66,6 +68,13 @@ pub struct FrameShift {
   pub codon: AaRefRange,
   pub gaps_leading: AaRefRange,
   pub gaps_trailing: AaRefRange,
+  /// How the frame-shifted region was translated
+  #[serde(default)]
+  pub policy: FrameShiftPolicy,
+  /// Whether the reading frame is restored within a short distance (see `--compensated-frame-shift-max-length`), e.g.
+  /// by an insertion followed by a deletion, such that the rest of the CDS is translated in the original frame
+  #[serde(default)]
+  pub is_compensated: bool,
 }
 
 pub fn frame_shift_transform(
@@ -73,6 +82,7 @@ pub fn frame_shift_transform(
   query: &[Nuc],
   coord_map_local: &CoordMapLocal,
   cds: &Cds,
+  params: &AlignPairwiseParams,
 ) -> Result<FrameShift, Report> {
   let codon = coord_map_local.local_to_codon_ref_range(nuc_aln_local);
 
@@ -89,6 +99,10 @@ pub fn frame_shift_transform(
   let gaps_leading = Range::new(codon_mask.begin, codon.begin);
   let gaps_trailing = Range::new(codon.end, codon_mask.end);
 
+  // Frame shifts which are not compensated extend until the end of the CDS
+  let is_compensated = nuc_aln_local.end < NucAlnLocalPosition::from(query.len())
+    && nuc_aln_local.len() <= params.compensated_frame_shift_max_length;
+
   Ok(FrameShift {
     gene_name: cds.name.clone(),
     nuc_abs: nuc_ref_global,
@@ -96,6 +110,8 @@ pub fn frame_shift_transform(
     nuc_rel: nuc_aln_local.clone(),
     gaps_leading,
     gaps_trailing,
+    policy: params.frame_shift_policy,
+    is_compensated,
   })
 }
 
@@ -106,9 +122,10 @@ pub fn frame_shifts_transform_coordinates(
   query: &[Nuc],
   coord_map_local: &CoordMapLocal,
   cds: &Cds,
+  params: &AlignPairwiseParams,
 ) -> Result<Vec<FrameShift>, Report> {
   nuc_rel_frame_shifts
     .iter()
-    .map(|fs_nuc_rel_aln| frame_shift_transform(fs_nuc_rel_aln, query, coord_map_local, cds))
+    .map(|fs_nuc_rel_aln| frame_shift_transform(fs_nuc_rel_aln, query, coord_map_local, cds, params))
     .collect()
 }
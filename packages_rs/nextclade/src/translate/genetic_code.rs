@@ -0,0 +1,269 @@
+use crate::alphabet::aa::{to_aa, Aa};
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::{nuc_to_bases, Nuc};
+use crate::gene::gene_map::GeneMap;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// NCBI translation table number of the standard genetic code
+pub const STANDARD_TRANSL_TABLE: u8 = 1;
+
+/// Genetic code, as defined by NCBI translation tables.
+///
+/// Amino acids are listed for all 64 codons, in the order used by NCBI: bases of each codon position iterate
+/// in `T, C, A, G` order, with the first position changing the slowest.
+///
+/// See: https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi
+#[derive(Clone, Debug)]
+pub struct GeneticCode {
+  pub transl_table: u8,
+  pub name: &'static str,
+  pub amino_acids: &'static [u8; 64],
+}
+
+#[rustfmt::skip]
+const GENETIC_CODES: &[GeneticCode] = &[
+  GeneticCode { transl_table: 1, name: "Standard", amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 2, name: "Vertebrate Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 3, name: "Yeast Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 4, name: "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 5, name: "Invertebrate Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 6, name: "Ciliate, Dasycladacean and Hexamita Nuclear", amino_acids: b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 9, name: "Echinoderm and Flatworm Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 10, name: "Euplotid Nuclear", amino_acids: b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 11, name: "Bacterial, Archaeal and Plant Plastid", amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 12, name: "Alternative Yeast Nuclear", amino_acids: b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 13, name: "Ascidian Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 14, name: "Alternative Flatworm Mitochondrial", amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 16, name: "Chlorophycean Mitochondrial", amino_acids: b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 21, name: "Trematode Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 22, name: "Scenedesmus obliquus Mitochondrial", amino_acids: b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 23, name: "Thraustochytrium Mitochondrial", amino_acids: b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 24, name: "Rhabdopleuridae Mitochondrial", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 25, name: "Candidate Division SR1 and Gracilibacteria", amino_acids: b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 26, name: "Pachysolen tannophilus Nuclear", amino_acids: b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 29, name: "Mesodinium Nuclear", amino_acids: b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 30, name: "Peritrich Nuclear", amino_acids: b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG" },
+  GeneticCode { transl_table: 33, name: "Cephalodiscidae Mitochondrial", amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG" },
+];
+
+impl GeneticCode {
+  /// The standard genetic code (translation table 1)
+  pub const fn standard() -> &'static Self {
+    &GENETIC_CODES[0]
+  }
+
+  pub fn from_transl_table(transl_table: u8) -> Result<&'static Self, Report> {
+    GENETIC_CODES
+      .iter()
+      .find(|code| code.transl_table == transl_table)
+      .map_or_else(
+        || {
+          let supported = GENETIC_CODES.iter().map(|code| code.transl_table).join(", ");
+          make_error!("Unknown or unsupported translation table: {transl_table}. Supported tables are: {supported}")
+        },
+        Ok,
+      )
+  }
+
+  /// Translates a codon. Ambiguous nucleotides are resolved if all the codons they represent encode the same
+  /// aminoacid, otherwise the result is `X`.
+  pub fn decode(&self, triplet: &[Nuc]) -> Aa {
+    match *triplet {
+      [Nuc::Gap, Nuc::Gap, Nuc::Gap] => Aa::Gap,
+      [n1, n2, n3] => {
+        let mut result = None;
+        for &b1 in codon_bases(n1) {
+          for &b2 in codon_bases(n2) {
+            for &b3 in codon_bases(n3) {
+              let aa = self.decode_canonical(b1, b2, b3);
+              if result.is_some_and(|prev| prev != aa) {
+                return Aa::X;
+              }
+              result = Some(aa);
+            }
+          }
+        }
+        result.unwrap_or(Aa::X)
+      }
+      _ => Aa::X,
+    }
+  }
+
+  /// Translates a codon, same as `decode()`, except that codons encoding one of two aminoacids sharing an IUPAC code
+  /// are translated into this code: `B` (`N` or `D`), `Z` (`Q` or `E`) and `J` (`I` or `L`)
+  pub fn decode_partial(&self, triplet: &[Nuc]) -> Aa {
+    let [n1, n2, n3] = *triplet else {
+      return Aa::X;
+    };
+
+    if triplet.iter().any(Nuc::is_gap) {
+      return self.decode(triplet);
+    }
+
+    // Up to two distinct aminoacids are tracked: any more cannot be represented by a single IUPAC code
+    let mut aas: [Option<Aa>; 2] = [None, None];
+    for &b1 in codon_bases(n1) {
+      for &b2 in codon_bases(n2) {
+        for &b3 in codon_bases(n3) {
+          let aa = self.decode_canonical(b1, b2, b3);
+          match aas {
+            [None, _] => aas[0] = Some(aa),
+            [Some(first), _] if first == aa => {}
+            [_, None] => aas[1] = Some(aa),
+            [_, Some(second)] if second == aa => {}
+            _ => return Aa::X,
+          }
+        }
+      }
+    }
+
+    match aas {
+      [Some(aa), None] => aa,
+      [Some(first), Some(second)] => match (first.min(second), first.max(second)) {
+        (Aa::D, Aa::N) => Aa::B,
+        (Aa::E, Aa::Q) => Aa::Z,
+        (Aa::I, Aa::L) => Aa::J,
+        _ => Aa::X,
+      },
+      _ => Aa::X,
+    }
+  }
+
+  fn decode_canonical(&self, b1: Nuc, b2: Nuc, b3: Nuc) -> Aa {
+    let index = codon_base_index(b1) * 16 + codon_base_index(b2) * 4 + codon_base_index(b3);
+    to_aa(char::from(self.amino_acids[index])).unwrap_or(Aa::X)
+  }
+}
+
+/// Canonical bases represented by a nucleotide in a codon. Unlike in consensus calculation, `N` stands for any base.
+const fn codon_bases(nuc: Nuc) -> &'static [Nuc] {
+  match nuc {
+    Nuc::N => &[Nuc::A, Nuc::C, Nuc::G, Nuc::T],
+    _ => nuc_to_bases(nuc),
+  }
+}
+
+/// Index of a canonical base in the NCBI codon order (T, C, A, G)
+const fn codon_base_index(nuc: Nuc) -> usize {
+  match nuc {
+    Nuc::T => 0,
+    Nuc::C => 1,
+    Nuc::A => 2,
+    _ => 3,
+  }
+}
+
+/// Parses the value of `transl_table` attribute of genome annotation
+pub fn parse_transl_table(value: &str) -> Result<u8, Report> {
+  let transl_table = value
+    .trim()
+    .parse::<u8>()
+    .wrap_err_with(|| format!("Expected translation table to be a positive integer, but found: '{value}'"))?;
+  GeneticCode::from_transl_table(transl_table)?;
+  Ok(transl_table)
+}
+
+/// Sets translation tables of CDSes, according to the overrides provided in pathogen.json.
+///
+/// Overrides for individual CDSes take precedence over the override for all CDSes, which takes precedence over the
+/// `transl_table` attributes in genome annotation.
+pub fn gene_map_override_transl_tables(
+  gene_map: &mut GeneMap,
+  transl_table: Option<u8>,
+  cds_transl_tables: &BTreeMap<String, u8>,
+) -> Result<(), Report> {
+  if let Some(transl_table) = transl_table {
+    GeneticCode::from_transl_table(transl_table)?;
+  }
+
+  for (cds_name, &cds_transl_table) in cds_transl_tables {
+    GeneticCode::from_transl_table(cds_transl_table)
+      .wrap_err_with(|| format!("When reading translation table override for CDS '{cds_name}'"))?;
+  }
+
+  for cds in gene_map.iter_cdses_mut() {
+    if let Some(&cds_transl_table) = cds_transl_tables.get(&cds.name) {
+      cds.transl_table = Some(cds_transl_table);
+    } else if transl_table.is_some() {
+      cds.transl_table = transl_table;
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::from_aa_seq;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::translate::translate::decode;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn translate_with(transl_table: u8, seq: &str) -> Result<String, Report> {
+    let code = GeneticCode::from_transl_table(transl_table)?;
+    let nucs = to_nuc_seq(seq)?;
+    Ok(from_aa_seq(
+      &nucs.chunks_exact(3).map(|triplet| code.decode(triplet)).collect_vec(),
+    ))
+  }
+
+  #[rstest]
+  fn standard_genetic_code_agrees_with_builtin_decoder() -> Result<(), Report> {
+    let code = GeneticCode::from_transl_table(STANDARD_TRANSL_TABLE)?;
+    let bases = [Nuc::T, Nuc::C, Nuc::A, Nuc::G];
+    for &b1 in &bases {
+      for &b2 in &bases {
+        for &b3 in &bases {
+          assert_eq!(code.decode(&[b1, b2, b3]), decode(&[b1, b2, b3]));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  #[rstest]
+  fn translates_with_alternative_genetic_codes() -> Result<(), Report> {
+    assert_eq!(translate_with(1, "ATGTGAATAAGAAGG---")?, "M*IRR-");
+    assert_eq!(translate_with(2, "ATGTGAATAAGAAGG---")?, "MWM**-");
+    assert_eq!(translate_with(5, "ATGTGAATAAGAAGG---")?, "MWMSS-");
+    assert_eq!(translate_with(11, "ATGTGAATAAGAAGG---")?, "M*IRR-");
+    Ok(())
+  }
+
+  #[rstest]
+  fn resolves_ambiguous_codons() -> Result<(), Report> {
+    assert_eq!(translate_with(2, "TGRAGRATRCTN")?, "W*ML");
+    assert_eq!(translate_with(1, "TGRAGRATR")?, "XRX");
+    Ok(())
+  }
+
+  #[rstest]
+  fn partially_resolves_ambiguous_codons() -> Result<(), Report> {
+    let code = GeneticCode::from_transl_table(STANDARD_TRANSL_TABLE)?;
+    let nucs = to_nuc_seq("RATSARMTTCTNNNNAT-")?;
+    let aas = nucs
+      .chunks_exact(3)
+      .map(|triplet| code.decode_partial(triplet))
+      .collect_vec();
+    assert_eq!(from_aa_seq(&aas), "BZJLXX");
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_unknown_translation_table() {
+    assert_eq!(
+      report_to_string(&GeneticCode::from_transl_table(7).unwrap_err()),
+      "Unknown or unsupported translation table: 7. Supported tables are: 1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 16, 21, 22, 23, 24, 25, 26, 29, 30, 33"
+    );
+    assert_eq!(
+      report_to_string(&parse_transl_table("abc").unwrap_err()),
+      "Expected translation table to be a positive integer, but found: 'abc': invalid digit found in string"
+    );
+  }
+}
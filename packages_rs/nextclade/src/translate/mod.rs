@@ -1,9 +1,12 @@
 pub mod aa_alignment_ranges;
+pub mod codon_alignment;
 pub mod complement;
 pub mod extract;
 pub mod frame_shifts_detect;
 pub mod frame_shifts_flatten;
 pub mod frame_shifts_translate;
+pub mod genetic_code;
 pub mod translate;
 pub mod translate_genes;
 pub mod translate_genes_ref;
+pub mod translation_cache;
@@ -1,8 +1,10 @@
-use crate::align::params::AlignPairwiseParams;
+use crate::align::params::{AlignPairwiseParams, AmbiguousCodonPolicy};
 use crate::alphabet::aa::Aa;
-use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq};
+use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq, Letter};
 use crate::alphabet::nuc::Nuc;
 use crate::gene::cds::Cds;
+use crate::translate::genetic_code::{GeneticCode, STANDARD_TRANSL_TABLE};
+use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 
 pub const fn decode(triplet: &[Nuc]) -> Aa {
@@ -199,25 +201,127 @@ pub struct CdsPeptide {
   pub seq: Vec<Aa>,
 }
 
-/// Translates a nucleotide sequence of a gene into the corresponding aminoacid sequence (peptide)
-pub fn translate(gene_nuc_seq: &[Nuc], cds: &Cds, params: &AlignPairwiseParams) -> CdsPeptide {
+/// Translates a nucleotide sequence of a gene into the corresponding aminoacid sequence (peptide). Stop codons at the
+/// codon indices listed in `recoded_stops` do not terminate translation.
+pub fn translate(
+  gene_nuc_seq: &[Nuc],
+  cds: &Cds,
+  recoded_stops: &[usize],
+  params: &AlignPairwiseParams,
+) -> Result<CdsPeptide, Report> {
   // NOTE: rounds the result to the multiple of 3 (floor) so that translation does not overrun the buffer
   let peptide_length = gene_nuc_seq.len() / 3;
 
+  // NOTE: the standard code uses the built-in decoder
+  let genetic_code = cds
+    .transl_table
+    .filter(|&transl_table| transl_table != STANDARD_TRANSL_TABLE)
+    .map(GeneticCode::from_transl_table)
+    .transpose()
+    .wrap_err_with(|| format!("When translating CDS '{}'", cds.name))?;
+
+  // NOTE: the built-in decoder does not resolve ambiguous codons partially, so the standard code is used explicitly
+  let genetic_code = if params.ambiguous_codons == AmbiguousCodonPolicy::ResolvePartial {
+    Some(genetic_code.unwrap_or_else(GeneticCode::standard))
+  } else {
+    genetic_code
+  };
+
   let mut peptide = Vec::<Aa>::with_capacity(peptide_length);
   for i_aa in 0..peptide_length {
     let i_nuc = i_aa * 3;
     let triplet: &[Nuc] = &gene_nuc_seq[i_nuc..(i_nuc + 3)];
-    let aminoacid = decode(triplet);
+    let is_ambiguous = triplet.iter().any(|nuc| !nuc.is_acgt() && !nuc.is_gap());
+    let aminoacid = match (params.ambiguous_codons, genetic_code) {
+      (AmbiguousCodonPolicy::Unknown, _) if is_ambiguous => Aa::X,
+      (AmbiguousCodonPolicy::ResolvePartial, Some(genetic_code)) => genetic_code.decode_partial(triplet),
+      (_, None) => decode(triplet),
+      (_, Some(genetic_code)) => genetic_code.decode(triplet),
+    };
     peptide.push(aminoacid);
-    if params.no_translate_past_stop && aminoacid == Aa::Stop {
+
+    // Stop codons recoded into selenocysteine or pyrrolysine do not terminate translation
+    if params.no_translate_past_stop && aminoacid == Aa::Stop && !recoded_stops.contains(&i_aa) {
       break;
     }
   }
   peptide.shrink_to_fit();
 
-  CdsPeptide {
+  Ok(CdsPeptide {
     name: cds.name.clone(),
     seq: peptide,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::from_aa_seq;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::gene::cds_partial::CdsPartial;
+  use crate::gene::transl_except::{transl_exceptions_find_recoded_stops, TranslException};
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use itertools::Itertools;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::collections::HashMap;
+  use std::sync::OnceLock;
+
+  fn create_cds(transl_except: Vec<TranslException>) -> Cds {
+    Cds {
+      id: o!("S"),
+      name: o!("S"),
+      product: String::new(),
+      segments: vec![],
+      proteins: vec![],
+      exceptions: vec![],
+      attributes: HashMap::new(),
+      compat_is_gene: false,
+      color: None,
+      transl_table: None,
+      ribosomal_slippage: None,
+      partial: CdsPartial::default(),
+      transl_except,
+      name_interned: OnceLock::new(),
+    }
+  }
+
+  #[rstest]
+  #[case::no_indels("ATGCCCTGAAAATAA", "ATGCCCTGAAAATAA", "MP*K*")]
+  #[case::upstream_deletion("ATGCCCTGAAAATAA", "ATG---TGAAAATAA", "M*K*")]
+  #[case::upstream_insertion("ATG---CCCTGAAAATAA", "ATGGGGCCCTGAAAATAA", "MGP*K*")]
+  fn translates_past_recoded_stop_codon(
+    #[case] ref_seq: &str,
+    #[case] qry_seq: &str,
+    #[case] expected: &str,
+  ) -> Result<(), Report> {
+    let cds = create_cds(vec![TranslException { codon: 2, aa: Aa::U }]);
+    let params = AlignPairwiseParams {
+      no_translate_past_stop: true,
+      ..AlignPairwiseParams::default()
+    };
+
+    let qry_seq = to_nuc_seq(qry_seq)?;
+    let recoded_stops = transl_exceptions_find_recoded_stops(&cds.transl_except, &to_nuc_seq(ref_seq)?, &qry_seq);
+    let qry_seq = qry_seq.into_iter().filter(|nuc| !nuc.is_gap()).collect_vec();
+
+    let peptide = translate(&qry_seq, &cds, &recoded_stops, &params)?;
+    assert_eq!(from_aa_seq(&peptide.seq), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  fn fails_to_translate_with_unknown_translation_table() -> Result<(), Report> {
+    let cds = Cds {
+      transl_table: Some(7),
+      ..create_cds(vec![])
+    };
+    let result = translate(&to_nuc_seq("ATGTAA")?, &cds, &[], &AlignPairwiseParams::default());
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "When translating CDS 'S': Unknown or unsupported translation table: 7. Supported tables are: 1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 16, 21, 22, 23, 24, 25, 26, 29, 30, 33"
+    );
+    Ok(())
   }
 }
@@ -1,6 +1,7 @@
 use crate::align::align::align_aa;
+use crate::align::backtrace::AlignmentOutput;
 use crate::align::insertions_strip::{insertions_strip, Insertion};
-use crate::align::params::AlignPairwiseParams;
+use crate::align::params::{AlignPairwiseParams, FrameShiftPolicy, PeptideReliabilityThresholds};
 use crate::align::remove_gaps::remove_gaps_in_place;
 use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq, Letter};
@@ -13,14 +14,18 @@ use crate::coord::range::{AaRefRange, NucRefGlobalRange, Range};
 use crate::gene::cds::Cds;
 use crate::gene::gene::Gene;
 use crate::gene::gene_map::GeneMap;
+use crate::gene::transl_except::{transl_exceptions_apply_in_place, transl_exceptions_find_recoded_stops};
 use crate::translate::aa_alignment_ranges::calculate_aa_alignment_ranges_in_place;
+use crate::translate::codon_alignment::{codon_align, codon_alignment_mask_in_place};
 use crate::translate::extract::extract_cds_from_aln;
 use crate::translate::frame_shifts_detect::frame_shifts_detect;
 use crate::translate::frame_shifts_translate::{frame_shifts_transform_coordinates, FrameShift};
 use crate::translate::translate::translate;
+use crate::translate::translation_cache::{CdsTranslationCache, CdsTranslationKey};
 use crate::types::outputs::PeptideWarning;
 use crate::utils::collections::{first, last};
 use crate::utils::error::report_to_string;
+use crate::utils::progress::{report_progress, ProgressEvent, PROGRESS_MIN_SEQ_LEN};
 use crate::{make_error, make_internal_report};
 use eyre::Report;
 use indexmap::IndexMap;
@@ -28,6 +33,7 @@ use itertools::Itertools;
 use num_traits::clamp_max;
 use rayon::iter::Either;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -136,6 +142,14 @@ pub struct CdsTranslation {
   pub frame_shifts: Vec<FrameShift>,
   pub alignment_ranges: Vec<AaRefRange>,
   pub unsequenced_ranges: Vec<AaRefRange>,
+
+  /// Nucleotide sequence of the CDS, aligned codon by codon to the reference CDS (see `codon_align()`)
+  #[serde(skip)]
+  pub codon_alignment: Vec<Nuc>,
+
+  /// Identifies the translation in the `CdsTranslationCache`, if the cache is in use
+  #[serde(skip)]
+  pub cache_key: Option<CdsTranslationKey>,
 }
 
 /// Results of the aminoacid alignment parameters estimation
@@ -219,6 +233,18 @@ pub fn mask_peptide_frame_shifts_in_place(seq: &mut [Aa], frame_shifts: &[FrameS
   }
 }
 
+/// Masks the peptide with `X` from the beginning of the first frame shift to the end, such that aminoacid changes past
+/// the frame shift are not reported
+pub fn truncate_peptide_at_frame_shift_in_place(seq: &mut [Aa], frame_shifts: &[FrameShift]) {
+  if let Some(begin) = frame_shifts
+    .iter()
+    .map(|frame_shift| frame_shift.gaps_leading.begin)
+    .min()
+  {
+    fill_range_inplace(seq, &AaRefRange::new(begin, seq.len().into()), Aa::X);
+  }
+}
+
 pub fn translate_cds(
   qry_seq: &[Nuc],
   ref_seq: &[Nuc],
@@ -227,10 +253,43 @@ pub fn translate_cds(
   gap_open_close_aa: &[i32],
   coord_map_global: &CoordMapGlobal,
   params: &AlignPairwiseParams,
+  cache: Option<&CdsTranslationCache>,
 ) -> Result<CdsTranslation, Report> {
-  let mut ref_cds_seq = extract_cds_from_aln(ref_seq, cds, coord_map_global);
-  let mut qry_cds_seq = extract_cds_from_aln(qry_seq, cds, coord_map_global);
+  let ref_cds_seq = extract_cds_from_aln(ref_seq, cds, coord_map_global);
+  let qry_cds_seq = extract_cds_from_aln(qry_seq, cds, coord_map_global);
+
+  let Some(cache) = cache else {
+    return translate_cds_seq(ref_cds_seq, qry_cds_seq, cds, ref_cds_translation, gap_open_close_aa, params);
+  };
 
+  let key = CdsTranslationKey::new(&cds.name, &ref_cds_seq, &qry_cds_seq);
+  if let Some(translation) = cache.get(&key) {
+    return Ok(CdsTranslation::clone(&translation));
+  }
+
+  let mut translation = translate_cds_seq(
+    ref_cds_seq,
+    qry_cds_seq,
+    cds,
+    ref_cds_translation,
+    gap_open_close_aa,
+    params,
+  )?;
+  translation.cache_key = Some(key);
+  cache.insert(key, Arc::new(translation.clone()));
+  Ok(translation)
+}
+
+/// Translates and aligns a CDS, given the aligned nucleotide sequences of the CDS, extracted from the query and from
+/// the reference
+fn translate_cds_seq(
+  mut ref_cds_seq: Vec<Nuc>,
+  mut qry_cds_seq: Vec<Nuc>,
+  cds: &Cds,
+  ref_cds_translation: &CdsTranslation,
+  gap_open_close_aa: &[i32],
+  params: &AlignPairwiseParams,
+) -> Result<CdsTranslation, Report> {
   // Coordinate map local to this CDS
   let coord_map_local = CoordMapLocal::new(&ref_cds_seq);
 
@@ -246,10 +305,11 @@ pub fn translate_cds(
 
   // If start and end nucs of qry are gaps, don't penalize them in alignment
   // TODO: Think about qry insertions, they will also be free?
+  // Ends of partial CDS are truncated at the edge of the sequence, so the query is likely to be incomplete there too.
   let aa_params = AlignPairwiseParams {
     // Set to false for internal genes
-    left_terminal_gaps_free: first(&qry_cds_seq)?.is_gap(),
-    right_terminal_gaps_free: last(&qry_cds_seq)?.is_gap(),
+    left_terminal_gaps_free: first(&qry_cds_seq)?.is_gap() || cds.partial.missing_start,
+    right_terminal_gaps_free: last(&qry_cds_seq)?.is_gap() || cds.partial.missing_stop,
     ..params.clone()
   };
 
@@ -259,14 +319,20 @@ pub fn translate_cds(
 
   // NOTE: frame shift detection should be performed on unstripped genes
   let nuc_rel_frame_shifts = frame_shifts_detect(&qry_cds_seq, &ref_cds_seq);
-  let frame_shifts = frame_shifts_transform_coordinates(&nuc_rel_frame_shifts, &qry_cds_seq, &coord_map_local, cds)?;
+  let frame_shifts =
+    frame_shifts_transform_coordinates(&nuc_rel_frame_shifts, &qry_cds_seq, &coord_map_local, cds, params)?;
 
-  mask_nuc_frame_shifts_in_place(&mut qry_cds_seq, &frame_shifts);
+  if params.frame_shift_policy != FrameShiftPolicy::TranslateThrough {
+    mask_nuc_frame_shifts_in_place(&mut qry_cds_seq, &frame_shifts);
+  }
+
+  // Exception sites are in reference codons, so they are mapped to the query while it is still aligned
+  let recoded_stops = transl_exceptions_find_recoded_stops(&cds.transl_except, &ref_cds_seq, &qry_cds_seq);
 
   // Strip all GAP characters to "forget" gaps introduced during alignment
   remove_gaps_in_place(&mut qry_cds_seq);
 
-  let query_peptide = translate(&qry_cds_seq, cds, params);
+  let query_peptide = translate(&qry_cds_seq, cds, &recoded_stops, params)?;
 
   // Instead of performing seed matching, like we do for nucleotide alignment, here we estimate parameters
   // by counting gaps in the aligned nucleotide sequences;
@@ -279,11 +345,25 @@ pub fn translate_cds(
     &aa_params,
     band_width,
     mean_shift,
-  );
+  )?;
+
+  check_peptide_alignment_reliability(&alignment, cds, params)?;
 
   let mut stripped = insertions_strip(&alignment.qry_seq, &alignment.ref_seq);
 
-  mask_peptide_frame_shifts_in_place(&mut stripped.qry_seq, &frame_shifts);
+  let mut codon_alignment = codon_align(&qry_cds_seq, &alignment.qry_seq, &alignment.ref_seq);
+  let peptide_unmasked = stripped.qry_seq.clone();
+
+  match params.frame_shift_policy {
+    FrameShiftPolicy::Mask => mask_peptide_frame_shifts_in_place(&mut stripped.qry_seq, &frame_shifts),
+    FrameShiftPolicy::TranslateThrough => {}
+    FrameShiftPolicy::Truncate => truncate_peptide_at_frame_shift_in_place(&mut stripped.qry_seq, &frame_shifts),
+  }
+
+  codon_alignment_mask_in_place(&mut codon_alignment, &peptide_unmasked, &stripped.qry_seq);
+
+  // Query peptide is now in reference coordinates, so that the exception sites of the CDS can be applied
+  transl_exceptions_apply_in_place(&mut stripped.qry_seq, &cds.transl_except);
 
   Ok(CdsTranslation {
     name: cds.name.clone(),
@@ -292,9 +372,70 @@ pub fn translate_cds(
     frame_shifts,
     alignment_ranges: vec![],
     unsequenced_ranges: vec![],
+    codon_alignment,
+    cache_key: None,
   })
 }
 
+/// Checks that the peptide alignment of the CDS passes the reliability thresholds. Aminoacid changes derived from an
+/// unreliable alignment are misleading, so an error is returned instead, which is reported as a warning for this CDS.
+fn check_peptide_alignment_reliability(
+  alignment: &AlignmentOutput<Aa>,
+  cds: &Cds,
+  params: &AlignPairwiseParams,
+) -> Result<(), Report> {
+  let PeptideReliabilityThresholds {
+    max_gap_fraction,
+    max_unknown_fraction,
+    min_score_per_aa,
+  } = params.peptide_reliability_thresholds(&cds.name);
+
+  // Only the aligned part of the query is considered, such that partially sequenced CDSes are not penalized
+  let (Some(begin), Some(last)) = (
+    alignment.qry_seq.iter().position(|aa| !aa.is_gap()),
+    alignment.qry_seq.iter().rposition(|aa| !aa.is_gap()),
+  ) else {
+    return Ok(());
+  };
+  let aligned = &alignment.qry_seq[begin..=last];
+
+  let n_gaps = aligned.iter().filter(|aa| aa.is_gap()).count();
+  let n_unknown = aligned.iter().filter(|aa| aa.is_unknown()).count();
+  let n_aa = aligned.len() - n_gaps;
+
+  let gap_fraction = n_gaps as f64 / aligned.len() as f64;
+  if let Some(max_gap_fraction) = max_gap_fraction {
+    if gap_fraction > max_gap_fraction {
+      return make_error!(
+        "When processing gene \"{}\": Peptide alignment is unreliable: fraction of gaps {gap_fraction:.3} is higher than the threshold {max_gap_fraction}. Aminoacid changes in this gene are not reported.",
+        cds.name
+      );
+    }
+  }
+
+  let unknown_fraction = n_unknown as f64 / n_aa as f64;
+  if let Some(max_unknown_fraction) = max_unknown_fraction {
+    if unknown_fraction > max_unknown_fraction {
+      return make_error!(
+        "When processing gene \"{}\": Peptide alignment is unreliable: fraction of unknown aminoacids {unknown_fraction:.3} is higher than the threshold {max_unknown_fraction}. Aminoacid changes in this gene are not reported.",
+        cds.name
+      );
+    }
+  }
+
+  let score_per_aa = alignment.alignment_score as f64 / n_aa as f64;
+  if let Some(min_score_per_aa) = min_score_per_aa {
+    if score_per_aa < min_score_per_aa {
+      return make_error!(
+        "When processing gene \"{}\": Peptide alignment is unreliable: alignment score per aminoacid {score_per_aa:.3} is lower than the threshold {min_score_per_aa}. Aminoacid changes in this gene are not reported.",
+        cds.name
+      );
+    }
+  }
+
+  Ok(())
+}
+
 /// Translates all requested genes
 ///
 /// NOTE: we handle translation errors as warnings, so we return a collection of `Results` as is, to handle elsewhere
@@ -307,26 +448,49 @@ pub fn translate_genes(
   global_alignment_range: &NucRefGlobalRange,
   gap_open_close_aa: &[i32],
   params: &AlignPairwiseParams,
+  cache: Option<&CdsTranslationCache>,
 ) -> Result<Translation, Report> {
+  let mut cdses = vec![];
+  for gene in gene_map.iter_genes() {
+    let ref_gene_translation = ref_peptides.get_gene(&gene.name)?;
+    for cds in &gene.cdses {
+      cdses.push((cds, ref_gene_translation.get_cds(&cds.name)?));
+    }
+  }
+
+  // NOTE: CDSes are translated sequentially. Sequences are already processed in parallel, one per worker thread, so
+  // parallelizing within a sequence would only compete with the other workers for the same cores.
+  let n_cdses_total = cdses.len();
+  let mut results = Vec::with_capacity(n_cdses_total);
+  for (cds, ref_cds_translation) in cdses {
+    results.push(translate_cds(
+      qry_seq,
+      ref_seq,
+      cds,
+      ref_cds_translation,
+      gap_open_close_aa,
+      coord_map_global,
+      params,
+      cache,
+    ));
+
+    if ref_seq.len() >= PROGRESS_MIN_SEQ_LEN {
+      report_progress(|| ProgressEvent::CdsTranslated {
+        cds_name: cds.name.clone(),
+        n_done: results.len(),
+        n_total: n_cdses_total,
+      });
+    }
+  }
+
+  let mut results = results.into_iter();
   let genes: IndexMap<String, GeneTranslation> = gene_map
     .iter_genes()
     .map(|gene| {
-      let ref_gene_translation = ref_peptides.get_gene(&gene.name)?;
-
       let (cdses, warnings): (IndexMap<String, CdsTranslation>, Vec<PeptideWarning>) =
-        gene.cdses.iter().partition_map(|cds| {
-          let ref_cds_translation = ref_gene_translation.get_cds(&cds.name).unwrap();
-
+        gene.cdses.iter().zip(&mut results).partition_map(|(cds, result)| {
           // Treat translation errors as warnings
-          match translate_cds(
-            qry_seq,
-            ref_seq,
-            cds,
-            ref_cds_translation,
-            gap_open_close_aa,
-            coord_map_global,
-            params,
-          ) {
+          match result {
             Ok(translation) => Either::Left((cds.name.clone(), translation)),
             Err(report) => Either::Right(PeptideWarning {
               gene_name: cds.name.clone(),
@@ -335,16 +499,16 @@ pub fn translate_genes(
           }
         });
 
-      Ok((
+      (
         gene.name.clone(),
         GeneTranslation {
           gene: gene.clone(),
           cdses,
           warnings,
         },
-      ))
+      )
     })
-    .collect::<Result<IndexMap<String, GeneTranslation>, Report>>()?;
+    .collect();
 
   let mut translation = Translation { genes };
 
@@ -353,3 +517,73 @@ pub fn translate_genes(
 
   Ok(translation)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::align::gap_open::get_gap_open_close_scores_flat;
+  use crate::alphabet::aa::from_aa_seq;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::translate::translate_genes_ref::translate_genes_ref;
+  use pretty_assertions::assert_eq;
+
+  // Circular genome of length 30, with a CDS starting at position 21 and wrapping around the origin to position 9
+  const GFF_WRAPPING: &str = "##gff-version 3
+##sequence-region circ 1 30
+circ\tRefSeq\tregion\t1\t30\t.\t+\t.\tID=circ;Is_circular=true
+circ\tRefSeq\tgene\t22\t39\t.\t+\t.\tName=W;ID=gene-W
+circ\tRefSeq\tCDS\t22\t39\t.\t+\t.\tName=W;Parent=gene-W;ID=cds-W
+";
+
+  const REF_SEQ: &str = "GGGTTTTAAACGTACGTACGTATGAAACCC";
+
+  fn translate_wrapping(qry_seq: &str) -> Result<CdsTranslation, Report> {
+    let gene_map = GeneMap::from_str(GFF_WRAPPING)?;
+    let params = AlignPairwiseParams::default();
+    let ref_seq = to_nuc_seq(REF_SEQ)?;
+    let qry_seq = to_nuc_seq(qry_seq)?;
+    let ref_peptides = translate_genes_ref(&ref_seq, &gene_map, &params)?;
+    let translation = translate_genes(
+      &qry_seq,
+      &ref_seq,
+      &ref_peptides,
+      &gene_map,
+      &CoordMapGlobal::new(&ref_seq),
+      &NucRefGlobalRange::from_usize(0, ref_seq.len()),
+      &get_gap_open_close_scores_flat(&ref_seq, &params),
+      &params,
+      None,
+    )?;
+    Ok(translation.get_cds("W")?.clone())
+  }
+
+  #[test]
+  fn translates_cds_wrapping_around_origin() -> Result<(), Report> {
+    let translation = translate_wrapping("GGGTTCTAAACGTACGTACGTATGAAACCC")?;
+    assert_eq!(from_aa_seq(&translation.seq), "MKPGF*");
+    assert!(translation.frame_shifts.is_empty());
+    // The sequenced part of the CDS is reported as one range, even though it is split at the origin
+    assert_eq!(translation.alignment_ranges, vec![AaRefRange::from_usize(0, 6)]);
+    assert_eq!(translation.unsequenced_ranges, vec![]);
+    Ok(())
+  }
+
+  #[test]
+  fn translates_frame_shift_in_cds_wrapping_around_origin() -> Result<(), Report> {
+    // Deletion of 1 nucleotide at position 27, before the origin, shifts the frame of the following nucleotides, until
+    // the end of the CDS
+    let translation = translate_wrapping("GGGTTTTAAACGTACGTACGTATGAAA-CC")?;
+    assert_eq!(translation.frame_shifts.len(), 1);
+    let frame_shift = &translation.frame_shifts[0];
+    // Nucleotide range of the frame shift continues across the origin
+    assert_eq!(
+      frame_shift.nuc_abs,
+      vec![
+        NucRefGlobalRange::from_usize(28, 30),
+        NucRefGlobalRange::from_usize(0, 9)
+      ]
+    );
+    assert_eq!(frame_shift.codon, AaRefRange::from_usize(3, 6));
+    Ok(())
+  }
+}
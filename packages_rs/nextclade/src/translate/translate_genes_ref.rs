@@ -2,6 +2,7 @@ use crate::align::params::AlignPairwiseParams;
 use crate::alphabet::nuc::Nuc;
 use crate::coord::range::Range;
 use crate::gene::gene_map::GeneMap;
+use crate::gene::transl_except::{transl_exceptions_apply_in_place, transl_exceptions_find_recoded_stops};
 use crate::translate::extract::extract_cds_from_ref;
 use crate::translate::translate::translate;
 use crate::translate::translate_genes::{CdsTranslation, GeneTranslation, Translation};
@@ -21,10 +22,12 @@ pub fn translate_genes_ref(
         .iter()
         .map(|cds| {
           let nucs = extract_cds_from_ref(ref_seq, cds);
-          let tr = translate(&nucs, cds, params);
+          let recoded_stops = transl_exceptions_find_recoded_stops(&cds.transl_except, &nucs, &nucs);
+          let mut tr = translate(&nucs, cds, &recoded_stops, params)?;
+          transl_exceptions_apply_in_place(&mut tr.seq, &cds.transl_except);
           let len = tr.seq.len();
 
-          (
+          Ok((
             cds.name.clone(),
             CdsTranslation {
               name: cds.name.clone(),
@@ -33,21 +36,23 @@ pub fn translate_genes_ref(
               frame_shifts: vec![],
               alignment_ranges: vec![Range::from_usize(0, len)],
               unsequenced_ranges: vec![],
+              codon_alignment: nucs[..len * 3].to_vec(),
+              cache_key: None,
             },
-          )
+          ))
         })
-        .collect();
+        .collect::<Result<_, Report>>()?;
 
-      (
+      Ok((
         gene.name.clone(),
         GeneTranslation {
           gene: gene.clone(),
           cdses,
           warnings: vec![],
         },
-      )
+      ))
     })
-    .collect();
+    .collect::<Result<_, Report>>()?;
 
   Ok(Translation { genes })
 }
@@ -0,0 +1,217 @@
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::aa_changes::FindAaChangesOutput;
+use crate::coord::range::AaRefRange;
+use crate::translate::translate_genes::CdsTranslation;
+use crate::utils::lru_cache::LruCache;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+/// Identifies translation of a CDS. The translation only depends on the aligned nucleotide sequences of the CDS (of the
+/// query and of the reference, which differs between queries by the gaps of insertions), given the same parameters.
+///
+/// The key is a checksum of the CDS name and of the sequences, such that the sequences themselves are not kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CdsTranslationKey([u8; 32]);
+
+impl CdsTranslationKey {
+  pub fn new(cds_name: &str, ref_cds_seq: &[Nuc], qry_cds_seq: &[Nuc]) -> Self {
+    let mut hasher = Sha256::new();
+    // Lengths delimit the fields, such that the same characters split differently produce different keys
+    for bytes in [
+      cds_name.as_bytes(),
+      &ref_cds_seq.iter().map(|&nuc| nuc as u8).collect::<Vec<u8>>(),
+      &qry_cds_seq.iter().map(|&nuc| nuc as u8).collect::<Vec<u8>>(),
+    ] {
+      hasher.update((bytes.len() as u64).to_le_bytes());
+      hasher.update(bytes);
+    }
+    Self(hasher.finalize().into())
+  }
+}
+
+struct CacheEntry {
+  translation: Arc<CdsTranslation>,
+  /// Aminoacid changes in the CDS, without the nucleotide changes attached to them (see `find_aa_changes()`), along
+  /// with the aminoacid alignment ranges they were found in. Added once they are calculated for the first query with
+  /// this translation.
+  aa_changes: Option<(Vec<AaRefRange>, Arc<FindAaChangesOutput>)>,
+}
+
+/// Translations of CDSes of the query sequences analyzed so far, such that a CDS with the same nucleotide sequence as in
+/// one of the previous queries (common among closely related sequences) is not translated and aligned again. The
+/// aminoacid changes found in the translations are kept as well.
+///
+/// Keeps at most `capacity` translations. When full, the least recently used translation is evicted. Shared between
+/// threads.
+pub struct CdsTranslationCache {
+  entries: Mutex<LruCache<CdsTranslationKey, CacheEntry>>,
+}
+
+impl CdsTranslationCache {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      entries: Mutex::new(LruCache::new(capacity)),
+    }
+  }
+
+  pub fn get(&self, key: &CdsTranslationKey) -> Option<Arc<CdsTranslation>> {
+    let mut entries = self.entries.lock().unwrap();
+    entries.get(key).map(|entry| Arc::clone(&entry.translation))
+  }
+
+  pub fn insert(&self, key: CdsTranslationKey, translation: Arc<CdsTranslation>) {
+    let mut entries = self.entries.lock().unwrap();
+    entries.insert(
+      key,
+      CacheEntry {
+        translation,
+        aa_changes: None,
+      },
+    );
+  }
+
+  /// Returns aminoacid changes found previously in the same translation. Alignment ranges of the translation depend on
+  /// the alignment of the whole query, not only of the CDS, so the changes are only reused if the ranges are the same.
+  pub fn get_aa_changes(&self, cds_tr: &CdsTranslation) -> Option<Arc<FindAaChangesOutput>> {
+    let key = cds_tr.cache_key.as_ref()?;
+    let mut entries = self.entries.lock().unwrap();
+    match &entries.get(key)?.aa_changes {
+      Some((alignment_ranges, aa_changes)) if alignment_ranges == &cds_tr.alignment_ranges => {
+        Some(Arc::clone(aa_changes))
+      }
+      _ => None,
+    }
+  }
+
+  /// Remembers aminoacid changes found in a translation. Does nothing if the translation itself is not in the cache
+  /// (anymore).
+  pub fn insert_aa_changes(&self, cds_tr: &CdsTranslation, aa_changes: Arc<FindAaChangesOutput>) {
+    let Some(key) = &cds_tr.cache_key else {
+      return;
+    };
+    let mut entries = self.entries.lock().unwrap();
+    if let Some(entry) = entries.get(key) {
+      entry.aa_changes = Some((cds_tr.alignment_ranges.clone(), aa_changes));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn translation(name: &str) -> Arc<CdsTranslation> {
+    Arc::new(CdsTranslation {
+      name: name.to_owned(),
+      seq: vec![],
+      insertions: vec![],
+      frame_shifts: vec![],
+      alignment_ranges: vec![],
+      unsequenced_ranges: vec![],
+      codon_alignment: vec![],
+      cache_key: None,
+    })
+  }
+
+  fn keyed_translation(key: CdsTranslationKey, alignment_range: AaRefRange) -> CdsTranslation {
+    CdsTranslation {
+      alignment_ranges: vec![alignment_range],
+      cache_key: Some(key),
+      ..CdsTranslation::clone(&translation("a"))
+    }
+  }
+
+  fn cached_name(cache: &CdsTranslationCache, key: &CdsTranslationKey) -> Option<String> {
+    cache.get(key).map(|translation| translation.name.clone())
+  }
+
+  #[rstest]
+  fn distinguishes_keys() -> Result<(), Report> {
+    let reff = to_nuc_seq("ACGTAC")?;
+    let qry = to_nuc_seq("ACGTAA")?;
+    assert_eq!(
+      CdsTranslationKey::new("S", &reff, &qry),
+      CdsTranslationKey::new("S", &reff, &qry)
+    );
+    assert_ne!(
+      CdsTranslationKey::new("S", &reff, &qry),
+      CdsTranslationKey::new("S", &reff, &reff)
+    );
+    assert_ne!(
+      CdsTranslationKey::new("S", &reff, &qry),
+      CdsTranslationKey::new("E", &reff, &qry)
+    );
+    // Same characters, split differently between the reference and the query
+    assert_ne!(
+      CdsTranslationKey::new("S", &to_nuc_seq("AC")?, &to_nuc_seq("GT")?),
+      CdsTranslationKey::new("S", &to_nuc_seq("ACG")?, &to_nuc_seq("T")?)
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  fn returns_cached_translation() -> Result<(), Report> {
+    let reff = to_nuc_seq("ACGTAC")?;
+    let key_a = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAC")?);
+    let key_b = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAA")?);
+
+    let cache = CdsTranslationCache::new(2);
+    assert_eq!(cached_name(&cache, &key_a), None);
+
+    cache.insert(key_a, translation("a"));
+    assert_eq!(cached_name(&cache, &key_a), Some("a".to_owned()));
+    assert_eq!(cached_name(&cache, &key_b), None);
+    Ok(())
+  }
+
+  #[rstest]
+  fn evicts_least_recently_used() -> Result<(), Report> {
+    let reff = to_nuc_seq("ACGTAC")?;
+    let key_a = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAC")?);
+    let key_b = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAA")?);
+    let key_c = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAG")?);
+
+    let cache = CdsTranslationCache::new(2);
+    cache.insert(key_a, translation("a"));
+    cache.insert(key_b, translation("b"));
+    assert!(cache.get(&key_a).is_some());
+
+    cache.insert(key_c, translation("c"));
+    assert_eq!(cached_name(&cache, &key_a), Some("a".to_owned()));
+    assert_eq!(cached_name(&cache, &key_b), None);
+    assert_eq!(cached_name(&cache, &key_c), Some("c".to_owned()));
+    Ok(())
+  }
+
+  #[rstest]
+  fn keeps_aa_changes_of_cached_translations_only() -> Result<(), Report> {
+    let reff = to_nuc_seq("ACGTAC")?;
+    let key_a = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAC")?);
+    let key_b = CdsTranslationKey::new("a", &reff, &to_nuc_seq("ACGTAA")?);
+
+    let cache = CdsTranslationCache::new(2);
+    cache.insert(key_a, translation("a"));
+
+    let tr_a = keyed_translation(key_a, AaRefRange::from_usize(0, 2));
+    let tr_b = keyed_translation(key_b, AaRefRange::from_usize(0, 2));
+    assert!(cache.get_aa_changes(&tr_a).is_none());
+
+    cache.insert_aa_changes(&tr_a, Arc::new(FindAaChangesOutput::default()));
+    cache.insert_aa_changes(&tr_b, Arc::new(FindAaChangesOutput::default()));
+    assert!(cache.get_aa_changes(&tr_a).is_some());
+    assert!(cache.get_aa_changes(&tr_b).is_none());
+
+    // Same translation, but in a query aligned to a shorter part of the genome
+    let tr_a_partial = keyed_translation(key_a, AaRefRange::from_usize(1, 2));
+    assert!(cache.get_aa_changes(&tr_a_partial).is_none());
+
+    // Replacing the translation drops its aminoacid changes
+    cache.insert(key_a, translation("a"));
+    assert!(cache.get_aa_changes(&tr_a).is_none());
+    Ok(())
+  }
+}
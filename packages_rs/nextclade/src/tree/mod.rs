@@ -3,6 +3,10 @@ pub mod split_muts;
 pub mod split_muts2;
 pub mod tree;
 pub mod tree_attach_new_nodes;
+pub mod tree_augment;
 pub mod tree_builder;
+pub mod tree_context;
 pub mod tree_find_nearest_node;
+pub mod tree_metadata;
+pub mod tree_nwk;
 pub mod tree_preprocess;
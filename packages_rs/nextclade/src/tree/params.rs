@@ -1,3 +1,4 @@
+use crate::tree::tree::DivergenceUnits;
 use clap::Parser;
 use optfield::optfield;
 use serde::{Deserialize, Serialize};
@@ -17,6 +18,60 @@ pub struct TreeBuilderParams {
 
   #[clap(long)]
   pub masked_muts_weight: f64,
+
+  /// Radius of the local search around the attachment point found by the greedy tree builder, in number of branches.
+  /// All nodes within this radius are checked, and the new node is attached to the one against which it has the least
+  /// private mutations, if it is better than the greedy attachment point. Zero disables the refinement.
+  #[clap(long)]
+  pub placement_refinement_radius: usize,
+
+  /// Unit of measurement of divergence of the reference tree nodes: "mutations-per-site" or "mutations" (across the
+  /// whole genome). Divergence of query sequences and branch lengths of the new nodes are calculated in the same unit.
+  /// If not set, the unit is guessed from the greatest divergence on the tree.
+  #[clap(long, value_enum)]
+  pub divergence_units: Option<DivergenceUnits>,
+
+  /// Clock rate, in substitutions per site per year. If set, the new nodes are assigned dates (`num_date` node
+  /// attribute) extrapolated from the dates of their parent nodes, by the number of substitutions on their branches
+  /// divided by the clock rate. Parent nodes without a date are ignored.
+  #[clap(long)]
+  pub clock_rate: Option<f64>,
+
+  /// Number of query sequences attached to the output tree at a time, as their results arrive, rather than all at once
+  /// at the end of the run. This reduces memory consumption when placing large numbers of sequences, because only the
+  /// results of one batch are held in memory. Batches consist of sequences with consecutive indices in the input files.
+  /// Sequences are ordered by the number of private mutations within each batch only, so the resulting tree may
+  /// slightly differ. Zero means a single batch.
+  #[clap(long)]
+  pub tree_batch_size: usize,
+
+  /// Default coloring of the output Auspice tree: the key of one of its colorings, e.g. "clade_membership" (default),
+  /// "region" or a key of a metadata column (see `--input-metadata`).
+  #[clap(long)]
+  pub auspice_color_by: Option<String>,
+
+  /// Default branch label of the output Auspice tree, e.g. "clade" (default), "aa" or "none".
+  #[clap(long)]
+  pub auspice_branch_label: Option<String>,
+
+  /// Default distance measure of the output Auspice tree: "div" (default) or "num_date" (see `--clock-rate`).
+  #[clap(long)]
+  pub auspice_distance_measure: Option<String>,
+
+  /// Default layout of the output Auspice tree: "rect" (default), "radial", "unrooted", "clock" or "scatter".
+  #[clap(long)]
+  pub auspice_layout: Option<String>,
+
+  /// Panels displayed by Auspice for the output tree, e.g. "tree", "entropy", "map", "frequencies". Replaces the default
+  /// panels ("tree" and "entropy").
+  #[clap(long, value_delimiter = ',')]
+  pub auspice_panels: Option<Vec<String>>,
+
+  /// Filters available in Auspice for the output tree: the keys of node attributes by which the displayed nodes can be
+  /// filtered. Replaces the default filters (clade, node type, QC status, PCR primer changes and the filters of the
+  /// reference tree).
+  #[clap(long, value_delimiter = ',')]
+  pub auspice_filters: Option<Vec<String>>,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -25,6 +80,16 @@ impl Default for TreeBuilderParams {
     Self {
       without_greedy_tree_builder: false,
       masked_muts_weight: 0.05,
+      placement_refinement_radius: 0,
+      divergence_units: None,
+      clock_rate: None,
+      tree_batch_size: 0,
+      auspice_color_by: None,
+      auspice_branch_label: None,
+      auspice_distance_measure: None,
+      auspice_layout: None,
+      auspice_panels: None,
+      auspice_filters: None,
     }
   }
 }
@@ -1,7 +1,7 @@
 use crate::alphabet::aa::Aa;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::find_private_nuc_mutations::BranchMutations;
-use crate::coord::position::{AaRefPosition, NucRefGlobalPosition};
+use crate::coord::position::{AaRefPosition, NucRefGlobalPosition, PositionLike};
 use crate::coord::range::NucRefGlobalRange;
 use crate::graph::edge::{Edge, GraphEdge};
 use crate::graph::graph::Graph;
@@ -9,6 +9,7 @@ use crate::graph::node::{GraphNode, Node};
 use crate::graph::traits::{HasDivergence, HasName};
 use crate::io::fs::read_file_to_string;
 use crate::io::json::json_parse;
+use clap::ValueEnum;
 use eyre::{Report, WrapErr};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::BTreeMap;
@@ -202,6 +203,18 @@ pub struct TreeNodeTempData {
   pub aa_mutations: BTreeMap<String, BTreeMap<AaRefPosition, Aa>>,
 }
 
+impl TreeNodeTempData {
+  /// Nucleotide of the node at a position: the one it is mutated to, relative to the reference, if any, or otherwise
+  /// the reference nucleotide
+  pub fn nuc_at(&self, pos: NucRefGlobalPosition, ref_seq: &[Nuc]) -> Nuc {
+    self
+      .mutations
+      .get(&pos)
+      .copied()
+      .unwrap_or_else(|| ref_seq[pos.as_usize()])
+  }
+}
+
 #[derive(Clone, Serialize, Deserialize, schemars::JsonSchema, Validate, Debug)]
 pub struct AuspiceGraphNodePayload {
   pub name: String,
@@ -243,12 +256,23 @@ impl From<&AuspiceTreeNode> for AuspiceGraphNodePayload {
 }
 
 impl AuspiceGraphNodePayload {
+  /// Checks whether the node is one of the query sequences placed onto the reference tree
+  pub fn is_new(&self) -> bool {
+    self
+      .node_attrs
+      .node_type
+      .as_ref()
+      .map_or(false, |node_type| node_type.value == "New")
+  }
+
   /// Extracts clade of the node
   pub fn clade(&self) -> String {
     self.node_attrs.clade_membership.value.clone()
   }
 
-  /// Extracts clade-like node attributes, given a list of key descriptions
+  /// Extracts clade-like node attributes, given a list of key descriptions. Each attribute is an independent
+  /// classification system (e.g. Nextstrain clade, WHO label, lab-specific lineage). String, number and boolean values
+  /// are accepted, and are converted to strings.
   pub fn get_clade_node_attrs(&self, clade_node_attr_keys: &[CladeNodeAttrKeyDesc]) -> BTreeMap<String, String> {
     clade_node_attr_keys
       .iter()
@@ -259,7 +283,13 @@ impl AuspiceGraphNodePayload {
           Some(attr) => attr.get("value"),
           None => None,
         }
-        .and_then(|val| val.as_str().map(|val| (key.clone(), val.to_owned())))
+        .and_then(|val| match val {
+          serde_json::Value::String(val) => Some(val.clone()),
+          serde_json::Value::Number(val) => Some(val.to_string()),
+          serde_json::Value::Bool(val) => Some(val.to_string()),
+          _ => None,
+        })
+        .map(|val| (key.clone(), val))
       })
       .collect()
   }
@@ -432,11 +462,18 @@ impl AuspiceTreeMeta {
   }
 }
 
+/// Unit of measurement of divergence of the tree nodes
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[derive(ValueEnum, Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize, schemars::JsonSchema)]
 pub enum DivergenceUnits {
+  /// Number of substitutions per site
+  #[value(name = "mutations-per-site")]
+  #[serde(rename = "mutations-per-site")]
   NumSubstitutionsPerYearPerSite,
+  /// Number of substitutions across the whole genome
   #[default]
+  #[value(name = "mutations")]
+  #[serde(rename = "mutations")]
   NumSubstitutionsPerYear,
 }
 
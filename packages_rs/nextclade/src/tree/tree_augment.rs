@@ -0,0 +1,156 @@
+use crate::graph::node::GraphNodeKey;
+use crate::make_error;
+use crate::tree::params::TreeBuilderParams;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload};
+use crate::tree::tree_builder::graph_attach_new_nodes_in_place;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::HashSet;
+
+/// Prefix of names of internal nodes created when augmenting the tree
+const AUGMENTED_INTERNAL_NODE_PREFIX: &str = "NODE_augmented";
+
+/// Adds query sequences from a previous Nextclade run to the reference tree as permanent nodes.
+///
+/// The queries are placed the same way as during `nextclade run` (starting from the nearest node found during that
+/// run), so the graph must be built from the same reference tree and preprocessed with the same reference sequence
+/// and genome annotation as in that run. After placement, the new nodes are converted into regular reference nodes:
+/// their clade-like attributes are inherited from their parents, the run-specific node attributes (QC, alignment,
+/// missing data etc.) are removed, and the newly created internal nodes receive unique names.
+///
+/// Returns the number of added query nodes.
+pub fn graph_augment_in_place(
+  graph: &mut AuspiceGraph,
+  results: Vec<NextcladeOutputs>,
+  ref_seq_len: usize,
+  params: &TreeBuilderParams,
+) -> Result<usize, Report> {
+  for result in &results {
+    check_placement_matches_graph(graph, result)?;
+  }
+
+  let n_nodes_before = graph.num_nodes();
+  let n_added = results.len();
+
+  graph_attach_new_nodes_in_place(graph, results, ref_seq_len, params)?;
+
+  // Nodes are appended to the graph, so all the nodes created by the placement have the largest keys
+  let is_new = |key: GraphNodeKey| key.as_usize() >= n_nodes_before;
+
+  let clade_node_attr_keys = graph
+    .data
+    .meta
+    .clade_node_attr_descs()
+    .iter()
+    .map(|desc| desc.name.clone())
+    .collect_vec();
+
+  let mut existing_names: HashSet<String> = graph
+    .iter_nodes()
+    .filter(|node| !is_new(node.key()))
+    .map(|node| node.payload().name.clone())
+    .collect();
+  let mut internal_node_counter = 0_usize;
+
+  // Parents are visited before their children, such that clade-like attributes propagate through chains of new nodes
+  for key in graph_keys_preorder(graph)? {
+    if !is_new(key) {
+      continue;
+    }
+
+    let Some(parent_key) = graph.parent_key_of_by_key(key) else {
+      continue;
+    };
+    let parent = graph.get_node(parent_key)?.payload().clone();
+
+    let is_leaf = graph.is_leaf_key(key);
+    let node = graph.get_node_mut(key)?.payload_mut();
+
+    inherit_clade_attrs(node, &parent, &clade_node_attr_keys);
+    make_reference_node(node);
+
+    if !is_leaf {
+      node.name = loop {
+        internal_node_counter += 1;
+        let name = format!("{AUGMENTED_INTERNAL_NODE_PREFIX}_{internal_node_counter}");
+        if !existing_names.contains(&name) {
+          break name;
+        }
+      };
+    }
+    existing_names.insert(node.name.clone());
+  }
+
+  Ok(n_added)
+}
+
+/// Verifies that the placement of a query refers to an existing node of the same clade. Otherwise the results were
+/// most likely produced with a different reference tree.
+fn check_placement_matches_graph(graph: &AuspiceGraph, result: &NextcladeOutputs) -> Result<(), Report> {
+  let nearest_node = graph.get_node(result.nearest_node_id).wrap_err_with(|| {
+    format!(
+      "When looking up the nearest node of query sequence '{}'. Make sure the results are produced using the same reference tree.",
+      result.seq_name
+    )
+  })?;
+
+  let clade = nearest_node.payload().clade();
+  if clade != result.clade {
+    return make_error!(
+      "Query sequence '{}' is assigned to clade '{}', but its nearest node '{}' in the reference tree belongs to clade '{clade}'. Make sure the results are produced using the same reference tree.",
+      result.seq_name,
+      result.clade,
+      nearest_node.payload().name
+    );
+  }
+
+  Ok(())
+}
+
+fn graph_keys_preorder(graph: &AuspiceGraph) -> Result<Vec<GraphNodeKey>, Report> {
+  let mut keys = vec![];
+  let mut stack = vec![graph.get_exactly_one_root()?.key()];
+  while let Some(key) = stack.pop() {
+    keys.push(key);
+    stack.extend(graph.iter_child_keys_of_by_key(key).rev());
+  }
+  Ok(keys)
+}
+
+fn inherit_clade_attrs(
+  node: &mut AuspiceGraphNodePayload,
+  parent: &AuspiceGraphNodePayload,
+  clade_node_attr_keys: &[String],
+) {
+  node.node_attrs.clade_membership = parent.node_attrs.clade_membership.clone();
+
+  if node.node_attrs.other.is_null() {
+    node.node_attrs.other = serde_json::Value::Object(serde_json::Map::default());
+  }
+
+  for key in clade_node_attr_keys {
+    let parent_value = parent.node_attrs.other.get(key).cloned();
+    if let Some(other) = node.node_attrs.other.as_object_mut() {
+      match parent_value {
+        Some(value) => other.insert(key.clone(), value),
+        None => other.remove(key),
+      };
+    }
+  }
+}
+
+/// Removes attributes which only make sense for query nodes of a particular run
+fn make_reference_node(node: &mut AuspiceGraphNodePayload) {
+  let attrs = &mut node.node_attrs;
+  attrs.node_type = None;
+  attrs.placement_prior = None;
+  attrs.alignment = None;
+  attrs.missing = None;
+  attrs.gaps = None;
+  attrs.non_acgtns = None;
+  attrs.has_pcr_primer_changes = None;
+  attrs.pcr_primer_changes = None;
+  attrs.qc_status = None;
+  attrs.missing_genes = None;
+}
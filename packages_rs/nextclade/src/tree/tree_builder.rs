@@ -6,51 +6,307 @@ use crate::analyze::nuc_del::NucDel;
 use crate::analyze::nuc_sub::NucSub;
 use crate::coord::range::NucRefGlobalRange;
 use crate::graph::node::{GraphNodeKey, Node};
+use crate::graph::traits::HasDivergence;
+use crate::make_error;
 use crate::tree::params::TreeBuilderParams;
 use crate::tree::split_muts::{difference_of_muts, split_muts, union_of_muts, SplitMutsResult};
-use crate::tree::tree::{AuspiceGraph, AuspiceGraphEdgePayload, AuspiceGraphNodePayload, TreeBranchAttrsLabels};
+use crate::tree::tree::{
+  AuspiceGraph, AuspiceGraphEdgePayload, AuspiceGraphNodePayload, DivergenceUnits, TreeBranchAttrsLabels,
+};
 use crate::tree::tree_attach_new_nodes::create_new_auspice_node;
-use crate::tree::tree_preprocess::add_auspice_metadata_in_place;
+use crate::tree::tree_preprocess::{add_auspice_metadata_in_place, apply_auspice_display_params_in_place};
 use crate::types::outputs::NextcladeOutputs;
 use crate::utils::collections::concat_to_vec;
 use eyre::{Report, WrapErr};
 use itertools::Itertools;
-use std::collections::BTreeMap;
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use traversal::DftPre;
 
 pub fn graph_attach_new_nodes_in_place(
   graph: &mut AuspiceGraph,
-  mut results: Vec<NextcladeOutputs>,
+  results: Vec<NextcladeOutputs>,
   ref_seq_len: usize,
   params: &TreeBuilderParams,
 ) -> Result<(), Report> {
+  let num_nodes_before = graph.num_nodes();
+  let num_results = results.len();
+  let num_refined = graph_attach_new_nodes_batch_in_place(graph, results, ref_seq_len, params)?;
+  graph_finalize_new_nodes_in_place(graph, num_nodes_before, num_results, num_refined, ref_seq_len, params)
+}
+
+/// Attaches query sequences to the tree in batches, as their results arrive, such that the results of at most one batch
+/// (`--tree-batch-size`) are held in memory, rather than the results of all sequences until the end of the run. The
+/// tree is finalized (dates, ladderization, metadata) once, in [BatchedTreeBuilder::finish].
+///
+/// Batches consist of sequences with consecutive input indices, regardless of the order in which the results arrive,
+/// such that the resulting tree does not depend on thread scheduling. Sequences are sorted by the number of private
+/// mutations within each batch only, so the resulting tree may differ slightly from the tree built from all sequences
+/// at once. Errors are deferred until [BatchedTreeBuilder::finish].
+///
+/// When batching is disabled, the sequences are attached directly to the reference tree at the end of the run, so that
+/// the reference tree is not copied. Otherwise the sequences are attached to a copy of the reference tree, because the
+/// reference tree itself is still needed for the placement of the subsequent sequences.
+pub struct BatchedTreeBuilder {
+  graph: Option<AuspiceGraph>,
+  ref_seq_len: usize,
+  params: TreeBuilderParams,
+  num_nodes_before: usize,
+  num_nodes_compacted: usize,
+  batches: IndexOrderedBatches<NextcladeOutputs>,
+  num_attached: usize,
+  num_refined: usize,
+  error: Option<Report>,
+}
+
+impl BatchedTreeBuilder {
+  pub fn new(reference_graph: &AuspiceGraph, ref_seq_len: usize, params: &TreeBuilderParams) -> Self {
+    Self {
+      graph: None,
+      ref_seq_len,
+      params: params.clone(),
+      num_nodes_before: reference_graph.num_nodes(),
+      num_nodes_compacted: 0,
+      batches: IndexOrderedBatches::new(params.tree_batch_size),
+      num_attached: 0,
+      num_refined: 0,
+      error: None,
+    }
+  }
+
+  /// Adds result of the sequence with a given input index. Sequences which failed to be analyzed should be added as
+  /// `None`, such that the batches are not waiting for them.
+  pub fn add(&mut self, reference_graph: &AuspiceGraph, index: usize, result: Option<NextcladeOutputs>) {
+    if self.error.is_some() {
+      return;
+    }
+    for batch in self.batches.add(index, result) {
+      let mut graph = self.graph.take().unwrap_or_else(|| reference_graph.clone());
+      let attached = self.attach_batch(&mut graph, batch);
+      self.graph = Some(graph);
+      if let Err(report) = attached {
+        self.error = Some(report);
+        return;
+      }
+    }
+  }
+
+  pub fn finish(mut self, reference_graph: AuspiceGraph) -> Result<AuspiceGraph, Report> {
+    if let Some(report) = self.error.take() {
+      return Err(report);
+    }
+    let mut graph = self.graph.take().unwrap_or(reference_graph);
+    let batch = std::mem::take(&mut self.batches).finish();
+    self.attach_batch(&mut graph, batch)?;
+    graph_finalize_new_nodes_in_place(
+      &mut graph,
+      self.num_nodes_before,
+      self.num_attached,
+      self.num_refined,
+      self.ref_seq_len,
+      &self.params,
+    )?;
+    Ok(graph)
+  }
+
+  fn attach_batch(&mut self, graph: &mut AuspiceGraph, batch: Vec<NextcladeOutputs>) -> Result<(), Report> {
+    self.num_attached += batch.len();
+    self.num_refined += graph_attach_new_nodes_batch_in_place(graph, batch, self.ref_seq_len, &self.params)?;
+    self.num_nodes_compacted = graph_compact_in_place(graph, self.num_nodes_compacted);
+    Ok(())
+  }
+}
+
+/// Groups items, arriving in arbitrary order, into batches of items with consecutive indices. Every index is expected
+/// to arrive exactly once, with `None` for the indices without an item. Zero batch size means a single batch.
+#[derive(Debug)]
+struct IndexOrderedBatches<T> {
+  batch_size: usize,
+  pending: BTreeMap<usize, Option<T>>,
+  next_index: usize,
+  batch: Vec<T>,
+  batch_len: usize,
+}
+
+impl<T> Default for IndexOrderedBatches<T> {
+  fn default() -> Self {
+    Self::new(0)
+  }
+}
+
+impl<T> IndexOrderedBatches<T> {
+  const fn new(batch_size: usize) -> Self {
+    Self {
+      batch_size,
+      pending: BTreeMap::new(),
+      next_index: 0,
+      batch: vec![],
+      batch_len: 0,
+    }
+  }
+
+  /// Adds an item and returns the batches completed by it, if any
+  fn add(&mut self, index: usize, item: Option<T>) -> Vec<Vec<T>> {
+    if self.batch_size == 0 {
+      self.batch.extend(item);
+      return vec![];
+    }
+
+    self.pending.insert(index, item);
+    let mut completed = vec![];
+    while let Some(item) = self.pending.remove(&self.next_index) {
+      self.next_index += 1;
+      self.batch_len += 1;
+      self.batch.extend(item);
+      if self.batch_len >= self.batch_size {
+        // Release the memory held by the items of the batch once they are consumed, keeping room for the next batch
+        completed.push(std::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size)));
+        self.batch_len = 0;
+      }
+    }
+    completed
+  }
+
+  /// Returns the last, incomplete batch, including the items still waiting for the preceding indices, if any
+  fn finish(mut self) -> Vec<T> {
+    self.batch.extend(self.pending.into_values().flatten());
+    self.batch
+  }
+}
+
+/// Releases the data of the nodes which is no longer needed once the nodes are in the tree: the full lists of mutations
+/// relative to the reference. Attachment of the subsequent sequences only relies on the private mutations of the
+/// branches, and the mutation lists are not written to the outputs. Nodes with keys below `num_nodes_compacted` are
+/// skipped, as they have been compacted before. Returns the number of nodes compacted so far.
+fn graph_compact_in_place(graph: &mut AuspiceGraph, num_nodes_compacted: usize) -> usize {
+  for node in graph
+    .iter_nodes_mut()
+    .filter(|node| node.key().as_usize() >= num_nodes_compacted)
+  {
+    let tmp = &mut node.payload_mut().tmp;
+    tmp.substitutions = BTreeMap::new();
+    tmp.mutations = BTreeMap::new();
+    tmp.aa_substitutions = BTreeMap::new();
+    tmp.aa_mutations = BTreeMap::new();
+  }
+  graph.num_nodes()
+}
+
+/// Attaches the new nodes to the graph, without finalizing it. Returns the number of nodes whose attachment point was
+/// changed by the local refinement of placement.
+fn graph_attach_new_nodes_batch_in_place(
+  graph: &mut AuspiceGraph,
+  mut results: Vec<NextcladeOutputs>,
+  ref_seq_len: usize,
+  params: &TreeBuilderParams,
+) -> Result<usize, Report> {
   // Add sequences with less private mutations first to avoid un-treelike behavior in the graph.
   // And then also sort by the index in the original fasta inputs, to avoid non-deterministic order due to differences
   // in thread scheduling.
   results.sort_by_key(|result| (result.private_nuc_mutations.total_private_substitutions, result.index));
 
   // Look for a query sample result for which this node was decided to be nearest
+  let mut num_refined = 0_usize;
   for result in &results {
-    graph_attach_new_node_in_place(graph, result, ref_seq_len, params).wrap_err_with(|| {
+    let is_refined = graph_attach_new_node_in_place(graph, result, ref_seq_len, params).wrap_err_with(|| {
       format!(
         "When attaching the new node for query sequence '{}' to the tree",
         result.seq_name
       )
     })?;
+    if is_refined {
+      num_refined += 1;
+    }
+  }
+
+  Ok(num_refined)
+}
+
+/// Finalizes the graph after all new nodes are attached to it
+fn graph_finalize_new_nodes_in_place(
+  graph: &mut AuspiceGraph,
+  num_nodes_before: usize,
+  num_results: usize,
+  num_refined: usize,
+  ref_seq_len: usize,
+  params: &TreeBuilderParams,
+) -> Result<(), Report> {
+  if params.placement_refinement_radius > 0 {
+    info!(
+      "Local refinement of placement changed the attachment point of {num_refined} out of {num_results} sequence(s)"
+    );
+  }
+
+  if let Some(clock_rate) = params.clock_rate {
+    graph_assign_new_node_dates_in_place(graph, num_nodes_before, clock_rate, ref_seq_len)
+      .wrap_err("When assigning dates to the new nodes")?;
   }
 
   graph.ladderize_tree().wrap_err("When ladderizing the resulting tree")?;
 
   add_auspice_metadata_in_place(&mut graph.data.meta);
+  apply_auspice_display_params_in_place(&mut graph.data.meta, params);
 
   Ok(())
 }
 
+/// Assigns dates (`num_date` node attribute) to the nodes added to the tree, i.e. to the nodes with keys starting from
+/// `num_nodes_before`. The date of a node is the date of its parent plus the length of its branch divided by the clock
+/// rate (in substitutions per site per year, converted to the units of divergence of the tree).
+fn graph_assign_new_node_dates_in_place(
+  graph: &mut AuspiceGraph,
+  num_nodes_before: usize,
+  clock_rate: f64,
+  ref_seq_len: usize,
+) -> Result<(), Report> {
+  if clock_rate <= 0.0 {
+    return make_error!("Clock rate should be positive, but found: {clock_rate}");
+  }
+  let rate = if graph.data.tmp.divergence_units == DivergenceUnits::NumSubstitutionsPerYearPerSite {
+    clock_rate
+  } else {
+    clock_rate * ref_seq_len as f64
+  };
+
+  // Parents come before children, such that dates of the new internal nodes are assigned before their children's
+  let new_node_keys = DftPre::new(graph.get_exactly_one_root()?, |node| graph.iter_children_of(node))
+    .map(|(_, node)| node.key())
+    .filter(|key| key.as_usize() >= num_nodes_before)
+    .collect_vec();
+
+  for node_key in new_node_keys {
+    let Some(parent) = graph.parent_of_by_key(node_key) else {
+      continue;
+    };
+    let Some(parent_date) = num_date_of(parent.payload()) else {
+      continue;
+    };
+    let branch_length = graph.get_node(node_key)?.payload().divergence() - parent.payload().divergence();
+    let date = parent_date + branch_length.max(0.0) / rate;
+
+    let attrs = &mut graph.get_node_mut(node_key)?.payload_mut().node_attrs;
+    if !attrs.other.is_object() {
+      attrs.other = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let Some(other) = attrs.other.as_object_mut() {
+      other.insert("num_date".to_owned(), serde_json::json!({ "value": date }));
+    }
+  }
+
+  Ok(())
+}
+
+fn num_date_of(node: &AuspiceGraphNodePayload) -> Option<f64> {
+  node.node_attrs.other.get("num_date")?.get("value")?.as_f64()
+}
+
+/// Attaches the new node to the graph. Returns whether the local refinement of placement changed the attachment point.
 pub fn graph_attach_new_node_in_place(
   graph: &mut AuspiceGraph,
   result: &NextcladeOutputs,
   ref_seq_len: usize,
   params: &TreeBuilderParams,
-) -> Result<(), Report> {
+) -> Result<bool, Report> {
   let mut private_aa_mutations = BTreeMap::<String, Vec<AaSub>>::new();
   for key in result.private_aa_mutations.keys() {
     let subs = result.private_aa_mutations[key].private_substitutions.clone();
@@ -80,20 +336,39 @@ pub fn graph_attach_new_node_in_place(
     aa_muts: private_aa_mutations,
   };
 
+  let mut is_refined = false;
   let (nearest_node_key, private_mutations) = if params.without_greedy_tree_builder {
     // Skip tree fine-tuning
     (result.nearest_node_id, mutations_seq)
   } else {
     // for the attachment on the reference tree ('result') fine tune the position
     // on the updated graph to minimize the number of private mutations
-    finetune_nearest_node(graph, result.nearest_node_id, &mutations_seq, params)?
+    let (greedy_node_key, private_mutations) =
+      finetune_nearest_node(graph, result.nearest_node_id, &mutations_seq, params)?;
+
+    if params.placement_refinement_radius > 0 {
+      let (refined_node_key, private_mutations) =
+        refine_nearest_node(graph, greedy_node_key, private_mutations, params)?;
+      is_refined = refined_node_key != greedy_node_key;
+      if is_refined {
+        info!(
+          "Local refinement of placement moved sequence '{}' from node '{}' to node '{}'",
+          result.seq_name,
+          graph.get_node(greedy_node_key)?.payload().name,
+          graph.get_node(refined_node_key)?.payload().name
+        );
+      }
+      (refined_node_key, private_mutations)
+    } else {
+      (greedy_node_key, private_mutations)
+    }
   };
 
   // add the new node at the fine tuned position while accounting for shared mutations
   // on the branch leading to the nearest node.
   knit_into_graph(graph, nearest_node_key, result, &private_mutations, ref_seq_len, params)?;
 
-  Ok(())
+  Ok(is_refined)
 }
 
 /// Moves the new sequences, defined by its set of private mutations
@@ -141,6 +416,70 @@ pub fn finetune_nearest_node(
   Ok((best_node.key(), private_mutations))
 }
 
+/// Explores the nodes within `placement_refinement_radius` branches of the attachment point found by the greedy
+/// search, in both directions along the tree. While moving from node to node, the private mutations of the new
+/// sequence are updated, same as during the greedy search. Returns the node against which the new sequence has the
+/// least private mutations, along with these mutations. The attachment point only changes if the improvement is
+/// strict, so that the greedy search, which cannot see past nodes that do not immediately improve the placement, can
+/// be overruled, but ties are resolved in its favor.
+pub fn refine_nearest_node(
+  graph: &AuspiceGraph,
+  nearest_node_key: GraphNodeKey,
+  private_mutations: BranchMutations,
+  params: &TreeBuilderParams,
+) -> Result<(GraphNodeKey, BranchMutations), Report> {
+  let masked_ranges = graph.data.meta.placement_mask_ranges();
+  let mut best_score = score_nuc_muts(&private_mutations.nuc_muts, masked_ranges, params);
+  let mut best = (nearest_node_key, private_mutations.clone());
+
+  let mut visited = BTreeSet::from([nearest_node_key]);
+  let mut frontier = vec![(nearest_node_key, private_mutations)];
+  for _ in 0..params.placement_refinement_radius {
+    let mut next_frontier = vec![];
+    for (node_key, node_private_mutations) in &frontier {
+      let node = graph.get_node(*node_key)?;
+
+      // Mutations on the path from the current node to each of its neighbors
+      let parent = graph
+        .parent_of(node)
+        .map(|parent| (parent, node.payload().tmp.private_mutations.invert()));
+      let children = graph
+        .iter_children_of(node)
+        .map(|child| (child, child.payload().tmp.private_mutations.clone()));
+
+      for (neighbor, path_mutations) in parent.into_iter().chain(children) {
+        if !visited.insert(neighbor.key()) {
+          continue;
+        }
+
+        let split = split_muts(&path_mutations, node_private_mutations).wrap_err_with(|| {
+          format!(
+            "When splitting mutations between query sequence and the branch towards node '{}'",
+            neighbor.payload().name
+          )
+        })?;
+        let neighbor_private_mutations =
+          update_private_mutations(node_private_mutations, &split).wrap_err_with(|| {
+            format!(
+              "When updating private mutations against node '{}'",
+              neighbor.payload().name
+            )
+          })?;
+
+        let score = score_nuc_muts(&neighbor_private_mutations.nuc_muts, masked_ranges, params);
+        if score < best_score {
+          best_score = score;
+          best = (neighbor.key(), neighbor_private_mutations.clone());
+        }
+        next_frontier.push((neighbor.key(), neighbor_private_mutations));
+      }
+    }
+    frontier = next_frontier;
+  }
+
+  Ok(best)
+}
+
 /// Check how many mutations are shared with the branch leading to the current_best_node or any of its children
 fn find_shared_muts<'g>(
   graph: &'g AuspiceGraph,
@@ -430,3 +769,64 @@ fn set_branch_attrs_aa_labels(node: &mut AuspiceGraphNodePayload) {
     });
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::Nuc;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn batches_items_by_consecutive_indices_regardless_of_arrival_order() {
+    let mut batches = IndexOrderedBatches::new(3);
+    let mut completed = vec![];
+    for index in [2, 0, 4, 1, 5, 6, 3] {
+      completed.extend(batches.add(index, Some(index)));
+    }
+    assert_eq!(completed, vec![vec![0, 1, 2], vec![3, 4, 5]]);
+    assert_eq!(batches.finish(), vec![6]);
+  }
+
+  #[rstest]
+  fn counts_missing_items_towards_batch_size() {
+    let mut batches = IndexOrderedBatches::new(2);
+    let mut completed = vec![];
+    completed.extend(batches.add(1, None));
+    completed.extend(batches.add(0, Some(0)));
+    completed.extend(batches.add(2, Some(2)));
+    assert_eq!(completed, vec![vec![0]]);
+    assert_eq!(batches.finish(), vec![2]);
+  }
+
+  #[rstest]
+  fn returns_single_batch_when_batching_is_disabled() {
+    let mut batches = IndexOrderedBatches::new(0);
+    for index in [1, 0, 2] {
+      assert_eq!(batches.add(index, Some(index)), Vec::<Vec<usize>>::new());
+    }
+    assert_eq!(batches.finish(), vec![1, 0, 2]);
+  }
+
+  #[rstest]
+  fn compacts_only_nodes_not_compacted_before() -> Result<(), Report> {
+    let tree = auspice_tree_from_nwk_str("((A:1,B:1)X:1,C:1)R;", None)?;
+    let mut graph = convert_auspice_tree_to_graph(tree)?;
+    for payload in graph.iter_node_payloads_mut() {
+      payload.tmp.substitutions.insert(1_usize.into(), Nuc::T);
+    }
+
+    let num_nodes_compacted = graph_compact_in_place(&mut graph, 3);
+
+    assert_eq!(num_nodes_compacted, graph.num_nodes());
+    let num_subs_by_key = graph
+      .iter_nodes()
+      .map(|node| (node.key().as_usize(), node.payload().tmp.substitutions.len()))
+      .sorted()
+      .collect_vec();
+    assert_eq!(num_subs_by_key, vec![(0, 1), (1, 1), (2, 1), (3, 0), (4, 0)]);
+    Ok(())
+  }
+}
@@ -0,0 +1,173 @@
+use crate::graph::node::{GraphNodeKey, Node};
+use crate::make_internal_error;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload, AuspiceTree, AuspiceTreeNode};
+use eyre::Report;
+use itertools::Itertools;
+use std::collections::BTreeSet;
+
+/// Extracts a small subtree around the given node (typically a newly placed query sequence), such that the node can be
+/// viewed in its phylogenetic context without loading the full tree.
+///
+/// Starting from the node, moves up the tree until reaching the lowest ancestor which has at least `num_leaves` leaves
+/// (or the root), and keeps the node itself and the leaves of this ancestor which are the closest to the node, by
+/// divergence, `num_leaves` in total. Internal nodes which are left with a single child are removed, and the mutations on
+/// their branches are moved onto the branch of the child.
+pub fn extract_context_subtree(
+  graph: &AuspiceGraph,
+  node_key: GraphNodeKey,
+  num_leaves: usize,
+) -> Result<AuspiceTree, Report> {
+  let node = graph.get_node(node_key)?;
+  let node_div = divergence_of(node);
+
+  let mut ancestor = node;
+  let mut path_keys = BTreeSet::from([node_key]);
+  let mut leaves = vec![];
+  loop {
+    leaves.clear();
+    collect_leaves_with_distances(
+      graph,
+      ancestor,
+      node_div,
+      divergence_of(ancestor),
+      &path_keys,
+      &mut leaves,
+    );
+    if leaves.len() >= num_leaves {
+      break;
+    }
+    let Some(parent) = graph.parent_of(ancestor) else {
+      break;
+    };
+    ancestor = parent;
+    path_keys.insert(parent.key());
+  }
+
+  // The node itself comes first, then the other leaves by distance from it
+  let selected: BTreeSet<GraphNodeKey> = leaves
+    .into_iter()
+    .sorted_by(|(key_a, dist_a), (key_b, dist_b)| {
+      (*key_a != node_key)
+        .cmp(&(*key_b != node_key))
+        .then(dist_a.total_cmp(dist_b))
+    })
+    .take(num_leaves.max(1))
+    .map(|(key, _)| key)
+    .collect();
+
+  let Some(tree) = extract_induced_subtree(graph, ancestor, &selected) else {
+    return make_internal_error!(
+      "Context subtree of node '{}' contains no leaves",
+      node.payload().name
+    );
+  };
+
+  Ok(AuspiceTree {
+    version: graph.data.auspice_tree_version.clone(),
+    meta: graph.data.meta.clone(),
+    tree,
+    other: graph.data.other.clone(),
+  })
+}
+
+fn divergence_of(node: &Node<AuspiceGraphNodePayload>) -> f64 {
+  node.payload().node_attrs.div.unwrap_or_default()
+}
+
+/// Collects leaves of the subtree along with their distances to the node of interest. The nodes on the path from the
+/// node of interest to the root of the subtree are given in `path_keys`: the last of them above a leaf is the most
+/// recent common ancestor of the leaf and of the node of interest.
+fn collect_leaves_with_distances(
+  graph: &AuspiceGraph,
+  node: &Node<AuspiceGraphNodePayload>,
+  target_div: f64,
+  mrca_div: f64,
+  path_keys: &BTreeSet<GraphNodeKey>,
+  leaves: &mut Vec<(GraphNodeKey, f64)>,
+) {
+  let mrca_div = if path_keys.contains(&node.key()) {
+    divergence_of(node)
+  } else {
+    mrca_div
+  };
+
+  if node.is_leaf() {
+    leaves.push((node.key(), target_div + divergence_of(node) - 2.0 * mrca_div));
+  } else {
+    for child in graph.iter_children_of(node) {
+      collect_leaves_with_distances(graph, child, target_div, mrca_div, path_keys, leaves);
+    }
+  }
+}
+
+/// Converts the part of the subtree which connects the selected leaves. Returns `None` if the subtree contains none of
+/// them.
+fn extract_induced_subtree(
+  graph: &AuspiceGraph,
+  node: &Node<AuspiceGraphNodePayload>,
+  selected: &BTreeSet<GraphNodeKey>,
+) -> Option<AuspiceTreeNode> {
+  if node.is_leaf() {
+    return selected
+      .contains(&node.key())
+      .then(|| AuspiceTreeNode::from_graph_node_payload(node.payload(), vec![]));
+  }
+
+  let mut children = graph
+    .iter_children_of(node)
+    .filter_map(|child| extract_induced_subtree(graph, child, selected))
+    .collect_vec();
+
+  if children.len() == 1 {
+    let mut child = children.pop()?;
+    for (gene, mutations) in &node.payload().branch_attrs.mutations {
+      let child_mutations = child.branch_attrs.mutations.entry(gene.clone()).or_default();
+      let merged = mutations.iter().chain(child_mutations.iter()).cloned().collect_vec();
+      *child_mutations = merged;
+    }
+    return Some(child);
+  }
+
+  (!children.is_empty()).then(|| AuspiceTreeNode::from_graph_node_payload(node.payload(), children))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn leaf_names(node: &AuspiceTreeNode) -> Vec<String> {
+    if node.children.is_empty() {
+      vec![node.name.clone()]
+    } else {
+      node.children.iter().flat_map(leaf_names).collect()
+    }
+  }
+
+  #[rstest]
+  #[case::two_leaves(2, "Y", &["C", "Q"])]
+  #[case::three_leaves(3, "Z", &["C", "Q", "D"])]
+  #[case::whole_tree(10, "R", &["A", "B", "C", "Q", "D"])]
+  fn extracts_context_subtree(
+    #[case] num_leaves: usize,
+    #[case] root: &str,
+    #[case] leaves: &[&str],
+  ) -> Result<(), Report> {
+    let tree = auspice_tree_from_nwk_str("((A:1,B:1)X:1,((C:1,Q:0.1)Y:1,D:1)Z:1)R;", None)?;
+    let graph = convert_auspice_tree_to_graph(tree)?;
+    let node_key = graph
+      .iter_nodes()
+      .find(|node| node.payload().name == "Q")
+      .map(Node::key)
+      .expect("Expected node 'Q' in the tree");
+
+    let subtree = extract_context_subtree(&graph, node_key, num_leaves)?;
+
+    assert_eq!(subtree.tree.name, root);
+    assert_eq!(leaf_names(&subtree.tree), leaves);
+    Ok(())
+  }
+}
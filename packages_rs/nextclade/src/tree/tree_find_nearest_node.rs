@@ -7,6 +7,7 @@ use crate::graph::node::GraphNodeKey;
 use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload, TreeNodeAttr};
 use eyre::Report;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use traversal::DftPre;
 
 /// Distance and placement prior for a ref tree node
@@ -52,6 +53,84 @@ pub fn graph_find_nearest_nodes(
   })
 }
 
+/// Candidate attachment point of a query sequence on the reference tree
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementCandidate {
+  pub node_name: String,
+  pub clade: String,
+  /// Distance between the query and the node (see the phylogenetic placement algorithm)
+  pub distance: i64,
+  /// Number of nucleotide substitutions of the query which are also present in the node
+  pub num_shared_mutations: usize,
+  /// Relative weight of the node among all nodes of the tree, from 0 to 1
+  pub weight: f64,
+}
+
+/// Best candidate attachment points of a query sequence, with the confidence of the placement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementCandidates {
+  /// Candidates, from best to worst. The first candidate is the nearest node the query is attached to.
+  pub candidates: Vec<PlacementCandidate>,
+  /// Weight of the nearest node: close to 1 if the placement is unambiguous, and lower if other nodes are about as
+  /// close to the query
+  pub confidence: f64,
+}
+
+/// Summarizes the `num_candidates` best attachment points, given all nodes sorted by placement score (as returned by
+/// [graph_find_nearest_nodes]).
+///
+/// Each node is weighted by `prior * exp(-distance)`, normalized over all nodes of the tree, such that the nodes which
+/// are farther from the query than the nearest node by a few mutations have negligible weight, unless their
+/// placement prior is much higher.
+pub fn find_placement_candidates(
+  graph: &AuspiceGraph,
+  nodes_by_placement_score: &[TreePlacementInfo],
+  qry_nuc_subs: &[NucSub],
+  num_candidates: usize,
+) -> Result<PlacementCandidates, Report> {
+  let min_distance = nodes_by_placement_score.first().map_or(0, |node| node.distance);
+  let weight_of = |node: &TreePlacementInfo| node.prior * (-((node.distance - min_distance) as f64)).exp();
+  let total_weight: f64 = nodes_by_placement_score.iter().map(weight_of).sum();
+  let normalize = |weight: f64| if total_weight > 0.0 { weight / total_weight } else { 0.0 };
+
+  let masked_ranges = graph.data.meta.placement_mask_ranges();
+  let candidates = nodes_by_placement_score
+    .iter()
+    .take(num_candidates)
+    .map(|node| {
+      let payload = graph.get_node(node.node_key)?.payload();
+      Ok(PlacementCandidate {
+        node_name: payload.name.clone(),
+        clade: payload.clade(),
+        distance: node.distance,
+        num_shared_mutations: count_shared_nuc_subs(payload, qry_nuc_subs, masked_ranges),
+        weight: normalize(weight_of(node)),
+      })
+    })
+    .collect::<Result<Vec<_>, Report>>()?;
+
+  let confidence = nodes_by_placement_score
+    .first()
+    .map_or(0.0, |node| normalize(weight_of(node)));
+
+  Ok(PlacementCandidates { candidates, confidence })
+}
+
+/// Counts unmasked nucleotide substitutions of the query which are also present in the node
+fn count_shared_nuc_subs(
+  node: &AuspiceGraphNodePayload,
+  qry_nuc_subs: &[NucSub],
+  masked_ranges: &[NucRefGlobalRange],
+) -> usize {
+  qry_nuc_subs
+    .iter()
+    .filter(|sub| !masked_ranges.iter().any(|range| range.contains(sub.pos)))
+    .filter(|sub| node.tmp.substitutions.get(&sub.pos) == Some(&sub.qry_nuc))
+    .count()
+}
+
 /// Gets non-log scale prior from node attributes
 fn get_prior(node: &AuspiceGraphNodePayload) -> f64 {
   10.0_f64.powf(
@@ -130,6 +209,9 @@ mod tests {
 
   use super::*;
   use crate::coord::position::NucRefGlobalPosition;
+  use crate::graph::graph::convert_auspice_tree_to_graph;
+  use crate::graph::node::Node;
+  use crate::tree::tree_nwk::auspice_tree_from_nwk_str;
   use eyre::Report;
   use pretty_assertions::assert_eq;
   use rstest::{fixture, rstest};
@@ -363,4 +445,35 @@ mod tests {
 
     Ok(())
   }
+
+  #[rstest]
+  fn placement_candidates_with_ambiguous_placement() -> Result<(), Report> {
+    let graph = convert_auspice_tree_to_graph(auspice_tree_from_nwk_str("(A:1,B:1)R;", None)?)?;
+    let node_key = |name: &str| {
+      graph
+        .iter_nodes()
+        .find(|node| node.payload().name == name)
+        .map(Node::key)
+        .expect("Expected node in the tree")
+    };
+    let nodes_by_placement_score = [("A", 0), ("B", 0), ("R", 2)].map(|(name, distance)| TreePlacementInfo {
+      node_key: node_key(name),
+      distance,
+      prior: 1.0,
+    });
+
+    let result = find_placement_candidates(&graph, &nodes_by_placement_score, &[], 2)?;
+
+    assert_eq!(
+      result
+        .candidates
+        .iter()
+        .map(|candidate| (candidate.node_name.as_str(), candidate.distance))
+        .collect_vec(),
+      vec![("A", 0), ("B", 0)]
+    );
+    assert!((result.confidence - 1.0 / (2.0 + (-2.0_f64).exp())).abs() < 1e-9);
+
+    Ok(())
+  }
 }
@@ -0,0 +1,155 @@
+use crate::io::csv::read_csv_file_with_delimiter;
+use crate::io::fs::has_extension;
+use crate::make_error;
+use crate::tree::tree::{AuspiceColoring, AuspiceGraph, TreeNodeAttr};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::warn;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const SEQ_NAME_COLUMN: &str = "seqName";
+
+/// User-provided metadata of the query sequences, to be added to the newly placed nodes of the output tree
+#[derive(Clone, Debug, Default)]
+pub struct SampleMetadata {
+  columns: Vec<String>,
+  /// Values of the selected columns, by sequence name
+  rows: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl SampleMetadata {
+  /// Reads metadata from a CSV or TSV file with a `seqName` column. Values are delimited by semicolons if the file path
+  /// ends with ".csv", and by tabs otherwise. Only the given columns are kept, or all of them if none are given.
+  pub fn from_path(filepath: impl AsRef<Path>, columns: &[String]) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let delimiter = if has_extension(filepath, "csv") { b';' } else { b'\t' };
+    let rows: Vec<BTreeMap<String, String>> = read_csv_file_with_delimiter(filepath, delimiter)
+      .wrap_err_with(|| format!("When reading sequence metadata from file {filepath:#?}"))?;
+    Self::from_rows(rows, columns).wrap_err_with(|| format!("When reading sequence metadata from file {filepath:#?}"))
+  }
+
+  /// Keeps the given columns of the metadata rows (see [SampleMetadata::from_path])
+  pub fn from_rows(rows: Vec<BTreeMap<String, String>>, columns: &[String]) -> Result<Self, Report> {
+    let columns = if columns.is_empty() {
+      rows
+        .first()
+        .map(|row| row.keys().filter(|&key| key != SEQ_NAME_COLUMN).cloned().collect_vec())
+        .unwrap_or_default()
+    } else {
+      columns.to_vec()
+    };
+
+    let rows = rows
+      .into_iter()
+      .map(|mut row| {
+        let Some(seq_name) = row.remove(SEQ_NAME_COLUMN) else {
+          return make_error!("Column '{SEQ_NAME_COLUMN}' is not found");
+        };
+        let values = columns
+          .iter()
+          .map(|column| match row.remove(column) {
+            Some(value) => Ok((column.clone(), value)),
+            None => make_error!("Column '{column}' is not found"),
+          })
+          .collect::<Result<BTreeMap<_, _>, Report>>()?;
+        Ok((seq_name, values))
+      })
+      .collect::<Result<BTreeMap<_, _>, Report>>()?;
+
+    Ok(Self { columns, rows })
+  }
+}
+
+/// Adds the metadata columns as node attributes to the newly placed nodes of the tree, matching them by sequence name,
+/// and adds categorical colorings and filters for these columns, such that the tree can be colored by them in Auspice.
+/// Columns `region`, `country` and `division` fill the corresponding built-in node attributes.
+pub fn graph_join_metadata_in_place(graph: &mut AuspiceGraph, metadata: &SampleMetadata) {
+  let mut num_matched = 0_usize;
+  for node in graph.iter_nodes_mut() {
+    let payload = node.payload_mut();
+    if !payload.is_new() {
+      continue;
+    }
+    let Some(values) = metadata.rows.get(&payload.name) else {
+      continue;
+    };
+    num_matched += 1;
+
+    for (column, value) in values.iter().filter(|(_, value)| !value.is_empty()) {
+      let attrs = &mut payload.node_attrs;
+      match column.as_str() {
+        "region" => attrs.region = Some(TreeNodeAttr::new(value)),
+        "country" => attrs.country = Some(TreeNodeAttr::new(value)),
+        "division" => attrs.division = Some(TreeNodeAttr::new(value)),
+        _ => {
+          if !attrs.other.is_object() {
+            attrs.other = serde_json::Value::Object(serde_json::Map::new());
+          }
+          if let Some(other) = attrs.other.as_object_mut() {
+            other.insert(column.clone(), serde_json::json!({ "value": value }));
+          }
+        }
+      }
+    }
+  }
+
+  let num_unmatched = metadata.rows.len().saturating_sub(num_matched);
+  if num_unmatched > 0 {
+    warn!("Sequence metadata: {num_unmatched} row(s) do not match any of the sequences placed onto the tree by name. They are ignored.");
+  }
+
+  let meta = &mut graph.data.meta;
+  for column in &metadata.columns {
+    if !meta.colorings.iter().any(|coloring| &coloring.key == column) {
+      meta.colorings.push(AuspiceColoring {
+        type_: "categorical".to_owned(),
+        key: column.clone(),
+        title: column.clone(),
+        scale: vec![],
+        other: serde_json::Value::default(),
+      });
+    }
+    if !meta.filters.contains(column) {
+      meta.filters.push(column.clone());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn row(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+    entries.iter().map(|&(key, value)| (o!(key), o!(value))).collect()
+  }
+
+  #[rstest]
+  fn selects_metadata_columns() -> Result<(), Report> {
+    let rows = vec![
+      row(&[("seqName", "seq1"), ("country", "Kenya"), ("host", "human")]),
+      row(&[("seqName", "seq2"), ("country", "Chile"), ("host", "bat")]),
+    ];
+
+    let metadata = SampleMetadata::from_rows(rows.clone(), &[o!("host")])?;
+    assert_eq!(metadata.columns, vec![o!("host")]);
+    assert_eq!(metadata.rows["seq2"], row(&[("host", "bat")]));
+
+    let metadata = SampleMetadata::from_rows(rows, &[])?;
+    assert_eq!(metadata.columns, vec![o!("country"), o!("host")]);
+    Ok(())
+  }
+
+  #[rstest]
+  fn fails_on_missing_metadata_column() {
+    let rows = vec![row(&[("seqName", "seq1"), ("country", "Kenya")])];
+    assert_eq!(
+      report_to_string(&SampleMetadata::from_rows(rows, &[o!("host")]).unwrap_err()),
+      "Column 'host' is not found"
+    );
+  }
+}
@@ -0,0 +1,421 @@
+use crate::io::csv::parse_csv_with_delimiter;
+use crate::io::fs::{has_extension, read_file_to_string};
+use crate::make_error;
+use crate::tree::tree::{
+  AuspiceDisplayDefaults, AuspiceMetaExtensions, AuspiceMetaExtensionsNextclade, AuspiceTree, AuspiceTreeMeta,
+  AuspiceTreeNode, CladeNodeAttrKeyDesc, TreeBranchAttrs, TreeNodeAttr, TreeNodeAttrs,
+};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::warn;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Columns of the node metadata with special meaning. The remaining columns are clade-like node attributes.
+const NAME_COLUMN: &str = "name";
+pub(crate) const CLADE_COLUMN: &str = "clade";
+pub(crate) const NUC_MUTATIONS_COLUMN: &str = "mutations";
+const AA_MUTATIONS_COLUMN: &str = "aaMutations";
+
+/// Checks whether the path looks like a Newick file, judging by its extension
+pub fn is_nwk_path(filepath: impl AsRef<Path>) -> bool {
+  let filepath = filepath.as_ref();
+  ["nwk", "newick", "tree", "tre"]
+    .iter()
+    .any(|ext| has_extension(filepath, ext))
+}
+
+/// Reads reference tree from a Newick file, optionally accompanied by a node metadata file
+/// (see [auspice_tree_from_nwk_str]). Metadata values are delimited by semicolons if the file path ends with ".csv",
+/// and by tabs otherwise.
+pub fn auspice_tree_from_nwk_path(
+  nwk_path: impl AsRef<Path>,
+  metadata_path: Option<impl AsRef<Path>>,
+) -> Result<AuspiceTree, Report> {
+  let nwk_path = nwk_path.as_ref();
+  let nwk = read_file_to_string(nwk_path)?;
+
+  let metadata = metadata_path
+    .map(|metadata_path| {
+      let metadata_path = metadata_path.as_ref();
+      let delimiter = if has_extension(metadata_path, "csv") {
+        b';'
+      } else {
+        b'\t'
+      };
+      read_file_to_string(metadata_path)
+        .and_then(|data| parse_csv_with_delimiter::<BTreeMap<String, String>, _>(data, delimiter))
+        .wrap_err_with(|| format!("When reading tree node metadata from file {metadata_path:#?}"))
+    })
+    .transpose()?;
+
+  auspice_tree_from_nwk_str(&nwk, metadata.as_deref())
+    .wrap_err_with(|| format!("When reading reference tree from Newick file {nwk_path:#?}"))
+}
+
+/// Converts a tree in Newick format into Auspice tree, such that it can be used as the reference tree.
+///
+/// Divergence of the nodes is the sum of the branch lengths from the root. Unnamed internal nodes receive names
+/// `NODE_<index>`. Each row of the optional node metadata is matched to a node by the `name` column. The `clade` column
+/// sets the clade of the node, `mutations` the comma-separated nucleotide mutations on the branch leading to the node
+/// (e.g. `C241T,A23403G`) and `aaMutations` the comma-separated aminoacid mutations, with CDS names (e.g. `S:D614G`).
+/// All other columns become clade-like node attributes.
+pub fn auspice_tree_from_nwk_str(
+  nwk: &str,
+  metadata: Option<&[BTreeMap<String, String>]>,
+) -> Result<AuspiceTree, Report> {
+  let nodes = parse_nwk(nwk)?;
+
+  let mut metadata_by_name = BTreeMap::<&str, &BTreeMap<String, String>>::new();
+  for row in metadata.unwrap_or_default() {
+    let Some(name) = row.get(NAME_COLUMN) else {
+      return make_error!("Tree node metadata: column '{NAME_COLUMN}' is required, but not found");
+    };
+    metadata_by_name.insert(name.as_str(), row);
+  }
+
+  let num_unmatched = metadata_by_name
+    .keys()
+    .filter(|&&name| !nodes.iter().any(|node| node.name == name))
+    .count();
+  if num_unmatched > 0 {
+    warn!(
+      "Tree node metadata: {num_unmatched} row(s) do not match any node of the Newick tree by name. They are ignored."
+    );
+  }
+
+  let node_metadata = nodes
+    .iter()
+    .map(|node| metadata_by_name.get(node.name.as_str()).copied())
+    .collect_vec();
+
+  Ok(auspice_tree_from_nwk_nodes(nodes, &node_metadata))
+}
+
+/// Same as [auspice_tree_from_nwk_str], but the rows of the node metadata correspond to the nodes of the Newick tree
+/// in preorder (depth-first, parent before children, children in the order of the Newick string), rather than being
+/// matched by name.
+pub fn auspice_tree_from_nwk_str_preorder(
+  nwk: &str,
+  metadata: &[BTreeMap<String, String>],
+) -> Result<AuspiceTree, Report> {
+  let nodes = parse_nwk(nwk)?;
+  if nodes.len() != metadata.len() {
+    return make_error!(
+      "Tree node metadata: expected {} rows, one for each node of the Newick tree, but found {}",
+      nodes.len(),
+      metadata.len()
+    );
+  }
+  let node_metadata = metadata.iter().map(Some).collect_vec();
+  Ok(auspice_tree_from_nwk_nodes(nodes, &node_metadata))
+}
+
+/// Builds Auspice tree from the parsed Newick nodes and the metadata of each of the nodes
+fn auspice_tree_from_nwk_nodes(
+  mut nodes: Vec<NwkNode>,
+  node_metadata: &[Option<&BTreeMap<String, String>>],
+) -> AuspiceTree {
+  let attr_keys: BTreeSet<&str> = node_metadata
+    .iter()
+    .flatten()
+    .flat_map(|row| row.keys().map(String::as_str))
+    .filter(|&key| ![NAME_COLUMN, CLADE_COLUMN, NUC_MUTATIONS_COLUMN, AA_MUTATIONS_COLUMN].contains(&key))
+    .collect();
+
+  // Nodes are created before their children, so the divergence of the parent is always known
+  let mut divs = vec![0.0; nodes.len()];
+  for (i, node) in nodes.iter().enumerate() {
+    if let Some(parent) = node.parent {
+      divs[i] = divs[parent] + node.branch_length.unwrap_or_default();
+    }
+  }
+
+  // Children have greater indices than their parents, so that every node is complete by the time its parent is built
+  let mut built: Vec<Option<AuspiceTreeNode>> = vec![None; nodes.len()];
+  for (i, node) in nodes.iter_mut().enumerate().rev() {
+    let children = node
+      .children
+      .iter()
+      .map(|&child| built[child].take())
+      .collect::<Option<Vec<_>>>()
+      .expect("Expected child nodes to be built before their parents");
+    let name = std::mem::take(&mut node.name);
+    built[i] = Some(auspice_tree_node(name, divs[i], node_metadata[i], children));
+  }
+  let tree = built[0].take().expect("Expected root node to be built");
+
+  let clade_node_attrs = attr_keys
+    .into_iter()
+    .map(|key| CladeNodeAttrKeyDesc {
+      name: key.to_owned(),
+      display_name: key.to_owned(),
+      description: None,
+      hide_in_web: false,
+      other: serde_json::Value::default(),
+    })
+    .collect();
+
+  AuspiceTree {
+    version: Some("v2".to_owned()),
+    meta: AuspiceTreeMeta {
+      extensions: AuspiceMetaExtensions {
+        nextclade: AuspiceMetaExtensionsNextclade {
+          clade_node_attrs,
+          placement_mask_ranges: vec![],
+          other: serde_json::Value::default(),
+        },
+        other: serde_json::Value::default(),
+      },
+      colorings: vec![],
+      panels: vec![],
+      filters: vec![],
+      display_defaults: AuspiceDisplayDefaults::default(),
+      geo_resolutions: None,
+      other: serde_json::Value::default(),
+    },
+    tree,
+    other: serde_json::Value::default(),
+  }
+}
+
+fn auspice_tree_node(
+  name: String,
+  div: f64,
+  metadata: Option<&BTreeMap<String, String>>,
+  children: Vec<AuspiceTreeNode>,
+) -> AuspiceTreeNode {
+  let get = |key: &str| {
+    metadata
+      .and_then(|metadata| metadata.get(key))
+      .map(String::as_str)
+      .unwrap_or_default()
+  };
+  let split_list = |s: &str| {
+    s.split(',')
+      .map(str::trim)
+      .filter(|item| !item.is_empty())
+      .map(str::to_owned)
+      .collect_vec()
+  };
+
+  let mut mutations = BTreeMap::<String, Vec<String>>::new();
+  let nuc_mutations = split_list(get(NUC_MUTATIONS_COLUMN));
+  if !nuc_mutations.is_empty() {
+    mutations.insert("nuc".to_owned(), nuc_mutations);
+  }
+  for aa_mutation in split_list(get(AA_MUTATIONS_COLUMN)) {
+    if let Some((cds, mutation)) = aa_mutation.split_once(':') {
+      mutations.entry(cds.to_owned()).or_default().push(mutation.to_owned());
+    }
+  }
+
+  let other = metadata
+    .map(|metadata| {
+      metadata
+        .iter()
+        .filter(|(key, value)| {
+          ![NAME_COLUMN, CLADE_COLUMN, NUC_MUTATIONS_COLUMN, AA_MUTATIONS_COLUMN].contains(&key.as_str())
+            && !value.is_empty()
+        })
+        .map(|(key, value)| (key.clone(), serde_json::json!({ "value": value })))
+        .collect::<serde_json::Map<_, _>>()
+    })
+    .map_or_else(serde_json::Value::default, serde_json::Value::Object);
+
+  AuspiceTreeNode {
+    name,
+    branch_attrs: TreeBranchAttrs {
+      mutations,
+      labels: None,
+      other: serde_json::Value::default(),
+    },
+    node_attrs: TreeNodeAttrs {
+      div: Some(div),
+      clade_membership: TreeNodeAttr::new(get(CLADE_COLUMN)),
+      node_type: None,
+      region: None,
+      country: None,
+      division: None,
+      placement_prior: None,
+      alignment: None,
+      missing: None,
+      gaps: None,
+      non_acgtns: None,
+      has_pcr_primer_changes: None,
+      pcr_primer_changes: None,
+      qc_status: None,
+      missing_genes: None,
+      other,
+    },
+    children,
+    other: serde_json::Value::default(),
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+struct NwkNode {
+  name: String,
+  branch_length: Option<f64>,
+  parent: Option<usize>,
+  children: Vec<usize>,
+}
+
+/// Parses Newick string into a list of nodes, in which the root comes first and every node comes before its children.
+/// Unnamed internal nodes receive names `NODE_<index>`. Quoted names (`'...'`, with `''` standing for a single quote) and comments (`[...]`) are supported.
+///
+/// See: https://en.wikipedia.org/wiki/Newick_format
+fn parse_nwk(nwk: &str) -> Result<Vec<NwkNode>, Report> {
+  let mut nodes = vec![NwkNode::default()];
+  let mut current = 0_usize;
+  let mut chars = nwk.trim().chars().peekable();
+
+  let new_child = |nodes: &mut Vec<NwkNode>, parent: usize| {
+    let child = nodes.len();
+    nodes.push(NwkNode {
+      parent: Some(parent),
+      ..NwkNode::default()
+    });
+    nodes[parent].children.push(child);
+    child
+  };
+
+  while let Some(c) = chars.next() {
+    match c {
+      '(' => current = new_child(&mut nodes, current),
+      ',' => {
+        let Some(parent) = nodes[current].parent else {
+          return make_error!("Unexpected ',' at the top level of the Newick tree");
+        };
+        current = new_child(&mut nodes, parent);
+      }
+      ')' => {
+        let Some(parent) = nodes[current].parent else {
+          return make_error!("Unbalanced ')' in the Newick tree");
+        };
+        current = parent;
+      }
+      ':' => {
+        let length: String = std::iter::from_fn(|| chars.next_if(|c| !"(),:;[".contains(*c))).collect();
+        let length = length.trim();
+        nodes[current].branch_length = Some(
+          length
+            .parse::<f64>()
+            .wrap_err_with(|| format!("Unable to parse branch length: '{length}'"))?,
+        );
+      }
+      ';' => break,
+      '[' => {
+        if !chars.by_ref().any(|c| c == ']') {
+          return make_error!("Unterminated comment in the Newick tree");
+        }
+      }
+      '\'' => {
+        let mut name = String::new();
+        loop {
+          match chars.next() {
+            Some('\'') if chars.next_if_eq(&'\'').is_some() => name.push('\''),
+            Some('\'') => break,
+            Some(c) => name.push(c),
+            None => return make_error!("Unterminated quoted name in the Newick tree"),
+          }
+        }
+        nodes[current].name = name;
+      }
+      c if c.is_whitespace() => {}
+      c => {
+        let rest: String = std::iter::from_fn(|| chars.next_if(|c| !"(),:;[".contains(*c))).collect();
+        // Underscores in unquoted names stand for spaces according to the Newick standard, but in practice they are
+        // part of the names (e.g. in sequence names), so they are kept
+        nodes[current].name = format!("{c}{rest}").trim().to_owned();
+      }
+    }
+  }
+
+  if current != 0 {
+    return make_error!("Unbalanced '(' in the Newick tree");
+  }
+
+  let mut internal_index = 0_usize;
+  for node in &mut nodes {
+    if node.name.is_empty() && !node.children.is_empty() {
+      internal_index += 1;
+      node.name = format!("NODE_{internal_index:07}");
+    }
+  }
+
+  Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn converts_nwk_with_metadata_to_auspice_tree() -> Result<(), Report> {
+    let nwk = "((A:0.1,'B c':0.2)AB:0.3,D:0.4);";
+    let metadata = vec![
+      BTreeMap::from([
+        (o!("name"), o!("AB")),
+        (o!("clade"), o!("20A")),
+        (o!("mutations"), o!("A23403G")),
+        (o!("aaMutations"), o!("S:D614G")),
+        (o!("lineage"), o!("B.1")),
+      ]),
+      BTreeMap::from([
+        (o!("name"), o!("A")),
+        (o!("clade"), o!("20A")),
+        (o!("mutations"), o!("C241T, C3037T")),
+        (o!("aaMutations"), o!("")),
+        (o!("lineage"), o!("B.1.1")),
+      ]),
+    ];
+
+    let tree = auspice_tree_from_nwk_str(nwk, Some(metadata.as_slice()))?;
+
+    let root = &tree.tree;
+    assert_eq!(root.name, "NODE_0000001");
+    assert_eq!(
+      root.children.iter().map(|child| child.name.as_str()).collect_vec(),
+      vec!["AB", "D"]
+    );
+
+    let ab = &root.children[0];
+    assert_eq!(ab.node_attrs.clade_membership.value, "20A");
+    assert_eq!(ab.branch_attrs.mutations["nuc"], vec![o!("A23403G")]);
+    assert_eq!(ab.branch_attrs.mutations["S"], vec![o!("D614G")]);
+    assert_eq!(ab.node_attrs.other["lineage"]["value"], "B.1");
+
+    let a = &ab.children[0];
+    assert_eq!(a.branch_attrs.mutations["nuc"], vec![o!("C241T"), o!("C3037T")]);
+    assert!((a.node_attrs.div.unwrap_or_default() - 0.4).abs() < 1e-9);
+    assert_eq!(ab.children[1].name, "B c");
+
+    assert_eq!(
+      tree
+        .meta
+        .clade_node_attr_descs()
+        .iter()
+        .map(|desc| desc.name.as_str())
+        .collect_vec(),
+      vec!["lineage"]
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  #[case::unbalanced_open("((A,B);", "Unbalanced '(' in the Newick tree")]
+  #[case::unbalanced_close("(A,B));", "Unbalanced ')' in the Newick tree")]
+  #[case::bad_length("(A:x,B);", "Unable to parse branch length: 'x': invalid float literal")]
+  #[case::unterminated_quote("('A,B);", "Unterminated quoted name in the Newick tree")]
+  fn rejects_invalid_nwk(#[case] nwk: &str, #[case] message: &str) {
+    assert_eq!(
+      report_to_string(&auspice_tree_from_nwk_str(nwk, None).unwrap_err()),
+      message
+    );
+  }
+}
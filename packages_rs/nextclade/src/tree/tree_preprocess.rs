@@ -6,9 +6,10 @@ use crate::analyze::find_private_nuc_mutations::BranchMutations;
 use crate::analyze::nuc_del::NucDel;
 use crate::analyze::nuc_sub::NucSub;
 use crate::coord::position::{AaRefPosition, NucRefGlobalPosition, PositionLike};
-use crate::graph::node::GraphNodeKey;
+use crate::graph::node::Node;
 use crate::make_error;
 use crate::translate::translate_genes::Translation;
+use crate::tree::params::TreeBuilderParams;
 use crate::tree::tree::{
   AuspiceColoring, AuspiceGraph, AuspiceGraphNodePayload, AuspiceTreeMeta, AUSPICE_UNKNOWN_VALUE,
 };
@@ -18,67 +19,69 @@ use itertools::Itertools;
 use maplit::btreemap;
 use std::collections::BTreeMap;
 use std::str::FromStr;
-
+use traversal::DftPre;
+
+/// Computes mutations of every node of the reference tree, relative to the reference sequence, and caches them in the
+/// node's temporary data, for use in placement and in mutation calling.
+///
+/// Nodes are visited in preorder, such that the mutations of the parent are already cached by the time a node is
+/// visited, and the node's mutations are computed from the parent's mutations and from the mutations on the branch
+/// leading to the node. No recursion is involved, so trees of any depth can be processed.
 pub fn graph_preprocess_in_place(
   graph: &mut AuspiceGraph,
   ref_seq: &[Nuc],
   ref_translation: &Translation,
 ) -> Result<(), Report> {
-  let mut parent_nuc_muts = BTreeMap::<NucRefGlobalPosition, Nuc>::new();
-  let mut parent_aa_muts = BTreeMap::<String, BTreeMap<AaRefPosition, Aa>>::new();
-
-  let root_key = graph.get_exactly_one_root()?.key();
-  graph_preprocess_in_place_recursive(
-    graph,
-    root_key,
-    &mut parent_nuc_muts,
-    &mut parent_aa_muts,
-    ref_seq,
-    ref_translation,
-  )?;
-
-  Ok(())
-}
-
-pub fn graph_preprocess_in_place_recursive(
-  graph: &mut AuspiceGraph,
-  graph_node_key: GraphNodeKey,
-  parent_nuc_muts: &mut BTreeMap<NucRefGlobalPosition, Nuc>,
-  parent_aa_muts: &mut BTreeMap<String, BTreeMap<AaRefPosition, Aa>>,
-  ref_seq: &[Nuc],
-  ref_translation: &Translation,
-) -> Result<GraphNodeKey, Report> {
-  let (mut nuc_muts, mut aa_muts) = {
-    let node = graph.get_node_mut(graph_node_key)?.payload_mut();
-
-    let nuc_muts: BTreeMap<NucRefGlobalPosition, Nuc> = map_nuc_muts(node, ref_seq, parent_nuc_muts)
-      .wrap_err_with(|| format!("When retrieving nuc mutations from reference tree node {}", node.name))?;
-    let nuc_subs: BTreeMap<NucRefGlobalPosition, Nuc> =
-      nuc_muts.clone().into_iter().filter(|(_, nuc)| !nuc.is_gap()).collect();
-
-    let aa_muts: BTreeMap<String, BTreeMap<AaRefPosition, Aa>> = map_aa_muts(node, ref_translation, parent_aa_muts)
-      .wrap_err_with(|| format!("When retrieving aa mutations from reference tree node {}", node.name))?;
+  let node_keys = DftPre::new(graph.get_exactly_one_root()?, |node| graph.iter_children_of(node))
+    .map(|(_, node)| node.key())
+    .collect_vec();
+
+  let no_nuc_muts = BTreeMap::<NucRefGlobalPosition, Nuc>::new();
+  let no_aa_muts = BTreeMap::<String, BTreeMap<AaRefPosition, Aa>>::new();
+
+  for node_key in node_keys {
+    let (nuc_muts, aa_muts) = {
+      let node = graph.get_node(node_key)?.payload();
+      let parent = graph.parent_of_by_key(node_key).map(Node::payload);
+      let parent_nuc_muts = parent.map_or(&no_nuc_muts, |parent| &parent.tmp.mutations);
+      let parent_aa_muts = parent.map_or(&no_aa_muts, |parent| &parent.tmp.aa_mutations);
+
+      let nuc_muts: BTreeMap<NucRefGlobalPosition, Nuc> = map_nuc_muts(node, ref_seq, parent_nuc_muts)
+        .wrap_err_with(|| format!("When retrieving nuc mutations from reference tree node {}", node.name))?;
+
+      let aa_muts: BTreeMap<String, BTreeMap<AaRefPosition, Aa>> =
+        map_aa_muts(node, ref_translation, parent_aa_muts)
+          .wrap_err_with(|| format!("When retrieving aa mutations from reference tree node {}", node.name))?;
+
+      (nuc_muts, aa_muts)
+    };
+
+    let nuc_subs: BTreeMap<NucRefGlobalPosition, Nuc> = nuc_muts
+      .iter()
+      .filter(|(_, nuc)| !nuc.is_gap())
+      .map(|(&pos, &nuc)| (pos, nuc))
+      .collect();
     let aa_subs: BTreeMap<String, BTreeMap<AaRefPosition, Aa>> = aa_muts
-      .clone()
-      .into_iter()
-      .map(|(gene, aa_muts)| (gene, aa_muts.into_iter().filter(|(_, aa)| !aa.is_gap()).collect()))
+      .iter()
+      .map(|(gene, aa_muts)| {
+        let aa_subs = aa_muts
+          .iter()
+          .filter(|(_, aa)| !aa.is_gap())
+          .map(|(&pos, &aa)| (pos, aa))
+          .collect();
+        (gene.clone(), aa_subs)
+      })
       .collect();
 
-    node.tmp.mutations = nuc_muts.clone();
+    let node = graph.get_node_mut(node_key)?.payload_mut();
+    node.tmp.mutations = nuc_muts;
     node.tmp.private_mutations = calc_node_private_mutations(node)?;
     node.tmp.substitutions = nuc_subs;
-    node.tmp.aa_mutations = aa_muts.clone();
+    node.tmp.aa_mutations = aa_muts;
     node.tmp.aa_substitutions = aa_subs;
-    // node.node_attrs.node_type = Some(TreeNodeAttr::new("Reference"));
-
-    (nuc_muts, aa_muts)
-  };
-
-  for child_key in graph.iter_child_keys_of_by_key(graph_node_key).collect_vec() {
-    graph_preprocess_in_place_recursive(graph, child_key, &mut nuc_muts, &mut aa_muts, ref_seq, ref_translation)?;
   }
 
-  Ok(graph_node_key)
+  Ok(())
 }
 
 pub fn calc_node_private_mutations(node: &AuspiceGraphNodePayload) -> Result<BranchMutations, Report> {
@@ -303,3 +306,31 @@ pub fn add_auspice_metadata_in_place(meta: &mut AuspiceTreeMeta) {
 
   meta.geo_resolutions = None;
 }
+
+/// Overrides the display defaults, panels and filters of the output tree with the values configured by the user
+pub fn apply_auspice_display_params_in_place(meta: &mut AuspiceTreeMeta, params: &TreeBuilderParams) {
+  let display_defaults = &mut meta.display_defaults;
+  if let Some(color_by) = &params.auspice_color_by {
+    display_defaults.color_by = Some(color_by.clone());
+  }
+  if let Some(branch_label) = &params.auspice_branch_label {
+    display_defaults.branch_label = Some(branch_label.clone());
+  }
+  if let Some(distance_measure) = &params.auspice_distance_measure {
+    display_defaults.distance_measure = Some(distance_measure.clone());
+  }
+  if let Some(layout) = &params.auspice_layout {
+    if !display_defaults.other.is_object() {
+      display_defaults.other = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let Some(other) = display_defaults.other.as_object_mut() {
+      other.insert("layout".to_owned(), serde_json::Value::String(layout.clone()));
+    }
+  }
+  if let Some(panels) = &params.auspice_panels {
+    meta.panels = panels.clone();
+  }
+  if let Some(filters) = &params.auspice_filters {
+    meta.filters = filters.clone();
+  }
+}
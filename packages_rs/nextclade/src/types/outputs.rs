@@ -1,20 +1,33 @@
+use crate::align::alignment_diagnostics::AlignmentDiagnostics;
+use crate::align::homopolymer_indels::HomopolymerIndel;
 use crate::align::insertions_strip::{AaIns, Insertion};
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::aa_changes::AaChangesGroup;
 use crate::analyze::aa_del::AaDel;
 use crate::analyze::aa_sub::AaSub;
+use crate::analyze::clade_defining_mutations::CladeDefiningMutations;
 use crate::analyze::find_aa_motifs_changes::{AaMotifsChangesMap, AaMotifsMap};
 use crate::analyze::find_private_aa_mutations::PrivateAaMutations;
 use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
+use crate::analyze::hgvs::HgvsMutations;
+use crate::analyze::impute::ImputedNuc;
 use crate::analyze::letter_ranges::{GeneAaRange, NucRange};
+use crate::analyze::lineage_caller::LineageCall;
+use crate::analyze::minor_variants::MinorVariant;
+use crate::analyze::mutation_annotations::MutationAnnotation;
+use crate::analyze::mutation_path::MutationPathBranch;
 use crate::analyze::nuc_del::NucDelRange;
 use crate::analyze::nuc_sub::NucSub;
 use crate::analyze::pcr_primer_changes::PcrPrimerChange;
+use crate::analyze::recombination::Recombinant;
+use crate::analyze::relative_mutations::RelativeNucMutations;
+use crate::analyze::structural_events::StructuralEvent;
 use crate::coord::range::{AaRefRange, NucRefGlobalRange};
 use crate::graph::node::GraphNodeKey;
 use crate::io::json::json_parse;
 use crate::qc::qc_run::QcResult;
 use crate::translate::frame_shifts_translate::FrameShift;
+use crate::tree::tree_find_nearest_node::PlacementCandidates;
 use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -66,26 +79,75 @@ pub struct NextcladeOutputs {
   pub nuc_to_aa_muts: BTreeMap<String, Vec<AaSub>>,
   pub alignment_range: NucRefGlobalRange,
   pub alignment_score: i32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub alignment_diagnostics: Option<AlignmentDiagnostics>,
   pub aa_alignment_ranges: BTreeMap<String, Vec<AaRefRange>>,
   pub aa_unsequenced_ranges: BTreeMap<String, Vec<AaRefRange>>,
   pub pcr_primer_changes: Vec<PcrPrimerChange>,
   pub total_pcr_primer_changes: usize,
   pub clade: String,
+  #[serde(default)]
+  pub clade_defining_mutations: CladeDefiningMutations,
   pub private_nuc_mutations: PrivateNucMutations,
   pub private_aa_mutations: BTreeMap<String, PrivateAaMutations>,
+  /// Nucleotide mutations relative to the node of the reference tree requested using `--mutations-relative-to`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub relative_nuc_mutations: Option<RelativeNucMutations>,
+  /// Mutations in HGVS nomenclature, requested using `--include-hgvs`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub hgvs: Option<HgvsMutations>,
   pub warnings: Vec<PeptideWarning>,
   pub missing_genes: Vec<String>,
   pub divergence: f64,
   pub coverage: f64,
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub cds_coverage: BTreeMap<String, f64>,
   pub qc: QcResult,
   pub custom_node_attributes: BTreeMap<String, String>,
   pub nearest_node_id: GraphNodeKey,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub nearest_nodes: Option<Vec<String>>,
   pub is_reverse_complement: bool,
+  /// Whether the steps which require a reference tree (placement, clade assignment, private mutations, divergence and
+  /// the QC rules based on them) were skipped, because the reference tree was not provided. The corresponding fields
+  /// are empty and should not be interpreted as absence of mutations.
+  #[serde(default)]
+  pub tree_dependent_skipped: bool,
+  /// Name of the reference sequence the query was analyzed against. Differs between queries when multiple
+  /// references are in use.
+  #[serde(default)]
+  pub ref_name: String,
   pub phenotype_values: Option<Vec<PhenotypeValue>>,
   pub aa_motifs: AaMotifsMap,
   pub aa_motifs_changes: AaMotifsChangesMap,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub structural_events: Vec<StructuralEvent>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub normalized_homopolymer_indels: Vec<HomopolymerIndel>,
+  /// Ranges of reference positions masked in the query sequence (see `maskedRanges` in pathogen JSON)
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub masked_ranges: Vec<NucRefGlobalRange>,
+  /// Ambiguous nucleotides resolved using the nearest node of the reference tree (see `--impute-ambiguous`)
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub imputed_nucs: Vec<ImputedNuc>,
+  /// Putative recombinant parents and breakpoint (see `--detect-recombinants`)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub recombinant: Option<Recombinant>,
+  /// Best candidate attachment points on the reference tree (see `--num-placement-candidates`)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub placement_candidates: Option<PlacementCandidates>,
+  /// Mutations along the path from the root of the reference tree to the nearest node (see `--include-mutation-path`)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mutation_path: Option<Vec<MutationPathBranch>>,
+  /// Lineage assigned using lineage definitions (see `--input-lineages`)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub lineage_call: Option<LineageCall>,
+  /// Aminoacid substitutions found in the knowledge base of known mutations (see `--input-mutation-annotations`)
+  #[serde(default, rename = "annotations", skip_serializing_if = "Vec::is_empty")]
+  pub mutation_annotations: Vec<MutationAnnotation>,
+  /// Sub-consensus variants from the allele frequencies of the sample (see `--input-minor-variants`)
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub minor_variants: Vec<MinorVariant>,
 }
 
 impl NextcladeOutputs {
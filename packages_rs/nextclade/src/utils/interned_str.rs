@@ -0,0 +1,203 @@
+use lazy_static::lazy_static;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError};
+
+lazy_static! {
+  /// Every string ever interned. Entries are never removed (see `InternedStr`).
+  static ref POOL: Mutex<HashSet<Arc<str>>> = Mutex::new(HashSet::new());
+}
+
+/// Immutable string which is stored only once per process: all instances with the same content share the same
+/// allocation, and cloning is just a reference count increment.
+///
+/// Currently only used for the CDS names of aminoacid mutations (`AaSub`, `AaDel`, `AaChangeWithContext`), such that
+/// millions of mutations refer to a handful of strings rather than own millions of copies of them. The other fields of
+/// the results are unchanged. Serialized as a plain string.
+///
+/// NOTE: the pool is global and is never freed: every distinct string stays in memory until the process exits, even
+/// after all of its instances are dropped. Only use it for a small, bounded set of strings, such as the names from the
+/// genome annotation, and never for per-sequence data, such as sequence names.
+#[derive(Clone)]
+pub struct InternedStr(Arc<str>);
+
+impl InternedStr {
+  pub fn new(s: &str) -> Self {
+    let mut pool = POOL.lock().unwrap_or_else(PoisonError::into_inner);
+    let interned = if let Some(interned) = pool.get(s) {
+      Arc::clone(interned)
+    } else {
+      let interned: Arc<str> = Arc::from(s);
+      pool.insert(Arc::clone(&interned));
+      interned
+    };
+    drop(pool);
+    Self(interned)
+  }
+
+  #[inline]
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl Default for InternedStr {
+  fn default() -> Self {
+    Self::new("")
+  }
+}
+
+impl Deref for InternedStr {
+  type Target = str;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    self.as_str()
+  }
+}
+
+impl AsRef<str> for InternedStr {
+  #[inline]
+  fn as_ref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl Borrow<str> for InternedStr {
+  #[inline]
+  fn borrow(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl From<&str> for InternedStr {
+  fn from(s: &str) -> Self {
+    Self::new(s)
+  }
+}
+
+impl From<&String> for InternedStr {
+  fn from(s: &String) -> Self {
+    Self::new(s)
+  }
+}
+
+impl From<String> for InternedStr {
+  fn from(s: String) -> Self {
+    Self::new(&s)
+  }
+}
+
+impl PartialEq for InternedStr {
+  #[inline]
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0) || self.as_str() == other.as_str()
+  }
+}
+
+impl Eq for InternedStr {}
+
+impl PartialEq<str> for InternedStr {
+  #[inline]
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl PartialEq<&str> for InternedStr {
+  #[inline]
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl PartialEq<String> for InternedStr {
+  #[inline]
+  fn eq(&self, other: &String) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl PartialOrd for InternedStr {
+  #[inline]
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for InternedStr {
+  #[inline]
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+impl Hash for InternedStr {
+  #[inline]
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_str().hash(state);
+  }
+}
+
+impl Display for InternedStr {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    Display::fmt(self.as_str(), f)
+  }
+}
+
+impl Debug for InternedStr {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    Debug::fmt(self.as_str(), f)
+  }
+}
+
+impl Serialize for InternedStr {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for InternedStr {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Ok(Self::new(&s))
+  }
+}
+
+impl JsonSchema for InternedStr {
+  fn is_referenceable() -> bool {
+    false
+  }
+
+  fn schema_name() -> String {
+    String::schema_name()
+  }
+
+  fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+    String::json_schema(gen)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn shares_storage_between_equal_strings() {
+    let a = InternedStr::new("ORF1a");
+    let b = InternedStr::from(String::from("ORF1a"));
+    assert!(Arc::ptr_eq(&a.0, &b.0));
+    assert_eq!(a, b);
+    assert_eq!(a, "ORF1a");
+  }
+}
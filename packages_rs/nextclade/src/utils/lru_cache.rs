@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+const NIL: usize = usize::MAX;
+
+struct Node<K, V> {
+  key: K,
+  value: V,
+  prev: usize,
+  next: usize,
+}
+
+/// Map of at most `capacity` entries, which evicts the least recently used entry when full. Lookups, insertions and
+/// evictions take constant time.
+///
+/// Entries are stored in a vector and linked into a list, in the order of use (the most recently used first), by their
+/// indices in the vector. The slot of an evicted entry is reused for the new one.
+pub struct LruCache<K, V> {
+  indices: HashMap<K, usize>,
+  nodes: Vec<Node<K, V>>,
+  head: usize,
+  tail: usize,
+  capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      indices: HashMap::with_capacity(capacity),
+      nodes: Vec::with_capacity(capacity),
+      head: NIL,
+      tail: NIL,
+      capacity,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Returns the value for the key, if any, and marks it as the most recently used
+  pub fn get(&mut self, key: &K) -> Option<&mut V> {
+    let index = *self.indices.get(key)?;
+    self.detach(index);
+    self.push_front(index);
+    Some(&mut self.nodes[index].value)
+  }
+
+  /// Inserts or replaces the value for the key and marks it as the most recently used. If the cache is full, the least
+  /// recently used entry is evicted.
+  pub fn insert(&mut self, key: K, value: V) {
+    if self.capacity == 0 {
+      return;
+    }
+
+    if let Some(&index) = self.indices.get(&key) {
+      self.nodes[index].value = value;
+      self.detach(index);
+      self.push_front(index);
+      return;
+    }
+
+    let node = Node {
+      key: key.clone(),
+      value,
+      prev: NIL,
+      next: NIL,
+    };
+
+    let index = if self.nodes.len() < self.capacity {
+      self.nodes.push(node);
+      self.nodes.len() - 1
+    } else {
+      let index = self.tail;
+      self.detach(index);
+      let evicted = std::mem::replace(&mut self.nodes[index], node);
+      self.indices.remove(&evicted.key);
+      index
+    };
+
+    self.indices.insert(key, index);
+    self.push_front(index);
+  }
+
+  fn detach(&mut self, index: usize) {
+    let Node { prev, next, .. } = self.nodes[index];
+    if prev == NIL {
+      self.head = next;
+    } else {
+      self.nodes[prev].next = next;
+    }
+    if next == NIL {
+      self.tail = prev;
+    } else {
+      self.nodes[next].prev = prev;
+    }
+  }
+
+  fn push_front(&mut self, index: usize) {
+    self.nodes[index].prev = NIL;
+    self.nodes[index].next = self.head;
+    if self.head != NIL {
+      self.nodes[self.head].prev = index;
+    }
+    self.head = index;
+    if self.tail == NIL {
+      self.tail = index;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn evicts_least_recently_used() {
+    let mut cache = LruCache::new(2);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    assert_eq!(cache.get(&"a"), Some(&mut 1));
+
+    cache.insert("c", 3);
+    assert_eq!(cache.get(&"b"), None);
+    assert_eq!(cache.get(&"a"), Some(&mut 1));
+    assert_eq!(cache.get(&"c"), Some(&mut 3));
+
+    cache.insert("d", 4);
+    assert_eq!(cache.get(&"a"), None);
+    assert_eq!(cache.get(&"c"), Some(&mut 3));
+    assert_eq!(cache.get(&"d"), Some(&mut 4));
+    assert_eq!(cache.len(), 2);
+  }
+
+  #[test]
+  fn replaces_existing_value_without_eviction() {
+    let mut cache = LruCache::new(2);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    cache.insert("a", 10);
+    assert_eq!(cache.len(), 2);
+
+    cache.insert("c", 3);
+    assert_eq!(cache.get(&"a"), Some(&mut 10));
+    assert_eq!(cache.get(&"b"), None);
+  }
+
+  #[test]
+  fn keeps_nothing_with_zero_capacity() {
+    let mut cache = LruCache::new(0);
+    cache.insert("a", 1);
+    assert!(cache.is_empty());
+    assert_eq!(cache.get(&"a"), None);
+  }
+
+  #[test]
+  fn works_with_capacity_of_one() {
+    let mut cache = LruCache::new(1);
+    cache.insert("a", 1);
+    cache.insert("b", 2);
+    assert_eq!(cache.get(&"a"), None);
+    assert_eq!(cache.get(&"b"), Some(&mut 2));
+  }
+}
@@ -6,8 +6,13 @@ pub mod error;
 pub mod getenv;
 pub mod global_init;
 pub mod info;
+pub mod interned_str;
+pub mod lru_cache;
 pub mod num;
 pub mod option;
+pub mod progress;
+pub mod random;
+pub mod sampling;
 pub mod string;
 pub mod vec2d;
 pub mod wraparound;
@@ -0,0 +1,157 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+
+/// Minimum length of a sequence for which the intra-sequence progress is reported. Shorter sequences are processed
+/// quickly enough that per-sequence progress is sufficient.
+pub const PROGRESS_MIN_SEQ_LEN: usize = 100_000;
+
+/// Step, in percent, at which the progress of filling the alignment matrix is reported
+const ALIGNMENT_PROGRESS_STEP_PERCENT: usize = 10;
+
+/// Progress of processing of a single sequence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+  /// Part of the alignment band which has been filled so far
+  AlignmentMatrixFilled {
+    percent: usize,
+    cells_filled: usize,
+    cells_total: usize,
+  },
+
+  /// Translation and alignment of a CDS is complete (successfully or not)
+  CdsTranslated {
+    cds_name: String,
+    n_done: usize,
+    n_total: usize,
+  },
+}
+
+impl Display for ProgressEvent {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ProgressEvent::AlignmentMatrixFilled {
+        percent,
+        cells_filled,
+        cells_total,
+      } => write!(
+        f,
+        "alignment matrix {percent}% filled ({cells_filled} of {cells_total} cells)"
+      ),
+      ProgressEvent::CdsTranslated {
+        cds_name,
+        n_done,
+        n_total,
+      } => write!(f, "translated CDS '{cds_name}' ({n_done} of {n_total})"),
+    }
+  }
+}
+
+pub type ProgressSink = Box<dyn Fn(&ProgressEvent)>;
+
+thread_local! {
+  static PROGRESS_SINK: RefCell<Option<ProgressSink>> = RefCell::new(None);
+}
+
+/// Runs the function with the progress sink installed for the current thread. All progress events emitted by the
+/// function (on this thread) are passed to the sink. The previous sink, if any, is restored afterwards.
+pub fn with_progress_sink<R>(sink: ProgressSink, f: impl FnOnce() -> R) -> R {
+  struct RestoreGuard(Option<ProgressSink>);
+
+  impl Drop for RestoreGuard {
+    fn drop(&mut self) {
+      let previous = self.0.take();
+      PROGRESS_SINK.with(|current| *current.borrow_mut() = previous);
+    }
+  }
+
+  let previous = PROGRESS_SINK.with(|current| current.borrow_mut().replace(sink));
+  let _guard = RestoreGuard(previous);
+  f()
+}
+
+/// Passes the progress event to the sink of the current thread, if any. The event is only constructed if there is
+/// a sink to receive it.
+///
+/// NOTE: the sink is thread-local, so the events emitted on other threads are silently dropped. Work spread across
+/// threads should be reported from the thread which installed the sink.
+pub fn report_progress(make_event: impl FnOnce() -> ProgressEvent) {
+  PROGRESS_SINK.with(|sink| {
+    if let Some(sink) = sink.borrow().as_ref() {
+      sink(&make_event());
+    }
+  });
+}
+
+/// Tracks filling of the alignment matrix and reports progress every few percent
+pub struct AlignmentProgress {
+  cells_total: usize,
+  cells_filled: usize,
+  next_percent: usize,
+  enabled: bool,
+}
+
+impl AlignmentProgress {
+  pub const fn new(seq_len: usize, cells_total: usize) -> Self {
+    Self {
+      cells_total,
+      cells_filled: 0,
+      next_percent: ALIGNMENT_PROGRESS_STEP_PERCENT,
+      enabled: seq_len >= PROGRESS_MIN_SEQ_LEN && cells_total > 0,
+    }
+  }
+
+  #[inline]
+  pub fn add(&mut self, n_cells: usize) {
+    if !self.enabled {
+      return;
+    }
+
+    self.cells_filled += n_cells;
+    let percent = self.cells_filled * 100 / self.cells_total;
+    if percent >= self.next_percent {
+      self.next_percent = (percent / ALIGNMENT_PROGRESS_STEP_PERCENT + 1) * ALIGNMENT_PROGRESS_STEP_PERCENT;
+      report_progress(|| ProgressEvent::AlignmentMatrixFilled {
+        percent,
+        cells_filled: self.cells_filled,
+        cells_total: self.cells_total,
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+  use std::rc::Rc;
+
+  #[rstest]
+  fn reports_alignment_progress_in_steps() {
+    let events = Rc::new(RefCell::new(vec![]));
+
+    with_progress_sink(
+      {
+        let events = Rc::clone(&events);
+        Box::new(move |event| events.borrow_mut().push(event.clone()))
+      },
+      || {
+        let mut progress = AlignmentProgress::new(PROGRESS_MIN_SEQ_LEN, 1000);
+        for _ in 0..100 {
+          progress.add(10);
+        }
+      },
+    );
+
+    let percents = events
+      .borrow()
+      .iter()
+      .filter_map(|event| match event {
+        ProgressEvent::AlignmentMatrixFilled { percent, .. } => Some(*percent),
+        ProgressEvent::CdsTranslated { .. } => None,
+      })
+      .collect::<Vec<_>>();
+
+    assert_eq!(percents, vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+  }
+}
@@ -0,0 +1,17 @@
+use crate::alphabet::nuc::Nuc;
+
+/// Generates a pseudo-random nucleotide sequence, using a linear congruential generator. The same initial `state`
+/// always produces the same sequence, and `state` is advanced, such that consecutive calls produce different sequences.
+///
+/// Intended for tests and benchmarks, which need long sequences with enough diversity for seed matching, without
+/// adding them to the test data. Not suitable for anything requiring good statistical properties.
+pub fn random_nuc_seq(state: &mut u64, len: usize) -> Vec<Nuc> {
+  (0..len)
+    .map(|_| {
+      *state = state
+        .wrapping_mul(6_364_136_223_846_793_005)
+        .wrapping_add(1_442_695_040_888_963_407);
+      [Nuc::A, Nuc::C, Nuc::G, Nuc::T][(*state >> 62) as usize]
+    })
+    .collect()
+}
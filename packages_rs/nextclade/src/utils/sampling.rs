@@ -0,0 +1,96 @@
+use crate::make_error;
+use eyre::Report;
+
+/// Selects a reproducible subset of records, based on record indices only: the same input and the same sampling
+/// parameters always select the same records, regardless of the number of processing jobs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordSampler {
+  /// Select all records
+  All,
+  /// Select approximately the given fraction of records, pseudo-randomly (but deterministically) spread across input
+  Fraction(f64),
+  /// Select every n-th record, starting from the first
+  EveryN(usize),
+}
+
+impl RecordSampler {
+  pub fn new(sample_fraction: Option<f64>, sample_every_n: Option<usize>) -> Result<Self, Report> {
+    match (sample_fraction, sample_every_n) {
+      (None, None) => Ok(Self::All),
+      (Some(fraction), None) => {
+        if fraction.is_nan() || fraction <= 0.0 || fraction > 1.0 {
+          return make_error!("Sample fraction is expected to be in range (0; 1], but found: {fraction}");
+        }
+        Ok(Self::Fraction(fraction))
+      }
+      (None, Some(n)) => {
+        if n == 0 {
+          return make_error!("Sampling interval is expected to be at least 1, but found: {n}");
+        }
+        Ok(Self::EveryN(n))
+      }
+      (Some(_), Some(_)) => make_error!("Sample fraction and sampling interval are mutually exclusive"),
+    }
+  }
+
+  pub fn is_selected(&self, index: usize) -> bool {
+    match self {
+      Self::All => true,
+      Self::Fraction(fraction) => unit_interval_hash(index) < *fraction,
+      Self::EveryN(n) => index % n == 0,
+    }
+  }
+}
+
+/// Maps an index to a pseudo-random number in [0; 1), using SplitMix64 finalizer. This spreads selected records evenly
+/// across the input, without depending on a random number generator state.
+fn unit_interval_hash(index: usize) -> f64 {
+  let mut x = (index as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+  x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  x ^= x >> 31;
+  (x >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::error::report_to_string;
+  use itertools::Itertools;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn samples_every_n() -> Result<(), Report> {
+    let sampler = RecordSampler::new(None, Some(3))?;
+    let selected = (0..10).filter(|&index| sampler.is_selected(index)).collect_vec();
+    assert_eq!(selected, vec![0, 3, 6, 9]);
+    Ok(())
+  }
+
+  #[rstest]
+  fn samples_fraction_deterministically() -> Result<(), Report> {
+    let sampler = RecordSampler::new(Some(0.1), None)?;
+    let selected = (0..10_000).filter(|&index| sampler.is_selected(index)).collect_vec();
+    let selected_again = (0..10_000).filter(|&index| sampler.is_selected(index)).collect_vec();
+    assert_eq!(selected, selected_again);
+    assert!((900..1100).contains(&selected.len()));
+    Ok(())
+  }
+
+  #[rstest]
+  #[case::zero_fraction(Some(0.0), None, "Sample fraction is expected to be in range (0; 1], but found: 0")]
+  #[case::fraction_above_one(Some(1.5), None, "Sample fraction is expected to be in range (0; 1], but found: 1.5")]
+  #[case::zero_interval(None, Some(0), "Sampling interval is expected to be at least 1, but found: 0")]
+  #[case::both(Some(0.5), Some(2), "Sample fraction and sampling interval are mutually exclusive")]
+  fn rejects_invalid_parameters(
+    #[case] sample_fraction: Option<f64>,
+    #[case] sample_every_n: Option<usize>,
+    #[case] message: &str,
+  ) {
+    assert_eq!(
+      report_to_string(&RecordSampler::new(sample_fraction, sample_every_n).unwrap_err()),
+      message
+    );
+  }
+}